@@ -0,0 +1,122 @@
+/*!
+# Extraction order benchmark
+
+Times extracting a synthetic DAT2 archive whose entry data is stored in a
+different order than its directory tree, in data offset order (the default)
+and in tree order (`--tree-order`):
+
+```text
+cargo bench --bench extract_order
+```
+
+The archive is read into memory whole before extraction, so the order only
+changes how reads walk that buffer and which entries each worker gets, not
+what is read from disk. Page cache state affects both orders alike and isn't
+controlled here.
+*/
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use fallout_dat3::{DatArchive, ExtractOptions, ExtractionMode, Progress};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ENTRIES: usize = 4_000;
+const ENTRY_SIZE: usize = 16 * 1024;
+const RUNS: usize = 7;
+
+/// xorshift32 noise, for a shuffled layout without a dependency
+fn noise(mut state: u32) -> impl FnMut() -> u32 {
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    }
+}
+
+/// An archive with `ENTRIES` zlib-compressed files, sorted by name in the
+/// tree but with their data stored in shuffled order
+fn synthetic_archive() -> Vec<u8> {
+    let mut next = noise(0x9E37_79B9);
+    let mut layout: Vec<usize> = (0..ENTRIES).collect();
+    for i in (1..ENTRIES).rev() {
+        layout.swap(i, next() as usize % (i + 1));
+    }
+
+    let mut data = Vec::new();
+    let mut offsets = vec![(0u32, 0u32); ENTRIES];
+    for &entry in &layout {
+        let content: Vec<u8> = (0..ENTRY_SIZE)
+            .map(|i| {
+                if i % 7 == 0 {
+                    next() as u8
+                } else {
+                    entry as u8
+                }
+            })
+            .collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(6));
+        encoder.write_all(&content).unwrap();
+        let packed = encoder.finish().unwrap();
+        offsets[entry] = (data.len() as u32, packed.len() as u32);
+        data.extend(packed);
+    }
+
+    let tree_start = data.len();
+    data.write_u32::<LittleEndian>(ENTRIES as u32).unwrap();
+    for (i, &(offset, packed_size)) in offsets.iter().enumerate() {
+        let name = format!("ART\\CRITTERS\\HM{:02}\\FRAME{i:06}.FRM", i % 100);
+        data.write_u32::<LittleEndian>(name.len() as u32).unwrap();
+        data.extend_from_slice(name.as_bytes());
+        data.push(1);
+        data.write_u32::<LittleEndian>(ENTRY_SIZE as u32).unwrap();
+        data.write_u32::<LittleEndian>(packed_size).unwrap();
+        data.write_u32::<LittleEndian>(offset).unwrap();
+    }
+    let tree_size = data.len() - tree_start;
+    data.write_u32::<LittleEndian>(tree_size as u32).unwrap();
+    let dat_size = data.len() + 4;
+    data.write_u32::<LittleEndian>(dat_size as u32).unwrap();
+    data
+}
+
+/// Median time of `RUNS` extractions of everything into `out`
+fn median_extract(archive: &DatArchive, out: &Path, tree_order: bool) -> Duration {
+    let options = ExtractOptions {
+        tree_order,
+        ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+    };
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            fs::remove_dir_all(out).ok();
+            let started = Instant::now();
+            archive
+                .extract(out, &[], &options, &Progress::default())
+                .unwrap();
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    let data = synthetic_archive();
+    println!(
+        "{ENTRIES} entries of {ENTRY_SIZE} bytes, {} byte archive",
+        data.len()
+    );
+    let archive = DatArchive::from_bytes(data).unwrap();
+    let out = std::env::temp_dir().join(format!("dat3-extract-order-{}", std::process::id()));
+
+    let offset = median_extract(&archive, &out, false);
+    let tree = median_extract(&archive, &out, true);
+    fs::remove_dir_all(&out).ok();
+
+    println!("offset order: {offset:>10.2?}");
+    println!("tree order:   {tree:>10.2?}");
+}
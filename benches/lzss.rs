@@ -0,0 +1,115 @@
+/*!
+# LZSS decompression benchmark
+
+Times decompressing a synthetic DAT1-style LZSS stream, whole and through
+the streaming `Decoder` in 64 KiB reads the way extraction does:
+
+```text
+cargo bench --bench lzss
+```
+*/
+
+use fallout_dat3::lzss;
+use std::hint::black_box;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Compressed blocks in the stream, each followed by a raw block
+const BLOCKS: usize = 2_000;
+/// Bytes of each compressed block
+const BLOCK_SIZE: usize = 16 * 1024;
+/// Bytes of each raw block, like a stretch that didn't compress
+const RAW_SIZE: usize = 2 * 1024;
+const RUNS: usize = 7;
+
+/// xorshift32: deterministic noise without a dependency
+struct Noise(u32);
+
+impl Noise {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// A stream of compressed blocks mixing literals (60%) and dictionary
+/// references, with a raw block after each
+fn synthetic_stream() -> Vec<u8> {
+    let mut noise = Noise(0x2545_F491);
+    let mut stream = Vec::new();
+    for _ in 0..BLOCKS {
+        let mut block = Vec::with_capacity(BLOCK_SIZE);
+        while block.len() + 17 <= BLOCK_SIZE {
+            let flags_at = block.len();
+            block.push(0);
+            for bit in 0..8 {
+                if noise.next() % 10 < 6 {
+                    block[flags_at] |= 1 << bit;
+                    block.push(b'a' + (noise.next() % 26) as u8);
+                } else {
+                    let reference = noise.next();
+                    block.push(reference as u8);
+                    block.push((reference >> 8) as u8);
+                }
+            }
+        }
+        stream.extend((block.len() as i16).to_be_bytes());
+        stream.extend(block);
+
+        stream.extend((-(RAW_SIZE as i16)).to_be_bytes());
+        stream.extend((0..RAW_SIZE).map(|_| noise.next() as u8));
+    }
+    stream.extend([0, 0]);
+    stream
+}
+
+/// Median time of `RUNS` runs of `run`
+fn median(mut run: impl FnMut()) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            run();
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+/// Throughput of `size` bytes in `time`
+fn mib_per_second(size: usize, time: Duration) -> f64 {
+    size as f64 / (1024.0 * 1024.0) / time.as_secs_f64()
+}
+
+fn main() {
+    let stream = synthetic_stream();
+    let size = lzss::decompress(&stream).unwrap().len();
+    println!("{} byte stream, {size} bytes decompressed", stream.len());
+
+    let whole = median(|| {
+        black_box(lzss::decompress_with_capacity(black_box(&stream), size).unwrap());
+    });
+    let streamed = median(|| {
+        let mut decoder = lzss::Decoder::new(black_box(&stream));
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut total = 0;
+        loop {
+            match decoder.read(&mut buffer).unwrap() {
+                0 => break,
+                read => total += black_box(&buffer[..read]).len(),
+            }
+        }
+        assert_eq!(total, size);
+    });
+
+    println!(
+        "decompress:     {whole:>10.2?} ({:.0} MiB/s)",
+        mib_per_second(size, whole)
+    );
+    println!(
+        "Decoder reads:  {streamed:>10.2?} ({:.0} MiB/s)",
+        mib_per_second(size, streamed)
+    );
+}
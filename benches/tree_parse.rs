@@ -0,0 +1,115 @@
+/*!
+# DAT2 directory tree parsing benchmark
+
+Times opening a synthetic DAT2 archive with a 200k-entry directory tree,
+against the deku-based entry parser the tree reader replaced:
+
+```text
+cargo bench --bench tree_parse
+```
+*/
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use deku::prelude::*;
+use fallout_dat3::dat2::Dat2Archive;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ENTRIES: usize = 200_000;
+const RUNS: usize = 7;
+
+/// The tree entry layout as the old parser read it
+#[derive(DekuRead)]
+#[deku(endian = "little")]
+struct DekuEntry {
+    #[allow(dead_code)] // Only read by deku, as the name's length
+    filename_size: u32,
+    #[deku(count = "filename_size")]
+    filename_bytes: Vec<u8>,
+    compression_type: u8,
+    real_size: u32,
+    packed_size: u32,
+    offset: u32,
+}
+
+/// An archive with `ENTRIES` empty files, as Fallout 2 names them
+fn synthetic_archive() -> Vec<u8> {
+    let mut data = vec![0u8; 4];
+    let tree_start = data.len();
+    data.write_u32::<LittleEndian>(ENTRIES as u32).unwrap();
+    for i in 0..ENTRIES {
+        let name = format!("ART\\CRITTERS\\HM{:02}\\FRAME{i:06}.FRM", i % 100);
+        data.write_u32::<LittleEndian>(name.len() as u32).unwrap();
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+        data.write_u32::<LittleEndian>(0).unwrap();
+        data.write_u32::<LittleEndian>(0).unwrap();
+        data.write_u32::<LittleEndian>(0).unwrap();
+    }
+    let tree_size = data.len() - tree_start;
+    data.write_u32::<LittleEndian>(tree_size as u32).unwrap();
+    let dat_size = data.len() + 4;
+    data.write_u32::<LittleEndian>(dat_size as u32).unwrap();
+    data
+}
+
+/// Parse the tree the way the deku-based parser did, for comparison
+fn parse_with_deku(data: &[u8]) -> Vec<(String, u32, u32, u32, u8)> {
+    let tree_size = u32::from_le_bytes(data[data.len() - 8..data.len() - 4].try_into().unwrap());
+    let tree_start = data.len() - tree_size as usize - 8;
+    let count = u32::from_le_bytes(data[tree_start..tree_start + 4].try_into().unwrap());
+    let mut rest = &data[tree_start + 4..data.len() - 8];
+    let mut files = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let ((remaining, _), entry) = DekuEntry::from_bytes((rest, 0)).unwrap();
+        let trimmed: Vec<u8> = entry
+            .filename_bytes
+            .iter()
+            .take_while(|&&b| b != 0)
+            .copied()
+            .collect();
+        let name = std::str::from_utf8(&trimmed).unwrap().to_string();
+        files.push((
+            name,
+            entry.offset,
+            entry.real_size,
+            entry.packed_size,
+            entry.compression_type,
+        ));
+        rest = remaining;
+    }
+    files
+}
+
+/// Median time of `RUNS` runs of `run`
+fn median(mut run: impl FnMut()) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            run();
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    let data = synthetic_archive();
+    println!("{ENTRIES} entries, {} byte archive", data.len());
+
+    let deku = median(|| {
+        black_box(parse_with_deku(black_box(&data)));
+    });
+    let reader = median(|| {
+        let archive = Dat2Archive::from_bytes(black_box(data.clone())).unwrap();
+        assert_eq!(archive.entries().count(), ENTRIES);
+        black_box(archive);
+    });
+    let clone = median(|| {
+        black_box(black_box(&data).clone());
+    });
+
+    println!("deku entry parser: {deku:>10.2?}");
+    println!("tree reader:       {reader:>10.2?} (including {clone:.2?} to copy the archive)");
+}
@@ -0,0 +1,115 @@
+/*!
+# Batch Commands
+
+`dat3 batch archive.dat` opens an archive once and runs commands read from
+stdin against it, one per line, which is much faster than running `dat3`
+for each step of a script on a large archive:
+
+```text
+# Comments and blank lines are skipped
+list art/critters/hm*.frm
+extract text/english/game/combat.msg ./out
+add mods/new.msg as text/english/game/new.msg
+delete art/critters/old.frm
+save
+```
+
+Arguments are separated by spaces; quote one that contains spaces with `"`
+or `'`. Changes stay in memory until a `save` line writes the archive.
+
+This module only parses the lines; the `dat3` binary runs them.
+*/
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+/// One line of a batch script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchCommand {
+    /// `list [PATTERN...]`: print the paths of matching entries, or all
+    List { patterns: Vec<String> },
+    /// `extract PATTERN DIR`: extract matching entries under `DIR`,
+    /// keeping their directories
+    Extract { pattern: String, dest: PathBuf },
+    /// `add SOURCE [as PATH]`: add a file, or a directory with its
+    /// subdirectories; `as` gives a single file its archive path
+    Add {
+        source: PathBuf,
+        dest: Option<String>,
+    },
+    /// `delete PATTERN`: delete matching entries
+    Delete { pattern: String },
+    /// `save`: write the archive
+    Save,
+}
+
+impl BatchCommand {
+    /// Parse a line of a batch script; None for a blank line or a comment
+    pub fn parse(line: &str) -> Result<Option<Self>> {
+        let args = split_args(line)?;
+        let Some((name, args)) = args.split_first() else {
+            return Ok(None);
+        };
+        let command = match (name.as_str(), args) {
+            ("list", patterns) => Self::List {
+                patterns: patterns.to_vec(),
+            },
+            ("extract", [pattern, dest]) => Self::Extract {
+                pattern: pattern.clone(),
+                dest: PathBuf::from(dest),
+            },
+            ("extract", _) => bail!("extract takes a pattern and a directory"),
+            ("add", [source]) => Self::Add {
+                source: PathBuf::from(source),
+                dest: None,
+            },
+            ("add", [source, as_, dest]) if as_ == "as" => Self::Add {
+                source: PathBuf::from(source),
+                dest: Some(dest.clone()),
+            },
+            ("add", _) => bail!("add takes a file or directory, and optionally `as PATH`"),
+            ("delete", [pattern]) => Self::Delete {
+                pattern: pattern.clone(),
+            },
+            ("delete", _) => bail!("delete takes one name or pattern"),
+            ("save", []) => Self::Save,
+            ("save", _) => bail!("save takes no arguments"),
+            (other, _) => {
+                bail!("Unknown command '{other}' (expected list, extract, add, delete or save)")
+            }
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Split `line` at spaces, keeping quoted arguments together. Everything
+/// from a `#` that starts an argument is a comment.
+fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(args);
+        };
+        if first == '#' {
+            return Ok(args);
+        }
+
+        let mut arg = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            if c == '"' || c == '\'' {
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(other) => arg.push(other),
+                        None => bail!("Unterminated {c} quote"),
+                    }
+                }
+            } else {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
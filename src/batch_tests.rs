@@ -0,0 +1,84 @@
+/*!
+Unit tests for parsing batch commands.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::batch::BatchCommand;
+    use std::path::PathBuf;
+
+    fn parse(line: &str) -> Option<BatchCommand> {
+        BatchCommand::parse(line).unwrap()
+    }
+
+    fn error(line: &str) -> String {
+        BatchCommand::parse(line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_skipped() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("   \t"), None);
+        assert_eq!(parse("# list everything"), None);
+    }
+
+    #[test]
+    fn commands_take_their_arguments() {
+        assert_eq!(parse("list"), Some(BatchCommand::List { patterns: vec![] }));
+        assert_eq!(
+            parse("list art/*.frm text/*  # two patterns"),
+            Some(BatchCommand::List {
+                patterns: vec!["art/*.frm".to_string(), "text/*".to_string()]
+            })
+        );
+        assert_eq!(
+            parse("extract text/* out"),
+            Some(BatchCommand::Extract {
+                pattern: "text/*".to_string(),
+                dest: PathBuf::from("out")
+            })
+        );
+        assert_eq!(
+            parse("add mods/new.msg as text/new.msg"),
+            Some(BatchCommand::Add {
+                source: PathBuf::from("mods/new.msg"),
+                dest: Some("text/new.msg".to_string())
+            })
+        );
+        assert_eq!(
+            parse("add mods"),
+            Some(BatchCommand::Add {
+                source: PathBuf::from("mods"),
+                dest: None
+            })
+        );
+        assert_eq!(
+            parse("delete art/old.frm"),
+            Some(BatchCommand::Delete {
+                pattern: "art/old.frm".to_string()
+            })
+        );
+        assert_eq!(parse("  save  "), Some(BatchCommand::Save));
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        assert_eq!(
+            parse(r#"add "my mods/a b.msg" as 'text/a b.msg'"#),
+            Some(BatchCommand::Add {
+                source: PathBuf::from("my mods/a b.msg"),
+                dest: Some("text/a b.msg".to_string())
+            })
+        );
+        assert!(error(r#"delete "art/a.frm"#).contains("Unterminated"));
+    }
+
+    #[test]
+    fn wrong_arguments_are_errors() {
+        assert!(error("extract text/*").contains("extract takes"));
+        assert!(error("add a to b").contains("add takes"));
+        assert!(error("delete").contains("delete takes"));
+        assert!(error("save now").contains("save takes no arguments"));
+        assert!(error("rename a b").contains("Unknown command 'rename'"));
+    }
+}
@@ -0,0 +1,260 @@
+/*!
+# Throughput Benchmarks
+
+`dat3 bench archive.dat` times decompressing every entry into memory, with
+nothing written to disk, first on one thread and then doubling up to the
+worker thread count, to show how extraction scales. With
+`--compress-level`, it also times recompressing the decompressed contents
+with zlib at each given level, on all worker threads, and reports the
+resulting size, an entry staying stored when compressing doesn't shrink it
+(as `a` does by default).
+
+Each measurement is the fastest of a number of rounds. Contents to
+recompress are decompressed once beforehand, outside the timing.
+Throughput is in decimal megabytes of decompressed content per second.
+*/
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::common::{CompressionLevel, DatArchive, FileEntry};
+use crate::dat2::Dat2Archive;
+use crate::usage::group_thousands;
+
+/// Decompressing the whole archive on a number of threads
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecompressionRun {
+    pub threads: usize,
+    /// Decompressed bytes per round
+    pub bytes: u64,
+    /// Fastest round
+    pub elapsed: Duration,
+}
+
+/// Recompressing the archive's contents at one zlib level
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionRun {
+    pub level: u8,
+    /// Decompressed bytes per round
+    pub bytes: u64,
+    /// Total size of the entries as they would be stored
+    pub packed: u64,
+    /// Fastest round
+    pub elapsed: Duration,
+}
+
+/// Everything `bench` measured
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub entries: usize,
+    pub size: u64,
+    pub packed: u64,
+    pub decompression: Vec<DecompressionRun>,
+    pub compression: Vec<CompressionRun>,
+}
+
+/// Decimal megabytes per second
+fn mb_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / 1e6 / elapsed.as_secs_f64().max(1e-9)
+}
+
+impl DecompressionRun {
+    pub fn mb_per_sec(&self) -> f64 {
+        mb_per_sec(self.bytes, self.elapsed)
+    }
+}
+
+impl CompressionRun {
+    pub fn mb_per_sec(&self) -> f64 {
+        mb_per_sec(self.bytes, self.elapsed)
+    }
+
+    /// Stored size as a percentage of the decompressed size
+    pub fn ratio(&self) -> f64 {
+        if self.bytes == 0 {
+            100.0
+        } else {
+            self.packed as f64 * 100.0 / self.bytes as f64
+        }
+    }
+}
+
+/// Thread counts to time: 1, 2, 4 and so on, then `max` itself
+pub fn thread_counts(max: usize) -> Vec<usize> {
+    let max = max.max(1);
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |&n| Some(n * 2))
+        .take_while(|&n| n < max)
+        .collect();
+    counts.push(max);
+    counts
+}
+
+/// Entries whose data can be decompressed
+fn readable(archive: &DatArchive) -> Vec<&FileEntry> {
+    archive
+        .entries()
+        .filter(|file| file.unsupported_compression.is_none())
+        .collect()
+}
+
+/// Fastest of `rounds` runs of `work`, and what the last one returned
+fn fastest<T>(rounds: usize, mut work: impl FnMut() -> Result<T>) -> Result<(Duration, T)> {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..rounds.max(1) {
+        let start = Instant::now();
+        let value = work()?;
+        best = best.min(start.elapsed());
+        result = Some(value);
+    }
+    Ok((best, result.expect("at least one round")))
+}
+
+/// Time decompressing every entry on a pool of `threads` threads, with the
+/// content discarded as it's produced
+pub fn time_decompression(
+    archive: &DatArchive,
+    threads: usize,
+    rounds: usize,
+) -> Result<DecompressionRun> {
+    let entries = readable(archive);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .context("Failed to start worker threads")?;
+    let (elapsed, bytes) = fastest(rounds, || {
+        pool.install(|| {
+            entries
+                .par_iter()
+                .map(|file| {
+                    archive
+                        .read_entry_to(file, &mut io::sink())
+                        .with_context(|| format!("Failed to read {}", file.path()))
+                })
+                .sum::<Result<u64>>()
+        })
+    })?;
+    Ok(DecompressionRun {
+        threads,
+        bytes,
+        elapsed,
+    })
+}
+
+/// Time compressing `contents` at `level` on the current thread pool
+pub fn time_compression(
+    contents: &[Vec<u8>],
+    level: CompressionLevel,
+    rounds: usize,
+) -> Result<CompressionRun> {
+    let bytes = contents.iter().map(|data| data.len() as u64).sum();
+    let (elapsed, packed) = fastest(rounds, || {
+        contents
+            .par_iter()
+            .map(|data| {
+                let compressed = Dat2Archive::compress_zlib(data, level.level())?;
+                Ok(compressed.len().min(data.len()) as u64)
+            })
+            .sum::<Result<u64>>()
+    })?;
+    Ok(CompressionRun {
+        level: level.level(),
+        bytes,
+        packed,
+        elapsed,
+    })
+}
+
+/// Time decompression on up to `max_threads` threads and, for each of
+/// `levels`, recompression, each the fastest of `rounds`
+pub fn bench(
+    archive: &DatArchive,
+    max_threads: usize,
+    levels: &[CompressionLevel],
+    rounds: usize,
+) -> Result<BenchReport> {
+    let decompression = thread_counts(max_threads)
+        .into_iter()
+        .map(|threads| time_decompression(archive, threads, rounds))
+        .collect::<Result<_>>()?;
+
+    let compression = if levels.is_empty() {
+        Vec::new()
+    } else {
+        let contents: Vec<Vec<u8>> = readable(archive)
+            .par_iter()
+            .map(|file| {
+                archive
+                    .read_entry(file)
+                    .with_context(|| format!("Failed to read {}", file.path()))
+            })
+            .collect::<Result<_>>()?;
+        levels
+            .iter()
+            .map(|&level| time_compression(&contents, level, rounds))
+            .collect::<Result<_>>()?
+    };
+
+    Ok(BenchReport {
+        entries: archive.len(),
+        size: archive.entries().map(|file| file.size as u64).sum(),
+        packed: archive.entries().map(|file| file.packed_size as u64).sum(),
+        decompression,
+        compression,
+    })
+}
+
+/// A table of decompression throughput by thread count, then one of
+/// recompression by level if any was timed
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} entries, {} bytes, {} packed",
+            group_thousands(self.entries as u64),
+            group_thousands(self.size),
+            group_thousands(self.packed)
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>7} {:>10} {:>10} {:>8}",
+            "Threads", "Seconds", "MB/s", "Speedup"
+        )?;
+        let single = self.decompression.first().map(DecompressionRun::mb_per_sec);
+        for run in &self.decompression {
+            writeln!(
+                f,
+                "{:>7} {:>10.3} {:>10.1} {:>7.2}x",
+                run.threads,
+                run.elapsed.as_secs_f64(),
+                run.mb_per_sec(),
+                run.mb_per_sec() / single.unwrap_or(1.0).max(f64::MIN_POSITIVE)
+            )?;
+        }
+        if self.compression.is_empty() {
+            return Ok(());
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:>7} {:>10} {:>10} {:>15} {:>6}",
+            "Level", "Seconds", "MB/s", "Packed", "Ratio"
+        )?;
+        for run in &self.compression {
+            writeln!(
+                f,
+                "{:>7} {:>10.3} {:>10.1} {:>15} {:>5.1}%",
+                run.level,
+                run.elapsed.as_secs_f64(),
+                run.mb_per_sec(),
+                group_thousands(run.packed),
+                run.ratio()
+            )?;
+        }
+        Ok(())
+    }
+}
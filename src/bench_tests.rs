@@ -0,0 +1,77 @@
+/*!
+Unit tests for throughput benchmarks.
+
+Tests cover the thread counts timed, the bytes and sizes measured (timings
+themselves vary), and the report tables.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::bench::*;
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+
+    fn archive(dat1: bool) -> DatArchive {
+        let entries = vec![
+            ("a.txt".to_string(), b"some repeated text ".repeat(200)),
+            ("art\\b.bin".to_string(), (0..=255).collect()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+    }
+
+    fn levels(levels: &[u8]) -> Vec<CompressionLevel> {
+        levels
+            .iter()
+            .map(|&level| CompressionLevel::new(level).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn thread_counts_double_up_to_the_maximum() {
+        assert_eq!(thread_counts(0), [1]);
+        assert_eq!(thread_counts(1), [1]);
+        assert_eq!(thread_counts(4), [1, 2, 4]);
+        assert_eq!(thread_counts(6), [1, 2, 4, 6]);
+    }
+
+    #[test]
+    fn decompression_reads_every_entry() {
+        for dat1 in [false, true] {
+            let archive = archive(dat1);
+            let report = bench(&archive, 3, &[], 2).unwrap();
+            let threads: Vec<usize> = report.decompression.iter().map(|r| r.threads).collect();
+            assert_eq!(threads, [1, 2, 3]);
+            assert!(report.decompression.iter().all(|r| r.bytes == report.size));
+            assert_eq!(report.size, 19 * 200 + 256);
+            assert!(report.compression.is_empty());
+        }
+    }
+
+    #[test]
+    fn recompression_sizes_follow_the_level() {
+        let archive = archive(false);
+        let report = bench(&archive, 1, &levels(&[0, 9]), 1).unwrap();
+        let (stored, best) = (&report.compression[0], &report.compression[1]);
+        assert_eq!((stored.level, best.level), (0, 9));
+        assert_eq!(stored.packed, report.size);
+        assert_eq!(stored.ratio(), 100.0);
+        // The 256 distinct bytes don't shrink and stay stored
+        assert_eq!(best.packed, report.packed);
+        assert!(best.packed < report.size);
+    }
+
+    #[test]
+    fn report_has_a_table_per_measurement() {
+        let archive = archive(false);
+        let text = bench(&archive, 2, &[], 1).unwrap().to_string();
+        assert!(text.starts_with("2 entries, 4,056 bytes, "), "{text}");
+        assert!(text.contains("Threads    Seconds       MB/s  Speedup\n"));
+        assert!(text.contains("\n      1 "));
+        assert!(text.contains("\n      2 "));
+        assert!(!text.contains("Level"));
+
+        let text = bench(&archive, 1, &levels(&[6]), 1).unwrap().to_string();
+        assert!(text.contains("  Level    Seconds       MB/s          Packed  Ratio\n"));
+        assert!(text.contains("\n      6 "));
+    }
+}
@@ -0,0 +1,270 @@
+/*!
+# Decompressed Entry Cache
+
+Opt-in on-disk cache (`--cache-dir`) of decompressed entry payloads, so that
+repeated operations on the same archive don't inflate the same entries again.
+
+## Layout
+```text
+<cache_dir>/<archive hash>/<entry key hash>.bin   raw payload
+<cache_dir>/<archive hash>/<entry key hash>.zst   zstd-recompressed payload
+```
+
+The archive hash is a SHA-256 of the whole archive file, so an edited
+archive gets a fresh namespace and can never be served stale data. An
+entry's key is its name, data offset and packed size, so entries sharing a
+name (see `dedupe`) keep separate payloads.
+Eviction is least-recently-used by file modification time, which is bumped
+on every cache hit.
+*/
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use crate::common::{utils, FileEntry};
+
+/// Hex characters of the SHA-256 used in cache paths (128 bits is plenty)
+const KEY_HEX_LEN: usize = 32;
+
+/// zstd level for recompressed cache entries: fast, still a good ratio
+const ZSTD_LEVEL: i32 = 3;
+
+/// User-facing cache settings, shared by every archive opened in a run
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub dir: PathBuf,
+    /// Size cap in bytes for the whole cache directory
+    pub max_bytes: u64,
+    /// Recompress cached payloads with zstd
+    pub zstd: bool,
+}
+
+/// Cache bound to one archive's content hash
+#[derive(Debug)]
+pub struct EntryCache {
+    config: CacheConfig,
+    archive_dir: PathBuf,
+    /// Set when something was written, so eviction only runs when needed
+    dirty: AtomicBool,
+}
+
+impl EntryCache {
+    /// Bind the cache to an archive by hashing its full contents
+    pub fn for_archive(config: &CacheConfig, archive_data: &[u8]) -> Result<Self> {
+        let archive_dir = config.dir.join(hash_hex(archive_data));
+        fs::create_dir_all(&archive_dir).with_context(|| {
+            format!(
+                "Failed to create cache directory: {}",
+                archive_dir.display()
+            )
+        })?;
+
+        Ok(Self {
+            config: config.clone(),
+            archive_dir,
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Look up an entry's decompressed payload.
+    ///
+    /// Any problem reading the cache is treated as a miss; a payload whose
+    /// length doesn't match the entry's size is discarded.
+    pub fn get(&self, entry: &FileEntry) -> Option<Vec<u8>> {
+        let (path, zstd) = self.existing_path(entry)?;
+        let stored = fs::read(&path).ok()?;
+        let data = if zstd {
+            zstd::decode_all(stored.as_slice()).ok()?
+        } else {
+            stored
+        };
+
+        if data.len() != entry.size as usize {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        // Bump the modification time so LRU eviction sees this entry as fresh
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(data)
+    }
+
+    /// Store an entry's decompressed payload.
+    /// Failures only produce a warning: the cache is an optimization.
+    pub fn put(&self, entry: &FileEntry, data: &[u8]) {
+        if let Err(e) = self.try_put(entry, data) {
+            log::warn!(
+                "failed to cache {}: {e:#}",
+                utils::canonical_entry_path(&entry.name)
+            );
+        }
+    }
+
+    fn try_put(&self, entry: &FileEntry, data: &[u8]) -> Result<()> {
+        let path = self.entry_path(entry, self.config.zstd);
+        let payload = if self.config.zstd {
+            zstd::encode_all(data, ZSTD_LEVEL).context("Failed to compress cache entry")?
+        } else {
+            data.to_vec()
+        };
+
+        // Write to a temp file and rename, so parallel readers never see a partial payload
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        let mut file = fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(&payload)?;
+        drop(file);
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Return the cached payload or decompress it and cache the result
+    pub fn get_or_insert(
+        cache: Option<&Self>,
+        entry: &FileEntry,
+        decompress: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        // Only entries backed by the hashed archive bytes can be cached;
+        // newly added entries may reuse a name with different content
+        let Some(cache) = cache.filter(|_| entry.data.is_none()) else {
+            return decompress();
+        };
+        if let Some(data) = cache.get(entry) {
+            return Ok(data);
+        }
+        let data = decompress()?;
+        cache.put(entry, &data);
+        Ok(data)
+    }
+
+    fn entry_path(&self, entry: &FileEntry, zstd: bool) -> PathBuf {
+        let extension = if zstd { "zst" } else { "bin" };
+        self.archive_dir
+            .join(format!("{}.{extension}", hash_hex(&entry_key(entry))))
+    }
+
+    /// Find a cached payload in either representation
+    fn existing_path(&self, entry: &FileEntry) -> Option<(PathBuf, bool)> {
+        [self.config.zstd, !self.config.zstd]
+            .into_iter()
+            .map(|zstd| (self.entry_path(entry, zstd), zstd))
+            .find(|(path, _)| path.is_file())
+    }
+}
+
+impl Drop for EntryCache {
+    fn drop(&mut self) {
+        if self.dirty.load(Ordering::Relaxed) {
+            if let Err(e) = evict(&self.config.dir, self.config.max_bytes) {
+                log::warn!("failed to trim cache: {e:#}");
+            }
+        }
+    }
+}
+
+/// Delete least-recently-used payloads until the cache fits in `max_bytes`.
+/// Returns the number of bytes freed.
+pub fn evict(cache_dir: &Path, max_bytes: u64) -> Result<u64> {
+    let mut payloads = Vec::new();
+    let mut total = 0u64;
+
+    for archive_dir in read_cache_dir(cache_dir)? {
+        for entry in fs::read_dir(&archive_dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                total += metadata.len();
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                payloads.push((modified, metadata.len(), entry.path()));
+            }
+        }
+    }
+
+    // Oldest first
+    payloads.sort();
+
+    let mut freed = 0;
+    for (_, size, path) in payloads {
+        if total - freed <= max_bytes {
+            break;
+        }
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        freed += size;
+    }
+
+    // Drop namespaces of archives that have nothing cached anymore
+    for archive_dir in read_cache_dir(cache_dir)? {
+        if fs::read_dir(&archive_dir)?.next().is_none() {
+            fs::remove_dir(&archive_dir)?;
+        }
+    }
+
+    Ok(freed)
+}
+
+/// Remove every cached payload. Returns the number of bytes freed.
+pub fn clear(cache_dir: &Path) -> Result<u64> {
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+    evict(cache_dir, 0)
+}
+
+/// List the per-archive subdirectories of the cache.
+///
+/// Refuses to touch directories with unexpected content, so a mistyped
+/// `--cache-dir` can't make `cache clear` delete unrelated files.
+fn read_cache_dir(cache_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let entries = fs::read_dir(cache_dir)
+        .with_context(|| format!("Failed to read cache directory: {}", cache_dir.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_key = name.len() == KEY_HEX_LEN
+            && name
+                .to_str()
+                .is_some_and(|n| n.bytes().all(|b| b.is_ascii_hexdigit()));
+        if !is_key || !entry.file_type()?.is_dir() {
+            bail!(
+                "Not a dat3 cache directory (unexpected entry {}): {}",
+                entry.path().display(),
+                cache_dir.display()
+            );
+        }
+        dirs.push(entry.path());
+    }
+
+    Ok(dirs)
+}
+
+/// Bytes identifying an entry's data within its archive
+fn entry_key(entry: &FileEntry) -> Vec<u8> {
+    let mut key = entry.name.as_bytes().to_vec();
+    key.push(0);
+    key.extend(entry.offset.to_le_bytes());
+    key.extend(entry.packed_size.to_le_bytes());
+    key
+}
+
+/// Truncated SHA-256 of `data` as lowercase hex
+fn hash_hex(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    digest
+        .iter()
+        .take(KEY_HEX_LEN / 2)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,144 @@
+/*!
+Unit tests for the decompressed entry cache.
+
+Tests cover round-trips in both payload representations, stale-data
+protection, entries sharing a name, LRU eviction and the safety check on
+foreign directories.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::*;
+    use crate::common::FileEntry;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    fn make_entry(name: &str, size: u32) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            offset: 0,
+            size,
+            packed_size: size,
+            compressed: true,
+            data: None,
+            original_index: None,
+            unsupported_compression: None,
+            stored_type: None,
+            directory_index: None,
+            nul_terminated_name: false,
+        }
+    }
+
+    fn config(dir: &std::path::Path, zstd: bool) -> CacheConfig {
+        CacheConfig {
+            dir: dir.to_path_buf(),
+            max_bytes: 1 << 20,
+            zstd,
+        }
+    }
+
+    #[test]
+    fn round_trips_raw_payload() {
+        let dir = TempDir::new("raw");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        let entry = make_entry("text\\english\\game\\combat.msg", 5);
+        assert!(cache.get(&entry).is_none());
+        cache.put(&entry, b"hello");
+        assert_eq!(cache.get(&entry).unwrap(), b"hello");
+        drop(cache);
+    }
+
+    #[test]
+    fn round_trips_zstd_payload() {
+        let dir = TempDir::new("zstd");
+        let cache = EntryCache::for_archive(&config(&dir, true), b"archive").unwrap();
+        let entry = make_entry("a.txt", 1000);
+        let data = vec![b'x'; 1000];
+        cache.put(&entry, &data);
+        assert_eq!(cache.get(&entry).unwrap(), data);
+        drop(cache);
+    }
+
+    #[test]
+    fn different_archive_content_misses() {
+        let dir = TempDir::new("stale");
+        let entry = make_entry("a.txt", 3);
+        let original = EntryCache::for_archive(&config(&dir, false), b"v1").unwrap();
+        original.put(&entry, b"old");
+        let edited = EntryCache::for_archive(&config(&dir, false), b"v2").unwrap();
+        assert!(edited.get(&entry).is_none());
+        drop((original, edited));
+    }
+
+    #[test]
+    fn size_mismatch_is_a_miss() {
+        let dir = TempDir::new("size");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        cache.put(&make_entry("a.txt", 3), b"abc");
+        assert!(cache.get(&make_entry("a.txt", 4)).is_none());
+        drop(cache);
+    }
+
+    #[test]
+    fn entries_sharing_a_name_are_cached_apart() {
+        let dir = TempDir::new("same_name");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        let first = make_entry("a.txt", 3);
+        let second = FileEntry {
+            offset: 3,
+            ..make_entry("a.txt", 3)
+        };
+        cache.put(&first, b"one");
+        assert!(cache.get(&second).is_none());
+        cache.put(&second, b"two");
+        assert_eq!(cache.get(&first).unwrap(), b"one");
+        assert_eq!(cache.get(&second).unwrap(), b"two");
+        drop(cache);
+    }
+
+    #[test]
+    fn added_entries_bypass_cache() {
+        let dir = TempDir::new("added");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        let mut entry = make_entry("a.txt", 3);
+        cache.put(&entry, b"old");
+        entry.data = Some(b"new".to_vec());
+        let data = EntryCache::get_or_insert(Some(&cache), &entry, || Ok(b"new".to_vec()));
+        assert_eq!(data.unwrap(), b"new");
+        drop(cache);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let dir = TempDir::new("lru");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        let old = make_entry("old.txt", 100);
+        let new = make_entry("new.txt", 100);
+        cache.put(&old, &[0; 100]);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.put(&new, &[0; 100]);
+
+        assert_eq!(evict(&dir, 150).unwrap(), 100);
+        assert!(cache.get(&old).is_none());
+        assert!(cache.get(&new).is_some());
+        drop(cache);
+    }
+
+    #[test]
+    fn clear_removes_everything() {
+        let dir = TempDir::new("clear");
+        let cache = EntryCache::for_archive(&config(&dir, false), b"archive").unwrap();
+        cache.put(&make_entry("a.txt", 3), b"abc");
+        drop(cache);
+        assert_eq!(clear(&dir).unwrap(), 3);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn clear_refuses_foreign_directory() {
+        let dir = TempDir::new("foreign");
+        fs::write(dir.join("important.txt"), b"keep me").unwrap();
+        assert!(clear(&dir).is_err());
+        assert!(dir.join("important.txt").exists());
+    }
+}
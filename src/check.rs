@@ -0,0 +1,382 @@
+/*!
+# Archive Health Checks
+
+`dat3 check archive.dat` runs every validation dat3 knows in one pass and
+reports what it finds, each finding with a severity:
+
+- **footer**: a DAT2 footer recording the wrong archive size, or bytes after it
+- **sort_order**: entries (DAT1: directories, and files within them) out of
+  the order the game's lookups expect
+- **duplicates**: names with more than one entry
+- **case_collisions**: names that differ only by case
+- **bounds**: entry data past the end of the archive or outside the data region
+- **overlap**: entries whose data ranges overlap; entries sharing the same
+  range (`--dedup`) are only noted
+- **compression**: unknown DAT2 compression types
+- **names**: control characters, or characters Windows can't have in file names
+- **unreferenced**: data region bytes no entry points into
+- **decompression**: entries that fail to read (only with `deep`)
+
+Names that aren't ASCII, and so not representable the way the game reads
+them, already fail to open the archive.
+*/
+
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+use crate::common::{utils, DatArchive, FileEntry};
+use crate::dedupe::find_duplicates;
+
+/// How serious a finding is. Ordered, so `--severity` can pick a threshold.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Worth knowing, but harmless
+    Info,
+    /// Works, but may surprise the game or other tools
+    #[default]
+    Warning,
+    /// Entries the game or dat3 can't read correctly
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Which check a finding comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    Footer,
+    SortOrder,
+    Duplicates,
+    CaseCollisions,
+    Bounds,
+    Overlap,
+    Compression,
+    Names,
+    Unreferenced,
+    Decompression,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Footer => "footer",
+            Self::SortOrder => "sort_order",
+            Self::Duplicates => "duplicates",
+            Self::CaseCollisions => "case_collisions",
+            Self::Bounds => "bounds",
+            Self::Overlap => "overlap",
+            Self::Compression => "compression",
+            Self::Names => "names",
+            Self::Unreferenced => "unreferenced",
+            Self::Decompression => "decompression",
+        })
+    }
+}
+
+/// One problem found in an archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub check: Check,
+    pub severity: Severity,
+    /// The entry it's about, with forward slashes; None for the archive
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+/// Everything `check` found in an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    /// "dat1" or "dat2"
+    pub format: &'static str,
+    pub entries: usize,
+    /// Whether entries were decompressed (`deep`)
+    pub deep: bool,
+    /// In the order the checks run, each check's findings in archive order
+    pub findings: Vec<Finding>,
+}
+
+impl CheckReport {
+    /// Number of findings at `severity` or above
+    pub fn count_at_least(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity >= severity)
+            .count()
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    fn push(&mut self, check: Check, severity: Severity, entry: Option<String>, message: String) {
+        self.findings.push(Finding {
+            check,
+            severity,
+            entry,
+            message,
+        });
+    }
+}
+
+/// A line per finding, then a summary
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            write!(f, "{:<7} [{}] ", finding.severity, finding.check)?;
+            if let Some(entry) = &finding.entry {
+                write!(f, "{entry}: ")?;
+            }
+            writeln!(f, "{}", finding.message)?;
+        }
+        let noun = if self.entries == 1 {
+            "entry"
+        } else {
+            "entries"
+        };
+        let deep = if self.deep { ", decompressed" } else { "" };
+        if self.findings.is_empty() {
+            writeln!(f, "{} {noun}{deep}: no problems found", self.entries)
+        } else {
+            writeln!(
+                f,
+                "{} {noun}{deep}: {} errors, {} warnings, {} info",
+                self.entries,
+                self.count(Severity::Error),
+                self.count(Severity::Warning),
+                self.count(Severity::Info)
+            )
+        }
+    }
+}
+
+/// Run every check on `archive`; `deep` also decompresses every entry
+pub fn check(archive: &DatArchive, deep: bool) -> CheckReport {
+    let mut report = CheckReport {
+        format: if archive.is_dat1() { "dat1" } else { "dat2" },
+        entries: archive.len(),
+        deep,
+        findings: Vec::new(),
+    };
+    check_footer(archive, &mut report);
+    check_sort_order(archive, &mut report);
+    check_duplicates(archive, &mut report);
+    check_bounds(archive, &mut report);
+    check_overlap(archive, &mut report);
+    check_compression(archive, &mut report);
+    check_names(archive, &mut report);
+    check_unreferenced(archive, &mut report);
+    if deep {
+        check_decompression(archive, &mut report);
+    }
+    report
+}
+
+fn check_footer(archive: &DatArchive, report: &mut CheckReport) {
+    let DatArchive::Dat2(dat2) = archive else {
+        return;
+    };
+    if let Some(mismatch) = dat2.footer_size_mismatch() {
+        report.push(Check::Footer, Severity::Error, None, mismatch.to_string());
+    }
+    if let Some(trailing) = dat2.trailing_bytes() {
+        report.push(
+            Check::Footer,
+            Severity::Error,
+            None,
+            format!("{trailing} bytes follow the footer"),
+        );
+    }
+}
+
+fn check_sort_order(archive: &DatArchive, report: &mut CheckReport) {
+    match archive {
+        DatArchive::Dat1(dat1) => {
+            // The root directory comes first, the others are sorted
+            let names: Vec<&str> = dat1.directories().skip(1).map(|(name, _)| name).collect();
+            report_unsorted(report, "directory", &names);
+            for (_, files) in dat1.directories() {
+                let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+                report_unsorted(report, "entry", &names);
+            }
+        }
+        DatArchive::Dat2(dat2) => {
+            let names: Vec<&str> = dat2.entries().map(|file| file.name.as_str()).collect();
+            report_unsorted(report, "entry", &names);
+        }
+    }
+}
+
+/// A finding for each name that sorts before the one listed ahead of it
+fn report_unsorted(report: &mut CheckReport, what: &str, names: &[&str]) {
+    for pair in names.windows(2) {
+        if utils::compare_entry_paths(pair[0], pair[1]).is_gt() {
+            report.push(
+                Check::SortOrder,
+                Severity::Warning,
+                Some(utils::canonical_entry_path(pair[1])),
+                format!(
+                    "{what} is listed after {}, which sorts after it",
+                    utils::canonical_entry_path(pair[0])
+                ),
+            );
+        }
+    }
+}
+
+fn check_duplicates(archive: &DatArchive, report: &mut CheckReport) {
+    for duplicate in find_duplicates(archive, Default::default()) {
+        if duplicate.differs_by_case() {
+            report.push(
+                Check::CaseCollisions,
+                Severity::Warning,
+                Some(duplicate.name.clone()),
+                format!(
+                    "{} differ only by case",
+                    duplicate.spellings().join(" and ")
+                ),
+            );
+        } else {
+            report.push(
+                Check::Duplicates,
+                Severity::Error,
+                Some(duplicate.name.clone()),
+                format!("{} entries have this name", duplicate.entries.len()),
+            );
+        }
+    }
+}
+
+fn check_bounds(archive: &DatArchive, report: &mut CheckReport) {
+    use crate::common::EntryProblemKind;
+    for problem in archive.entry_problems() {
+        let severity = match problem.kind {
+            EntryProblemKind::EmptyWithData { .. } => Severity::Warning,
+            _ => Severity::Error,
+        };
+        let message = problem.to_string();
+        let message = message
+            .strip_prefix(&format!("{}: ", problem.name))
+            .unwrap_or(&message)
+            .to_string();
+        report.push(Check::Bounds, severity, Some(problem.name), message);
+    }
+}
+
+fn check_overlap(archive: &DatArchive, report: &mut CheckReport) {
+    let mut ranges: Vec<(u64, u64, &FileEntry)> = archive
+        .entries()
+        .filter(|file| file.data.is_none() && file.packed_size > 0)
+        .map(|file| (file.offset, file.offset + file.packed_size as u64, file))
+        .collect();
+    ranges.sort_by_key(|&(start, end, _)| (start, end));
+
+    // The entry reaching furthest so far, which later ones may overlap
+    let mut furthest: Option<(u64, u64, &FileEntry)> = None;
+    for &(start, end, file) in &ranges {
+        if let Some((other_start, other_end, other)) = furthest {
+            if (start, end) == (other_start, other_end) {
+                report.push(
+                    Check::Overlap,
+                    Severity::Info,
+                    Some(file.path()),
+                    format!("shares its data with {}", other.path()),
+                );
+            } else if start < other_end {
+                report.push(
+                    Check::Overlap,
+                    Severity::Error,
+                    Some(file.path()),
+                    format!(
+                        "data at {start}..{end} overlaps {} at {other_start}..{other_end}",
+                        other.path()
+                    ),
+                );
+            }
+        }
+        if furthest.is_none_or(|(_, other_end, _)| end > other_end) {
+            furthest = Some((start, end, file));
+        }
+    }
+}
+
+fn check_compression(archive: &DatArchive, report: &mut CheckReport) {
+    for file in archive.entries() {
+        if let Some(kind) = file.unsupported_compression {
+            report.push(
+                Check::Compression,
+                Severity::Error,
+                Some(file.path()),
+                format!("unsupported compression type {kind}"),
+            );
+        }
+    }
+}
+
+/// Characters Windows doesn't allow in file names, besides separators
+const WINDOWS_RESERVED: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+fn check_names(archive: &DatArchive, report: &mut CheckReport) {
+    for file in archive.entries() {
+        let message = if file.name.chars().any(|c| c.is_ascii_control()) {
+            "name contains control characters"
+        } else if file.name.contains(WINDOWS_RESERVED) {
+            "name contains characters Windows doesn't allow in file names"
+        } else {
+            continue;
+        };
+        report.push(
+            Check::Names,
+            Severity::Warning,
+            Some(file.path().escape_debug().to_string()),
+            message.to_string(),
+        );
+    }
+}
+
+fn check_unreferenced(archive: &DatArchive, report: &mut CheckReport) {
+    let space = archive.space_report();
+    let unreferenced = space.unreferenced();
+    if unreferenced > 0 {
+        let gaps = space.gaps.len();
+        let noun = if gaps == 1 { "gap" } else { "gaps" };
+        report.push(
+            Check::Unreferenced,
+            Severity::Info,
+            None,
+            format!("{unreferenced} bytes of data no entry uses, in {gaps} {noun}"),
+        );
+    }
+}
+
+fn check_decompression(archive: &DatArchive, report: &mut CheckReport) {
+    let entries: Vec<&FileEntry> = archive
+        .entries()
+        .filter(|file| file.unsupported_compression.is_none())
+        .collect();
+    let failures: Vec<(String, String)> = entries
+        .par_iter()
+        .filter_map(|file| {
+            let error = archive.read_entry(file).err()?;
+            Some((file.path(), error.to_string()))
+        })
+        .collect();
+    for (entry, error) in failures {
+        report.push(Check::Decompression, Severity::Error, Some(entry), error);
+    }
+}
@@ -0,0 +1,214 @@
+/*!
+Unit tests for archive health checks.
+
+Tests cover clean archives, each check on a hand-built DAT2 with one
+problem, the severity threshold, and the text report.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::check::*;
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive, OpenOptions};
+
+    /// One DAT2 tree entry: name, compressed flag, real size, packed size, offset
+    type Entry<'a> = (&'a str, bool, u32, u32, u32);
+
+    /// Bytes of a DAT2 archive with `data` as its data region and `entries`
+    /// as its tree, taken as given
+    fn dat2(data: &[u8], entries: &[Entry]) -> Vec<u8> {
+        let mut tree = (entries.len() as u32).to_le_bytes().to_vec();
+        for &(name, compressed, real_size, packed_size, offset) in entries {
+            tree.extend((name.len() as u32).to_le_bytes());
+            tree.extend(name.as_bytes());
+            tree.push(compressed as u8);
+            tree.extend(real_size.to_le_bytes());
+            tree.extend(packed_size.to_le_bytes());
+            tree.extend(offset.to_le_bytes());
+        }
+        let mut bytes = data.to_vec();
+        bytes.extend(&tree);
+        bytes.extend((tree.len() as u32).to_le_bytes());
+        let total = bytes.len() as u32 + 4;
+        bytes.extend(total.to_le_bytes());
+        bytes
+    }
+
+    fn open(bytes: Vec<u8>) -> DatArchive {
+        let options = OpenOptions {
+            lenient: true,
+            ..OpenOptions::default()
+        };
+        DatArchive::from_bytes_with(bytes, &options).unwrap()
+    }
+
+    /// Findings of `check` as (check, severity) pairs
+    fn findings(archive: &DatArchive, deep: bool) -> Vec<(Check, Severity)> {
+        check(archive, deep)
+            .findings
+            .iter()
+            .map(|finding| (finding.check, finding.severity))
+            .collect()
+    }
+
+    const DATA: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn built_archives_have_no_findings() {
+        for dat1 in [false, true] {
+            let entries = vec![
+                ("b.txt".to_string(), b"second entry".to_vec()),
+                ("text\\a.txt".to_string(), b"first entry".repeat(20)),
+            ];
+            let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+            let (archive, _) = DatArchive::from_entries(dat1, entries, &policy).unwrap();
+            let report = check(&archive, true);
+            assert!(report.findings.is_empty(), "{report}");
+            assert_eq!(report.entries, 2);
+        }
+    }
+
+    #[test]
+    fn unsorted_entries_are_warnings() {
+        let archive = open(dat2(
+            DATA,
+            &[("b.txt", false, 8, 8, 0), ("a.txt", false, 8, 8, 8)],
+        ));
+        let report = check(&archive, false);
+        assert_eq!(
+            findings(&archive, false),
+            [(Check::SortOrder, Severity::Warning)]
+        );
+        assert_eq!(report.findings[0].entry.as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn duplicates_are_errors_and_case_collisions_warnings() {
+        let same = open(dat2(
+            DATA,
+            &[("a.txt", false, 8, 8, 0), ("a.txt", false, 8, 8, 8)],
+        ));
+        assert_eq!(
+            findings(&same, false),
+            [(Check::Duplicates, Severity::Error)]
+        );
+
+        let cased = open(dat2(
+            DATA,
+            &[("A.TXT", false, 8, 8, 0), ("a.txt", false, 8, 8, 8)],
+        ));
+        assert_eq!(
+            findings(&cased, false),
+            [(Check::CaseCollisions, Severity::Warning)]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_data_is_an_error() {
+        let archive = open(dat2(
+            DATA,
+            &[("a.txt", false, 8, 8, 0), ("b.txt", false, 8, 8, 4000)],
+        ));
+        let found = findings(&archive, false);
+        assert!(
+            found.contains(&(Check::Bounds, Severity::Error)),
+            "{found:?}"
+        );
+    }
+
+    #[test]
+    fn partial_overlap_is_an_error_and_shared_data_is_noted() {
+        let overlapping = open(dat2(
+            DATA,
+            &[("a.txt", false, 10, 10, 0), ("b.txt", false, 10, 10, 6)],
+        ));
+        assert_eq!(
+            findings(&overlapping, false),
+            [(Check::Overlap, Severity::Error)]
+        );
+
+        let shared = open(dat2(
+            DATA,
+            &[
+                ("a.txt", false, 8, 8, 0),
+                ("b.txt", false, 8, 8, 0),
+                ("c.txt", false, 8, 8, 8),
+            ],
+        ));
+        let report = check(&shared, false);
+        assert_eq!(findings(&shared, false), [(Check::Overlap, Severity::Info)]);
+        assert_eq!(report.count_at_least(Severity::Warning), 0);
+    }
+
+    #[test]
+    fn unreferenced_data_is_noted() {
+        let archive = open(dat2(DATA, &[("a.txt", false, 8, 8, 4)]));
+        assert_eq!(
+            findings(&archive, false),
+            [(Check::Unreferenced, Severity::Info)]
+        );
+        assert!(check(&archive, false).findings[0]
+            .message
+            .starts_with("8 bytes of data no entry uses, in 2 gaps"));
+    }
+
+    #[test]
+    fn reserved_characters_in_names_are_warnings() {
+        let archive = open(dat2(
+            DATA,
+            &[("a?.txt", false, 8, 8, 0), ("b.txt", false, 8, 8, 8)],
+        ));
+        assert_eq!(
+            findings(&archive, false),
+            [(Check::Names, Severity::Warning)]
+        );
+    }
+
+    #[test]
+    fn wrong_footer_size_is_an_error() {
+        let mut bytes = dat2(
+            DATA,
+            &[("a.txt", false, 8, 8, 0), ("b.txt", false, 8, 8, 8)],
+        );
+        let end = bytes.len();
+        bytes[end - 4] += 1;
+        let archive = open(bytes);
+        assert_eq!(
+            findings(&archive, false),
+            [(Check::Footer, Severity::Error)]
+        );
+    }
+
+    #[test]
+    fn deep_check_decompresses_entries() {
+        let archive = open(dat2(
+            DATA,
+            &[("a.txt", true, 100, 8, 0), ("b.txt", false, 8, 8, 8)],
+        ));
+        assert!(findings(&archive, false).is_empty());
+        assert_eq!(
+            findings(&archive, true),
+            [(Check::Decompression, Severity::Error)]
+        );
+    }
+
+    #[test]
+    fn report_lists_findings_then_a_summary() {
+        let archive = open(dat2(
+            DATA,
+            &[("b.txt", false, 8, 8, 0), ("a.txt", false, 8, 8, 0)],
+        ));
+        let report = check(&archive, false);
+        assert_eq!(report.count_at_least(Severity::Info), 3);
+        assert_eq!(report.count_at_least(Severity::Warning), 1);
+        assert_eq!(report.count_at_least(Severity::Error), 0);
+        let text = report.to_string();
+        assert!(
+            text.starts_with("warning [sort_order] a.txt: entry is listed after b.txt"),
+            "{text}"
+        );
+        assert!(
+            text.ends_with("2 entries: 0 errors, 1 warnings, 2 info\n"),
+            "{text}"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+/*!
+# Terminal Colors
+
+Listings color directory prefixes and mark compressed entries apart from
+stored ones; `Warning:` and `Error:` prefixes are yellow and red. Everything
+is written through `anstream`, which drops the escape codes unless colors are
+on, so piped output is the same plain text as without colors.
+
+`--color auto` (the default) colors only a terminal, and not when `NO_COLOR`
+is set; `always` and `never` override that.
+*/
+
+use anstyle::{AnsiColor, Style};
+use clap::ValueEnum;
+use fallout_dat3::common::utils::RowPart;
+use std::fmt;
+
+/// Style of directory prefixes in listings
+pub const DIRECTORY: Style = AnsiColor::Blue.on_default().bold();
+/// Style of the Comp column of compressed entries
+pub const COMPRESSED: Style = AnsiColor::Green.on_default();
+/// Style of the Comp column of stored entries
+pub const STORED: Style = Style::new().dimmed();
+/// Style of the `Warning:` prefix
+pub const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+/// Style of the `Error:` prefix
+pub const ERROR: Style = AnsiColor::Red.on_default().bold();
+/// Style of the level and module before info, debug and trace messages
+pub const LOG_PREFIX: Style = Style::new().dimmed();
+
+/// When to color output (`--color`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// On a terminal, unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Use this choice for all output from here on
+    pub fn apply(self) {
+        anstream::ColorChoice::write_global(match self {
+            Self::Auto => anstream::ColorChoice::Auto,
+            Self::Always => anstream::ColorChoice::Always,
+            Self::Never => anstream::ColorChoice::Never,
+        });
+    }
+}
+
+/// `text` in `style`
+pub fn paint(style: Style, text: &str) -> String {
+    format!("{style}{text}{style:#}")
+}
+
+/// Style of each part of a listing row
+pub fn paint_row_part(part: RowPart, text: &str) -> String {
+    let style = match part {
+        RowPart::Directory => DIRECTORY,
+        RowPart::Compressed => COMPRESSED,
+        RowPart::Stored => STORED,
+    };
+    paint(style, text)
+}
+
+/// Print a warning to stderr
+pub fn warning(args: fmt::Arguments) {
+    anstream::eprintln!("{}: {args}", paint(WARNING, "Warning"));
+}
+
+/// Print an error to stderr
+pub fn error(args: fmt::Arguments) {
+    anstream::eprintln!("{}: {args}", paint(ERROR, "Error"));
+}
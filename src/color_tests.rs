@@ -0,0 +1,39 @@
+/*!
+Unit tests for terminal colors.
+
+Tests cover painting listing parts and that stripping the colors gives the
+plain text back.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::color::*;
+    use fallout_dat3::common::utils::RowPart;
+
+    /// `text` with escape codes removed, as `anstream` writes it with colors off
+    fn strip(text: &str) -> String {
+        anstream::adapter::strip_str(text).to_string()
+    }
+
+    #[test]
+    fn paints_each_row_part() {
+        for (part, style) in [
+            (RowPart::Directory, DIRECTORY),
+            (RowPart::Compressed, COMPRESSED),
+            (RowPart::Stored, STORED),
+        ] {
+            let painted = paint_row_part(part, "art/");
+            assert!(
+                painted.starts_with(&style.render().to_string()),
+                "{painted:?}"
+            );
+            assert_eq!(strip(&painted), "art/");
+        }
+    }
+
+    #[test]
+    fn styles_differ() {
+        assert_ne!(COMPRESSED, STORED);
+        assert_ne!(WARNING, ERROR);
+    }
+}
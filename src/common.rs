@@ -7,24 +7,37 @@ enum so callers don't need to know which format they're working with.
 
 use anyhow::{bail, Context, Result};
 use glob::glob;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
 
+use crate::cache::CacheConfig;
 use crate::dat1::Dat1Archive;
 use crate::dat2::Dat2Archive;
+use crate::error::{DatError, DatResult};
+use crate::ignore::{IgnoreFile, IgnoreStack, IGNORE_FILE_NAME};
 
 // DAT1 format detection: big-endian header with known format IDs
 const DAT1_FORMAT_ID_1: u32 = 0x0A;
 const DAT1_FORMAT_ID_2: u32 = 0x5E;
 const DAT1_MAX_DIRECTORIES: u32 = 1000;
 
-/// Write to stdout, exiting cleanly on broken pipe (e.g., when piped to `head`)
-fn print_stdout(args: std::fmt::Arguments) {
-    if writeln!(io::stdout(), "{args}").is_err() {
-        std::process::exit(0);
-    }
-}
+/// Separator between an archive and an archive stored inside it
+pub const NESTED_ARCHIVE_SEPARATOR: &str = "::";
+
+/// How many archives deep `outer.dat::inner.dat::...` may go
+pub const MAX_NESTING_DEPTH: usize = 4;
+
+/// Inner archives bigger than this, decompressed, are spilled to a temp file
+/// by `DatArchive::open_nested` instead of being decompressed into memory
+pub const NESTED_SPILL_THRESHOLD: u64 = 64 * 1024 * 1024;
 
 // ── Core types ─────────────────────────────────────────────────────
 
@@ -51,6 +64,832 @@ impl CompressionLevel {
     }
 }
 
+/// Extensions stored uncompressed by default: formats that are already
+/// compressed, so zlib only burns time (ACM audio, MVE movies).
+pub const DEFAULT_STORE_EXTENSIONS: &[&str] = &["acm", "mve"];
+
+/// Decides how each file being added is compressed.
+///
+/// Combines the user's compression level with the list of extensions that
+/// are always stored as-is regardless of that level.
+#[derive(Debug, Clone)]
+pub struct CompressionPolicy {
+    pub level: CompressionLevel,
+    /// Lowercase extensions without the leading dot
+    pub store_extensions: Vec<String>,
+    /// Minimum space saving (percent of the original size) required to keep
+    /// the compressed form
+    pub min_gain_percent: f64,
+}
+
+impl CompressionPolicy {
+    /// Create a policy with the built-in store-only extension list
+    pub fn new(level: CompressionLevel) -> Self {
+        Self {
+            level,
+            store_extensions: DEFAULT_STORE_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            min_gain_percent: 0.0,
+        }
+    }
+
+    /// Create a policy with no store-only extensions
+    pub fn without_store_extensions(level: CompressionLevel) -> Self {
+        Self {
+            level,
+            store_extensions: Vec::new(),
+            min_gain_percent: 0.0,
+        }
+    }
+
+    /// Require compression to save at least `percent` of the original size (0-100)
+    pub fn with_min_gain(mut self, percent: f64) -> Result<Self> {
+        if !(0.0..=100.0).contains(&percent) {
+            bail!("Minimum compression gain must be 0-100%, got {}", percent);
+        }
+        self.min_gain_percent = percent;
+        Ok(self)
+    }
+
+    /// Add extensions to the store-only list ("ACM", ".acm" and "acm" are equivalent)
+    pub fn with_store_extensions<S: AsRef<str>>(mut self, extensions: &[S]) -> Self {
+        for ext in extensions {
+            let ext = ext
+                .as_ref()
+                .trim()
+                .trim_start_matches('.')
+                .to_ascii_lowercase();
+            if !ext.is_empty() && !self.store_extensions.contains(&ext) {
+                self.store_extensions.push(ext);
+            }
+        }
+        self
+    }
+
+    /// Check whether a file must be stored uncompressed because of its extension
+    pub fn is_store_only(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                self.store_extensions
+                    .iter()
+                    .any(|store| store.eq_ignore_ascii_case(ext))
+            })
+    }
+
+    /// Decide between the compressed and stored forms of a file.
+    ///
+    /// Compression must always make the file smaller; on top of that,
+    /// `packed_size <= real_size * (1 - min_gain / 100)` must hold.
+    pub fn decide(&self, real_size: usize, packed_size: usize) -> CompressionOutcome {
+        if packed_size >= real_size {
+            return CompressionOutcome::Stored;
+        }
+        let max_packed = real_size as f64 * (1.0 - self.min_gain_percent / 100.0);
+        if packed_size as f64 <= max_packed {
+            CompressionOutcome::Compressed
+        } else {
+            CompressionOutcome::StoredBelowMinGain
+        }
+    }
+}
+
+/// How a newly added file ended up being stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionOutcome {
+    /// Stored compressed
+    Compressed,
+    /// Stored uncompressed (level 0, or compression didn't save space)
+    Stored,
+    /// Stored uncompressed because its extension is on the store-only list
+    StoredByExtension,
+    /// Stored uncompressed because compression saved less than `--min-gain`
+    StoredBelowMinGain,
+}
+
+/// Case folding applied to archive paths of added files (`--case`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NameCase {
+    /// Keep names as computed from the source paths
+    #[default]
+    Preserve,
+    Upper,
+    Lower,
+}
+
+impl NameCase {
+    /// Fold `name` into this case; borrowed if nothing changed.
+    /// Names are ASCII (enforced on add), so ASCII folding is enough.
+    pub fn fold(self, name: &str) -> Cow<'_, str> {
+        let needs_change = match self {
+            Self::Preserve => false,
+            Self::Upper => name.bytes().any(|b| b.is_ascii_lowercase()),
+            Self::Lower => name.bytes().any(|b| b.is_ascii_uppercase()),
+        };
+        match self {
+            _ if !needs_change => Cow::Borrowed(name),
+            Self::Upper => Cow::Owned(name.to_ascii_uppercase()),
+            _ => Cow::Owned(name.to_ascii_lowercase()),
+        }
+    }
+
+    /// Fail if folding would give two of `names` the same name
+    pub fn check_collisions<'a>(self, names: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let mut seen: HashMap<Cow<'a, str>, &'a str> = HashMap::new();
+        for name in names {
+            if let Some(other) = seen.insert(self.fold(name), name) {
+                bail!(
+                    "Changing case would merge {} and {}",
+                    utils::canonical_entry_path(other),
+                    utils::canonical_entry_path(name)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// File names left out when adding by default: metadata that macOS
+/// (`.DS_Store`, `._*` AppleDouble files) and Windows (`Thumbs.db`,
+/// `desktop.ini`) leave next to the files a mod is made of
+pub const DEFAULT_EXCLUDES: &[&str] = &[".DS_Store", "._*", "Thumbs.db", "desktop.ini"];
+
+/// File name patterns of files never added, matched case-insensitively
+/// against the file name alone, wherever the file is found
+#[derive(Debug, Clone)]
+pub struct ExcludeList {
+    patterns: Vec<glob::Pattern>,
+    /// Also leave out what the `.dat3ignore` files of walked directories
+    /// list (see `crate::ignore`)
+    pub ignore_files: bool,
+}
+
+impl Default for ExcludeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExcludeList {
+    /// The built-in list, `DEFAULT_EXCLUDES`, and ignore files
+    pub fn new() -> Self {
+        Self {
+            ignore_files: true,
+            ..Self::empty()
+                .with_patterns(DEFAULT_EXCLUDES)
+                .expect("built-in exclude patterns are valid")
+        }
+    }
+
+    /// A list that excludes nothing, and doesn't read ignore files
+    pub fn empty() -> Self {
+        Self {
+            patterns: Vec::new(),
+            ignore_files: false,
+        }
+    }
+
+    /// Add names or glob patterns (`*.bak`) to the list
+    pub fn with_patterns<S: AsRef<str>>(mut self, patterns: &[S]) -> Result<Self> {
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            self.patterns.push(
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("Invalid exclude pattern: {pattern}"))?,
+            );
+        }
+        Ok(self)
+    }
+
+    /// Whether the file at `path` is left out, by its file name
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..glob::MatchOptions::new()
+        };
+        path.file_name().is_some_and(|name| {
+            let name = name.to_string_lossy();
+            self.patterns
+                .iter()
+                .any(|pattern| pattern.matches_with(&name, options))
+        })
+    }
+}
+
+/// Files found by `utils::collect_files_excluding`, and how many were left out
+#[derive(Debug, Default)]
+pub struct CollectedFiles {
+    pub files: Vec<PathBuf>,
+    /// Files the exclude list matched
+    pub excluded: usize,
+    /// Files and directories ignore files matched; what's in the
+    /// directories isn't counted
+    pub ignored: usize,
+}
+
+/// Everything that controls how files are added, besides the files themselves
+#[derive(Debug, Clone)]
+pub struct AddOptions {
+    pub compression: CompressionPolicy,
+    /// Directory inside the archive to add files under (`-t`)
+    pub target_dir: Option<String>,
+    /// Directory that operands were resolved against (`-C`)
+    pub source_root: Option<PathBuf>,
+    /// Include subdirectories of directory operands (`-r`)
+    pub recursive: bool,
+    /// Case folding of the resulting archive paths (`--case`)
+    pub case: NameCase,
+    /// Archive path of a single file operand, instead of one computed from
+    /// its path (`source => dest` lines of response files)
+    pub dest: Option<String>,
+    /// Files left out, even when named directly (`--exclude`)
+    pub excludes: ExcludeList,
+    /// Only add files modified after this time (`--newer-than`)
+    pub newer_than: Option<SystemTime>,
+    /// Compress identical file contents found by one `add_file` once, giving
+    /// every file that has them a copy of the same stored bytes, for a
+    /// deduplicating save to share (`--dedup`, DAT2 only)
+    pub dedup: bool,
+}
+
+impl AddOptions {
+    /// Options with the given compression and everything else at its default
+    pub fn new(compression: CompressionPolicy) -> Self {
+        Self {
+            compression,
+            target_dir: None,
+            source_root: None,
+            recursive: false,
+            case: NameCase::Preserve,
+            dest: None,
+            excludes: ExcludeList::new(),
+            newer_than: None,
+            dedup: false,
+        }
+    }
+
+    /// Archive path for `file` (found under operand `base_path`), case-folded.
+    /// The flag tells whether folding changed the name.
+    pub fn archive_path(&self, file: &Path, base_path: &Path) -> Result<(String, bool)> {
+        let path = self.source_archive_path(file, base_path)?;
+        Ok(match self.case.fold(&path) {
+            Cow::Borrowed(_) => (path, false),
+            Cow::Owned(folded) => (folded, true),
+        })
+    }
+
+    /// Archive path for `file` (found under operand `base_path`) before case
+    /// folding: `dest` if it's set, else computed from the file's path
+    pub fn source_archive_path(&self, file: &Path, base_path: &Path) -> Result<String> {
+        match self.dest.as_deref() {
+            Some(dest) if file == base_path => Ok(utils::stored_entry_path(
+                &utils::validate_add_archive_path(dest)?,
+            )),
+            Some(_) => bail!(
+                "An explicit archive path needs a single file, not the directory {}",
+                base_path.display()
+            ),
+            None => utils::calculate_archive_path(
+                file,
+                base_path,
+                self.target_dir.as_deref(),
+                self.source_root.as_deref(),
+            ),
+        }
+    }
+
+    /// Archive path for content named `name` (a relative path with either
+    /// separator) rather than found on disk: under `target_dir` if it's set,
+    /// case-folded. The flag tells whether folding changed the name.
+    pub fn content_archive_path(&self, name: &str) -> Result<(String, bool)> {
+        let path = match self.target_dir.as_deref() {
+            Some(target) => format!("{target}/{name}"),
+            None => name.to_string(),
+        };
+        let path = utils::stored_entry_path(&utils::validate_add_archive_path(&path)?);
+        Ok(match self.case.fold(&path) {
+            Cow::Borrowed(_) => (path, false),
+            Cow::Owned(folded) => (folded, true),
+        })
+    }
+
+    /// Archive paths for in-memory `(name, content)` pairs (`add_contents`),
+    /// leaving out excluded names, which are counted in `stats` along with
+    /// case-folded ones. Fails if two names give the same archive path.
+    pub fn content_archive_paths(
+        &self,
+        contents: Vec<(String, Vec<u8>)>,
+        stats: &mut AddStats,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut named = Vec::with_capacity(contents.len());
+        let mut seen = HashMap::new();
+        for (name, data) in contents {
+            if self
+                .excludes
+                .is_excluded(Path::new(&utils::canonical_entry_path(&name)))
+            {
+                stats.excluded += 1;
+                continue;
+            }
+            let (path, folded) = self.content_archive_path(&name)?;
+            if let Some(other) = seen.insert(utils::entry_path_key(&path), name.clone()) {
+                bail!(
+                    "Both {other} and {name} would be added as {}",
+                    utils::canonical_entry_path(&path)
+                );
+            }
+            stats.record_case_fold(folded);
+            named.push((path, data));
+        }
+        Ok(named)
+    }
+
+    /// Drop the files not modified after `newer_than` from `files`,
+    /// returning how many went
+    pub fn keep_newer(&self, files: &mut Vec<PathBuf>) -> Result<usize> {
+        let Some(newer_than) = self.newer_than else {
+            return Ok(0);
+        };
+        let before = files.len();
+        let mut error = None;
+        files.retain(
+            |file| match fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified > newer_than,
+                Err(e) => {
+                    error.get_or_insert_with(|| {
+                        anyhow::Error::new(e).context(format!(
+                            "Failed to read the modification time of {}",
+                            file.display()
+                        ))
+                    });
+                    true
+                }
+            },
+        );
+        match error {
+            Some(error) => Err(error),
+            None => Ok(before - files.len()),
+        }
+    }
+
+    /// Fail if `case` would give two different archive paths of `files`
+    /// (each with the operand it was found under) the same name
+    pub fn check_case_collisions<'a>(
+        &self,
+        files: impl IntoIterator<Item = (&'a Path, &'a Path)>,
+    ) -> Result<()> {
+        if self.case == NameCase::Preserve {
+            return Ok(());
+        }
+        let paths = files
+            .into_iter()
+            .map(|(file, base_path)| self.source_archive_path(file, base_path))
+            .collect::<Result<BTreeSet<_>>>()?;
+        self.case.check_collisions(paths.iter().map(String::as_str))
+    }
+}
+
+/// Everything under a directory, to be added with paths relative to it
+/// (`dat3 c` and `dat3 patch`): `src/art/foo.frm` becomes `art\foo.frm`
+#[derive(Debug)]
+pub struct DirectoryContents {
+    /// Canonical path of the directory
+    pub root: PathBuf,
+    pub options: AddOptions,
+    /// Files on disk and their stored archive paths, sorted like DAT2 entries
+    pub files: Vec<(PathBuf, String)>,
+}
+
+impl DirectoryContents {
+    /// Collect the files under `dir`, which must be a directory with at
+    /// least one file, including subdirectories, leaving out the built-in
+    /// exclude list. Fails if `case` would give two of them the same
+    /// archive path.
+    pub fn new(dir: &Path, compression: CompressionPolicy, case: NameCase) -> Result<Self> {
+        Self::with_excludes(dir, compression, case, ExcludeList::new())
+    }
+
+    /// Collect the files under `dir` like `new`, leaving out `excludes`
+    pub fn with_excludes(
+        dir: &Path,
+        compression: CompressionPolicy,
+        case: NameCase,
+        excludes: ExcludeList,
+    ) -> Result<Self> {
+        let root = fs::canonicalize(dir)
+            .with_context(|| format!("Failed to resolve directory: {}", dir.display()))?;
+        if !root.is_dir() {
+            bail!("Not a directory: {}", dir.display());
+        }
+
+        let options = AddOptions {
+            source_root: Some(root.clone()),
+            recursive: true,
+            case,
+            excludes,
+            ..AddOptions::new(compression)
+        };
+        let on_disk =
+            utils::collect_files_excluding(&root, true, &options.excludes, &Progress::default())?
+                .files;
+        options
+            .check_case_collisions(on_disk.iter().map(|file| (file.as_path(), root.as_path())))?;
+        let mut files = on_disk
+            .into_iter()
+            .map(|file| {
+                let (path, _) = options.archive_path(&file, &root)?;
+                Ok((file, path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if files.is_empty() {
+            bail!("No files to add under {}", dir.display());
+        }
+        files.sort_by(|(_, a), (_, b)| utils::compare_entry_paths(a, b));
+
+        Ok(Self {
+            root,
+            options,
+            files,
+        })
+    }
+
+    /// Build a new archive of the files
+    pub fn build(&self, dat1: bool, progress: &Progress) -> Result<(DatArchive, AddStats)> {
+        let mut archive = if dat1 {
+            DatArchive::new_dat1()
+        } else {
+            DatArchive::new_dat2()
+        };
+        let stats = archive.add_file(&self.root, &self.options, progress)?;
+        Ok((archive, stats))
+    }
+}
+
+/// Counters collected while adding files, printed as a summary at the end
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AddStats {
+    pub files_added: usize,
+    pub compressed: usize,
+    pub stored_by_extension: usize,
+    pub stored_below_min_gain: usize,
+    /// Files whose archive path was changed by `--case`
+    pub case_folded: usize,
+    /// Files left out by the exclude list
+    pub excluded: usize,
+    /// Files and directories left out by ignore files
+    pub ignored: usize,
+    /// Files left out by `--newer-than` as not modified since
+    pub older: usize,
+}
+
+impl AddStats {
+    /// Count one added file
+    pub fn record(&mut self, outcome: CompressionOutcome) {
+        self.files_added += 1;
+        match outcome {
+            CompressionOutcome::Compressed => self.compressed += 1,
+            CompressionOutcome::Stored => {}
+            CompressionOutcome::StoredByExtension => self.stored_by_extension += 1,
+            CompressionOutcome::StoredBelowMinGain => self.stored_below_min_gain += 1,
+        }
+    }
+
+    /// Count an added file whose name was case-folded
+    pub fn record_case_fold(&mut self, folded: bool) {
+        if folded {
+            self.case_folded += 1;
+        }
+    }
+
+    /// Accumulate counters from another add operation
+    pub fn merge(&mut self, other: AddStats) {
+        self.files_added += other.files_added;
+        self.compressed += other.compressed;
+        self.stored_by_extension += other.stored_by_extension;
+        self.stored_below_min_gain += other.stored_below_min_gain;
+        self.case_folded += other.case_folded;
+        self.excluded += other.excluded;
+        self.ignored += other.ignored;
+        self.older += other.older;
+    }
+}
+
+/// The add summary, one line per non-zero counter
+impl fmt::Display for AddStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Added {} files ({} compressed)",
+            self.files_added, self.compressed
+        )?;
+        if self.stored_by_extension > 0 {
+            writeln!(
+                f,
+                "{} files stored due to extension rule",
+                self.stored_by_extension
+            )?;
+        }
+        if self.stored_below_min_gain > 0 {
+            writeln!(
+                f,
+                "{} files stored due to minimum gain threshold",
+                self.stored_below_min_gain
+            )?;
+        }
+        if self.case_folded > 0 {
+            writeln!(f, "{} file names changed case", self.case_folded)?;
+        }
+        if self.excluded > 0 {
+            writeln!(f, "{} files skipped by the exclude list", self.excluded)?;
+        }
+        if self.ignored > 0 {
+            writeln!(
+                f,
+                "{} files or directories skipped by {IGNORE_FILE_NAME} files",
+                self.ignored
+            )?;
+        }
+        if self.older > 0 {
+            writeln!(
+                f,
+                "{} files skipped as not modified after the given time",
+                self.older
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Entries saved without a copy of their own data, pointing at another
+/// entry's identical data instead (`--dedup`)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SharedData {
+    pub entries: usize,
+    /// Bytes not written because of the sharing
+    pub bytes: u64,
+}
+
+impl SharedData {
+    /// Count one entry that shares `len` bytes of data
+    pub fn record(&mut self, len: usize) {
+        self.entries += 1;
+        self.bytes += len as u64;
+    }
+}
+
+impl fmt::Display for SharedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} entries share identical data, saving {} bytes",
+            self.entries, self.bytes
+        )
+    }
+}
+
+/// How much of an archive's data region is actually referenced by entries.
+///
+/// Some legacy editors delete entries by dropping them from the tree only,
+/// leaving their bytes behind; `optimize` uses this to find and drop them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceReport {
+    pub archive_size: u64,
+    /// Byte range where entry data may live (between header and tree)
+    pub data_region: Range<u64>,
+    /// Parts of the data region no entry points into, in file order
+    pub gaps: Vec<Range<u64>>,
+}
+
+impl SpaceReport {
+    /// Analyze `entry_ranges` (possibly overlapping) against the data region
+    pub fn analyze(
+        archive_size: u64,
+        data_region: Range<u64>,
+        entry_ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Self {
+        let gaps = utils::unreferenced_ranges(data_region.clone(), entry_ranges);
+        Self {
+            archive_size,
+            data_region,
+            gaps,
+        }
+    }
+
+    /// Total size of all gaps
+    pub fn unreferenced(&self) -> u64 {
+        self.gaps.iter().map(|gap| gap.end - gap.start).sum()
+    }
+
+    /// Size of the data region covered by at least one entry
+    pub fn referenced(&self) -> u64 {
+        self.data_region.end - self.data_region.start - self.unreferenced()
+    }
+}
+
+/// The space analysis as printed by `optimize`
+impl fmt::Display for SpaceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unreferenced = self.unreferenced();
+        let percent = if self.archive_size == 0 {
+            0.0
+        } else {
+            unreferenced as f64 * 100.0 / self.archive_size as f64
+        };
+        writeln!(f, "Archive size: {} bytes", self.archive_size)?;
+        writeln!(f, "Referenced:   {} bytes", self.referenced())?;
+        writeln!(
+            f,
+            "Unreferenced: {unreferenced} bytes ({percent:.1}%) in {} gaps",
+            self.gaps.len()
+        )
+    }
+}
+
+/// An entry whose recorded data location can't be right.
+///
+/// Found at open time from the parsed tree alone, so extraction doesn't have
+/// to discover it halfway through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryProblem {
+    /// Entry path with forward slashes
+    pub name: String,
+    pub kind: EntryProblemKind,
+}
+
+/// What is wrong with an entry's data location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryProblemKind {
+    /// The data runs past the end of the archive
+    PastEnd { end: u64, archive_size: u64 },
+    /// The data overlaps the header, directory tree or footer
+    OutsideDataRegion {
+        data: Range<u64>,
+        data_region: Range<u64>,
+    },
+    /// The entry is empty, but has stored bytes
+    EmptyWithData { packed_size: u32 },
+}
+
+impl fmt::Display for EntryProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            EntryProblemKind::PastEnd { end, archive_size } => write!(
+                f,
+                "{}: data ends at {end}, past the end of the archive ({archive_size} bytes)",
+                self.name
+            ),
+            EntryProblemKind::OutsideDataRegion { data, data_region } => write!(
+                f,
+                "{}: data at {}..{} lies outside the data region {}..{}",
+                self.name, data.start, data.end, data_region.start, data_region.end
+            ),
+            EntryProblemKind::EmptyWithData { packed_size } => write!(
+                f,
+                "{}: empty entry with {packed_size} stored bytes",
+                self.name
+            ),
+        }
+    }
+}
+
+/// Something an archive operation reports while it runs.
+///
+/// The library never prints; callers that want console output (like the
+/// `dat3` binary) install a handler with `Progress::with_events`. Names are
+/// archive paths as stored, with backslashes.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// Extraction of `total` entries is starting
+    ExtractStarted { total: usize },
+    /// An entry is about to be extracted (DAT1, which extracts sequentially)
+    Extracting { name: &'a str },
+    /// Running count of extracted entries (DAT2, every 1000 and at the end)
+    Extracted {
+        count: usize,
+        total: usize,
+        elapsed: Duration,
+    },
+    /// Extraction is done; `written` entries were written, `skipped` were
+    /// left out by `strip_components`, and `current` weren't written because
+    /// `update` found them up to date
+    ExtractFinished {
+        elapsed: Duration,
+        written: usize,
+        skipped: usize,
+        current: usize,
+    },
+    /// An entry's extraction ended; `bytes` is its decompressed size if it
+    /// was written, otherwise 0
+    EntryExtracted {
+        name: &'a str,
+        bytes: u64,
+        outcome: ExtractOutcome,
+    },
+    /// An entry failed to extract and `keep_going` is set
+    EntryFailed { error: &'a anyhow::Error },
+    /// An entry is skipped because its path has no more than
+    /// `strip_components` components
+    SkippedShallow {
+        name: &'a str,
+        strip_components: usize,
+    },
+    /// An entry is skipped because a later one has the same name, maybe
+    /// spelled in another case, and would be written to the same file
+    SkippedDuplicateName { name: &'a str, kept: &'a str },
+    /// An extracted file's modification time couldn't be set
+    TimeNotSet {
+        path: &'a Path,
+        error: &'a std::io::Error,
+    },
+    /// Requested names or patterns that matched no entries
+    NotFound { patterns: &'a [String] },
+    /// Adding of `total` files from one path or set of contents is starting
+    AddStarted { total: usize },
+    /// A file is being added under this archive path
+    Adding { name: &'a str },
+    /// A file of `bytes` bytes was added under this archive path
+    Added { name: &'a str, bytes: u64 },
+    /// An entry is being deleted
+    Deleting { name: &'a str },
+    /// A symlink was skipped while collecting files to add
+    SkippedSymlink { path: &'a Path, dangling: bool },
+}
+
+/// How the extraction of one entry ended (`Event::EntryExtracted`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractOutcome {
+    Written,
+    /// Not written because `update` found the file up to date
+    Current,
+    Failed,
+}
+
+impl ExtractOutcome {
+    /// Bytes `Event::EntryExtracted` reports for `file`
+    pub(crate) fn bytes(self, file: &FileEntry) -> u64 {
+        match self {
+            Self::Written => file.size as u64,
+            Self::Current | Self::Failed => 0,
+        }
+    }
+}
+
+/// Handler for `Event`s; called from worker threads during parallel work
+pub type EventHandler = Box<dyn Fn(Event<'_>) + Send + Sync>;
+
+/// Counters of completed work, updated while a command runs.
+///
+/// Kept outside the commands' return values so that partial counts are still
+/// available when a command fails midway (reported by `--json`).
+#[derive(Default)]
+pub struct Progress {
+    pub listed: AtomicUsize,
+    pub extracted: AtomicUsize,
+    pub added: AtomicUsize,
+    pub deleted: AtomicUsize,
+    /// Whether the archive was written to disk
+    pub saved: AtomicBool,
+    /// Receives events as work happens; without one they're dropped
+    events: Option<EventHandler>,
+}
+
+impl Progress {
+    /// Track progress and pass every event to `handler`
+    pub fn with_events(handler: impl Fn(Event<'_>) + Send + Sync + 'static) -> Self {
+        Self {
+            events: Some(Box::new(handler)),
+            ..Self::default()
+        }
+    }
+
+    /// Count `n` more units of work
+    pub fn add(counter: &AtomicUsize, n: usize) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Report an event to the handler, if there is one
+    pub fn emit(&self, event: Event<'_>) {
+        if let Some(handler) = &self.events {
+            handler(event);
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("listed", &self.listed)
+            .field("extracted", &self.extracted)
+            .field("added", &self.added)
+            .field("deleted", &self.deleted)
+            .field("saved", &self.saved)
+            .field("events", &self.events.is_some())
+            .finish()
+    }
+}
+
 /// Represents a single file stored in a DAT archive.
 ///
 /// Used by both DAT1 and DAT2 formats. For files already in an archive,
@@ -70,10 +909,26 @@ pub struct FileEntry {
     pub compressed: bool,
     /// Raw file data for new/modified files (None for existing archive files)
     pub data: Option<Vec<u8>>,
+    /// Position in the directory tree the archive was parsed from.
+    /// Stays with the entry through sorting and deletes; None for added files.
+    pub original_index: Option<usize>,
+    /// A DAT2 compression type other than 0 (stored) or 1 (zlib), as read
+    /// from the tree. `compressed` is false; reading or extracting the entry
+    /// fails unless raw bytes are asked for (`ExtractOptions::force_raw`).
+    pub unsupported_compression: Option<u8>,
+    /// Type field as stored in the tree: DAT2's compression type byte, or
+    /// DAT1's attributes (0x20 stored, 0x40 compressed). None for added files.
+    pub stored_type: Option<u32>,
+    /// Position of the DAT1 directory the entry is listed under.
+    /// None for DAT2 and added files.
+    pub directory_index: Option<usize>,
+    /// The DAT2 name field as read ended with a NUL after the name, which
+    /// saving writes back under `OpenOptions::nul_names`
+    pub nul_terminated_name: bool,
 }
 
-/// Allows `&[FileEntry]` to work with `print_file_listing`,
-/// which accepts `&[T: AsRef<FileEntry>]` so it also works with `&[&FileEntry]`.
+/// Lets listing code accept `&[T: AsRef<FileEntry>]`, so both
+/// `&[FileEntry]` and `&[&FileEntry]` work.
 impl AsRef<FileEntry> for FileEntry {
     fn as_ref(&self) -> &FileEntry {
         self
@@ -81,6 +936,12 @@ impl AsRef<FileEntry> for FileEntry {
 }
 
 impl FileEntry {
+    /// The entry's path in canonical form, with forward slashes
+    /// (see `utils::canonical_entry_path`)
+    pub fn path(&self) -> String {
+        utils::canonical_entry_path(&self.name)
+    }
+
     /// Create a file entry with uncompressed data.
     /// The `offset` is set to 0 and will be computed when saving.
     pub fn with_data(name: String, data: Vec<u8>, compressed: bool) -> Self {
@@ -92,6 +953,11 @@ impl FileEntry {
             packed_size,
             compressed,
             data: Some(data),
+            original_index: None,
+            unsupported_compression: None,
+            stored_type: None,
+            directory_index: None,
+            nul_terminated_name: false,
         }
     }
 
@@ -109,52 +975,785 @@ impl FileEntry {
             packed_size: compressed_data.len() as u32,
             compressed: true,
             data: Some(compressed_data),
+            original_index: None,
+            unsupported_compression: None,
+            stored_type: None,
+            directory_index: None,
+            nul_terminated_name: false,
+        }
+    }
+}
+
+/// Controls how files are extracted from archives
+#[derive(Debug, Clone, Copy)]
+pub enum ExtractionMode {
+    /// Keep the original directory structure
+    PreserveStructure,
+    /// Put all files in one flat directory
+    Flat,
+}
+
+impl ExtractionMode {
+    /// Where the entry `archive_name` goes under `output_dir`
+    pub fn output_path(self, output_dir: &Path, archive_name: &str) -> PathBuf {
+        match self {
+            Self::PreserveStructure => output_dir.join(utils::to_system_path(archive_name)),
+            Self::Flat => output_dir.join(utils::get_filename_from_dat_path(archive_name)),
+        }
+    }
+}
+
+/// Which parser opens an archive (`--format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    /// Detect the format from the header, trying DAT2 if a detected DAT1
+    /// archive doesn't parse
+    #[default]
+    Auto,
+    Dat1,
+    Dat2,
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Dat1 => "DAT1",
+            Self::Dat2 => "DAT2",
+        })
+    }
+}
+
+/// Options controlling how an archive is opened
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenOptions {
+    /// Parse as this format instead of detecting it. Archives nested in the
+    /// opened one (`open_nested`) are always detected.
+    pub format: ArchiveFormat,
+    /// Open DAT2 archives whose footer records the wrong archive size,
+    /// locating the tree from the end of the file, as long as the tree
+    /// parses and every entry lies within the file
+    pub lenient: bool,
+    /// Fail when an entry's data location is invalid (see
+    /// `DatArchive::entry_problems`) instead of failing only that entry when
+    /// it's read
+    pub strict: bool,
+    /// Write back the NUL that ends some DAT2 name fields on save, so
+    /// archives from tools that store one keep their byte layout. Without
+    /// it names are saved without the NUL, like every name dat3 writes.
+    pub nul_names: bool,
+}
+
+/// Options controlling extraction
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    pub mode: ExtractionMode,
+    /// Report per-entry failures and continue instead of stopping at the first
+    pub keep_going: bool,
+    /// Don't fail when a requested pattern matches no entries
+    pub ignore_missing: bool,
+    /// Extract in directory-tree order instead of data offset order
+    pub tree_order: bool,
+    /// Write the stored bytes of entries with an unsupported compression
+    /// type instead of failing them
+    pub force_raw: bool,
+    /// Most bytes of decompressed entries held in memory at once during
+    /// parallel extraction (default: `DEFAULT_MEMORY_PER_THREAD` per worker)
+    pub max_memory: Option<u64>,
+    /// Leading directory removed from entry paths before they're placed
+    /// under the output directory (`--strip-prefix`). Only for
+    /// `PreserveStructure`, and every extracted entry must be under it.
+    pub strip_prefix: Option<String>,
+    /// Number of leading components removed from entry paths, after
+    /// `strip_prefix` (`--strip-components`). Only for `PreserveStructure`;
+    /// entries with nothing left are skipped.
+    pub strip_components: usize,
+    /// Case folding of output paths (`--lowercase`). Entries whose paths
+    /// would become the same fail the extraction before anything is written.
+    pub case: NameCase,
+    /// Leave files that already exist with the entry's size as they are
+    /// (`--update`)
+    pub update: bool,
+    /// With `update`, also require the file's content to match the entry's
+    /// (`--checksum`)
+    pub checksum: bool,
+    /// Modification time given to every written file (`--mtime`); None
+    /// leaves the time of writing
+    pub mtime: Option<SystemTime>,
+    /// Write every entry's stored bytes without decompressing them
+    /// (`--raw`): the zlib stream of a compressed DAT2 entry, the LZSS
+    /// blocks of a DAT1 one. Compressed entries get a `.z` or `.lzss`
+    /// suffix unless `raw_keep_names` is set.
+    pub raw: bool,
+    /// With `raw`, write compressed entries under their own names
+    /// (`--raw-keep-names`)
+    pub raw_keep_names: bool,
+}
+
+/// Default extraction memory budget per worker thread
+pub const DEFAULT_MEMORY_PER_THREAD: u64 = 64 << 20;
+
+/// Most entries named when some aren't under `strip_prefix`
+const MAX_LISTED_OUTSIDE_PREFIX: usize = 10;
+
+impl ExtractOptions {
+    /// Options with every flag off: stop at the first failure, fail on
+    /// unmatched patterns, extract in offset order
+    pub fn new(mode: ExtractionMode) -> Self {
+        Self {
+            mode,
+            keep_going: false,
+            ignore_missing: false,
+            tree_order: false,
+            force_raw: false,
+            max_memory: None,
+            strip_prefix: None,
+            strip_components: 0,
+            case: NameCase::Preserve,
+            update: false,
+            checksum: false,
+            mtime: None,
+            raw: false,
+            raw_keep_names: false,
+        }
+    }
+
+    /// The part of the entry path `archive_name` that's placed under the
+    /// output directory, after `strip_prefix` and `strip_components`. None
+    /// if nothing is left.
+    pub fn kept_path<'a>(&self, archive_name: &'a str) -> Option<Cow<'a, str>> {
+        let mut path = Cow::Borrowed(archive_name);
+        if let Some(prefix) = self.strip_prefix.as_deref() {
+            path = Cow::Owned(utils::strip_entry_prefix(&path, prefix)?);
+        }
+        if self.strip_components > 0 {
+            path = Cow::Owned(utils::strip_entry_components(&path, self.strip_components)?);
+        }
+        Some(path)
+    }
+
+    /// Where the entry `archive_name` goes under `output_dir`
+    pub fn output_path(&self, output_dir: &Path, archive_name: &str) -> PathBuf {
+        let kept = self.kept_path(archive_name);
+        let kept = kept.as_deref().unwrap_or(archive_name);
+        self.mode.output_path(output_dir, &self.case.fold(kept))
+    }
+
+    /// Where `file` is written under `output_dir`: its `output_path`, plus
+    /// `raw_suffix` (the archive format's, with the dot) for a compressed
+    /// entry extracted `raw`
+    pub fn entry_output_path(
+        &self,
+        output_dir: &Path,
+        file: &FileEntry,
+        raw_suffix: &str,
+    ) -> PathBuf {
+        let path = self.output_path(output_dir, &file.name);
+        if self.raw && file.compressed && !self.raw_keep_names {
+            let mut path = path.into_os_string();
+            path.push(raw_suffix);
+            return path.into();
+        }
+        path
+    }
+
+    /// Check, before anything is written, that the path stripping options
+    /// fit the mode, that `strip_prefix` applies to every entry in `files`
+    /// and that `case` doesn't give two of them the same output path
+    pub fn check_output_paths(&self, files: &[&FileEntry]) -> Result<()> {
+        let stripping = self.strip_prefix.is_some() || self.strip_components > 0;
+        if stripping && matches!(self.mode, ExtractionMode::Flat) {
+            bail!("Paths can't be stripped in flat extraction, which drops all directories");
+        }
+        if let Some(prefix) = self.strip_prefix.as_deref() {
+            Self::check_under_prefix(files, prefix)?;
+        }
+        if self.case != NameCase::Preserve {
+            // Only collisions that folding causes; the same name twice
+            // already went to one file before
+            let names: BTreeSet<String> = files
+                .iter()
+                .filter_map(|file| self.kept_path(&file.name))
+                .map(|kept| match self.mode {
+                    ExtractionMode::PreserveStructure => kept.into_owned(),
+                    ExtractionMode::Flat => utils::get_filename_from_dat_path(&kept).to_string(),
+                })
+                .collect();
+            self.case
+                .check_collisions(names.iter().map(String::as_str))?;
+        }
+        Ok(())
+    }
+
+    /// Fail if any of `files` isn't under `prefix`, naming the first few
+    fn check_under_prefix(files: &[&FileEntry], prefix: &str) -> Result<()> {
+        let outside: Vec<String> = files
+            .iter()
+            .filter(|file| utils::strip_entry_prefix(&file.name, prefix).is_none())
+            .map(|file| file.path())
+            .collect();
+        if outside.is_empty() {
+            return Ok(());
+        }
+        let shown = outside.len().min(MAX_LISTED_OUTSIDE_PREFIX);
+        let more = match outside.len() - shown {
+            0 => String::new(),
+            n => format!("\n  ... and {n} more"),
+        };
+        bail!(
+            "{} of {} entries to extract are not under {}:\n  {}{more}",
+            outside.len(),
+            files.len(),
+            utils::canonical_entry_path(prefix),
+            outside[..shown].join("\n  ")
+        )
+    }
+
+    /// Drop every entry of `files`, which are in tree order, that a later one
+    /// has the same name as, reporting each. On a case-insensitive file
+    /// system they would go to the same file, written by whichever worker
+    /// finished last; keeping the last entry makes that the same everywhere.
+    /// Names are compared by `entry_path_key`, or spelled the same when
+    /// `case` folds them, which leaves names that differ by case to fail
+    /// `check_output_paths`. Returns how many were dropped.
+    pub fn skip_duplicate_names(&self, files: &mut Vec<&FileEntry>, progress: &Progress) -> usize {
+        let key = |file: &FileEntry| match self.case {
+            NameCase::Preserve => utils::entry_path_key(&file.name),
+            _ => utils::canonical_entry_path(&file.name),
+        };
+        let mut last: HashMap<String, usize> = HashMap::new();
+        for (i, file) in files.iter().enumerate() {
+            last.insert(key(file), i);
+        }
+        let mut skipped = HashSet::new();
+        for (i, file) in files.iter().enumerate() {
+            let kept = last[&key(file)];
+            if kept != i {
+                progress.emit(Event::SkippedDuplicateName {
+                    name: &file.name,
+                    kept: &files[kept].name,
+                });
+                skipped.insert(i);
+            }
+        }
+        let mut i = 0;
+        files.retain(|_| {
+            i += 1;
+            !skipped.contains(&(i - 1))
+        });
+        skipped.len()
+    }
+
+    /// Drop the entries `strip_components` leaves nothing of from `files`,
+    /// reporting each. Returns how many were dropped.
+    pub fn skip_shallow_entries(&self, files: &mut Vec<&FileEntry>, progress: &Progress) -> usize {
+        if self.strip_components == 0 {
+            return 0;
+        }
+        let before = files.len();
+        files.retain(|file| {
+            let kept = self.kept_path(&file.name).is_some();
+            if !kept {
+                progress.emit(Event::SkippedShallow {
+                    name: &file.name,
+                    strip_components: self.strip_components,
+                });
+            }
+            kept
+        });
+        before - files.len()
+    }
+
+    /// The memory budget of parallel extraction: `max_memory`, or
+    /// `DEFAULT_MEMORY_PER_THREAD` for each thread of the current pool
+    pub fn memory_budget(&self) -> u64 {
+        self.max_memory.unwrap_or_else(|| {
+            DEFAULT_MEMORY_PER_THREAD.saturating_mul(rayon::current_num_threads() as u64)
+        })
+    }
+
+    /// Put the selected entries in the order they'll be extracted.
+    ///
+    /// By default that's ascending data offset, so reads walk through the
+    /// archive front to back and each parallel worker, which gets a contiguous
+    /// slice, stays within one stretch of it. Entries with in-memory data
+    /// (offset 0) come first. The sort is stable; `tree_order` skips it.
+    pub fn order_entries(&self, files: &mut [&FileEntry]) {
+        if !self.tree_order {
+            files.sort_by_key(|file| file.offset);
+        }
+    }
+
+    /// Whether `update` may leave the file at `output_path` alone: it exists
+    /// with the size extracting the entry gives (the stored size if `raw`).
+    /// With `checksum`, `holds_content` has to agree once the entry is read.
+    pub fn may_be_current(&self, output_path: &Path, file: &FileEntry) -> bool {
+        let size = if self.raw {
+            file.packed_size
+        } else {
+            file.size
+        };
+        self.update
+            && fs::metadata(output_path)
+                .is_ok_and(|metadata| metadata.is_file() && metadata.len() == size as u64)
+    }
+
+    /// Give the file just written at `output_path` the `mtime`, if set. A
+    /// failure is reported as `Event::TimeNotSet`; the file itself is fine.
+    pub fn set_mtime(&self, output_path: &Path, progress: &Progress) {
+        let Some(mtime) = self.mtime else {
+            return;
+        };
+        let result = fs::File::options()
+            .write(true)
+            .open(output_path)
+            .and_then(|file| file.set_modified(mtime));
+        if let Err(error) = result {
+            progress.emit(Event::TimeNotSet {
+                path: output_path,
+                error: &error,
+            });
+        }
+    }
+
+    /// Whether the file at `output_path` holds exactly `content`
+    pub fn holds_content(output_path: &Path, content: &[u8]) -> bool {
+        fs::read(output_path).is_ok_and(|existing| existing == content)
+    }
+
+    /// Pass through an entry's extraction result; with `keep_going` an error
+    /// is reported and counted in `failures` instead of being returned.
+    pub fn handle_entry_result(
+        &self,
+        result: Result<()>,
+        failures: &AtomicUsize,
+        progress: &Progress,
+    ) -> Result<()> {
+        match result {
+            Err(e) if self.keep_going => {
+                progress.emit(Event::EntryFailed { error: &e });
+                failures.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Report patterns that matched no entries, unless `ignore_missing` is set
+    pub fn check_missing(&self, missing_patterns: &[String], progress: &Progress) -> Result<()> {
+        if self.ignore_missing {
+            return Ok(());
+        }
+        report_missing_patterns(missing_patterns, progress)
+    }
+
+    /// Fail the run if any entry failed under `keep_going`
+    pub fn check_failures(failures: &AtomicUsize, total: usize) -> Result<()> {
+        let failed = failures.load(Ordering::Relaxed);
+        if failed > 0 {
+            bail!("Failed to extract {failed} of {total} files");
+        }
+        Ok(())
+    }
+}
+
+/// A counting semaphore over bytes, bounding how much decompressed entry
+/// data parallel workers hold at once.
+///
+/// A request larger than the whole budget is clamped to it, so it waits
+/// until every other holder is done and then runs alone.
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    total: u64,
+    available: Mutex<u64>,
+    released: Condvar,
+}
+
+/// Bytes held from a `MemoryBudget`, given back on drop
+#[derive(Debug)]
+pub(crate) struct MemoryGuard<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(total: u64) -> Self {
+        Self {
+            total,
+            available: Mutex::new(total),
+            released: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Block until `bytes` (at most the whole budget) are free, and hold them
+    pub(crate) fn acquire(&self, bytes: u64) -> MemoryGuard<'_> {
+        let bytes = bytes.min(self.total);
+        let mut available = self
+            .available
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while *available < bytes {
+            available = self
+                .released
+                .wait(available)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *available -= bytes;
+        MemoryGuard {
+            budget: self,
+            bytes,
+        }
+    }
+
+    /// Bytes not held by any guard
+    #[cfg(test)]
+    pub(crate) fn available(&self) -> u64 {
+        *self
+            .available
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for MemoryGuard<'_> {
+    fn drop(&mut self) {
+        let mut available = self
+            .budget
+            .available
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *available += self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+// ── DatArchive enum ────────────────────────────────────────────────
+
+/// Unified interface for both DAT1 and DAT2 archives.
+///
+/// Uses an enum instead of trait objects because there are exactly two
+/// known formats - this gives us static dispatch, exhaustive matching,
+/// and no heap allocation for the wrapper.
+///
+/// **Memory**: The entire archive is loaded into memory on open. This works
+/// well for typical Fallout archives (up to ~200MB).
+///
+/// ```no_run
+/// use fallout_dat3::DatArchive;
+///
+/// let archive = DatArchive::open("master.dat")?;  // auto-detects format
+/// let dat1 = DatArchive::new_dat1();               // create new DAT1
+/// let dat2 = DatArchive::new_dat2();               // create new DAT2
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+pub enum DatArchive {
+    /// Fallout 1 format (big-endian, hierarchical dirs, LZSS compression)
+    Dat1(Dat1Archive),
+    /// Fallout 2 format (little-endian, flat file list, zlib compression)
+    Dat2(Dat2Archive),
+}
+
+/// A temp file holding a spilled nested archive, removed when dropped
+struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    fn create() -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "dat3-nested-{}-{}.dat",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::File::create_new(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self { path })
+    }
+
+    fn file(&self) -> Result<fs::File> {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl DatArchive {
+    /// Open an existing DAT archive, auto-detecting the format
+    pub fn open<P: AsRef<Path>>(path: P) -> DatResult<Self> {
+        Self::open_with(path, &OpenOptions::default())
+    }
+
+    /// Open an existing DAT archive with the given options
+    pub fn open_with<P: AsRef<Path>>(path: P, options: &OpenOptions) -> DatResult<Self> {
+        let data = fs::read(&path).map_err(|source| DatError::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        })?;
+        let archive = Self::from_bytes_with(data, options)?;
+        log::info!(
+            "Opened {}: {}, {} entries",
+            path.as_ref().display(),
+            if archive.is_dat1() { "DAT1" } else { "DAT2" },
+            archive.len()
+        );
+        Ok(archive)
+    }
+
+    /// Parse an archive from raw bytes, auto-detecting the format
+    pub fn from_bytes(data: Vec<u8>) -> DatResult<Self> {
+        Self::from_bytes_with(data, &OpenOptions::default())
+    }
+
+    /// Parse an archive from raw bytes with the given options
+    pub fn from_bytes_with(data: Vec<u8>, options: &OpenOptions) -> DatResult<Self> {
+        let archive = match options.format {
+            ArchiveFormat::Dat1 => Self::Dat1(Dat1Archive::from_bytes(data)?),
+            ArchiveFormat::Dat2 => Self::Dat2(Dat2Archive::from_bytes_with(data, options)?),
+            ArchiveFormat::Auto if Self::is_dat1_format(&data) => {
+                match Dat1Archive::try_from_bytes(data) {
+                    Ok(archive) => Self::Dat1(archive),
+                    // The DAT1 check is a heuristic that a DAT2 file can pass
+                    // by chance; if that fails too, the DAT1 error is the one
+                    // to report
+                    Err((error, data)) => {
+                        Self::Dat2(Dat2Archive::from_bytes_with(data, options).map_err(|_| error)?)
+                    }
+                }
+            }
+            ArchiveFormat::Auto => Self::Dat2(Dat2Archive::from_bytes_with(data, options)?),
+        };
+
+        if options.strict {
+            let problems = archive.entry_problems();
+            if !problems.is_empty() {
+                return Err(DatError::InvalidEntries { problems });
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Check every entry's recorded data location against the archive's
+    /// layout. This is arithmetic over the parsed tree; no data is read.
+    pub fn entry_problems(&self) -> Vec<EntryProblem> {
+        let (archive_size, data_region) = match self {
+            Self::Dat1(a) => (a.archive_size(), a.data_region()),
+            Self::Dat2(a) => (a.archive_size(), a.data_region()),
+        };
+
+        let mut problems = Vec::new();
+        // Newly added entries hold their data in memory
+        for entry in self.entries().filter(|e| e.data.is_none()) {
+            let data = entry.offset..entry.offset + entry.packed_size as u64;
+            let kind = if data.end > archive_size {
+                EntryProblemKind::PastEnd {
+                    end: data.end,
+                    archive_size,
+                }
+            } else if !data.is_empty()
+                && (data.start < data_region.start || data.end > data_region.end)
+            {
+                EntryProblemKind::OutsideDataRegion {
+                    data,
+                    data_region: data_region.clone(),
+                }
+            } else if entry.size == 0 && entry.packed_size > 0 {
+                EntryProblemKind::EmptyWithData {
+                    packed_size: entry.packed_size,
+                }
+            } else {
+                continue;
+            };
+            problems.push(EntryProblem {
+                name: entry.path(),
+                kind,
+            });
+        }
+        problems
+    }
+
+    /// Problems found while opening that didn't stop it: a footer size a
+    /// lenient open ignored, invalid entry locations, entries that will
+    /// fail to read, and names with more than one entry
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Self::Dat2(archive) = self {
+            if let Some(mismatch) = archive.footer_size_mismatch() {
+                warnings.push(mismatch.to_string());
+            }
+            if let Some(trailing) = archive.trailing_bytes() {
+                warnings.push(format!(
+                    "{trailing} bytes after the DAT2 footer were ignored; saving drops them"
+                ));
+            }
+        }
+        warnings.extend(self.entry_problems().iter().map(|p| p.to_string()));
+        for entry in self.entries() {
+            if let Some(kind) = entry.unsupported_compression {
+                warnings.push(format!(
+                    "unsupported compression type {kind} in entry {}",
+                    entry.path()
+                ));
+            }
+        }
+        for duplicate in crate::dedupe::find_duplicates(self, Default::default()) {
+            warnings.push(if duplicate.differs_by_case() {
+                format!(
+                    "{} differ only by case",
+                    duplicate.spellings().join(" and ")
+                )
+            } else {
+                format!(
+                    "{} entries are named {}",
+                    duplicate.entries.len(),
+                    duplicate.name
+                )
+            });
+        }
+        warnings
+    }
+
+    /// Open an archive that may be nested inside other archives.
+    ///
+    /// `outer.dat::art/inner.dat` opens `art/inner.dat` stored inside
+    /// `outer.dat`; levels can be chained up to `MAX_NESTING_DEPTH`.
+    ///
+    /// An inner archive up to `NESTED_SPILL_THRESHOLD` bytes is decompressed
+    /// in memory, so both archives are held until the inner one is parsed.
+    /// A bigger one is streamed to a temp file and read back only after the
+    /// outer archive is released, so peak memory is the bigger of the two
+    /// rather than their sum. The temp file is removed once parsed.
+    ///
+    /// `options` apply to every level.
+    pub fn open_nested<P: AsRef<Path>>(spec: P, options: &OpenOptions) -> Result<Self> {
+        Self::open_nested_spilling(spec.as_ref(), options, NESTED_SPILL_THRESHOLD)
+    }
+
+    /// `open_nested`, spilling inner archives bigger than `spill_above` bytes
+    pub(crate) fn open_nested_spilling(
+        spec: &Path,
+        options: &OpenOptions,
+        spill_above: u64,
+    ) -> Result<Self> {
+        let (outer_path, inner_names) = utils::split_nested_archive_path(spec)?;
+        let mut archive = Self::open_with(&outer_path, options)?;
+        let inner_options = OpenOptions {
+            format: ArchiveFormat::Auto,
+            ..*options
+        };
+
+        for inner_name in inner_names {
+            let read_context = || {
+                format!(
+                    "Failed to read nested archive {}",
+                    utils::canonical_entry_path(&inner_name)
+                )
+            };
+            let parse_context = || {
+                format!(
+                    "Failed to parse nested archive {}",
+                    utils::canonical_entry_path(&inner_name)
+                )
+            };
+            let size = archive
+                .entries()
+                .find(|file| utils::compare_entry_paths(&file.name, &inner_name).is_eq())
+                .map_or(0, |file| file.size as u64);
+
+            archive = if size > spill_above {
+                let spill = SpillFile::create()?;
+                let mut out = io::BufWriter::new(spill.file()?);
+                archive
+                    .read_file_to(&inner_name, &mut out)
+                    .with_context(read_context)?;
+                out.flush()
+                    .with_context(|| format!("Failed to write {}", spill.path.display()))?;
+                drop(out);
+                drop(archive);
+                Self::open_with(&spill.path, &inner_options).with_context(parse_context)?
+            } else {
+                let data = archive.read_file(&inner_name).with_context(read_context)?;
+                drop(archive);
+                Self::from_bytes_with(data, &inner_options).with_context(parse_context)?
+            };
         }
+
+        Ok(archive)
     }
-}
 
-/// Controls how files are extracted from archives
-#[derive(Debug, Clone, Copy)]
-pub enum ExtractionMode {
-    /// Keep the original directory structure
-    PreserveStructure,
-    /// Put all files in one flat directory
-    Flat,
-}
+    /// Find data-region bytes that no entry references
+    pub fn space_report(&self) -> SpaceReport {
+        match self {
+            Self::Dat1(a) => a.space_report(),
+            Self::Dat2(a) => a.space_report(),
+        }
+    }
 
-// ── DatArchive enum ────────────────────────────────────────────────
+    /// Read one entry's decompressed content by its archive path.
+    ///
+    /// Names accept either slash and any case. A missing entry is
+    /// `DatError::EntryNotFound`; content that doesn't decompress to the size
+    /// recorded in the directory tree is `DatError::SizeMismatch`.
+    pub fn read_file(&self, name: &str) -> DatResult<Vec<u8>> {
+        match self {
+            Self::Dat1(a) => a.read_file(name),
+            Self::Dat2(a) => a.read_file(name),
+        }
+    }
 
-/// Unified interface for both DAT1 and DAT2 archives.
-///
-/// Uses an enum instead of trait objects because there are exactly two
-/// known formats - this gives us static dispatch, exhaustive matching,
-/// and no heap allocation for the wrapper.
-///
-/// **Memory**: The entire archive is loaded into memory on open. This works
-/// well for typical Fallout archives (up to ~200MB).
-///
-/// ```ignore
-/// let archive = DatArchive::open("master.dat")?;  // auto-detects format
-/// let dat1 = DatArchive::new_dat1();               // create new DAT1
-/// let dat2 = DatArchive::new_dat2();               // create new DAT2
-/// ```
-pub enum DatArchive {
-    /// Fallout 1 format (big-endian, hierarchical dirs, LZSS compression)
-    Dat1(Dat1Archive),
-    /// Fallout 2 format (little-endian, flat file list, zlib compression)
-    Dat2(Dat2Archive),
-}
+    /// Read the decompressed content of one of this archive's entries, as
+    /// returned by `entries` or `find_entries`
+    pub fn read_entry(&self, file: &FileEntry) -> DatResult<Vec<u8>> {
+        match self {
+            Self::Dat1(a) => a.read_entry_contents(file),
+            Self::Dat2(a) => a.read_entry_contents(file),
+        }
+    }
 
-impl DatArchive {
-    /// Open an existing DAT archive, auto-detecting the format
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let data = fs::read(&path)
-            .with_context(|| format!("Failed to read DAT file: {}", path.as_ref().display()))?;
+    /// Stream one entry's decompressed content into `out`, without holding
+    /// all of it in memory, and return the number of bytes written.
+    ///
+    /// Names and errors are as for `read_file`; a failure of `out` is
+    /// `DatError::Write`. Content past the size recorded in the directory
+    /// tree isn't written, so `out` holds at most that many bytes.
+    ///
+    /// ```no_run
+    /// use fallout_dat3::DatArchive;
+    ///
+    /// let archive = DatArchive::open("master.dat")?;
+    /// let mut sink = std::io::sink();
+    /// let written = archive.read_file_to("art/intrface/iface.frm", &mut sink)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn read_file_to<W: Write + ?Sized>(&self, name: &str, out: &mut W) -> DatResult<u64> {
+        match self {
+            Self::Dat1(a) => a.read_file_to(name, out),
+            Self::Dat2(a) => a.read_file_to(name, out),
+        }
+    }
 
-        if Self::is_dat1_format(&data) {
-            Ok(Self::Dat1(Dat1Archive::from_bytes(data)?))
-        } else {
-            Ok(Self::Dat2(Dat2Archive::from_bytes(data)?))
+    /// Stream the decompressed content of one of this archive's entries into
+    /// `out`, like `read_file_to`
+    pub fn read_entry_to<W: Write + ?Sized>(
+        &self,
+        file: &FileEntry,
+        out: &mut W,
+    ) -> DatResult<u64> {
+        match self {
+            Self::Dat1(a) => a.write_entry_contents(file, out),
+            Self::Dat2(a) => a.write_entry_contents(file, out),
         }
     }
 
@@ -168,6 +1767,22 @@ impl DatArchive {
         Self::Dat2(Dat2Archive::new())
     }
 
+    /// Serve decompressed entries from (and store them in) an on-disk cache
+    pub fn enable_cache(&mut self, config: &CacheConfig) -> Result<()> {
+        match self {
+            Self::Dat1(a) => a.enable_cache(config),
+            Self::Dat2(a) => a.enable_cache(config),
+        }
+    }
+
+    /// Preserve the parsed entry order when adding files (`--keep-order`)
+    pub fn set_keep_order(&mut self, keep_order: bool) {
+        match self {
+            Self::Dat1(a) => a.set_keep_order(keep_order),
+            Self::Dat2(a) => a.set_keep_order(keep_order),
+        }
+    }
+
     /// Check if this is a DAT1 archive
     pub fn is_dat1(&self) -> bool {
         matches!(self, Self::Dat1(_))
@@ -175,7 +1790,7 @@ impl DatArchive {
 
     /// Detect DAT1 format by examining the big-endian header.
     /// DAT1 has a directory count and a known format identifier (0x0A or 0x5E).
-    fn is_dat1_format(data: &[u8]) -> bool {
+    pub(crate) fn is_dat1_format(data: &[u8]) -> bool {
         if data.len() < 16 {
             return false;
         }
@@ -196,46 +1811,237 @@ impl DatArchive {
         false
     }
 
-    /// List files in the archive (all or filtered by patterns)
-    pub fn list(&self, files: &[String]) -> Result<()> {
+    /// All entries, in archive order (tree order for DAT1).
+    /// `FileEntry::path` gives an entry's name with forward slashes.
+    pub fn entries(&self) -> Box<dyn Iterator<Item = &FileEntry> + '_> {
+        match self {
+            Self::Dat1(a) => Box::new(a.entries()),
+            Self::Dat2(a) => Box::new(a.entries()),
+        }
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
         match self {
-            Self::Dat1(a) => a.list(files),
-            Self::Dat2(a) => a.list(files),
+            Self::Dat1(a) => a.len(),
+            Self::Dat2(a) => a.len(),
         }
     }
 
-    /// Extract files from the archive
+    /// Whether the archive has no entries
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Dat1(a) => a.is_empty(),
+            Self::Dat2(a) => a.is_empty(),
+        }
+    }
+
+    /// Whether an entry's path equals `name` by `compare_entry_paths`
+    pub fn contains(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// The first entry whose path equals `name` by `compare_entry_paths`
+    pub fn entry(&self, name: &str) -> Option<&FileEntry> {
+        self.entries()
+            .find(|f| utils::compare_entry_paths(&f.name, name).is_eq())
+    }
+
+    /// Entries matching any of `patterns` (all entries if there are none),
+    /// plus the patterns that matched nothing
+    pub fn find_entries(&self, patterns: &[String]) -> (Vec<&FileEntry>, Vec<String>) {
+        filter_files_by_patterns(&self.entries().collect::<Vec<_>>(), patterns)
+    }
+
+    /// Entries with an extension `filter` keeps, under any of `prefixes`
+    /// (all entries if there are none), plus the prefixes that matched nothing.
+    /// Prefixes match whole path components; globs match as in `find_entries`.
+    pub fn find_entries_by_extension(
+        &self,
+        prefixes: &[String],
+        filter: &utils::ExtensionFilter,
+    ) -> (Vec<&FileEntry>, Vec<String>) {
+        let kept: Vec<&FileEntry> = self.entries().filter(|f| filter.matches(&f.name)).collect();
+        let (found, missing_prefixes) =
+            filter_and_track_patterns(&kept, prefixes, |file, prefix| {
+                utils::matches_path_prefix(&file.name, prefix)
+            });
+        (found.into_iter().copied().collect(), missing_prefixes)
+    }
+
+    /// Entries `d` deletes for `patterns`: names match a single entry exactly
+    /// (by `compare_entry_paths`), globs match as in `find_entries`, and names
+    /// ending in a slash every entry under that directory
+    pub fn plan_delete(&self, patterns: &[String]) -> DeletePlan {
+        let entries: Vec<&FileEntry> = self.entries().collect();
+        let mut selected = vec![false; entries.len()];
+        let matches = patterns
+            .iter()
+            .map(|pattern| {
+                let mut count = 0;
+                for (i, entry) in entries.iter().enumerate() {
+                    if utils::matches_delete_pattern(&entry.name, pattern) {
+                        selected[i] = true;
+                        count += 1;
+                    }
+                }
+                (pattern.clone(), count)
+            })
+            .collect();
+        let names = entries
+            .iter()
+            .zip(selected)
+            .filter(|(_, selected)| *selected)
+            .map(|(entry, _)| entry.name.clone())
+            .collect();
+        DeletePlan { names, matches }
+    }
+
+    /// Extract files from the archive.
+    /// `files` are names or glob patterns; an empty list extracts everything.
+    ///
+    /// ```no_run
+    /// use fallout_dat3::{DatArchive, Event, ExtractOptions, ExtractionMode, Progress};
+    ///
+    /// let archive = DatArchive::open("master.dat")?;
+    /// let options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+    /// let progress = Progress::with_events(|event| {
+    ///     if let Event::ExtractFinished { elapsed, .. } = event {
+    ///         eprintln!("done in {elapsed:?}");
+    ///     }
+    /// });
+    /// archive.extract("out", &["art/critters/*.frm".to_string()], &options, &progress)?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
     pub fn extract<P: AsRef<Path>>(
         &self,
         output_dir: P,
         files: &[String],
-        mode: ExtractionMode,
+        options: &ExtractOptions,
+        progress: &Progress,
     ) -> Result<()> {
         match self {
-            Self::Dat1(a) => a.extract(output_dir.as_ref(), files, mode),
-            Self::Dat2(a) => a.extract(output_dir.as_ref(), files, mode),
+            Self::Dat1(a) => a.extract(output_dir.as_ref(), files, options, progress),
+            Self::Dat2(a) => a.extract(output_dir.as_ref(), files, options, progress),
         }
     }
 
-    /// Add a file to the archive (directories are processed recursively)
+    /// Add a file or directory to the archive.
+    /// Subdirectories of a directory are only included with `options.recursive`.
     pub fn add_file<P: AsRef<Path>>(
         &mut self,
         file_path: P,
-        compression: CompressionLevel,
-        target_dir: Option<&str>,
-        source_root: Option<&Path>,
-    ) -> Result<()> {
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
+        match self {
+            Self::Dat1(a) => a.add_file(file_path.as_ref(), options, progress),
+            Self::Dat2(a) => a.add_file(file_path.as_ref(), options, progress),
+        }
+    }
+
+    /// Add in-memory `(name, content)` pairs, named like `content_archive_path`
+    /// of `options` describes, replacing same-named entries like `add_file`.
+    /// Names the exclude list matches are left out; the rest must be unique.
+    pub fn add_contents(
+        &mut self,
+        contents: Vec<(String, Vec<u8>)>,
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
+        match self {
+            Self::Dat1(a) => a.add_contents(contents, options, progress),
+            Self::Dat2(a) => a.add_contents(contents, options, progress),
+        }
+    }
+
+    /// Add an empty DAT1 directory record (`a --mkdir`), kept on save until
+    /// it's deleted. Returns false if a directory of that name, ignoring
+    /// case, already exists. DAT2 stores only file paths, so has no empty
+    /// directories.
+    pub fn add_directory(&mut self, name: &str) -> Result<bool> {
+        match self {
+            Self::Dat1(a) => a.add_directory(name),
+            Self::Dat2(_) => bail!("DAT2 archives can't hold empty directories"),
+        }
+    }
+
+    /// Fold the names of entries already in the archive (`--rename-existing`).
+    /// Fails without changes if two entries would end up with the same name.
+    /// Returns the number of renamed entries.
+    pub fn fold_existing_names(&mut self, case: NameCase) -> Result<usize> {
+        match self {
+            Self::Dat1(a) => a.fold_existing_names(case),
+            Self::Dat2(a) => a.fold_existing_names(case),
+        }
+    }
+
+    /// Rebuild the archive in canonical, deterministic form (same format).
+    /// DAT1 archives ignore the compression level since LZSS writing isn't supported.
+    pub fn repack(&self, compression: &CompressionPolicy) -> Result<(Self, AddStats)> {
+        match self {
+            Self::Dat1(a) => a.repack().map(|(a, stats)| (Self::Dat1(a), stats)),
+            Self::Dat2(a) => a
+                .repack(compression)
+                .map(|(a, stats)| (Self::Dat2(a), stats)),
+        }
+    }
+
+    /// Build a new archive from `(archive path, content)` pairs.
+    /// DAT1 archives ignore the compression level since LZSS writing isn't supported.
+    pub fn from_entries(
+        dat1: bool,
+        entries: Vec<(String, Vec<u8>)>,
+        compression: &CompressionPolicy,
+    ) -> Result<(Self, AddStats)> {
+        if dat1 {
+            let (archive, stats) = Dat1Archive::from_entries(entries);
+            Ok((Self::Dat1(archive), stats))
+        } else {
+            Dat2Archive::from_entries(entries, compression)
+                .map(|(archive, stats)| (Self::Dat2(archive), stats))
+        }
+    }
+
+    /// Read the decompressed content of the entries named in `names`
+    pub fn read_entries(&self, names: &HashSet<String>) -> Result<Vec<(String, Vec<u8>)>> {
+        match self {
+            Self::Dat1(a) => a.read_entries(names),
+            Self::Dat2(a) => a.read_entries(names),
+        }
+    }
+
+    /// Names of all entries, in archive order
+    pub fn entry_names(&self) -> Vec<String> {
         match self {
-            Self::Dat1(a) => a.add_file(file_path.as_ref(), compression, target_dir, source_root),
-            Self::Dat2(a) => a.add_file(file_path.as_ref(), compression, target_dir, source_root),
+            Self::Dat1(a) => a.entry_names(),
+            Self::Dat2(a) => a.entry_names(),
         }
     }
 
     /// Delete a file from the archive
-    pub fn delete_file(&mut self, file_name: &str) -> Result<()> {
+    pub fn delete_file(&mut self, file_name: &str, progress: &Progress) -> Result<()> {
+        match self {
+            Self::Dat1(a) => a.delete_file(file_name, progress),
+            Self::Dat2(a) => a.delete_file(file_name, progress),
+        }
+    }
+
+    /// Keep only the entries `keep` returns true for. Unlike `delete_file`,
+    /// this tells entries with the same name apart.
+    pub fn retain_entries(&mut self, keep: impl FnMut(&FileEntry) -> bool) {
+        match self {
+            Self::Dat1(a) => a.retain_entries(keep),
+            Self::Dat2(a) => a.retain_entries(keep),
+        }
+    }
+
+    /// Remove DAT1 directory records at or under `prefix` left without
+    /// files, returning how many went. DAT2 has no directory records.
+    pub fn remove_empty_directories(&mut self, prefix: &str) -> usize {
         match self {
-            Self::Dat1(a) => a.delete_file(file_name),
-            Self::Dat2(a) => a.delete_file(file_name),
+            Self::Dat1(a) => a.remove_empty_directories(prefix),
+            Self::Dat2(_) => 0,
         }
     }
 
@@ -244,71 +2050,156 @@ impl DatArchive {
         match self {
             Self::Dat1(a) => a.save(path.as_ref()),
             Self::Dat2(a) => a.save(path.as_ref()),
-        }
+        }?;
+        self.log_saved(path.as_ref(), "in full");
+        Ok(())
     }
-}
 
-// ── Shared archive operations ──────────────────────────────────────
+    /// Save like `save`, but writing data that several entries have in
+    /// common once, with all of them pointing at it. DAT1 archives are saved
+    /// as usual.
+    pub fn save_deduplicated<P: AsRef<Path>>(&self, path: P) -> Result<SharedData> {
+        let shared = match self {
+            Self::Dat1(a) => a.save(path.as_ref()).map(|()| SharedData::default()),
+            Self::Dat2(a) => a.save_deduplicated(path.as_ref()),
+        }?;
+        self.log_saved(path.as_ref(), "with shared data");
+        Ok(shared)
+    }
 
-/// List files using shared filter-and-print logic.
-///
-/// Both DAT1 and DAT2 use this same flow:
-/// normalize patterns -> filter entries -> print listing -> report missing.
-pub fn list_files_filtered(all_files: &[&FileEntry], patterns: &[String]) -> Result<()> {
-    let normalized_patterns = utils::normalize_user_patterns(patterns);
+    /// Save by appending the added entries to the file the archive was opened
+    /// from, when nothing was deleted or replaced; otherwise, and always for
+    /// DAT1, a full `save`. Returns whether the archive was appended to.
+    pub fn save_appending<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let appended = match self {
+            Self::Dat1(a) => a.save(path.as_ref()).map(|()| false),
+            Self::Dat2(a) => a.save_appending(path.as_ref()),
+        }?;
+        self.log_saved(
+            path.as_ref(),
+            if appended { "by appending" } else { "in full" },
+        );
+        Ok(appended)
+    }
 
-    let (files_to_list, missing_patterns) =
-        filter_and_track_patterns(all_files, &normalized_patterns, |file, pattern| {
-            utils::matches_pattern(&file.name, pattern)
-        });
+    /// Save after deletes by keeping the data as it is, the deleted entries'
+    /// data included, and writing only a new directory tree after it;
+    /// otherwise, and always for DAT1, a full `save`. The file is replaced
+    /// atomically either way. Returns whether the data was kept as it was.
+    pub fn save_tree_only<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let tree_only = match self {
+            Self::Dat1(a) => a.save(path.as_ref()).map(|()| false),
+            Self::Dat2(a) => a.save_tree_only(path.as_ref()),
+        }?;
+        self.log_tree_saved(path.as_ref(), tree_only);
+        Ok(tree_only)
+    }
 
-    utils::print_file_listing(&files_to_list);
+    /// `save_tree_only`, but writing the new tree over the old one in the
+    /// file itself. Not atomic: an interrupted save may leave the file
+    /// without a valid tree. Returns whether only the tree was written.
+    pub fn save_tree_in_place<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let tree_only = match self {
+            Self::Dat1(a) => a.save(path.as_ref()).map(|()| false),
+            Self::Dat2(a) => a.save_tree_in_place(path.as_ref()),
+        }?;
+        self.log_tree_saved(path.as_ref(), tree_only);
+        Ok(tree_only)
+    }
 
-    if !missing_patterns.is_empty() {
-        eprintln!("\nFiles not found:");
-        for pattern in &missing_patterns {
-            let display = utils::normalize_path_for_display(pattern);
-            eprintln!("  {display}");
-        }
-        bail!("Some requested files were not found");
+    fn log_tree_saved(&self, path: &Path, tree_only: bool) {
+        self.log_saved(
+            path,
+            if tree_only {
+                "rewriting the tree"
+            } else {
+                "in full"
+            },
+        );
+    }
+
+    fn log_saved(&self, path: &Path, how: &str) {
+        log::info!("Saved {} {how}: {} entries", path.display(), self.len());
     }
+}
+
+/// What `DatArchive::plan_delete` found for a set of patterns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletePlan {
+    /// Stored names of the entries to delete, each once, in archive order
+    pub names: Vec<String>,
+    /// Each pattern, with how many entries it matched
+    pub matches: Vec<(String, usize)>,
+}
+
+impl DeletePlan {
+    /// Patterns that matched no entries
+    pub fn missing(&self) -> Vec<String> {
+        self.matches
+            .iter()
+            .filter(|(_, count)| *count == 0)
+            .map(|(pattern, _)| pattern.clone())
+            .collect()
+    }
+}
 
-    Ok(())
+// ── Shared archive operations ──────────────────────────────────────
+
+/// Report each pattern that matched nothing as `Event::NotFound` and fail.
+///
+/// Shared by list and extract.
+pub fn report_missing_patterns(missing_patterns: &[String], progress: &Progress) -> Result<()> {
+    if missing_patterns.is_empty() {
+        return Ok(());
+    }
+
+    progress.emit(Event::NotFound {
+        patterns: missing_patterns,
+    });
+    Err(DatError::PatternsNotFound {
+        patterns: missing_patterns.to_vec(),
+    }
+    .into())
 }
 
-/// Filter files by patterns and return matched files.
+/// Filter files by patterns.
 ///
-/// Shared by DAT1 and DAT2 extract paths.
+/// Returns (matched_files, unmatched_patterns). Shared by DAT1 and DAT2 extract paths.
 pub fn filter_files_by_patterns<'a>(
-    all_files: &'a [FileEntry],
+    all_files: &[&'a FileEntry],
     patterns: &[String],
-) -> Vec<&'a FileEntry> {
-    let normalized_patterns = utils::normalize_user_patterns(patterns);
-
-    let (filtered, _) =
-        filter_and_track_patterns(all_files, &normalized_patterns, |file, pattern| {
+) -> (Vec<&'a FileEntry>, Vec<String>) {
+    let (filtered, missing_patterns) =
+        filter_and_track_patterns(all_files, patterns, |file, pattern| {
             utils::matches_pattern(&file.name, pattern)
         });
 
-    filtered
+    (filtered.into_iter().copied().collect(), missing_patterns)
 }
 
-/// Delete a file from a list by normalized name.
+/// Delete the first file whose path equals `file_name` by `compare_entry_paths`.
 ///
 /// Shared by DAT1 and DAT2 delete implementations.
-pub fn delete_file_from_list(files: &mut Vec<FileEntry>, file_name: &str) -> Result<()> {
-    let normalized_name = utils::normalize_user_path(file_name).into_owned();
-
-    if let Some(pos) = files.iter().position(|f| f.name == normalized_name) {
-        let display_name = utils::normalize_path_for_display(&normalized_name);
-        println!("Deleting: {display_name}");
+pub fn delete_file_from_list(
+    files: &mut Vec<FileEntry>,
+    file_name: &str,
+    progress: &Progress,
+) -> Result<()> {
+    if let Some(pos) = files
+        .iter()
+        .position(|f| utils::compare_entry_paths(&f.name, file_name).is_eq())
+    {
+        log::debug!("Deleting {}", utils::canonical_entry_path(&files[pos].name));
+        progress.emit(Event::Deleting {
+            name: &files[pos].name,
+        });
         files.remove(pos);
         Ok(())
     } else {
-        bail!(
-            "File not found: {}",
-            utils::normalize_path_for_display(file_name)
-        );
+        Err(DatError::EntryNotFound {
+            name: utils::canonical_entry_path(file_name),
+        }
+        .into())
     }
 }
 
@@ -338,60 +2229,454 @@ pub fn filter_and_track_patterns<'a, T>(
         }
     }
 
-    let missing_patterns: Vec<String> = patterns
-        .iter()
-        .enumerate()
-        .filter_map(|(idx, pattern)| {
-            if !patterns_found[idx] {
-                Some(pattern.clone())
-            } else {
-                None
-            }
-        })
-        .collect();
+    let missing_patterns: Vec<String> = patterns
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, pattern)| {
+            if !patterns_found[idx] {
+                Some(pattern.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    (filtered_items, missing_patterns)
+}
+
+// ── Utility functions ──────────────────────────────────────────────
+
+/// Helper functions for file/path operations and pattern matching
+pub mod utils {
+    use super::*;
+
+    /// Width of the right-aligned Size and Packed columns
+    const SIZE_COLUMN_WIDTH: usize = 11;
+    /// Width of the Comp column
+    const COMP_COLUMN_WIDTH: usize = 4;
+    /// Width of the optional Index column
+    const INDEX_COLUMN_WIDTH: usize = 6;
+    /// Width of the verbose Offset column
+    const OFFSET_COLUMN_WIDTH: usize = 10;
+    /// Width of the verbose Type column
+    const TYPE_COLUMN_WIDTH: usize = 4;
+    /// Width of the verbose Dir column
+    const DIR_COLUMN_WIDTH: usize = 5;
+    /// Length of the dashed line under the listing header
+    const HEADER_RULE_WIDTH: usize = 50;
+
+    /// Parts of a listing row that `ListingLayout::styled_row` can style
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RowPart {
+        /// Directory part of the name, with its trailing `/`
+        Directory,
+        /// Comp column of a compressed entry
+        Compressed,
+        /// Comp column of a stored entry
+        Stored,
+    }
+
+    /// Column layout for file listings.
+    ///
+    /// Chosen once per listing so every row has the same columns. On narrow
+    /// terminals optional columns are dropped first (Comp, then Packed),
+    /// and only then are names truncated. Verbose layouts keep every column.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ListingLayout {
+        /// Leading original-index column; never dropped when requested
+        pub show_index: bool,
+        pub show_packed: bool,
+        pub show_comp: bool,
+        /// Offset, stored type and DAT1 directory columns (`l -l`)
+        pub show_details: bool,
+        /// Maximum name width in characters (None = never truncate)
+        pub name_width: Option<usize>,
+        /// Total available width (None = unlimited)
+        pub total_width: Option<usize>,
+    }
+
+    impl ListingLayout {
+        /// Pick a layout for `width` columns (None = not a terminal, use the full table)
+        pub fn for_width(width: Option<usize>, longest_name: usize) -> Self {
+            let full = Self {
+                show_index: false,
+                show_packed: true,
+                show_comp: true,
+                show_details: false,
+                name_width: None,
+                total_width: width,
+            };
+            let Some(width) = width else {
+                return full;
+            };
+
+            let candidates = [
+                full,
+                Self {
+                    show_comp: false,
+                    ..full
+                },
+                Self {
+                    show_packed: false,
+                    show_comp: false,
+                    ..full
+                },
+            ];
+
+            for layout in candidates {
+                if layout.prefix_width() + longest_name <= width {
+                    return layout;
+                }
+            }
+
+            // Even the narrowest layout doesn't fit: truncate names to what's
+            // left, down to the tail of the basename or just an ellipsis
+            let narrowest = candidates[candidates.len() - 1];
+            Self {
+                name_width: Some(width.saturating_sub(narrowest.prefix_width())),
+                ..narrowest
+            }
+        }
+
+        /// Like `for_width`, with the Index column in front of the table
+        pub fn with_index(width: Option<usize>, longest_name: usize) -> Self {
+            let rest = width.map(|w| w.saturating_sub(INDEX_COLUMN_WIDTH + 1));
+            Self {
+                show_index: true,
+                total_width: width,
+                ..Self::for_width(rest, longest_name)
+            }
+        }
+
+        /// Every column, with the Index column if `show_index`. Names are
+        /// truncated only if even then they don't fit in `width`.
+        pub fn verbose(width: Option<usize>, longest_name: usize, show_index: bool) -> Self {
+            let full = Self {
+                show_index,
+                show_packed: true,
+                show_comp: true,
+                show_details: true,
+                name_width: None,
+                total_width: width,
+            };
+            let index_width = if show_index {
+                INDEX_COLUMN_WIDTH + 1
+            } else {
+                0
+            };
+            let name_width = width
+                .map(|w| w.saturating_sub(index_width + full.prefix_width()))
+                .filter(|&available| available < longest_name);
+            Self { name_width, ..full }
+        }
+
+        /// Width of everything before the name column, including spacing
+        fn prefix_width(&self) -> usize {
+            let mut width = SIZE_COLUMN_WIDTH;
+            if self.show_details {
+                // Offset before the sizes; Type and Dir after Comp
+                width += OFFSET_COLUMN_WIDTH + 1;
+                width += 2 + TYPE_COLUMN_WIDTH + 1 + DIR_COLUMN_WIDTH;
+            }
+            if self.show_packed {
+                width += 1 + SIZE_COLUMN_WIDTH;
+            }
+            if self.show_comp {
+                width += 2 + COMP_COLUMN_WIDTH;
+            }
+            width + 2
+        }
+
+        /// Format the header line
+        pub fn header(&self) -> String {
+            self.format_columns(Cells {
+                index: "Index",
+                offset: "Offset",
+                size: "Size",
+                packed: "Packed",
+                comp: "Comp",
+                kind: "Type",
+                dir: "Dir",
+                name: "Name",
+            })
+        }
+
+        /// Format the dashed line under the header
+        pub fn rule(&self) -> String {
+            let width = self
+                .total_width
+                .map_or(HEADER_RULE_WIDTH, |w| w.min(HEADER_RULE_WIDTH));
+            "-".repeat(width)
+        }
+
+        /// Format one entry's row
+        pub fn row(&self, file: &FileEntry) -> String {
+            self.styled_row(file, |_, text| text.to_string())
+        }
+
+        /// Format one entry's row, passing the parts `RowPart` names through
+        /// `paint` once they're padded, e.g. to color them
+        pub fn styled_row(
+            &self,
+            file: &FileEntry,
+            paint: impl Fn(RowPart, &str) -> String,
+        ) -> String {
+            self.styled_row_named(file, &file.path(), paint)
+        }
+
+        /// `styled_row`, showing `display_name` in the Name column instead
+        /// of the entry's path, e.g. a name relative to a directory header
+        pub fn styled_row_named(
+            &self,
+            file: &FileEntry,
+            display_name: &str,
+            paint: impl Fn(RowPart, &str) -> String,
+        ) -> String {
+            let (comp_str, comp_part) = if file.compressed {
+                ("Yes", RowPart::Compressed)
+            } else {
+                ("No", RowPart::Stored)
+            };
+            let display_name = match self.name_width {
+                Some(max) => truncate_path_middle(display_name, max),
+                None => display_name.to_string(),
+            };
+            let name = match display_name.rfind('/') {
+                Some(slash) => {
+                    let (dir, file_name) = display_name.split_at(slash + 1);
+                    paint(RowPart::Directory, dir) + file_name
+                }
+                None => display_name,
+            };
+            let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            self.format_columns(Cells {
+                index: &or_dash(file.original_index.map(|i| i.to_string())),
+                // Added entries get their offset when saved
+                offset: &or_dash(file.data.is_none().then(|| file.offset.to_string())),
+                size: &file.size.to_string(),
+                packed: &file.packed_size.to_string(),
+                comp: &paint(comp_part, &format!("{comp_str:>COMP_COLUMN_WIDTH$}")),
+                kind: &or_dash(file.stored_type.map(|t| format!("{t:#04x}"))),
+                dir: &or_dash(file.directory_index.map(|i| i.to_string())),
+                name: &name,
+            })
+        }
+
+        fn format_columns(&self, cells: Cells) -> String {
+            let Cells {
+                index,
+                offset,
+                size,
+                packed,
+                comp,
+                kind,
+                dir,
+                name,
+            } = cells;
+            let mut line = String::new();
+            if self.show_index {
+                line.push_str(&format!("{index:>INDEX_COLUMN_WIDTH$} "));
+            }
+            if self.show_details {
+                line.push_str(&format!("{offset:>OFFSET_COLUMN_WIDTH$} "));
+            }
+            line.push_str(&format!("{size:>SIZE_COLUMN_WIDTH$}"));
+            if self.show_packed {
+                line.push_str(&format!(" {packed:>SIZE_COLUMN_WIDTH$}"));
+            }
+            if self.show_comp {
+                line.push_str(&format!("  {comp:>COMP_COLUMN_WIDTH$}"));
+            }
+            if self.show_details {
+                line.push_str(&format!(
+                    "  {kind:>TYPE_COLUMN_WIDTH$} {dir:>DIR_COLUMN_WIDTH$}"
+                ));
+            }
+            line.push_str("  ");
+            line.push_str(name);
+            line
+        }
+    }
+
+    /// Text of each column of one listing line
+    struct Cells<'a> {
+        index: &'a str,
+        offset: &'a str,
+        size: &'a str,
+        packed: &'a str,
+        comp: &'a str,
+        kind: &'a str,
+        dir: &'a str,
+        name: &'a str,
+    }
+
+    /// Order of listed entries (`l --sort`)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+    pub enum ListingSort {
+        /// By path, as the game compares names (case-insensitive)
+        Name,
+        /// By real size
+        Size,
+        /// By packed size
+        Packed,
+        /// By packed size relative to real size (stored entries are 1.0)
+        Ratio,
+    }
+
+    impl ListingSort {
+        /// Sort `entries` by this key, smallest first or, if `descending`,
+        /// largest first. Ties stay in archive order either way.
+        pub fn sort(self, entries: &mut [&FileEntry], descending: bool) {
+            let order = |ordering: std::cmp::Ordering| {
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            };
+            match self {
+                Self::Name => entries.sort_by(|a, b| order(compare_entry_paths(&a.name, &b.name))),
+                Self::Size => entries.sort_by(|a, b| order(a.size.cmp(&b.size))),
+                Self::Packed => entries.sort_by(|a, b| order(a.packed_size.cmp(&b.packed_size))),
+                Self::Ratio => entries
+                    .sort_by(|a, b| order(compression_ratio(a).total_cmp(&compression_ratio(b)))),
+            }
+        }
+    }
+
+    /// Extensions a listing keeps or drops (`l --ext`, `--not-ext`).
+    ///
+    /// Extensions are compared case-insensitively against the end of the
+    /// file name, so `frm` matches `a.FRM` but not `a.fr` or `a.frm2`.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct ExtensionFilter {
+        /// Lowercased, without the leading dot; empty keeps every extension
+        pub include: Vec<String>,
+        /// Lowercased, without the leading dot
+        pub exclude: Vec<String>,
+    }
+
+    impl ExtensionFilter {
+        /// A filter keeping `include` (any if empty) but not `exclude`;
+        /// a leading `.` on an extension is ignored
+        pub fn new(include: &[String], exclude: &[String]) -> Self {
+            let normalize = |exts: &[String]| {
+                exts.iter()
+                    .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                    .filter(|ext| !ext.is_empty())
+                    .collect()
+            };
+            Self {
+                include: normalize(include),
+                exclude: normalize(exclude),
+            }
+        }
+
+        /// Whether the filter keeps every entry
+        pub fn is_empty(&self) -> bool {
+            self.include.is_empty() && self.exclude.is_empty()
+        }
+
+        /// Whether an entry named `path` passes the filter
+        pub fn matches(&self, path: &str) -> bool {
+            let name = get_filename_from_dat_path(path).to_ascii_lowercase();
+            let has = |ext: &String| {
+                name.len() > ext.len() + 1
+                    && name.ends_with(ext.as_str())
+                    && name.as_bytes()[name.len() - ext.len() - 1] == b'.'
+            };
+            (self.include.is_empty() || self.include.iter().any(has))
+                && !self.exclude.iter().any(has)
+        }
+    }
+
+    /// Whether `path` is `prefix` or lies under it, ignoring case and
+    /// slash direction. Glob patterns are matched as globs instead.
+    pub fn matches_path_prefix(path: &str, prefix: &str) -> bool {
+        if contains_glob_metacharacters(prefix) {
+            return matches_pattern(path, prefix);
+        }
+        let prefix = entry_path_key(prefix);
+        let prefix = prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            return true;
+        }
+        let path = entry_path_key(path);
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
 
-    (filtered_items, missing_patterns)
-}
+    /// Packed size as a fraction of real size; 1.0 for stored and empty entries
+    pub fn compression_ratio(entry: &FileEntry) -> f64 {
+        if !entry.compressed || entry.size == 0 {
+            return 1.0;
+        }
+        f64::from(entry.packed_size) / f64::from(entry.size)
+    }
 
-// ── Utility functions ──────────────────────────────────────────────
+    /// Shorten a path to at most `max_width` characters by replacing the middle
+    /// with "...", keeping the basename visible.
+    ///
+    /// "art/critters/hmjmpsaa.frm" at 20 -> "art/.../hmjmpsaa.frm".
+    /// If the basename alone is too long, its tail is kept instead.
+    pub fn truncate_path_middle(path: &str, max_width: usize) -> String {
+        const ELLIPSIS: &str = "...";
+
+        let path_len = path.chars().count();
+        if path_len <= max_width {
+            return path.to_string();
+        }
+        if max_width <= ELLIPSIS.len() {
+            return ELLIPSIS[..max_width].to_string();
+        }
 
-/// Helper functions for file/path operations and pattern matching
-pub mod utils {
-    use super::*;
-    use std::borrow::Cow;
-
-    /// Print formatted file listing to stdout.
-    /// Exits cleanly on broken pipe (e.g., when piped to `head`).
-    pub fn print_file_listing<T: AsRef<FileEntry>>(files: &[T]) {
-        print_stdout(format_args!(
-            "{:>11} {:>11}  {:>4}  Name",
-            "Size", "Packed", "Comp"
-        ));
-        print_stdout(format_args!("{}", "-".repeat(50)));
+        let basename = get_filename_from_dat_path(path);
+        // Separator plus basename, e.g. "/hmjmpsaa.frm"
+        let tail_len = (basename.chars().count() + 1).min(path_len);
 
-        for file in files {
-            let file = file.as_ref();
-            let comp_str = if file.compressed { "Yes" } else { "No" };
-            let display_name = normalize_path_for_display(&file.name);
-            print_stdout(format_args!(
-                "{:>11} {:>11}  {:>4}  {}",
-                file.size, file.packed_size, comp_str, display_name
-            ));
+        if tail_len + ELLIPSIS.len() > max_width {
+            let keep = max_width - ELLIPSIS.len();
+            let tail: String = path.chars().skip(path_len - keep).collect();
+            return format!("{ELLIPSIS}{tail}");
         }
+
+        let head_len = max_width - ELLIPSIS.len() - tail_len;
+        let head: String = path.chars().take(head_len).collect();
+        let tail: String = path.chars().skip(path_len - tail_len).collect();
+        format!("{head}{ELLIPSIS}{tail}")
     }
 
-    /// Collect all files from a path (file or directory, recursive).
-    /// Validates that all filenames are ASCII-only.
-    pub fn collect_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
-        collect_files_inner(path.as_ref(), &mut files)?;
-        Ok(files)
+    /// Collect all files from a path (file or directory).
+    /// A directory contributes its immediate files; subdirectories are only
+    /// walked when `recursive` is set. Validates that all filenames are ASCII-only.
+    /// Skipped symlinks are reported as `Event::SkippedSymlink`.
+    pub fn collect_files<P: AsRef<Path>>(
+        path: P,
+        recursive: bool,
+        progress: &Progress,
+    ) -> Result<Vec<PathBuf>> {
+        collect_files_excluding(path, recursive, &ExcludeList::empty(), progress)
+            .map(|found| found.files)
     }
 
-    /// Inner recursive worker for `collect_files`.
-    ///
-    /// Validates ASCII at the leaf push site so each path is checked exactly once.
-    fn collect_files_inner(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    /// Collect files like `collect_files`, leaving out those `excludes`
+    /// matches, also when `path` itself is one, and with
+    /// `excludes.ignore_files`, those the ignore files of walked directories
+    /// match (see `crate::ignore`)
+    pub fn collect_files_excluding<P: AsRef<Path>>(
+        path: P,
+        recursive: bool,
+        excludes: &ExcludeList,
+        progress: &Progress,
+    ) -> Result<CollectedFiles> {
+        let mut walk = FileWalk {
+            recursive,
+            excludes,
+            progress,
+            ignores: IgnoreStack::default(),
+            found: CollectedFiles::default(),
+        };
+        let path = path.as_ref();
         let metadata = match fs::symlink_metadata(path) {
             Ok(m) => m,
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
@@ -405,46 +2690,101 @@ pub mod utils {
 
         if metadata.file_type().is_symlink() {
             // Distinguish dangling symlinks (target missing) from non-dangling ones.
-            match path.try_exists() {
-                Ok(true) => eprintln!("Skipping symlink: {}", path.display()),
-                _ => eprintln!("Skipping dangling symlink: {}", path.display()),
-            }
-            return Ok(());
+            progress.emit(Event::SkippedSymlink {
+                path,
+                dangling: !matches!(path.try_exists(), Ok(true)),
+            });
+        } else if metadata.is_file() {
+            walk.add_file(path)?;
+        } else if metadata.is_dir() {
+            walk.visit_dir(path, "")?;
         }
+        Ok(walk.found)
+    }
+
+    /// State of one `collect_files_excluding` walk
+    struct FileWalk<'a> {
+        recursive: bool,
+        excludes: &'a ExcludeList,
+        progress: &'a Progress,
+        /// Ignore files of the directories being walked
+        ignores: IgnoreStack,
+        found: CollectedFiles,
+    }
 
-        if metadata.is_file() {
+    impl FileWalk<'_> {
+        /// Take `path` unless the exclude list matches it.
+        ///
+        /// Validates ASCII here so each path is checked exactly once, after
+        /// the exclude list and ignore files, so names they leave out
+        /// needn't be ASCII.
+        fn add_file(&mut self, path: &Path) -> Result<()> {
+            if self.excludes.is_excluded(path) {
+                self.found.excluded += 1;
+                return Ok(());
+            }
             let path_str = path
                 .to_str()
                 .ok_or_else(|| anyhow::anyhow!("Invalid path encoding: {}", path.display()))?;
             validate_filename_ascii(path_str)
                 .with_context(|| format!("Invalid path: {}", path.display()))?;
-            out.push(path.to_path_buf());
-        } else if metadata.is_dir() {
-            for entry in fs::read_dir(path)? {
+            self.found.files.push(path.to_path_buf());
+            Ok(())
+        }
+
+        /// Take the files of `dir`, found at `relative` under the walked
+        /// root, and with `recursive` those of its subdirectories
+        fn visit_dir(&mut self, dir: &Path, relative: &str) -> Result<()> {
+            let ignore_file = if self.excludes.ignore_files {
+                IgnoreFile::load(dir, relative)?
+            } else {
+                None
+            };
+            let has_ignore_file = ignore_file.is_some();
+            if let Some(file) = ignore_file {
+                self.ignores.push(file);
+            }
+
+            for entry in fs::read_dir(dir)? {
                 let entry = entry?;
                 let entry_path = entry.path();
                 let entry_metadata = fs::symlink_metadata(&entry_path)
                     .with_context(|| format!("Failed to inspect path: {}", entry_path.display()))?;
-
-                if entry_metadata.file_type().is_symlink() {
-                    match entry_path.try_exists() {
-                        Ok(true) => eprintln!("Skipping symlink: {}", entry_path.display()),
-                        _ => eprintln!("Skipping dangling symlink: {}", entry_path.display()),
-                    }
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let entry_relative = if relative.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{relative}/{name}")
+                };
+
+                if self.excludes.ignore_files
+                    && entry_metadata.is_file()
+                    && name == IGNORE_FILE_NAME
+                {
+                    continue;
+                }
+                if self
+                    .ignores
+                    .is_ignored(&entry_relative, entry_metadata.is_dir())
+                {
+                    self.found.ignored += 1;
+                } else if entry_metadata.file_type().is_symlink() {
+                    self.progress.emit(Event::SkippedSymlink {
+                        path: &entry_path,
+                        dangling: !matches!(entry_path.try_exists(), Ok(true)),
+                    });
                 } else if entry_metadata.is_file() {
-                    let path_str = entry_path.to_str().ok_or_else(|| {
-                        anyhow::anyhow!("Invalid path encoding: {}", entry_path.display())
-                    })?;
-                    validate_filename_ascii(path_str)
-                        .with_context(|| format!("Invalid path: {}", entry_path.display()))?;
-                    out.push(entry_path);
-                } else if entry_metadata.is_dir() {
-                    collect_files_inner(&entry_path, out)?;
+                    self.add_file(&entry_path)?;
+                } else if entry_metadata.is_dir() && self.recursive {
+                    self.visit_dir(&entry_path, &entry_relative)?;
                 }
             }
-        }
 
-        Ok(())
+            if has_ignore_file {
+                self.ignores.pop();
+            }
+            Ok(())
+        }
     }
 
     /// Create all parent directories for a file path
@@ -456,35 +2796,376 @@ pub mod utils {
         Ok(())
     }
 
-    /// Convert internal backslash paths to OS-native format for display.
-    /// On Unix this converts `\` to `/`; on Windows it's a no-op.
-    pub fn normalize_path_for_display(path: &str) -> String {
-        #[cfg(windows)]
-        {
-            path.to_string()
+    /// Write a file so that `path` holds either its old contents or the
+    /// complete new ones, never a partial write.
+    ///
+    /// `write` fills a temporary file next to `path` (`NAME.tmp-PID-N`),
+    /// which is synced to disk and renamed over `path`. On any error the
+    /// temporary file is removed and `path` is left as it was.
+    pub fn write_atomically(
+        path: &Path,
+        write: impl FnOnce(&mut fs::File) -> Result<()>,
+    ) -> Result<()> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("Not a file path: {}", path.display()))?;
+        let mut tmp_name = file_name.to_os_string();
+        tmp_name.push(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let result = (|| {
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+                .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+            write(&mut file)?;
+            file.sync_all()
+                .with_context(|| format!("Failed to sync {}", tmp_path.display()))?;
+            drop(file);
+            // Keep the permissions of the file being replaced
+            if let Ok(metadata) = fs::metadata(path) {
+                fs::set_permissions(&tmp_path, metadata.permissions())?;
+            }
+            fs::rename(&tmp_path, path)
+                .with_context(|| format!("Failed to replace {}", path.display()))
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
         }
-        #[cfg(not(windows))]
-        {
-            path.replace('\\', "/")
+        result
+    }
+
+    /// `path` with `suffix` added to its file name: `master.dat` -> `master.dat.bak`
+    pub fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        path.with_file_name(name)
+    }
+
+    /// Run `save`, which overwrites `path`, keeping a copy of the file as it
+    /// was before at `backup_path(path, suffix)`.
+    ///
+    /// The copy is made first, under a temporary name, and only replaces an
+    /// existing backup once `save` succeeded; if it fails, the old backup is
+    /// kept and the copy removed.
+    pub fn save_with_backup<T>(
+        path: &Path,
+        suffix: &str,
+        save: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let backup = backup_path(path, suffix);
+        let mut copy = backup.clone().into_os_string();
+        copy.push(format!(".tmp-{}", std::process::id()));
+        let copy = PathBuf::from(copy);
+
+        fs::copy(path, &copy).with_context(|| {
+            format!("Failed to back up {} to {}", path.display(), copy.display())
+        })?;
+        let result = save().and_then(|saved| {
+            fs::rename(&copy, &backup)
+                .with_context(|| format!("Failed to write backup {}", backup.display()))?;
+            Ok(saved)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&copy);
+        }
+        result
+    }
+
+    // ── Entry paths ────────────────────────────────────────────────
+
+    /// Canonical form of an archive entry path, used to match, sort and show entries.
+    ///
+    /// Both slashes become `/`, repeated separators collapse into one and
+    /// leading separators are dropped; case is preserved. User input and
+    /// stored names (which use `\`) have the same canonical form:
+    /// `\\Art//Critters\\HmJmpSaa.frm` -> `Art/Critters/HmJmpSaa.frm`.
+    pub fn canonical_entry_path(path: &str) -> String {
+        let mut result = String::with_capacity(path.len());
+        // Starting as if after a separator drops leading ones
+        let mut last_was_separator = true;
+        for ch in path.chars() {
+            if ch == '/' || ch == '\\' {
+                if !last_was_separator {
+                    result.push('/');
+                }
+                last_was_separator = true;
+            } else {
+                result.push(ch);
+                last_was_separator = false;
+            }
+        }
+        result
+    }
+
+    /// `name` without its leading directory `prefix`, both compared like
+    /// entry paths, with backslashes. None unless `name` is under `prefix`.
+    pub fn strip_entry_prefix(name: &str, prefix: &str) -> Option<String> {
+        let name = canonical_entry_path(name);
+        let prefix = canonical_entry_path(prefix);
+        let prefix = prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            return Some(name.replace('/', "\\"));
+        }
+
+        let mut components = name.split('/');
+        let head: Vec<&str> = components
+            .by_ref()
+            .take(prefix.split('/').count())
+            .collect();
+        if entry_path_key(&head.join("/")) != entry_path_key(prefix) {
+            return None;
         }
+        let rest: Vec<&str> = components.collect();
+        (!rest.is_empty()).then(|| rest.join("\\"))
+    }
+
+    /// `name` without its first `count` components, with backslashes. None
+    /// if nothing is left.
+    pub fn strip_entry_components(name: &str, count: usize) -> Option<String> {
+        let name = canonical_entry_path(name);
+        let rest: Vec<&str> = name
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .skip(count)
+            .collect();
+        (!rest.is_empty()).then(|| rest.join("\\"))
+    }
+
+    /// `canonical_entry_path` with the backslashes archives store names with
+    pub fn stored_entry_path(path: &str) -> String {
+        canonical_entry_path(path).replace('/', "\\")
+    }
+
+    /// Whether `path` is already its own `stored_entry_path`: no `/`, and no
+    /// leading or repeated `\`
+    pub fn is_stored_entry_path(path: &str) -> bool {
+        !path.starts_with('\\') && !path.contains('/') && !path.contains("\\\\")
+    }
+
+    /// Compare entry paths the way the game and the original tools do.
+    ///
+    /// Paths compare in canonical form, with ASCII letters folded to
+    /// lowercase and separators as the stored `\`, byte-wise (like MSVC
+    /// `_stricmp` on stored names). So `art/a.frm` equals `ART\\A.FRM`, and
+    /// "a\\b.txt" < "a_b.txt" < "ab.txt" because `\` (0x5C) and `_` (0x5F)
+    /// sort before letters. Other characters compare by the Windows-1252
+    /// byte they're stored as, ones it lacks after all of those.
+    pub fn compare_entry_paths(a: &str, b: &str) -> std::cmp::Ordering {
+        folded_entry_bytes(a).cmp(folded_entry_bytes(b))
+    }
+
+    /// Key that is equal for two paths exactly when `compare_entry_paths`
+    /// says they are, for hashing and map lookups
+    pub fn entry_path_key(path: &str) -> String {
+        canonical_entry_path(path).to_ascii_lowercase()
+    }
+
+    /// Stored bytes `compare_entry_paths` compares: canonical, case-folded,
+    /// `\` separators, and characters Windows-1252 lacks past every byte
+    fn folded_entry_bytes(path: &str) -> impl Iterator<Item = u32> + '_ {
+        let mut last_was_separator = true;
+        path.chars().filter_map(move |c| {
+            let is_separator = c == '/' || c == '\\';
+            let repeated = is_separator && last_was_separator;
+            last_was_separator = is_separator;
+            if repeated {
+                None
+            } else if is_separator {
+                Some(u32::from(b'\\'))
+            } else {
+                let c = c.to_ascii_lowercase();
+                Some(crate::cp1252::encode_char(c).map_or(0x100 + u32::from(c), u32::from))
+            }
+        })
+    }
+
+    /// Parse a human-readable size such as "512M", "2G", "64k" or "1000".
+    /// Suffixes are binary (1K = 1024 bytes) and case-insensitive.
+    pub fn parse_size(text: &str) -> Result<u64> {
+        let text = text.trim();
+        let (number, multiplier) = match text.char_indices().last() {
+            Some((idx, c)) if c.is_ascii_alphabetic() => {
+                let multiplier = match c.to_ascii_uppercase() {
+                    'K' => 1u64 << 10,
+                    'M' => 1 << 20,
+                    'G' => 1 << 30,
+                    'T' => 1 << 40,
+                    _ => bail!("Invalid size suffix in '{}' (use K, M, G or T)", text),
+                };
+                (&text[..idx], multiplier)
+            }
+            _ => (text, 1),
+        };
+
+        let number: u64 = number
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid size: '{text}'"))?;
+        number
+            .checked_mul(multiplier)
+            .with_context(|| format!("Size too large: '{text}'"))
     }
 
-    /// Normalize user input path to internal backslash format.
-    /// Uses `Cow` to avoid allocation when the path already uses backslashes.
-    pub fn normalize_user_path(path: &str) -> Cow<'_, str> {
-        if path.contains('/') {
-            Cow::Owned(path.replace('/', "\\"))
+    /// Parse a time such as "2024-06-01", "2024-06-01T12:30" or
+    /// "2024-06-01 12:30:45Z" (a space works in place of the `T`), in UTC
+    /// unless it ends in an RFC 3339 offset like "+02:00". Fractions of a
+    /// second are dropped. Times before 1970 aren't accepted.
+    pub fn parse_timestamp(text: &str) -> Result<SystemTime> {
+        let invalid = || {
+            anyhow::anyhow!("Invalid time '{text}' (use YYYY-MM-DD, optionally with THH:MM[:SS])")
+        };
+        let trimmed = text.trim();
+        let trimmed = trimmed.strip_suffix(['Z', 'z']).unwrap_or(trimmed);
+        let (date, time) = match trimmed.split_once(['T', 't', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (trimmed, None),
+        };
+        let number = |part: &str, digits: usize| -> Result<u64> {
+            if part.len() != digits || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        };
+
+        let date: Vec<&str> = date.split('-').collect();
+        let [year, month, day] = date[..] else {
+            return Err(invalid());
+        };
+        let (year, month, day) = (number(year, 4)?, number(month, 2)?, number(day, 2)?);
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let month_days = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return Err(invalid()),
+        };
+        if year < 1970 || !(1..=month_days).contains(&day) {
+            return Err(invalid());
+        }
+
+        let (mut hour, mut minute, mut second, mut offset) = (0, 0, 0, 0);
+        if let Some(time) = time {
+            // Seconds east of UTC, from a trailing "+HH:MM" or "-HH:MM"
+            let time = match time.rfind(['+', '-']) {
+                Some(sign) => {
+                    let (offset_hours, offset_minutes) =
+                        time[sign + 1..].split_once(':').ok_or_else(invalid)?;
+                    let (offset_hours, offset_minutes) =
+                        (number(offset_hours, 2)?, number(offset_minutes, 2)?);
+                    if offset_hours > 23 || offset_minutes > 59 {
+                        return Err(invalid());
+                    }
+                    offset = (offset_hours * 3600 + offset_minutes * 60) as i64;
+                    if time.as_bytes()[sign] == b'-' {
+                        offset = -offset;
+                    }
+                    &time[..sign]
+                }
+                None => time,
+            };
+            let time: Vec<&str> = time.split(':').collect();
+            let (hours, minutes, seconds) = match time[..] {
+                [hour, minute] => (hour, minute, "00"),
+                [hour, minute, second] => (hour, minute, second),
+                _ => return Err(invalid()),
+            };
+            let seconds = match seconds.split_once('.') {
+                Some((whole, fraction)) => {
+                    if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                        return Err(invalid());
+                    }
+                    whole
+                }
+                None => seconds,
+            };
+            (hour, minute, second) = (number(hours, 2)?, number(minutes, 2)?, number(seconds, 2)?);
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+
+        // Days since 1970-01-01 in the proleptic Gregorian calendar, counting
+        // years from March so that leap days come last
+        let (y, m) = if month <= 2 {
+            (year - 1, month + 9)
         } else {
-            Cow::Borrowed(path)
+            (year, month - 3)
+        };
+        let era = y / 400;
+        let year_of_era = y % 400;
+        let day_of_year = (153 * m + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds = (days * 86_400 + hour * 3600 + minute * 60 + second) as i64 - offset;
+        let seconds = u64::try_from(seconds).map_err(|_| invalid())?;
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    /// Return the parts of `region` not covered by any of `ranges`.
+    /// Ranges may overlap, be unsorted or extend past the region.
+    pub fn unreferenced_ranges(
+        region: Range<u64>,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Vec<Range<u64>> {
+        let mut ranges: Vec<Range<u64>> = ranges
+            .into_iter()
+            .filter(|range| range.start < range.end)
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut gaps = Vec::new();
+        let mut covered_to = region.start;
+        for range in ranges {
+            if range.start > covered_to && covered_to < region.end {
+                gaps.push(covered_to..range.start.min(region.end));
+            }
+            covered_to = covered_to.max(range.end);
+        }
+        if covered_to < region.end {
+            gaps.push(covered_to..region.end);
         }
+
+        gaps
     }
 
-    /// Normalize a batch of user patterns to internal backslash format
-    pub fn normalize_user_patterns(patterns: &[String]) -> Vec<String> {
-        patterns
-            .iter()
-            .map(|p| normalize_user_path(p).into_owned())
-            .collect()
+    /// Split `outer.dat::inner/a.dat::b.dat` into the on-disk path and the
+    /// chain of archive paths inside it (as stored, with backslashes).
+    pub fn split_nested_archive_path(spec: &Path) -> Result<(PathBuf, Vec<String>)> {
+        let spec_str = spec.to_string_lossy();
+        let mut parts = spec_str.split(NESTED_ARCHIVE_SEPARATOR);
+        let outer = parts.next().unwrap_or_default();
+        let inner: Vec<String> = parts.map(stored_entry_path).collect();
+
+        if outer.is_empty() || inner.iter().any(|name| name.is_empty()) {
+            bail!("Invalid nested archive path: {}", spec.display());
+        }
+        if inner.len() > MAX_NESTING_DEPTH {
+            bail!(
+                "Archives nested more than {} levels deep are not supported: {}",
+                MAX_NESTING_DEPTH,
+                spec.display()
+            );
+        }
+
+        Ok((PathBuf::from(outer), inner))
+    }
+
+    /// Reject nested archive paths for commands that write the archive
+    pub fn reject_nested_archive_path(path: &Path) -> Result<()> {
+        if path.to_string_lossy().contains(NESTED_ARCHIVE_SEPARATOR) {
+            bail!("Archives inside archives are read-only: {}", path.display());
+        }
+        Ok(())
     }
 
     /// Check if a string contains glob metacharacters (*, ?, [)
@@ -497,30 +3178,44 @@ pub mod utils {
     /// If the pattern contains glob metacharacters, uses glob matching.
     /// Otherwise uses substring matching for backward compatibility.
     /// Patterns without path separators match against just the filename portion.
+    /// Both are compared by `entry_path_key`, so either slash works and case is ignored.
     pub fn matches_pattern(file_name: &str, pattern: &str) -> bool {
-        if contains_glob_metacharacters(pattern) {
-            // Normalize both to forward slashes for glob matching
-            let normalized_name = file_name.replace('\\', "/");
-            let normalized_pattern = pattern.replace('\\', "/");
+        let name = entry_path_key(file_name);
+        let pattern_key = entry_path_key(pattern);
 
+        if contains_glob_metacharacters(pattern) {
             // If pattern has no path separator, match against filename only
-            let (name_to_match, pattern_to_use) = if !normalized_pattern.contains('/') {
-                let filename = normalized_name
-                    .rsplit('/')
-                    .next()
-                    .unwrap_or(&normalized_name);
-                (filename.to_string(), normalized_pattern)
+            let name_to_match = if pattern_key.contains('/') {
+                name.as_str()
             } else {
-                (normalized_name, normalized_pattern)
+                get_filename_from_dat_path(&name)
             };
 
-            match glob::Pattern::new(&pattern_to_use) {
-                Ok(glob_pattern) => glob_pattern.matches(&name_to_match),
+            match glob::Pattern::new(&pattern_key) {
+                Ok(glob_pattern) => glob_pattern.matches(name_to_match),
                 // Invalid glob pattern: fall back to substring matching
-                Err(_) => file_name.contains(pattern),
+                Err(_) => name.contains(&pattern_key),
             }
         } else {
-            file_name.contains(pattern)
+            name.contains(&pattern_key)
+        }
+    }
+
+    /// Whether `pattern` names a directory, by ending in a slash
+    pub fn is_directory_prefix(pattern: &str) -> bool {
+        pattern.ends_with(['/', '\\'])
+    }
+
+    /// Whether `d` deletes the entry `file_name` for `pattern`: globs match as
+    /// in `matches_pattern`, a directory prefix everything under it, and
+    /// plain names only the same path
+    pub fn matches_delete_pattern(file_name: &str, pattern: &str) -> bool {
+        if contains_glob_metacharacters(pattern) {
+            matches_pattern(file_name, pattern)
+        } else if is_directory_prefix(pattern) {
+            matches_path_prefix(file_name, pattern)
+        } else {
+            compare_entry_paths(file_name, pattern).is_eq()
         }
     }
 
@@ -533,62 +3228,264 @@ pub mod utils {
             .replace('\x00', "\\") // Restore escaped backslashes
     }
 
+    /// Marks a response file argument; doubled, a literal leading `@`
+    const RESPONSE_FILE_PREFIX: char = '@';
+
+    /// Separates source and archive path in add response file lines
+    const MAPPING_ARROW: &str = "=>";
+
+    /// A parsed response file line
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ResponseLine {
+        /// Name or pattern the line gives
+        pub name: String,
+        /// Whether `name` was quoted: taken as it is, never as `@file`
+        pub quoted: bool,
+        /// Archive path after `=>`, for `a`
+        pub dest: Option<String>,
+    }
+
+    /// Parse one line of a response file, `None` if it's blank or a comment.
+    ///
+    /// A line is `entry [=> entry] [# comment]`. An entry is either
+    /// `"quoted text"`, kept exactly, or bare text without its surrounding
+    /// whitespace. `#` starts a comment at the start of the line or after
+    /// whitespace, outside quotes.
+    pub fn parse_response_line(line: &str) -> Result<Option<ResponseLine>> {
+        let parts = split_response_line(line)?;
+        let mut entries = parts
+            .iter()
+            .map(|part| parse_response_entry(part))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+        match (entries.next(), entries.next(), entries.next()) {
+            (Some(None), None, _) => Ok(None),
+            (Some(Some((name, quoted))), None, _) => Ok(Some(ResponseLine {
+                name,
+                quoted,
+                dest: None,
+            })),
+            (Some(Some((name, quoted))), Some(Some((dest, _))), None) => Ok(Some(ResponseLine {
+                name,
+                quoted,
+                dest: Some(dest),
+            })),
+            (_, _, Some(_)) => bail!("Expected at most one '{MAPPING_ARROW}'"),
+            _ => bail!("Expected 'source {MAPPING_ARROW} archive path'"),
+        }
+    }
+
+    /// Split a line at each `=>` and drop its comment, outside quotes
+    fn split_response_line(line: &str) -> Result<Vec<&str>> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut end = line.len();
+        let mut in_quote = false;
+        let mut after_space = true;
+        let mut chars = line.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => in_quote = !in_quote,
+                '#' if !in_quote && after_space => {
+                    end = i;
+                    break;
+                }
+                '=' if !in_quote && line[i..].starts_with(MAPPING_ARROW) => {
+                    parts.push(&line[start..i]);
+                    start = i + MAPPING_ARROW.len();
+                    chars.next();
+                }
+                _ => {}
+            }
+            after_space = c.is_whitespace();
+        }
+        if in_quote {
+            bail!("Unterminated quote");
+        }
+        parts.push(&line[start..end]);
+        Ok(parts)
+    }
+
+    /// A name and whether it was quoted, `None` if `part` is blank
+    fn parse_response_entry(part: &str) -> Result<Option<(String, bool)>> {
+        let part = part.trim();
+        if part.is_empty() {
+            return Ok(None);
+        }
+        if let Some(inner) = part
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .filter(|inner| !inner.contains('"'))
+        {
+            return Ok(Some((inner.to_string(), true)));
+        }
+        if part.contains('"') {
+            bail!("Quotes must surround a whole name");
+        }
+        Ok(Some((part.to_string(), false)))
+    }
+
+    /// One file argument, from the command line or a line of a response file
+    struct Argument {
+        text: String,
+        /// Archive path from a `source => dest` line
+        dest: Option<String>,
+        /// The response file and line the argument was read from
+        origin: Option<(String, String)>,
+    }
+
+    impl Argument {
+        fn invalid_line(&self) -> String {
+            match &self.origin {
+                Some((file, line)) => format!("Invalid line in response file {file}: {line}"),
+                None => format!("Invalid argument: {}", self.text),
+            }
+        }
+    }
+
+    /// Replace every `@file` argument with the lines of that file, in place.
+    ///
+    /// Response files may list further `@file`s, read relative to the
+    /// current directory like those on the command line. `@@name` is the
+    /// literal name `@name`.
+    fn expand_arguments(files: &[String]) -> Result<Vec<Argument>> {
+        let mut arguments = Vec::new();
+        let mut open_files = Vec::new();
+        for file in files {
+            match file.strip_prefix(RESPONSE_FILE_PREFIX) {
+                Some(rest) if !rest.starts_with(RESPONSE_FILE_PREFIX) => {
+                    read_response_file(rest, &mut open_files, &mut arguments)?
+                }
+                escaped => arguments.push(Argument {
+                    text: escaped.unwrap_or(file).to_string(),
+                    dest: None,
+                    origin: None,
+                }),
+            }
+        }
+        Ok(arguments)
+    }
+
+    fn read_response_file(
+        path: &str,
+        open_files: &mut Vec<PathBuf>,
+        arguments: &mut Vec<Argument>,
+    ) -> Result<()> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read response file: {path}"))?;
+        let identity = fs::canonicalize(path)
+            .with_context(|| format!("Failed to read response file: {path}"))?;
+        if open_files.contains(&identity) {
+            bail!("Response file {path} includes itself");
+        }
+        open_files.push(identity);
+        for line in content.lines() {
+            let invalid_line = || format!("Invalid line in response file {path}: {}", line.trim());
+            let Some(parsed) = parse_response_line(line).with_context(invalid_line)? else {
+                continue;
+            };
+            let name = match parsed.name.strip_prefix(RESPONSE_FILE_PREFIX) {
+                Some(rest) if !parsed.quoted && !rest.starts_with(RESPONSE_FILE_PREFIX) => {
+                    if parsed.dest.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "A response file can't have an archive path"
+                        ))
+                        .with_context(invalid_line);
+                    }
+                    read_response_file(rest, open_files, arguments)
+                        .with_context(|| format!("In response file {path}"))?;
+                    continue;
+                }
+                Some(rest) if !parsed.quoted => rest.to_string(),
+                _ => parsed.name,
+            };
+            arguments.push(Argument {
+                text: name,
+                dest: parsed.dest,
+                origin: Some((path.to_string(), line.trim().to_string())),
+            });
+        }
+        open_files.pop();
+        Ok(())
+    }
+
     /// Expand @response-file syntax, returning patterns as-is for archive matching.
     ///
     /// Does NOT expand glob patterns on the filesystem - used for
     /// list/extract/delete commands where patterns match archive entries.
+    /// Any number of response files can be mixed with other arguments; the
+    /// result keeps their order, without repeats.
     pub fn expand_response_files_for_archive(files: &[String]) -> Result<Vec<String>> {
-        if files.len() == 1 && files[0].starts_with('@') {
-            let response_file_path = &files[0][1..];
-            let content = fs::read_to_string(response_file_path)
-                .with_context(|| format!("Failed to read response file: {response_file_path}"))?;
-
-            return Ok(content
-                .lines()
-                .map(|line| line.trim())
-                .filter(|line| !line.is_empty() && !line.starts_with('#'))
-                .map(String::from)
-                .collect());
+        let mut seen = HashSet::new();
+        let mut patterns = Vec::new();
+        for argument in expand_arguments(files)? {
+            if argument.dest.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Only `a` takes 'source {MAPPING_ARROW} archive path' lines"
+                ))
+                .context(argument.invalid_line());
+            }
+            if seen.insert(argument.text.clone()) {
+                patterns.push(argument.text);
+            }
         }
+        Ok(patterns)
+    }
 
-        if files.iter().any(|f| f.starts_with('@')) {
-            bail!("Cannot mix @response-file with explicit file arguments");
-        }
+    /// A file or directory to add, from the command line or a response file
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct AddOperand {
+        pub source: PathBuf,
+        /// Archive path to store the file under, from a `source => dest`
+        /// response file line
+        pub dest: Option<String>,
+    }
 
-        Ok(files.to_vec())
+    impl From<PathBuf> for AddOperand {
+        fn from(source: PathBuf) -> Self {
+            Self { source, dest: None }
+        }
     }
 
     /// Expand @response-file syntax and glob patterns for add operations.
+    /// Response file lines may be `source => dest` to give a file's archive path.
+    /// As for [`expand_response_files_for_archive`], the result keeps argument
+    /// order without repeats.
     pub fn expand_response_files_with_stripping(
         files: &[String],
         change_dir: Option<&Path>,
-    ) -> Result<Vec<PathBuf>> {
-        if files.len() == 1 && files[0].starts_with('@') {
-            return expand_response_file(&files[0][1..], change_dir);
-        }
-
-        if files.iter().any(|f| f.starts_with('@')) {
-            bail!("Cannot mix @response-file with explicit file arguments");
+    ) -> Result<Vec<AddOperand>> {
+        let mut operands = Vec::new();
+        for argument in expand_arguments(files)? {
+            match &argument.dest {
+                Some(dest) => operands.push(
+                    expand_mapping(&argument.text, dest, change_dir)
+                        .with_context(|| argument.invalid_line())?,
+                ),
+                None => operands.extend(
+                    expand_file_patterns(&[argument.text], change_dir)?
+                        .into_iter()
+                        .map(AddOperand::from),
+                ),
+            }
         }
-
-        expand_file_patterns(files, change_dir)
+        let mut seen = HashSet::new();
+        operands.retain(|operand| seen.insert(operand.clone()));
+        Ok(operands)
     }
 
-    fn expand_response_file(
-        response_file_path: &str,
-        change_dir: Option<&Path>,
-    ) -> Result<Vec<PathBuf>> {
-        let content = fs::read_to_string(response_file_path)
-            .with_context(|| format!("Failed to read response file: {response_file_path}"))?;
-
-        let paths: Vec<String> = content
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty() && !line.starts_with('#'))
-            .map(String::from)
-            .collect();
-
-        expand_file_patterns(&paths, change_dir)
+    /// A `source => dest` line: one file, stored under `dest`
+    fn expand_mapping(source: &str, dest: &str, change_dir: Option<&Path>) -> Result<AddOperand> {
+        if contains_glob_metacharacters(source) {
+            bail!("A source with an archive path must be a single file, not a pattern");
+        }
+        let source = expand_file_patterns(&[source.to_string()], change_dir)?
+            .pop()
+            .context("Empty source")?;
+        Ok(AddOperand {
+            source,
+            dest: Some(dest.to_string()),
+        })
     }
 
     /// Expand glob patterns and join relative patterns against `-C`.
@@ -662,12 +3559,11 @@ pub mod utils {
     /// A malicious archive could contain entries like "../../../etc/passwd"
     /// which would write outside the output directory during extraction.
     pub fn validate_archive_path(path: &str) -> Result<()> {
-        let normalized = path.replace('\\', "/");
-        for component in normalized.split('/') {
+        for component in canonical_entry_path(path).split('/') {
             if component == ".." {
                 bail!(
                     "Path traversal detected in archive entry: {}",
-                    normalize_path_for_display(path)
+                    canonical_entry_path(path)
                 );
             }
         }
@@ -700,7 +3596,7 @@ pub mod utils {
                 _ => {
                     bail!(
                         "Invalid archive path for add operation: {}",
-                        normalize_path_for_display(path)
+                        canonical_entry_path(path)
                     );
                 }
             }
@@ -741,7 +3637,7 @@ pub mod utils {
         if metadata.file_type().is_symlink() {
             bail!(
                 "Symlinks are not allowed in add operations: {}",
-                normalize_path_for_display(&path.display().to_string())
+                canonical_entry_path(&path.display().to_string())
             );
         }
 
@@ -759,18 +3655,13 @@ pub mod utils {
         if !resolved.starts_with(&canonical_base) {
             bail!(
                 "Add path escapes -C directory: {}",
-                normalize_path_for_display(&path.display().to_string())
+                canonical_entry_path(&path.display().to_string())
             );
         }
 
         Ok(resolved)
     }
 
-    /// Convert path to backslashes for DAT archive storage
-    pub fn normalize_path_for_archive(path: &str) -> String {
-        path.replace('/', "\\")
-    }
-
     /// Convert a DAT archive path (backslashes) to the OS path format
     pub fn to_system_path(dat_path: &str) -> PathBuf {
         PathBuf::from(dat_path.replace('\\', std::path::MAIN_SEPARATOR_STR))
@@ -792,20 +3683,109 @@ pub mod utils {
             .unwrap_or(".")
     }
 
-    /// Decode filename bytes from DAT files to ASCII strings.
-    /// Strips C-style null terminators and rejects non-ASCII content.
-    pub fn decode_filename(bytes: &[u8]) -> Result<String> {
-        let trimmed_bytes: Vec<u8> = bytes.iter().take_while(|&&b| b != 0).copied().collect();
+    /// Largest offset or size either DAT format can record: they're all u32
+    pub const MAX_DAT_SIZE: u64 = u32::MAX as u64;
 
-        match std::str::from_utf8(&trimmed_bytes) {
-            Ok(ascii_str) => {
-                validate_filename_ascii(ascii_str)?;
-                Ok(ascii_str.to_string())
-            }
-            Err(_) => {
-                bail!("Invalid filename encoding - not valid UTF-8")
-            }
+    /// Read a file to add to an archive, failing before reading it if it's
+    /// too large to be stored
+    pub fn read_file_to_add(file: &Path) -> Result<Vec<u8>> {
+        let len = fs::metadata(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?
+            .len();
+        if len > MAX_DAT_SIZE {
+            bail!(
+                "{} is {len} bytes, over the 4 GiB DAT limit for a single file",
+                file.display()
+            );
+        }
+        fs::read(file).with_context(|| format!("Failed to read {}", file.display()))
+    }
+
+    /// Copy an entry's content from `content`, a decompressing reader, into
+    /// `out`; it must have the entry's recorded `size`. A read failure is
+    /// `CorruptData`, described by `describe`, and a write failure `Write`.
+    /// Bytes past `size` are counted for the error but not written.
+    pub(crate) fn copy_entry_content<W: Write + ?Sized>(
+        file: &FileEntry,
+        mut content: impl Read,
+        out: &mut W,
+        describe: impl Fn(io::Error) -> io::Error,
+    ) -> DatResult<u64> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0usize;
+        let size = file.size as usize;
+        loop {
+            let read = match content.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(DatError::CorruptData {
+                        name: file.path(),
+                        source: describe(e),
+                    })
+                }
+            };
+            let wanted = read.min(size.saturating_sub(written));
+            out.write_all(&buffer[..wanted])
+                .map_err(|source| DatError::Write {
+                    name: file.path(),
+                    source,
+                })?;
+            written += read;
+        }
+
+        if written != size {
+            return Err(DatError::SizeMismatch {
+                name: file.path(),
+                expected: size,
+                actual: written,
+            });
+        }
+        Ok(written as u64)
+    }
+
+    /// Fail if the data of `entries` plus `overhead` bytes (header, tree and
+    /// footer) would pass the 4 GiB DAT limit, past which offsets wrap
+    pub fn check_archive_size<'a>(
+        entries: impl IntoIterator<Item = &'a FileEntry>,
+        overhead: u64,
+    ) -> Result<()> {
+        let data = entries
+            .into_iter()
+            .map(|entry| entry.packed_size as u64)
+            .sum::<u64>();
+        check_archive_total(data + overhead)
+    }
+
+    /// Fail if an archive of `total` bytes would pass the 4 GiB DAT limit
+    pub fn check_archive_total(total: u64) -> Result<()> {
+        if total > MAX_DAT_SIZE {
+            bail!("Archive would exceed the 4 GiB DAT limit (current total: {total} bytes)");
         }
+        Ok(())
+    }
+
+    /// Decode filename bytes from DAT files, as Windows-1252 (see
+    /// `crate::cp1252`). Strips C-style null terminators.
+    pub fn decode_filename(bytes: &[u8]) -> Result<String> {
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(crate::cp1252::decode(&bytes[..end]))
+    }
+
+    /// Encode a filename for writing to a DAT file, the inverse of
+    /// `decode_filename`, so names read from an archive are saved as the
+    /// bytes they were read as. Fails on characters Windows-1252 lacks,
+    /// which names given to the library directly may have.
+    pub fn encode_filename(filename: &str) -> Result<Vec<u8>> {
+        crate::cp1252::encode(filename).map_err(|c| {
+            anyhow::anyhow!("Filename {filename:?} has a character Windows-1252 lacks: {c:?}")
+        })
+    }
+
+    /// Length in bytes of `filename` once encoded: one per character
+    pub fn encoded_name_len(filename: &str) -> usize {
+        filename.chars().count()
     }
 
     /// Validate that a filename contains only ASCII characters.
@@ -868,7 +3848,7 @@ pub mod utils {
         };
 
         let archive_path = validate_add_archive_path(&archive_path)?;
-        Ok(normalize_path_for_archive(&archive_path))
+        Ok(stored_entry_path(&archive_path))
     }
 
     fn validate_change_dir_operand(path: &Path) -> Result<()> {
@@ -882,7 +3862,7 @@ pub mod utils {
                 _ => {
                     bail!(
                         "Invalid add path with -C: {}",
-                        normalize_path_for_display(&path.display().to_string())
+                        canonical_entry_path(&path.display().to_string())
                     );
                 }
             }
@@ -8,6 +8,7 @@ compression levels, filtering, and path traversal protection.
 #[cfg(test)]
 mod tests {
     use crate::common::*;
+    use crate::test_support::TempDir;
     use std::path::Path;
 
     // ── CompressionLevel ───────────────────────────────────────────
@@ -35,1051 +36,3628 @@ mod tests {
         }
     }
 
-    // ── normalize_path_for_display ─────────────────────────────────
+    // ── CompressionPolicy ──────────────────────────────────────────
 
-    mod normalize_path_for_display {
+    mod compression_policy {
         use super::*;
 
+        fn level() -> CompressionLevel {
+            CompressionLevel::new(9).unwrap()
+        }
+
         #[test]
-        fn converts_backslashes_to_forward_on_unix() {
-            // On non-Windows, backslashes become forward slashes
-            #[cfg(not(windows))]
+        fn default_list_stores_acm_and_mve() {
+            let policy = CompressionPolicy::new(level());
+            assert!(policy.is_store_only(Path::new("sound/music/07desert.acm")));
+            assert!(policy.is_store_only(Path::new("art/cuts/intro.mve")));
+            assert!(!policy.is_store_only(Path::new("text/english/game/combat.msg")));
+        }
+
+        #[test]
+        fn extension_match_is_case_insensitive() {
+            let policy = CompressionPolicy::new(level());
+            assert!(policy.is_store_only(Path::new("SOUND/MUSIC/07DESERT.ACM")));
+        }
+
+        #[test]
+        fn default_list_can_be_disabled() {
+            let policy = CompressionPolicy::without_store_extensions(level());
+            assert!(!policy.is_store_only(Path::new("music.acm")));
+        }
+
+        #[test]
+        fn user_extensions_are_normalized() {
+            let policy = CompressionPolicy::without_store_extensions(level())
+                .with_store_extensions(&["WAV", ".ogg", " "]);
+            assert_eq!(policy.store_extensions, vec!["wav", "ogg"]);
+            assert!(policy.is_store_only(Path::new("sfx.wav")));
+        }
+
+        #[test]
+        fn default_min_gain_keeps_any_saving() {
+            let policy = CompressionPolicy::new(level());
+            assert_eq!(policy.decide(1000, 999), CompressionOutcome::Compressed);
+        }
+
+        #[test]
+        fn no_saving_is_always_stored() {
+            let policy = CompressionPolicy::new(level());
+            assert_eq!(policy.decide(1000, 1000), CompressionOutcome::Stored);
+            assert_eq!(policy.decide(1000, 1200), CompressionOutcome::Stored);
+        }
+
+        #[test]
+        fn min_gain_threshold_is_inclusive() {
+            let policy = CompressionPolicy::new(level()).with_min_gain(10.0).unwrap();
+            assert_eq!(policy.decide(1000, 900), CompressionOutcome::Compressed);
             assert_eq!(
-                utils::normalize_path_for_display("ART\\CRITTERS\\FILE.FRM"),
-                "ART/CRITTERS/FILE.FRM"
+                policy.decide(1000, 901),
+                CompressionOutcome::StoredBelowMinGain
             );
         }
 
         #[test]
-        fn no_separators_unchanged() {
-            assert_eq!(utils::normalize_path_for_display("file.txt"), "file.txt");
+        fn rejects_min_gain_out_of_range() {
+            assert!(CompressionPolicy::new(level()).with_min_gain(-1.0).is_err());
+            assert!(CompressionPolicy::new(level())
+                .with_min_gain(100.5)
+                .is_err());
         }
 
         #[test]
-        fn empty_string() {
-            assert_eq!(utils::normalize_path_for_display(""), "");
+        fn files_without_extension_are_compressed() {
+            let policy = CompressionPolicy::new(level());
+            assert!(!policy.is_store_only(Path::new("art/acm")));
         }
     }
 
-    // ── normalize_path_for_archive ─────────────────────────────────
+    // ── NameCase ───────────────────────────────────────────────────
 
-    mod normalize_path_for_archive {
+    mod name_case {
         use super::*;
+        use std::borrow::Cow;
 
         #[test]
-        fn converts_forward_to_backslashes() {
+        fn folds_ascii_names() {
             assert_eq!(
-                utils::normalize_path_for_archive("art/critters/file.frm"),
-                "art\\critters\\file.frm"
+                NameCase::Upper.fold("art\\Critters\\a.frm"),
+                "ART\\CRITTERS\\A.FRM"
+            );
+            assert_eq!(
+                NameCase::Lower.fold("ART\\Critters\\A.FRM"),
+                "art\\critters\\a.frm"
             );
+            assert_eq!(NameCase::Preserve.fold("Art\\A.frm"), "Art\\A.frm");
         }
 
         #[test]
-        fn backslashes_unchanged() {
-            assert_eq!(
-                utils::normalize_path_for_archive("art\\critters\\file.frm"),
-                "art\\critters\\file.frm"
-            );
+        fn unchanged_names_are_borrowed() {
+            assert!(matches!(
+                NameCase::Upper.fold("ART\\A_1.FRM"),
+                Cow::Borrowed(_)
+            ));
+            assert!(matches!(
+                NameCase::Lower.fold("art\\a_1.frm"),
+                Cow::Borrowed(_)
+            ));
+            assert!(matches!(NameCase::Lower.fold("art\\A.frm"), Cow::Owned(_)));
         }
 
         #[test]
-        fn no_separators_unchanged() {
-            assert_eq!(utils::normalize_path_for_archive("file.txt"), "file.txt");
+        fn detects_collisions_after_folding() {
+            let names = ["ART\\A.FRM", "art\\a.frm", "art\\b.frm"];
+            assert!(NameCase::Lower.check_collisions(names).is_err());
+            assert!(NameCase::Preserve.check_collisions(names).is_ok());
+            assert!(NameCase::Upper.check_collisions(["a.txt", "b.txt"]).is_ok());
+        }
+
+        #[test]
+        fn add_options_report_folding() {
+            let mut options =
+                AddOptions::new(CompressionPolicy::new(CompressionLevel::new(1).unwrap()));
+            options.case = NameCase::Upper;
+            let file = Path::new("patch000").join("file.txt");
+            let (name, folded) = options.archive_path(&file, &file).unwrap();
+            assert_eq!(name, "PATCH000\\FILE.TXT");
+            assert!(folded);
+
+            options.case = NameCase::Preserve;
+            let (name, folded) = options.archive_path(&file, &file).unwrap();
+            assert_eq!(name, "patch000\\file.txt");
+            assert!(!folded);
+        }
+
+        #[test]
+        fn add_options_detect_case_collisions() {
+            let mut options =
+                AddOptions::new(CompressionPolicy::new(CompressionLevel::new(1).unwrap()));
+            let mixed = Path::new("mod").join("Art").join("A.FRM");
+            let lower = Path::new("mod").join("art").join("a.frm");
+            let base = Path::new("mod");
+            let pairs = [(mixed.as_path(), base), (lower.as_path(), base)];
+
+            // Without folding the two paths stay apart
+            assert!(options.check_case_collisions(pairs).is_ok());
+
+            options.case = NameCase::Lower;
+            let err = options.check_case_collisions(pairs).unwrap_err();
+            assert!(err.to_string().contains("would merge"), "{err}");
+
+            // The same file given twice isn't a collision
+            let twice = [(lower.as_path(), base), (lower.as_path(), base)];
+            assert!(options.check_case_collisions(twice).is_ok());
+        }
+
+        #[test]
+        fn add_options_use_explicit_archive_path() {
+            let mut options =
+                AddOptions::new(CompressionPolicy::new(CompressionLevel::new(1).unwrap()));
+            options.target_dir = Some("ignored".into());
+            options.case = NameCase::Lower;
+            options.dest = Some("./Scripts/GL_Highres.int".into());
+            let file = Path::new("build").join("obj").join("gl_highres.int");
+            let (name, folded) = options.archive_path(&file, &file).unwrap();
+            assert_eq!(name, "scripts\\gl_highres.int");
+            assert!(folded);
+
+            // Only a single file can be stored under one archive path
+            let err = options.archive_path(&file, Path::new("build")).unwrap_err();
+            assert!(err.to_string().contains("single file"), "{err}");
+
+            options.dest = Some("../escape.int".into());
+            assert!(options.archive_path(&file, &file).is_err());
         }
     }
 
-    // ── normalize_user_path ────────────────────────────────────────
+    // ── parse_size ─────────────────────────────────────────────────
 
-    mod normalize_user_path {
+    mod parse_size {
         use super::*;
 
         #[test]
-        fn converts_forward_slashes_to_backslashes() {
-            let result = utils::normalize_user_path("art/critters/file.frm");
-            assert_eq!(result.as_ref(), "art\\critters\\file.frm");
+        fn plain_bytes() {
+            assert_eq!(utils::parse_size("1000").unwrap(), 1000);
         }
 
         #[test]
-        fn borrows_when_no_conversion_needed() {
-            let result = utils::normalize_user_path("art\\critters\\file.frm");
-            // Should be Cow::Borrowed (no allocation)
-            assert!(matches!(result, std::borrow::Cow::Borrowed(_)));
+        fn binary_suffixes() {
+            assert_eq!(utils::parse_size("64k").unwrap(), 64 * 1024);
+            assert_eq!(utils::parse_size("512M").unwrap(), 512 << 20);
+            assert_eq!(utils::parse_size("2G").unwrap(), 2 << 30);
         }
 
         #[test]
-        fn allocates_when_conversion_needed() {
-            let result = utils::normalize_user_path("art/critters/file.frm");
-            assert!(matches!(result, std::borrow::Cow::Owned(_)));
+        fn rejects_garbage() {
+            assert!(utils::parse_size("").is_err());
+            assert!(utils::parse_size("12X").is_err());
+            assert!(utils::parse_size("M").is_err());
+            assert!(utils::parse_size("-1").is_err());
         }
     }
 
-    // ── decode_filename ────────────────────────────────────────────
+    // ── parse_timestamp ────────────────────────────────────────────
 
-    mod decode_filename {
+    mod parse_timestamp {
         use super::*;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        fn seconds(text: &str) -> u64 {
+            utils::parse_timestamp(text)
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        }
 
         #[test]
-        fn valid_ascii() {
-            assert_eq!(
-                utils::decode_filename(b"CRITTERS.LST").unwrap(),
-                "CRITTERS.LST"
-            );
+        fn dates_are_midnight_utc() {
+            assert_eq!(seconds("1970-01-01"), 0);
+            assert_eq!(seconds("2000-03-01"), 951868800);
+            assert_eq!(seconds("2024-02-29"), 1709164800);
         }
 
         #[test]
-        fn strips_null_bytes() {
+        fn times_with_or_without_seconds() {
+            assert_eq!(seconds("2024-06-01T00:00:00"), 1717200000);
             assert_eq!(
-                utils::decode_filename(b"FILE.TXT\0\0\0").unwrap(),
-                "FILE.TXT"
+                seconds("2024-06-01 12:30"),
+                1717200000 + 12 * 3600 + 30 * 60
+            );
+            assert_eq!(seconds("2024-06-01T12:30:15Z"), 1717245015);
+            assert_eq!(seconds("2024-06-01T12:30:15.250Z"), 1717245015);
+            assert_eq!(
+                utils::parse_timestamp("1970-01-02").unwrap(),
+                UNIX_EPOCH + Duration::from_secs(86400)
             );
         }
 
         #[test]
-        fn rejects_non_ascii() {
-            // UTF-8 encoded e-acute: 0xC3 0xA9
-            assert!(utils::decode_filename(&[0xC3, 0xA9]).is_err());
+        fn rfc3339_offsets() {
+            assert_eq!(seconds("2024-06-01T14:30:15+02:00"), 1717245015);
+            assert_eq!(seconds("2024-06-01T07:00:15-05:30"), 1717245015);
+            assert_eq!(seconds("1970-01-01T01:00:00+01:00"), 0);
+            assert!(utils::parse_timestamp("1970-01-01T00:00:00+01:00").is_err());
         }
 
         #[test]
-        fn empty_input() {
-            assert_eq!(utils::decode_filename(b"").unwrap(), "");
+        fn rejects_garbage() {
+            for text in [
+                "",
+                "yesterday",
+                "2024-06-01T12:00+2",
+                "2024-06-01T12:00:00.",
+                "2024-6-1",
+                "2023-02-29",
+                "2024-13-01",
+                "1969-12-31",
+                "2024-06-01T24:00",
+                "2024-06-01T12",
+            ] {
+                let err = utils::parse_timestamp(text).unwrap_err();
+                assert!(err.to_string().contains("Invalid time"), "{text}: {err}");
+            }
         }
+    }
 
-        #[test]
-        fn null_only_input() {
-            assert_eq!(utils::decode_filename(b"\0\0").unwrap(), "");
+    // ── MemoryBudget ───────────────────────────────────────────────
+
+    mod memory_budget {
+        use super::*;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        #[test]
+        fn guards_return_their_bytes_on_drop() {
+            let budget = MemoryBudget::new(100);
+            let first = budget.acquire(60);
+            let second = budget.acquire(40);
+            assert_eq!(budget.available(), 0);
+            drop(first);
+            assert_eq!(budget.available(), 60);
+            drop(second);
+            assert_eq!(budget.available(), 100);
+        }
+
+        #[test]
+        fn requests_over_the_budget_take_all_of_it() {
+            let budget = MemoryBudget::new(100);
+            let guard = budget.acquire(1000);
+            assert_eq!(budget.available(), 0);
+            drop(guard);
+            assert_eq!(budget.available(), 100);
+        }
+
+        #[test]
+        fn holders_never_exceed_the_budget() {
+            let budget = MemoryBudget::new(100);
+            let held = AtomicU64::new(0);
+            let peak = AtomicU64::new(0);
+
+            std::thread::scope(|scope| {
+                for i in 0..8u64 {
+                    let (budget, held, peak) = (&budget, &held, &peak);
+                    scope.spawn(move || {
+                        for _ in 0..50 {
+                            let bytes = 30 + i * 5;
+                            let _guard = budget.acquire(bytes);
+                            let now = held.fetch_add(bytes, Ordering::SeqCst) + bytes;
+                            peak.fetch_max(now, Ordering::SeqCst);
+                            std::thread::yield_now();
+                            held.fetch_sub(bytes, Ordering::SeqCst);
+                        }
+                    });
+                }
+            });
+
+            assert!(peak.load(Ordering::SeqCst) <= 100);
+            assert_eq!(budget.available(), 100);
         }
     }
 
-    // ── validate_filename_ascii ────────────────────────────────────
+    // ── unreferenced_ranges ────────────────────────────────────────
 
-    mod validate_filename_ascii {
+    mod unreferenced_ranges {
         use super::*;
 
         #[test]
-        fn accepts_ascii() {
-            assert!(utils::validate_filename_ascii("hello.txt").is_ok());
+        fn fully_covered_region_has_no_gaps() {
+            let gaps = utils::unreferenced_ranges(0..30, [10..30, 0..10]);
+            assert!(gaps.is_empty());
         }
 
         #[test]
-        fn rejects_unicode() {
-            assert!(utils::validate_filename_ascii("héllo.txt").is_err());
+        fn finds_leading_middle_and_trailing_gaps() {
+            let gaps = utils::unreferenced_ranges(0..100, [10..20, 40..50]);
+            assert_eq!(gaps, vec![0..10, 20..40, 50..100]);
         }
 
         #[test]
-        fn accepts_empty() {
-            assert!(utils::validate_filename_ascii("").is_ok());
+        fn overlapping_and_shared_ranges_are_merged() {
+            let gaps = utils::unreferenced_ranges(0..100, [0..30, 10..20, 0..30, 25..60]);
+            assert_eq!(gaps, vec![60..100]);
+        }
+
+        #[test]
+        fn ranges_outside_region_are_clipped() {
+            let gaps = utils::unreferenced_ranges(16..100, [0..20, 90..200]);
+            assert_eq!(gaps, vec![20..90]);
+        }
+
+        #[test]
+        fn empty_entries_reference_nothing() {
+            let gaps = utils::unreferenced_ranges(0..10, [5..5, 7..7]);
+            assert_eq!(gaps, vec![0..10]);
+        }
+
+        #[test]
+        fn space_report_totals() {
+            let report = SpaceReport::analyze(120, 0..100, [10..20, 40..50]);
+            assert_eq!(report.unreferenced(), 80);
+            assert_eq!(report.referenced(), 20);
         }
     }
 
-    // ── get_filename_from_dat_path ─────────────────────────────────
+    // ── split_nested_archive_path ──────────────────────────────────
 
-    mod get_filename_from_dat_path {
+    mod split_nested_archive_path {
         use super::*;
 
         #[test]
-        fn extracts_from_backslash_path() {
-            assert_eq!(
-                utils::get_filename_from_dat_path("ART\\CRITTERS\\FILE.FRM"),
-                "FILE.FRM"
-            );
+        fn plain_path_has_no_inner_archives() {
+            let (outer, inner) = utils::split_nested_archive_path(Path::new("master.dat")).unwrap();
+            assert_eq!(outer, Path::new("master.dat"));
+            assert!(inner.is_empty());
         }
 
         #[test]
-        fn extracts_from_forward_slash_path() {
-            assert_eq!(
-                utils::get_filename_from_dat_path("art/critters/file.frm"),
-                "file.frm"
-            );
+        fn splits_and_normalizes_inner_paths() {
+            let (outer, inner) =
+                utils::split_nested_archive_path(Path::new("dir/outer.dat::mods/a.dat::b.dat"))
+                    .unwrap();
+            assert_eq!(outer, Path::new("dir/outer.dat"));
+            assert_eq!(inner, vec!["mods\\a.dat", "b.dat"]);
         }
 
         #[test]
-        fn no_separators_returns_whole_string() {
-            assert_eq!(utils::get_filename_from_dat_path("file.frm"), "file.frm");
+        fn rejects_empty_components() {
+            assert!(utils::split_nested_archive_path(Path::new("::inner.dat")).is_err());
+            assert!(utils::split_nested_archive_path(Path::new("outer.dat::")).is_err());
+            assert!(utils::split_nested_archive_path(Path::new("a.dat::::b.dat")).is_err());
+        }
+
+        #[test]
+        fn enforces_depth_limit() {
+            let at_limit = format!("a.dat{}", "::x.dat".repeat(MAX_NESTING_DEPTH));
+            assert!(utils::split_nested_archive_path(Path::new(&at_limit)).is_ok());
+            let too_deep = format!("a.dat{}", "::x.dat".repeat(MAX_NESTING_DEPTH + 1));
+            assert!(utils::split_nested_archive_path(Path::new(&too_deep)).is_err());
+        }
+
+        #[test]
+        fn mutating_commands_reject_nested_paths() {
+            assert!(utils::reject_nested_archive_path(Path::new("a.dat")).is_ok());
+            assert!(utils::reject_nested_archive_path(Path::new("a.dat::b.dat")).is_err());
         }
     }
 
-    // ── get_dirname_from_dat_path ──────────────────────────────────
+    // ── open_nested ────────────────────────────────────────────────
 
-    mod get_dirname_from_dat_path {
+    mod open_nested {
         use super::*;
+        use std::fs;
 
-        #[test]
-        fn extracts_directory_with_backslash() {
+        /// Save an outer DAT2 archive holding a DAT1 archive as `mods\inner.dat`
+        fn save_nested(path: &Path) {
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            let inner_entries = ["art\\a.frm", "text\\b.msg"]
+                .iter()
+                .map(|name| (name.to_string(), name.repeat(20).into_bytes()))
+                .collect();
+            let (inner, _) = DatArchive::from_entries(true, inner_entries, &policy).unwrap();
+            let inner_path = path.with_extension("inner");
+            inner.save(&inner_path).unwrap();
+            let entries = vec![(
+                "mods\\inner.dat".to_string(),
+                fs::read(&inner_path).unwrap(),
+            )];
+            let (outer, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+            outer.save(path).unwrap();
+        }
+
+        #[test]
+        fn spilled_and_in_memory_inner_archives_match() {
+            let dir = TempDir::new("open-nested");
+            let outer = dir.join("outer.dat");
+            save_nested(&outer);
+            let spec = format!("{}::mods/inner.dat", outer.display());
+            let options = OpenOptions::default();
+
+            let in_memory =
+                DatArchive::open_nested_spilling(Path::new(&spec), &options, u64::MAX).unwrap();
+            let spilled = DatArchive::open_nested_spilling(Path::new(&spec), &options, 0).unwrap();
+            assert_eq!(spilled.entry_names(), in_memory.entry_names());
             assert_eq!(
-                utils::get_dirname_from_dat_path("ART\\CRITTERS\\FILE.FRM"),
-                "ART\\CRITTERS"
+                spilled.read_file("text/b.msg").unwrap(),
+                in_memory.read_file("text/b.msg").unwrap()
             );
-        }
 
-        #[test]
-        fn no_directory_returns_dot() {
-            assert_eq!(utils::get_dirname_from_dat_path("FILE.FRM"), ".");
+            // The spill file is gone once the inner archive is parsed
+            let prefix = format!("dat3-nested-{}-", std::process::id());
+            let leftover = fs::read_dir(std::env::temp_dir())
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix));
+            assert!(!leftover);
         }
     }
 
-    // ── matches_pattern ────────────────────────────────────────────
+    // ── canonical_entry_path ───────────────────────────────────────
 
-    mod matches_pattern {
+    mod canonical_entry_path {
         use super::*;
 
         #[test]
-        fn substring_match() {
-            assert!(utils::matches_pattern(
-                "ART\\CRITTERS\\FILE.FRM",
-                "FILE.FRM"
-            ));
+        fn converts_backslashes_to_forward() {
+            assert_eq!(
+                utils::canonical_entry_path("ART\\CRITTERS\\FILE.FRM"),
+                "ART/CRITTERS/FILE.FRM"
+            );
         }
 
         #[test]
-        fn substring_no_match() {
-            assert!(!utils::matches_pattern(
-                "ART\\CRITTERS\\FILE.FRM",
-                "MISSING.TXT"
-            ));
+        fn collapses_repeated_and_mixed_separators() {
+            assert_eq!(
+                utils::canonical_entry_path("art//critters\\/\\file.frm"),
+                "art/critters/file.frm"
+            );
         }
 
         #[test]
-        fn glob_star_matches_extension() {
-            assert!(utils::matches_pattern("ART\\CRITTERS\\FILE.FRM", "*.FRM"));
+        fn drops_leading_separators() {
+            assert_eq!(utils::canonical_entry_path("\\/art/a.frm"), "art/a.frm");
         }
 
         #[test]
-        fn glob_star_no_match_wrong_extension() {
-            assert!(!utils::matches_pattern("ART\\CRITTERS\\FILE.FRM", "*.TXT"));
+        fn preserves_case() {
+            assert_eq!(
+                utils::canonical_entry_path("Art\\HmJmpSaa.frm"),
+                "Art/HmJmpSaa.frm"
+            );
         }
 
         #[test]
-        fn glob_question_mark() {
-            assert!(utils::matches_pattern("ART\\CRITTERS\\A.FRM", "?.FRM"));
-            assert!(!utils::matches_pattern("ART\\CRITTERS\\AB.FRM", "?.FRM"));
+        fn no_separators_unchanged() {
+            assert_eq!(utils::canonical_entry_path("file.txt"), "file.txt");
         }
 
         #[test]
-        fn glob_with_path_prefix() {
-            assert!(utils::matches_pattern(
-                "ART\\CRITTERS\\FILE.FRM",
-                "ART/CRITTERS/*.FRM"
-            ));
+        fn empty_string() {
+            assert_eq!(utils::canonical_entry_path(""), "");
         }
+    }
+
+    // ── strip_entry_prefix ─────────────────────────────────────────
+
+    mod strip_entry_prefix {
+        use super::*;
 
         #[test]
-        fn glob_path_no_match_wrong_dir() {
-            assert!(!utils::matches_pattern(
-                "ART\\CRITTERS\\FILE.FRM",
-                "SOUND/*.FRM"
-            ));
+        fn strips_whole_leading_directories() {
+            assert_eq!(
+                utils::strip_entry_prefix("text\\english\\dialog\\a.msg", "text/english"),
+                Some("dialog\\a.msg".to_string())
+            );
+            assert_eq!(
+                utils::strip_entry_prefix("TEXT\\English\\a.msg", "/text\\english/"),
+                Some("a.msg".to_string())
+            );
         }
 
         #[test]
-        fn character_range() {
-            assert!(utils::matches_pattern(
-                "ART\\CRITTERS\\FILE1.FRM",
-                "[A-Z]*.FRM"
-            ));
+        fn none_outside_the_prefix() {
+            assert_eq!(
+                utils::strip_entry_prefix("text\\english2\\a.msg", "text/english"),
+                None
+            );
+            assert_eq!(
+                utils::strip_entry_prefix("text\\a.msg", "text/english"),
+                None
+            );
+            // The prefix itself names a directory, so a file by that name isn't under it
+            assert_eq!(
+                utils::strip_entry_prefix("text\\english", "text/english"),
+                None
+            );
+        }
+
+        #[test]
+        fn empty_prefix_strips_nothing() {
+            assert_eq!(
+                utils::strip_entry_prefix("text\\a.msg", "/"),
+                Some("text\\a.msg".to_string())
+            );
         }
     }
 
-    // ── contains_glob_metacharacters ───────────────────────────────
+    // ── strip_entry_components ─────────────────────────────────────
 
-    mod contains_glob_metacharacters {
+    mod strip_entry_components {
         use super::*;
 
         #[test]
-        fn detects_star() {
-            assert!(utils::contains_glob_metacharacters("*.txt"));
-        }
-
-        #[test]
-        fn detects_question() {
-            assert!(utils::contains_glob_metacharacters("file?.txt"));
+        fn drops_leading_components() {
+            assert_eq!(
+                utils::strip_entry_components("data\\art\\critters\\a.frm", 1),
+                Some("art\\critters\\a.frm".to_string())
+            );
+            assert_eq!(
+                utils::strip_entry_components("data/art/critters/a.frm", 3),
+                Some("a.frm".to_string())
+            );
+            assert_eq!(
+                utils::strip_entry_components("\\data\\\\a.frm", 1),
+                Some("a.frm".to_string())
+            );
         }
 
         #[test]
-        fn detects_bracket() {
-            assert!(utils::contains_glob_metacharacters("[abc].txt"));
+        fn none_when_nothing_is_left() {
+            assert_eq!(utils::strip_entry_components("data\\a.frm", 2), None);
+            assert_eq!(utils::strip_entry_components("a.frm", 1), None);
         }
 
         #[test]
-        fn no_metacharacters() {
-            assert!(!utils::contains_glob_metacharacters("file.txt"));
+        fn zero_keeps_the_path() {
+            assert_eq!(
+                utils::strip_entry_components("data\\a.frm", 0),
+                Some("data\\a.frm".to_string())
+            );
         }
     }
 
-    // ── strip_dot_prefix_from_path ──────────────────────────────────
+    // ── stored_entry_path ──────────────────────────────────────────
 
-    mod strip_dot_prefix {
+    mod stored_entry_path {
         use super::*;
 
         #[test]
-        fn leaves_plain_path_unchanged() {
+        fn converts_forward_to_backslashes() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path("patch000/file.txt"),
-                "patch000/file.txt"
+                utils::stored_entry_path("art/critters/file.frm"),
+                "art\\critters\\file.frm"
             );
         }
 
         #[test]
-        fn strips_with_dot_slash_prefix() {
+        fn backslashes_unchanged() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path("./patch000/file.txt"),
-                "patch000/file.txt"
+                utils::stored_entry_path("art\\critters\\file.frm"),
+                "art\\critters\\file.frm"
             );
         }
 
         #[test]
-        fn preserves_subdirectories() {
+        fn canonicalizes_first() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path("./patch000/subdir/file.txt"),
-                "patch000/subdir/file.txt"
+                utils::stored_entry_path("/art//critters/file.frm"),
+                "art\\critters\\file.frm"
             );
         }
 
         #[test]
-        fn no_directory_returns_filename() {
-            assert_eq!(utils::strip_dot_prefix_from_path("file.txt"), "file.txt");
+        fn is_stored_entry_path_matches_conversion() {
+            for path in [
+                "art\\critters\\file.frm",
+                "file.frm",
+                "art\\",
+                "art/critters/file.frm",
+                "\\art\\file.frm",
+                "art\\\\file.frm",
+                "",
+            ] {
+                assert_eq!(
+                    utils::is_stored_entry_path(path),
+                    utils::stored_entry_path(path) == path,
+                    "{path:?}"
+                );
+            }
         }
+    }
+
+    // ── compare_entry_paths ────────────────────────────────────────
 
+    mod compare_entry_paths {
+        use super::*;
+        use std::cmp::Ordering;
+
+        /// Names compare as their bytes with ASCII letters lowercased, so
+        /// `\` (0x5C) and `_` (0x5F) sort before letters and after digits
         #[test]
-        fn handles_backslashes() {
+        fn sorts_by_ascii_lowercased_bytes() {
+            let mut names = vec![
+                "Z.txt", "a_b.txt", "a2.txt", "a\\b.txt", "_x.txt", "A10.txt", "9.txt", "a1.txt",
+                "ab.txt",
+            ];
+            names.sort_by(|a, b| utils::compare_entry_paths(a, b));
             assert_eq!(
-                utils::strip_dot_prefix_from_path(".\\patch000\\file.txt"),
-                "patch000/file.txt"
+                names,
+                vec![
+                    "9.txt", "_x.txt", "a1.txt", "A10.txt", "a2.txt", "a\\b.txt", "a_b.txt",
+                    "ab.txt", "Z.txt",
+                ]
             );
         }
 
+        /// "\u{20AC}" is stored as 0x80 and "\u{fc}" as 0xFC, though in UTF-8
+        /// the euro sign's bytes are the larger
         #[test]
-        fn collapses_consecutive_slashes() {
+        fn non_ascii_sorts_by_stored_byte() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path(".\\patch000//file.txt"),
-                "patch000/file.txt"
+                utils::compare_entry_paths("\u{20AC}.txt", "\u{fc}.txt"),
+                Ordering::Less
+            );
+            assert_eq!(
+                utils::compare_entry_paths("\u{ff}.txt", "\u{0416}.txt"),
+                Ordering::Less
             );
         }
 
         #[test]
-        fn strips_unix_root_prefix() {
+        fn forward_slash_sorts_like_backslash() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path("/patch000/file.txt"),
-                "patch000/file.txt"
+                utils::compare_entry_paths("a/b.txt", "a_b.txt"),
+                Ordering::Less
             );
         }
 
-        #[cfg(windows)]
         #[test]
-        fn strips_windows_drive_prefix() {
+        fn ignores_ascii_case_only() {
             assert_eq!(
-                utils::strip_dot_prefix_from_path(r"C:\patch000\file.txt"),
-                "patch000/file.txt"
+                utils::compare_entry_paths("ART\\FILE.FRM", "art\\file.frm"),
+                Ordering::Equal
             );
+            assert_eq!(utils::compare_entry_paths("a", "ab"), Ordering::Less);
+            assert_ne!(
+                utils::compare_entry_paths("\u{e9}.txt", "\u{c9}.txt"),
+                Ordering::Equal
+            );
+        }
+
+        /// Every separator and case spelling of one path is equal to the others,
+        /// and has the same key
+        #[test]
+        fn separator_and_case_matrix() {
+            let spellings = [
+                "art\\critters\\a.frm",
+                "art/critters/a.frm",
+                "ART/CRITTERS/A.FRM",
+                "Art\\Critters/a.FRM",
+                "/art//critters\\\\a.frm",
+                "\\ART\\critters\\A.frm",
+            ];
+            for a in spellings {
+                for b in spellings {
+                    assert_eq!(
+                        utils::compare_entry_paths(a, b),
+                        Ordering::Equal,
+                        "{a} vs {b}"
+                    );
+                    assert_eq!(utils::entry_path_key(a), utils::entry_path_key(b));
+                }
+                assert_ne!(
+                    utils::compare_entry_paths(a, "art/critters/b.frm"),
+                    Ordering::Equal
+                );
+                assert!(utils::matches_pattern("ART\\CRITTERS\\A.FRM", a));
+            }
         }
     }
 
-    // ── filter_and_track_patterns ──────────────────────────────────
+    // ── entry_path_key ─────────────────────────────────────────────
 
-    mod filter_and_track_patterns {
+    mod entry_path_key {
         use super::*;
 
-        fn make_entry(name: &str) -> FileEntry {
-            FileEntry {
-                name: name.to_string(),
-                offset: 0,
-                size: 100,
-                packed_size: 100,
-                compressed: false,
-                data: None,
-            }
+        #[test]
+        fn canonical_lowercase() {
+            assert_eq!(
+                utils::entry_path_key("\\ART\\Critters//A.FRM"),
+                "art/critters/a.frm"
+            );
         }
+    }
+
+    // ── decode_filename ────────────────────────────────────────────
+
+    mod decode_filename {
+        use super::*;
 
         #[test]
-        fn empty_patterns_returns_all() {
-            let entries = vec![make_entry("a.txt"), make_entry("b.txt")];
-            let (filtered, missing) = filter_and_track_patterns(&entries, &[], |entry, pattern| {
-                entry.name.contains(pattern)
-            });
-            assert_eq!(filtered.len(), 2);
-            assert!(missing.is_empty());
+        fn valid_ascii() {
+            assert_eq!(
+                utils::decode_filename(b"CRITTERS.LST").unwrap(),
+                "CRITTERS.LST"
+            );
         }
 
         #[test]
-        fn filters_matching_entries() {
-            let entries = vec![
-                make_entry("a.txt"),
-                make_entry("b.txt"),
-                make_entry("c.dat"),
-            ];
-            let patterns = vec!["a.txt".to_string()];
-            let (filtered, missing) =
-                filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
-                    entry.name.contains(pattern)
-                });
-            assert_eq!(filtered.len(), 1);
-            assert_eq!(filtered[0].name, "a.txt");
-            assert!(missing.is_empty());
+        fn strips_null_bytes() {
+            assert_eq!(
+                utils::decode_filename(b"FILE.TXT\0\0\0").unwrap(),
+                "FILE.TXT"
+            );
         }
 
         #[test]
-        fn reports_missing_patterns() {
-            let entries = vec![make_entry("a.txt")];
-            let patterns = vec!["missing.txt".to_string()];
-            let (filtered, missing) =
-                filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
-                    entry.name.contains(pattern)
-                });
-            assert!(filtered.is_empty());
-            assert_eq!(missing, vec!["missing.txt"]);
+        fn decodes_windows_1252() {
+            // UTF-8 encoded e-acute: 0xC3 0xA9, read as two cp1252 characters
+            assert_eq!(
+                utils::decode_filename(&[0xC3, 0xA9]).unwrap(),
+                "\u{c3}\u{a9}"
+            );
+            assert_eq!(
+                utils::decode_filename(b"M\xdcLLER.FRM").unwrap(),
+                "M\u{dc}LLER.FRM"
+            );
         }
 
         #[test]
-        fn no_duplicate_matches() {
-            let entries = vec![make_entry("abc.txt")];
-            // Both patterns match the same entry
-            let patterns = vec!["abc".to_string(), "txt".to_string()];
-            let (filtered, _) = filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
-                entry.name.contains(pattern)
-            });
-            // Should only appear once (matched by first pattern)
-            assert_eq!(filtered.len(), 1);
+        fn empty_input() {
+            assert_eq!(utils::decode_filename(b"").unwrap(), "");
+        }
+
+        #[test]
+        fn null_only_input() {
+            assert_eq!(utils::decode_filename(b"\0\0").unwrap(), "");
         }
     }
 
-    // ── Path traversal protection ──────────────────────────────────
+    // ── check_archive_size ─────────────────────────────────────────
 
-    mod path_traversal {
+    mod check_archive_size {
         use super::*;
 
-        #[test]
-        fn rejects_dot_dot_in_path() {
-            assert!(utils::validate_archive_path("../etc/passwd").is_err());
+        /// An entry read from an archive: only its recorded sizes matter
+        fn mocked_entry(packed_size: u32) -> FileEntry {
+            FileEntry {
+                name: "a.txt".to_string(),
+                offset: 0,
+                size: packed_size,
+                packed_size,
+                compressed: false,
+                data: None,
+                original_index: None,
+                unsupported_compression: None,
+                stored_type: None,
+                directory_index: None,
+                nul_terminated_name: false,
+            }
         }
 
         #[test]
-        fn rejects_dot_dot_with_backslashes() {
-            assert!(utils::validate_archive_path("..\\etc\\passwd").is_err());
+        fn accepts_exactly_the_limit() {
+            let entries = [mocked_entry(u32::MAX - 8)];
+            assert!(utils::check_archive_size(&entries, 8).is_ok());
         }
 
         #[test]
-        fn rejects_embedded_dot_dot() {
-            assert!(utils::validate_archive_path("art/../../etc/passwd").is_err());
+        fn rejects_totals_past_the_limit() {
+            let entries = [mocked_entry(3 << 30), mocked_entry(2 << 30)];
+            let err = utils::check_archive_size(&entries, 8).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Archive would exceed the 4 GiB DAT limit (current total: 5368709128 bytes)"
+            );
         }
 
         #[test]
-        fn rejects_trailing_dot_dot() {
-            assert!(utils::validate_archive_path("art/critters/..").is_err());
+        fn overhead_counts() {
+            let entries = [mocked_entry(u32::MAX)];
+            assert!(utils::check_archive_size(&entries, 0).is_ok());
+            assert!(utils::check_archive_size(&entries, 1).is_err());
         }
+    }
+
+    // ── encode_filename ────────────────────────────────────────────
+
+    mod encode_filename {
+        use super::*;
 
         #[test]
-        fn accepts_normal_path() {
-            assert!(utils::validate_archive_path("art/critters/file.frm").is_ok());
+        fn round_trips_ascii() {
+            let bytes = utils::encode_filename("ART\\CRITTERS\\HMJMPSAA.FRM").unwrap();
+            assert_eq!(bytes, b"ART\\CRITTERS\\HMJMPSAA.FRM");
+            assert_eq!(
+                utils::decode_filename(&bytes).unwrap(),
+                "ART\\CRITTERS\\HMJMPSAA.FRM"
+            );
         }
 
         #[test]
-        fn accepts_path_with_dots_in_filename() {
-            assert!(utils::validate_archive_path("art/file.v2.0.frm").is_ok());
+        fn encodes_windows_1252() {
+            let bytes = utils::encode_filename("m\u{00fc}ller\u{20AC}.frm").unwrap();
+            assert_eq!(bytes, b"m\xfcller\x80.frm");
+            assert_eq!(
+                utils::encoded_name_len("m\u{00fc}ller\u{20AC}.frm"),
+                bytes.len()
+            );
         }
 
         #[test]
-        fn accepts_dotfile() {
-            assert!(utils::validate_archive_path("art/.hidden").is_ok());
+        fn rejects_characters_windows_1252_lacks() {
+            let err = utils::encode_filename("\u{0416}.frm").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Filename \"\u{0416}.frm\" has a character Windows-1252 lacks: '\u{0416}'"
+            );
         }
+    }
+
+    // ── validate_filename_ascii ────────────────────────────────────
+
+    mod validate_filename_ascii {
+        use super::*;
 
         #[test]
-        fn accepts_single_dot_component() {
-            // "." as a component is harmless (current directory)
-            assert!(utils::validate_archive_path("art/./file.frm").is_ok());
+        fn accepts_ascii() {
+            assert!(utils::validate_filename_ascii("hello.txt").is_ok());
         }
 
         #[test]
-        fn rejects_dot_dot_only() {
-            assert!(utils::validate_archive_path("..").is_err());
+        fn rejects_unicode() {
+            assert!(utils::validate_filename_ascii("héllo.txt").is_err());
         }
 
         #[test]
-        fn normalizes_single_dot_component_for_added_archive_paths() {
-            assert_eq!(
-                utils::validate_add_archive_path("art/./file.frm").unwrap(),
-                "art/file.frm"
-            );
+        fn accepts_empty() {
+            assert!(utils::validate_filename_ascii("").is_ok());
         }
+    }
+
+    // ── get_filename_from_dat_path ─────────────────────────────────
+
+    mod get_filename_from_dat_path {
+        use super::*;
 
         #[test]
-        fn normalizes_leading_dot_slash() {
-            assert_eq!(utils::validate_add_archive_path("./foo").unwrap(), "foo");
+        fn extracts_from_backslash_path() {
+            assert_eq!(
+                utils::get_filename_from_dat_path("ART\\CRITTERS\\FILE.FRM"),
+                "FILE.FRM"
+            );
         }
 
         #[test]
-        fn normalizes_internal_dot_component() {
+        fn extracts_from_forward_slash_path() {
             assert_eq!(
-                utils::validate_add_archive_path("foo/./bar").unwrap(),
-                "foo/bar"
+                utils::get_filename_from_dat_path("art/critters/file.frm"),
+                "file.frm"
             );
         }
 
         #[test]
-        fn rejects_dot_only_as_empty() {
-            assert!(utils::validate_add_archive_path(".").is_err());
+        fn no_separators_returns_whole_string() {
+            assert_eq!(utils::get_filename_from_dat_path("file.frm"), "file.frm");
         }
+    }
+
+    // ── get_dirname_from_dat_path ──────────────────────────────────
+
+    mod get_dirname_from_dat_path {
+        use super::*;
 
         #[test]
-        fn rejects_dot_slash_dot_as_empty() {
-            assert!(utils::validate_add_archive_path("./.").is_err());
+        fn extracts_directory_with_backslash() {
+            assert_eq!(
+                utils::get_dirname_from_dat_path("ART\\CRITTERS\\FILE.FRM"),
+                "ART\\CRITTERS"
+            );
         }
 
         #[test]
-        fn rejects_absolute_path_for_added_archive_paths() {
-            assert!(utils::validate_add_archive_path("/art/file.frm").is_err());
+        fn no_directory_returns_dot() {
+            assert_eq!(utils::get_dirname_from_dat_path("FILE.FRM"), ".");
         }
     }
 
-    // ── calculate_archive_path ─────────────────────────────────────
+    // ── matches_pattern ────────────────────────────────────────────
 
-    mod calculate_archive_path {
+    mod matches_pattern {
         use super::*;
 
         #[test]
-        fn single_file_no_target() {
-            let result = utils::calculate_archive_path(
-                Path::new("file.txt"),
-                Path::new("file.txt"),
-                None,
-                None,
-            )
-            .unwrap();
-            assert_eq!(result, "file.txt");
-        }
-
-        #[test]
-        fn single_file_with_target() {
-            let result = utils::calculate_archive_path(
-                Path::new("file.txt"),
-                Path::new("file.txt"),
-                Some("data"),
-                None,
-            )
-            .unwrap();
-            assert_eq!(result, "data\\file.txt");
+        fn substring_match() {
+            assert!(utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "FILE.FRM"
+            ));
         }
 
         #[test]
-        fn strip_leading_directory() {
-            let result = utils::calculate_archive_path(
-                Path::new("patch000/file.txt"),
-                Path::new("patch000/file.txt"),
-                None,
-                None,
-            )
-            .unwrap();
-            assert_eq!(result, "patch000\\file.txt");
+        fn substring_no_match() {
+            assert!(!utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "MISSING.TXT"
+            ));
         }
 
         #[test]
-        fn dot_slash_prefix_is_only_normalized() {
-            let result = utils::calculate_archive_path(
-                Path::new("./patch000/file.txt"),
-                Path::new("./patch000/file.txt"),
-                None,
-                None,
-            )
-            .unwrap();
-            assert_eq!(result, "patch000\\file.txt");
+        fn glob_star_matches_extension() {
+            assert!(utils::matches_pattern("ART\\CRITTERS\\FILE.FRM", "*.FRM"));
         }
 
         #[test]
-        fn result_uses_backslashes() {
-            let result = utils::calculate_archive_path(
-                Path::new("art/critters/file.frm"),
-                Path::new("art/critters/file.frm"),
-                None,
-                None,
-            )
-            .unwrap();
-            // Should use backslashes (archive format)
-            assert!(result.contains('\\') || !result.contains('/'));
+        fn glob_star_no_match_wrong_extension() {
+            assert!(!utils::matches_pattern("ART\\CRITTERS\\FILE.FRM", "*.TXT"));
         }
 
         #[test]
-        fn absolute_unix_path_strips_root_only() {
-            let result = utils::calculate_archive_path(
-                Path::new("/patch000/file.txt"),
-                Path::new("/patch000/file.txt"),
-                None,
-                None,
-            )
-            .unwrap();
-            assert_eq!(result, "patch000\\file.txt");
+        fn glob_question_mark() {
+            assert!(utils::matches_pattern("ART\\CRITTERS\\A.FRM", "?.FRM"));
+            assert!(!utils::matches_pattern("ART\\CRITTERS\\AB.FRM", "?.FRM"));
         }
 
         #[test]
-        fn change_dir_becomes_archive_root() {
+        fn glob_with_path_prefix() {
+            assert!(utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "ART/CRITTERS/*.FRM"
+            ));
+        }
+
+        #[test]
+        fn glob_path_no_match_wrong_dir() {
+            assert!(!utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "SOUND/*.FRM"
+            ));
+        }
+
+        #[test]
+        fn ignores_case_and_separator_style() {
+            assert!(utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "art/critters/file.frm"
+            ));
+            assert!(utils::matches_pattern(
+                "ART\\CRITTERS\\FILE.FRM",
+                "art\\*\\*.frm"
+            ));
+            assert!(utils::matches_pattern("ART\\CRITTERS\\FILE.FRM", "*.frm"));
+        }
+
+        #[test]
+        fn character_range() {
+            assert!(utils::matches_pattern(
+                "ART\\CRITTERS\\FILE1.FRM",
+                "[A-Z]*.FRM"
+            ));
+        }
+    }
+
+    // ── contains_glob_metacharacters ───────────────────────────────
+
+    mod contains_glob_metacharacters {
+        use super::*;
+
+        #[test]
+        fn detects_star() {
+            assert!(utils::contains_glob_metacharacters("*.txt"));
+        }
+
+        #[test]
+        fn detects_question() {
+            assert!(utils::contains_glob_metacharacters("file?.txt"));
+        }
+
+        #[test]
+        fn detects_bracket() {
+            assert!(utils::contains_glob_metacharacters("[abc].txt"));
+        }
+
+        #[test]
+        fn no_metacharacters() {
+            assert!(!utils::contains_glob_metacharacters("file.txt"));
+        }
+    }
+
+    // ── strip_dot_prefix_from_path ──────────────────────────────────
+
+    mod strip_dot_prefix {
+        use super::*;
+
+        #[test]
+        fn leaves_plain_path_unchanged() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path("patch000/file.txt"),
+                "patch000/file.txt"
+            );
+        }
+
+        #[test]
+        fn strips_with_dot_slash_prefix() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path("./patch000/file.txt"),
+                "patch000/file.txt"
+            );
+        }
+
+        #[test]
+        fn preserves_subdirectories() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path("./patch000/subdir/file.txt"),
+                "patch000/subdir/file.txt"
+            );
+        }
+
+        #[test]
+        fn no_directory_returns_filename() {
+            assert_eq!(utils::strip_dot_prefix_from_path("file.txt"), "file.txt");
+        }
+
+        #[test]
+        fn handles_backslashes() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path(".\\patch000\\file.txt"),
+                "patch000/file.txt"
+            );
+        }
+
+        #[test]
+        fn collapses_consecutive_slashes() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path(".\\patch000//file.txt"),
+                "patch000/file.txt"
+            );
+        }
+
+        #[test]
+        fn strips_unix_root_prefix() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path("/patch000/file.txt"),
+                "patch000/file.txt"
+            );
+        }
+
+        #[cfg(windows)]
+        #[test]
+        fn strips_windows_drive_prefix() {
+            assert_eq!(
+                utils::strip_dot_prefix_from_path(r"C:\patch000\file.txt"),
+                "patch000/file.txt"
+            );
+        }
+    }
+
+    // ── filter_and_track_patterns ──────────────────────────────────
+
+    mod filter_and_track_patterns {
+        use super::*;
+
+        fn make_entry(name: &str) -> FileEntry {
+            FileEntry {
+                name: name.to_string(),
+                offset: 0,
+                size: 100,
+                packed_size: 100,
+                compressed: false,
+                data: None,
+                original_index: None,
+                unsupported_compression: None,
+                stored_type: None,
+                directory_index: None,
+                nul_terminated_name: false,
+            }
+        }
+
+        #[test]
+        fn empty_patterns_returns_all() {
+            let entries = vec![make_entry("a.txt"), make_entry("b.txt")];
+            let (filtered, missing) = filter_and_track_patterns(&entries, &[], |entry, pattern| {
+                entry.name.contains(pattern)
+            });
+            assert_eq!(filtered.len(), 2);
+            assert!(missing.is_empty());
+        }
+
+        #[test]
+        fn filters_matching_entries() {
+            let entries = vec![
+                make_entry("a.txt"),
+                make_entry("b.txt"),
+                make_entry("c.dat"),
+            ];
+            let patterns = vec!["a.txt".to_string()];
+            let (filtered, missing) =
+                filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
+                    entry.name.contains(pattern)
+                });
+            assert_eq!(filtered.len(), 1);
+            assert_eq!(filtered[0].name, "a.txt");
+            assert!(missing.is_empty());
+        }
+
+        #[test]
+        fn reports_missing_patterns() {
+            let entries = vec![make_entry("a.txt")];
+            let patterns = vec!["missing.txt".to_string()];
+            let (filtered, missing) =
+                filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
+                    entry.name.contains(pattern)
+                });
+            assert!(filtered.is_empty());
+            assert_eq!(missing, vec!["missing.txt"]);
+        }
+
+        #[test]
+        fn no_duplicate_matches() {
+            let entries = vec![make_entry("abc.txt")];
+            // Both patterns match the same entry
+            let patterns = vec!["abc".to_string(), "txt".to_string()];
+            let (filtered, _) = filter_and_track_patterns(&entries, &patterns, |entry, pattern| {
+                entry.name.contains(pattern)
+            });
+            // Should only appear once (matched by first pattern)
+            assert_eq!(filtered.len(), 1);
+        }
+    }
+
+    // ── plan_delete ────────────────────────────────────────────────
+
+    mod plan_delete {
+        use super::*;
+
+        fn archive(dat1: bool) -> DatArchive {
+            let entries = [
+                "ART\\A.FRM",
+                "art\\b.frm",
+                "sound\\sfx\\c.acm",
+                "readme.txt",
+            ]
+            .iter()
+            .map(|name| (name.to_string(), b"data".to_vec()))
+            .collect();
+            let policy = CompressionPolicy::new(CompressionLevel::new(1).unwrap());
+            DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+        }
+
+        fn patterns(list: &[&str]) -> Vec<String> {
+            list.iter().map(|pattern| pattern.to_string()).collect()
+        }
+
+        #[test]
+        fn globs_match_many_entries_names_one() {
+            for dat1 in [false, true] {
+                let plan = archive(dat1).plan_delete(&patterns(&["art/*.frm", "README.TXT"]));
+                let mut names: Vec<_> = plan
+                    .names
+                    .iter()
+                    .map(|n| utils::entry_path_key(n))
+                    .collect();
+                names.sort();
+                assert_eq!(names, ["art/a.frm", "art/b.frm", "readme.txt"]);
+                assert_eq!(
+                    plan.matches,
+                    [("art/*.frm".to_string(), 2), ("README.TXT".to_string(), 1)]
+                );
+            }
+        }
+
+        #[test]
+        fn plain_names_are_not_substrings() {
+            let plan = archive(false).plan_delete(&patterns(&["frm", "sound/sfx"]));
+            assert!(plan.names.is_empty());
+            assert_eq!(plan.missing(), ["frm", "sound/sfx"]);
+        }
+
+        #[test]
+        fn trailing_slash_matches_the_whole_directory() {
+            let plan = archive(false).plan_delete(&patterns(&["Sound\\", "art/"]));
+            assert_eq!(plan.names.len(), 3);
+            assert_eq!(plan.matches[0].1, 1);
+
+            let plan = archive(false).plan_delete(&patterns(&["sou/"]));
+            assert_eq!(plan.missing(), ["sou/"]);
+        }
+
+        #[test]
+        fn empty_dat1_directories_under_the_prefix_are_removed() {
+            let mut dat1 = archive(true);
+            let progress = Progress::default();
+            for name in dat1.plan_delete(&patterns(&["sound/", "readme.txt"])).names {
+                dat1.delete_file(&name, &progress).unwrap();
+            }
+            // The root stays though it's now empty
+            assert_eq!(dat1.remove_empty_directories("sound/"), 1);
+            assert_eq!(dat1.remove_empty_directories("/"), 0);
+            assert_eq!(archive(false).remove_empty_directories("/"), 0);
+        }
+
+        #[test]
+        fn overlapping_patterns_delete_each_entry_once() {
+            let plan = archive(false).plan_delete(&patterns(&["*.frm", "art\\a.frm"]));
+            assert_eq!(plan.names.len(), 2);
+            assert!(plan.missing().is_empty());
+        }
+    }
+
+    // ── save_appending, save_tree_only, save_tree_in_place ─────────
+
+    mod save_in_place {
+        use super::*;
+        use std::fs;
+        use std::io::{self, Write};
+
+        /// Save a small DAT2 archive to `path`
+        fn save_archive(path: &Path) {
+            let entries = ["art\\a.frm", "text\\b.msg"]
+                .iter()
+                .map(|name| (name.to_string(), name.repeat(20).into_bytes()))
+                .collect();
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            let (archive, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+            archive.save(path).unwrap();
+        }
+
+        /// Open `path`, add the directory `name` under `root`, and save,
+        /// appending if asked
+        fn add_and_save(path: &Path, root: &Path, name: &str, append: bool) -> bool {
+            let mut archive = DatArchive::open(path).unwrap();
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            let options = AddOptions {
+                source_root: Some(root.to_path_buf()),
+                ..AddOptions::new(policy)
+            };
+            archive
+                .add_file(root.join(name), &options, &Progress::default())
+                .unwrap();
+            if append {
+                archive.save_appending(path).unwrap()
+            } else {
+                archive.save(path).map(|()| false).unwrap()
+            }
+        }
+
+        /// Names, sizes and contents of every entry
+        fn contents(path: &Path) -> Vec<(String, u32, Vec<u8>)> {
+            let archive = DatArchive::open(path).unwrap();
+            let names: Vec<_> = archive
+                .entries()
+                .map(|f| (f.name.clone(), f.size))
+                .collect();
+            names
+                .into_iter()
+                .map(|(name, size)| {
+                    let data = archive.read_file(&name).unwrap();
+                    (name, size, data)
+                })
+                .collect()
+        }
+
+        #[test]
+        fn appended_archive_matches_a_rewrite() {
+            let root = TempDir::new("fast-append");
+            fs::create_dir_all(root.join("scripts")).unwrap();
+            fs::write(root.join("scripts/new.int"), b"new script ".repeat(50)).unwrap();
+            let (appended, rewritten) = (root.join("appended.dat"), root.join("rewritten.dat"));
+            save_archive(&appended);
+            save_archive(&rewritten);
+            let before = fs::read(&appended).unwrap();
+
+            assert!(add_and_save(&appended, &root, "scripts", true));
+            add_and_save(&rewritten, &root, "scripts", false);
+
+            assert_eq!(contents(&appended), contents(&rewritten));
+            assert_eq!(contents(&appended).len(), 3);
+            // The existing data stayed where it was
+            let footer: [u8; 4] = before[before.len() - 8..before.len() - 4]
+                .try_into()
+                .unwrap();
+            let tree_start = before.len() - 8 - u32::from_le_bytes(footer) as usize;
+            let after = fs::read(&appended).unwrap();
+            assert_eq!(after[..tree_start], before[..tree_start]);
+        }
+
+        #[test]
+        fn replacing_or_deleting_falls_back_to_a_rewrite() {
+            let root = TempDir::new("fast-append-replace");
+            fs::create_dir_all(root.join("art")).unwrap();
+            fs::write(root.join("art/a.frm"), b"replaced").unwrap();
+            let path = root.join("replace.dat");
+            save_archive(&path);
+
+            assert!(!add_and_save(&path, &root, "art", true));
+            let entries = contents(&path);
+            assert_eq!(entries[0].2, b"replaced");
+
+            let mut archive = DatArchive::open(&path).unwrap();
+            archive
+                .delete_file("text\\b.msg", &Progress::default())
+                .unwrap();
+            assert!(!archive.save_appending(&path).unwrap());
+            assert_eq!(contents(&path).len(), 1);
+        }
+
+        #[test]
+        fn deleting_rewrites_only_the_tree() {
+            let root = TempDir::new("tree-only");
+            let (path, compacted) = (root.join("tree.dat"), root.join("compacted.dat"));
+            save_archive(&path);
+            let before = fs::read(&path).unwrap();
+
+            let progress = Progress::default();
+            let mut archive = DatArchive::open(&path).unwrap();
+            archive.delete_file("art\\a.frm", &progress).unwrap();
+            assert!(archive.save_tree_only(&path).unwrap());
+            archive.save(&compacted).unwrap();
+
+            assert_eq!(contents(&path), contents(&compacted));
+            let after = fs::read(&path).unwrap();
+            let data_end = DatArchive::open(&path)
+                .unwrap()
+                .space_report()
+                .data_region
+                .end;
+            assert_eq!(after[..data_end as usize], before[..data_end as usize]);
+            // The deleted entry's data is left as a gap for `optimize`
+            assert_eq!(
+                DatArchive::open(&path).unwrap().space_report().gaps.len(),
+                1
+            );
+            assert!(fs::metadata(&compacted).unwrap().len() < after.len() as u64);
+        }
+
+        #[test]
+        fn tree_only_needs_the_file_it_was_opened_from() {
+            let root = TempDir::new("tree-only-changed");
+            let path = root.join("changed.dat");
+            save_archive(&path);
+            let mut archive = DatArchive::open(&path).unwrap();
+
+            // Same size, different tree
+            let mut changed = fs::read(&path).unwrap();
+            let len = changed.len();
+            changed[len - 12] ^= 1;
+            fs::write(&path, changed).unwrap();
+
+            archive
+                .delete_file("art\\a.frm", &Progress::default())
+                .unwrap();
+            assert!(!archive.save_tree_only(&path).unwrap());
+            assert_eq!(contents(&path).len(), 1);
+        }
+
+        #[test]
+        fn in_place_tree_matches_the_replaced_file() {
+            let root = TempDir::new("tree-in-place");
+            let (path, replaced) = (root.join("in-place.dat"), root.join("replaced.dat"));
+            save_archive(&path);
+            save_archive(&replaced);
+
+            for path in [&path, &replaced] {
+                let mut archive = DatArchive::open(path).unwrap();
+                archive
+                    .delete_file("art\\a.frm", &Progress::default())
+                    .unwrap();
+                let tree_only = if path == &replaced {
+                    archive.save_tree_only(path)
+                } else {
+                    archive.save_tree_in_place(path)
+                };
+                assert!(tree_only.unwrap());
+            }
+            assert_eq!(fs::read(&path).unwrap(), fs::read(&replaced).unwrap());
+        }
+
+        /// Delete `art\a.frm` from the archive at `path` and save its tree,
+        /// failing halfway through writing the new bytes
+        fn fail_tree_save(path: &Path, in_place: bool) -> anyhow::Error {
+            let mut archive = DatArchive::open(path).unwrap();
+            archive
+                .delete_file("art\\a.frm", &Progress::default())
+                .unwrap();
+            let DatArchive::Dat2(archive) = archive else {
+                unreachable!()
+            };
+            archive
+                .save_tree_with(path, in_place, |file, output| {
+                    file.write_all(&output[..output.len() / 2])?;
+                    Err(io::Error::other("disk full"))
+                })
+                .unwrap_err()
+        }
+
+        #[test]
+        fn failed_tree_save_leaves_the_archive_untouched() {
+            let root = TempDir::new("tree-only-fail");
+            let path = root.join("archive.dat");
+            save_archive(&path);
+            let before = fs::read(&path).unwrap();
+
+            let err = fail_tree_save(&path, false);
+
+            assert!(format!("{err:#}").ends_with("disk full"));
+            assert_eq!(fs::read(&path).unwrap(), before);
+            assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+        }
+
+        #[test]
+        fn failed_in_place_save_keeps_the_data_but_not_the_tree() {
+            let root = TempDir::new("tree-in-place-fail");
+            let path = root.join("archive.dat");
+            save_archive(&path);
+            let before = fs::read(&path).unwrap();
+            let data_end = DatArchive::open(&path)
+                .unwrap()
+                .space_report()
+                .data_region
+                .end as usize;
+
+            let err = fail_tree_save(&path, true);
+
+            assert!(format!("{err:#}").ends_with("disk full"));
+            let after = fs::read(&path).unwrap();
+            assert_eq!(after[..data_end], before[..data_end]);
+            assert!(DatArchive::open(&path).is_err());
+        }
+
+        #[test]
+        fn dat1_is_always_rewritten() {
+            let root = TempDir::new("fast-append-dat1");
+            let path = root.join("dat1.dat");
+            let entries = vec![("a.txt".to_string(), b"data".to_vec())];
+            let policy = CompressionPolicy::new(CompressionLevel::new(0).unwrap());
+            let (archive, _) = DatArchive::from_entries(true, entries, &policy).unwrap();
+            archive.save(&path).unwrap();
+
+            let archive = DatArchive::open(&path).unwrap();
+            assert!(!archive.save_appending(&path).unwrap());
+            assert!(!archive.save_tree_only(&path).unwrap());
+            assert_eq!(contents(&path).len(), 1);
+        }
+    }
+
+    // ── save_deduplicated ──────────────────────────────────────────
+
+    mod save_deduplicated {
+        use super::*;
+        use std::fs;
+
+        /// Three copies of one placeholder, one other file and an empty one
+        fn archive(dat1: bool) -> DatArchive {
+            let placeholder = b"placeholder frame ".repeat(40);
+            let entries = vec![
+                ("art\\a.frm".to_string(), placeholder.clone()),
+                ("art\\b.frm".to_string(), placeholder.clone()),
+                ("art\\c.frm".to_string(), placeholder),
+                ("art\\d.frm".to_string(), b"something else".to_vec()),
+                ("empty.txt".to_string(), Vec::new()),
+            ];
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+        }
+
+        fn contents(path: &Path) -> Vec<(String, Vec<u8>)> {
+            let archive = DatArchive::open(path).unwrap();
+            let names: Vec<String> = archive.entries().map(|f| f.name.clone()).collect();
+            names
+                .into_iter()
+                .map(|name| {
+                    let data = archive.read_file(&name).unwrap();
+                    (name, data)
+                })
+                .collect()
+        }
+
+        #[test]
+        fn identical_data_is_written_once() {
+            let root = TempDir::new("dedup");
+            let (plain, dedup) = (root.join("plain.dat"), root.join("dedup.dat"));
+            let archive = archive(false);
+            archive.save(&plain).unwrap();
+            let shared = archive.save_deduplicated(&dedup).unwrap();
+
+            let packed = archive.entries().next().unwrap().packed_size as u64;
+            assert_eq!(
+                shared,
+                SharedData {
+                    entries: 2,
+                    bytes: 2 * packed
+                }
+            );
+            let size = |path: &Path| fs::metadata(path).unwrap().len();
+            assert_eq!(size(&plain) - size(&dedup), 2 * packed);
+            assert_eq!(contents(&plain), contents(&dedup));
+
+            let reopened = DatArchive::open(&dedup).unwrap();
+            let offsets: Vec<u64> = reopened.entries().map(|f| f.offset).collect();
+            assert_eq!(offsets[0], offsets[1]);
+            assert_eq!(offsets[1], offsets[2]);
+            assert_ne!(offsets[2], offsets[3]);
+            assert!(reopened.entry_problems().is_empty());
+            assert_eq!(reopened.space_report().unreferenced(), 0);
+        }
+
+        #[test]
+        fn plain_save_keeps_shared_data_shared() {
+            let root = TempDir::new("dedup-resave");
+            let path = root.join("dedup.dat");
+            archive(false).save_deduplicated(&path).unwrap();
+            let size = fs::metadata(&path).unwrap().len();
+
+            let mut reopened = DatArchive::open(&path).unwrap();
+            reopened
+                .delete_file("art\\d.frm", &Progress::default())
+                .unwrap();
+            reopened.save(&path).unwrap();
+
+            assert!(fs::metadata(&path).unwrap().len() < size);
+            assert_eq!(contents(&path).len(), 4);
+            let offsets: Vec<u64> = DatArchive::open(&path)
+                .unwrap()
+                .entries()
+                .take(3)
+                .map(|f| f.offset)
+                .collect();
+            assert_eq!(offsets, [offsets[0]; 3]);
+        }
+
+        #[test]
+        fn adding_with_dedup_builds_the_same_entries() {
+            let root = TempDir::new("dedup-add");
+            let source = root.join("src");
+            fs::create_dir_all(source.join("art")).unwrap();
+            let placeholder = b"placeholder frame ".repeat(40);
+            for name in ["a.frm", "b.frm", "c.frm", "d.acm"] {
+                fs::write(source.join("art").join(name), &placeholder).unwrap();
+            }
+            fs::write(source.join("art/e.frm"), b"something else").unwrap();
+
+            let entries = |dedup: bool| {
+                let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap())
+                    .with_store_extensions(&["acm"]);
+                let options = AddOptions {
+                    source_root: Some(source.clone()),
+                    recursive: true,
+                    dedup,
+                    ..AddOptions::new(policy)
+                };
+                let mut archive = DatArchive::new_dat2();
+                let stats = archive
+                    .add_file(source.join("art"), &options, &Progress::default())
+                    .unwrap();
+                assert_eq!((stats.compressed, stats.stored_by_extension), (3, 1));
+                archive
+                    .entries()
+                    .map(|f| (f.name.clone(), f.size, f.compressed, f.data.clone()))
+                    .collect::<Vec<_>>()
+            };
+
+            let deduplicated = entries(true);
+            assert_eq!(deduplicated, entries(false));
+            // The same content under a store-only extension stays stored
+            assert!(deduplicated[0].2 && !deduplicated[3].2);
+        }
+
+        #[test]
+        fn dat1_is_saved_as_usual() {
+            let root = TempDir::new("dedup-dat1");
+            let path = root.join("dat1.dat");
+            let shared = archive(true).save_deduplicated(&path).unwrap();
+            assert_eq!(shared, SharedData::default());
+            assert_eq!(contents(&path).len(), 5);
+        }
+    }
+
+    // ── ArchiveFormat ──────────────────────────────────────────────
+
+    mod archive_format {
+        use super::*;
+        use std::fs;
+
+        /// Bytes of a DAT2 archive whose first entry's data starts with
+        /// `header`, so the archive begins with it
+        fn dat2_starting_with(header: &[u8], name: &str) -> Vec<u8> {
+            let entries = vec![
+                ("a.bin".to_string(), header.to_vec()),
+                ("b.txt".to_string(), b"second entry".to_vec()),
+            ];
+            let policy = CompressionPolicy::new(CompressionLevel::new(0).unwrap());
+            let (archive, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+            let dir = TempDir::new("format");
+            let path = dir.join(format!("{name}.dat"));
+            archive.save(&path).unwrap();
+            fs::read(&path).unwrap()
+        }
+
+        fn open(data: Vec<u8>, format: ArchiveFormat) -> crate::error::DatResult<DatArchive> {
+            let options = OpenOptions {
+                format,
+                ..OpenOptions::default()
+            };
+            DatArchive::from_bytes_with(data, &options)
+        }
+
+        /// One directory named "." with no files: a complete DAT1 tree
+        const EMPTY_DAT1: &[u8] = &[
+            0, 0, 0, 1, 0, 0, 0, 0x0A, 0, 0, 0, 0, 0, 0, 0, 0, // header
+            1, b'.', // directory name
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // no files
+        ];
+
+        #[test]
+        fn forcing_dat2_reads_a_dat2_that_looks_like_dat1() {
+            let data = dat2_starting_with(EMPTY_DAT1, "lookalike");
+
+            let detected = open(data.clone(), ArchiveFormat::Auto).unwrap();
+            assert!(detected.is_dat1());
+            assert_eq!(detected.len(), 0);
+
+            let forced = open(data, ArchiveFormat::Dat2).unwrap();
+            assert!(!forced.is_dat1());
+            assert_eq!(forced.len(), 2);
+            assert_eq!(forced.read_file("a.bin").unwrap(), EMPTY_DAT1);
+        }
+
+        #[test]
+        fn detected_dat1_that_fails_to_parse_is_retried_as_dat2() {
+            // 999 directories, but no valid names follow
+            let header = [
+                0, 0, 0x03, 0xE7, 0, 0, 0, 0x5E, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF,
+            ];
+            let data = dat2_starting_with(&header, "retry");
+            assert!(DatArchive::is_dat1_format(&data));
+
+            let archive = open(data.clone(), ArchiveFormat::Auto).unwrap();
+            assert!(!archive.is_dat1());
+            assert_eq!(archive.len(), 2);
+            assert!(open(data, ArchiveFormat::Dat1).is_err());
+        }
+
+        #[test]
+        fn forcing_dat1_fails_on_dat2() {
+            let data = dat2_starting_with(b"plain data here", "plain");
+            assert!(open(data.clone(), ArchiveFormat::Dat1).is_err());
+            assert!(!open(data, ArchiveFormat::Auto).unwrap().is_dat1());
+        }
+    }
+
+    // ── write_atomically, save_with_backup ─────────────────────────
+
+    mod write_atomically {
+        use super::*;
+        use anyhow::bail;
+        use std::fs;
+        use std::io::Write;
+
+        fn file_names(dir: &Path) -> Vec<String> {
+            let mut names: Vec<_> = fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+
+        #[test]
+        fn replaces_the_file() {
+            let root = TempDir::new("atomic-replace");
+            let path = root.join("archive.dat");
+            fs::write(&path, b"old contents").unwrap();
+
+            utils::write_atomically(&path, |file| Ok(file.write_all(b"new")?)).unwrap();
+
+            assert_eq!(fs::read(&path).unwrap(), b"new");
+            assert_eq!(file_names(&root), ["archive.dat"]);
+        }
+
+        #[test]
+        fn failed_write_leaves_the_original_untouched() {
+            let root = TempDir::new("atomic-fail");
+            let path = root.join("archive.dat");
+            fs::write(&path, b"old contents").unwrap();
+
+            let result = utils::write_atomically(&path, |file| {
+                file.write_all(b"half of the new")?;
+                bail!("disk full")
+            });
+
+            assert_eq!(format!("{:#}", result.unwrap_err()), "disk full");
+            assert_eq!(fs::read(&path).unwrap(), b"old contents");
+            assert_eq!(file_names(&root), ["archive.dat"]);
+        }
+
+        #[test]
+        fn creates_a_new_file() {
+            let root = TempDir::new("atomic-new");
+            let path = root.join("new.dat");
+            utils::write_atomically(&path, |file| Ok(file.write_all(b"data")?)).unwrap();
+            assert_eq!(fs::read(&path).unwrap(), b"data");
+        }
+
+        #[test]
+        fn backup_keeps_the_previous_file() {
+            let root = TempDir::new("backup");
+            let path = root.join("archive.dat");
+            fs::write(&path, b"first").unwrap();
+            fs::write(root.join("archive.dat.bak"), b"older").unwrap();
+
+            utils::save_with_backup(&path, ".bak", || {
+                utils::write_atomically(&path, |file| Ok(file.write_all(b"second")?))
+            })
+            .unwrap();
+
+            assert_eq!(fs::read(&path).unwrap(), b"second");
+            assert_eq!(fs::read(root.join("archive.dat.bak")).unwrap(), b"first");
+            assert_eq!(file_names(&root), ["archive.dat", "archive.dat.bak"]);
+        }
+
+        #[test]
+        fn failed_save_keeps_the_old_backup() {
+            let root = TempDir::new("backup-fail");
+            let path = root.join("archive.dat");
+            fs::write(&path, b"first").unwrap();
+            fs::write(root.join("archive.dat.old"), b"older").unwrap();
+
+            let result = utils::save_with_backup(&path, ".old", || -> anyhow::Result<()> {
+                bail!("disk full")
+            });
+
+            assert!(result.is_err());
+            assert_eq!(fs::read(&path).unwrap(), b"first");
+            assert_eq!(fs::read(root.join("archive.dat.old")).unwrap(), b"older");
+            assert_eq!(file_names(&root), ["archive.dat", "archive.dat.old"]);
+        }
+    }
+
+    // ── Path traversal protection ──────────────────────────────────
+
+    mod path_traversal {
+        use super::*;
+
+        #[test]
+        fn rejects_dot_dot_in_path() {
+            assert!(utils::validate_archive_path("../etc/passwd").is_err());
+        }
+
+        #[test]
+        fn rejects_dot_dot_with_backslashes() {
+            assert!(utils::validate_archive_path("..\\etc\\passwd").is_err());
+        }
+
+        #[test]
+        fn rejects_embedded_dot_dot() {
+            assert!(utils::validate_archive_path("art/../../etc/passwd").is_err());
+        }
+
+        #[test]
+        fn rejects_trailing_dot_dot() {
+            assert!(utils::validate_archive_path("art/critters/..").is_err());
+        }
+
+        #[test]
+        fn accepts_normal_path() {
+            assert!(utils::validate_archive_path("art/critters/file.frm").is_ok());
+        }
+
+        #[test]
+        fn accepts_path_with_dots_in_filename() {
+            assert!(utils::validate_archive_path("art/file.v2.0.frm").is_ok());
+        }
+
+        #[test]
+        fn accepts_dotfile() {
+            assert!(utils::validate_archive_path("art/.hidden").is_ok());
+        }
+
+        #[test]
+        fn accepts_single_dot_component() {
+            // "." as a component is harmless (current directory)
+            assert!(utils::validate_archive_path("art/./file.frm").is_ok());
+        }
+
+        #[test]
+        fn rejects_dot_dot_only() {
+            assert!(utils::validate_archive_path("..").is_err());
+        }
+
+        #[test]
+        fn normalizes_single_dot_component_for_added_archive_paths() {
+            assert_eq!(
+                utils::validate_add_archive_path("art/./file.frm").unwrap(),
+                "art/file.frm"
+            );
+        }
+
+        #[test]
+        fn normalizes_leading_dot_slash() {
+            assert_eq!(utils::validate_add_archive_path("./foo").unwrap(), "foo");
+        }
+
+        #[test]
+        fn normalizes_internal_dot_component() {
+            assert_eq!(
+                utils::validate_add_archive_path("foo/./bar").unwrap(),
+                "foo/bar"
+            );
+        }
+
+        #[test]
+        fn rejects_dot_only_as_empty() {
+            assert!(utils::validate_add_archive_path(".").is_err());
+        }
+
+        #[test]
+        fn rejects_dot_slash_dot_as_empty() {
+            assert!(utils::validate_add_archive_path("./.").is_err());
+        }
+
+        #[test]
+        fn rejects_absolute_path_for_added_archive_paths() {
+            assert!(utils::validate_add_archive_path("/art/file.frm").is_err());
+        }
+    }
+
+    // ── calculate_archive_path ─────────────────────────────────────
+
+    mod calculate_archive_path {
+        use super::*;
+
+        #[test]
+        fn single_file_no_target() {
+            let result = utils::calculate_archive_path(
+                Path::new("file.txt"),
+                Path::new("file.txt"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "file.txt");
+        }
+
+        #[test]
+        fn single_file_with_target() {
+            let result = utils::calculate_archive_path(
+                Path::new("file.txt"),
+                Path::new("file.txt"),
+                Some("data"),
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "data\\file.txt");
+        }
+
+        #[test]
+        fn strip_leading_directory() {
+            let result = utils::calculate_archive_path(
+                Path::new("patch000/file.txt"),
+                Path::new("patch000/file.txt"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "patch000\\file.txt");
+        }
+
+        #[test]
+        fn dot_slash_prefix_is_only_normalized() {
+            let result = utils::calculate_archive_path(
+                Path::new("./patch000/file.txt"),
+                Path::new("./patch000/file.txt"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "patch000\\file.txt");
+        }
+
+        #[test]
+        fn result_uses_backslashes() {
+            let result = utils::calculate_archive_path(
+                Path::new("art/critters/file.frm"),
+                Path::new("art/critters/file.frm"),
+                None,
+                None,
+            )
+            .unwrap();
+            // Should use backslashes (archive format)
+            assert!(result.contains('\\') || !result.contains('/'));
+        }
+
+        #[test]
+        fn absolute_unix_path_strips_root_only() {
+            let result = utils::calculate_archive_path(
+                Path::new("/patch000/file.txt"),
+                Path::new("/patch000/file.txt"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "patch000\\file.txt");
+        }
+
+        #[test]
+        fn change_dir_becomes_archive_root() {
+            let result = utils::calculate_archive_path(
+                Path::new("mods/patch000/file.txt"),
+                Path::new("mods/patch000/file.txt"),
+                None,
+                Some(Path::new("mods")),
+            )
+            .unwrap();
+            assert_eq!(result, "patch000\\file.txt");
+        }
+
+        #[test]
+        fn change_dir_resolution_happens_before_target_dir() {
             let result = utils::calculate_archive_path(
                 Path::new("mods/patch000/file.txt"),
                 Path::new("mods/patch000/file.txt"),
+                Some("data"),
+                Some(Path::new("mods")),
+            )
+            .unwrap();
+            assert_eq!(result, "data\\patch000\\file.txt");
+        }
+
+        #[test]
+        fn normalizes_internal_dot_component() {
+            let result = utils::calculate_archive_path(
+                Path::new("art/./file.frm"),
+                Path::new("art/./file.frm"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "art\\file.frm");
+        }
+
+        #[test]
+        fn rejects_file_outside_change_dir() {
+            let result = utils::calculate_archive_path(
+                Path::new("outside/file.txt"),
+                Path::new("outside/file.txt"),
                 None,
                 Some(Path::new("mods")),
+            );
+            assert!(result.is_err());
+        }
+
+        #[cfg(windows)]
+        #[test]
+        fn absolute_windows_path_strips_drive_only() {
+            let result = utils::calculate_archive_path(
+                Path::new(r"C:\patch000\file.txt"),
+                Path::new(r"C:\patch000\file.txt"),
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(result, "patch000\\file.txt");
+        }
+    }
+
+    // ── resolve_add_input_path ────────────────────────────────────
+
+    mod resolve_add_input_path {
+        use super::*;
+        use std::fs;
+        use std::path::PathBuf;
+
+        #[test]
+        fn rejects_absolute_path_outside_change_dir() {
+            let parent = TempDir::new("abs-outside");
+            let mods = parent.join("mods");
+            fs::create_dir_all(&mods).unwrap();
+            let outside = parent.join("outside.txt");
+            fs::write(&outside, b"secret").unwrap();
+
+            let result = utils::resolve_add_input_path(&outside, Some(&mods));
+
+            assert!(
+                result.is_err(),
+                "absolute path outside change_dir must be rejected"
+            );
+        }
+
+        /// RAII guard that restores the original working directory on drop.
+        /// Ensures test cleanup even if the test panics.
+        struct CurrentDirGuard {
+            original: PathBuf,
+        }
+
+        impl CurrentDirGuard {
+            fn enter(new_dir: &std::path::Path) -> Self {
+                let original = std::env::current_dir().unwrap();
+                std::env::set_current_dir(new_dir).unwrap();
+                Self { original }
+            }
+        }
+
+        impl Drop for CurrentDirGuard {
+            fn drop(&mut self) {
+                let _ = std::env::set_current_dir(&self.original);
+            }
+        }
+
+        #[test]
+        fn resolves_relative_path_inside_change_dir() {
+            let root = TempDir::new("resolve-inside");
+            let file = root.join("patch000/file.txt");
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, b"test").unwrap();
+
+            let resolved =
+                utils::resolve_add_input_path(Path::new("patch000/file.txt"), Some(&root)).unwrap();
+
+            assert_eq!(resolved, fs::canonicalize(file).unwrap());
+        }
+
+        #[test]
+        fn allows_absolute_path_inside_change_dir() {
+            let root = TempDir::new("resolve-absolute");
+            let file = root.join("patch000/file.txt");
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, b"test").unwrap();
+
+            let resolved = utils::resolve_add_input_path(&file, Some(&root)).unwrap();
+
+            assert_eq!(resolved, fs::canonicalize(file).unwrap());
+        }
+
+        #[test]
+        fn leaves_relative_path_unchanged_without_change_dir() {
+            let root = TempDir::new("resolve-relative");
+            let file = root.join("patch000/file.txt");
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, b"test").unwrap();
+
+            let _guard = CurrentDirGuard::enter(&root);
+            let resolved =
+                utils::resolve_add_input_path(Path::new("patch000/file.txt"), None).unwrap();
+
+            assert_eq!(resolved, Path::new("patch000/file.txt"));
+        }
+
+        #[test]
+        fn rejects_parent_traversal_outside_change_dir() {
+            let parent = TempDir::new("resolve-parent");
+            let root = parent.join("mods");
+            fs::create_dir_all(&root).unwrap();
+            let outside = parent.join("outside.txt");
+            fs::write(&outside, b"test").unwrap();
+
+            let result = utils::resolve_add_input_path(Path::new("../outside.txt"), Some(&root));
+
+            assert!(result.is_err());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn rejects_symlinked_file_inside_change_dir() {
+            use std::os::unix::fs::symlink;
+
+            let root = TempDir::new("resolve-symlink-file");
+            let real_file = root.join("real.txt");
+            let link_file = root.join("link.txt");
+            fs::write(&real_file, b"real").unwrap();
+            symlink(&real_file, &link_file).unwrap();
+
+            let result = utils::resolve_add_input_path(Path::new("link.txt"), Some(&root));
+
+            assert!(result.is_err());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn rejects_symlinked_directory_inside_change_dir() {
+            use std::os::unix::fs::symlink;
+
+            let root = TempDir::new("resolve-symlink-dir");
+            let real_dir = root.join("real_dir");
+            let link_dir = root.join("link_dir");
+            fs::create_dir(&real_dir).unwrap();
+            symlink(&real_dir, &link_dir).unwrap();
+
+            let result = utils::resolve_add_input_path(Path::new("link_dir"), Some(&root));
+
+            assert!(result.is_err());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn rejects_symlink_to_outside_via_absolute_path() {
+            use std::os::unix::fs::symlink;
+
+            let parent = TempDir::new("resolve-symlink-abs");
+            let root = parent.join("mods");
+            let outside = parent.join("secret.txt");
+            fs::create_dir_all(&root).unwrap();
+            fs::write(&outside, b"secret").unwrap();
+
+            // Create symlink inside root that points to file outside
+            let link_in_root = root.join("link_to_outside");
+            symlink(&outside, &link_in_root).unwrap();
+
+            // Using absolute path to the symlink should still reject it
+            let result = utils::resolve_add_input_path(&link_in_root, Some(&root));
+
+            assert!(result.is_err());
+        }
+    }
+
+    // ── collect_files ─────────────────────────────────────────────
+
+    mod collect_files {
+        use super::*;
+        use std::fs;
+
+        #[cfg(unix)]
+        #[test]
+        fn reports_dangling_symlink() {
+            // A dangling symlink (target does not exist) must be skipped silently;
+            // the message includes the word "dangling" to distinguish it from a
+            // non-dangling symlink skip.
+            use std::os::unix::fs::symlink;
+
+            let root = TempDir::new("collect-dangling");
+            let real_file = root.join("real.txt");
+            fs::write(&real_file, b"real").unwrap();
+
+            // Create a symlink whose target does not exist
+            let dangling = root.join("dangling.txt");
+            symlink(root.join("nonexistent.txt"), &dangling).unwrap();
+
+            let files = utils::collect_files(&root, true, &Progress::default()).unwrap();
+
+            // The dangling symlink must be skipped; only real.txt is collected
+            assert_eq!(files.len(), 1);
+            assert!(files[0].ends_with("real.txt"));
+        }
+
+        #[test]
+        fn rejects_non_ascii_in_nested_subdir() {
+            // Files with non-ASCII names inside nested subdirectories must cause an error.
+            let root = TempDir::new("collect-nonascii");
+            let sub = root.join("sub");
+            fs::create_dir_all(&sub).unwrap();
+
+            // Write a file whose path will contain a non-ASCII character.
+            // We use a byte-level approach to create the file since the name itself
+            // is non-ASCII on the filesystem.
+            let non_ascii_name = "caf\u{00e9}.txt"; // "café.txt"
+            let file = sub.join(non_ascii_name);
+            fs::write(&file, b"content").unwrap();
+
+            let result = utils::collect_files(&root, true, &Progress::default());
+
+            assert!(
+                result.is_err(),
+                "non-ASCII filename in nested subdir must be rejected"
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn skips_symlinked_files_and_directories() {
+            use std::os::unix::fs::symlink;
+
+            let root = TempDir::new("collect-symlinks");
+            let real_file = root.join("patch000/real.txt");
+            let outside_dir = root.join("outside");
+            let outside_file = outside_dir.join("secret.txt");
+            let file_link = root.join("patch000/link.txt");
+            let dir_link = root.join("patch000/linkdir");
+
+            fs::create_dir_all(real_file.parent().unwrap()).unwrap();
+            fs::create_dir_all(&outside_dir).unwrap();
+            fs::write(&real_file, b"real").unwrap();
+            fs::write(&outside_file, b"secret").unwrap();
+            symlink(&outside_file, &file_link).unwrap();
+            symlink(&outside_dir, &dir_link).unwrap();
+
+            let files =
+                utils::collect_files(root.join("patch000"), true, &Progress::default()).unwrap();
+
+            assert_eq!(files, vec![real_file]);
+        }
+
+        #[test]
+        fn recursion_is_opt_in() {
+            let root = TempDir::new("collect-recursive");
+            fs::create_dir_all(root.join("sub/deeper")).unwrap();
+            fs::write(root.join("top.txt"), b"top").unwrap();
+            fs::write(root.join("sub/mid.txt"), b"mid").unwrap();
+            fs::write(root.join("sub/deeper/low.txt"), b"low").unwrap();
+
+            let shallow = utils::collect_files(&root, false, &Progress::default()).unwrap();
+            assert_eq!(shallow, vec![root.join("top.txt")]);
+
+            let mut deep = utils::collect_files(&root, true, &Progress::default()).unwrap();
+            deep.sort();
+            assert_eq!(
+                deep,
+                vec![
+                    root.join("sub/deeper/low.txt"),
+                    root.join("sub/mid.txt"),
+                    root.join("top.txt"),
+                ]
+            );
+        }
+
+        #[test]
+        fn excluded_files_are_left_out_and_counted() {
+            let root = TempDir::new("collect-excluded");
+            fs::create_dir_all(root.join("art/critters")).unwrap();
+            fs::write(root.join("art/critters/a.frm"), b"frame").unwrap();
+            fs::write(root.join("art/critters/._a.frm"), b"fork").unwrap();
+            fs::write(root.join("art/.DS_Store"), b"finder").unwrap();
+            fs::write(root.join("THUMBS.DB"), b"thumbs").unwrap();
+
+            let excludes = ExcludeList::new();
+            let found =
+                utils::collect_files_excluding(&root, true, &excludes, &Progress::default())
+                    .unwrap();
+            assert_eq!(found.files, vec![root.join("art/critters/a.frm")]);
+            assert_eq!(found.excluded, 3);
+
+            // Also when named directly
+            let found = utils::collect_files_excluding(
+                root.join("art/.DS_Store"),
+                false,
+                &excludes,
+                &Progress::default(),
             )
             .unwrap();
-            assert_eq!(result, "patch000\\file.txt");
+            assert!(found.files.is_empty());
+            assert_eq!(found.excluded, 1);
+
+            // collect_files keeps everything
+            let all = utils::collect_files(&root, true, &Progress::default()).unwrap();
+            assert_eq!(all.len(), 4);
+        }
+
+        #[test]
+        fn ignore_files_apply_to_their_subtree() {
+            let root = TempDir::new("collect-ignored");
+            fs::create_dir_all(root.join("scripts/templates")).unwrap();
+            fs::create_dir_all(root.join("docs")).unwrap();
+            fs::write(root.join(".dat3ignore"), "*.ssl\n/docs/\n").unwrap();
+            fs::write(root.join("scripts/.dat3ignore"), "!keep.ssl\ntemplates/\n").unwrap();
+            for file in [
+                "scripts/a.int",
+                "scripts/a.ssl",
+                "scripts/keep.ssl",
+                "scripts/templates/t.int",
+                "docs/readme.txt",
+                "other.ssl",
+            ] {
+                fs::write(root.join(file), b"data").unwrap();
+            }
+
+            let found = utils::collect_files_excluding(
+                &root,
+                true,
+                &ExcludeList::new(),
+                &Progress::default(),
+            )
+            .unwrap();
+            let mut files = found.files;
+            files.sort();
+            assert_eq!(
+                files,
+                vec![root.join("scripts/a.int"), root.join("scripts/keep.ssl")]
+            );
+            // scripts/a.ssl, other.ssl, docs and scripts/templates
+            assert_eq!(found.ignored, 4);
+
+            // Without ignore files, they're added like any other file
+            let found = utils::collect_files_excluding(
+                &root,
+                true,
+                &ExcludeList::empty(),
+                &Progress::default(),
+            )
+            .unwrap();
+            assert_eq!(found.files.len(), 8);
+            assert_eq!(found.ignored, 0);
+        }
+    }
+
+    // ── ExcludeList ────────────────────────────────────────────────
+
+    mod exclude_list {
+        use super::*;
+
+        #[test]
+        fn default_list_matches_junk_in_any_case() {
+            let excludes = ExcludeList::new();
+            for junk in [
+                "mod/.DS_Store",
+                "mod/.ds_store",
+                "mod/art/._hmjmps.frm",
+                "Thumbs.db",
+                "text/DESKTOP.INI",
+            ] {
+                assert!(excludes.is_excluded(Path::new(junk)), "{junk}");
+            }
+            for kept in [
+                "art/hmjmps.frm",
+                "art/_a.frm",
+                "thumbs.db.txt",
+                "data/.DS_Store/a.txt",
+            ] {
+                assert!(!excludes.is_excluded(Path::new(kept)), "{kept}");
+            }
+        }
+
+        #[test]
+        fn patterns_extend_the_list() {
+            let excludes = ExcludeList::new()
+                .with_patterns(&["*.BAK", "notes.txt"])
+                .unwrap();
+            assert!(excludes.is_excluded(Path::new("scripts/foo.bak")));
+            assert!(excludes.is_excluded(Path::new("NOTES.TXT")));
+            assert!(excludes.is_excluded(Path::new(".DS_Store")));
+
+            let only = ExcludeList::empty().with_patterns(&["*.bak"]).unwrap();
+            assert!(!only.is_excluded(Path::new(".DS_Store")));
+            assert!(!ExcludeList::empty().is_excluded(Path::new("Thumbs.db")));
+        }
+
+        #[test]
+        fn invalid_patterns_fail() {
+            let err = ExcludeList::new().with_patterns(&["[a"]).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid exclude pattern: [a"),
+                "{err}"
+            );
+        }
+    }
+
+    // ── DirectoryContents ──────────────────────────────────────────
+
+    mod directory_contents {
+        use super::*;
+        use std::fs;
+
+        fn policy() -> CompressionPolicy {
+            CompressionPolicy::new(CompressionLevel::new(9).unwrap())
+        }
+
+        #[test]
+        fn paths_are_relative_to_the_directory() {
+            let root = TempDir::new("contents-build");
+            fs::create_dir_all(root.join("art/critters")).unwrap();
+            fs::create_dir_all(root.join("scripts")).unwrap();
+            fs::write(root.join("art/critters/hmjmpsaa.frm"), vec![0; 4096]).unwrap();
+            fs::write(root.join("scripts/obj_dude.int"), vec![1; 100]).unwrap();
+            fs::write(root.join("readme.txt"), b"mod readme").unwrap();
+
+            let contents = DirectoryContents::new(&root, policy(), NameCase::Preserve).unwrap();
+            let paths: Vec<&str> = contents
+                .files
+                .iter()
+                .map(|(_, path)| path.as_str())
+                .collect();
+            assert_eq!(
+                paths,
+                [
+                    "art\\critters\\hmjmpsaa.frm",
+                    "readme.txt",
+                    "scripts\\obj_dude.int"
+                ]
+            );
+
+            let (archive, stats) = contents.build(false, &Progress::default()).unwrap();
+            assert!(!archive.is_dat1());
+            assert_eq!(stats.files_added, 3);
+            assert!(
+                archive
+                    .entry("art\\critters\\hmjmpsaa.frm")
+                    .unwrap()
+                    .compressed
+            );
+            assert_eq!(archive.read_file("readme.txt").unwrap(), b"mod readme");
+
+            let (archive, _) = contents.build(true, &Progress::default()).unwrap();
+            assert!(archive.is_dat1());
+            assert_eq!(archive.len(), 3);
+            assert_eq!(
+                archive.read_file("scripts/obj_dude.int").unwrap(),
+                vec![1; 100]
+            );
+        }
+
+        #[test]
+        fn lowercase_paths_and_collisions() {
+            let root = TempDir::new("contents-lowercase");
+            fs::create_dir_all(root.join("Art/Critters")).unwrap();
+            fs::write(root.join("Art/Critters/HMJMPSAA.FRM"), b"frm").unwrap();
+
+            let contents = DirectoryContents::new(&root, policy(), NameCase::Lower).unwrap();
+            assert_eq!(contents.files[0].1, "art\\critters\\hmjmpsaa.frm");
+            let (archive, _) = contents.build(false, &Progress::default()).unwrap();
+            assert_eq!(
+                archive
+                    .entries()
+                    .map(|f| f.name.as_str())
+                    .collect::<Vec<_>>(),
+                ["art\\critters\\hmjmpsaa.frm"]
+            );
+
+            fs::write(root.join("Art/Critters/hmjmpsaa.frm"), b"other").unwrap();
+            assert!(DirectoryContents::new(&root, policy(), NameCase::Preserve).is_ok());
+            assert!(DirectoryContents::new(&root, policy(), NameCase::Lower).is_err());
+        }
+
+        #[test]
+        fn empty_or_missing_directory_fails() {
+            let root = TempDir::new("contents-empty");
+            assert!(DirectoryContents::new(&root, policy(), NameCase::Preserve).is_err());
+            assert!(
+                DirectoryContents::new(&root.join("nope"), policy(), NameCase::Preserve).is_err()
+            );
+        }
+    }
+
+    // ── parse_response_line ───────────────────────────────────────
+
+    mod parse_response_line {
+        use super::*;
+        use crate::common::utils::ResponseLine;
+
+        fn parse(line: &str) -> Option<ResponseLine> {
+            utils::parse_response_line(line).unwrap()
+        }
+
+        fn name(name: &str, quoted: bool) -> Option<ResponseLine> {
+            Some(ResponseLine {
+                name: name.into(),
+                quoted,
+                dest: None,
+            })
+        }
+
+        #[test]
+        fn skips_blank_and_comment_lines() {
+            assert_eq!(parse(""), None);
+            assert_eq!(parse("   \t"), None);
+            assert_eq!(parse("# comment"), None);
+            assert_eq!(parse("   # indented comment"), None);
+        }
+
+        #[test]
+        fn trims_bare_names_and_strips_comments() {
+            assert_eq!(parse("  art/a.frm  "), name("art/a.frm", false));
+            assert_eq!(parse("art/my file.frm"), name("art/my file.frm", false));
+            assert_eq!(parse("art/a.frm # why"), name("art/a.frm", false));
+            // `#` inside a name isn't a comment
+            assert_eq!(parse("art/a#1.frm"), name("art/a#1.frm", false));
+            assert_eq!(parse("art/*.frm"), name("art/*.frm", false));
+        }
+
+        #[test]
+        fn keeps_quoted_names_exactly() {
+            assert_eq!(parse("\"  spaced.txt \""), name("  spaced.txt ", true));
+            assert_eq!(parse("\"a # b.txt\" # comment"), name("a # b.txt", true));
+            assert_eq!(parse("\"x => y\""), name("x => y", true));
+            assert_eq!(parse("\"@notes.txt\""), name("@notes.txt", true));
+        }
+
+        #[test]
+        fn parses_mappings() {
+            assert_eq!(
+                parse("build/a.int => scripts/a.int # generated"),
+                Some(ResponseLine {
+                    name: "build/a.int".into(),
+                    quoted: false,
+                    dest: Some("scripts/a.int".into()),
+                })
+            );
+            assert_eq!(
+                parse("\"my file.int\"=>\"scripts/my file.int\""),
+                Some(ResponseLine {
+                    name: "my file.int".into(),
+                    quoted: true,
+                    dest: Some("scripts/my file.int".into()),
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_malformed_lines() {
+            for line in [
+                "\"unterminated.txt",
+                "a\"b.txt",
+                "\"a\" b.txt",
+                "a.int =>",
+                "=> b.int",
+                "a => b => c",
+            ] {
+                assert!(
+                    utils::parse_response_line(line).is_err(),
+                    "{line:?} should be rejected"
+                );
+            }
+        }
+    }
+
+    // ── expand_response_files_for_archive ─────────────────────────
+
+    mod expand_response_files_for_archive {
+        use super::*;
+        use std::fs;
+
+        fn response_arg(path: &Path) -> String {
+            format!("@{}", path.display())
+        }
+
+        #[test]
+        fn expands_in_place_without_repeats() {
+            let root = TempDir::new("archive-args");
+            let inner = root.join("inner.txt");
+            let outer = root.join("outer.txt");
+            fs::write(&inner, "c.frm\n# comment\na.frm\n").unwrap();
+            fs::write(&outer, format!("b.frm\n{}\n", response_arg(&inner))).unwrap();
+
+            let args = [
+                "a.frm".to_string(),
+                response_arg(&outer),
+                "@@d.frm".to_string(),
+                response_arg(&inner),
+            ];
+            assert_eq!(
+                utils::expand_response_files_for_archive(&args).unwrap(),
+                ["a.frm", "b.frm", "c.frm", "@d.frm"]
+            );
         }
 
         #[test]
-        fn change_dir_resolution_happens_before_target_dir() {
-            let result = utils::calculate_archive_path(
-                Path::new("mods/patch000/file.txt"),
-                Path::new("mods/patch000/file.txt"),
-                Some("data"),
-                Some(Path::new("mods")),
-            )
-            .unwrap();
-            assert_eq!(result, "data\\patch000\\file.txt");
+        fn rejects_missing_and_recursive_response_files() {
+            let root = TempDir::new("archive-args-bad");
+            let missing = root.join("missing.txt");
+            assert!(utils::expand_response_files_for_archive(&[response_arg(&missing)]).is_err());
+
+            let broken = root.join("broken.txt");
+            fs::write(&broken, format!("{}\n", response_arg(&missing))).unwrap();
+            let err =
+                utils::expand_response_files_for_archive(&[response_arg(&broken)]).unwrap_err();
+            assert!(format!("{err:#}").contains("broken.txt"), "{err:#}");
+
+            let looping = root.join("loop.txt");
+            fs::write(&looping, format!("a.frm\n{}\n", response_arg(&looping))).unwrap();
+            let err =
+                utils::expand_response_files_for_archive(&[response_arg(&looping)]).unwrap_err();
+            assert!(format!("{err:#}").contains("includes itself"), "{err:#}");
         }
+    }
+
+    // ── expand_response_files_with_stripping ──────────────────────
+
+    mod expand_response_files_with_stripping {
+        use super::*;
+        use crate::common::utils::AddOperand;
+        use std::fs;
+        use std::path::PathBuf;
 
         #[test]
-        fn normalizes_internal_dot_component() {
-            let result = utils::calculate_archive_path(
-                Path::new("art/./file.frm"),
-                Path::new("art/./file.frm"),
-                None,
-                None,
+        fn dot_slash_prefix_does_not_enable_directory_stripping() {
+            let expanded =
+                utils::expand_response_files_with_stripping(&["./patch000/file.txt".into()], None)
+                    .unwrap();
+
+            assert_eq!(
+                expanded,
+                vec![AddOperand::from(PathBuf::from("./patch000/file.txt"))]
+            );
+        }
+
+        #[test]
+        fn allows_absolute_path_inside_change_dir() {
+            let root = TempDir::new("expand-abs-inside");
+            let file = root.join("file.txt");
+            fs::write(&file, b"test").unwrap();
+
+            let expanded = utils::expand_response_files_with_stripping(
+                &[file.to_str().unwrap().into()],
+                Some(&root),
             )
             .unwrap();
-            assert_eq!(result, "art\\file.frm");
+
+            assert_eq!(expanded, vec![AddOperand::from(file)]);
         }
 
         #[test]
-        fn rejects_file_outside_change_dir() {
-            let result = utils::calculate_archive_path(
-                Path::new("outside/file.txt"),
-                Path::new("outside/file.txt"),
-                None,
-                Some(Path::new("mods")),
+        fn passes_through_absolute_path_outside_change_dir() {
+            // expand_response_files_with_stripping no longer validates bounds —
+            // that is deferred to resolve_add_input_path (the sole security gate).
+            // The function must return the absolute path unchanged.
+            let parent = TempDir::new("expand-abs-outside");
+            let root = parent.join("mods");
+            let outside = parent.join("secret.txt");
+            fs::create_dir_all(&root).unwrap();
+            fs::write(&outside, b"secret").unwrap();
+
+            let result = utils::expand_response_files_with_stripping(
+                &[outside.to_str().unwrap().into()],
+                Some(&root),
             );
-            assert!(result.is_err());
+
+            // expand_response_files_with_stripping should succeed; security
+            // enforcement happens in resolve_add_input_path
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), vec![AddOperand::from(outside)]);
         }
 
-        #[cfg(windows)]
         #[test]
-        fn absolute_windows_path_strips_drive_only() {
-            let result = utils::calculate_archive_path(
-                Path::new(r"C:\patch000\file.txt"),
-                Path::new(r"C:\patch000\file.txt"),
-                None,
+        fn passes_through_absolute_path_not_in_change_dir() {
+            // Same as above: absolute path resolution is deferred to
+            // resolve_add_input_path, not done here.
+            let root = TempDir::new("expand-abs-parent");
+
+            // /etc/passwd may not exist; use a path that does to avoid NotFound
+            // inside glob expansion (no glob here, so it just passes through).
+            let result =
+                utils::expand_response_files_with_stripping(&["/etc/passwd".into()], Some(&root));
+
+            // No longer rejected at this stage — validation is in resolve_add_input_path
+            assert!(result.is_ok());
+            assert_eq!(
+                result.unwrap(),
+                vec![AddOperand::from(PathBuf::from("/etc/passwd"))]
+            );
+        }
+
+        #[test]
+        fn response_file_maps_sources_to_archive_paths() {
+            let root = TempDir::new("expand-mapping");
+            fs::write(root.join("one.int"), b"one").unwrap();
+            fs::write(root.join("two.int"), b"two").unwrap();
+            let list = root.join("files.txt");
+            fs::write(
+                &list,
+                "# comment\none.int => scripts/gl_one.int\n  two.int  =>  data\\two.int  \nthree.int\n",
+            )
+            .unwrap();
+
+            let expanded = utils::expand_response_files_with_stripping(
+                &[format!("@{}", list.display())],
+                Some(&root),
+            )
+            .unwrap();
+
+            assert_eq!(
+                expanded,
+                vec![
+                    AddOperand {
+                        source: root.join("one.int"),
+                        dest: Some("scripts/gl_one.int".into()),
+                    },
+                    AddOperand {
+                        source: root.join("two.int"),
+                        dest: Some("data\\two.int".into()),
+                    },
+                    AddOperand::from(root.join("three.int")),
+                ]
+            );
+        }
+
+        #[test]
+        fn mixes_response_files_with_explicit_files() {
+            let root = TempDir::new("expand-mixed");
+            let list = root.join("files.txt");
+            fs::write(&list, "b.txt => docs/b.txt\na.txt\n").unwrap();
+
+            let expanded = utils::expand_response_files_with_stripping(
+                &[
+                    "a.txt".into(),
+                    format!("@{}", list.display()),
+                    "b.txt".into(),
+                ],
                 None,
             )
             .unwrap();
-            assert_eq!(result, "patch000\\file.txt");
+
+            // The mapped b.txt is a different operand from the plain one
+            assert_eq!(
+                expanded,
+                vec![
+                    AddOperand::from(PathBuf::from("a.txt")),
+                    AddOperand {
+                        source: PathBuf::from("b.txt"),
+                        dest: Some("docs/b.txt".into()),
+                    },
+                    AddOperand::from(PathBuf::from("b.txt")),
+                ]
+            );
+        }
+
+        #[test]
+        fn response_file_rejects_malformed_mappings() {
+            let root = TempDir::new("expand-bad-mapping");
+            for line in ["a.int =>", "=> b.int", "*.int => scripts/x.int"] {
+                let list = root.join("files.txt");
+                fs::write(&list, format!("{line}\n")).unwrap();
+                let err = utils::expand_response_files_with_stripping(
+                    &[format!("@{}", list.display())],
+                    None,
+                )
+                .unwrap_err();
+                assert!(
+                    format!("{err}").contains(line),
+                    "error for {line:?} should quote the line: {err}"
+                );
+            }
         }
     }
 
-    // ── resolve_add_input_path ────────────────────────────────────
+    // ── to_system_path ─────────────────────────────────────────────
 
-    mod resolve_add_input_path {
+    mod to_system_path {
         use super::*;
-        use std::fs;
-        use std::path::PathBuf;
-        use std::time::{SystemTime, UNIX_EPOCH};
 
         #[test]
-        fn rejects_absolute_path_outside_change_dir() {
-            let unique = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos();
-            let parent = std::env::temp_dir().join(format!("dat3-abs-outside-{unique}"));
-            let mods = parent.join("mods");
-            fs::create_dir_all(&mods).unwrap();
-            let outside = parent.join("outside.txt");
-            fs::write(&outside, b"secret").unwrap();
-
-            let result = utils::resolve_add_input_path(&outside, Some(&mods));
-
-            assert!(
-                result.is_err(),
-                "absolute path outside change_dir must be rejected"
+        fn converts_backslashes_to_system_separator() {
+            let result = utils::to_system_path("ART\\CRITTERS\\FILE.FRM");
+            let expected = std::path::PathBuf::from(
+                "ART/CRITTERS/FILE.FRM".replace('/', std::path::MAIN_SEPARATOR_STR),
             );
-            fs::remove_dir_all(parent).unwrap();
+            assert_eq!(result, expected);
         }
+    }
 
-        /// RAII guard that restores the original working directory on drop.
-        /// Ensures test cleanup even if the test panics.
-        struct CurrentDirGuard {
-            original: PathBuf,
+    // ── ExtractionMode ─────────────────────────────────────────────
+
+    mod extraction_mode {
+        use super::*;
+
+        #[test]
+        fn preserve_structure_keeps_directories() {
+            let path =
+                ExtractionMode::PreserveStructure.output_path(Path::new("out"), "ART\\FILE.FRM");
+            assert_eq!(path, Path::new("out").join("ART").join("FILE.FRM"));
         }
 
-        impl CurrentDirGuard {
-            fn enter(new_dir: &std::path::Path) -> Self {
-                let original = std::env::current_dir().unwrap();
-                std::env::set_current_dir(new_dir).unwrap();
-                Self { original }
-            }
+        #[test]
+        fn flat_keeps_only_the_file_name() {
+            let path = ExtractionMode::Flat.output_path(Path::new("out"), "ART\\CRITTERS\\A.FRM");
+            assert_eq!(path, Path::new("out").join("A.FRM"));
         }
+    }
 
-        impl Drop for CurrentDirGuard {
-            fn drop(&mut self) {
-                let _ = std::env::set_current_dir(&self.original);
+    // ── ExtractOptions ─────────────────────────────────────────────
+
+    mod extract_options {
+        use super::*;
+
+        fn make_entry(name: &str, offset: u64) -> FileEntry {
+            FileEntry {
+                name: name.to_string(),
+                offset,
+                size: 10,
+                packed_size: 10,
+                compressed: false,
+                data: None,
+                original_index: None,
+                unsupported_compression: None,
+                stored_type: None,
+                directory_index: None,
+                nul_terminated_name: false,
             }
         }
 
-        fn make_temp_dir(name: &str) -> std::path::PathBuf {
-            let unique = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos();
-            let path = std::env::temp_dir().join(format!("dat3-{name}-{unique}"));
-            fs::create_dir_all(&path).unwrap();
-            path
+        fn names(files: &[&FileEntry]) -> Vec<String> {
+            files.iter().map(|f| f.name.clone()).collect()
         }
 
         #[test]
-        fn resolves_relative_path_inside_change_dir() {
-            let root = make_temp_dir("resolve-inside");
-            let file = root.join("patch000/file.txt");
-            fs::create_dir_all(file.parent().unwrap()).unwrap();
-            fs::write(&file, b"test").unwrap();
-
-            let resolved =
-                utils::resolve_add_input_path(Path::new("patch000/file.txt"), Some(&root)).unwrap();
-
-            assert_eq!(resolved, fs::canonicalize(file).unwrap());
-            fs::remove_dir_all(root).unwrap();
+        fn entries_are_ordered_by_offset() {
+            let (a, b, c) = (
+                make_entry("a", 200),
+                make_entry("b", 0),
+                make_entry("c", 100),
+            );
+            let mut files = vec![&a, &b, &c];
+            ExtractOptions::new(ExtractionMode::Flat).order_entries(&mut files);
+            assert_eq!(names(&files), ["b", "c", "a"]);
         }
 
         #[test]
-        fn allows_absolute_path_inside_change_dir() {
-            let root = make_temp_dir("resolve-absolute");
-            let file = root.join("patch000/file.txt");
-            fs::create_dir_all(file.parent().unwrap()).unwrap();
-            fs::write(&file, b"test").unwrap();
-
-            let resolved = utils::resolve_add_input_path(&file, Some(&root)).unwrap();
+        fn tree_order_keeps_selection_order() {
+            let (a, b) = (make_entry("a", 200), make_entry("b", 0));
+            let mut files = vec![&a, &b];
+            let options = ExtractOptions {
+                tree_order: true,
+                ..ExtractOptions::new(ExtractionMode::Flat)
+            };
+            options.order_entries(&mut files);
+            assert_eq!(names(&files), ["a", "b"]);
+        }
 
-            assert_eq!(resolved, fs::canonicalize(file).unwrap());
-            fs::remove_dir_all(root).unwrap();
+        fn stripping(prefix: &str) -> ExtractOptions {
+            ExtractOptions {
+                strip_prefix: Some(prefix.to_string()),
+                ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+            }
         }
 
         #[test]
-        fn leaves_relative_path_unchanged_without_change_dir() {
-            let root = make_temp_dir("resolve-relative");
-            let file = root.join("patch000/file.txt");
-            fs::create_dir_all(file.parent().unwrap()).unwrap();
-            fs::write(&file, b"test").unwrap();
+        fn strip_prefix_output_path() {
+            let options = stripping("Text/English/");
+            assert_eq!(
+                options.output_path(Path::new("out"), "TEXT\\ENGLISH\\DIALOG\\A.MSG"),
+                Path::new("out").join("DIALOG").join("A.MSG")
+            );
+        }
 
-            let _guard = CurrentDirGuard::enter(&root);
-            let resolved =
-                utils::resolve_add_input_path(Path::new("patch000/file.txt"), None).unwrap();
+        #[test]
+        fn strip_prefix_checks_every_entry() {
+            let (a, b) = (
+                make_entry("text\\english\\a.msg", 0),
+                make_entry("text\\german\\a.msg", 0),
+            );
+            let options = stripping("text/english");
+            assert!(options.check_output_paths(&[&a]).is_ok());
 
-            assert_eq!(resolved, Path::new("patch000/file.txt"));
-            fs::remove_dir_all(root).unwrap();
+            let err = options.check_output_paths(&[&a, &b]).unwrap_err();
+            let message = err.to_string();
+            assert!(message.starts_with("1 of 2 entries"), "{message}");
+            assert!(message.contains("text/german/a.msg"), "{message}");
         }
 
         #[test]
-        fn rejects_parent_traversal_outside_change_dir() {
-            let parent = make_temp_dir("resolve-parent");
-            let root = parent.join("mods");
-            fs::create_dir_all(&root).unwrap();
-            let outside = parent.join("outside.txt");
-            fs::write(&outside, b"test").unwrap();
+        fn strip_prefix_rejects_flat_extraction() {
+            let a = make_entry("text\\english\\a.msg", 0);
+            let options = ExtractOptions {
+                strip_prefix: Some("text".to_string()),
+                ..ExtractOptions::new(ExtractionMode::Flat)
+            };
+            assert!(options.check_output_paths(&[&a]).is_err());
 
-            let result = utils::resolve_add_input_path(Path::new("../outside.txt"), Some(&root));
+            let options = ExtractOptions {
+                strip_components: 1,
+                ..ExtractOptions::new(ExtractionMode::Flat)
+            };
+            assert!(options.check_output_paths(&[&a]).is_err());
+        }
 
-            assert!(result.is_err());
-            fs::remove_dir_all(parent).unwrap();
+        #[test]
+        fn strip_components_at_varying_depths() {
+            let options = ExtractOptions {
+                strip_components: 1,
+                ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+            };
+            assert_eq!(
+                options.output_path(Path::new("out"), "data\\art\\a.frm"),
+                Path::new("out").join("art").join("a.frm")
+            );
+            assert_eq!(
+                options.output_path(Path::new("out"), "data\\b.frm"),
+                Path::new("out").join("b.frm")
+            );
+            assert_eq!(options.kept_path("c.frm"), None);
         }
 
-        #[cfg(unix)]
         #[test]
-        fn rejects_symlinked_file_inside_change_dir() {
-            use std::os::unix::fs::symlink;
+        fn strip_components_after_strip_prefix() {
+            let options = ExtractOptions {
+                strip_components: 1,
+                ..stripping("text")
+            };
+            assert_eq!(
+                options.kept_path("text\\english\\a.msg").as_deref(),
+                Some("a.msg")
+            );
+            assert_eq!(options.kept_path("text\\b.msg"), None);
+        }
 
-            let root = make_temp_dir("resolve-symlink-file");
-            let real_file = root.join("real.txt");
-            let link_file = root.join("link.txt");
-            fs::write(&real_file, b"real").unwrap();
-            symlink(&real_file, &link_file).unwrap();
+        #[test]
+        fn lowercase_output_paths() {
+            let options = ExtractOptions {
+                case: NameCase::Lower,
+                ..stripping("Data")
+            };
+            assert_eq!(
+                options.output_path(Path::new("out"), "DATA\\Art\\Foo.FRM"),
+                Path::new("out").join("art").join("foo.frm")
+            );
+        }
 
-            let result = utils::resolve_add_input_path(Path::new("link.txt"), Some(&root));
+        #[test]
+        fn lowercase_collisions_fail() {
+            let (a, b, c) = (
+                make_entry("art\\Foo.FRM", 0),
+                make_entry("art\\FOO.frm", 0),
+                make_entry("ART\\bar.frm", 0),
+            );
+            let lower = |mode| ExtractOptions {
+                case: NameCase::Lower,
+                ..ExtractOptions::new(mode)
+            };
+            let options = lower(ExtractionMode::PreserveStructure);
+            assert!(options.check_output_paths(&[&a, &c]).is_ok());
+            let err = options.check_output_paths(&[&a, &b, &c]).unwrap_err();
+            assert!(err.to_string().contains("would merge"), "{err}");
+
+            // The same name twice isn't a collision folding causes
+            assert!(options.check_output_paths(&[&a, &a]).is_ok());
+
+            // Flat extraction compares file names only
+            let d = make_entry("text\\Foo.frm", 0);
+            assert!(options.check_output_paths(&[&a, &d]).is_ok());
+            assert!(lower(ExtractionMode::Flat)
+                .check_output_paths(&[&a, &d])
+                .is_err());
+        }
+
+        #[test]
+        fn shallow_entries_are_skipped_and_reported() {
+            let (a, b, c) = (
+                make_entry("data\\art\\a.frm", 0),
+                make_entry("data\\b.frm", 0),
+                make_entry("c.frm", 0),
+            );
+            let options = ExtractOptions {
+                strip_components: 2,
+                ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+            };
+            let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let progress = Progress::with_events({
+                let reported = reported.clone();
+                move |event| {
+                    if let Event::SkippedShallow { name, .. } = event {
+                        reported.lock().unwrap().push(name.to_string());
+                    }
+                }
+            });
 
-            assert!(result.is_err());
-            fs::remove_dir_all(root).unwrap();
+            let mut files = vec![&a, &b, &c];
+            assert_eq!(options.skip_shallow_entries(&mut files, &progress), 2);
+            assert_eq!(names(&files), ["data\\art\\a.frm"]);
+            assert_eq!(*reported.lock().unwrap(), ["data\\b.frm", "c.frm"]);
         }
 
-        #[cfg(unix)]
         #[test]
-        fn rejects_symlinked_directory_inside_change_dir() {
-            use std::os::unix::fs::symlink;
+        fn duplicate_names_keep_the_last_entry() {
+            let (a, b, c, d) = (
+                make_entry("art\\foo.frm", 0),
+                make_entry("art\\bar.frm", 10),
+                make_entry("ART\\FOO.FRM", 20),
+                make_entry("art\\foo.frm", 30),
+            );
+            let options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+            let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let progress = Progress::with_events({
+                let reported = reported.clone();
+                move |event| {
+                    if let Event::SkippedDuplicateName { name, kept } = event {
+                        reported
+                            .lock()
+                            .unwrap()
+                            .push((name.to_string(), kept.to_string()));
+                    }
+                }
+            });
 
-            let root = make_temp_dir("resolve-symlink-dir");
-            let real_dir = root.join("real_dir");
-            let link_dir = root.join("link_dir");
-            fs::create_dir(&real_dir).unwrap();
-            symlink(&real_dir, &link_dir).unwrap();
+            let mut files = vec![&a, &b, &c, &d];
+            assert_eq!(options.skip_duplicate_names(&mut files, &progress), 2);
+            assert_eq!(files.iter().map(|f| f.offset).collect::<Vec<_>>(), [10, 30]);
+            let kept = "art\\foo.frm".to_string();
+            assert_eq!(
+                *reported.lock().unwrap(),
+                [
+                    ("art\\foo.frm".to_string(), kept.clone()),
+                    ("ART\\FOO.FRM".to_string(), kept)
+                ]
+            );
 
-            let result = utils::resolve_add_input_path(Path::new("link_dir"), Some(&root));
+            let mut distinct = vec![&a, &b];
+            assert_eq!(options.skip_duplicate_names(&mut distinct, &progress), 0);
+            assert_eq!(distinct.len(), 2);
+
+            // Folding case only skips the same spelling; the rest collide
+            let lower = ExtractOptions {
+                case: NameCase::Lower,
+                ..options
+            };
+            let mut files = vec![&a, &b, &c, &d];
+            assert_eq!(lower.skip_duplicate_names(&mut files, &progress), 1);
+            assert_eq!(
+                files.iter().map(|f| f.offset).collect::<Vec<_>>(),
+                [10, 20, 30]
+            );
+            assert!(lower.check_output_paths(&files).is_err());
+        }
+    }
 
-            assert!(result.is_err());
-            fs::remove_dir_all(root).unwrap();
+    // ── Extracting to disk ─────────────────────────────────────────
+
+    mod extract_to_disk {
+        use super::*;
+        use std::fs;
+        use std::sync::{Arc, Mutex};
+        use std::time::UNIX_EPOCH;
+
+        fn sample(dat1: bool) -> DatArchive {
+            let entries = vec![
+                ("art\\a.frm".to_string(), b"frame ".repeat(40)),
+                ("text\\b.msg".to_string(), b"hello".to_vec()),
+            ];
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+        }
+
+        /// Extract `archive` into `out` with `update`, returning the written
+        /// and up-to-date counts
+        fn extract(archive: &DatArchive, out: &Path, checksum: bool) -> (usize, usize) {
+            let counts = Arc::new(Mutex::new((0, 0)));
+            let progress = Progress::with_events({
+                let counts = counts.clone();
+                move |event| {
+                    if let Event::ExtractFinished {
+                        written, current, ..
+                    } = event
+                    {
+                        *counts.lock().unwrap() = (written, current);
+                    }
+                }
+            });
+            let options = ExtractOptions {
+                update: true,
+                checksum,
+                ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+            };
+            archive.extract(out, &[], &options, &progress).unwrap();
+            let counts = *counts.lock().unwrap();
+            counts
+        }
+
+        #[test]
+        fn files_of_the_same_size_are_left_alone() {
+            let root = TempDir::new("extract-update");
+            for dat1 in [false, true] {
+                let out = root.join(if dat1 { "dat1" } else { "dat2" });
+                let archive = sample(dat1);
+
+                assert_eq!(extract(&archive, &out, false), (2, 0));
+                assert_eq!(extract(&archive, &out, false), (0, 2));
+
+                // Same size, different bytes: only --checksum notices
+                fs::write(out.join("text/b.msg"), b"HELLO").unwrap();
+                assert_eq!(extract(&archive, &out, false), (0, 2));
+                assert_eq!(fs::read(out.join("text/b.msg")).unwrap(), b"HELLO");
+                assert_eq!(extract(&archive, &out, true), (1, 1));
+                assert_eq!(fs::read(out.join("text/b.msg")).unwrap(), b"hello");
+
+                // A different size is always rewritten
+                fs::write(out.join("art/a.frm"), b"short").unwrap();
+                assert_eq!(extract(&archive, &out, false), (1, 1));
+                assert_eq!(
+                    fs::read(out.join("art/a.frm")).unwrap(),
+                    b"frame ".repeat(40)
+                );
+            }
         }
 
-        #[cfg(unix)]
         #[test]
-        fn rejects_symlink_to_outside_via_absolute_path() {
-            use std::os::unix::fs::symlink;
+        fn raw_writes_stored_bytes() {
+            use std::io::Read;
+
+            let root = TempDir::new("extract-raw");
+            let archive = sample(false);
+            let frame = archive.entries().find(|f| f.name == "art\\a.frm").unwrap();
+            assert!(frame.compressed);
+            let packed_size = frame.packed_size as usize;
+
+            let raw = |keep_names| ExtractOptions {
+                raw: true,
+                raw_keep_names: keep_names,
+                ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+            };
+            let out = root.join("suffixed");
+            archive
+                .extract(&out, &[], &raw(false), &Progress::default())
+                .unwrap();
+            let stored = fs::read(out.join("art/a.frm.z")).unwrap();
+            assert_eq!(stored.len(), packed_size);
+            let mut content = Vec::new();
+            flate2::read::ZlibDecoder::new(&stored[..])
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, b"frame ".repeat(40));
+            assert!(!out.join("art/a.frm").exists());
+            assert_eq!(fs::read(out.join("text/b.msg")).unwrap(), b"hello");
+
+            let out = root.join("kept");
+            archive
+                .extract(&out, &[], &raw(true), &Progress::default())
+                .unwrap();
+            assert_eq!(fs::read(out.join("art/a.frm")).unwrap(), stored);
+        }
+
+        #[test]
+        fn written_files_get_the_given_mtime() {
+            let root = TempDir::new("extract-mtime");
+            let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_717_200_000);
+            for dat1 in [false, true] {
+                let out = root.join(if dat1 { "dat1" } else { "dat2" });
+                let options = ExtractOptions {
+                    mtime: Some(mtime),
+                    ..ExtractOptions::new(ExtractionMode::PreserveStructure)
+                };
+                sample(dat1)
+                    .extract(&out, &[], &options, &Progress::default())
+                    .unwrap();
+                for name in ["art/a.frm", "text/b.msg"] {
+                    let modified = fs::metadata(out.join(name)).unwrap().modified().unwrap();
+                    assert_eq!(modified, mtime, "{name}");
+                }
+            }
+        }
+    }
 
-            let parent = make_temp_dir("resolve-symlink-abs");
-            let root = parent.join("mods");
-            let outside = parent.join("secret.txt");
-            fs::create_dir_all(&root).unwrap();
-            fs::write(&outside, b"secret").unwrap();
+    // ── read_file_to ───────────────────────────────────────────────
 
-            // Create symlink inside root that points to file outside
-            let link_in_root = root.join("link_to_outside");
-            symlink(&outside, &link_in_root).unwrap();
+    mod read_file_to {
+        use super::*;
+        use crate::error::DatError;
 
-            // Using absolute path to the symlink should still reject it
-            let result = utils::resolve_add_input_path(&link_in_root, Some(&root));
+        /// An archive of a compressible and a small entry, saved and opened
+        /// again so content is read from the file's data
+        fn saved_sample(dat1: bool) -> DatArchive {
+            let entries = vec![
+                ("art\\a.frm".to_string(), b"frame ".repeat(40)),
+                ("text\\b.msg".to_string(), b"hello".to_vec()),
+            ];
+            let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+            let (archive, _) = DatArchive::from_entries(dat1, entries, &policy).unwrap();
+            let dir = TempDir::new("read-to");
+            let path = dir.join("sample.dat");
+            archive.save(&path).unwrap();
+            DatArchive::open(&path).unwrap()
+        }
+
+        #[test]
+        fn streams_what_read_file_returns() {
+            for dat1 in [false, true] {
+                let archive = saved_sample(dat1);
+                for name in ["art/a.frm", "TEXT\\B.MSG"] {
+                    let mut out = Vec::new();
+                    let written = archive.read_file_to(name, &mut out).unwrap();
+                    assert_eq!(out, archive.read_file(name).unwrap(), "{name}");
+                    assert_eq!(written, out.len() as u64);
+                }
+            }
+        }
 
-            assert!(result.is_err());
-            fs::remove_dir_all(parent).unwrap();
+        #[test]
+        fn missing_entry_writes_nothing() {
+            let archive = saved_sample(false);
+            let mut out = Vec::new();
+            let err = archive.read_file_to("text/none.msg", &mut out).unwrap_err();
+            assert!(matches!(err, DatError::EntryNotFound { .. }));
+            assert!(out.is_empty());
+        }
+
+        #[test]
+        fn failing_writer_is_a_write_error() {
+            let archive = saved_sample(false);
+            let mut buffer = [0u8; 16];
+            let err = archive
+                .read_file_to("art/a.frm", &mut &mut buffer[..])
+                .unwrap_err();
+            assert!(matches!(err, DatError::Write { ref name, .. } if name == "art/a.frm"));
         }
     }
 
-    // ── collect_files ─────────────────────────────────────────────
+    // ── FileEntry constructors ─────────────────────────────────────
 
-    mod collect_files {
+    mod file_entry {
         use super::*;
-        use std::fs;
-        use std::time::{SystemTime, UNIX_EPOCH};
 
-        fn make_temp_dir(name: &str) -> std::path::PathBuf {
-            let unique = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos();
-            let path = std::env::temp_dir().join(format!("dat3-{name}-{unique}"));
-            fs::create_dir_all(&path).unwrap();
-            path
+        #[test]
+        fn with_data_sets_packed_size() {
+            let data = vec![1, 2, 3, 4, 5];
+            let entry = FileEntry::with_data("test.txt".to_string(), data, false);
+            assert_eq!(entry.packed_size, 5);
+            assert_eq!(entry.offset, 0);
+            assert!(!entry.compressed);
         }
 
-        #[cfg(unix)]
         #[test]
-        fn reports_dangling_symlink() {
-            // A dangling symlink (target does not exist) must be skipped silently;
-            // the message includes the word "dangling" to distinguish it from a
-            // non-dangling symlink skip.
-            use std::os::unix::fs::symlink;
+        fn with_compression_data_tracks_both_sizes() {
+            let original = vec![1, 2, 3, 4, 5, 6, 7, 8];
+            let compressed = vec![1, 2, 3];
+            let entry =
+                FileEntry::with_compression_data("test.txt".to_string(), original, compressed);
+            assert_eq!(entry.size, 8);
+            assert_eq!(entry.packed_size, 3);
+            assert!(entry.compressed);
+        }
+    }
 
-            let root = make_temp_dir("collect-dangling");
-            let real_file = root.join("real.txt");
-            fs::write(&real_file, b"real").unwrap();
+    // ── ExtensionFilter ────────────────────────────────────────────
 
-            // Create a symlink whose target does not exist
-            let dangling = root.join("dangling.txt");
-            symlink(root.join("nonexistent.txt"), &dangling).unwrap();
+    mod extension_filter {
+        use super::*;
+        use utils::{matches_path_prefix, ExtensionFilter};
+
+        fn exts(list: &[&str]) -> Vec<String> {
+            list.iter().map(|ext| ext.to_string()).collect()
+        }
 
-            let files = utils::collect_files(&root).unwrap();
+        #[test]
+        fn keeps_listed_extensions_in_any_case() {
+            let filter = ExtensionFilter::new(&exts(&["frm", ".MSG"]), &[]);
+            assert!(filter.matches("ART\\CRITTERS\\A.FRM"));
+            assert!(filter.matches("text/english/dialog/b.msg"));
+            assert!(!filter.matches("art/a.fr"));
+            assert!(!filter.matches("art/a.frm2"));
+            assert!(!filter.matches("art/frm"));
+            assert!(!filter.matches("art/.frm"));
+        }
 
-            // The dangling symlink must be skipped; only real.txt is collected
-            assert_eq!(files.len(), 1);
-            assert!(files[0].ends_with("real.txt"));
+        #[test]
+        fn drops_excluded_extensions() {
+            let filter = ExtensionFilter::new(&[], &exts(&["acm"]));
+            assert!(filter.matches("art/a.frm"));
+            assert!(filter.matches("readme"));
+            assert!(!filter.matches("sound/music/a.ACM"));
 
-            fs::remove_dir_all(root).unwrap();
+            let both = ExtensionFilter::new(&exts(&["frm", "acm"]), &exts(&["acm"]));
+            assert!(both.matches("a.frm"));
+            assert!(!both.matches("a.acm"));
         }
 
         #[test]
-        fn rejects_non_ascii_in_nested_subdir() {
-            // Files with non-ASCII names inside nested subdirectories must cause an error.
-            let root = make_temp_dir("collect-nonascii");
-            let sub = root.join("sub");
-            fs::create_dir_all(&sub).unwrap();
+        fn empty_filter_keeps_everything() {
+            let filter = ExtensionFilter::new(&exts(&["", "."]), &[]);
+            assert!(filter.is_empty());
+            assert!(filter.matches("anything"));
+        }
 
-            // Write a file whose path will contain a non-ASCII character.
-            // We use a byte-level approach to create the file since the name itself
-            // is non-ASCII on the filesystem.
-            let non_ascii_name = "caf\u{00e9}.txt"; // "café.txt"
-            let file = sub.join(non_ascii_name);
-            fs::write(&file, b"content").unwrap();
+        #[test]
+        fn prefixes_match_whole_components() {
+            assert!(matches_path_prefix("ART\\CRITTERS\\A.FRM", "art/critters"));
+            assert!(matches_path_prefix("ART\\CRITTERS\\A.FRM", "Art\\"));
+            assert!(matches_path_prefix(
+                "ART\\CRITTERS\\A.FRM",
+                "art/critters/a.frm"
+            ));
+            assert!(!matches_path_prefix("ART\\CRITTERS\\A.FRM", "art/crit"));
+            assert!(!matches_path_prefix("ART\\CRITTERS\\A.FRM", "critters"));
+            assert!(matches_path_prefix("ART\\CRITTERS\\A.FRM", "art/*/a.*"));
+        }
+    }
 
-            let result = utils::collect_files(&root);
+    // ── ListingSort ────────────────────────────────────────────────
 
-            assert!(
-                result.is_err(),
-                "non-ASCII filename in nested subdir must be rejected"
-            );
+    mod listing_sort {
+        use super::*;
+        use utils::ListingSort;
 
-            fs::remove_dir_all(root).unwrap();
+        fn entry(name: &str, size: u32, packed_size: u32, compressed: bool) -> FileEntry {
+            FileEntry {
+                name: name.to_string(),
+                offset: 0,
+                size,
+                packed_size,
+                compressed,
+                data: None,
+                original_index: None,
+                unsupported_compression: None,
+                stored_type: None,
+                directory_index: None,
+                nul_terminated_name: false,
+            }
         }
 
-        #[cfg(unix)]
-        #[test]
-        fn skips_symlinked_files_and_directories() {
-            use std::os::unix::fs::symlink;
+        fn sorted(entries: &[FileEntry], sort: ListingSort, descending: bool) -> Vec<&str> {
+            let mut refs: Vec<&FileEntry> = entries.iter().collect();
+            sort.sort(&mut refs, descending);
+            refs.iter().map(|entry| entry.name.as_str()).collect()
+        }
 
-            let root = make_temp_dir("collect-symlinks");
-            let real_file = root.join("patch000/real.txt");
-            let outside_dir = root.join("outside");
-            let outside_file = outside_dir.join("secret.txt");
-            let file_link = root.join("patch000/link.txt");
-            let dir_link = root.join("patch000/linkdir");
+        fn entries() -> Vec<FileEntry> {
+            vec![
+                entry("b\\stored.txt", 100, 100, false),
+                entry("A\\half.frm", 400, 200, true),
+                entry("empty.txt", 0, 0, false),
+                entry("a\\quarter.frm", 400, 100, true),
+            ]
+        }
 
-            fs::create_dir_all(real_file.parent().unwrap()).unwrap();
-            fs::create_dir_all(&outside_dir).unwrap();
-            fs::write(&real_file, b"real").unwrap();
-            fs::write(&outside_file, b"secret").unwrap();
-            symlink(&outside_file, &file_link).unwrap();
-            symlink(&outside_dir, &dir_link).unwrap();
+        #[test]
+        fn sorts_by_each_key() {
+            let entries = entries();
+            assert_eq!(
+                sorted(&entries, ListingSort::Name, false),
+                [
+                    "A\\half.frm",
+                    "a\\quarter.frm",
+                    "b\\stored.txt",
+                    "empty.txt"
+                ]
+            );
+            assert_eq!(
+                sorted(&entries, ListingSort::Size, false),
+                [
+                    "empty.txt",
+                    "b\\stored.txt",
+                    "A\\half.frm",
+                    "a\\quarter.frm"
+                ]
+            );
+            assert_eq!(
+                sorted(&entries, ListingSort::Packed, false),
+                [
+                    "empty.txt",
+                    "b\\stored.txt",
+                    "a\\quarter.frm",
+                    "A\\half.frm"
+                ]
+            );
+        }
 
-            let files = utils::collect_files(root.join("patch000")).unwrap();
+        #[test]
+        fn ratio_treats_stored_and_empty_entries_as_one() {
+            let entries = entries();
+            assert_eq!(utils::compression_ratio(&entries[0]), 1.0);
+            assert_eq!(utils::compression_ratio(&entries[2]), 1.0);
+            assert_eq!(utils::compression_ratio(&entries[3]), 0.25);
+            assert_eq!(
+                utils::compression_ratio(&entry("odd.frm", 0, 10, true)),
+                1.0
+            );
+            assert_eq!(
+                sorted(&entries, ListingSort::Ratio, false),
+                [
+                    "a\\quarter.frm",
+                    "A\\half.frm",
+                    "b\\stored.txt",
+                    "empty.txt"
+                ]
+            );
+        }
 
-            assert_eq!(files, vec![real_file]);
-            fs::remove_dir_all(root).unwrap();
+        #[test]
+        fn descending_keeps_ties_in_archive_order() {
+            let entries = entries();
+            assert_eq!(
+                sorted(&entries, ListingSort::Size, true),
+                [
+                    "A\\half.frm",
+                    "a\\quarter.frm",
+                    "b\\stored.txt",
+                    "empty.txt"
+                ]
+            );
+            assert_eq!(
+                sorted(&entries, ListingSort::Ratio, true),
+                [
+                    "b\\stored.txt",
+                    "empty.txt",
+                    "A\\half.frm",
+                    "a\\quarter.frm"
+                ]
+            );
         }
     }
 
-    // ── expand_response_files_with_stripping ──────────────────────
+    // ── ListingLayout ──────────────────────────────────────────────
 
-    mod expand_response_files_with_stripping {
+    mod listing_layout {
         use super::*;
-        use std::fs;
-        use std::time::{SystemTime, UNIX_EPOCH};
+        use utils::ListingLayout;
 
-        fn make_temp_dir(name: &str) -> std::path::PathBuf {
-            let unique = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos();
-            let path = std::env::temp_dir().join(format!("dat3-{name}-{unique}"));
-            fs::create_dir_all(&path).unwrap();
-            path
+        fn make_entry(name: &str) -> FileEntry {
+            FileEntry {
+                name: name.to_string(),
+                offset: 0,
+                size: 1234,
+                packed_size: 567,
+                compressed: true,
+                data: None,
+                original_index: None,
+                unsupported_compression: None,
+                stored_type: None,
+                directory_index: None,
+                nul_terminated_name: false,
+            }
         }
 
         #[test]
-        fn dot_slash_prefix_does_not_enable_directory_stripping() {
-            let expanded =
-                utils::expand_response_files_with_stripping(&["./patch000/file.txt".into()], None)
-                    .unwrap();
+        fn no_width_keeps_full_table() {
+            let layout = ListingLayout::for_width(None, 200);
+            assert!(layout.show_packed);
+            assert!(layout.show_comp);
+            assert_eq!(layout.name_width, None);
+        }
 
-            assert_eq!(expanded, vec![Path::new("./patch000/file.txt")]);
+        #[test]
+        fn no_width_matches_legacy_format() {
+            let layout = ListingLayout::for_width(None, 0);
+            let entry = make_entry("ART\\FILE.FRM");
+            assert_eq!(
+                layout.header(),
+                format!("{:>11} {:>11}  {:>4}  Name", "Size", "Packed", "Comp")
+            );
+            assert_eq!(layout.rule(), "-".repeat(50));
+            #[cfg(not(windows))]
+            assert_eq!(
+                layout.row(&entry),
+                format!("{:>11} {:>11}  {:>4}  {}", 1234, 567, "Yes", "ART/FILE.FRM")
+            );
         }
 
         #[test]
-        fn allows_absolute_path_inside_change_dir() {
-            let root = make_temp_dir("expand-abs-inside");
-            let file = root.join("file.txt");
-            fs::write(&file, b"test").unwrap();
+        fn styled_row_marks_parts_after_padding() {
+            let layout = ListingLayout::for_width(None, 0);
+            let mut entry = make_entry("ART\\CRITTERS\\FILE.FRM");
+            let mark = |part: utils::RowPart, text: &str| format!("<{part:?}>{text}</>");
+            #[cfg(not(windows))]
+            assert_eq!(
+                layout.styled_row(&entry, mark),
+                format!(
+                    "{:>11} {:>11}  <Compressed> Yes</>  <Directory>ART/CRITTERS/</>FILE.FRM",
+                    1234, 567
+                )
+            );
 
-            let expanded = utils::expand_response_files_with_stripping(
-                &[file.to_str().unwrap().into()],
-                Some(&root),
-            )
-            .unwrap();
+            entry.compressed = false;
+            entry.name = "README.TXT".into();
+            assert!(layout
+                .styled_row(&entry, mark)
+                .ends_with("<Stored>  No</>  README.TXT"));
+        }
 
-            assert_eq!(expanded, vec![file]);
-            fs::remove_dir_all(root).unwrap();
+        #[test]
+        fn wide_terminal_keeps_all_columns() {
+            let layout = ListingLayout::for_width(Some(200), 40);
+            assert!(layout.show_packed && layout.show_comp);
+            assert_eq!(layout.name_width, None);
         }
 
         #[test]
-        fn passes_through_absolute_path_outside_change_dir() {
-            // expand_response_files_with_stripping no longer validates bounds —
-            // that is deferred to resolve_add_input_path (the sole security gate).
-            // The function must return the absolute path unchanged.
-            let parent = make_temp_dir("expand-abs-outside");
-            let root = parent.join("mods");
-            let outside = parent.join("secret.txt");
-            fs::create_dir_all(&root).unwrap();
-            fs::write(&outside, b"secret").unwrap();
+        fn drops_comp_column_first() {
+            // Full prefix is 31 columns, without Comp it is 25
+            let layout = ListingLayout::for_width(Some(80), 52);
+            assert!(layout.show_packed);
+            assert!(!layout.show_comp);
+            assert_eq!(layout.name_width, None);
+        }
 
-            let result = utils::expand_response_files_with_stripping(
-                &[outside.to_str().unwrap().into()],
-                Some(&root),
-            );
+        #[test]
+        fn drops_packed_column_second() {
+            let layout = ListingLayout::for_width(Some(80), 60);
+            assert!(!layout.show_packed);
+            assert!(!layout.show_comp);
+            assert_eq!(layout.name_width, None);
+        }
 
-            // expand_response_files_with_stripping should succeed; security
-            // enforcement happens in resolve_add_input_path
-            assert!(result.is_ok());
-            assert_eq!(result.unwrap(), vec![outside]);
-            fs::remove_dir_all(parent).unwrap();
+        #[test]
+        fn truncates_names_when_columns_are_gone() {
+            let layout = ListingLayout::for_width(Some(40), 60);
+            assert!(!layout.show_packed && !layout.show_comp);
+            assert_eq!(layout.name_width, Some(27));
         }
 
         #[test]
-        fn passes_through_absolute_path_not_in_change_dir() {
-            // Same as above: absolute path resolution is deferred to
-            // resolve_add_input_path, not done here.
-            let root = make_temp_dir("expand-abs-parent");
+        fn rows_never_exceed_width() {
+            let name = "art\\critters\\some\\very\\deep\\directory\\hmjmpsaa.frm";
+            for width in [40, 60, 80] {
+                let layout = ListingLayout::for_width(Some(width), name.len());
+                assert!(layout.row(&make_entry(name)).len() <= width);
+                assert!(layout.header().len() <= width);
+                assert!(layout.rule().len() <= width);
+            }
+        }
 
-            // /etc/passwd may not exist; use a path that does to avoid NotFound
-            // inside glob expansion (no glob here, so it just passes through).
-            let result =
-                utils::expand_response_files_with_stripping(&["/etc/passwd".into()], Some(&root));
+        #[test]
+        fn very_narrow_terminals_still_fit_rows() {
+            // The narrowest prefix is 13 columns, leaving 7 for the name
+            let name = "art\\critters\\hmjmpsaa.frm";
+            let layout = ListingLayout::for_width(Some(20), name.len());
+            assert_eq!(layout.name_width, Some(7));
+            let row = layout.row(&make_entry(name));
+            assert!(row.ends_with("  ....frm"), "{row}");
+            assert_eq!(row.len(), 20);
+            assert!(layout.header().len() <= 20);
 
-            // No longer rejected at this stage — validation is in resolve_add_input_path
-            assert!(result.is_ok());
-            assert_eq!(
-                result.unwrap(),
-                vec![std::path::PathBuf::from("/etc/passwd")]
-            );
-            fs::remove_dir_all(root).unwrap();
+            let layout = ListingLayout::for_width(Some(15), name.len());
+            assert!(layout.row(&make_entry(name)).ends_with("  .."));
         }
-    }
 
-    // ── to_system_path ─────────────────────────────────────────────
+        #[test]
+        fn index_column_shows_original_position() {
+            let layout = ListingLayout::with_index(None, 0);
+            let mut entry = make_entry("FILE.FRM");
+            assert!(layout.header().starts_with(" Index "));
+            assert!(layout.row(&entry).starts_with("     - "));
+            entry.original_index = Some(42);
+            assert!(layout.row(&entry).starts_with("    42 "));
+        }
 
-    mod to_system_path {
-        use super::*;
+        #[test]
+        fn index_column_is_kept_on_narrow_terminals() {
+            let name = "art\\critters\\some\\very\\deep\\directory\\hmjmpsaa.frm";
+            let layout = ListingLayout::with_index(Some(40), name.len());
+            assert!(layout.show_index);
+            assert!(layout.row(&make_entry(name)).len() <= 40);
+        }
 
         #[test]
-        fn converts_backslashes_to_system_separator() {
-            let result = utils::to_system_path("ART\\CRITTERS\\FILE.FRM");
-            let expected = std::path::PathBuf::from(
-                "ART/CRITTERS/FILE.FRM".replace('/', std::path::MAIN_SEPARATOR_STR),
+        fn verbose_adds_offset_type_and_dir_columns() {
+            let layout = ListingLayout::verbose(None, 0, false);
+            let mut entry = make_entry("ART\\FILE.FRM");
+            entry.offset = 4096;
+            entry.stored_type = Some(0x40);
+            entry.directory_index = Some(3);
+            assert_eq!(
+                layout.header(),
+                format!(
+                    "{:>10} {:>11} {:>11}  {:>4}  {:>4} {:>5}  Name",
+                    "Offset", "Size", "Packed", "Comp", "Type", "Dir"
+                )
+            );
+            #[cfg(not(windows))]
+            assert_eq!(
+                layout.row(&entry),
+                format!(
+                    "{:>10} {:>11} {:>11}  {:>4}  {:>4} {:>5}  {}",
+                    4096, 1234, 567, "Yes", "0x40", 3, "ART/FILE.FRM"
+                )
             );
-            assert_eq!(result, expected);
         }
-    }
-
-    // ── FileEntry constructors ─────────────────────────────────────
-
-    mod file_entry {
-        use super::*;
 
         #[test]
-        fn with_data_sets_packed_size() {
-            let data = vec![1, 2, 3, 4, 5];
-            let entry = FileEntry::with_data("test.txt".to_string(), data, false);
-            assert_eq!(entry.packed_size, 5);
-            assert_eq!(entry.offset, 0);
-            assert!(!entry.compressed);
+        fn verbose_dashes_fields_the_entry_lacks() {
+            let layout = ListingLayout::verbose(None, 0, true);
+            let entry = FileEntry::with_data("NEW.TXT".to_string(), vec![1, 2, 3], false);
+            assert_eq!(
+                layout.row(&entry),
+                format!(
+                    "{:>6} {:>10} {:>11} {:>11}  {:>4}  {:>4} {:>5}  {}",
+                    "-", "-", 0, 3, "No", "-", "-", "NEW.TXT"
+                )
+            );
         }
 
         #[test]
-        fn with_compression_data_tracks_both_sizes() {
-            let original = vec![1, 2, 3, 4, 5, 6, 7, 8];
-            let compressed = vec![1, 2, 3];
-            let entry =
-                FileEntry::with_compression_data("test.txt".to_string(), original, compressed);
-            assert_eq!(entry.size, 8);
-            assert_eq!(entry.packed_size, 3);
-            assert!(entry.compressed);
+        fn verbose_keeps_columns_and_truncates_names() {
+            let name = "art\\critters\\some\\very\\deep\\directory\\hmjmpsaa.frm";
+            // The verbose prefix is 54 columns
+            let layout = ListingLayout::verbose(Some(80), name.len(), false);
+            assert!(layout.show_packed && layout.show_comp && layout.show_details);
+            assert_eq!(layout.name_width, Some(26));
+            assert_eq!(
+                ListingLayout::verbose(Some(200), name.len(), true).name_width,
+                None
+            );
         }
-    }
 
-    // ── normalize_user_patterns ────────────────────────────────────
-
-    mod normalize_user_patterns {
-        use super::*;
+        #[test]
+        fn truncate_keeps_basename() {
+            assert_eq!(
+                utils::truncate_path_middle("art/critters/deep/hmjmpsaa.frm", 20),
+                "art/.../hmjmpsaa.frm"
+            );
+        }
 
         #[test]
-        fn converts_all_patterns() {
-            let patterns = vec![
-                "art/critters/file.frm".to_string(),
-                "sound\\music.acm".to_string(),
-            ];
-            let normalized = utils::normalize_user_patterns(&patterns);
-            assert_eq!(normalized[0], "art\\critters\\file.frm");
-            assert_eq!(normalized[1], "sound\\music.acm");
+        fn truncate_short_path_unchanged() {
+            assert_eq!(
+                utils::truncate_path_middle("art/file.frm", 20),
+                "art/file.frm"
+            );
         }
 
         #[test]
-        fn empty_patterns() {
-            let normalized = utils::normalize_user_patterns(&[]);
-            assert!(normalized.is_empty());
+        fn truncate_long_basename_keeps_tail() {
+            assert_eq!(
+                utils::truncate_path_middle("dir/averyveryverylongname.frm", 12),
+                "...gname.frm"
+            );
         }
     }
 }
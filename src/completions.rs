@@ -0,0 +1,22 @@
+/*!
+# Shell Completions
+
+`dat3 completions bash` prints a completion script for the shell, generated
+from the same clap definitions the parser uses. Archive and directory
+arguments carry value hints, so shells complete them as paths of the right
+kind, and enum options such as `--case` or `--algo` complete their values.
+
+Completion is static: shells that can't filter by extension offer every
+file for archive arguments, not only `.dat` ones.
+*/
+
+use clap::Command;
+use clap_complete::{generate, Shell};
+
+/// The completion script of `command` for `shell`
+pub fn script(shell: Shell, command: &mut Command) -> String {
+    let name = command.get_name().to_string();
+    let mut out = Vec::new();
+    generate(shell, command, name, &mut out);
+    String::from_utf8_lossy(&out).into_owned()
+}
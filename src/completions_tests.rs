@@ -0,0 +1,28 @@
+/*!
+Unit tests for shell completions.
+
+Tests cover script generation for every supported shell.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::completions::*;
+    use crate::Cli;
+    use clap::{CommandFactory, ValueEnum};
+    use clap_complete::Shell;
+
+    #[test]
+    fn every_shell_gets_a_script() {
+        for shell in Shell::value_variants() {
+            let script = script(*shell, &mut Cli::command());
+            assert!(!script.is_empty(), "{shell} script is empty");
+            assert!(script.contains("dat3"), "{shell} script doesn't name dat3");
+        }
+    }
+
+    #[test]
+    fn enum_values_are_completed() {
+        let script = script(Shell::Bash, &mut Cli::command());
+        assert!(script.contains("preserve upper lower"), "{script}");
+    }
+}
@@ -0,0 +1,297 @@
+/*!
+# Per-user Defaults
+
+An optional TOML file sets defaults for options passed on every run:
+
+```toml
+compression = 9
+store-ext = ["acm", "mve"]
+threads = 4
+progress = false
+output-dir = "extracted"
+```
+
+It's read from `$DAT3_CONFIG` if that's set, else from
+`$XDG_CONFIG_HOME/dat3/config.toml` or `~/.config/dat3/config.toml`
+(`%APPDATA%\dat3\config.toml` on Windows). A missing default file is no
+error; a missing `$DAT3_CONFIG` is. Flags given on the command line win over
+the file, and unknown keys only warn so one file can serve several versions.
+*/
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the config file to use
+pub const CONFIG_ENV: &str = "DAT3_CONFIG";
+
+/// Compression level of commands that don't pick their own
+pub const DEFAULT_COMPRESSION: u8 = 1;
+
+/// Where a setting's value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Flag(flag) => write!(f, "{flag}"),
+        }
+    }
+}
+
+/// A value and where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn default(value: T) -> Self {
+        Self {
+            value,
+            source: Source::Default,
+        }
+    }
+
+    /// Replace the value with `value` from `source`, if there is one
+    fn set(&mut self, value: Option<T>, source: Source) {
+        if let Some(value) = value {
+            *self = Self { value, source };
+        }
+    }
+}
+
+/// Values read from a config file; `None` for keys it doesn't set
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub compression: Option<u8>,
+    pub store_ext: Option<Vec<String>>,
+    pub threads: Option<NonZeroUsize>,
+    pub progress: Option<bool>,
+    pub output_dir: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// Parse the text of the config file at `path`, with a warning for each
+    /// key this version doesn't know
+    pub fn parse(text: &str, path: &Path) -> Result<(Self, Vec<String>)> {
+        let table: toml::Table = text
+            .parse()
+            .with_context(|| format!("Invalid config file: {}", path.display()))?;
+        let mut file = Self {
+            path: path.to_path_buf(),
+            ..Self::default()
+        };
+        let mut warnings = Vec::new();
+        for (key, value) in &table {
+            let parsed = match key.as_str() {
+                "compression" => parse_compression(value).map(|v| file.compression = Some(v)),
+                "store-ext" => parse_store_ext(value).map(|v| file.store_ext = Some(v)),
+                "threads" => parse_threads(value).map(|v| file.threads = Some(v)),
+                "progress" => parse_progress(value).map(|v| file.progress = Some(v)),
+                "output-dir" => parse_output_dir(value).map(|v| file.output_dir = Some(v)),
+                _ => {
+                    warnings.push(format!(
+                        "Unknown key '{key}' in config file {}",
+                        path.display()
+                    ));
+                    Ok(())
+                }
+            };
+            parsed.with_context(|| format!("Invalid '{key}' in config file {}", path.display()))?;
+        }
+        Ok((file, warnings))
+    }
+
+    /// Read the config file, if there is one, printing its warnings
+    pub fn load() -> Result<Option<Self>> {
+        let Some((path, explicit)) = config_path() else {
+            return Ok(None);
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read config file: {}", path.display()))
+            }
+        };
+        let (file, warnings) = Self::parse(&text, &path)?;
+        for warning in warnings {
+            crate::color::warning(format_args!("{warning}"));
+        }
+        Ok(Some(file))
+    }
+}
+
+fn parse_compression(value: &toml::Value) -> Result<u8> {
+    match value {
+        toml::Value::Integer(level @ 0..=9) => Ok(*level as u8),
+        _ => bail!("expected a compression level 0-9"),
+    }
+}
+
+fn parse_store_ext(value: &toml::Value) -> Result<Vec<String>> {
+    let toml::Value::Array(items) = value else {
+        bail!("expected a list of extensions");
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            toml::Value::String(ext) => Ok(ext.clone()),
+            _ => bail!("expected a list of extensions"),
+        })
+        .collect()
+}
+
+fn parse_threads(value: &toml::Value) -> Result<NonZeroUsize> {
+    match value {
+        toml::Value::Integer(n) => usize::try_from(*n)
+            .ok()
+            .and_then(NonZeroUsize::new)
+            .context("expected a positive thread count"),
+        _ => bail!("expected a positive thread count"),
+    }
+}
+
+fn parse_progress(value: &toml::Value) -> Result<bool> {
+    match value {
+        toml::Value::Boolean(progress) => Ok(*progress),
+        _ => bail!("expected true or false"),
+    }
+}
+
+fn parse_output_dir(value: &toml::Value) -> Result<PathBuf> {
+    match value {
+        toml::Value::String(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => bail!("expected a directory path"),
+    }
+}
+
+/// The config file to read and whether it was named by `$DAT3_CONFIG`
+pub fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+        return Some((PathBuf::from(path), true));
+    }
+    let config_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    Some((config_dir?.join("dat3").join("config.toml"), false))
+}
+
+/// Effective defaults: built in, then the config file, then global flags.
+/// Per-command flags (`-c`, `--store-ext`, `-o`) are applied where they're
+/// parsed, falling back to these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub compression: Setting<u8>,
+    /// Extensions stored uncompressed besides the built-in ones
+    pub store_ext: Setting<Vec<String>>,
+    /// Worker threads; `None` leaves the choice to rayon
+    pub threads: Setting<Option<NonZeroUsize>>,
+    /// Whether progress messages are printed
+    pub progress: Setting<bool>,
+    /// Where `x` and `e` extract to without `-o`
+    pub output_dir: Setting<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            compression: Setting::default(DEFAULT_COMPRESSION),
+            store_ext: Setting::default(Vec::new()),
+            threads: Setting::default(None),
+            progress: Setting::default(true),
+            output_dir: Setting::default(PathBuf::from(".")),
+        }
+    }
+}
+
+impl Settings {
+    /// Merge `file` and the global `--threads` and `--progress`/`--no-progress`
+    /// flags over the built-in defaults
+    pub fn new(
+        file: Option<ConfigFile>,
+        threads: Option<NonZeroUsize>,
+        progress: Option<bool>,
+    ) -> Self {
+        let mut settings = Self::default();
+        if let Some(file) = file {
+            let source = Source::File(file.path);
+            settings.compression.set(file.compression, source.clone());
+            settings.store_ext.set(file.store_ext, source.clone());
+            settings.threads.set(file.threads.map(Some), source.clone());
+            settings.progress.set(file.progress, source.clone());
+            settings.output_dir.set(file.output_dir, source);
+        }
+        settings
+            .threads
+            .set(threads.map(Some), Source::Flag("--threads"));
+        let progress_flag = match progress {
+            Some(true) => "--progress",
+            _ => "--no-progress",
+        };
+        settings.progress.set(progress, Source::Flag(progress_flag));
+        settings
+    }
+}
+
+impl fmt::Display for Settings {
+    /// TOML lines, each commented with where its value came from
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            compression,
+            store_ext,
+            threads,
+            progress,
+            output_dir,
+        } = self;
+        let store_ext_value = store_ext
+            .value
+            .iter()
+            .map(|ext| format!("{ext:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let lines = [
+            (
+                format!("compression = {}", compression.value),
+                &compression.source,
+            ),
+            (
+                format!("store-ext = [{store_ext_value}]"),
+                &store_ext.source,
+            ),
+            (
+                match threads.value {
+                    Some(n) => format!("threads = {n}"),
+                    None => "# threads = (one per CPU)".to_string(),
+                },
+                &threads.source,
+            ),
+            (format!("progress = {}", progress.value), &progress.source),
+            (
+                format!("output-dir = {:?}", output_dir.value.display().to_string()),
+                &output_dir.source,
+            ),
+        ];
+        for (line, source) in lines {
+            writeln!(f, "{line:<32} # {source}")?;
+        }
+        Ok(())
+    }
+}
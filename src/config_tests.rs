@@ -0,0 +1,98 @@
+/*!
+Unit tests for per-user defaults.
+
+Tests cover parsing config files, warnings for unknown keys, rejecting bad
+values, and the order in which defaults, the file and flags apply.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+    use std::num::NonZeroUsize;
+    use std::path::{Path, PathBuf};
+
+    fn parse(text: &str) -> anyhow::Result<(ConfigFile, Vec<String>)> {
+        ConfigFile::parse(text, Path::new("config.toml"))
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let (file, warnings) = parse(
+            "compression = 9\n\
+             store-ext = [\"acm\", \"mve\"]\n\
+             threads = 4\n\
+             progress = false\n\
+             output-dir = \"out\"\n",
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            file,
+            ConfigFile {
+                path: PathBuf::from("config.toml"),
+                compression: Some(9),
+                store_ext: Some(vec!["acm".into(), "mve".into()]),
+                threads: NonZeroUsize::new(4),
+                progress: Some(false),
+                output_dir: Some(PathBuf::from("out")),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_keys_warn() {
+        let (file, warnings) = parse("compression = 3\ncolour = \"red\"\n").unwrap();
+        assert_eq!(file.compression, Some(3));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'colour'"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for text in [
+            "compression = 10",
+            "compression = \"9\"",
+            "store-ext = \"acm\"",
+            "store-ext = [1]",
+            "threads = 0",
+            "threads = -2",
+            "progress = 1",
+            "output-dir = \"\"",
+            "not toml",
+        ] {
+            assert!(parse(text).is_err(), "{text:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let (file, _) = parse("compression = 9\nthreads = 4\nprogress = false\n").unwrap();
+        let settings = Settings::new(Some(file), NonZeroUsize::new(2), None);
+        assert_eq!(settings.compression.value, 9);
+        assert_eq!(
+            settings.compression.source,
+            Source::File(PathBuf::from("config.toml"))
+        );
+        assert_eq!(settings.threads.value, NonZeroUsize::new(2));
+        assert_eq!(settings.threads.source, Source::Flag("--threads"));
+        assert!(!settings.progress.value);
+
+        let (file, _) = parse("progress = false\n").unwrap();
+        let settings = Settings::new(Some(file), None, Some(true));
+        assert!(settings.progress.value);
+        assert_eq!(settings.progress.source, Source::Flag("--progress"));
+        assert_eq!(settings.output_dir, Settings::default().output_dir);
+    }
+
+    #[test]
+    fn show_lists_values_and_sources() {
+        let (file, _) = parse("store-ext = [\"wav\"]\n").unwrap();
+        let shown = Settings::new(Some(file), None, Some(false)).to_string();
+        assert!(shown.contains("compression = 1"), "{shown}");
+        assert!(shown.contains("# default"), "{shown}");
+        assert!(shown.contains("store-ext = [\"wav\"]"), "{shown}");
+        assert!(shown.contains("# config.toml"), "{shown}");
+        assert!(shown.contains("progress = false"), "{shown}");
+        assert!(shown.contains("# --no-progress"), "{shown}");
+    }
+}
@@ -0,0 +1,93 @@
+/*!
+# Confirmation Prompts
+
+Operations that can't be undone, like deleting entries, say what they are
+about to do and ask before doing it:
+
+```text
+Delete 3 entries from master.dat?
+  art/critters/hmjmpsaa.frm
+  art/critters/hmjmpsab.frm
+  art/critters/hmjmpsac.frm
+Continue? [y/N]
+```
+
+Only a `y` or `yes` answer goes ahead. `-y`/`--yes` answers for the user.
+Without a terminal to ask on, the same list is printed and the operation
+refused unless `-y` was given, so a script never blocks on a prompt or
+deletes by accident.
+*/
+
+use anyhow::{bail, Context, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Names listed before "... and N more"
+pub const PREVIEW_NAMES: usize = 10;
+
+/// Whether risky operations may go ahead, and how to ask
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Confirm {
+    /// Go ahead without asking (`-y`/`--yes`)
+    pub yes: bool,
+}
+
+impl Confirm {
+    /// Ask on the terminal whether to go ahead with `action`, listing the
+    /// first `PREVIEW_NAMES` of `names`; fail unless the answer is yes
+    pub fn ask(&self, action: &str, names: &[String]) -> Result<()> {
+        if self.yes {
+            return Ok(());
+        }
+        let stdin = io::stdin();
+        let terminal = stdin.is_terminal();
+        self.ask_with(
+            action,
+            names,
+            terminal,
+            &mut stdin.lock(),
+            &mut io::stderr(),
+        )
+    }
+
+    /// `ask`, reading the answer from `input` if `terminal`, and writing the
+    /// prompt to `output`
+    pub fn ask_with(
+        &self,
+        action: &str,
+        names: &[String],
+        terminal: bool,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<()> {
+        if self.yes {
+            return Ok(());
+        }
+        write!(output, "{}", preview(action, names))?;
+        if !terminal {
+            bail!("No terminal to confirm on; pass -y to go ahead");
+        }
+        write!(output, "Continue? [y/N] ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        input
+            .read_line(&mut answer)
+            .context("Failed to read the answer")?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+            bail!("Cancelled");
+        }
+        Ok(())
+    }
+}
+
+/// `action`, then the first `PREVIEW_NAMES` names indented, one per line
+pub fn preview(action: &str, names: &[String]) -> String {
+    let mut text = format!("{action}\n");
+    for name in names.iter().take(PREVIEW_NAMES) {
+        text.push_str(&format!("  {name}\n"));
+    }
+    if names.len() > PREVIEW_NAMES {
+        text.push_str(&format!("  ... and {} more\n", names.len() - PREVIEW_NAMES));
+    }
+    text
+}
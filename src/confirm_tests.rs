@@ -0,0 +1,87 @@
+/*!
+Unit tests for confirmation prompts.
+
+Tests cover the answers that go ahead, refusing without a terminal, `-y`,
+and the preview of long name lists.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::confirm::*;
+
+    fn names(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("file{i}.txt")).collect()
+    }
+
+    /// Ask on a pretend terminal that answers `answer`, returning the result
+    /// and what was written
+    fn ask(confirm: Confirm, answer: &str) -> (bool, String) {
+        let mut output = Vec::new();
+        let result = confirm.ask_with(
+            "Delete 2 entries?",
+            &names(2),
+            true,
+            &mut answer.as_bytes(),
+            &mut output,
+        );
+        (result.is_ok(), String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn yes_answers_go_ahead() {
+        for answer in ["y\n", "Y\n", "yes\n", " YES \n"] {
+            assert!(ask(Confirm { yes: false }, answer).0, "{answer:?}");
+        }
+    }
+
+    #[test]
+    fn anything_else_cancels() {
+        for answer in ["\n", "n\n", "no\n", "yep\n", ""] {
+            assert!(!ask(Confirm { yes: false }, answer).0, "{answer:?}");
+        }
+    }
+
+    #[test]
+    fn prompt_lists_the_names() {
+        let (_, output) = ask(Confirm { yes: false }, "n\n");
+        assert_eq!(
+            output,
+            "Delete 2 entries?\n  file1.txt\n  file2.txt\nContinue? [y/N] "
+        );
+    }
+
+    #[test]
+    fn refuses_without_a_terminal() {
+        let mut output = Vec::new();
+        let result = Confirm { yes: false }.ask_with(
+            "Delete 2 entries?",
+            &names(2),
+            false,
+            &mut "y\n".as_bytes(),
+            &mut output,
+        );
+        assert!(format!("{:#}", result.unwrap_err()).contains("pass -y"));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Delete 2 entries?\n  file1.txt\n  file2.txt\n"
+        );
+    }
+
+    #[test]
+    fn yes_flag_skips_the_prompt() {
+        let mut output = Vec::new();
+        Confirm { yes: true }
+            .ask_with("Delete?", &names(2), false, &mut "".as_bytes(), &mut output)
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn preview_shortens_long_lists() {
+        let text = preview("Delete 25 entries?", &names(25));
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), PREVIEW_NAMES + 2);
+        assert_eq!(lines[PREVIEW_NAMES], "  file10.txt");
+        assert_eq!(lines[PREVIEW_NAMES + 1], "  ... and 15 more");
+    }
+}
@@ -0,0 +1,46 @@
+/*!
+# Windows-1252
+
+Entry names are stored as single bytes, in the code page of the game's
+release: Windows-1252 for the western ones. Names are decoded to UTF-8 when
+read and encoded back when saved, so every byte value round-trips: the five
+positions cp1252 leaves undefined map to the C1 controls of the same value.
+*/
+
+/// cp1252 0x80-0x9F; undefined positions map to the C1 control of the same value
+const HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// The character of byte `b`
+pub fn decode_byte(b: u8) -> char {
+    match b {
+        0x80..=0x9F => HIGH[(b - 0x80) as usize],
+        // ASCII and 0xA0-0xFF match Latin-1, i.e. the same code points
+        _ => b as char,
+    }
+}
+
+/// The byte of character `c`, if cp1252 has one
+pub fn encode_char(c: char) -> Option<u8> {
+    match c {
+        '\0'..='\u{7F}' | '\u{A0}'..='\u{FF}' => Some(c as u8),
+        _ => HIGH
+            .iter()
+            .position(|&high| high == c)
+            .map(|i| 0x80 + i as u8),
+    }
+}
+
+/// Decode `bytes` to a string; every byte has a character
+pub fn decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| decode_byte(b)).collect()
+}
+
+/// Encode `text` to bytes, or return the first character cp1252 can't encode
+pub fn encode(text: &str) -> Result<Vec<u8>, char> {
+    text.chars().map(|c| encode_char(c).ok_or(c)).collect()
+}
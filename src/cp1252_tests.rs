@@ -0,0 +1,35 @@
+/*!
+Unit tests for Windows-1252 names.
+
+Tests cover decoding the high range, encoding back and rejecting characters
+outside the code page.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::cp1252;
+
+    #[test]
+    fn every_byte_round_trips() {
+        let bytes: Vec<u8> = (0..=u8::MAX).collect();
+        assert_eq!(cp1252::encode(&cp1252::decode(&bytes)), Ok(bytes));
+    }
+
+    #[test]
+    fn maps_high_punctuation_and_latin1() {
+        assert_eq!(
+            cp1252::decode(b"m\xfcller \x80 \x85"),
+            "m\u{fc}ller \u{20AC} \u{2026}"
+        );
+        assert_eq!(
+            cp1252::encode("\u{201C}caf\u{e9}\u{201D}"),
+            Ok(b"\x93caf\xe9\x94".to_vec())
+        );
+    }
+
+    #[test]
+    fn rejects_characters_outside_the_code_page() {
+        assert_eq!(cp1252::encode("art\\\u{0416}.frm"), Err('\u{0416}'));
+        assert_eq!(cp1252::encode_char('\u{0100}'), None);
+    }
+}
@@ -14,11 +14,21 @@ LZSS compression for writing is not implemented - files are stored uncompressed.
 
 use anyhow::{bail, Context, Result};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io::{Cursor, Read, Write};
+use std::ops::Range;
 use std::path::Path;
-
-use crate::common::{self, utils, CompressionLevel, ExtractionMode, FileEntry};
+use std::sync::atomic::AtomicUsize;
+use std::time::Instant;
+
+use crate::cache::{CacheConfig, EntryCache};
+use crate::common::{
+    self, utils, AddOptions, AddStats, CompressionOutcome, Event, ExtractOptions, ExtractOutcome,
+    FileEntry, NameCase, Progress, SpaceReport,
+};
+use crate::error::{DatError, DatResult, TreeContext};
 use crate::lzss;
 
 // DAT1 format constants
@@ -26,6 +36,15 @@ const DAT1_COMPRESSED_FLAG: u32 = 0x40;
 const DAT1_UNCOMPRESSED_FLAG: u32 = 0x20;
 const DAT1_FORMAT_ID: u32 = 0x0A;
 const DAT1_DIRECTORY_UNKNOWN5: u32 = 0x10;
+/// Header fields after the directory count in a new archive: the format
+/// identifier seen in original files, then two zeros
+const DAT1_NEW_HEADER_UNKNOWNS: [u32; 3] = [DAT1_FORMAT_ID, 0, 0];
+/// Fields after the file count in a new directory record
+const DAT1_NEW_DIRECTORY_UNKNOWNS: [u32; 3] = [DAT1_FORMAT_ID, DAT1_DIRECTORY_UNKNOWN5, 0];
+/// Directory and file name lengths are stored in one byte
+const DAT1_MAX_NAME_LEN: usize = u8::MAX as usize;
+/// Suffix of compressed entries extracted raw: their LZSS blocks
+const RAW_SUFFIX: &str = ".lzss";
 
 /// A directory within a DAT1 archive.
 /// DAT1 uses hierarchical directories; the root is named ".".
@@ -33,6 +52,61 @@ const DAT1_DIRECTORY_UNKNOWN5: u32 = 0x10;
 struct Directory {
     name: String,
     files: Vec<FileEntry>,
+    /// Fields after the file count, of unknown meaning: kept as read so
+    /// saving writes them back
+    unknowns: [u32; 3],
+}
+
+/// Reads the DAT1 header and directory tree. A failed read is a
+/// `DatError::CorruptTree` at the offset the read started from.
+struct TreeReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl TreeReader<'_> {
+    /// A big-endian u32; `what` names it for the error message
+    fn u32(&mut self, what: impl FnOnce() -> String) -> DatResult<u32> {
+        let offset = self.cursor.position();
+        self.cursor
+            .read_u32::<BigEndian>()
+            .tree_context(offset, || format!("Failed to read {}", what()))
+    }
+
+    /// A name: length byte, then that many ASCII bytes
+    fn name(&mut self, what: impl Fn() -> String) -> DatResult<String> {
+        let offset = self.cursor.position();
+        let len = self.cursor.read_u8().tree_context(offset, || {
+            format!("Failed to read name length for {}", what())
+        })?;
+        let mut bytes = vec![0u8; len as usize];
+        self.cursor
+            .read_exact(&mut bytes)
+            .tree_context(offset, || {
+                format!("Failed to read name bytes for {}", what())
+            })?;
+        utils::decode_filename(&bytes)
+            .tree_context(offset, || format!("Failed to decode name of {}", what()))
+    }
+}
+
+impl Directory {
+    /// A directory record not read from an archive
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            files: Vec::new(),
+            unknowns: DAT1_NEW_DIRECTORY_UNKNOWNS,
+        }
+    }
+
+    /// A file's name as written under this directory: the path minus the
+    /// directory prefix (full path in the root, or if the prefix doesn't match)
+    fn stored_file_name<'a>(&self, file: &'a FileEntry) -> &'a str {
+        match file.name.strip_prefix(self.name.as_str()) {
+            Some(rest) if self.name != "." => rest.strip_prefix('\\').unwrap_or(&file.name),
+            _ => &file.name,
+        }
+    }
 }
 
 /// DAT1 archive handler (Fallout 1 format)
@@ -41,105 +115,112 @@ pub struct Dat1Archive {
     directories: Vec<Directory>,
     /// Raw archive data for reading existing file content
     data: Vec<u8>,
+    /// Where file data starts in `data` (end of the directory tree)
+    data_start: u64,
+    /// Optional cache of decompressed entries (`--cache-dir`)
+    cache: Option<EntryCache>,
+    /// Replace existing entries in place instead of moving them to the end
+    keep_order: bool,
+    /// Header fields after the directory count, of unknown meaning: kept
+    /// as read so saving writes them back
+    header_unknowns: [u32; 3],
+}
+
+impl Default for Dat1Archive {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Dat1Archive {
     /// Create a new empty DAT1 archive with just a root directory
     pub fn new() -> Self {
         Self {
-            directories: vec![Directory {
-                name: ".".to_string(), // "." is the root directory in DAT1 format
-                files: Vec::new(),
-            }],
+            // "." is the root directory in DAT1 format
+            directories: vec![Directory::new(".".to_string())],
             data: Vec::new(),
+            data_start: 0,
+            cache: None,
+            keep_order: false,
+            header_unknowns: DAT1_NEW_HEADER_UNKNOWNS,
         }
     }
 
     /// Parse an existing DAT1 archive from raw bytes
-    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
-        let mut cursor = Cursor::new(&data);
+    pub fn from_bytes(data: Vec<u8>) -> DatResult<Self> {
+        Self::try_from_bytes(data).map_err(|(error, _)| error)
+    }
 
-        // Read 16-byte header
-        let dir_count = cursor
-            .read_u32::<BigEndian>()
-            .context("Failed to read directory count from DAT1 header")?;
-        let _unknown1 = cursor
-            .read_u32::<BigEndian>()
-            .context("Failed to read unknown1 field from DAT1 header")?;
-        let _unknown2 = cursor
-            .read_u32::<BigEndian>()
-            .context("Failed to read unknown2 field from DAT1 header")?;
-        let _unknown3 = cursor
-            .read_u32::<BigEndian>()
-            .context("Failed to read unknown3 field from DAT1 header")?;
+    /// `from_bytes`, handing `data` back with the error so it can be parsed
+    /// another way
+    pub(crate) fn try_from_bytes(data: Vec<u8>) -> Result<Self, (DatError, Vec<u8>)> {
+        match Self::parse_tree(&data) {
+            Ok((header_unknowns, directories, data_start)) => Ok(Self {
+                directories,
+                data,
+                data_start,
+                cache: None,
+                keep_order: false,
+                header_unknowns,
+            }),
+            Err(error) => Err((error, data)),
+        }
+    }
 
-        // Read directory names
+    /// Parse the header and directory tree, returning the header's unknown
+    /// fields, the directories and where the data after the tree starts
+    fn parse_tree(data: &[u8]) -> DatResult<([u32; 3], Vec<Directory>, u64)> {
+        let mut reader = TreeReader {
+            cursor: Cursor::new(data),
+        };
+
+        // Read 16-byte header
+        let dir_count = reader.u32(|| "directory count from DAT1 header".into())?;
+        let header_unknowns = [
+            reader.u32(|| "unknown1 field from DAT1 header".into())?,
+            reader.u32(|| "unknown2 field from DAT1 header".into())?,
+            reader.u32(|| "unknown3 field from DAT1 header".into())?,
+        ];
+
+        // Read directory names. Some third-party packers store `/`
+        // separators: names are kept in the stored form (`\` separators)
+        // that saving writes, like DAT2 entry names, so entries added or
+        // deleted later match them.
         let mut dir_names = Vec::new();
         for i in 0..dir_count {
-            let name_len = cursor
-                .read_u8()
-                .with_context(|| format!("Failed to read name length for directory {i}"))?
-                as usize;
-            let mut name_bytes = vec![0u8; name_len];
-            cursor
-                .read_exact(&mut name_bytes)
-                .with_context(|| format!("Failed to read name bytes for directory {i}"))?;
-            let name =
-                utils::decode_filename(&name_bytes).context("Failed to decode directory name")?;
-            dir_names.push(name);
+            let name = reader.name(|| format!("directory {i}"))?;
+            dir_names.push(utils::stored_entry_path(&name));
         }
 
         // Read directory contents (file entries per directory)
         let mut directories = Vec::new();
-        for dir_name in dir_names {
-            let file_count = cursor
-                .read_u32::<BigEndian>()
-                .with_context(|| format!("Failed to read file count for directory '{dir_name}'"))?;
-            let _unknown4 = cursor.read_u32::<BigEndian>().with_context(|| {
-                format!("Failed to read unknown4 field for directory '{dir_name}'")
-            })?;
-            let _unknown5 = cursor.read_u32::<BigEndian>().with_context(|| {
-                format!("Failed to read unknown5 field for directory '{dir_name}'")
-            })?;
-            let _unknown6 = cursor.read_u32::<BigEndian>().with_context(|| {
-                format!("Failed to read unknown6 field for directory '{dir_name}'")
-            })?;
+        let mut entry_index = 0;
+        for (dir_index, dir_name) in dir_names.into_iter().enumerate() {
+            let file_count = reader.u32(|| format!("file count for directory '{dir_name}'"))?;
+            let unknowns = [
+                reader.u32(|| format!("unknown4 field for directory '{dir_name}'"))?,
+                reader.u32(|| format!("unknown5 field for directory '{dir_name}'"))?,
+                reader.u32(|| format!("unknown6 field for directory '{dir_name}'"))?,
+            ];
 
             let mut files = Vec::new();
 
             for j in 0..file_count {
-                let name_len = cursor.read_u8().with_context(|| {
-                    format!("Failed to read name length for file {j} in directory '{dir_name}'")
-                })? as usize;
-                let mut name_bytes = vec![0u8; name_len];
-                cursor.read_exact(&mut name_bytes).with_context(|| {
-                    format!("Failed to read name bytes for file {j} in directory '{dir_name}'")
-                })?;
-                let name =
-                    utils::decode_filename(&name_bytes).context("Failed to decode file name")?;
-
-                let attributes = cursor.read_u32::<BigEndian>().with_context(|| {
-                    format!("Failed to read attributes for file '{name}' in directory '{dir_name}'")
-                })?;
-                let offset = cursor.read_u32::<BigEndian>().with_context(|| {
-                    format!("Failed to read offset for file '{name}' in directory '{dir_name}'")
-                })? as u64;
-                let size = cursor.read_u32::<BigEndian>().with_context(|| {
-                    format!("Failed to read size for file '{name}' in directory '{dir_name}'")
-                })?;
-                let packed_size = cursor.read_u32::<BigEndian>().with_context(|| {
-                    format!(
-                        "Failed to read packed size for file '{name}' in directory '{dir_name}'"
-                    )
-                })?;
+                let name = reader.name(|| format!("file {j} in directory '{dir_name}'"))?;
+                let field =
+                    |field: &str| format!("{field} for file '{name}' in directory '{dir_name}'");
+                let attributes = reader.u32(|| field("attributes"))?;
+                let offset = reader.u32(|| field("offset"))? as u64;
+                let size = reader.u32(|| field("size"))?;
+                let packed_size = reader.u32(|| field("packed size"))?;
 
                 let compressed = attributes & DAT1_COMPRESSED_FLAG != 0;
                 let actual_packed_size = if packed_size == 0 { size } else { packed_size };
 
                 let full_name = if dir_name == "." {
-                    name
+                    utils::stored_entry_path(&name)
                 } else {
-                    format!("{dir_name}\\{name}")
+                    utils::stored_entry_path(&format!("{dir_name}\\{name}"))
                 };
 
                 files.push(FileEntry {
@@ -149,93 +230,200 @@ impl Dat1Archive {
                     packed_size: actual_packed_size,
                     compressed,
                     data: None,
+                    original_index: Some(entry_index),
+                    unsupported_compression: None,
+                    stored_type: Some(attributes),
+                    directory_index: Some(dir_index),
+                    nul_terminated_name: false,
                 });
+                entry_index += 1;
             }
 
             directories.push(Directory {
                 name: dir_name,
                 files,
+                unknowns,
             });
         }
 
-        Ok(Self { directories, data })
+        let data_start = reader.cursor.position();
+        Ok((header_unknowns, directories, data_start))
     }
 
-    /// Collect references to all files across all directories
-    fn all_files(&self) -> Vec<&FileEntry> {
-        self.directories.iter().flat_map(|dir| &dir.files).collect()
+    /// Serve decompressed entries from (and store them in) an on-disk cache
+    pub fn enable_cache(&mut self, config: &CacheConfig) -> Result<()> {
+        self.cache = Some(EntryCache::for_archive(config, &self.data)?);
+        Ok(())
+    }
+
+    /// Keep entries in their parsed tree order when adding files: a replaced
+    /// entry keeps its slot, new names are appended to their directory.
+    pub fn set_keep_order(&mut self, keep_order: bool) {
+        self.keep_order = keep_order;
+    }
+
+    /// All files across all directories, in tree order
+    pub fn entries(&self) -> impl Iterator<Item = &FileEntry> + '_ {
+        self.directories.iter().flat_map(|dir| &dir.files)
     }
 
-    /// Collect all files as a flat owned list (for filter_files_by_patterns)
-    fn all_files_flat(&self) -> Vec<FileEntry> {
+    /// Directory records in tree order: each name (`.` for the root) and
+    /// its files
+    pub fn directories(&self) -> impl Iterator<Item = (&str, &[FileEntry])> + '_ {
         self.directories
             .iter()
-            .flat_map(|dir| dir.files.clone())
-            .collect()
+            .map(|dir| (dir.name.as_str(), dir.files.as_slice()))
+    }
+
+    /// Number of files across all directories
+    pub fn len(&self) -> usize {
+        self.directories.iter().map(|dir| dir.files.len()).sum()
     }
 
-    /// List files in the archive (all or filtered by patterns)
-    pub fn list(&self, files: &[String]) -> Result<()> {
-        let all_files = self.all_files();
-        common::list_files_filtered(&all_files, files)
+    /// Whether the archive has no files
+    pub fn is_empty(&self) -> bool {
+        self.directories.iter().all(|dir| dir.files.is_empty())
     }
 
     /// Extract files from the archive
-    pub fn extract(&self, output_dir: &Path, files: &[String], mode: ExtractionMode) -> Result<()> {
-        let all_flat = self.all_files_flat();
-        let files_to_extract = common::filter_files_by_patterns(&all_flat, files);
+    pub fn extract(
+        &self,
+        output_dir: &Path,
+        files: &[String],
+        options: &ExtractOptions,
+        progress: &Progress,
+    ) -> Result<()> {
+        let all_files: Vec<_> = self.entries().collect();
+        let (mut files_to_extract, missing_patterns) =
+            common::filter_files_by_patterns(&all_files, files);
+        options.skip_duplicate_names(&mut files_to_extract, progress);
+        options.order_entries(&mut files_to_extract);
+        options.check_output_paths(&files_to_extract)?;
+        let skipped = options.skip_shallow_entries(&mut files_to_extract, progress);
+        self.extract_files(&files_to_extract, skipped, output_dir, options, progress)?;
+        options.check_missing(&missing_patterns, progress)
+    }
 
-        for file in files_to_extract {
-            utils::validate_archive_path(&file.name)?;
+    /// Sequential extraction; DAT1 archives are small enough not to need rayon
+    fn extract_files(
+        &self,
+        files_to_extract: &[&FileEntry],
+        skipped: usize,
+        output_dir: &Path,
+        options: &ExtractOptions,
+        progress: &Progress,
+    ) -> Result<()> {
+        let total_files = files_to_extract.len();
+        let failures = AtomicUsize::new(0);
+        let mut written = 0;
+        let mut current = 0;
 
-            let display_name = utils::normalize_path_for_display(&file.name);
-            println!("Extracting: {display_name}");
+        progress.emit(Event::ExtractStarted { total: total_files });
+        let start = Instant::now();
 
-            let output_path = match mode {
-                ExtractionMode::Flat => {
-                    let filename = utils::get_filename_from_dat_path(&file.name);
-                    output_dir.join(filename)
+        for file in files_to_extract {
+            progress.emit(Event::Extracting { name: &file.name });
+            let result = self.extract_one(file, output_dir, options);
+            let outcome = match result {
+                Ok(true) => {
+                    options.set_mtime(
+                        &options.entry_output_path(output_dir, file, RAW_SUFFIX),
+                        progress,
+                    );
+                    written += 1;
+                    Progress::add(&progress.extracted, 1);
+                    ExtractOutcome::Written
                 }
-                ExtractionMode::PreserveStructure => {
-                    output_dir.join(utils::to_system_path(&file.name))
+                Ok(false) => {
+                    current += 1;
+                    ExtractOutcome::Current
                 }
+                Err(_) => ExtractOutcome::Failed,
             };
+            log::debug!(
+                "Extracting {}: {outcome:?}",
+                utils::canonical_entry_path(&file.name)
+            );
+            progress.emit(Event::EntryExtracted {
+                name: &file.name,
+                bytes: outcome.bytes(file),
+                outcome,
+            });
+            options.handle_entry_result(result.map(|_| ()), &failures, progress)?;
+        }
 
-            utils::ensure_dir_exists(&output_path)?;
-
-            let file_data = self
-                .read_file_data(file)
-                .with_context(|| format!("Failed to read data for file '{}'", file.name))?;
+        progress.emit(Event::ExtractFinished {
+            elapsed: start.elapsed(),
+            written,
+            skipped,
+            current,
+        });
+        ExtractOptions::check_failures(&failures, total_files)
+    }
 
-            // Decompress LZSS if needed
-            let final_data = if file.compressed {
-                lzss::decompress(&file_data)
-                    .with_context(|| format!("Failed to decompress {}", file.name))?
-            } else {
-                file_data
-            };
+    /// Extract a single entry to its place under `output_dir`. Returns
+    /// false if `update` left an up-to-date file there alone.
+    fn extract_one(
+        &self,
+        file: &FileEntry,
+        output_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<bool> {
+        utils::validate_archive_path(&file.name)?;
+
+        let output_path = options.entry_output_path(output_dir, file, RAW_SUFFIX);
+        let may_be_current = options.may_be_current(&output_path, file);
+        if may_be_current && !options.checksum {
+            return Ok(false);
+        }
 
-            fs::write(&output_path, final_data)
-                .with_context(|| format!("Failed to write {}", output_path.display()))?;
+        utils::ensure_dir_exists(&output_path)?;
+
+        let read_data = || {
+            self.read_file_data(file)
+                .with_context(|| format!("Failed to read data for file '{}'", file.path()))
+        };
+
+        // Decompress LZSS if needed
+        let final_data = if file.compressed && !options.raw {
+            EntryCache::get_or_insert(self.cache.as_ref(), file, || {
+                Ok(Self::decompress_entry(file, &read_data()?)?)
+            })?
+        } else {
+            read_data()?
+        };
+        if may_be_current && ExtractOptions::holds_content(&output_path, &final_data) {
+            return Ok(false);
         }
 
-        Ok(())
+        fs::write(&output_path, final_data)
+            .with_context(|| format!("Failed to write {}", output_path.display()))?;
+        Ok(true)
     }
 
     /// Read file data from the raw archive bytes
-    fn read_file_data(&self, file: &FileEntry) -> Result<Vec<u8>> {
+    fn read_file_data(&self, file: &FileEntry) -> DatResult<Vec<u8>> {
+        self.file_data(file).map(<[u8]>::to_vec)
+    }
+
+    /// Borrow an entry's stored bytes: its in-memory data, or its range of
+    /// the archive
+    fn file_data<'a>(&'a self, file: &'a FileEntry) -> DatResult<&'a [u8]> {
         if let Some(ref data) = file.data {
-            return Ok(data.clone());
+            return Ok(data);
         }
 
         let start = file.offset as usize;
         let end = start + file.packed_size as usize;
 
         if end > self.data.len() {
-            bail!("File data extends beyond archive: {}", file.name);
+            return Err(DatError::tree(
+                format!("File data extends beyond archive: {}", file.path()),
+                file.offset,
+            ));
         }
 
-        Ok(self.data[start..end].to_vec())
+        Ok(&self.data[start..end])
     }
 
     /// Add files to the archive.
@@ -243,74 +431,444 @@ impl Dat1Archive {
     pub fn add_file(
         &mut self,
         file_path: &Path,
-        _compression: CompressionLevel,
-        target_dir: Option<&str>,
-        source_root: Option<&Path>,
-    ) -> Result<()> {
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
         let base_path = file_path;
-        let files = utils::collect_files(file_path).with_context(|| {
+        let found = utils::collect_files_excluding(
+            file_path,
+            options.recursive,
+            &options.excludes,
+            progress,
+        )
+        .with_context(|| {
             format!(
                 "Failed to collect files from path '{}'",
                 file_path.display()
             )
         })?;
-
+        let mut files = found.files;
+        let older = options.keep_newer(&mut files)?;
+        options.check_case_collisions(files.iter().map(|file| (file.as_path(), base_path)))?;
+        progress.emit(Event::AddStarted { total: files.len() });
+
+        let mut stats = AddStats {
+            excluded: found.excluded,
+            ignored: found.ignored,
+            older,
+            ..AddStats::default()
+        };
         for file in files {
-            let data =
-                fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let data = utils::read_file_to_add(&file)?;
+
+            let (archive_path, folded) = options.archive_path(&file, base_path)?;
+            Self::check_name_lengths(&archive_path)?;
 
-            let archive_path =
-                utils::calculate_archive_path(&file, base_path, target_dir, source_root)?;
+            progress.emit(Event::Adding {
+                name: &archive_path,
+            });
 
-            let size = data.len() as u32;
-            let display_path = utils::normalize_path_for_display(&archive_path);
-            println!("Adding: {display_path}");
+            let bytes = data.len() as u64;
+            self.insert_entry(archive_path.clone(), data);
+            log::debug!(
+                "Added {}: {bytes} bytes, stored",
+                utils::canonical_entry_path(&archive_path)
+            );
+            progress.emit(Event::Added {
+                name: &archive_path,
+                bytes,
+            });
+            stats.record(CompressionOutcome::Stored);
+            stats.record_case_fold(folded);
+        }
 
-            // Find or create target directory
-            let dir_name = utils::get_dirname_from_dat_path(&archive_path);
-            let dir_index =
-                if let Some(index) = self.directories.iter().position(|d| d.name == dir_name) {
-                    index
-                } else {
-                    self.directories.push(Directory {
-                        name: dir_name.to_string(),
-                        files: Vec::new(),
-                    });
-                    self.directories.len() - 1
-                };
+        // Cheap early check; `save` checks again with the header and tree
+        utils::check_archive_size(self.entries(), 0)?;
 
-            // Remove any existing file with the same name from all directories
-            for dir in &mut self.directories {
-                dir.files
-                    .retain(|existing_file| existing_file.name != archive_path);
+        Ok(stats)
+    }
+
+    /// Add in-memory content, see `DatArchive::add_contents`
+    pub fn add_contents(
+        &mut self,
+        contents: Vec<(String, Vec<u8>)>,
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
+        let mut stats = AddStats::default();
+        let named = options.content_archive_paths(contents, &mut stats)?;
+        for (archive_path, _) in &named {
+            Self::check_name_lengths(archive_path)?;
+        }
+        progress.emit(Event::AddStarted { total: named.len() });
+        for (archive_path, data) in named {
+            progress.emit(Event::Adding {
+                name: &archive_path,
+            });
+            let bytes = data.len() as u64;
+            self.insert_entry(archive_path.clone(), data);
+            log::debug!(
+                "Added {}: {bytes} bytes, stored",
+                utils::canonical_entry_path(&archive_path)
+            );
+            progress.emit(Event::Added {
+                name: &archive_path,
+                bytes,
+            });
+            stats.record(CompressionOutcome::Stored);
+        }
+
+        // Cheap early check; `save` checks again with the header and tree
+        utils::check_archive_size(self.entries(), 0)?;
+
+        Ok(stats)
+    }
+
+    /// Add an empty directory record named `name`, see
+    /// `DatArchive::add_directory`
+    pub fn add_directory(&mut self, name: &str) -> Result<bool> {
+        let name = utils::stored_entry_path(name.trim_end_matches(['/', '\\']));
+        if name.is_empty() || name == "." {
+            return Ok(false);
+        }
+        Self::name_len(&name)?;
+        if self
+            .directories
+            .iter()
+            .any(|dir| utils::compare_entry_paths(&dir.name, &name).is_eq())
+        {
+            return Ok(false);
+        }
+        self.directories.push(Directory::new(name));
+        Ok(true)
+    }
+
+    /// Fold the names of existing entries and directories, merging directories
+    /// that end up with the same name. See `DatArchive::fold_existing_names`.
+    pub fn fold_existing_names(&mut self, case: NameCase) -> Result<usize> {
+        case.check_collisions(self.entries().map(|f| f.name.as_str()))?;
+
+        let mut renamed = 0;
+        let mut folded_dirs: Vec<Directory> = Vec::with_capacity(self.directories.len());
+        for dir in std::mem::take(&mut self.directories) {
+            let unknowns = dir.unknowns;
+            let mut files = dir.files;
+            for file in &mut files {
+                if let Cow::Owned(folded) = case.fold(&file.name) {
+                    file.name = folded;
+                    renamed += 1;
+                }
             }
 
-            // DAT1 stores files uncompressed
-            let mut file_entry = FileEntry::with_data(archive_path, data, false);
-            file_entry.size = size;
-            self.directories[dir_index].files.push(file_entry);
+            let name = case.fold(&dir.name).into_owned();
+            match folded_dirs.iter_mut().find(|d| d.name == name) {
+                Some(existing) => existing.files.extend(files),
+                None => folded_dirs.push(Directory {
+                    name,
+                    files,
+                    unknowns,
+                }),
+            }
         }
+        self.directories = folded_dirs;
+
+        Ok(renamed)
+    }
 
+    /// Fail if the directory (the whole path up to the file) or the file name
+    /// of `archive_path` is too long for DAT1's one-byte lengths
+    fn check_name_lengths(archive_path: &str) -> Result<()> {
+        let parts = [
+            ("directory", utils::get_dirname_from_dat_path(archive_path)),
+            ("file", utils::get_filename_from_dat_path(archive_path)),
+        ];
+        for (kind, name) in parts {
+            let len = utils::encoded_name_len(name);
+            if len > DAT1_MAX_NAME_LEN {
+                bail!(
+                    "Cannot add {}: its {kind} name is {len} bytes, DAT1 allows at most {DAT1_MAX_NAME_LEN}",
+                    utils::canonical_entry_path(archive_path),
+                );
+            }
+        }
         Ok(())
     }
 
-    /// Delete a file from the archive by name
-    pub fn delete_file(&mut self, file_name: &str) -> Result<()> {
-        let normalized_name = utils::normalize_user_path(file_name).into_owned();
+    /// Length byte of a directory or file name
+    fn name_len(name: &str) -> Result<u8> {
+        let len = utils::encoded_name_len(name);
+        u8::try_from(len).map_err(|_| {
+            anyhow::anyhow!(
+                "Name too long for DAT1 ({len} bytes, at most {DAT1_MAX_NAME_LEN}): {}",
+                utils::canonical_entry_path(name)
+            )
+        })
+    }
+
+    /// Insert new content under `archive_path`, replacing any existing entry
+    /// with that name. DAT1 stores files uncompressed.
+    ///
+    /// The file goes in the directory record for its whole parent path,
+    /// created if needed: DAT1 records are named by full path, like
+    /// `sound\music`, not nested. A record matching the parent path with
+    /// other case or separators takes the file, which is then named with the
+    /// record's spelling so the engine finds it there.
+    fn insert_entry(&mut self, mut archive_path: String, data: Vec<u8>) {
+        let size = data.len() as u32;
+
+        // Find or create target directory
+        let dir_name = utils::stored_entry_path(utils::get_dirname_from_dat_path(&archive_path));
+        let dir_name = if dir_name.is_empty() { "." } else { &dir_name };
+        let existing = self
+            .directories
+            .iter()
+            .position(|d| utils::compare_entry_paths(&d.name, dir_name).is_eq());
+        let dir_index = if let Some(index) = existing {
+            let dir = &self.directories[index];
+            if dir.name != dir_name && dir.name != "." {
+                let file_name = utils::get_filename_from_dat_path(&archive_path);
+                archive_path = format!("{}\\{file_name}", dir.name);
+            }
+            index
+        } else {
+            self.directories.push(Directory::new(dir_name.to_string()));
+            self.directories.len() - 1
+        };
+
+        let mut file_entry = FileEntry::with_data(archive_path, data, false);
+        file_entry.size = size;
+
+        if self.keep_order {
+            let existing = self
+                .directories
+                .iter_mut()
+                .flat_map(|dir| &mut dir.files)
+                .find(|f| f.name == file_entry.name);
+            if let Some(existing) = existing {
+                *existing = file_entry;
+                return;
+            }
+        }
 
+        // Remove any existing file with the same name from all directories
         for dir in &mut self.directories {
-            if let Some(pos) = dir.files.iter().position(|f| f.name == normalized_name) {
-                let display_name = utils::normalize_path_for_display(&normalized_name);
-                println!("Deleting: {display_name}");
+            dir.files
+                .retain(|existing_file| existing_file.name != file_entry.name);
+        }
+
+        self.directories[dir_index].files.push(file_entry);
+    }
+
+    /// Size of the archive as read, or 0 if it was never saved
+    pub(crate) fn archive_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Byte range where entry data may live: after the header and tree
+    pub(crate) fn data_region(&self) -> Range<u64> {
+        let archive_size = self.archive_size();
+        self.data_start.min(archive_size)..archive_size
+    }
+
+    /// Find data-region bytes that no entry references
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport::analyze(
+            self.archive_size(),
+            self.data_region(),
+            self.entries()
+                .filter(|f| f.data.is_none())
+                .map(|f| f.offset..f.offset + f.packed_size as u64),
+        )
+    }
+
+    /// Read one entry's decompressed content by its archive path
+    pub fn read_file(&self, name: &str) -> DatResult<Vec<u8>> {
+        let file = self
+            .entries()
+            .find(|f| utils::compare_entry_paths(&f.name, name).is_eq())
+            .ok_or_else(|| DatError::EntryNotFound {
+                name: utils::canonical_entry_path(name),
+            })?;
+        self.read_entry_contents(file)
+    }
+
+    /// Stream one entry's content into `out` by its archive path, see
+    /// `DatArchive::read_file_to`
+    pub fn read_file_to<W: Write + ?Sized>(&self, name: &str, out: &mut W) -> DatResult<u64> {
+        let file = self
+            .entries()
+            .find(|f| utils::compare_entry_paths(&f.name, name).is_eq())
+            .ok_or_else(|| DatError::EntryNotFound {
+                name: utils::canonical_entry_path(name),
+            })?;
+        self.write_entry_contents(file, out)
+    }
+
+    /// Read an entry's content, decompressing it if needed
+    pub(crate) fn read_entry_contents(&self, file: &FileEntry) -> DatResult<Vec<u8>> {
+        let data = self.file_data(file)?;
+        if file.compressed {
+            Self::decompress_entry(file, data)
+        } else {
+            Ok(data.to_vec())
+        }
+    }
+
+    /// Stream an entry's content into `out`, decompressing it if needed
+    pub(crate) fn write_entry_contents<W: Write + ?Sized>(
+        &self,
+        file: &FileEntry,
+        out: &mut W,
+    ) -> DatResult<u64> {
+        Self::write_entry(file, self.file_data(file)?, out)
+    }
+
+    /// Decompress an entry's stored LZSS data; the result must have the
+    /// size recorded in the tree
+    fn decompress_entry(file: &FileEntry, data: &[u8]) -> DatResult<Vec<u8>> {
+        lzss::decompress_with_size(data, file.size as usize).map_err(|source| {
+            match source
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<lzss::ShortStream>())
+            {
+                Some(short) => DatError::SizeMismatch {
+                    name: file.path(),
+                    expected: short.expected,
+                    actual: short.actual,
+                },
+                None => DatError::CorruptData {
+                    name: file.path(),
+                    source,
+                },
+            }
+        })
+    }
+
+    /// Write an entry's content, from its stored bytes `data`, into `out`,
+    /// decompressing it with the checks of `decompress_entry` if needed
+    fn write_entry<W: Write + ?Sized>(
+        file: &FileEntry,
+        data: &[u8],
+        out: &mut W,
+    ) -> DatResult<u64> {
+        if !file.compressed {
+            out.write_all(data).map_err(|source| DatError::Write {
+                name: file.path(),
+                source,
+            })?;
+            return Ok(data.len() as u64);
+        }
+        // Decoding stops past the recorded size, so a damaged stream can't
+        // make it produce much more
+        let decoder = lzss::Decoder::new(data).with_limit(file.size as u64);
+        utils::copy_entry_content(file, decoder, out, |e| e)
+    }
+
+    /// Rebuild the archive in canonical form.
+    ///
+    /// Every entry is decompressed and stored again (DAT1 writing is always
+    /// uncompressed); names are normalized to backslashes, duplicate names
+    /// keep the last occurrence, and directories and files are sorted.
+    /// Directories without files are kept. The same input always produces
+    /// byte-identical output.
+    pub fn repack(&self) -> Result<(Self, AddStats)> {
+        let mut latest: BTreeMap<String, &FileEntry> = BTreeMap::new();
+        for file in self.entries() {
+            latest.insert(utils::stored_entry_path(&file.name), file);
+        }
+
+        let contents = latest
+            .into_iter()
+            .map(|(name, file)| Ok((name, self.read_entry_contents(file)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (mut archive, stats) = Self::from_entries(contents);
+        // Directory records without files survive a repack too
+        for dir in self.directories.iter().filter(|dir| dir.files.is_empty()) {
+            archive.add_directory(&dir.name)?;
+        }
+        archive.directories[1..].sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+        Ok((archive, stats))
+    }
+
+    /// Build a new archive from `(archive path, content)` pairs, stored
+    /// uncompressed. Directories and files are sorted; names must be unique.
+    pub fn from_entries(entries: Vec<(String, Vec<u8>)>) -> (Self, AddStats) {
+        let mut archive = Self::new();
+        let mut stats = AddStats::default();
+        for (name, data) in entries {
+            archive.insert_entry(name, data);
+            stats.record(CompressionOutcome::Stored);
+        }
+
+        // Root first, then directories and files in case-insensitive order
+        archive.directories[1..].sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+        for dir in &mut archive.directories {
+            dir.files
+                .sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+        }
+
+        (archive, stats)
+    }
+
+    /// Read the decompressed content of every entry whose name is in `names`,
+    /// in archive order
+    pub fn read_entries(&self, names: &HashSet<String>) -> Result<Vec<(String, Vec<u8>)>> {
+        self.entries()
+            .filter(|file| names.contains(&file.name))
+            .map(|file| Ok((file.name.clone(), self.read_entry_contents(file)?)))
+            .collect()
+    }
+
+    /// Names of all entries, in archive order
+    pub fn entry_names(&self) -> Vec<String> {
+        self.entries().map(|file| file.name.clone()).collect()
+    }
+
+    /// Delete a file from the archive by name
+    pub fn delete_file(&mut self, file_name: &str, progress: &Progress) -> Result<()> {
+        for dir in &mut self.directories {
+            if let Some(pos) = dir
+                .files
+                .iter()
+                .position(|f| utils::compare_entry_paths(&f.name, file_name).is_eq())
+            {
+                log::debug!(
+                    "Deleting {}",
+                    utils::canonical_entry_path(&dir.files[pos].name)
+                );
+                progress.emit(Event::Deleting {
+                    name: &dir.files[pos].name,
+                });
                 dir.files.remove(pos);
                 return Ok(());
             }
         }
 
-        bail!(
-            "File not found: {}",
-            utils::normalize_path_for_display(file_name)
-        );
+        Err(DatError::EntryNotFound {
+            name: utils::canonical_entry_path(file_name),
+        }
+        .into())
+    }
+
+    /// Keep only the entries `keep` returns true for, leaving directory
+    /// records in place
+    pub fn retain_entries(&mut self, mut keep: impl FnMut(&FileEntry) -> bool) {
+        for dir in &mut self.directories {
+            dir.files.retain(&mut keep);
+        }
+    }
+
+    /// Remove directory records at or under `prefix` that hold no files,
+    /// returning how many went. The root directory always stays.
+    pub fn remove_empty_directories(&mut self, prefix: &str) -> usize {
+        let before = self.directories.len();
+        self.directories.retain(|dir| {
+            dir.name == "."
+                || !dir.files.is_empty()
+                || !utils::matches_path_prefix(&dir.name, prefix)
+        });
+        before - self.directories.len()
     }
 
     /// Save the archive to a file
@@ -320,14 +878,14 @@ impl Dat1Archive {
 
         // Write 16-byte header
         cursor.write_u32::<BigEndian>(self.directories.len() as u32)?;
-        cursor.write_u32::<BigEndian>(DAT1_FORMAT_ID)?; // unknown1: format identifier seen in original files
-        cursor.write_u32::<BigEndian>(0)?; // unknown2: always zero in practice
-        cursor.write_u32::<BigEndian>(0)?; // unknown3: always zero in practice
+        for field in self.header_unknowns {
+            cursor.write_u32::<BigEndian>(field)?;
+        }
 
         // Write directory names
         for dir in &self.directories {
-            cursor.write_u8(dir.name.len() as u8)?;
-            cursor.write_all(dir.name.as_bytes())?;
+            cursor.write_u8(Self::name_len(&dir.name)?)?;
+            cursor.write_all(&utils::encode_filename(&dir.name)?)?;
         }
 
         // Calculate where file data starts (after all directory content headers)
@@ -335,36 +893,27 @@ impl Dat1Archive {
         for dir in &self.directories {
             data_offset += 16; // Directory header: file_count + 3 unknown fields
             for file in &dir.files {
-                let file_name_len =
-                    if file.name.starts_with(&format!("{}\\", dir.name)) && dir.name != "." {
-                        file.name.len() - dir.name.len() - 1
-                    } else {
-                        file.name.len()
-                    };
+                let file_name_len = utils::encoded_name_len(dir.stored_file_name(file));
                 data_offset += 1 + file_name_len as u32 + 16; // name_len byte + name + entry fields
             }
         }
 
+        utils::check_archive_size(self.entries(), data_offset as u64)?;
+
         let mut current_offset = data_offset;
 
         // Write directory content headers and file entries
         for dir in &self.directories {
             cursor.write_u32::<BigEndian>(dir.files.len() as u32)?;
-            cursor.write_u32::<BigEndian>(DAT1_FORMAT_ID)?;
-            cursor.write_u32::<BigEndian>(DAT1_DIRECTORY_UNKNOWN5)?;
-            cursor.write_u32::<BigEndian>(0)?;
+            for field in dir.unknowns {
+                cursor.write_u32::<BigEndian>(field)?;
+            }
 
             for file in &dir.files {
-                // Strip directory prefix from filename for storage
-                let file_name =
-                    if file.name.starts_with(&format!("{}\\", dir.name)) && dir.name != "." {
-                        &file.name[dir.name.len() + 1..]
-                    } else {
-                        &file.name
-                    };
+                let file_name = dir.stored_file_name(file);
 
-                cursor.write_u8(file_name.len() as u8)?;
-                cursor.write_all(file_name.as_bytes())?;
+                cursor.write_u8(Self::name_len(file_name)?)?;
+                cursor.write_all(&utils::encode_filename(file_name)?)?;
 
                 let attributes = if file.compressed {
                     DAT1_COMPRESSED_FLAG
@@ -396,7 +945,9 @@ impl Dat1Archive {
             }
         }
 
-        fs::write(path, output).context("Failed to write DAT1 file")?;
+        utils::write_atomically(path, |file| {
+            file.write_all(&output).context("Failed to write DAT1 file")
+        })?;
 
         Ok(())
     }
@@ -9,22 +9,38 @@ Little-endian, flat file list, zlib compression, parallel extraction via rayon.
 3. Footer (8 bytes): tree_size + dat_size
 */
 
-use anyhow::{bail, Context, Result};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use anyhow::{Context, Result};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use deku::prelude::*;
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use rayon::prelude::*;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io::{Cursor, Read, Write};
-use std::path::Path;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc,
 };
 use std::time::Instant;
 
-use crate::common::{self, utils, CompressionLevel, ExtractionMode, FileEntry};
+use crate::cache::{CacheConfig, EntryCache};
+use crate::common::{
+    self, utils, AddOptions, AddStats, CompressionOutcome, CompressionPolicy, Event,
+    ExtractOptions, ExtractOutcome, FileEntry, MemoryBudget, NameCase, OpenOptions, Progress,
+    SharedData, SpaceReport,
+};
+use crate::error::{DatError, DatResult, TreeContext};
+
+/// Suffix of compressed entries extracted raw: their zlib streams
+const RAW_SUFFIX: &str = ".z";
+
+/// How far before the end of the file to look for the footer of an archive
+/// with bytes appended after it, like a self-extractor's padding
+const MAX_TRAILING_BYTES: usize = 64 * 1024;
 
 /// 8-byte footer at the end of every DAT2 file.
 /// Points to the directory tree and validates the total file size.
@@ -35,8 +51,8 @@ struct Dat2Footer {
     dat_size: u32,
 }
 
-/// File entry as stored in the DAT2 directory tree
-#[derive(Debug, DekuRead, DekuWrite)]
+/// File entry as stored in the DAT2 directory tree, for writing
+#[derive(Debug, DekuWrite)]
 #[deku(endian = "little")]
 struct Dat2FileEntry {
     filename_size: u32,
@@ -48,12 +64,94 @@ struct Dat2FileEntry {
     offset: u32,
 }
 
+/// Bytes of a tree entry besides its name: name size, compression type,
+/// real size, packed size and offset
+const TREE_ENTRY_FIXED_SIZE: usize = 4 + 1 + 4 + 4 + 4;
+
+/// File entry read in place from the DAT2 directory tree; the name is
+/// borrowed from the tree, so only the decoded `String` is allocated
+#[derive(Debug)]
+struct TreeEntry<'a> {
+    filename_bytes: &'a [u8],
+    compression_type: u8,
+    real_size: u32,
+    packed_size: u32,
+    offset: u32,
+}
+
+impl<'a> TreeEntry<'a> {
+    /// Read the entry at the start of `tree`, returning it and its size in
+    /// bytes; None if the tree ends inside it
+    fn read(tree: &'a [u8]) -> Option<(Self, usize)> {
+        let name_size = LittleEndian::read_u32(tree.get(..4)?) as usize;
+        let name_end = name_size.checked_add(4)?;
+        let entry_size = name_end.checked_add(TREE_ENTRY_FIXED_SIZE - 4)?;
+        let fields = tree.get(name_end..entry_size)?;
+        let entry = Self {
+            filename_bytes: &tree[4..name_end],
+            compression_type: fields[0],
+            real_size: LittleEndian::read_u32(&fields[1..5]),
+            packed_size: LittleEndian::read_u32(&fields[5..9]),
+            offset: LittleEndian::read_u32(&fields[9..13]),
+        };
+        Some((entry, entry_size))
+    }
+
+    /// The entry as listed in the archive, `index`th in the tree.
+    ///
+    /// Some third-party packers store `/` separators. The name is kept in
+    /// the stored form (`\` separators) that saving writes back, so adding
+    /// `a\b.txt` replaces an entry stored as `a/b.txt` instead of adding a
+    /// second one.
+    fn into_file_entry(self, name: String, index: usize) -> FileEntry {
+        let name = if utils::is_stored_entry_path(&name) {
+            name
+        } else {
+            utils::stored_entry_path(&name)
+        };
+        FileEntry {
+            name,
+            offset: self.offset as u64,
+            size: self.real_size,
+            packed_size: self.packed_size,
+            compressed: self.compression_type == 1,
+            data: None,
+            original_index: Some(index),
+            // Unknown types are kept, so the entry fails instead of being read as stored
+            unsupported_compression: (self.compression_type > 1).then_some(self.compression_type),
+            stored_type: Some(self.compression_type.into()),
+            directory_index: None,
+            nul_terminated_name: self.filename_bytes.iter().position(|&b| b == 0)
+                == Some(self.filename_bytes.len().saturating_sub(1)),
+        }
+    }
+}
+
 /// DAT2 archive handler (Fallout 2 format)
 #[derive(Debug)]
 pub struct Dat2Archive {
     files: Vec<FileEntry>,
     /// Raw archive data for reading existing file content
     data: Vec<u8>,
+    /// Optional cache of decompressed entries (`--cache-dir`)
+    cache: Option<EntryCache>,
+    /// Keep the parsed tree order on add instead of re-sorting (`--keep-order`)
+    keep_order: bool,
+    /// Archive size recorded in the footer, when a lenient open found it wrong
+    recorded_size: Option<u32>,
+    /// Number of entries in the tree the archive was parsed from; None if it
+    /// wasn't parsed from a whole archive (see `save_appending`)
+    opened_entries: Option<usize>,
+    /// Save names read with a trailing NUL with it (`OpenOptions::nul_names`)
+    nul_names: bool,
+    /// Bytes found after the footer and left out of `data`
+    trailing_bytes: Option<usize>,
+}
+
+impl Default for Dat2Archive {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Dat2Archive {
@@ -62,317 +160,1187 @@ impl Dat2Archive {
         Self {
             files: Vec::new(),
             data: Vec::new(),
+            cache: None,
+            keep_order: false,
+            recorded_size: None,
+            opened_entries: None,
+            nul_names: false,
+            trailing_bytes: None,
         }
     }
 
     /// Parse an existing DAT2 archive from raw bytes
-    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+    pub fn from_bytes(data: Vec<u8>) -> DatResult<Self> {
+        Self::from_bytes_with(data, &OpenOptions::default())
+    }
+
+    /// Parse an existing DAT2 archive from raw bytes with the given options
+    ///
+    /// If the last 8 bytes aren't a footer recording the file's size, the
+    /// archive may have bytes appended after its footer: the last
+    /// `MAX_TRAILING_BYTES` are searched for a footer that records its own
+    /// end as the archive size and whose tree parses, and the archive ends
+    /// there. The bytes after it are dropped, so saving writes a clean
+    /// archive (see `trailing_bytes`).
+    pub fn from_bytes_with(mut data: Vec<u8>, options: &OpenOptions) -> DatResult<Self> {
         if data.len() < 8 {
-            bail!("DAT2 file too small");
+            return Err(DatError::tree("DAT2 file too small", 0));
         }
 
-        let files = Self::parse_directory_tree(&data)?;
-        Ok(Self { files, data })
+        let (files, footer, trailing_bytes) = match Self::parse_directory_tree(&data, options) {
+            Ok((files, footer)) if footer.dat_size as usize == data.len() => (files, footer, None),
+            parsed => match Self::parse_before_trailing_bytes(&data, options) {
+                Some((end, files, footer)) => {
+                    let trailing = data.len() - end;
+                    data.truncate(end);
+                    (files, footer, Some(trailing))
+                }
+                None => {
+                    let (files, footer) = parsed?;
+                    (files, footer, None)
+                }
+            },
+        };
+        let recorded_size = (footer.dat_size as usize != data.len()).then_some(footer.dat_size);
+        Ok(Self {
+            opened_entries: Some(files.len()),
+            files,
+            data,
+            cache: None,
+            keep_order: false,
+            recorded_size,
+            nul_names: options.nul_names,
+            trailing_bytes,
+        })
     }
 
-    fn parse_directory_tree(data: &[u8]) -> Result<Vec<FileEntry>> {
-        // Parse 8-byte footer at end of file
-        let footer_bytes = &data[data.len() - 8..];
-        let (_, footer) = Dat2Footer::from_bytes((footer_bytes, 0))
-            .map_err(|e| anyhow::anyhow!("Failed to parse DAT2 footer: {}", e))?;
+    /// Find the archive at the start of `data` when bytes follow its footer:
+    /// the last end position, within `MAX_TRAILING_BYTES` of the end of
+    /// `data`, preceded by a footer recording that position as the archive
+    /// size and a tree that parses. Returns the end, entries and footer.
+    fn parse_before_trailing_bytes(
+        data: &[u8],
+        options: &OpenOptions,
+    ) -> Option<(usize, Vec<FileEntry>, Dat2Footer)> {
+        let strict_size = OpenOptions {
+            lenient: false,
+            ..*options
+        };
+        let earliest = data.len().saturating_sub(MAX_TRAILING_BYTES).max(8);
+        (earliest..data.len()).rev().find_map(|end| {
+            if LittleEndian::read_u32(&data[end - 4..end]) as usize != end {
+                return None;
+            }
+            let (files, footer) = Self::parse_directory_tree(&data[..end], &strict_size).ok()?;
+            // As with a wrong footer size, the tree must fit what's left
+            let fits = files
+                .iter()
+                .all(|file| file.offset + file.packed_size as u64 <= end as u64);
+            fits.then_some((end, files, footer))
+        })
+    }
 
-        if footer.dat_size as usize != data.len() {
-            bail!(
-                "DAT size mismatch: expected {}, got {}",
-                footer.dat_size,
-                data.len()
-            );
+    /// An archive over `data` with the entries `repair` recovered whose data
+    /// reads back intact, and the errors of those that don't
+    pub(crate) fn from_recovered(files: Vec<FileEntry>, data: Vec<u8>) -> (Self, Vec<DatError>) {
+        let mut archive = Self {
+            data,
+            ..Self::new()
+        };
+        let verdicts: Vec<_> = files
+            .par_iter()
+            .map(|file| archive.read_entry_contents(file).err())
+            .collect();
+
+        let mut errors = Vec::new();
+        for (file, verdict) in files.into_iter().zip(verdicts) {
+            match verdict {
+                None => archive.files.push(file),
+                Some(error) => errors.push(error),
+            }
         }
+        archive.sort_entries();
+        (archive, errors)
+    }
+
+    /// Parse the tree entry at the start of `tree`, returning it and its size
+    /// in bytes. `repair` probes arbitrary offsets with this, so the caller
+    /// must have checked that the name size is plausible.
+    pub(crate) fn parse_tree_entry(tree: &[u8], index: usize) -> Option<(FileEntry, usize)> {
+        let (entry, consumed) = TreeEntry::read(tree)?;
+        let name = utils::decode_filename(entry.filename_bytes).ok()?;
+        Some((entry.into_file_entry(name, index), consumed))
+    }
 
-        // Directory tree position: dat_size - tree_size - 8 (footer)
-        let tree_start = footer.dat_size as usize - footer.tree_size as usize - 8;
-        if tree_start < 4 {
-            bail!("Invalid directory tree position");
+    /// How many bytes after the footer were left out when the archive was
+    /// opened, if any
+    pub fn trailing_bytes(&self) -> Option<usize> {
+        self.trailing_bytes
+    }
+
+    /// The wrong footer size accepted by an `OpenOptions::lenient` open, if any
+    pub fn footer_size_mismatch(&self) -> Option<DatError> {
+        self.recorded_size
+            .map(|recorded| DatError::FooterSizeMismatch {
+                recorded: recorded as u64,
+                actual: self.data.len() as u64,
+            })
+    }
+
+    /// Serve decompressed entries from (and store them in) an on-disk cache
+    pub fn enable_cache(&mut self, config: &CacheConfig) -> Result<()> {
+        self.cache = Some(EntryCache::for_archive(config, &self.data)?);
+        Ok(())
+    }
+
+    /// Keep entries in their parsed tree order when adding files.
+    ///
+    /// A replaced entry keeps the slot of the entry it replaces; entries with
+    /// new names are appended in operand order, each operand's files sorted.
+    pub fn set_keep_order(&mut self, keep_order: bool) {
+        self.keep_order = keep_order;
+    }
+
+    fn parse_directory_tree(
+        data: &[u8],
+        options: &OpenOptions,
+    ) -> DatResult<(Vec<FileEntry>, Dat2Footer)> {
+        // Parse 8-byte footer at end of file
+        let footer_start = data.len() - 8;
+        let (_, footer) = Dat2Footer::from_bytes((&data[footer_start..], 0))
+            .tree_context(footer_start as u64, || "Failed to parse DAT2 footer")?;
+
+        let size_mismatch = footer.dat_size as usize != data.len();
+        if size_mismatch && !options.lenient {
+            return Err(DatError::FooterSizeMismatch {
+                recorded: footer.dat_size as u64,
+                actual: data.len() as u64,
+            });
         }
 
+        // Directory tree position: file size - tree_size - 8 (footer). The
+        // footer is always the last 8 bytes, so the actual size is used even
+        // when a lenient open accepted a wrong recorded one.
+        let tree_start = data
+            .len()
+            .checked_sub(footer.tree_size as usize + 8)
+            .filter(|&start| start >= 4)
+            .ok_or_else(|| {
+                DatError::tree("Invalid directory tree position", footer_start as u64)
+            })?;
+
         // Read file count
         let mut cursor = Cursor::new(&data[tree_start..]);
         let file_count = cursor
             .read_u32::<LittleEndian>()
-            .context("Failed to read file count from DAT2 directory tree")?;
+            .tree_context(tree_start as u64, || {
+                "Failed to read file count from DAT2 directory tree"
+            })?;
 
-        // Parse file entries using deku
-        let mut files = Vec::with_capacity(file_count as usize);
+        // Every entry takes at least TREE_ENTRY_FIXED_SIZE bytes, which
+        // bounds the allocation for a corrupt count
         let tree_data = &data[tree_start + 4..data.len() - 8];
+        let mut files =
+            Vec::with_capacity((file_count as usize).min(tree_data.len() / TREE_ENTRY_FIXED_SIZE));
         let mut current_offset = 0;
 
         for i in 0..file_count {
-            let remaining_data = &tree_data[current_offset..];
-            let ((remaining_slice, _bit_offset), entry) =
-                Dat2FileEntry::from_bytes((remaining_data, 0))
-                    .map_err(|e| anyhow::anyhow!("Failed to parse file entry: {}", e))?;
-
-            let filename = utils::decode_filename(&entry.filename_bytes)
-                .with_context(|| format!("Failed to decode filename for file entry {i}"))?;
-
-            files.push(FileEntry {
-                name: filename,
-                offset: entry.offset as u64,
-                size: entry.real_size,
-                packed_size: entry.packed_size,
-                compressed: entry.compression_type == 1,
-                data: None,
-            });
+            let entry_start = (tree_start + 4 + current_offset) as u64;
+            let (entry, entry_size) = TreeEntry::read(&tree_data[current_offset..])
+                .ok_or("the directory tree ends inside the entry")
+                .tree_context(entry_start, || "Failed to parse file entry")?;
+
+            let filename = utils::decode_filename(entry.filename_bytes)
+                .tree_context(entry_start, || {
+                    format!("Failed to decode filename for file entry {i}")
+                })?;
+
+            // With a wrong footer, an intact tree is the only evidence the
+            // archive is usable: every entry must lie within the file
+            if size_mismatch && entry.offset as u64 + entry.packed_size as u64 > data.len() as u64 {
+                return Err(DatError::tree(
+                    format!(
+                        "Entry {} extends past the end of the file",
+                        utils::canonical_entry_path(&filename)
+                    ),
+                    entry_start,
+                ));
+            }
 
-            let bytes_consumed = remaining_data.len() - remaining_slice.len();
-            current_offset += bytes_consumed;
+            files.push(entry.into_file_entry(filename, i as usize));
+            current_offset += entry_size;
         }
 
-        Ok(files)
+        Ok((files, footer))
+    }
+
+    /// All entries, in archive order
+    pub fn entries(&self) -> impl Iterator<Item = &FileEntry> + '_ {
+        self.files.iter()
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.files.len()
     }
 
-    /// List files in the archive (all or filtered by patterns)
-    pub fn list(&self, files: &[String]) -> Result<()> {
-        let all_files: Vec<&FileEntry> = self.files.iter().collect();
-        common::list_files_filtered(&all_files, files)
+    /// Whether the archive has no entries
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
     }
 
     /// Extract files from the archive using parallel processing
-    pub fn extract(&self, output_dir: &Path, files: &[String], mode: ExtractionMode) -> Result<()> {
-        let files_to_extract = common::filter_files_by_patterns(&self.files, files);
-        self.extract_files_parallel(&files_to_extract, output_dir, mode)
+    pub fn extract(
+        &self,
+        output_dir: &Path,
+        files: &[String],
+        options: &ExtractOptions,
+        progress: &Progress,
+    ) -> Result<()> {
+        let all_files: Vec<_> = self.entries().collect();
+        let (mut files_to_extract, missing_patterns) =
+            common::filter_files_by_patterns(&all_files, files);
+        options.skip_duplicate_names(&mut files_to_extract, progress);
+        options.order_entries(&mut files_to_extract);
+        options.check_output_paths(&files_to_extract)?;
+        let skipped = options.skip_shallow_entries(&mut files_to_extract, progress);
+        self.extract_files_parallel(&files_to_extract, skipped, output_dir, options, progress)?;
+        options.check_missing(&missing_patterns, progress)
     }
 
     /// Parallel extraction using rayon
     fn extract_files_parallel(
         &self,
         files_to_extract: &[&FileEntry],
+        skipped: usize,
         output_dir: &Path,
-        mode: ExtractionMode,
+        options: &ExtractOptions,
+        progress: &Progress,
     ) -> Result<()> {
         let archive_data = Arc::new(self.data.as_slice());
         let total_files = files_to_extract.len();
         let completed = Arc::new(AtomicUsize::new(0));
+        let failures = AtomicUsize::new(0);
+        let written = AtomicUsize::new(0);
+        let current = AtomicUsize::new(0);
+        let budget = MemoryBudget::new(options.memory_budget());
 
-        println!("Extracting {total_files} files...");
+        progress.emit(Event::ExtractStarted { total: total_files });
         let start = Instant::now();
 
         files_to_extract
             .par_iter()
             .try_for_each(|file| -> Result<()> {
-                utils::validate_archive_path(&file.name)?;
-
                 // Progress reporting every 1000 files
                 let count = completed.fetch_add(1, Ordering::Relaxed) + 1;
                 if count.is_multiple_of(1000) || count == total_files {
-                    let elapsed = start.elapsed().as_millis();
-                    let files_per_sec = count as f64 / elapsed as f64 * 1000.0;
-                    println!(
-                        "Progress: {count}/{total_files} files extracted ({files_per_sec:.1} files/sec)"
-                    );
+                    progress.emit(Event::Extracted {
+                        count,
+                        total: total_files,
+                        elapsed: start.elapsed(),
+                    });
                 }
 
-                let output_path = match mode {
-                    ExtractionMode::Flat => {
-                        let filename = utils::get_filename_from_dat_path(&file.name);
-                        output_dir.join(filename)
+                let result = self.extract_one(&archive_data, file, output_dir, options, &budget);
+                let outcome = match result {
+                    Ok(true) => {
+                        options.set_mtime(
+                            &options.entry_output_path(output_dir, file, RAW_SUFFIX),
+                            progress,
+                        );
+                        Progress::add(&written, 1);
+                        Progress::add(&progress.extracted, 1);
+                        ExtractOutcome::Written
                     }
-                    ExtractionMode::PreserveStructure => {
-                        output_dir.join(utils::to_system_path(&file.name))
+                    Ok(false) => {
+                        Progress::add(&current, 1);
+                        ExtractOutcome::Current
                     }
+                    Err(_) => ExtractOutcome::Failed,
                 };
+                log::debug!(
+                    "Extracting {}: {outcome:?}",
+                    utils::canonical_entry_path(&file.name)
+                );
+                progress.emit(Event::EntryExtracted {
+                    name: &file.name,
+                    bytes: outcome.bytes(file),
+                    outcome,
+                });
+                options.handle_entry_result(result.map(|_| ()), &failures, progress)
+            })?;
 
-                utils::ensure_dir_exists(&output_path)?;
+        progress.emit(Event::ExtractFinished {
+            elapsed: start.elapsed(),
+            written: written.into_inner(),
+            skipped,
+            current: current.into_inner(),
+        });
+        ExtractOptions::check_failures(&failures, total_files)
+    }
 
-                // Read and optionally decompress
-                let file_data = self.read_file_data_from_slice(&archive_data, file)?;
-                let final_data = if file.compressed {
-                    Self::decompress_zlib(&file_data, file.size as usize)
-                        .with_context(|| format!("Failed to decompress {}", file.name))?
-                } else {
-                    file_data
-                };
+    /// Extract a single entry to its place under `output_dir`.
+    ///
+    /// A compressed entry holds its decompressed size from `budget` while
+    /// it's in memory; one larger than the whole budget is decompressed
+    /// straight to its file instead. Stored entries are written from the
+    /// archive's data without a copy.
+    ///
+    /// Returns false if `update` left an up-to-date file there alone. An
+    /// entry compared by `checksum` is always decompressed in memory.
+    fn extract_one(
+        &self,
+        archive_data: &[u8],
+        file: &FileEntry,
+        output_dir: &Path,
+        options: &ExtractOptions,
+        budget: &MemoryBudget,
+    ) -> Result<bool> {
+        utils::validate_archive_path(&file.name)?;
+        if !options.force_raw && !options.raw {
+            Self::check_compression_type(file)?;
+        }
+
+        let output_path = options.entry_output_path(output_dir, file, RAW_SUFFIX);
+        let may_be_current = options.may_be_current(&output_path, file);
+        if may_be_current && !options.checksum {
+            return Ok(false);
+        }
 
-                fs::write(&output_path, final_data)
-                    .with_context(|| format!("Failed to write {}", output_path.display()))?;
+        utils::ensure_dir_exists(&output_path)?;
 
-                Ok(())
+        let stored = Self::file_data_from_slice(archive_data, file)?;
+        let final_data;
+        let _held;
+        let content = if !file.compressed || options.raw {
+            stored
+        } else if file.size as u64 > budget.total() && !may_be_current {
+            Self::decompress_entry_to_file(file, stored, &output_path)?;
+            return Ok(true);
+        } else {
+            _held = budget.acquire(file.size as u64);
+            final_data = EntryCache::get_or_insert(self.cache.as_ref(), file, || {
+                Ok(Self::decompress_entry(file, stored)?)
             })?;
+            &final_data[..]
+        };
+        if may_be_current && ExtractOptions::holds_content(&output_path, content) {
+            return Ok(false);
+        }
 
-        let total_time = start.elapsed();
-        println!("Extraction completed in {:.2}s", total_time.as_secs_f64());
-        Ok(())
+        fs::write(&output_path, content)
+            .with_context(|| format!("Failed to write {}", output_path.display()))?;
+        Ok(true)
     }
 
-    /// Read file data from a shared byte slice (thread-safe for parallel extraction)
-    fn read_file_data_from_slice(&self, archive_data: &[u8], file: &FileEntry) -> Result<Vec<u8>> {
+    /// Borrow an entry's stored bytes: its in-memory data, or its range of
+    /// `archive_data`
+    fn file_data_from_slice<'a>(
+        archive_data: &'a [u8],
+        file: &'a FileEntry,
+    ) -> DatResult<&'a [u8]> {
         if let Some(ref data) = file.data {
-            return Ok(data.clone());
+            return Ok(data);
         }
 
         let start = file.offset as usize;
         let end = start + file.packed_size as usize;
 
         if end > archive_data.len() {
-            bail!(
-                "File data extends beyond archive: {} (offset: {}, size: {})",
-                file.name,
+            return Err(DatError::tree(
+                format!(
+                    "File data extends beyond archive: {} (size: {})",
+                    file.path(),
+                    file.packed_size
+                ),
                 file.offset,
-                file.packed_size
-            );
+            ));
         }
 
-        Ok(archive_data[start..end].to_vec())
+        Ok(&archive_data[start..end])
     }
 
-    /// Read file data from the archive's own data buffer
-    fn read_file_data(&self, file: &FileEntry) -> Result<Vec<u8>> {
-        self.read_file_data_from_slice(&self.data, file)
+    /// Decompress an entry's stored zlib data with a pre-allocated output buffer
+    ///
+    /// The stream's Adler-32 trailer is verified by the decoder; the result
+    /// must also have exactly the size recorded in the directory tree.
+    fn decompress_entry(file: &FileEntry, data: &[u8]) -> DatResult<Vec<u8>> {
+        let mut decompressed = Vec::with_capacity(file.size as usize);
+        Self::write_entry(file, data, &mut decompressed)?;
+        Ok(decompressed)
     }
 
-    /// Decompress zlib data with a pre-allocated output buffer
-    fn decompress_zlib(data: &[u8], expected_size: usize) -> Result<Vec<u8>> {
-        let mut decoder = ZlibDecoder::new(data);
-        let mut decompressed = Vec::with_capacity(expected_size);
-        decoder
-            .read_to_end(&mut decompressed)
-            .context("Failed to decompress zlib data")?;
-        Ok(decompressed)
+    /// Write an entry's content, from its stored bytes `data`, into `out`,
+    /// decompressing it with the checks of `decompress_entry` if needed
+    fn write_entry<W: Write + ?Sized>(
+        file: &FileEntry,
+        data: &[u8],
+        out: &mut W,
+    ) -> DatResult<u64> {
+        if !file.compressed {
+            out.write_all(data).map_err(|source| DatError::Write {
+                name: file.path(),
+                source,
+            })?;
+            return Ok(data.len() as u64);
+        }
+        utils::copy_entry_content(file, ZlibDecoder::new(data), out, |e| {
+            io::Error::new(
+                e.kind(),
+                format!("corrupt zlib stream or checksum mismatch: {e}"),
+            )
+        })
+    }
+
+    /// Decompress an entry's stored zlib data straight to `output_path`, with
+    /// the checks of `decompress_entry`. A partly written file is removed.
+    fn decompress_entry_to_file(file: &FileEntry, data: &[u8], output_path: &Path) -> Result<()> {
+        let result = Self::stream_entry_to_file(file, data, output_path);
+        if result.is_err() {
+            let _ = fs::remove_file(output_path);
+        }
+        result
+    }
+
+    fn stream_entry_to_file(file: &FileEntry, data: &[u8], output_path: &Path) -> Result<()> {
+        let write_context = || format!("Failed to write {}", output_path.display());
+        let mut output =
+            io::BufWriter::new(fs::File::create(output_path).with_context(write_context)?);
+        match Self::write_entry(file, data, &mut output) {
+            Err(DatError::Write { source, .. }) => {
+                return Err(anyhow::Error::new(source).context(write_context()))
+            }
+            result => result?,
+        };
+        output.flush().with_context(write_context)
     }
 
     /// Compress data using zlib
-    fn compress_zlib(data: &[u8], level: u8) -> Result<Vec<u8>> {
+    pub(crate) fn compress_zlib(data: &[u8], level: u8) -> Result<Vec<u8>> {
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level as u32));
         encoder.write_all(data)?;
         encoder.finish().context("Failed to compress with zlib")
     }
 
     /// Process a single file for adding to the archive
+    /// Returns the entry, how it was stored and whether its name was case-folded.
     fn process_single_file_for_adding(
         &self,
         file: &Path,
         base_path: &Path,
-        compression: CompressionLevel,
-        target_dir: Option<&str>,
-        source_root: Option<&Path>,
-    ) -> Result<FileEntry> {
-        let data = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
-        let archive_path = utils::calculate_archive_path(file, base_path, target_dir, source_root)?;
-        let display_path = utils::normalize_path_for_display(&archive_path);
-        println!("Adding: {display_path}");
-
-        if compression.level() > 0 {
-            let compressed_data = Self::compress_zlib(&data, compression.level())?;
-            // Only use compression if it actually saves space
-            if compressed_data.len() < data.len() {
-                Ok(FileEntry::with_compression_data(
-                    archive_path,
-                    data,
-                    compressed_data,
-                ))
-            } else {
-                let mut entry = FileEntry::with_data(archive_path, data, false);
-                entry.size = entry.packed_size;
-                Ok(entry)
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<(FileEntry, CompressionOutcome, bool)> {
+        let data = utils::read_file_to_add(file)?;
+        let (archive_path, folded) = options.archive_path(file, base_path)?;
+        progress.emit(Event::Adding {
+            name: &archive_path,
+        });
+
+        let (entry, outcome) = Self::build_entry(archive_path, data, &options.compression)?;
+        log::debug!(
+            "Added {}: {} bytes, {outcome:?}",
+            utils::canonical_entry_path(&entry.name),
+            entry.size
+        );
+        progress.emit(Event::Added {
+            name: &entry.name,
+            bytes: entry.size as u64,
+        });
+        Ok((entry, outcome, folded))
+    }
+
+    /// Process files for adding like `process_single_file_for_adding`, but
+    /// compress each distinct content once (`AddOptions::dedup`): every file
+    /// is read first, and files with identical content and the same store
+    /// decision get copies of one built entry. Results are in `files` order.
+    fn process_files_sharing_content(
+        &self,
+        files: &[PathBuf],
+        base_path: &Path,
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<Vec<(FileEntry, CompressionOutcome, bool)>> {
+        let read: Vec<(String, bool, Vec<u8>)> = files
+            .par_iter()
+            .map(|file| {
+                let data = utils::read_file_to_add(file)?;
+                let (archive_path, folded) = options.archive_path(file, base_path)?;
+                progress.emit(Event::Adding {
+                    name: &archive_path,
+                });
+                Ok((archive_path, folded, data))
+            })
+            .collect::<Result<_>>()?;
+
+        // Distinct contents, each with the positions of the files that have it
+        type Group = (Vec<u8>, Vec<(usize, String, bool)>);
+        let mut groups: Vec<Group> = Vec::new();
+        let mut by_content: HashMap<([u8; 32], bool), usize> = HashMap::new();
+        for (i, (archive_path, folded, data)) in read.into_iter().enumerate() {
+            let store_only = options.compression.is_store_only(Path::new(&archive_path));
+            let key = (Sha256::digest(&data).into(), store_only);
+            match by_content.get(&key) {
+                Some(&group) => groups[group].1.push((i, archive_path, folded)),
+                None => {
+                    by_content.insert(key, groups.len());
+                    groups.push((data, vec![(i, archive_path, folded)]));
+                }
             }
+        }
+
+        let built: Vec<Vec<(usize, FileEntry, CompressionOutcome, bool)>> = groups
+            .into_par_iter()
+            .map(|(data, names)| {
+                let (entry, outcome) =
+                    Self::build_entry(names[0].1.clone(), data, &options.compression)?;
+                Ok(names
+                    .into_iter()
+                    .map(|(i, archive_path, folded)| {
+                        let entry = FileEntry {
+                            name: archive_path,
+                            ..entry.clone()
+                        };
+                        log::debug!(
+                            "Added {}: {} bytes, {outcome:?}",
+                            utils::canonical_entry_path(&entry.name),
+                            entry.size
+                        );
+                        progress.emit(Event::Added {
+                            name: &entry.name,
+                            bytes: entry.size as u64,
+                        });
+                        (i, entry, outcome, folded)
+                    })
+                    .collect())
+            })
+            .collect::<Result<_>>()?;
+
+        let mut processed: Vec<_> = built.into_iter().flatten().collect();
+        processed.sort_by_key(|(i, ..)| *i);
+        Ok(processed
+            .into_iter()
+            .map(|(_, entry, outcome, folded)| (entry, outcome, folded))
+            .collect())
+    }
+
+    /// Build an entry for new content, compressing it according to `compression`
+    fn build_entry(
+        archive_path: String,
+        data: Vec<u8>,
+        compression: &CompressionPolicy,
+    ) -> Result<(FileEntry, CompressionOutcome)> {
+        let outcome = if compression.level.level() == 0 {
+            CompressionOutcome::Stored
+        } else if compression.is_store_only(Path::new(&archive_path)) {
+            // Already-compressed formats: zlib would only waste time
+            CompressionOutcome::StoredByExtension
         } else {
-            let mut entry = FileEntry::with_data(archive_path, data, false);
-            entry.size = entry.packed_size;
-            Ok(entry)
+            let compressed_data = Self::compress_zlib(&data, compression.level.level())?;
+            // Only use compression if it saves enough space
+            match compression.decide(data.len(), compressed_data.len()) {
+                CompressionOutcome::Compressed => {
+                    return Ok((
+                        FileEntry::with_compression_data(archive_path, data, compressed_data),
+                        CompressionOutcome::Compressed,
+                    ));
+                }
+                outcome => outcome,
+            }
+        };
+
+        let mut entry = FileEntry::with_data(archive_path, data, false);
+        entry.size = entry.packed_size;
+        Ok((entry, outcome))
+    }
+
+    /// Size of the archive as read, or 0 if it was never saved
+    pub(crate) fn archive_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// Byte range where entry data may live: from the start of the file up
+    /// to the directory tree
+    pub(crate) fn data_region(&self) -> Range<u64> {
+        let tree_start = match self.data.len().checked_sub(8) {
+            Some(footer_start) => {
+                let tree_size = LittleEndian::read_u32(&self.data[footer_start..]) as u64;
+                (footer_start as u64).saturating_sub(tree_size)
+            }
+            None => 0,
+        };
+        0..tree_start
+    }
+
+    /// Find data-region bytes that no entry references
+    pub fn space_report(&self) -> SpaceReport {
+        SpaceReport::analyze(
+            self.archive_size(),
+            self.data_region(),
+            self.files
+                .iter()
+                .filter(|f| f.data.is_none())
+                .map(|f| f.offset..f.offset + f.packed_size as u64),
+        )
+    }
+
+    /// Read one entry's decompressed content by its archive path
+    pub fn read_file(&self, name: &str) -> DatResult<Vec<u8>> {
+        let file = self
+            .files
+            .iter()
+            .find(|f| utils::compare_entry_paths(&f.name, name).is_eq())
+            .ok_or_else(|| DatError::EntryNotFound {
+                name: utils::canonical_entry_path(name),
+            })?;
+        self.read_entry_contents(file)
+    }
+
+    /// Stream one entry's content into `out` by its archive path, see
+    /// `DatArchive::read_file_to`
+    pub fn read_file_to<W: Write + ?Sized>(&self, name: &str, out: &mut W) -> DatResult<u64> {
+        let file = self
+            .files
+            .iter()
+            .find(|f| utils::compare_entry_paths(&f.name, name).is_eq())
+            .ok_or_else(|| DatError::EntryNotFound {
+                name: utils::canonical_entry_path(name),
+            })?;
+        self.write_entry_contents(file, out)
+    }
+
+    /// Stream an entry's content into `out`, decompressing it if needed
+    pub(crate) fn write_entry_contents<W: Write + ?Sized>(
+        &self,
+        file: &FileEntry,
+        out: &mut W,
+    ) -> DatResult<u64> {
+        Self::check_compression_type(file)?;
+        Self::write_entry(file, Self::file_data_from_slice(&self.data, file)?, out)
+    }
+
+    /// Fail an entry whose compression type isn't known
+    fn check_compression_type(file: &FileEntry) -> DatResult<()> {
+        match file.unsupported_compression {
+            Some(kind) => Err(DatError::UnsupportedCompression {
+                name: file.path(),
+                kind,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Read an entry's content, decompressing it if needed
+    pub(crate) fn read_entry_contents(&self, file: &FileEntry) -> DatResult<Vec<u8>> {
+        let mut content = Vec::with_capacity(file.size as usize);
+        self.write_entry_contents(file, &mut content)?;
+        Ok(content)
+    }
+
+    /// Sort entries the way the DAT2 format requires
+    fn sort_entries(&mut self) {
+        // Alphabetical, ASCII case-insensitive; stable, so equal names keep their order
+        self.files
+            .sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+    }
+
+    /// Rebuild the archive in canonical form.
+    ///
+    /// Every entry is decompressed and recompressed with `compression`;
+    /// names are normalized to backslashes, duplicate names keep the last
+    /// occurrence, and entries are sorted. The same input always produces
+    /// byte-identical output.
+    pub fn repack(&self, compression: &CompressionPolicy) -> Result<(Self, AddStats)> {
+        // BTreeMap makes the pre-sort order (and thus ties in the sort) deterministic
+        let mut latest: BTreeMap<String, &FileEntry> = BTreeMap::new();
+        for file in &self.files {
+            latest.insert(utils::stored_entry_path(&file.name), file);
+        }
+
+        let contents: Result<Vec<(String, Vec<u8>)>> = latest
+            .into_par_iter()
+            .map(|(name, file)| Ok((name, self.read_entry_contents(file)?)))
+            .collect();
+
+        Self::from_entries(contents?, compression)
+    }
+
+    /// Build a new archive from `(archive path, content)` pairs, compressing
+    /// with `compression` (parallel). Entries are sorted; names must be unique.
+    pub fn from_entries(
+        entries: Vec<(String, Vec<u8>)>,
+        compression: &CompressionPolicy,
+    ) -> Result<(Self, AddStats)> {
+        let processed: Result<Vec<(FileEntry, CompressionOutcome)>> = entries
+            .into_par_iter()
+            .map(|(name, data)| Self::build_entry(name, data, compression))
+            .collect();
+
+        let mut archive = Self::new();
+        let mut stats = AddStats::default();
+        for (entry, outcome) in processed? {
+            stats.record(outcome);
+            archive.files.push(entry);
         }
+        archive.sort_entries();
+
+        Ok((archive, stats))
     }
 
-    /// Add files to the archive (directories processed recursively, parallel)
+    /// Read the decompressed content of every entry whose name is in `names`,
+    /// in archive order
+    pub fn read_entries(&self, names: &HashSet<String>) -> Result<Vec<(String, Vec<u8>)>> {
+        self.files
+            .par_iter()
+            .filter(|file| names.contains(&file.name))
+            .map(|file| Ok((file.name.clone(), self.read_entry_contents(file)?)))
+            .collect()
+    }
+
+    /// Names of all entries, in archive order
+    pub fn entry_names(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.name.clone()).collect()
+    }
+
+    /// Add files to the archive (subdirectories only with `recursive`, parallel)
     pub fn add_file(
         &mut self,
         file_path: &Path,
-        compression: CompressionLevel,
-        target_dir: Option<&str>,
-        source_root: Option<&Path>,
-    ) -> Result<()> {
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
         let base_path = file_path;
-        let files = utils::collect_files(file_path).with_context(|| {
+        let found = utils::collect_files_excluding(
+            file_path,
+            options.recursive,
+            &options.excludes,
+            progress,
+        )
+        .with_context(|| {
             format!(
                 "Failed to collect files from path '{}'",
                 file_path.display()
             )
         })?;
+        let mut files = found.files;
+        let older = options.keep_newer(&mut files)?;
+        options.check_case_collisions(files.iter().map(|file| (file.as_path(), base_path)))?;
+        progress.emit(Event::AddStarted { total: files.len() });
 
         // Process files in parallel
-        let results: Result<Vec<FileEntry>> = files
-            .par_iter()
-            .map(|file| {
-                self.process_single_file_for_adding(
-                    file,
-                    base_path,
-                    compression,
-                    target_dir,
-                    source_root,
-                )
+        let processed = if options.dedup {
+            self.process_files_sharing_content(&files, base_path, options, progress)?
+        } else {
+            let results: Result<Vec<(FileEntry, CompressionOutcome, bool)>> = files
+                .par_iter()
+                .map(|file| self.process_single_file_for_adding(file, base_path, options, progress))
+                .collect();
+            results? // Collect results, propagating the first error if any file failed
+        };
+
+        let mut stats = AddStats {
+            excluded: found.excluded,
+            ignored: found.ignored,
+            older,
+            ..AddStats::default()
+        };
+        let mut new_entries = Vec::with_capacity(processed.len());
+        for (entry, outcome, folded) in processed {
+            stats.record(outcome);
+            stats.record_case_fold(folded);
+            new_entries.push(entry);
+        }
+        self.insert_new_entries(new_entries)?;
+        Ok(stats)
+    }
+
+    /// Add in-memory content, see `DatArchive::add_contents`
+    pub fn add_contents(
+        &mut self,
+        contents: Vec<(String, Vec<u8>)>,
+        options: &AddOptions,
+        progress: &Progress,
+    ) -> Result<AddStats> {
+        let mut stats = AddStats::default();
+        let named = options.content_archive_paths(contents, &mut stats)?;
+        progress.emit(Event::AddStarted { total: named.len() });
+        let processed: Result<Vec<(FileEntry, CompressionOutcome)>> = named
+            .into_par_iter()
+            .map(|(archive_path, data)| {
+                progress.emit(Event::Adding {
+                    name: &archive_path,
+                });
+                let built = Self::build_entry(archive_path, data, &options.compression)?;
+                log::debug!(
+                    "Added {}: {} bytes, {:?}",
+                    utils::canonical_entry_path(&built.0.name),
+                    built.0.size,
+                    built.1
+                );
+                progress.emit(Event::Added {
+                    name: &built.0.name,
+                    bytes: built.0.size as u64,
+                });
+                Ok(built)
             })
             .collect();
 
-        let new_entries = results?; // Collect results, propagating the first error if any file failed
+        let mut new_entries = Vec::new();
+        for (entry, outcome) in processed? {
+            stats.record(outcome);
+            new_entries.push(entry);
+        }
+        self.insert_new_entries(new_entries)?;
+        Ok(stats)
+    }
+
+    /// Put newly built entries into the archive, replacing existing entries
+    /// with the same names
+    fn insert_new_entries(&mut self, mut new_entries: Vec<FileEntry>) -> Result<()> {
+        // Deduplicate within the batch (keep first occurrence).
+        // This can happen if the user passes the same file or two files with the same name.
+        let mut seen_names = HashSet::new();
+        new_entries.retain(|entry| seen_names.insert(entry.name.clone()));
+
+        // Cheap early check, before the archive changes; `save` checks again
+        // with the tree included
+        utils::check_archive_size(
+            self.files
+                .iter()
+                .filter(|existing| !seen_names.contains(&existing.name))
+                .chain(&new_entries),
+            0,
+        )?;
+
+        if self.keep_order {
+            self.insert_keeping_order(new_entries);
+            return Ok(());
+        }
 
         // Remove existing files that match new file names
-        let new_file_names: HashSet<String> = new_entries.iter().map(|e| e.name.clone()).collect();
         self.files
-            .retain(|existing_file| !new_file_names.contains(&existing_file.name));
+            .retain(|existing_file| !seen_names.contains(&existing_file.name));
+        self.files.extend(new_entries);
+        self.sort_entries();
 
-        // Add new files, deduplicating within the batch (keep first occurrence).
-        // This can happen if the user passes the same file or two files with the same name.
-        let mut seen_names = HashSet::new();
+        Ok(())
+    }
+
+    /// Replace same-named entries in place and append the rest (see `set_keep_order`)
+    fn insert_keeping_order(&mut self, new_entries: Vec<FileEntry>) {
+        let positions: HashMap<String, usize> = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file.name.clone(), i))
+            .collect();
+
+        let mut appended = Vec::new();
         for entry in new_entries {
-            if seen_names.insert(entry.name.clone()) {
-                self.files.push(entry);
+            match positions.get(&entry.name) {
+                Some(&i) => self.files[i] = entry,
+                None => appended.push(entry),
             }
         }
 
-        // DAT2 format requires files sorted alphabetically (case-insensitive)
-        self.files
-            .sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        appended.sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+        self.files.extend(appended);
+    }
 
-        Ok(())
+    /// Fold the names of existing entries, see `DatArchive::fold_existing_names`
+    pub fn fold_existing_names(&mut self, case: NameCase) -> Result<usize> {
+        case.check_collisions(self.files.iter().map(|f| f.name.as_str()))?;
+
+        let mut renamed = 0;
+        for file in &mut self.files {
+            if let Cow::Owned(folded) = case.fold(&file.name) {
+                file.name = folded;
+                renamed += 1;
+            }
+        }
+        if !self.keep_order {
+            self.sort_entries();
+        }
+        Ok(renamed)
     }
 
     /// Delete a file from the archive by name
-    pub fn delete_file(&mut self, file_name: &str) -> Result<()> {
-        common::delete_file_from_list(&mut self.files, file_name)
+    pub fn delete_file(&mut self, file_name: &str, progress: &Progress) -> Result<()> {
+        common::delete_file_from_list(&mut self.files, file_name, progress)
+    }
+
+    /// Keep only the entries `keep` returns true for
+    pub fn retain_entries(&mut self, keep: impl FnMut(&FileEntry) -> bool) {
+        self.files.retain(keep);
     }
 
     /// Save the archive to a DAT2 file.
     ///
     /// DAT2 layout: file data, then directory tree, then 8-byte footer.
+    /// Entries that point at the same data keep sharing one copy of it.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let mut output = Vec::new();
-        let mut cursor = Cursor::new(&mut output);
+        self.save_sharing(path, false).map(|_| ())
+    }
 
-        // Step 1: Write all file data
-        let mut current_offset = 0u32;
-        let mut file_offsets = Vec::new();
+    /// Save like `save`, but also write identical stored data only once,
+    /// pointing every entry that has it at the one copy. Nothing in the
+    /// format ties an entry to data of its own, and readers find each
+    /// entry's data by its offset alone.
+    pub fn save_deduplicated(&self, path: &Path) -> Result<SharedData> {
+        self.save_sharing(path, true)
+    }
 
-        for file in &self.files {
-            file_offsets.push(current_offset);
+    /// Save, sharing data between entries that point at the same range of
+    /// the opened archive and, if `by_content`, between all entries with
+    /// identical stored data
+    fn save_sharing(&self, path: &Path, by_content: bool) -> Result<SharedData> {
+        // Step 1: Lay out the file data, each shared piece once, and check
+        // that what will be written fits before reading any of it
+        let mut current_offset = 0u64;
+        let mut file_offsets = Vec::with_capacity(self.files.len());
+        let mut written: Vec<&FileEntry> = Vec::new();
+        let mut by_range: HashMap<(u64, u32), u64> = HashMap::new();
+        let mut by_bytes: HashMap<(u8, u32, &[u8]), u64> = HashMap::new();
+        let mut shared = SharedData::default();
 
-            let data = if let Some(ref file_data) = file.data {
-                file_data.clone() // File data is already in memory (newly added file)
+        for file in &self.files {
+            let range = file
+                .data
+                .is_none()
+                .then_some((file.offset, file.packed_size));
+            // In memory for newly added files, otherwise in the original archive
+            let content = if by_content {
+                let data = Self::file_data_from_slice(&self.data, file)?;
+                Some((Self::stored_type_byte(file), file.size, data))
             } else {
-                self.read_file_data(file)? // Need to read from the original archive
+                None
             };
 
-            cursor.write_all(&data)?;
-            current_offset += data.len() as u32;
+            let previous = range
+                .and_then(|range| by_range.get(&range))
+                .or_else(|| content.and_then(|content| by_bytes.get(&content)));
+            if let (Some(&offset), false) = (previous, file.packed_size == 0) {
+                file_offsets.push(offset as u32);
+                shared.record(file.packed_size as usize);
+                continue;
+            }
+
+            file_offsets.push(current_offset as u32);
+            if let Some(range) = range {
+                by_range.insert(range, current_offset);
+            }
+            if let Some(content) = content {
+                by_bytes.insert(content, current_offset);
+            }
+            written.push(file);
+            current_offset += file.packed_size as u64;
         }
+        utils::check_archive_total(current_offset + self.tree_size() + 8)?;
 
-        // Step 2: Write directory tree
+        let mut output = Vec::new();
+        let mut cursor = Cursor::new(&mut output);
+        for file in written {
+            cursor.write_all(Self::file_data_from_slice(&self.data, file)?)?;
+        }
+
+        // Steps 2 and 3: Write directory tree and footer
         let tree_start = cursor.position();
+        self.write_tree(&mut cursor, &file_offsets, tree_start)?;
+
+        // Step 4: Write to disk
+        utils::write_atomically(path, |file| {
+            file.write_all(&output).context("Failed to write DAT2 file")
+        })?;
+
+        Ok(shared)
+    }
+
+    /// Save by appending only the added entries' data after the existing
+    /// data, then a fresh tree and footer, instead of rewriting the whole file.
+    ///
+    /// Only possible when the archive was opened from `path`, still has every
+    /// entry it was opened with (nothing deleted or replaced), and the file
+    /// hasn't changed since. Otherwise this is a full `save`. Returns whether
+    /// the archive was appended to.
+    pub fn save_appending(&self, path: &Path) -> Result<bool> {
+        let kept = self.files.iter().filter(|f| f.data.is_none()).count();
+        if self.opened_entries != Some(kept) {
+            self.save(path)?;
+            return Ok(false);
+        }
+        self.save_over_tree(path, true, |file, output| file.write_all(output))
+    }
+
+    /// Save after deleting entries by keeping the data as it is and writing
+    /// a new directory tree and footer after it. The deleted entries' data
+    /// stays behind as unreferenced bytes, which `optimize` reclaims.
+    ///
+    /// Only possible when no entry was added or replaced, the archive was
+    /// opened from `path`, and the file hasn't changed since. Otherwise this
+    /// is a full `save`. Either way the file is replaced atomically, like
+    /// `save`. Returns whether the data was kept as it was.
+    pub fn save_tree_only(&self, path: &Path) -> Result<bool> {
+        self.save_tree_with(path, false, |file, output| file.write_all(output))
+    }
+
+    /// `save_tree_only`, but writing the new tree over the old one in the
+    /// file itself rather than replacing the file, so only a few KB are
+    /// written. Not atomic: if interrupted, the file may be left without a
+    /// valid tree (`repair` recovers the entries).
+    pub fn save_tree_in_place(&self, path: &Path) -> Result<bool> {
+        self.save_tree_with(path, true, |file, output| file.write_all(output))
+    }
+
+    /// `save_tree_only` or `save_tree_in_place`, with `write` writing the
+    /// new bytes to the file, so tests can make it fail
+    pub(crate) fn save_tree_with(
+        &self,
+        path: &Path,
+        in_place: bool,
+        write: impl FnOnce(&mut fs::File, &[u8]) -> io::Result<()>,
+    ) -> Result<bool> {
+        if self.files.iter().any(|f| f.data.is_some()) {
+            self.save(path)?;
+            return Ok(false);
+        }
+        self.save_over_tree(path, in_place, write)
+    }
+
+    /// Write the added entries' data, then the tree and footer, in place of
+    /// the old tree of the file at `path`; or a full `save` if that file
+    /// isn't the one the archive was opened from, unchanged. Returns which
+    /// was done.
+    ///
+    /// `in_place` writes over the old tree in the file itself and syncs it;
+    /// otherwise the data before the old tree is copied into a new file,
+    /// which replaces `path` atomically. `write` writes the new bytes.
+    fn save_over_tree(
+        &self,
+        path: &Path,
+        in_place: bool,
+        write: impl FnOnce(&mut fs::File, &[u8]) -> io::Result<()>,
+    ) -> Result<bool> {
+        let Some(tree_start) = self.in_place_offset(path) else {
+            self.save(path)?;
+            return Ok(false);
+        };
+
+        let added: Vec<&FileEntry> = self.files.iter().filter(|f| f.data.is_some()).collect();
+        let tree_size = self.tree_size();
+        utils::check_archive_size(added.iter().copied(), tree_start + tree_size + 8)?;
+
+        let mut output = Vec::new();
+        let mut current_offset = tree_start as u32;
+        let mut file_offsets = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            match file.data {
+                Some(ref data) => {
+                    file_offsets.push(current_offset);
+                    output.extend_from_slice(data);
+                    current_offset += data.len() as u32;
+                }
+                None => file_offsets.push(file.offset as u32),
+            }
+        }
+        let appended_end = tree_start + output.len() as u64;
+        let mut cursor = Cursor::new(&mut output);
+        cursor.seek(SeekFrom::End(0))?;
+        self.write_tree(&mut cursor, &file_offsets, appended_end)?;
+
+        if !in_place {
+            utils::write_atomically(path, |file| {
+                file.write_all(&self.data[..tree_start as usize])
+                    .and_then(|()| write(file, &output))
+                    .context("Failed to write DAT2 file")
+            })?;
+            return Ok(true);
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .context("Failed to open DAT2 file for writing")?;
+        file.set_len(tree_start)
+            .and_then(|()| file.seek(SeekFrom::Start(tree_start)))
+            .and_then(|_| write(&mut file, &output))
+            .and_then(|()| file.sync_all())
+            .context("Failed to write DAT2 file")?;
+        Ok(true)
+    }
+
+    /// Where `save_over_tree` may start writing: the start of the old tree,
+    /// if `path` still holds the archive as it was opened and every kept
+    /// entry's data lies before the tree
+    fn in_place_offset(&self, path: &Path) -> Option<u64> {
+        self.opened_entries?;
+        if self.recorded_size.is_some() {
+            return None;
+        }
+        let tree_start = self.data_region().end;
+        // Same size and same tree and footer: the file we opened
+        let mut file = fs::File::open(path).ok()?;
+        if file.metadata().ok()?.len() != self.archive_size() {
+            return None;
+        }
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(tree_start)).ok()?;
+        file.read_to_end(&mut tail).ok()?;
+        if tail != self.data[tree_start as usize..] {
+            return None;
+        }
+        let fits = self
+            .files
+            .iter()
+            .filter(|f| f.data.is_none())
+            .all(|f| f.offset + f.packed_size as u64 <= tree_start);
+        fits.then_some(tree_start)
+    }
+
+    /// Compression type byte written to the tree for `file`
+    fn stored_type_byte(file: &FileEntry) -> u8 {
+        file.unsupported_compression
+            .unwrap_or(if file.compressed { 1 } else { 0 })
+    }
+
+    /// Size of the directory tree in bytes: file count, then per entry name
+    /// size, name, type, sizes and offset
+    fn tree_size(&self) -> u64 {
+        4 + self
+            .files
+            .iter()
+            .map(|file| {
+                4 + utils::encoded_name_len(&file.name) as u64
+                    + u64::from(self.writes_nul(file))
+                    + 13
+            })
+            .sum::<u64>()
+    }
+
+    /// Whether `file`'s name is saved with the NUL it was read with
+    fn writes_nul(&self, file: &FileEntry) -> bool {
+        self.nul_names && file.nul_terminated_name
+    }
+
+    /// Write the directory tree with the given data offsets, then the footer.
+    /// `tree_start` is the tree's position in the finished file.
+    fn write_tree(
+        &self,
+        cursor: &mut Cursor<&mut Vec<u8>>,
+        file_offsets: &[u32],
+        tree_start: u64,
+    ) -> Result<()> {
+        let written_before = cursor.position();
         cursor.write_u32::<LittleEndian>(self.files.len() as u32)?;
 
         for (i, file) in self.files.iter().enumerate() {
+            let mut filename_bytes = utils::encode_filename(&file.name)?;
+            if self.writes_nul(file) {
+                filename_bytes.push(0);
+            }
             let entry = Dat2FileEntry {
-                filename_size: file.name.len() as u32,
-                filename_bytes: file.name.as_bytes().to_vec(),
-                compression_type: if file.compressed { 1 } else { 0 },
+                filename_size: filename_bytes.len() as u32,
+                filename_bytes,
+                compression_type: Self::stored_type_byte(file),
                 real_size: file.size,
                 packed_size: file.packed_size,
                 offset: file_offsets[i],
@@ -382,10 +1350,8 @@ impl Dat2Archive {
             cursor.write_all(&entry_bytes)?;
         }
 
-        // Step 3: Write 8-byte footer
-        let tree_end = cursor.position();
-        let tree_size = (tree_end - tree_start) as u32;
-        let total_size = tree_end + 8;
+        let tree_size = (cursor.position() - written_before) as u32;
+        let total_size = tree_start + tree_size as u64 + 8;
 
         let footer = Dat2Footer {
             tree_size,
@@ -393,10 +1359,6 @@ impl Dat2Archive {
         };
         let footer_bytes = footer.to_bytes()?;
         cursor.write_all(&footer_bytes)?;
-
-        // Step 4: Write to disk
-        fs::write(path, output).context("Failed to write DAT2 file")?;
-
         Ok(())
     }
 }
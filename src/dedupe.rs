@@ -0,0 +1,146 @@
+/*!
+# Removing Duplicate Entry Names
+
+Some tools append an entry instead of replacing one with the same name,
+leaving an archive where two or more entries answer to the same path. Which
+one a reader finds depends on how it searches, so the game and other tools
+can disagree about what the archive holds.
+
+`dat3 dedupe archive.dat` lists every name with more than one entry, keeps
+one entry per name and rewrites the archive. Names are compared like entry
+paths: either slash, any case. `--keep first|last` picks which entry stays,
+counting in directory tree order; the last one is kept by default, since
+that's the one an appending tool wrote most recently.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::common::{utils, DatArchive, FileEntry};
+
+/// Which entry of a duplicated name stays (`--keep`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Keep {
+    /// The one listed first in the directory tree
+    First,
+    /// The one listed last in the directory tree
+    #[default]
+    Last,
+}
+
+/// The entries of an archive that share one name
+#[derive(Debug, Clone)]
+pub struct DuplicateName {
+    /// The name, as written in the first entry, with forward slashes
+    pub name: String,
+    /// Positions of the entries in directory tree order, with the entries
+    pub entries: Vec<(usize, FileEntry)>,
+    /// Index into `entries` of the one that stays
+    pub kept: usize,
+}
+
+impl DuplicateName {
+    /// The different spellings of the name, in tree order
+    pub fn spellings(&self) -> Vec<String> {
+        let mut spellings: Vec<String> = Vec::new();
+        for (_, file) in &self.entries {
+            let path = file.path();
+            if !spellings.contains(&path) {
+                spellings.push(path);
+            }
+        }
+        spellings
+    }
+
+    /// Whether the entries spell the name in more than one case, not just
+    /// the same way twice
+    pub fn differs_by_case(&self) -> bool {
+        self.spellings().len() > 1
+    }
+
+    /// Whether any of the entries was added after the archive was opened
+    pub fn has_added(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|(_, file)| file.original_index.is_none())
+    }
+
+    /// Positions of the entries that go
+    pub fn dropped(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| *i != self.kept)
+            .map(|(_, (position, _))| *position)
+    }
+}
+
+/// The name, then one line per entry with its position, sizes and offset,
+/// and its spelling if the entries spell the name differently
+impl fmt::Display for DuplicateName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({} entries):", self.name, self.entries.len())?;
+        let by_case = self.differs_by_case();
+        for (i, (position, file)) in self.entries.iter().enumerate() {
+            let action = if i == self.kept { "keep" } else { "drop" };
+            let spelling = if by_case {
+                format!(" {}", file.path())
+            } else {
+                String::new()
+            };
+            writeln!(
+                f,
+                "  {action} #{position}{spelling}: {} bytes ({} packed) at offset {}",
+                file.size, file.packed_size, file.offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names with more than one entry, in the order their first entries appear
+pub fn find_duplicates(archive: &DatArchive, keep: Keep) -> Vec<DuplicateName> {
+    let mut groups: Vec<Vec<(usize, FileEntry)>> = Vec::new();
+    let mut group_of: HashMap<String, usize> = HashMap::new();
+    for (position, file) in archive.entries().enumerate() {
+        let entry = FileEntry {
+            data: None,
+            ..file.clone()
+        };
+        let key = utils::entry_path_key(&file.name);
+        match group_of.get(&key) {
+            Some(&group) => groups[group].push((position, entry)),
+            None => {
+                group_of.insert(key, groups.len());
+                groups.push(vec![(position, entry)]);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|entries| entries.len() > 1)
+        .map(|entries| DuplicateName {
+            name: entries[0].1.path(),
+            kept: match keep {
+                Keep::First => 0,
+                Keep::Last => entries.len() - 1,
+            },
+            entries,
+        })
+        .collect()
+}
+
+/// Remove every entry `duplicates` drops from `archive`, which they have to
+/// have been found in. Returns how many entries went.
+pub fn remove_duplicates(archive: &mut DatArchive, duplicates: &[DuplicateName]) -> usize {
+    let mut dropped: Vec<usize> = duplicates.iter().flat_map(|d| d.dropped()).collect();
+    dropped.sort_unstable();
+    let mut position = 0;
+    archive.retain_entries(|_| {
+        let keep = dropped.binary_search(&position).is_err();
+        position += 1;
+        keep
+    });
+    dropped.len()
+}
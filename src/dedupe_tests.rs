@@ -0,0 +1,104 @@
+/*!
+Unit tests for removing duplicate entry names.
+
+Tests cover grouping names like entry paths, which entry `--keep` picks,
+that removal tells same-named entries apart, and the open-time warning.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::dedupe::*;
+
+    fn archive(dat1: bool, entries: &[(&str, &str)]) -> DatArchive {
+        let entries = entries
+            .iter()
+            .map(|(name, data)| (name.to_string(), data.as_bytes().to_vec()))
+            .collect();
+        let policy = CompressionPolicy::new(CompressionLevel::new(0).unwrap());
+        DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+    }
+
+    // DAT1 builds replace exact duplicates and put every file of a directory
+    // in one record, so the copies' file names differ in case
+    fn sample(dat1: bool) -> DatArchive {
+        archive(
+            dat1,
+            &[
+                ("text\\a.txt", "first copy"),
+                ("text\\b.txt", "only one"),
+                ("text\\A.TXT", "second copy"),
+                ("text\\a.TXT", "third copy"),
+            ],
+        )
+    }
+
+    #[test]
+    fn distinct_names_have_no_duplicates() {
+        let archive = archive(false, &[("a.txt", "one"), ("b.txt", "two")]);
+        assert!(find_duplicates(&archive, Keep::Last).is_empty());
+    }
+
+    #[test]
+    fn names_are_compared_like_entry_paths() {
+        let archive = archive(false, &[("Text\\A.txt", "one"), ("text/a.TXT", "two")]);
+        let duplicates = find_duplicates(&archive, Keep::Last);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "Text/A.txt");
+    }
+
+    #[test]
+    fn keep_picks_the_first_or_last_entry() {
+        let archive = sample(false);
+        let last = &find_duplicates(&archive, Keep::Last)[0];
+        assert_eq!(last.entries.len(), 3);
+        assert_eq!(last.kept, 2);
+        assert_eq!(last.dropped().collect::<Vec<_>>(), vec![0, 1]);
+
+        let first = &find_duplicates(&archive, Keep::First)[0];
+        assert_eq!(first.kept, 0);
+        assert_eq!(first.dropped().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn report_lists_every_entry() {
+        let report = find_duplicates(&sample(false), Keep::Last)[0].to_string();
+        assert!(report.starts_with("text/a.txt (3 entries):\n"));
+        assert_eq!(report.matches("drop #").count(), 2);
+        assert!(report.contains("keep #2 text/a.TXT: 10 bytes (10 packed)"));
+
+        let same = archive(false, &[("a.txt", "one"), ("a.txt", "two")]);
+        let report = find_duplicates(&same, Keep::First)[0].to_string();
+        assert!(report.contains("  keep #0: 3 bytes"), "{report}");
+    }
+
+    #[test]
+    fn removal_keeps_the_chosen_entry() {
+        for dat1 in [false, true] {
+            for (keep, content) in [(Keep::First, "first copy"), (Keep::Last, "third copy")] {
+                let mut archive = sample(dat1);
+                let duplicates = find_duplicates(&archive, keep);
+                assert_eq!(remove_duplicates(&mut archive, &duplicates), 2);
+                assert_eq!(archive.len(), 2);
+                assert_eq!(archive.read_file("text/a.txt").unwrap(), content.as_bytes());
+                assert_eq!(archive.read_file("text/b.txt").unwrap(), b"only one");
+                assert!(find_duplicates(&archive, keep).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_are_reported_as_warnings() {
+        let warnings = sample(false).warnings();
+        assert_eq!(
+            warnings,
+            vec!["text/a.txt and text/A.TXT and text/a.TXT differ only by case".to_string()]
+        );
+
+        let same = archive(false, &[("a.txt", "one"), ("a.txt", "two")]);
+        assert_eq!(
+            same.warnings(),
+            vec!["2 entries are named a.txt".to_string()]
+        );
+    }
+}
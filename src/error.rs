@@ -0,0 +1,117 @@
+/*!
+# Library Errors
+
+`DatError` is returned by the read API (`DatArchive::open`, `from_bytes`,
+`read_file` and `read_file_to`), so callers can tell a missing entry from a corrupt archive or
+an I/O failure without matching on message strings. Deleting a missing
+entry, and patterns that match nothing, fail with it too.
+
+Other operations return `anyhow::Error`. When one of these failures is the
+cause, it is still in the error chain: see `DatError::find`.
+*/
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::common::EntryProblem;
+
+/// Result of the typed read API
+pub type DatResult<T> = std::result::Result<T, DatError>;
+
+/// Why reading an archive or one of its entries failed
+#[derive(Debug, thiserror::Error)]
+pub enum DatError {
+    /// No entry has this path (compared with `compare_entry_paths`)
+    #[error("File not found: {name}")]
+    EntryNotFound { name: String },
+
+    /// Requested patterns matched no entries; each was also reported as
+    /// `Event::NotFound`
+    #[error("Some requested files were not found")]
+    PatternsNotFound { patterns: Vec<String> },
+
+    /// The header, directory tree or an entry's location is invalid.
+    /// `offset` is where in the archive the problem was found.
+    #[error("{reason} (at offset {offset})")]
+    CorruptTree { reason: String, offset: u64 },
+
+    /// The DAT2 footer records an archive size other than the file's.
+    /// `OpenOptions::lenient` opens such archives if the tree is intact.
+    #[error("DAT size mismatch: footer records {recorded} bytes, file has {actual}")]
+    FooterSizeMismatch { recorded: u64, actual: u64 },
+
+    /// Entries' data locations are invalid, found by an `OpenOptions::strict` open
+    #[error("{} entries have invalid data locations, first {}", .problems.len(), .problems[0])]
+    InvalidEntries { problems: Vec<EntryProblem> },
+
+    /// An entry's stored data isn't a valid compressed stream
+    #[error("Failed to decompress {name}")]
+    CorruptData {
+        name: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// An entry decompressed to a size other than the one in the directory tree
+    #[error(
+        "Failed to decompress {name}: Decompressed size mismatch: expected {expected} bytes, got {actual}"
+    )]
+    SizeMismatch {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A DAT2 entry's compression type is neither 0 (stored) nor 1 (zlib)
+    #[error("Unsupported compression type {kind} in entry {name}")]
+    UnsupportedCompression { name: String, kind: u8 },
+
+    /// The writer given to `read_file_to` failed while taking an entry's content
+    #[error("Failed to write {name}")]
+    Write {
+        name: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The archive file couldn't be read
+    #[error("Failed to read DAT file: {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl DatError {
+    /// The `DatError` in an `anyhow` error chain, if any
+    pub fn find(error: &anyhow::Error) -> Option<&DatError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// `CorruptTree` for a failed read of the tree, with the reason's context
+    pub(crate) fn tree(reason: impl Into<String>, offset: u64) -> Self {
+        Self::CorruptTree {
+            reason: reason.into(),
+            offset,
+        }
+    }
+}
+
+/// Turns a failed read of an archive header or tree into `DatError::CorruptTree`
+pub(crate) trait TreeContext<T> {
+    /// `offset` is where the failed read started; the cause is appended to `reason`
+    fn tree_context<S: Into<String>>(self, offset: u64, reason: impl FnOnce() -> S)
+        -> DatResult<T>;
+}
+
+impl<T, E: fmt::Display> TreeContext<T> for Result<T, E> {
+    fn tree_context<S: Into<String>>(
+        self,
+        offset: u64,
+        reason: impl FnOnce() -> S,
+    ) -> DatResult<T> {
+        self.map_err(|e| DatError::tree(format!("{}: {e:#}", reason().into()), offset))
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use crate::error::{DatError, TreeContext};
+
+    mod check_size {
+        use super::*;
+        use crate::common::{utils, FileEntry};
+
+        /// Copy `content` out as the content of an entry of `expected` bytes
+        fn copy(expected: u32, content: &[u8]) -> Result<Vec<u8>, DatError> {
+            let mut entry = FileEntry::with_data("a.txt".to_string(), Vec::new(), true);
+            entry.size = expected;
+            let mut out = Vec::new();
+            utils::copy_entry_content(&entry, content, &mut out, |e| e)?;
+            Ok(out)
+        }
+
+        #[test]
+        fn accepts_matching_size() {
+            let data = copy(5, b"hello").unwrap();
+            assert_eq!(data, b"hello");
+        }
+
+        #[test]
+        fn rejects_size_mismatch() {
+            for expected in [4, 6] {
+                let err = copy(expected, b"hello").unwrap_err();
+                assert!(matches!(
+                    err,
+                    DatError::SizeMismatch { ref name, actual: 5, .. } if name == "a.txt"
+                ));
+                assert_eq!(
+                    err.to_string(),
+                    format!(
+                        "Failed to decompress a.txt: Decompressed size mismatch: \
+                         expected {expected} bytes, got 5"
+                    )
+                );
+            }
+        }
+
+        #[test]
+        fn writes_no_more_than_the_recorded_size() {
+            let mut entry = FileEntry::with_data("a.txt".to_string(), Vec::new(), true);
+            entry.size = 4;
+            let mut out = Vec::new();
+            assert!(utils::copy_entry_content(&entry, &b"hello"[..], &mut out, |e| e).is_err());
+            assert_eq!(out, b"hell");
+        }
+
+        #[test]
+        fn failing_writer_is_a_write_error() {
+            let mut entry = FileEntry::with_data("a.txt".to_string(), Vec::new(), true);
+            entry.size = 5;
+            let mut out = [0u8; 2];
+            let err = utils::copy_entry_content(&entry, &b"hello"[..], &mut &mut out[..], |e| e)
+                .unwrap_err();
+            assert!(matches!(err, DatError::Write { ref name, .. } if name == "a.txt"));
+            assert_eq!(err.to_string(), "Failed to write a.txt");
+        }
+    }
+
+    mod tree_context {
+        use super::*;
+
+        #[test]
+        fn wraps_cause_with_offset() {
+            let result: Result<(), std::io::Error> = Err(std::io::ErrorKind::UnexpectedEof.into());
+            let err = result
+                .tree_context(16, || "Failed to read file count")
+                .unwrap_err();
+            assert!(matches!(err, DatError::CorruptTree { offset: 16, .. }));
+            assert_eq!(
+                err.to_string(),
+                "Failed to read file count: unexpected end of file (at offset 16)"
+            );
+        }
+    }
+
+    mod find {
+        use super::*;
+        use anyhow::Context;
+
+        #[test]
+        fn finds_error_under_context() {
+            let err = Err::<(), _>(DatError::EntryNotFound {
+                name: "a.txt".to_string(),
+            })
+            .context("Failed to read nested archive a.txt")
+            .unwrap_err();
+            assert!(matches!(
+                DatError::find(&err),
+                Some(DatError::EntryNotFound { name }) if name == "a.txt"
+            ));
+        }
+
+        #[test]
+        fn none_for_other_errors() {
+            assert!(DatError::find(&anyhow::anyhow!("other")).is_none());
+        }
+    }
+}
@@ -0,0 +1,377 @@
+/*!
+# Exporting to Zip
+
+`dat3 export archive.dat out.zip` writes every entry (or those matching the
+given names and patterns) into a standard zip file, for tools that read zip
+but not DAT. Paths keep their directories, with forward slashes.
+
+Each entry is decompressed from the archive and either deflated or stored,
+decided by the same `CompressionPolicy` as adding files: store-only
+extensions, level 0 and entries that don't shrink enough are stored. Entries
+are written to the zip one at a time, so only one is held in memory.
+
+The zip has no zip64 records: at most 65,535 entries, and the zip can't
+grow past 4 GiB. Every entry gets the same timestamp, 1980-01-01 00:00 (the
+earliest a zip can hold), since DAT archives don't store any, which also
+makes the zip byte-reproducible.
+
+`--tar FILE` writes a POSIX (ustar) tar stream instead, to a file or to
+stdout with `-`, for piping into other tools. Entries are decompressed and
+sorted by path; each gets mode 0644, owner 0 and the archive file's
+modification time. Paths too long for the ustar name and prefix fields get
+a PAX extended header.
+*/
+
+use anyhow::{bail, Result};
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::{write::DeflateEncoder, Compression};
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use crate::common::{utils, CompressionOutcome, CompressionPolicy, DatArchive, FileEntry};
+
+pub(crate) const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+pub(crate) const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+pub(crate) const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+/// Version 2.0: deflate
+const ZIP_VERSION: u16 = 20;
+pub(crate) const METHOD_STORED: u16 = 0;
+pub(crate) const METHOD_DEFLATED: u16 = 8;
+/// 1980-01-01 in MS-DOS date format; the time is 00:00
+const DOS_DATE: u16 = (1 << 5) | 1;
+const LOCAL_HEADER_LEN: u64 = 30;
+
+/// A file written to the zip, as the central directory lists it
+#[derive(Debug)]
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    packed_size: u32,
+    size: u32,
+    header_offset: u32,
+}
+
+/// Writes a zip file one entry at a time
+pub struct ZipWriter<W: Write> {
+    out: W,
+    /// Bytes written so far
+    offset: u64,
+    entries: Vec<ZipEntry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Write the file `data` under `name` (forward slashes): as `deflated`,
+    /// its raw deflate stream, if given, otherwise stored
+    pub fn add(&mut self, name: &str, data: &[u8], deflated: Option<&[u8]>) -> Result<()> {
+        if self.entries.len() == usize::from(u16::MAX) {
+            bail!("A zip without zip64 holds at most {} entries", u16::MAX);
+        }
+        let size = u32::try_from(data.len())?;
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let (method, stored) = match deflated {
+            Some(deflated) => (METHOD_DEFLATED, deflated),
+            None => (METHOD_STORED, data),
+        };
+
+        let entry = ZipEntry {
+            name: name.to_string(),
+            method,
+            crc: crc.sum(),
+            packed_size: stored.len() as u32,
+            size,
+            header_offset: self.checked_offset()?,
+        };
+        self.out.write_u32::<LittleEndian>(LOCAL_HEADER_SIGNATURE)?;
+        self.out.write_u16::<LittleEndian>(ZIP_VERSION)?;
+        Self::write_entry_fields(&mut self.out, &entry)?;
+        self.out.write_u16::<LittleEndian>(0)?; // Extra field length
+        self.out.write_all(name.as_bytes())?;
+        self.out.write_all(stored)?;
+
+        self.offset += LOCAL_HEADER_LEN + name.len() as u64 + stored.len() as u64;
+        self.checked_offset()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Write the central directory and return the output and the zip's size
+    pub fn finish(mut self) -> Result<(W, u64)> {
+        let directory_offset = self.checked_offset()?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.write_u32::<LittleEndian>(CENTRAL_HEADER_SIGNATURE)?;
+            directory.write_u16::<LittleEndian>(ZIP_VERSION)?; // Made by
+            directory.write_u16::<LittleEndian>(ZIP_VERSION)?; // Needed
+            Self::write_entry_fields(&mut directory, entry)?;
+            directory.write_u16::<LittleEndian>(0)?; // Extra field length
+            directory.write_u16::<LittleEndian>(0)?; // Comment length
+            directory.write_u16::<LittleEndian>(0)?; // Disk number
+            directory.write_u16::<LittleEndian>(0)?; // Internal attributes
+            directory.write_u32::<LittleEndian>(0)?; // External attributes
+            directory.write_u32::<LittleEndian>(entry.header_offset)?;
+            directory.write_all(entry.name.as_bytes())?;
+        }
+        let directory_len = u32::try_from(directory.len())?;
+        let count = self.entries.len() as u16;
+        directory.write_u32::<LittleEndian>(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+        directory.write_u16::<LittleEndian>(0)?; // This disk
+        directory.write_u16::<LittleEndian>(0)?; // Disk with the directory
+        directory.write_u16::<LittleEndian>(count)?;
+        directory.write_u16::<LittleEndian>(count)?;
+        directory.write_u32::<LittleEndian>(directory_len)?;
+        directory.write_u32::<LittleEndian>(directory_offset)?;
+        directory.write_u16::<LittleEndian>(0)?; // Comment length
+
+        self.out.write_all(&directory)?;
+        self.out.flush()?;
+        self.offset += directory.len() as u64;
+        if self.offset > u64::from(u32::MAX) {
+            bail!("The zip would be larger than 4 GiB, which needs zip64");
+        }
+        Ok((self.out, self.offset))
+    }
+
+    /// Fields from "flags" to "name length", shared by both headers
+    fn write_entry_fields(out: &mut impl Write, entry: &ZipEntry) -> Result<()> {
+        out.write_u16::<LittleEndian>(0)?; // Flags
+        out.write_u16::<LittleEndian>(entry.method)?;
+        out.write_u16::<LittleEndian>(0)?; // Time
+        out.write_u16::<LittleEndian>(DOS_DATE)?;
+        out.write_u32::<LittleEndian>(entry.crc)?;
+        out.write_u32::<LittleEndian>(entry.packed_size)?;
+        out.write_u32::<LittleEndian>(entry.size)?;
+        out.write_u16::<LittleEndian>(entry.name.len() as u16)?;
+        Ok(())
+    }
+
+    fn checked_offset(&self) -> Result<u32> {
+        u32::try_from(self.offset)
+            .map_err(|_| anyhow::anyhow!("The zip would be larger than 4 GiB, which needs zip64"))
+    }
+}
+
+/// Kind of file an export writes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Zip,
+    Tar,
+}
+
+/// What `export_zip` or `export_tar` wrote
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExportStats {
+    pub format: ExportFormat,
+    pub files: usize,
+    /// Files written deflated; the rest are stored
+    pub deflated: usize,
+    /// Decompressed size of all files
+    pub bytes: u64,
+    /// Size of the zip or tar file
+    pub output_size: u64,
+}
+
+impl fmt::Display for ExportStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            ExportFormat::Zip => writeln!(
+                f,
+                "Exported {} files ({} deflated), {} bytes into a {} byte zip",
+                self.files, self.deflated, self.bytes, self.output_size
+            ),
+            ExportFormat::Tar => writeln!(
+                f,
+                "Exported {} files, {} bytes into a {} byte tar",
+                self.files, self.bytes, self.output_size
+            ),
+        }
+    }
+}
+
+/// Write `entries` of `archive` into a zip on `out`, deflating those
+/// `policy` compresses. Entry names must be unique.
+pub fn export_zip<W: Write>(
+    archive: &DatArchive,
+    entries: &[&FileEntry],
+    policy: &CompressionPolicy,
+    out: W,
+) -> Result<(W, ExportStats)> {
+    let mut zip = ZipWriter::new(out);
+    let mut stats = ExportStats::default();
+    let level = u32::from(policy.level.level());
+    for file in entries {
+        let name = utils::canonical_entry_path(&file.name);
+        let data = archive.read_entry(file)?;
+        let deflated = if level > 0 && !policy.is_store_only(Path::new(&name)) {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(&data)?;
+            Some(encoder.finish()?)
+        } else {
+            None
+        };
+        let deflated = deflated.filter(|deflated| {
+            policy.decide(data.len(), deflated.len()) == CompressionOutcome::Compressed
+        });
+
+        zip.add(&name, &data, deflated.as_deref())?;
+        stats.files += 1;
+        stats.deflated += usize::from(deflated.is_some());
+        stats.bytes += data.len() as u64;
+    }
+    let (out, zip_size) = zip.finish()?;
+    stats.output_size = zip_size;
+    Ok((out, stats))
+}
+
+const TAR_BLOCK: usize = 512;
+/// Tar files are read and written in records of 20 blocks
+const TAR_RECORD: u64 = 20 * TAR_BLOCK as u64;
+const TAR_NAME_LEN: usize = 100;
+const TAR_PREFIX_LEN: usize = 155;
+
+/// Writes a POSIX tar stream one file at a time
+pub struct TarWriter<W: Write> {
+    out: W,
+    /// Modification time of every file, in seconds since the Unix epoch
+    mtime: u64,
+    /// Bytes written so far
+    offset: u64,
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(out: W, mtime: u64) -> Self {
+        Self {
+            out,
+            mtime,
+            offset: 0,
+        }
+    }
+
+    /// Write the file `data` under `name` (forward slashes)
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let (prefix, short_name) = match Self::split_name(name) {
+            Some(split) => split,
+            None => {
+                // Too long for ustar: a PAX header carries the full path
+                let record = format!(" path={name}\n");
+                // The record starts with its own length, digits included
+                let mut len = record.len();
+                while record.len() + len.to_string().len() != len {
+                    len = record.len() + len.to_string().len();
+                }
+                let record = format!("{len}{record}");
+                let pax_name = format!("PaxHeaders/{}", utils::get_filename_from_dat_path(name));
+                self.write_file(
+                    b'x',
+                    "",
+                    leading(&pax_name, TAR_NAME_LEN),
+                    record.as_bytes(),
+                )?;
+                ("", leading(name, TAR_NAME_LEN))
+            }
+        };
+        self.write_file(b'0', prefix, short_name, data)
+    }
+
+    /// Write the end-of-archive blocks, padded to a whole record, and return
+    /// the output and the stream's size
+    pub fn finish(mut self) -> Result<(W, u64)> {
+        let end = (self.offset + 2 * TAR_BLOCK as u64).div_ceil(TAR_RECORD) * TAR_RECORD;
+        let padding = vec![0; (end - self.offset) as usize];
+        self.out.write_all(&padding)?;
+        self.out.flush()?;
+        Ok((self.out, end))
+    }
+
+    /// Split `name` into ustar prefix and name fields, at a slash
+    fn split_name(name: &str) -> Option<(&str, &str)> {
+        if name.len() <= TAR_NAME_LEN {
+            return Some(("", name));
+        }
+        name.match_indices('/')
+            .map(|(i, _)| (&name[..i], &name[i + 1..]))
+            .find(|(prefix, rest)| {
+                prefix.len() <= TAR_PREFIX_LEN && !rest.is_empty() && rest.len() <= TAR_NAME_LEN
+            })
+    }
+
+    /// Write a header of type `kind` and `data`, padded to whole blocks
+    fn write_file(&mut self, kind: u8, prefix: &str, name: &str, data: &[u8]) -> Result<()> {
+        let mut header = [0u8; TAR_BLOCK];
+        // Every value fits its field; numbers are octal and NUL-terminated
+        let mut field = |start: usize, value: &[u8]| {
+            header[start..start + value.len()].copy_from_slice(value);
+        };
+        field(0, name.as_bytes());
+        field(100, b"0000644\0");
+        field(108, b"0000000\0"); // Owner
+        field(116, b"0000000\0"); // Group
+        field(124, format!("{:011o}\0", data.len()).as_bytes());
+        field(
+            136,
+            format!("{:011o}\0", self.mtime.min(0o777_7777_7777)).as_bytes(),
+        );
+        field(148, b"        "); // Checksum, counted as spaces
+        field(156, &[kind]);
+        field(257, b"ustar\0"); // Magic
+        field(263, b"00"); // Version
+        field(345, prefix.as_bytes());
+        let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+        let padding = (TAR_BLOCK - data.len() % TAR_BLOCK) % TAR_BLOCK;
+        self.out.write_all(&[0; TAR_BLOCK][..padding])?;
+        self.offset += (TAR_BLOCK + data.len() + padding) as u64;
+        Ok(())
+    }
+}
+
+/// The longest start of `text` of at most `max_len` bytes
+fn leading(text: &str, max_len: usize) -> &str {
+    let mut end = text.len().min(max_len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Write `entries` of `archive` into a tar stream on `out`, sorted by path,
+/// each with `mtime` (seconds since the Unix epoch). Entry names must be
+/// unique.
+pub fn export_tar<W: Write>(
+    archive: &DatArchive,
+    entries: &[&FileEntry],
+    mtime: u64,
+    out: W,
+) -> Result<(W, ExportStats)> {
+    let mut entries = entries.to_vec();
+    entries.sort_by(|a, b| utils::compare_entry_paths(&a.name, &b.name));
+
+    let mut tar = TarWriter::new(out, mtime);
+    let mut stats = ExportStats {
+        format: ExportFormat::Tar,
+        ..ExportStats::default()
+    };
+    for file in entries {
+        let data = archive.read_entry(file)?;
+        tar.add(&utils::canonical_entry_path(&file.name), &data)?;
+        stats.files += 1;
+        stats.bytes += data.len() as u64;
+    }
+    let (out, size) = tar.finish()?;
+    stats.output_size = size;
+    Ok((out, stats))
+}
@@ -0,0 +1,220 @@
+/*!
+Unit tests for exporting to zip.
+
+Tests read the written zip back with a minimal parser of its local headers
+and end record, and cover names, store-or-deflate decisions and checksums.
+Tar streams are read back block by block the same way.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::export::*;
+    use byteorder::{ByteOrder, LittleEndian};
+    use std::io::Read;
+
+    /// One file of a zip: name, method, CRC-32 and decompressed content
+    struct ZipFile {
+        name: String,
+        method: u16,
+        crc: u32,
+        content: Vec<u8>,
+    }
+
+    /// Read the files of a zip from its local headers, checking the end
+    /// record's entry count and central directory position
+    fn read_zip(zip: &[u8]) -> Vec<ZipFile> {
+        let mut files = Vec::new();
+        let mut pos = 0;
+        while LittleEndian::read_u32(&zip[pos..]) == 0x0403_4b50 {
+            let method = LittleEndian::read_u16(&zip[pos + 8..]);
+            let crc = LittleEndian::read_u32(&zip[pos + 14..]);
+            let packed = LittleEndian::read_u32(&zip[pos + 18..]) as usize;
+            let size = LittleEndian::read_u32(&zip[pos + 22..]) as usize;
+            let name_len = LittleEndian::read_u16(&zip[pos + 26..]) as usize;
+            let name = String::from_utf8(zip[pos + 30..pos + 30 + name_len].to_vec()).unwrap();
+            let data = &zip[pos + 30 + name_len..pos + 30 + name_len + packed];
+            let content = match method {
+                0 => data.to_vec(),
+                8 => {
+                    let mut content = Vec::new();
+                    flate2::read::DeflateDecoder::new(data)
+                        .read_to_end(&mut content)
+                        .unwrap();
+                    content
+                }
+                other => panic!("unexpected method {other}"),
+            };
+            assert_eq!(content.len(), size);
+            files.push(ZipFile {
+                name,
+                method,
+                crc,
+                content,
+            });
+            pos += 30 + name_len + packed;
+        }
+
+        let end = zip.len() - 22;
+        assert_eq!(LittleEndian::read_u32(&zip[end..]), 0x0605_4b50);
+        assert_eq!(
+            LittleEndian::read_u16(&zip[end + 10..]) as usize,
+            files.len()
+        );
+        assert_eq!(LittleEndian::read_u32(&zip[end + 16..]) as usize, pos);
+        assert_eq!(
+            LittleEndian::read_u32(&zip[end + 12..]) as usize,
+            end - pos,
+            "central directory size"
+        );
+        files
+    }
+
+    fn sample(dat1: bool) -> DatArchive {
+        let entries = vec![
+            ("art\\critters\\a.frm".to_string(), b"frame ".repeat(50)),
+            ("sound\\music\\m.acm".to_string(), b"music ".repeat(50)),
+            ("text\\b.msg".to_string(), b"hi".to_vec()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+        DatArchive::from_entries(dat1, entries, &policy).unwrap().0
+    }
+
+    fn export(archive: &DatArchive, level: u8) -> (Vec<ZipFile>, ExportStats) {
+        let entries: Vec<_> = archive.entries().collect();
+        let policy = CompressionPolicy::new(CompressionLevel::new(level).unwrap());
+        let (zip, stats) = export_zip(archive, &entries, &policy, Vec::new()).unwrap();
+        assert_eq!(stats.output_size, zip.len() as u64);
+        (read_zip(&zip), stats)
+    }
+
+    #[test]
+    fn entries_keep_their_paths_and_content() {
+        for dat1 in [false, true] {
+            let archive = sample(dat1);
+            let (files, stats) = export(&archive, 6);
+            let mut names: Vec<_> = files.iter().map(|f| f.name.as_str()).collect();
+            names.sort();
+            assert_eq!(
+                names,
+                ["art/critters/a.frm", "sound/music/m.acm", "text/b.msg"]
+            );
+            for file in &files {
+                let entry = archive.entry(&file.name).unwrap();
+                assert_eq!(file.content, archive.read_entry(entry).unwrap());
+                let mut crc = flate2::Crc::new();
+                crc.update(&file.content);
+                assert_eq!(file.crc, crc.sum(), "{}", file.name);
+            }
+            assert_eq!((stats.files, stats.bytes), (3, 602));
+        }
+    }
+
+    #[test]
+    fn store_extensions_and_small_files_are_stored() {
+        let (files, stats) = export(&sample(false), 6);
+        let method = |name: &str| files.iter().find(|f| f.name == name).unwrap().method;
+        assert_eq!(method("art/critters/a.frm"), 8);
+        assert_eq!(method("sound/music/m.acm"), 0);
+        // Deflating two bytes doesn't make them smaller
+        assert_eq!(method("text/b.msg"), 0);
+        assert_eq!(stats.deflated, 1);
+    }
+
+    #[test]
+    fn level_zero_stores_everything() {
+        let (files, stats) = export(&sample(false), 0);
+        assert!(files.iter().all(|f| f.method == 0));
+        assert_eq!(stats.deflated, 0);
+    }
+
+    /// Read the files of a tar stream as (path, header mtime, content),
+    /// checking header checksums and following PAX path records
+    fn read_tar(tar: &[u8]) -> Vec<(String, u64, Vec<u8>)> {
+        let text = |field: &[u8]| {
+            let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8(field[..end].to_vec()).unwrap()
+        };
+        let octal = |field: &[u8]| u64::from_str_radix(text(field).trim(), 8).unwrap();
+
+        assert_eq!(tar.len() % 10240, 0);
+        let mut files = Vec::new();
+        let mut pax_path = None;
+        let mut pos = 0;
+        while tar[pos..pos + 512].iter().any(|&b| b != 0) {
+            let header = &tar[pos..pos + 512];
+            let mut sum: u64 = header.iter().map(|&b| u64::from(b)).sum();
+            sum -= header[148..156].iter().map(|&b| u64::from(b)).sum::<u64>();
+            assert_eq!(octal(&header[148..156]), sum + 8 * u64::from(b' '));
+            assert_eq!(&header[257..263], b"ustar\0");
+            assert_eq!(text(&header[100..108]), "0000644");
+
+            let size = octal(&header[124..136]) as usize;
+            let data = tar[pos + 512..pos + 512 + size].to_vec();
+            pos += 512 + size.div_ceil(512) * 512;
+            if header[156] == b'x' {
+                let record = String::from_utf8(data).unwrap();
+                let (len, rest) = record.split_once(' ').unwrap();
+                assert_eq!(len.parse::<usize>().unwrap(), record.len());
+                pax_path = Some(rest.strip_prefix("path=").unwrap().trim_end().to_string());
+                continue;
+            }
+            let prefix = text(&header[345..500]);
+            let name = match pax_path.take() {
+                Some(path) => path,
+                None if prefix.is_empty() => text(&header[..100]),
+                None => format!("{prefix}/{}", text(&header[..100])),
+            };
+            files.push((name, octal(&header[136..148]), data));
+        }
+        files
+    }
+
+    #[test]
+    fn tar_holds_sorted_entries_with_long_paths() {
+        let long_dir = ["directory"; 12].join("\\");
+        let entries = vec![
+            ("text\\b.msg".to_string(), b"hi".to_vec()),
+            (format!("{long_dir}\\split.txt"), b"split".to_vec()),
+            (format!("art\\{}.frm", "f".repeat(200)), b"pax".to_vec()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(6).unwrap());
+        for dat1 in [false, true] {
+            let archive = DatArchive::from_entries(dat1, entries.clone(), &policy)
+                .unwrap()
+                .0;
+            let all: Vec<_> = archive.entries().collect();
+            let (tar, stats) = export_tar(&archive, &all, 1_700_000_000, Vec::new()).unwrap();
+            assert_eq!(stats.output_size, tar.len() as u64);
+            assert_eq!((stats.files, stats.bytes), (3, 10));
+
+            let files = read_tar(&tar);
+            let names: Vec<_> = files.iter().map(|(name, ..)| name.clone()).collect();
+            assert_eq!(
+                names,
+                [
+                    format!("art/{}.frm", "f".repeat(200)),
+                    format!("{}/split.txt", long_dir.replace('\\', "/")),
+                    "text/b.msg".to_string(),
+                ]
+            );
+            assert!(files.iter().all(|(_, mtime, _)| *mtime == 1_700_000_000));
+            assert_eq!(files[0].2, b"pax");
+            assert_eq!(files[2].2, b"hi");
+        }
+    }
+
+    #[test]
+    fn empty_tar_is_one_record_of_zeros() {
+        let (tar, size) = TarWriter::new(Vec::new(), 0).finish().unwrap();
+        assert_eq!(size, 10240);
+        assert!(tar.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn empty_zip_is_just_the_end_record() {
+        let (zip, size) = ZipWriter::new(Vec::new()).finish().unwrap();
+        assert_eq!(size, 22);
+        assert!(read_zip(&zip).is_empty());
+    }
+}
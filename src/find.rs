@@ -0,0 +1,71 @@
+/*!
+# Finding Entries
+
+`dat3 find archive.dat "*.int" --min-size 100k` prints the paths of entries
+that pass every given filter, one per line, for piping into other tools or
+into an `@response-file` for `x`:
+
+```text
+dat3 find master.dat "*.frm" --dir art/critters --stored > critters.txt
+dat3 x master.dat @critters.txt
+```
+
+Patterns work as in `l`; an entry has to match one of them. Sizes are real
+(decompressed) sizes.
+*/
+
+use crate::common::{utils, DatArchive, FileEntry};
+
+/// Filters an entry has to pass to be found; the default finds every entry
+#[derive(Debug, Clone, Default)]
+pub struct EntryQuery {
+    /// Names or glob patterns, as in `l` (any entry if empty)
+    pub patterns: Vec<String>,
+    /// Smallest real size, in bytes
+    pub min_size: Option<u64>,
+    /// Largest real size, in bytes
+    pub max_size: Option<u64>,
+    /// Only compressed (true) or only stored (false) entries
+    pub compressed: Option<bool>,
+    /// Only entries under this directory, compared like entry paths
+    pub dir: Option<String>,
+}
+
+impl EntryQuery {
+    /// Whether `file` passes every filter
+    pub fn matches(&self, file: &FileEntry) -> bool {
+        let size = file.size as u64;
+        (self.patterns.is_empty()
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| utils::matches_pattern(&file.name, pattern)))
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.compressed.is_none_or(|c| file.compressed == c)
+            && self
+                .dir
+                .as_deref()
+                .is_none_or(|dir| in_dir(&file.name, dir))
+    }
+
+    /// The entries of `archive` that pass, in archive order
+    pub fn find<'a>(&self, archive: &'a DatArchive) -> Vec<&'a FileEntry> {
+        archive
+            .entries()
+            .filter(|file| self.matches(file))
+            .collect()
+    }
+}
+
+/// Whether `path` is somewhere under `dir`
+fn in_dir(path: &str, dir: &str) -> bool {
+    let dir = utils::entry_path_key(dir);
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() {
+        return true;
+    }
+    utils::entry_path_key(path)
+        .strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
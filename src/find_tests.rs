@@ -0,0 +1,84 @@
+/*!
+Unit tests for finding entries.
+
+Tests cover each filter alone and combined, and the directory prefix's
+handling of case, slashes and partial directory names.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::FileEntry;
+    use crate::find::*;
+
+    fn entry(name: &str, size: u32, compressed: bool) -> FileEntry {
+        let mut file = FileEntry::with_data(name.to_string(), Vec::new(), compressed);
+        file.size = size;
+        file
+    }
+
+    fn sample_entries() -> Vec<FileEntry> {
+        vec![
+            entry("scripts\\big.int", 200_000, true),
+            entry("scripts\\small.int", 500, true),
+            entry("art\\critters\\hmjmps.frm", 4000, false),
+            entry("art\\critters2\\other.frm", 4000, true),
+            entry("readme.txt", 10, false),
+        ]
+    }
+
+    fn found(query: &EntryQuery) -> Vec<String> {
+        sample_entries()
+            .iter()
+            .filter(|file| query.matches(file))
+            .map(|file| file.path())
+            .collect()
+    }
+
+    #[test]
+    fn default_query_matches_everything() {
+        assert_eq!(found(&EntryQuery::default()).len(), 5);
+    }
+
+    #[test]
+    fn patterns_and_sizes() {
+        let query = EntryQuery {
+            patterns: vec!["*.int".to_string()],
+            min_size: Some(100 * 1024),
+            ..EntryQuery::default()
+        };
+        assert_eq!(found(&query), ["scripts/big.int"]);
+
+        let query = EntryQuery {
+            max_size: Some(500),
+            ..EntryQuery::default()
+        };
+        assert_eq!(found(&query), ["scripts/small.int", "readme.txt"]);
+    }
+
+    #[test]
+    fn compression_state() {
+        let query = EntryQuery {
+            compressed: Some(false),
+            ..EntryQuery::default()
+        };
+        assert_eq!(found(&query), ["art/critters/hmjmps.frm", "readme.txt"]);
+    }
+
+    #[test]
+    fn directory_prefix_is_a_whole_directory() {
+        for dir in ["art/critters", "ART\\Critters\\", "/art/critters/"] {
+            let query = EntryQuery {
+                dir: Some(dir.to_string()),
+                ..EntryQuery::default()
+            };
+            assert_eq!(found(&query), ["art/critters/hmjmps.frm"], "{dir}");
+        }
+
+        let query = EntryQuery {
+            dir: Some("art".to_string()),
+            compressed: Some(true),
+            ..EntryQuery::default()
+        };
+        assert_eq!(found(&query), ["art/critters2/other.frm"]);
+    }
+}
@@ -0,0 +1,143 @@
+/*!
+# Searching Entry Content
+
+`dat3 grep archive.dat "Tandi" --include "*.msg"` finds the lines of entries
+that contain a string, without extracting anything, and prints them as
+`entry:line_number:line`.
+
+Entries are decompressed and searched in parallel. Each worker holds one
+entry at a time, and together they stay within the memory budget extraction
+uses. Entries with a NUL byte near the start are taken as binary and skipped,
+unless binary entries are searched too; a binary match is reported without
+lines.
+
+Lines are matched as bytes, so text in any code page is searched. They're
+printed as UTF-8, with bytes that aren't valid UTF-8 replaced.
+*/
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::common::{utils, DatArchive, FileEntry, MemoryBudget, DEFAULT_MEMORY_PER_THREAD};
+
+/// How far into an entry to look for a NUL byte when telling binary from text
+const BINARY_PROBE_LEN: usize = 8000;
+
+/// What to search for and where
+#[derive(Debug, Clone, Default)]
+pub struct GrepOptions {
+    /// Treat the pattern as a regular expression instead of a literal string
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Also search entries that look binary
+    pub binary: bool,
+    /// Only search entries matching one of these patterns (all if empty)
+    pub include: Vec<String>,
+    /// Skip entries matching any of these patterns
+    pub exclude: Vec<String>,
+}
+
+/// The matches found in one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMatches {
+    /// Entry path with forward slashes
+    pub path: String,
+    /// 1-based line numbers and the matching lines; empty for a binary entry
+    pub lines: Vec<(usize, String)>,
+    pub binary: bool,
+}
+
+/// A compiled search
+#[derive(Debug)]
+pub struct Searcher {
+    regex: Regex,
+    options: GrepOptions,
+}
+
+impl Searcher {
+    /// Compile `pattern` with `options`
+    pub fn new(pattern: &str, options: GrepOptions) -> Result<Self> {
+        let source = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .with_context(|| format!("Invalid regular expression: {pattern}"))?;
+        Ok(Self { regex, options })
+    }
+
+    /// Whether `include` and `exclude` select the entry at `path`
+    pub fn selects(&self, path: &str) -> bool {
+        let included = self.options.include.is_empty()
+            || self
+                .options
+                .include
+                .iter()
+                .any(|pattern| utils::matches_pattern(path, pattern));
+        included
+            && !self
+                .options
+                .exclude
+                .iter()
+                .any(|pattern| utils::matches_pattern(path, pattern))
+    }
+
+    /// Search the selected entries of `archive`, in parallel, in entry order.
+    /// Entries without matches are left out.
+    pub fn search(&self, archive: &DatArchive) -> Result<Vec<EntryMatches>> {
+        let entries: Vec<&FileEntry> = archive
+            .entries()
+            .filter(|file| self.selects(&file.name))
+            .collect();
+        let budget = MemoryBudget::new(
+            DEFAULT_MEMORY_PER_THREAD.saturating_mul(rayon::current_num_threads() as u64),
+        );
+
+        let found: Vec<Option<EntryMatches>> = entries
+            .par_iter()
+            .map(|file| {
+                let _held = budget.acquire(file.size as u64);
+                let data = archive.read_entry(file)?;
+                Ok(self.search_content(&file.path(), &data))
+            })
+            .collect::<Result<_>>()?;
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    /// Search one entry's content
+    pub fn search_content(&self, path: &str, data: &[u8]) -> Option<EntryMatches> {
+        let binary = is_binary(data);
+        if binary {
+            if !self.options.binary || !self.regex.is_match(data) {
+                return None;
+            }
+            return Some(EntryMatches {
+                path: path.to_string(),
+                lines: Vec::new(),
+                binary,
+            });
+        }
+
+        let lines: Vec<(usize, String)> = data
+            .split(|&b| b == b'\n')
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.strip_suffix(b"\r").unwrap_or(line)))
+            .filter(|(_, line)| self.regex.is_match(line))
+            .map(|(number, line)| (number, String::from_utf8_lossy(line).into_owned()))
+            .collect();
+        (!lines.is_empty()).then(|| EntryMatches {
+            path: path.to_string(),
+            lines,
+            binary,
+        })
+    }
+}
+
+/// Whether `data` looks binary: a NUL byte near the start
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_PROBE_LEN)].contains(&0)
+}
@@ -0,0 +1,121 @@
+/*!
+Unit tests for searching entry content.
+
+Tests cover literal and regex patterns, case folding, line numbers, include
+and exclude patterns, and skipping binary entries.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::grep::*;
+
+    fn sample_archive() -> DatArchive {
+        let entries = vec![
+            (
+                "text\\english\\dialog\\tandi.msg".to_string(),
+                b"{100}{}{Hi, I'm Tandi.}\r\n{101}{}{Bye.}\r\n{102}{}{TANDI waves.}\r\n".to_vec(),
+            ),
+            (
+                "text\\english\\game\\misc.msg".to_string(),
+                b"{1}{}{Price: 5 caps}\n".to_vec(),
+            ),
+            (
+                "data\\notes.txt".to_string(),
+                b"tandi.msg is big\n".to_vec(),
+            ),
+            ("art\\tandi.frm".to_string(), b"FRM\0\0\0Tandi\0".to_vec()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        DatArchive::from_entries(false, entries, &policy).unwrap().0
+    }
+
+    fn search(pattern: &str, options: GrepOptions) -> Vec<EntryMatches> {
+        Searcher::new(pattern, options)
+            .unwrap()
+            .search(&sample_archive())
+            .unwrap()
+    }
+
+    fn paths(found: &[EntryMatches]) -> Vec<&str> {
+        found.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[test]
+    fn literal_search_reports_line_numbers() {
+        let found = search("Tandi", GrepOptions::default());
+        assert_eq!(paths(&found), ["text/english/dialog/tandi.msg"]);
+        assert_eq!(found[0].lines, [(1, "{100}{}{Hi, I'm Tandi.}".to_string())]);
+    }
+
+    #[test]
+    fn literal_patterns_are_not_regexes() {
+        assert_eq!(search("tandi.msg", GrepOptions::default()).len(), 1);
+        assert_eq!(search("{1}", GrepOptions::default()).len(), 1);
+        assert!(search("Price: .* caps", GrepOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn ignore_case_and_regex() {
+        let options = GrepOptions {
+            ignore_case: true,
+            ..GrepOptions::default()
+        };
+        let found = search("tandi", options);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[1].lines.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            [1, 3]
+        );
+
+        let options = GrepOptions {
+            regex: true,
+            ..GrepOptions::default()
+        };
+        let found = search(r"Price: \d+ caps", options);
+        assert_eq!(paths(&found), ["text/english/game/misc.msg"]);
+        assert!(Searcher::new(
+            "(",
+            GrepOptions {
+                regex: true,
+                ..GrepOptions::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn include_and_exclude_select_entries() {
+        let options = GrepOptions {
+            ignore_case: true,
+            include: vec!["*.msg".to_string(), "*.txt".to_string()],
+            exclude: vec!["*/dialog/*".to_string()],
+            ..GrepOptions::default()
+        };
+        assert_eq!(paths(&search("tandi", options)), ["data/notes.txt"]);
+    }
+
+    #[test]
+    fn binary_entries_are_skipped_unless_asked_for() {
+        assert!(search("FRM", GrepOptions::default()).is_empty());
+
+        let options = GrepOptions {
+            binary: true,
+            ..GrepOptions::default()
+        };
+        let found = search("Tandi", options);
+        assert_eq!(found.len(), 2);
+        let frm = found.iter().find(|entry| entry.binary).unwrap();
+        assert_eq!(frm.path, "art/tandi.frm");
+        assert!(frm.lines.is_empty());
+    }
+
+    #[test]
+    fn nul_near_the_start_means_binary() {
+        assert!(is_binary(b"abc\0def"));
+        assert!(!is_binary(b"plain text\n"));
+        let mut late = vec![b'a'; 9000];
+        late[8500] = 0;
+        assert!(!is_binary(&late));
+    }
+}
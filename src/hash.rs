@@ -0,0 +1,241 @@
+/*!
+# Entry Checksums
+
+`dat3 hash` prints a checksum of every entry's decompressed content, one
+`<hex>  <path>` line per entry: the format of `sha256sum`, so a manifest made
+from an archive also verifies the extracted files with `sha256sum -c`.
+
+`dat3 hash --check manifest.txt` verifies an archive against such a manifest
+and reports entries whose content differs, entries the manifest lists but the
+archive lacks, and entries the archive has but the manifest doesn't list.
+Paths are compared like entry paths: either slash, any case.
+*/
+
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::common::{utils, DatArchive, FileEntry};
+use crate::error::DatError;
+
+/// Checksum algorithm of `dat3 hash`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashAlgo {
+    /// CRC-32 (as in zip and PNG), 8 hex digits
+    Crc32,
+    /// SHA-256, 64 hex digits
+    #[default]
+    Sha256,
+}
+
+impl HashAlgo {
+    /// Lowercase hex checksum of `data`
+    pub fn digest(self, data: &[u8]) -> String {
+        match self {
+            Self::Crc32 => {
+                let mut crc = flate2::Crc::new();
+                crc.update(data);
+                format!("{:08x}", crc.sum())
+            }
+            Self::Sha256 => Sha256::digest(data)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+
+    /// Number of hex digits in a checksum
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Crc32 => 8,
+            Self::Sha256 => 64,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Crc32 => "crc32",
+            Self::Sha256 => "sha256",
+        })
+    }
+}
+
+/// The checksum of one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryHash {
+    /// Lowercase hex checksum of the decompressed content
+    pub hash: String,
+    /// Entry path with forward slashes
+    pub path: String,
+}
+
+/// A manifest line: checksum, two spaces, path
+impl fmt::Display for EntryHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}", self.hash, self.path)
+    }
+}
+
+/// Checksum the decompressed content of `entries` in parallel, keeping their order
+pub fn hash_entries(
+    archive: &DatArchive,
+    entries: &[&FileEntry],
+    algo: HashAlgo,
+) -> Result<Vec<EntryHash>> {
+    entries
+        .par_iter()
+        .map(|file| hash_entry(archive, file, algo).map_err(Into::into))
+        .collect()
+}
+
+fn hash_entry(
+    archive: &DatArchive,
+    file: &FileEntry,
+    algo: HashAlgo,
+) -> Result<EntryHash, DatError> {
+    let data = archive.read_entry(file)?;
+    Ok(EntryHash {
+        hash: algo.digest(&data),
+        path: file.path(),
+    })
+}
+
+/// Parse a manifest written by `dat3 hash` or `sha256sum`.
+///
+/// Each non-empty line is a checksum of `algo`, a space, then the path,
+/// optionally marked binary with `*`. A leading `./` on the path is dropped.
+pub fn parse_manifest(text: &str, algo: HashAlgo) -> Result<Vec<EntryHash>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_manifest_line(line, algo).with_context(|| format!("Manifest line {}", index + 1))
+        })
+        .collect()
+}
+
+fn parse_manifest_line(line: &str, algo: HashAlgo) -> Result<EntryHash> {
+    let line = line.trim_end_matches('\r');
+    let Some((hash, rest)) = line.split_once(' ') else {
+        bail!("Expected '<checksum>  <path>', got '{line}'");
+    };
+    if hash.len() != algo.hex_len() || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!(
+            "'{hash}' is not a {algo} checksum ({} hex digits)",
+            algo.hex_len()
+        );
+    }
+    let path = rest.strip_prefix([' ', '*']).unwrap_or(rest);
+    let path = path.strip_prefix("./").unwrap_or(path);
+    if path.is_empty() {
+        bail!("Missing path after checksum {hash}");
+    }
+    Ok(EntryHash {
+        hash: hash.to_ascii_lowercase(),
+        path: utils::canonical_entry_path(path),
+    })
+}
+
+/// Outcome of checking an archive against a manifest
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// Entries whose checksum matches the manifest
+    pub matched: usize,
+    /// Entries whose checksum differs from the manifest
+    pub mismatched: Vec<String>,
+    /// Entries whose content couldn't be read, with the reason
+    pub unreadable: Vec<(String, DatError)>,
+    /// Manifest paths with no entry in the archive
+    pub missing: Vec<String>,
+    /// Archive entries the manifest doesn't list
+    pub extra: Vec<String>,
+}
+
+impl CheckReport {
+    /// Whether every listed entry is present and matches, with none extra
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty()
+            && self.unreadable.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+/// One line per problem in the style of `sha256sum -c`, then a summary
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for path in &self.mismatched {
+            writeln!(f, "{path}: FAILED")?;
+        }
+        for (path, error) in &self.unreadable {
+            writeln!(f, "{path}: FAILED open or read ({error})")?;
+        }
+        for path in &self.missing {
+            writeln!(f, "{path}: MISSING")?;
+        }
+        for path in &self.extra {
+            writeln!(f, "{path}: EXTRA")?;
+        }
+        writeln!(
+            f,
+            "{} matched, {} mismatched, {} unreadable, {} missing, {} extra",
+            self.matched,
+            self.mismatched.len(),
+            self.unreadable.len(),
+            self.missing.len(),
+            self.extra.len()
+        )
+    }
+}
+
+/// Check `entries` of `archive` against `manifest`, hashing in parallel.
+///
+/// Every entry is expected to be listed; manifest lines for paths outside
+/// `entries` count as missing.
+pub fn check_manifest(
+    archive: &DatArchive,
+    entries: &[&FileEntry],
+    manifest: &[EntryHash],
+    algo: HashAlgo,
+) -> CheckReport {
+    let by_key: HashMap<String, &FileEntry> = entries
+        .iter()
+        .map(|file| (utils::entry_path_key(&file.name), *file))
+        .collect();
+    let listed: HashSet<String> = manifest
+        .iter()
+        .map(|expected| utils::entry_path_key(&expected.path))
+        .collect();
+
+    let mut report = CheckReport::default();
+    let mut to_hash = Vec::new();
+    for expected in manifest {
+        match by_key.get(&utils::entry_path_key(&expected.path)) {
+            Some(file) => to_hash.push((*file, expected)),
+            None => report.missing.push(expected.path.clone()),
+        }
+    }
+
+    let results: Vec<_> = to_hash
+        .par_iter()
+        .map(|(file, expected)| (file.path(), *expected, hash_entry(archive, file, algo)))
+        .collect();
+    for (path, expected, result) in results {
+        match result {
+            Ok(actual) if actual.hash == expected.hash => report.matched += 1,
+            Ok(_) => report.mismatched.push(path),
+            Err(error) => report.unreadable.push((path, error)),
+        }
+    }
+
+    report.extra = entries
+        .iter()
+        .filter(|file| !listed.contains(&utils::entry_path_key(&file.name)))
+        .map(|file| file.path())
+        .collect();
+    report
+}
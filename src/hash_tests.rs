@@ -0,0 +1,146 @@
+/*!
+Unit tests for entry checksums and manifest checks.
+
+Tests cover both algorithms against known digests, parsing manifests in
+`dat3 hash` and `sha256sum` form, and checking an archive against one.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::hash::*;
+
+    fn sample_archive() -> DatArchive {
+        let entries = vec![
+            ("art\\a.frm".to_string(), vec![7; 4096]),
+            ("text\\readme.txt".to_string(), b"abc".to_vec()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        DatArchive::from_entries(false, entries, &policy).unwrap().0
+    }
+
+    fn manifest_of(archive: &DatArchive, algo: HashAlgo) -> Vec<EntryHash> {
+        let entries: Vec<_> = archive.entries().collect();
+        hash_entries(archive, &entries, algo).unwrap()
+    }
+
+    // ── HashAlgo ───────────────────────────────────────────────────
+
+    mod algo {
+        use super::*;
+
+        #[test]
+        fn known_digests() {
+            assert_eq!(HashAlgo::Crc32.digest(b"abc"), "352441c2");
+            assert_eq!(
+                HashAlgo::Sha256.digest(b"abc"),
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            );
+        }
+
+        #[test]
+        fn digest_length_matches_hex_len() {
+            for algo in [HashAlgo::Crc32, HashAlgo::Sha256] {
+                assert_eq!(algo.digest(b"").len(), algo.hex_len());
+            }
+        }
+    }
+
+    // ── hash_entries ───────────────────────────────────────────────
+
+    mod hash_entries {
+        use super::*;
+
+        #[test]
+        fn hashes_decompressed_content_in_entry_order() {
+            let archive = sample_archive();
+            let hashes = manifest_of(&archive, HashAlgo::Crc32);
+            assert_eq!(
+                hashes.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+                [
+                    format!("{}  art/a.frm", HashAlgo::Crc32.digest(&[7; 4096])),
+                    "352441c2  text/readme.txt".to_string(),
+                ]
+            );
+        }
+    }
+
+    // ── parse_manifest ─────────────────────────────────────────────
+
+    mod parse_manifest {
+        use super::*;
+
+        #[test]
+        fn reads_its_own_output_and_sha256sum_forms() {
+            let text = "352441C2  ./text/readme.txt\r\n\n0000abcd *Art\\a.frm\n";
+            let manifest = parse_manifest(text, HashAlgo::Crc32).unwrap();
+            assert_eq!(
+                manifest,
+                [
+                    EntryHash {
+                        hash: "352441c2".into(),
+                        path: "text/readme.txt".into()
+                    },
+                    EntryHash {
+                        hash: "0000abcd".into(),
+                        path: "Art/a.frm".into()
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn rejects_checksums_of_another_algorithm() {
+            let err = parse_manifest("x\n352441c2  a.txt\n", HashAlgo::Sha256).unwrap_err();
+            assert_eq!(err.to_string(), "Manifest line 1");
+            let err = parse_manifest("352441c2  a.txt\n", HashAlgo::Sha256).unwrap_err();
+            assert!(format!("{err:#}").contains("is not a sha256 checksum (64 hex digits)"));
+        }
+
+        #[test]
+        fn rejects_missing_path() {
+            assert!(parse_manifest("352441c2  \n", HashAlgo::Crc32).is_err());
+        }
+    }
+
+    // ── check_manifest ─────────────────────────────────────────────
+
+    mod check_manifest {
+        use super::*;
+
+        #[test]
+        fn own_manifest_matches() {
+            let archive = sample_archive();
+            let manifest = manifest_of(&archive, HashAlgo::Sha256);
+            let entries: Vec<_> = archive.entries().collect();
+            let report = check_manifest(&archive, &entries, &manifest, HashAlgo::Sha256);
+            assert!(report.is_ok());
+            assert_eq!(report.matched, 2);
+        }
+
+        #[test]
+        fn reports_mismatched_missing_and_extra() {
+            let archive = sample_archive();
+            let entries: Vec<_> = archive.entries().collect();
+            let manifest = parse_manifest(
+                "00000000  TEXT\\README.TXT\n352441c2  maps/gone.map\n",
+                HashAlgo::Crc32,
+            )
+            .unwrap();
+
+            let report = check_manifest(&archive, &entries, &manifest, HashAlgo::Crc32);
+            assert!(!report.is_ok());
+            assert_eq!(report.matched, 0);
+            assert_eq!(report.mismatched, ["text/readme.txt"]);
+            assert_eq!(report.missing, ["maps/gone.map"]);
+            assert_eq!(report.extra, ["art/a.frm"]);
+            assert_eq!(
+                report.to_string(),
+                "text/readme.txt: FAILED\n\
+                 maps/gone.map: MISSING\n\
+                 art/a.frm: EXTRA\n\
+                 0 matched, 1 mismatched, 0 unreadable, 1 missing, 1 extra\n"
+            );
+        }
+    }
+}
@@ -0,0 +1,164 @@
+/*!
+# Ignore Files
+
+A `.dat3ignore` file in a directory that `a` or `c` walks lists files to
+leave out of the archive, one gitignore-style pattern per line:
+
+```text
+# Script sources, except one the mod ships
+*.ssl
+!scripts/global.ssl
+/docs/
+```
+
+- Blank lines and lines starting with `#` are skipped; `\#` and `\!` start
+  a pattern with those characters.
+- `!` re-includes what an earlier pattern left out. A file in a left-out
+  directory can't be re-included, since the directory isn't walked.
+- A trailing `/` only matches directories.
+- A pattern with a `/` anywhere else is matched against the path relative
+  to the directory of the ignore file; other patterns are matched against
+  the file or directory name at any depth.
+- `*`, `?` and `[...]` don't match `/`; `**` matches any number of
+  directories.
+
+Matching ignores case, like entry paths. Ignore files in subdirectories
+apply to their subtree and win over those above them; within one file the
+last matching pattern wins. The ignore files themselves are never added.
+*/
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Name of the ignore file read from every walked directory
+pub const IGNORE_FILE_NAME: &str = ".dat3ignore";
+
+/// One pattern line of an ignore file
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: glob::Pattern,
+    /// `!pattern`: re-include instead of leaving out
+    negated: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+    /// Has a `/` before its end: matched against the whole relative path
+    anchored: bool,
+}
+
+/// The patterns of one ignore file
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    /// Directory of the file, relative to the walked root, with `/`
+    /// separators ("" for the root itself)
+    base: String,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    /// Parse the text of an ignore file found in `base`, a directory
+    /// relative to the walked root with `/` separators
+    pub fn parse(text: &str, base: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.strip_prefix('/').unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let pattern = glob::Pattern::new(line)
+                .with_context(|| format!("Invalid pattern on line {}: {line}", number + 1))?;
+            rules.push(Rule {
+                pattern,
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+        Ok(Self {
+            base: base.trim_matches('/').to_string(),
+            rules,
+        })
+    }
+
+    /// Read the ignore file of `dir`, found at `base` relative to the walked
+    /// root, if it has one
+    pub fn load(dir: &Path, base: &str) -> Result<Option<Self>> {
+        let path = dir.join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text, base)
+            .with_context(|| format!("Invalid ignore file {}", path.display()))
+            .map(Some)
+    }
+
+    /// Whether the last pattern matching `path` (relative to the walked
+    /// root) leaves it out, or None if none matches or it's outside `base`
+    fn decide(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let relative = if self.base.is_empty() {
+            path
+        } else {
+            path.strip_prefix(&self.base)?.strip_prefix('/')?
+        };
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.dir_only)
+                    && rule
+                        .pattern
+                        .matches_with(if rule.anchored { relative } else { name }, options)
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// The ignore files that apply at one point of a directory walk, from the
+/// walked root down
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    files: Vec<IgnoreFile>,
+}
+
+impl IgnoreStack {
+    /// Start applying `file`, read from a directory the walk entered
+    pub fn push(&mut self, file: IgnoreFile) {
+        self.files.push(file);
+    }
+
+    /// Stop applying the file pushed last, when the walk leaves its directory
+    pub fn pop(&mut self) {
+        self.files.pop();
+    }
+
+    /// Whether `path`, relative to the walked root with `/` separators, is
+    /// left out. Deeper ignore files win over those above them.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.files
+            .iter()
+            .rev()
+            .find_map(|file| file.decide(path, is_dir))
+            .unwrap_or(false)
+    }
+}
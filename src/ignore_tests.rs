@@ -0,0 +1,104 @@
+/*!
+Unit tests for ignore files.
+
+Tests cover comments and escapes, negation, directory-only and anchored
+patterns, `**`, case-insensitive matching, and nested files winning over
+those above them.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::ignore::*;
+
+    fn stack(files: &[(&str, &str)]) -> IgnoreStack {
+        let mut stack = IgnoreStack::default();
+        for (base, text) in files {
+            stack.push(IgnoreFile::parse(text, base).unwrap());
+        }
+        stack
+    }
+
+    fn ignored(stack: &IgnoreStack, path: &str) -> bool {
+        stack.is_ignored(path, false)
+    }
+
+    #[test]
+    fn names_match_at_any_depth() {
+        let stack = stack(&[("", "*.ssl\nnotes.txt\n")]);
+        assert!(ignored(&stack, "a.ssl"));
+        assert!(ignored(&stack, "scripts/deep/b.SSL"));
+        assert!(ignored(&stack, "text/Notes.txt"));
+        assert!(!ignored(&stack, "scripts/a.int"));
+    }
+
+    #[test]
+    fn comments_blank_lines_and_escapes() {
+        let stack = stack(&[("", "# a comment\n\n\\#hash.txt\n\\!bang.txt   \n")]);
+        assert!(ignored(&stack, "#hash.txt"));
+        assert!(ignored(&stack, "!bang.txt"));
+        assert!(!ignored(&stack, "# a comment"));
+    }
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let ordered = stack(&[("", "*.ssl\n!global.ssl\n")]);
+        assert!(ignored(&ordered, "scripts/a.ssl"));
+        assert!(!ignored(&ordered, "scripts/global.ssl"));
+
+        let reordered = stack(&[("", "!global.ssl\n*.ssl\n")]);
+        assert!(ignored(&reordered, "scripts/global.ssl"));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let stack = stack(&[("", "build/\n")]);
+        assert!(stack.is_ignored("build", true));
+        assert!(stack.is_ignored("src/build", true));
+        assert!(!stack.is_ignored("build", false));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_anchored() {
+        let stack = stack(&[("", "/docs\nart/*.bak\n")]);
+        assert!(stack.is_ignored("docs", true));
+        assert!(!stack.is_ignored("text/docs", true));
+        assert!(ignored(&stack, "art/a.bak"));
+        assert!(!ignored(&stack, "art/critters/a.bak"));
+        assert!(!ignored(&stack, "mod/art/a.bak"));
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        let stack = stack(&[("", "art/**/*.bak\n**/tmp\n")]);
+        assert!(ignored(&stack, "art/a.bak"));
+        assert!(ignored(&stack, "art/critters/deep/a.bak"));
+        assert!(stack.is_ignored("a/b/tmp", true));
+    }
+
+    #[test]
+    fn nested_files_apply_to_their_subtree_and_win() {
+        let stack = stack(&[("", "*.ssl\n"), ("scripts", "!keep.ssl\n/local.txt\n")]);
+        assert!(!ignored(&stack, "scripts/keep.ssl"));
+        assert!(ignored(&stack, "keep.ssl"));
+        assert!(ignored(&stack, "scripts/other.ssl"));
+        // Anchored to the nested file's directory
+        assert!(ignored(&stack, "scripts/local.txt"));
+        assert!(!ignored(&stack, "local.txt"));
+        assert!(!ignored(&stack, "scriptsx/local.txt"));
+    }
+
+    #[test]
+    fn popping_stops_applying_a_file() {
+        let mut stack = stack(&[("", "*.ssl\n"), ("scripts", "*.int\n")]);
+        assert!(ignored(&stack, "scripts/a.int"));
+        stack.pop();
+        assert!(!ignored(&stack, "scripts/a.int"));
+        assert!(ignored(&stack, "scripts/a.ssl"));
+    }
+
+    #[test]
+    fn invalid_patterns_name_the_line() {
+        let err = IgnoreFile::parse("*.ssl\n[a\n", "").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+}
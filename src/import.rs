@@ -0,0 +1,144 @@
+/*!
+# Importing from Zip
+
+`dat3 import archive.dat mod.zip` adds the files of a zip to an archive, the
+inverse of `dat3 export`. Paths keep the zip's directories, with the
+archive's backslashes; `-t` puts them under a directory. Files replace
+same-named entries, like `dat3 a`, and are compressed by the same
+`CompressionPolicy`.
+
+The zip is read through its central directory. Directory entries are
+skipped. Only stored and deflated files are supported; encrypted files and
+zip64 archives (over 65,535 entries or 4 GiB) are rejected. Each file's
+CRC-32 is checked, and inflating stops as soon as a file passes the size
+the zip records for it.
+*/
+
+use anyhow::{bail, Context, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::Read;
+
+use crate::export::{
+    CENTRAL_HEADER_SIGNATURE, END_OF_CENTRAL_DIRECTORY_SIGNATURE, LOCAL_HEADER_SIGNATURE,
+    METHOD_DEFLATED, METHOD_STORED,
+};
+
+const END_OF_CENTRAL_DIRECTORY_LEN: usize = 22;
+const CENTRAL_HEADER_LEN: usize = 46;
+const LOCAL_HEADER_LEN: usize = 30;
+/// The end record is followed by a comment of at most this many bytes
+const MAX_COMMENT_LEN: usize = u16::MAX as usize;
+/// Flag bit 0: the file is encrypted
+const FLAG_ENCRYPTED: u16 = 1;
+/// Most bytes deflate can produce per input byte, bounding how much is
+/// allocated up front for a size the zip claims
+const MAX_DEFLATE_RATIO: u32 = 1032;
+
+/// Bytes `zip[start..start + len]`, failing if the zip is too short
+fn field(zip: &[u8], start: usize, len: usize) -> Result<&[u8]> {
+    start
+        .checked_add(len)
+        .and_then(|end| zip.get(start..end))
+        .ok_or_else(|| anyhow::anyhow!("Truncated zip: a record runs past the end of the file"))
+}
+
+/// Position of the end of central directory record
+fn find_end_record(zip: &[u8]) -> Result<usize> {
+    let last = zip
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIRECTORY_LEN)
+        .ok_or_else(|| anyhow::anyhow!("Not a zip file: too short"))?;
+    let first = last.saturating_sub(MAX_COMMENT_LEN);
+    (first..=last)
+        .rev()
+        .find(|&pos| LittleEndian::read_u32(&zip[pos..]) == END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+        .ok_or_else(|| anyhow::anyhow!("Not a zip file: no end of central directory record"))
+}
+
+/// Read the files of the zip `zip` as `(path, content)` pairs, in central
+/// directory order. Paths are as the zip stores them, usually with forward
+/// slashes; directory entries are left out.
+pub fn read_zip(zip: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let end = find_end_record(zip)?;
+    let count = LittleEndian::read_u16(&zip[end + 10..]);
+    let directory_offset = LittleEndian::read_u32(&zip[end + 16..]);
+    if count == u16::MAX || directory_offset == u32::MAX {
+        bail!("Zip64 archives are not supported");
+    }
+
+    let mut files = Vec::with_capacity(usize::from(count));
+    let mut pos = directory_offset as usize;
+    for _ in 0..count {
+        let header = field(zip, pos, CENTRAL_HEADER_LEN)?;
+        if LittleEndian::read_u32(header) != CENTRAL_HEADER_SIGNATURE {
+            bail!("Corrupt zip: bad central directory header at offset {pos}");
+        }
+        let flags = LittleEndian::read_u16(&header[8..]);
+        let method = LittleEndian::read_u16(&header[10..]);
+        let crc = LittleEndian::read_u32(&header[16..]);
+        let packed_size = LittleEndian::read_u32(&header[20..]);
+        let size = LittleEndian::read_u32(&header[24..]);
+        let name_len = usize::from(LittleEndian::read_u16(&header[28..]));
+        let extra_len = usize::from(LittleEndian::read_u16(&header[30..]));
+        let comment_len = usize::from(LittleEndian::read_u16(&header[32..]));
+        let header_offset = LittleEndian::read_u32(&header[42..]);
+        let name = field(zip, pos + CENTRAL_HEADER_LEN, name_len)?;
+        let name = String::from_utf8(name.to_vec())
+            .map_err(|_| anyhow::anyhow!("Zip entry name is not valid UTF-8 at offset {pos}"))?;
+        pos += CENTRAL_HEADER_LEN + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') || name.ends_with('\\') {
+            continue;
+        }
+        if [packed_size, size, header_offset].contains(&u32::MAX) {
+            bail!("{name}: zip64 entries are not supported");
+        }
+        if flags & FLAG_ENCRYPTED != 0 {
+            bail!("{name}: encrypted zip entries are not supported");
+        }
+
+        let local = field(zip, header_offset as usize, LOCAL_HEADER_LEN)?;
+        if LittleEndian::read_u32(local) != LOCAL_HEADER_SIGNATURE {
+            bail!("{name}: corrupt zip, bad local header at offset {header_offset}");
+        }
+        let data_start = header_offset as usize
+            + LOCAL_HEADER_LEN
+            + usize::from(LittleEndian::read_u16(&local[26..]))
+            + usize::from(LittleEndian::read_u16(&local[28..]));
+        let stored = field(zip, data_start, packed_size as usize)?;
+
+        let content = match method {
+            METHOD_STORED => stored.to_vec(),
+            METHOD_DEFLATED => {
+                // The sizes are untrusted: allocate no more than the packed
+                // data can inflate to, and stop one byte past the claimed size
+                let capacity = size.min(packed_size.saturating_mul(MAX_DEFLATE_RATIO));
+                let mut content = Vec::with_capacity(capacity as usize);
+                flate2::read::DeflateDecoder::new(stored)
+                    .take(u64::from(size) + 1)
+                    .read_to_end(&mut content)
+                    .with_context(|| format!("{name}: failed to inflate"))?;
+                if content.len() > size as usize {
+                    bail!("{name}: inflates to more than the {size} bytes the zip says");
+                }
+                content
+            }
+            other => bail!(
+                "{name}: compression method {other} is not supported, only stored and deflate"
+            ),
+        };
+        if content.len() != size as usize {
+            bail!(
+                "{name}: size mismatch, the zip says {size} bytes, got {}",
+                content.len()
+            );
+        }
+        let mut actual = flate2::Crc::new();
+        actual.update(&content);
+        if actual.sum() != crc {
+            bail!("{name}: CRC-32 mismatch, the zip is damaged");
+        }
+        files.push((name, content));
+    }
+    Ok(files)
+}
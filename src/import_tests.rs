@@ -0,0 +1,146 @@
+/*!
+Unit tests for importing from zip.
+
+Zips are written with the exporter's `ZipWriter`, read back, and added to
+DAT1 and DAT2 archives with the replace semantics of adding files.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{
+        AddOptions, CompressionLevel, CompressionPolicy, DatArchive, NameCase, Progress,
+    };
+    use crate::export::ZipWriter;
+    use crate::import::*;
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A zip with a directory entry, a stored and a deflated file
+    fn sample_zip() -> Vec<u8> {
+        let frame = b"frame ".repeat(50);
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add("art/", b"", None).unwrap();
+        zip.add("art/critters/a.frm", &frame, Some(&deflate(&frame)))
+            .unwrap();
+        zip.add("text/b.msg", b"hi", None).unwrap();
+        zip.finish().unwrap().0
+    }
+
+    fn options() -> AddOptions {
+        AddOptions::new(CompressionPolicy::new(CompressionLevel::new(6).unwrap()))
+    }
+
+    #[test]
+    fn files_are_read_and_directories_skipped() {
+        let files = read_zip(&sample_zip()).unwrap();
+        let names: Vec<_> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["art/critters/a.frm", "text/b.msg"]);
+        assert_eq!(files[0].1, b"frame ".repeat(50));
+        assert_eq!(files[1].1, b"hi");
+    }
+
+    #[test]
+    fn damaged_content_fails_the_checksum() {
+        let mut zip = sample_zip();
+        let pos = zip.windows(2).position(|w| w == b"hi").unwrap();
+        zip[pos] = b'H';
+        let error = read_zip(&zip).unwrap_err().to_string();
+        assert!(error.contains("text/b.msg: CRC-32 mismatch"), "{error}");
+    }
+
+    /// `zip` with the uncompressed size in its first central directory
+    /// header replaced by `size`
+    fn with_claimed_size(mut zip: Vec<u8>, size: u32) -> Vec<u8> {
+        let header = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        zip[header + 24..header + 28].copy_from_slice(&size.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn inflating_stops_past_the_claimed_size() {
+        let zeros = vec![0u8; 10 << 20];
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add("bomb.frm", &zeros, Some(&deflate(&zeros))).unwrap();
+        let zip = zip.finish().unwrap().0;
+
+        let error = read_zip(&with_claimed_size(zip.clone(), 100)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "bomb.frm: inflates to more than the 100 bytes the zip says"
+        );
+        // A claim near 4 GiB isn't allocated up front
+        let error = read_zip(&with_claimed_size(zip, u32::MAX - 1)).unwrap_err();
+        assert!(error.to_string().contains("size mismatch"), "{error}");
+    }
+
+    #[test]
+    fn not_a_zip_is_rejected() {
+        let error = read_zip(b"not a zip, just some text").unwrap_err();
+        assert!(error.to_string().contains("Not a zip file"), "{error}");
+    }
+
+    #[test]
+    fn contents_replace_entries_under_the_target_dir() {
+        for dat1 in [false, true] {
+            let policy = options().compression;
+            let (mut archive, _) = DatArchive::from_entries(
+                dat1,
+                vec![("data\\text\\b.msg".to_string(), b"old".to_vec())],
+                &policy,
+            )
+            .unwrap();
+
+            let mut options = options();
+            options.target_dir = Some("data".to_string());
+            let files = read_zip(&sample_zip()).unwrap();
+            let stats = archive
+                .add_contents(files, &options, &Progress::default())
+                .unwrap();
+
+            assert_eq!(stats.files_added, 2);
+            assert_eq!(archive.len(), 2);
+            assert_eq!(archive.read_file("data\\text\\b.msg").unwrap(), b"hi");
+            assert_eq!(
+                archive.read_file("data\\art\\critters\\a.frm").unwrap(),
+                b"frame ".repeat(50)
+            );
+        }
+    }
+
+    #[test]
+    fn excluded_names_are_left_out_and_case_is_folded() {
+        let mut options = options();
+        options.case = NameCase::Lower;
+        let contents = vec![
+            ("__MACOSX/._A.FRM".to_string(), b"junk".to_vec()),
+            ("ART/A.FRM".to_string(), b"frame".to_vec()),
+        ];
+        let mut archive = DatArchive::new_dat2();
+        let stats = archive
+            .add_contents(contents, &options, &Progress::default())
+            .unwrap();
+        assert_eq!(
+            (stats.files_added, stats.excluded, stats.case_folded),
+            (1, 1, 1)
+        );
+        assert_eq!(archive.entry_names(), ["art\\a.frm"]);
+    }
+
+    #[test]
+    fn names_that_collide_are_rejected() {
+        let contents = vec![
+            ("art/a.frm".to_string(), b"1".to_vec()),
+            ("art\\A.FRM".to_string(), b"2".to_vec()),
+        ];
+        let error = DatArchive::new_dat2()
+            .add_contents(contents, &options(), &Progress::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("would be added as"), "{error}");
+    }
+}
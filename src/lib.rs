@@ -0,0 +1,105 @@
+/*!
+# fallout-dat3
+
+Read, extract, create and modify Fallout 1 (DAT1) and Fallout 2 (DAT2)
+archives. This is the library behind the `dat3` command-line tool.
+
+`DatArchive` detects the format on open and dispatches to `Dat1Archive` or
+`Dat2Archive`. The library never prints: operations that do work report
+`Event`s to an optional handler installed with `Progress::with_events`.
+
+```
+use fallout_dat3::{CompressionLevel, CompressionPolicy, DatArchive};
+
+let policy = CompressionPolicy::new(CompressionLevel::new(9)?);
+let entries = vec![("text\\english\\readme.txt".to_string(), b"Hello".to_vec())];
+let (archive, _stats) = DatArchive::from_entries(false, entries, &policy)?;
+
+// Names accept either slash and any case, see `compare_entry_paths`
+assert_eq!(archive.read_file("Text/English/ReadMe.txt")?, b"Hello");
+# Ok::<(), anyhow::Error>(())
+```
+*/
+
+pub mod batch; // Commands of `dat3 batch` scripts
+pub mod bench; // Decompression and recompression throughput
+pub mod cache; // Optional on-disk cache of decompressed entries
+pub mod check; // Archive health checks
+pub mod common; // Shared utilities and the main DatArchive interface
+pub mod cp1252; // Windows-1252 entry names
+pub mod dat1; // Fallout 1 DAT format implementation
+pub mod dat2; // Fallout 2 DAT format implementation
+pub mod dedupe; // Removing entries with duplicate names
+pub mod error; // Typed errors of the read API
+pub mod export; // Writing entries into a zip file
+pub mod find; // Finding entries by name, size and compression
+pub mod grep; // Searching entry content
+pub mod hash; // Per-entry checksums and manifest checks
+pub mod ignore; // .dat3ignore files of directories being added
+pub mod import; // Adding the files of a zip file
+pub mod lzss; // LZSS decompression for DAT1 files
+pub mod manifest; // Build manifests and rebuilding archives from them
+pub mod merge; // Layering several archives into one
+pub mod patch; // Building the next patchNNN.dat of a game
+pub mod repair; // Salvaging entries from damaged DAT2 archives
+pub mod tree; // Directory hierarchy of entry paths
+pub mod usage; // Space usage by directory or extension
+pub mod verify; // Comparing an archive with a directory
+
+#[cfg(test)]
+mod batch_tests;
+#[cfg(test)]
+mod bench_tests;
+#[cfg(test)]
+mod cache_tests;
+#[cfg(test)]
+mod check_tests;
+#[cfg(test)]
+mod common_tests;
+#[cfg(test)]
+mod cp1252_tests;
+#[cfg(test)]
+mod dedupe_tests;
+#[cfg(test)]
+mod error_tests;
+#[cfg(test)]
+mod export_tests;
+#[cfg(test)]
+mod find_tests;
+#[cfg(test)]
+mod grep_tests;
+#[cfg(test)]
+mod hash_tests;
+#[cfg(test)]
+mod ignore_tests;
+#[cfg(test)]
+mod import_tests;
+#[cfg(test)]
+mod lzss_tests;
+#[cfg(test)]
+mod manifest_tests;
+#[cfg(test)]
+mod merge_tests;
+#[cfg(test)]
+mod patch_tests;
+#[cfg(test)]
+mod repair_tests;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod tree_tests;
+#[cfg(test)]
+mod usage_tests;
+#[cfg(test)]
+mod verify_tests; // Fixtures shared by the tests
+
+pub use common::utils::{canonical_entry_path, compare_entry_paths, entry_path_key};
+pub use common::{
+    AddOptions, AddStats, ArchiveFormat, CollectedFiles, CompressionLevel, CompressionPolicy,
+    DatArchive, DeletePlan, DirectoryContents, EntryProblem, EntryProblemKind, Event, ExcludeList,
+    ExtractOptions, ExtractOutcome, ExtractionMode, FileEntry, NameCase, OpenOptions, Progress,
+    SharedData,
+};
+pub use dat1::Dat1Archive;
+pub use dat2::Dat2Archive;
+pub use error::{DatError, DatResult};
@@ -0,0 +1,79 @@
+/*!
+# Diagnostic Logging
+
+dat3 and its library report diagnostics through the `log` facade, written
+to stderr by `env_logger`. Warnings and errors are always shown, as
+`Warning:` and `Error:` lines like the rest of dat3's; `-v` adds info
+messages (summaries of opens and saves), `-vv` debug messages (one per
+entry read, written, added or deleted) and `-vvv` trace messages (LZSS
+block internals). These are prefixed with their level and module.
+
+`RUST_LOG`, when set, replaces that choice with `env_logger` filters, e.g.
+`RUST_LOG=fallout_dat3::lzss=trace` for only the LZSS internals.
+*/
+
+use env_logger::fmt::Formatter;
+use env_logger::WriteStyle;
+use log::{Level, LevelFilter, Record};
+use std::io::{self, Write};
+
+use crate::color::{self, ColorChoice};
+
+/// Modules whose records are shown without `RUST_LOG`: the library and the
+/// command line tool
+const MODULES: [&str; 2] = ["fallout_dat3", "dat3"];
+
+/// Most detailed level shown for `-v` given `verbose` times
+pub fn level(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Start logging at the level `-v` chose, unless `RUST_LOG` is set
+pub fn init(verbose: u8, color: ColorChoice) {
+    let mut builder = env_logger::Builder::new();
+    match std::env::var("RUST_LOG") {
+        Ok(filters) if !filters.is_empty() => {
+            builder.parse_filters(&filters);
+        }
+        _ => {
+            for module in MODULES {
+                builder.filter_module(module, level(verbose));
+            }
+        }
+    }
+    builder
+        .write_style(match color {
+            ColorChoice::Auto => WriteStyle::Auto,
+            ColorChoice::Always => WriteStyle::Always,
+            ColorChoice::Never => WriteStyle::Never,
+        })
+        .format(format)
+        .init();
+}
+
+/// The text before a message of `level`, without styles
+pub fn prefix(level: Level, target: &str) -> String {
+    match level {
+        Level::Error => "Error:".to_string(),
+        Level::Warn => "Warning:".to_string(),
+        _ => format!("[{level} {target}]"),
+    }
+}
+
+fn format(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let style = match record.level() {
+        Level::Error => color::ERROR,
+        Level::Warn => color::WARNING,
+        _ => color::LOG_PREFIX,
+    };
+    let prefix = prefix(record.level(), record.target());
+    match prefix.strip_suffix(':') {
+        Some(word) => writeln!(buf, "{style}{word}{style:#}: {}", record.args()),
+        None => writeln!(buf, "{style}{prefix}{style:#} {}", record.args()),
+    }
+}
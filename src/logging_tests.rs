@@ -0,0 +1,30 @@
+/*!
+Unit tests for diagnostic logging.
+
+Tests cover the level each `-v` count shows and the message prefixes.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::logging::*;
+    use log::{Level, LevelFilter};
+
+    #[test]
+    fn each_v_shows_one_more_level() {
+        assert_eq!(level(0), LevelFilter::Warn);
+        assert_eq!(level(1), LevelFilter::Info);
+        assert_eq!(level(2), LevelFilter::Debug);
+        assert_eq!(level(3), LevelFilter::Trace);
+        assert_eq!(level(9), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn warnings_look_like_other_warnings() {
+        assert_eq!(prefix(Level::Error, "fallout_dat3::cache"), "Error:");
+        assert_eq!(prefix(Level::Warn, "fallout_dat3::cache"), "Warning:");
+        assert_eq!(
+            prefix(Level::Trace, "fallout_dat3::lzss"),
+            "[TRACE fallout_dat3::lzss]"
+        );
+    }
+}
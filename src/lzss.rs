@@ -4,11 +4,11 @@
 Implements the sliding-window dictionary compression used by DAT1 archives.
 
 Only decompression is implemented. Compression is stubbed for future work.
+`Decoder` decompresses as a stream, for writing an entry out without
+holding all of it in memory.
 */
 
-use anyhow::Result;
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io::{Cursor, Read};
+use std::io::{self, Read};
 
 /// Dictionary size (2^12) - standard for DAT1 format
 const DICT_SIZE: usize = 4096;
@@ -33,125 +33,399 @@ const INITIAL_DICT_POS: usize = DICT_SIZE - MAX_MATCH; // 4078
 /// Each compressed block resets the dictionary (filled with spaces, position 4078).
 /// A flag byte controls whether subsequent data is a literal byte or a
 /// 2-byte dictionary reference (position + length).
-pub fn decompress(compressed_data: &[u8]) -> Result<Vec<u8>> {
-    if compressed_data.is_empty() {
-        return Ok(Vec::new());
+///
+/// A stream that ends in the middle of a block is an `UnexpectedEof` error.
+/// Errors name the block and its offset in the input.
+pub fn decompress(compressed_data: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_with_capacity(compressed_data, 0)
+}
+
+/// `decompress` into a buffer allocated for `expected_size` bytes up front,
+/// such as the size recorded for the entry
+pub fn decompress_with_capacity(
+    compressed_data: &[u8],
+    expected_size: usize,
+) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(expected_size);
+    Decoder::new(compressed_data).read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// A stream that decompressed to fewer bytes than expected, the inner
+/// error of `decompress_with_size`'s `InvalidData`
+#[derive(Debug, thiserror::Error)]
+#[error("Stream ended after {actual} of {expected} bytes")]
+pub struct ShortStream {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+/// `decompress` into a buffer allocated for exactly `expected_size` bytes,
+/// failing with `InvalidData` unless the stream decompresses to that size:
+/// decoding stops as soon as it passes it, and a stream that ends short
+/// fails with a `ShortStream` inside. DAT1 entries are read this way.
+pub fn decompress_with_size(compressed_data: &[u8], expected_size: usize) -> io::Result<Vec<u8>> {
+    let output = decompress_limited(compressed_data, expected_size)?;
+    if output.len() != expected_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            ShortStream {
+                expected: expected_size,
+                actual: output.len(),
+            },
+        ));
+    }
+    Ok(output)
+}
+
+/// `decompress` into a buffer allocated for `max_size` bytes, failing with
+/// `InvalidData` if the stream decompresses to more. For untrusted input
+/// with a known size, like a DAT1 entry's recorded one.
+pub fn decompress_limited(compressed_data: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(max_size);
+    Decoder::new(compressed_data)
+        .with_limit(max_size as u64)
+        .read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// The block being decoded
+#[derive(Debug, Clone, Copy)]
+enum Block {
+    /// Between blocks: the next two bytes are a block length
+    Start,
+    /// `remaining` bytes are copied as they are
+    Raw {
+        remaining: usize,
+    },
+    /// Input ends at `end`, the position past the block's last byte
+    Compressed {
+        end: usize,
+    },
+    End,
+}
+
+/// Streaming LZSS decompression: reads decompressed bytes out of
+/// LZSS-encoded data, see `decompress` for the format.
+///
+/// Block lengths are checked against the input before a block is decoded,
+/// and every step consumes input or produces output: each input byte
+/// yields at most 9 output bytes, so decoding any input terminates. A
+/// limit (`with_limit`) stops it early.
+pub struct Decoder<'a> {
+    input: &'a [u8],
+    /// Position of the next unread input byte
+    pos: usize,
+    block: Block,
+    /// Number of the current block, counting from 0, and its input offset
+    block_index: usize,
+    block_offset: usize,
+    /// Most bytes to produce, and the bytes produced so far
+    limit: Option<u64>,
+    produced: u64,
+    dictionary: Box<[u8; DICT_SIZE]>,
+    dict_write_pos: usize,
+    /// Flag byte: shifted right each step, refilled when bit 8 is clear
+    flags: u16,
+    /// Dictionary position and byte count of a reference still being copied
+    match_pos: usize,
+    match_left: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(compressed_data: &'a [u8]) -> Self {
+        Self {
+            input: compressed_data,
+            pos: 0,
+            block: Block::Start,
+            block_index: 0,
+            block_offset: 0,
+            limit: None,
+            produced: 0,
+            dictionary: Box::new([0; DICT_SIZE]),
+            dict_write_pos: INITIAL_DICT_POS,
+            flags: 0,
+            match_pos: 0,
+            match_left: 0,
+        }
     }
 
-    let mut cursor = Cursor::new(compressed_data);
-    let mut output = Vec::new();
-    let mut dictionary = vec![0u8; DICT_SIZE];
-    let mut dict_write_pos;
+    /// Fail with `InvalidData` once more than `max_output` bytes would be
+    /// produced, instead of decoding the rest of the stream
+    pub fn with_limit(mut self, max_output: u64) -> Self {
+        self.limit = Some(max_output);
+        self
+    }
 
-    while let Ok(block_size) = cursor.read_i16::<BigEndian>() {
-        if block_size == 0 {
-            break;
+    /// Where the current block is, for errors
+    fn context(&self) -> BlockContext {
+        BlockContext {
+            index: self.block_index,
+            offset: self.block_offset,
         }
+    }
 
-        if block_size < 0 {
-            // Raw block: read |block_size| bytes directly
-            let bytes_to_read = (-block_size) as usize;
-            let mut direct_bytes = vec![0u8; bytes_to_read];
-            cursor.read_exact(&mut direct_bytes).map_err(|e| {
-                anyhow::anyhow!(
-                    "Failed to read {} uncompressed bytes: {} (remaining: {})",
-                    bytes_to_read,
-                    e,
-                    compressed_data.len() - cursor.position() as usize
-                )
-            })?;
-            output.extend_from_slice(&direct_bytes);
-        } else {
-            // Compressed block: LZSS-encoded data
-            let bytes_to_process = block_size as usize;
-            let mut bytes_read = 0;
-
-            // Reset dictionary for each compressed block
-            dict_write_pos = INITIAL_DICT_POS;
-            dictionary.fill(0x20); // Fill with spaces (ASCII 32)
-
-            // Flag byte: shifted right each iteration, refilled when bit 8 is clear
-            let mut flags: u16 = 0;
-
-            loop {
-                if bytes_read >= bytes_to_process {
-                    break;
+    /// Read the length of the next block and start decoding it
+    fn start_block(&mut self) -> io::Result<()> {
+        let Some(header) = self.input.get(self.pos..self.pos + 2) else {
+            self.block = Block::End;
+            return Ok(());
+        };
+        if self.pos > 0 {
+            self.block_index += 1;
+        }
+        self.block_offset = self.pos;
+        self.pos += 2;
+
+        let size = i16::from_be_bytes([header[0], header[1]]);
+        let length = usize::from(size.unsigned_abs());
+        let remaining = self.input.len() - self.pos;
+        if remaining < length {
+            let kind = if size < 0 {
+                "uncompressed"
+            } else {
+                "compressed"
+            };
+            return Err(truncated(
+                format!("{length} {kind} bytes (remaining: {remaining})"),
+                self.context(),
+            ));
+        }
+        log::trace!(
+            "block {} at input offset {}: {}",
+            self.block_index,
+            self.block_offset,
+            match size {
+                0 => "end of stream".to_string(),
+                size if size < 0 => format!("{length} uncompressed bytes"),
+                _ => format!("{length} compressed bytes"),
+            }
+        );
+        self.block = match size {
+            0 => Block::End,
+            size if size < 0 => Block::Raw { remaining: length },
+            _ => {
+                // Reset dictionary for each compressed block
+                self.dict_write_pos = INITIAL_DICT_POS;
+                self.dictionary.fill(0x20); // Fill with spaces (ASCII 32)
+                self.flags = 0;
+                Block::Compressed {
+                    end: self.pos + length,
                 }
+            }
+        };
+        Ok(())
+    }
+
+    /// Decode the compressed block ending at input position `end` into
+    /// `out` until it's full or the block ends, returning the bytes written.
+    /// The state lives in locals while decoding: this is the hot loop.
+    fn decode_compressed(&mut self, out: &mut [u8], end: usize) -> io::Result<usize> {
+        let context = self.context();
+        let input = self.input;
+        let dictionary = &mut *self.dictionary;
+        let mut pos = self.pos;
+        let mut flags = self.flags;
+        let mut dict_pos = self.dict_write_pos;
+        let mut filled = 0;
 
-                flags >>= 1;
-                if (flags & 256) == 0 {
-                    match cursor.read_u8() {
-                        Ok(c) => {
-                            flags = (c as u16) | 0xff00;
-                            bytes_read += 1;
-                            if bytes_read > bytes_to_process {
-                                break;
-                            }
-                        }
-                        Err(_) => break,
-                    }
+        let result = loop {
+            if filled == out.len() {
+                break Ok(());
+            }
+            if pos >= end {
+                self.block = Block::Start;
+                break Ok(());
+            }
+
+            flags >>= 1;
+            if (flags & 256) == 0 {
+                let Some(&c) = input.get(pos) else {
+                    self.block = Block::Start;
+                    break Ok(());
+                };
+                flags = (c as u16) | 0xff00;
+                pos += 1;
+            }
+
+            if (flags & 1) != 0 {
+                // Literal byte
+                let Some(&byte) = input.get(pos) else {
+                    break Err(truncated(
+                        format!("literal byte at position {pos}"),
+                        context,
+                    ));
+                };
+                pos += 1;
+                dictionary[dict_pos] = byte;
+                dict_pos = (dict_pos + 1) & (DICT_SIZE - 1);
+                out[filled] = byte;
+                filled += 1;
+                continue;
+            }
+
+            // Dictionary reference (2 bytes: position + length)
+            if pos + 1 >= end {
+                self.block = Block::Start;
+                break Ok(());
+            }
+            let Some(&byte1) = input.get(pos) else {
+                break Err(truncated(
+                    format!("dictionary byte 1 at position {pos}"),
+                    context,
+                ));
+            };
+            let Some(&byte2) = input.get(pos + 1) else {
+                break Err(truncated(
+                    format!("dictionary byte 2 at position {}", pos + 1),
+                    context,
+                ));
+            };
+            pos += 2;
+            let (byte1, byte2) = (byte1 as usize, byte2 as usize);
+
+            // Copy match_length+1 bytes from dictionary, as many as fit
+            let mut match_pos = byte1 | ((byte2 & 0xF0) << 4);
+            let length = (byte2 & 0x0F) + 3;
+            let count = length.min(out.len() - filled);
+            // Whole references that neither wrap around the dictionary nor
+            // read bytes they write themselves are copied as slices
+            let distance = dict_pos.wrapping_sub(match_pos) & (DICT_SIZE - 1);
+            if count == length
+                && match_pos + length <= DICT_SIZE
+                && dict_pos + length <= DICT_SIZE
+                && (distance == 0 || distance >= length)
+            {
+                dictionary.copy_within(match_pos..match_pos + length, dict_pos);
+                out[filled..filled + length]
+                    .copy_from_slice(&dictionary[dict_pos..dict_pos + length]);
+                dict_pos = (dict_pos + length) & (DICT_SIZE - 1);
+                filled += length;
+                continue;
+            }
+            for slot in &mut out[filled..filled + count] {
+                let byte = dictionary[match_pos & (DICT_SIZE - 1)];
+                match_pos += 1;
+                dictionary[dict_pos] = byte;
+                dict_pos = (dict_pos + 1) & (DICT_SIZE - 1);
+                *slot = byte;
+            }
+            filled += count;
+            self.match_pos = match_pos;
+            self.match_left = length - count;
+        };
+
+        self.pos = pos;
+        self.flags = flags;
+        self.dict_write_pos = dict_pos;
+        result.map(|()| filled)
+    }
+
+    fn push_to_dictionary(&mut self, byte: u8) {
+        self.dictionary[self.dict_write_pos] = byte;
+        self.dict_write_pos = (self.dict_write_pos + 1) & (DICT_SIZE - 1);
+    }
+}
+
+impl Read for Decoder<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(limit) = self.limit else {
+            return self.decode(buf);
+        };
+        let allowed = limit - self.produced;
+        if allowed == 0 && !buf.is_empty() {
+            // At the limit: any further byte is one too many
+            return match self.decode(&mut [0u8])? {
+                0 => Ok(0),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Output exceeds the limit of {limit} bytes{}",
+                        self.context()
+                    ),
+                )),
+            };
+        }
+        let wanted = buf
+            .len()
+            .min(usize::try_from(allowed).unwrap_or(usize::MAX));
+        let read = self.decode(&mut buf[..wanted])?;
+        self.produced += read as u64;
+        Ok(read)
+    }
+}
+
+impl Decoder<'_> {
+    /// Decode into `buf`, returning the bytes written; 0 at the end
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            if self.match_left > 0 {
+                // Copy from a dictionary reference
+                let count = self.match_left.min(buf.len() - filled);
+                for out in &mut buf[filled..filled + count] {
+                    let byte = self.dictionary[self.match_pos & (DICT_SIZE - 1)];
+                    self.match_pos += 1;
+                    self.push_to_dictionary(byte);
+                    *out = byte;
                 }
+                self.match_left -= count;
+                filled += count;
+                continue;
+            }
 
-                if (flags & 1) != 0 {
-                    // Literal byte
-                    let byte = cursor.read_u8().map_err(|e| {
-                        anyhow::anyhow!(
-                            "Failed to read literal byte at position {}: {}",
-                            bytes_read,
-                            e
-                        )
-                    })?;
-                    bytes_read += 1;
-
-                    output.push(byte);
-                    dictionary[dict_write_pos] = byte;
-                    dict_write_pos = (dict_write_pos + 1) & (DICT_SIZE - 1);
-                } else {
-                    // Dictionary reference (2 bytes: position + length)
-                    if bytes_read + 1 >= bytes_to_process {
-                        break;
-                    }
-
-                    let byte1 = cursor.read_u8().map_err(|e| {
-                        anyhow::anyhow!(
-                            "Failed to read dictionary byte 1 at position {}: {}",
-                            bytes_read,
-                            e
-                        )
-                    })? as u16;
-                    let byte2 = cursor.read_u8().map_err(|e| {
-                        anyhow::anyhow!(
-                            "Failed to read dictionary byte 2 at position {}: {}",
-                            bytes_read + 1,
-                            e
-                        )
-                    })? as u16;
-                    bytes_read += 2;
-
-                    let dict_read_pos = (byte1 | ((byte2 & 0xF0) << 4)) as usize;
-                    let match_length = ((byte2 & 0x0F) + 2) as usize;
-
-                    // Copy match_length+1 bytes from dictionary
-                    for offset in 0..=match_length {
-                        let read_offset = (dict_read_pos + offset) & (DICT_SIZE - 1);
-                        let byte = dictionary[read_offset];
-                        output.push(byte);
-                        dictionary[dict_write_pos] = byte;
-                        dict_write_pos = (dict_write_pos + 1) & (DICT_SIZE - 1);
-                    }
+            match self.block {
+                Block::End => break,
+                Block::Start => self.start_block()?,
+                Block::Raw { remaining: 0 } => self.block = Block::Start,
+                Block::Raw { remaining } => {
+                    // Raw runs are copied whole; start_block checked their length
+                    let count = remaining.min(buf.len() - filled);
+                    buf[filled..filled + count]
+                        .copy_from_slice(&self.input[self.pos..self.pos + count]);
+                    self.pos += count;
+                    filled += count;
+                    self.block = Block::Raw {
+                        remaining: remaining - count,
+                    };
+                }
+                Block::Compressed { end } => {
+                    filled += self.decode_compressed(&mut buf[filled..], end)?;
                 }
             }
         }
+        Ok(filled)
     }
+}
 
-    Ok(output)
+/// A block and its offset in the input, shown as ` in block N at offset O`
+#[derive(Debug, Clone, Copy)]
+struct BlockContext {
+    index: usize,
+    offset: usize,
+}
+
+impl std::fmt::Display for BlockContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, " in block {} at offset {}", self.index, self.offset)
+    }
+}
+
+/// The stream ended before `what`
+fn truncated(what: String, context: BlockContext) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("Failed to read {what}{context}: unexpected end of file"),
+    )
 }
 
 /// LZSS compression for DAT1 files (not yet implemented).
 ///
-/// Currently DAT1 archives are created with uncompressed files.
-/// This stub exists for future implementation.
-#[allow(dead_code)] // Stub for future LZSS compression support
-pub fn compress(_data: &[u8]) -> Result<Vec<u8>> {
-    todo!("LZSS compression not implemented - DAT1 files are stored uncompressed")
+/// DAT1 archives are created with uncompressed files, so this always fails
+/// with `io::ErrorKind::Unsupported`.
+pub fn compress(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "LZSS compression is not implemented; DAT1 files are stored uncompressed",
+    ))
 }
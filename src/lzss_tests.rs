@@ -0,0 +1,330 @@
+#[cfg(test)]
+mod tests {
+    use crate::lzss;
+
+    /// "hello" as one compressed block whose flag byte marks all literals
+    const HELLO_BLOCK: [u8; 10] = [0x00, 0x06, 0xFF, b'h', b'e', b'l', b'l', b'o', 0x00, 0x00];
+
+    /// "ab" as literals, then a reference copying 3 bytes from where "a" went
+    const REFERENCE_BLOCK: [u8; 9] = [0x00, 0x05, 0x03, b'a', b'b', 0xEE, 0xF0, 0x00, 0x00];
+
+    /// xorshift32 noise, for random streams without a dependency
+    fn noise(mut state: u32) -> impl FnMut() -> u32 {
+        move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        }
+    }
+
+    mod decompress {
+        use super::*;
+
+        #[test]
+        fn literal_block() {
+            assert_eq!(lzss::decompress(&HELLO_BLOCK).unwrap(), b"hello");
+        }
+
+        #[test]
+        fn uncompressed_block() {
+            // Negative block size: the next 3 bytes are copied as is
+            let data = [0xFF, 0xFD, b'a', b'b', b'c', 0x00, 0x00];
+            assert_eq!(lzss::decompress(&data).unwrap(), b"abc");
+        }
+
+        #[test]
+        fn truncated_uncompressed_block_is_unexpected_eof() {
+            let data = [0xFF, 0xFD, b'a'];
+            let err = lzss::decompress(&data).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+            assert!(err
+                .to_string()
+                .starts_with("Failed to read 3 uncompressed bytes"));
+        }
+
+        #[test]
+        fn empty_input() {
+            assert!(lzss::decompress(&[]).unwrap().is_empty());
+        }
+
+        #[test]
+        fn dictionary_reference() {
+            assert_eq!(lzss::decompress(&REFERENCE_BLOCK).unwrap(), b"ababa");
+        }
+
+        #[test]
+        fn truncated_compressed_block_is_unexpected_eof() {
+            // The block claims 6 bytes, but the input ends after "he"
+            let err = lzss::decompress(&HELLO_BLOCK[..5]).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+            assert!(err.to_string().starts_with(
+                "Failed to read 6 compressed bytes (remaining: 3) in block 0 at offset 0"
+            ));
+        }
+
+        #[test]
+        fn literal_past_the_input_is_unexpected_eof() {
+            // A block of just a flag byte: its literal would be past the end
+            let err = lzss::decompress(&[0x00, 0x01, 0xFF]).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+            assert!(err
+                .to_string()
+                .starts_with("Failed to read literal byte at position 3 in block 0"));
+        }
+
+        #[test]
+        fn errors_name_the_block_and_its_offset() {
+            let mut data = HELLO_BLOCK[..8].to_vec();
+            data.extend([0xFF, 0xFD, b'a']);
+            let err = lzss::decompress(&data).unwrap_err();
+            assert!(err.to_string().starts_with(
+                "Failed to read 3 uncompressed bytes (remaining: 1) in block 1 at offset 8"
+            ));
+        }
+
+        #[test]
+        fn capacity_is_only_a_hint() {
+            let output = lzss::decompress_with_capacity(&HELLO_BLOCK, 64).unwrap();
+            assert_eq!(output, b"hello");
+            assert!(output.capacity() >= 64);
+            assert_eq!(
+                lzss::decompress_with_capacity(&HELLO_BLOCK, 1).unwrap(),
+                b"hello"
+            );
+        }
+
+        #[test]
+        fn with_size_requires_the_exact_size() {
+            let output = lzss::decompress_with_size(&HELLO_BLOCK, 5).unwrap();
+            assert_eq!(output, b"hello");
+            assert_eq!(output.capacity(), 5);
+
+            let short = lzss::decompress_with_size(&HELLO_BLOCK, 6).unwrap_err();
+            assert_eq!(short.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(short.to_string(), "Stream ended after 5 of 6 bytes");
+            let inner = short.get_ref().unwrap();
+            let inner = inner.downcast_ref::<lzss::ShortStream>().unwrap();
+            assert_eq!((inner.expected, inner.actual), (6, 5));
+
+            let long = lzss::decompress_with_size(&HELLO_BLOCK, 4).unwrap_err();
+            assert!(long
+                .to_string()
+                .starts_with("Output exceeds the limit of 4 bytes"));
+        }
+
+        #[test]
+        fn output_past_the_limit_is_invalid_data() {
+            assert_eq!(lzss::decompress_limited(&HELLO_BLOCK, 5).unwrap(), b"hello");
+            assert_eq!(lzss::decompress_limited(&HELLO_BLOCK, 9).unwrap(), b"hello");
+
+            let err = lzss::decompress_limited(&HELLO_BLOCK, 4).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.to_string(),
+                "Output exceeds the limit of 4 bytes in block 0 at offset 0"
+            );
+        }
+    }
+
+    mod decoder {
+        use super::*;
+        use std::io::Read;
+
+        #[test]
+        fn reads_in_small_chunks_match_decompress() {
+            let mut data = REFERENCE_BLOCK[..7].to_vec();
+            data.extend([0xFF, 0xFD, b'x', b'y', b'z']);
+            data.extend(HELLO_BLOCK);
+
+            let mut decoder = lzss::Decoder::new(&data);
+            let mut output = Vec::new();
+            let mut chunk = [0u8; 2];
+            loop {
+                let read = decoder.read(&mut chunk).unwrap();
+                if read == 0 {
+                    break;
+                }
+                output.extend_from_slice(&chunk[..read]);
+            }
+            assert_eq!(output, b"ababaxyzhello");
+            assert_eq!(lzss::decompress(&data).unwrap(), output);
+        }
+
+        /// Byte-at-a-time decoding as the format describes it, to check the
+        /// decoder's slice copies against
+        fn reference_decompress(data: &[u8]) -> Vec<u8> {
+            let mut output = Vec::new();
+            let mut pos = 0;
+            while pos + 2 <= data.len() {
+                let size = i16::from_be_bytes([data[pos], data[pos + 1]]);
+                pos += 2;
+                if size == 0 {
+                    break;
+                }
+                if size < 0 {
+                    let size = usize::from(size.unsigned_abs());
+                    output.extend_from_slice(&data[pos..pos + size]);
+                    pos += size;
+                    continue;
+                }
+
+                let end = pos + size as usize;
+                let mut dictionary = [b' '; 4096];
+                let mut dict_pos = 4078;
+                let mut flags = 0u16;
+                while pos < end {
+                    flags >>= 1;
+                    if flags & 256 == 0 {
+                        flags = data[pos] as u16 | 0xff00;
+                        pos += 1;
+                    }
+                    if flags & 1 != 0 {
+                        output.push(data[pos]);
+                        dictionary[dict_pos] = data[pos];
+                        dict_pos = (dict_pos + 1) % 4096;
+                        pos += 1;
+                    } else if pos + 1 < end {
+                        let (byte1, byte2) = (data[pos] as usize, data[pos + 1] as usize);
+                        pos += 2;
+                        let start = byte1 | ((byte2 & 0xF0) << 4);
+                        for i in 0..(byte2 & 0x0F) + 3 {
+                            let byte = dictionary[(start + i) % 4096];
+                            output.push(byte);
+                            dictionary[dict_pos] = byte;
+                            dict_pos = (dict_pos + 1) % 4096;
+                        }
+                    } else {
+                        pos = end;
+                    }
+                }
+            }
+            output
+        }
+
+        #[test]
+        fn random_stream_matches_reference() {
+            // Compressed blocks of random literals and references (wrapping and overlapping ones included), with
+            // raw blocks between them
+            let mut next = noise(0x2545_F491);
+            let mut data = Vec::new();
+            for _ in 0..20 {
+                let mut block = Vec::new();
+                for _ in 0..200 {
+                    let mut flags = 0u8;
+                    let mut items = Vec::new();
+                    for bit in 0..8 {
+                        if next() & 1 == 0 {
+                            flags |= 1 << bit;
+                            items.push(next() as u8);
+                        } else {
+                            let reference = next();
+                            items.extend([reference as u8, (reference >> 8) as u8]);
+                        }
+                    }
+                    block.push(flags);
+                    block.extend(items);
+                }
+                data.extend((block.len() as i16).to_be_bytes());
+                data.extend(block);
+                data.extend((-7i16).to_be_bytes());
+                data.extend((0..7).map(|_| next() as u8));
+            }
+            data.extend([0, 0]);
+
+            let expected = reference_decompress(&data);
+            assert_eq!(lzss::decompress(&data).unwrap(), expected);
+
+            // Odd-sized reads stop in the middle of references
+            let mut decoder = lzss::Decoder::new(&data);
+            let mut output = Vec::new();
+            let mut chunk = [0u8; 7];
+            loop {
+                let read = decoder.read(&mut chunk).unwrap();
+                if read == 0 {
+                    break;
+                }
+                output.extend_from_slice(&chunk[..read]);
+            }
+            assert_eq!(output, expected);
+        }
+    }
+
+    mod malformed_input {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        /// Decoding `data` ends (in time) without a panic, within `limit`
+        fn decode_bounded(data: &[u8], limit: usize) {
+            let started = Instant::now();
+            if let Ok(output) = lzss::decompress_limited(data, limit) {
+                assert!(output.len() <= limit);
+            }
+            // Each input byte yields at most 9 output bytes
+            if let Ok(output) = lzss::decompress(data) {
+                assert!(output.len() <= 9 * data.len());
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+        }
+
+        #[test]
+        fn random_bytes() {
+            let mut next = noise(0x1234_5678);
+            for _ in 0..2000 {
+                let len = (next() % 300) as usize;
+                let data: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+                decode_bounded(&data, 1024);
+            }
+        }
+
+        #[test]
+        fn random_bytes_after_a_valid_header() {
+            // Plausible block lengths get the decoder past the header checks
+            let mut next = noise(0x9E37_79B9);
+            for _ in 0..2000 {
+                let len = (next() % 300) as usize;
+                let mut data = (len as i16).to_be_bytes().to_vec();
+                data.extend((0..len).map(|_| next() as u8));
+                decode_bounded(&data, 256);
+            }
+        }
+
+        #[test]
+        fn block_lengths_past_the_input() {
+            for header in [[0x7F, 0xFF], [0x80, 0x00], [0xFF, 0xFE], [0x00, 0x02]] {
+                let mut data = header.to_vec();
+                data.push(0xFF);
+                let err = lzss::decompress(&data).unwrap_err();
+                assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+            }
+        }
+
+        #[test]
+        fn longest_references_stop_at_the_limit() {
+            // A full-size block of 18-byte references: ~280 KB of output
+            let mut block = Vec::new();
+            while block.len() + 17 <= 0x7FFF {
+                block.push(0x00);
+                for _ in 0..8 {
+                    block.extend([0x00, 0x0F]);
+                }
+            }
+            let mut data = (block.len() as i16).to_be_bytes().to_vec();
+            data.extend(block);
+
+            let err = lzss::decompress_limited(&data, 1000).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert!(lzss::decompress(&data).unwrap().len() > 250_000);
+        }
+    }
+
+    mod compress {
+        use super::*;
+
+        #[test]
+        fn is_unsupported() {
+            let err = lzss::compress(b"hello").unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        }
+    }
+}
@@ -6,23 +6,77 @@ Supports both DAT1 (Fallout 1) and DAT2 (Fallout 2) formats.
 */
 
 use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{builder::NonEmptyStringValueParser, CommandFactory, Parser, Subcommand, ValueHint};
+use clap_complete::Shell;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // Use a faster memory allocator on Linux
 #[cfg(target_os = "linux")]
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-mod common; // Shared utilities and the main DatArchive interface
-mod dat1; // Fallout 1 DAT format implementation
-mod dat2; // Fallout 2 DAT format implementation
-mod lzss; // LZSS decompression for DAT1 files
+mod color; // Colored listings, warnings and errors (--color)
+mod completions; // Shell completion scripts
+mod config; // Per-user defaults from a config file
+mod confirm; // Confirmation prompts before risky operations (-y)
+mod logging; // Diagnostic log output (-v, RUST_LOG)
+#[macro_use]
+mod porcelain; // Tab-separated records for frontends (--porcelain)
+#[macro_use]
+mod report; // Exit codes and machine-readable command results (--json)
+mod view; // Text/hex preview of single entries
 
 #[cfg(test)]
-mod common_tests;
+mod color_tests;
+#[cfg(test)]
+mod completions_tests;
+#[cfg(test)]
+mod config_tests;
+#[cfg(test)]
+mod confirm_tests;
+#[cfg(test)]
+mod logging_tests;
+#[cfg(test)]
+mod porcelain_tests;
+#[cfg(test)]
+mod view_tests;
 
-use common::{utils, CompressionLevel, DatArchive, ExtractionMode};
+use color::ColorChoice;
+use config::{ConfigFile, Settings};
+use confirm::Confirm;
+use fallout_dat3::batch::BatchCommand;
+use fallout_dat3::bench;
+use fallout_dat3::cache::{self, CacheConfig};
+use fallout_dat3::check::{self, Severity};
+use fallout_dat3::common::{
+    self, utils,
+    utils::{AddOperand, ExtensionFilter, ListingLayout, ListingSort},
+};
+use fallout_dat3::dedupe::{find_duplicates, remove_duplicates, Keep};
+use fallout_dat3::export;
+use fallout_dat3::find::EntryQuery;
+use fallout_dat3::grep::{GrepOptions, Searcher};
+use fallout_dat3::hash::{self, HashAlgo};
+use fallout_dat3::import;
+use fallout_dat3::manifest::Manifest;
+use fallout_dat3::merge;
+use fallout_dat3::patch;
+use fallout_dat3::repair;
+use fallout_dat3::tree::DirNode;
+use fallout_dat3::usage::{self, Grouping, ListingSummary};
+use fallout_dat3::verify;
+use fallout_dat3::{
+    AddOptions, AddStats, ArchiveFormat, CompressionLevel, CompressionPolicy, DatArchive, DatError,
+    DirectoryContents, Event, ExcludeList, ExtractOptions, ExtractionMode, FileEntry, NameCase,
+    OpenOptions, Progress, SharedData,
+};
+use report::{CommandResult, ExitCategory};
+use view::TextEncoding;
 
 /// Command-line interface definition.
 /// The `clap` crate uses these derive macros to automatically parse arguments.
@@ -34,46 +88,471 @@ use common::{utils, CompressionLevel, DatArchive, ExtractionMode};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Cache decompressed entries in this directory to speed up repeated runs
+    #[arg(long, global = true, value_name = "PATH", value_hint = ValueHint::DirPath)]
+    cache_dir: Option<PathBuf>,
+
+    /// Size cap for the cache directory (e.g. 512M, 2G)
+    #[arg(long, global = true, value_name = "SIZE", default_value = "1G")]
+    cache_size: String,
+
+    /// Recompress cached entries with zstd to save disk space
+    #[arg(long, global = true)]
+    cache_zstd: bool,
+
+    /// Print a final JSON result object (status, counts, errors), even on failure
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Log more: info with -v, debug with -vv, trace with -vvv (RUST_LOG
+    /// overrides)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Print tab-separated progress, file and result records on stdout for
+    /// frontends, and everything else on stderr
+    #[arg(long, global = true, conflicts_with = "json")]
+    porcelain: bool,
+
+    /// Write the JSON result object to this file instead of stdout (implies --json)
+    #[arg(long, global = true, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    json_to: Option<PathBuf>,
+
+    /// Open DAT2 archives whose footer records the wrong size, if the tree is intact
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Fail to open archives with entries whose data lies outside the data region
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Save DAT2 names that were read with a trailing NUL with it, keeping their byte layout
+    #[arg(long, global = true)]
+    nul_names: bool,
+
+    /// Archive format to open archives as, instead of detecting it
+    #[arg(long, global = true, value_enum, default_value_t = ArchiveFormat::Auto)]
+    format: ArchiveFormat,
+
+    /// Worker threads for parallel work (default: RAYON_NUM_THREADS, or one per CPU)
+    #[arg(long, global = true, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+
+    /// Print progress messages (the default, unless the config file turns them off)
+    #[arg(long, global = true, overrides_with = "no_progress")]
+    progress: bool,
+
+    /// Don't print progress messages
+    #[arg(long, global = true, overrides_with = "progress")]
+    no_progress: bool,
+
+    /// When to color listings, warnings and errors
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Go ahead with risky operations, like deleting entries, without asking
+    #[arg(short, long, global = true)]
+    yes: bool,
+}
+
+impl Cli {
+    /// `--progress` or `--no-progress`, whichever came last
+    fn progress_flag(&self) -> Option<bool> {
+        match (self.progress, self.no_progress) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Maintenance actions for the `--cache-dir` entry cache
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Remove all cached entries
+    Clear,
 }
 
+/// Response file grammar, shown after the help of commands taking file lists
+const RESPONSE_FILE_HELP: &str = "\
+Response files: an @FILE argument is replaced by the names FILE lists, one per line.
+  name or pattern        Surrounding whitespace is dropped; globs match as on the command line
+  \"quoted name\"          Kept exactly, including whitespace, '#' and a leading '@'
+  # comment              At the start of a line or after whitespace, outside quotes
+  @other.txt             Another response file
+  @@name                 A name starting with '@'";
+
+/// `RESPONSE_FILE_HELP` with the `source => dest` lines only `a` takes
+const ADD_RESPONSE_FILE_HELP: &str = "\
+Response files: an @FILE argument is replaced by the names FILE lists, one per line.
+  name or pattern        Surrounding whitespace is dropped; globs match files on disk
+  \"quoted name\"          Kept exactly, including whitespace, '#' and a leading '@'
+  source => dest         Store the single file source under archive path dest
+  # comment              At the start of a line or after whitespace, outside quotes
+  @other.txt             Another response file
+  @@name                 A name starting with '@'";
+
+/// Commands `batch` reads from stdin
+const BATCH_HELP: &str = "\
+Commands, one per line, read from stdin:
+  list [PATTERN...]      Print the paths of matching entries, or of all
+  extract PATTERN DIR    Extract matching entries under DIR, keeping directories
+  add SOURCE [as PATH]   Add a file, or a directory with its subdirectories
+  delete PATTERN         Delete matching entries
+  save                   Write the archive; nothing is written without it
+  # comment              Blank lines and comments are skipped
+Quote arguments that contain spaces with \" or '.";
+
 /// All supported commands for working with DAT archives
 #[derive(Subcommand)]
 enum Commands {
     /// List files in a DAT archive
-    #[command(name = "l")]
+    #[command(name = "l", after_help = RESPONSE_FILE_HELP)]
     List {
+        #[arg(value_hint = ValueHint::FilePath)]
         dat_file: PathBuf,
         /// Specific files to list (if empty, lists all)
         files: Vec<String>,
+        /// Never truncate long paths to fit the terminal width
+        #[arg(long)]
+        full_paths: bool,
+        /// Show each entry's position in the archive's directory tree
+        #[arg(long)]
+        index: bool,
+        /// Order entries by this key instead of archive order
+        #[arg(long, value_enum, value_name = "KEY")]
+        sort: Option<ListingSort>,
+        /// Reverse the order: largest first with --sort, else last stored first
+        #[arg(long)]
+        reverse: bool,
+        /// Also show each entry's offset, stored type field and DAT1 directory
+        #[arg(short, long)]
+        long: bool,
+        /// Don't print the totals line under the listing
+        #[arg(long)]
+        no_summary: bool,
+        /// Group entries under a line per directory with its file count and
+        /// sizes: DAT1 directory records in tree order, DAT2 directory paths
+        #[arg(long)]
+        by_dir: bool,
+        /// Only list entries with these extensions (comma-separated, any case);
+        /// FILES then name path prefixes
+        #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+        ext: Vec<String>,
+        /// Leave out entries with these extensions (comma-separated, any case)
+        #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+        not_ext: Vec<String>,
+        /// Only list compressed entries
+        #[arg(long, conflicts_with = "stored_only")]
+        compressed_only: bool,
+        /// Only list stored (uncompressed) entries
+        #[arg(long)]
+        stored_only: bool,
+    },
+
+    /// Print the paths of entries matching filters, one per line
+    Find {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Names or glob patterns to match (if empty, matches all)
+        files: Vec<String>,
+        /// Smallest real size to match (e.g. 100k)
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<String>,
+        /// Largest real size to match (e.g. 2M)
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+        /// Only match compressed entries
+        #[arg(long, conflicts_with = "stored")]
+        compressed: bool,
+        /// Only match stored (uncompressed) entries
+        #[arg(long)]
+        stored: bool,
+        /// Only match entries under this archive directory
+        #[arg(long, value_name = "DIR")]
+        dir: Option<String>,
+        /// End each path with a NUL byte instead of a newline
+        #[arg(short = '0', long)]
+        null: bool,
+    },
+
+    /// Show the directory hierarchy with file counts and sizes
+    Tree {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Show at most this many levels of subdirectories
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+    },
+
+    /// Summarize real and packed sizes by directory or extension
+    Du {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Specific files to count (if empty, counts all)
+        files: Vec<String>,
+        /// Group by this many levels of directories
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        depth: usize,
+        /// Group by file extension instead of directory
+        #[arg(long, conflicts_with = "depth")]
+        by_ext: bool,
     },
 
     /// Extract files preserving directory structure
-    #[command(name = "x")]
+    #[command(name = "x", after_help = RESPONSE_FILE_HELP)]
     Extract {
+        #[arg(value_hint = ValueHint::FilePath)]
         dat_file: PathBuf,
-        #[arg(short, long)]
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
         output: Option<PathBuf>,
         files: Vec<String>,
+        /// Report entries that fail to extract and continue with the rest
+        #[arg(long)]
+        keep_going: bool,
+        /// Don't fail if a requested file matches no entries
+        #[arg(long)]
+        ignore_missing: bool,
+        /// Extract in directory-tree order instead of archive offset order
+        #[arg(long)]
+        tree_order: bool,
+        /// Write the stored bytes of entries with an unknown compression type
+        #[arg(long)]
+        force_raw: bool,
+        /// Most decompressed data held in memory at once, e.g. 512M
+        /// (default: 64M per worker thread)
+        #[arg(long, value_name = "SIZE")]
+        max_memory: Option<String>,
+        /// Remove this leading directory from extracted paths; every extracted
+        /// entry must be under it (without files, extracts everything under it)
+        #[arg(long, value_name = "DIR")]
+        strip_prefix: Option<String>,
+        /// Remove this many leading directories from extracted paths, after
+        /// --strip-prefix; entries with nothing left are skipped
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        strip_components: usize,
+        /// Write all file and directory names in lower case
+        #[arg(long)]
+        lowercase: bool,
+        /// Leave files that already exist with the entry's size as they are
+        #[arg(long)]
+        update: bool,
+        /// With --update, also compare the files' contents with the entries'
+        #[arg(long, requires = "update")]
+        checksum: bool,
+        /// Modification time of written files: now, archive (the archive
+        /// file's), or a time like 2024-06-01T12:00:00Z
+        #[arg(long, value_name = "WHEN", default_value = "now")]
+        mtime: String,
+        /// Write entries' stored bytes without decompressing them; compressed
+        /// ones get a .z (DAT2) or .lzss (DAT1) suffix
+        #[arg(long)]
+        raw: bool,
+        /// With --raw, keep compressed entries' names without a suffix
+        #[arg(long, requires = "raw")]
+        raw_keep_names: bool,
     },
 
     /// Extract files flat (no subdirectories)
-    #[command(name = "e")]
+    #[command(name = "e", after_help = RESPONSE_FILE_HELP)]
     ExtractFlat {
+        #[arg(value_hint = ValueHint::FilePath)]
         dat_file: PathBuf,
-        #[arg(short, long)]
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+        files: Vec<String>,
+        /// Report entries that fail to extract and continue with the rest
+        #[arg(long)]
+        keep_going: bool,
+        /// Don't fail if a requested file matches no entries
+        #[arg(long)]
+        ignore_missing: bool,
+        /// Extract in directory-tree order instead of archive offset order
+        #[arg(long)]
+        tree_order: bool,
+        /// Write the stored bytes of entries with an unknown compression type
+        #[arg(long)]
+        force_raw: bool,
+        /// Most decompressed data held in memory at once, e.g. 512M
+        /// (default: 64M per worker thread)
+        #[arg(long, value_name = "SIZE")]
+        max_memory: Option<String>,
+        /// Write all file names in lower case
+        #[arg(long)]
+        lowercase: bool,
+        /// Leave files that already exist with the entry's size as they are
+        #[arg(long)]
+        update: bool,
+        /// With --update, also compare the files' contents with the entries'
+        #[arg(long, requires = "update")]
+        checksum: bool,
+        /// Modification time of written files: now, archive (the archive
+        /// file's), or a time like 2024-06-01T12:00:00Z
+        #[arg(long, value_name = "WHEN", default_value = "now")]
+        mtime: String,
+        /// Write entries' stored bytes without decompressing them; compressed
+        /// ones get a .z (DAT2) or .lzss (DAT1) suffix
+        #[arg(long)]
+        raw: bool,
+        /// With --raw, keep compressed entries' names without a suffix
+        #[arg(long, requires = "raw")]
+        raw_keep_names: bool,
+    },
+
+    /// Write entries into a zip file, for tools that don't read DAT archives
+    #[command(after_help = RESPONSE_FILE_HELP)]
+    Export {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Zip file to write (not given with --tar)
+        #[arg(required_unless_present = "tar", value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
+        /// Names or glob patterns of the entries to export (default: all)
         files: Vec<String>,
+        /// Write a tar stream to this file instead of a zip, or to stdout with -
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["compression", "store_ext", "no_default_store_ext", "min_gain"],
+            value_hint = ValueHint::FilePath
+        )]
+        tar: Option<PathBuf>,
+        /// Deflate level 0-9; 0 stores every file
+        #[arg(short, long)]
+        compression: Option<u8>,
+        /// Extra extensions to always store uncompressed (comma-separated, e.g. acm,mve,wav)
+        #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+        store_ext: Vec<String>,
+        /// Don't store the built-in extensions (acm, mve) uncompressed
+        #[arg(long)]
+        no_default_store_ext: bool,
+        /// Only deflate a file if it saves at least this percent of the size
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+        min_gain: f64,
+        /// Replace the zip file if it exists
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Add the files of a zip file to a DAT archive
+    Import {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Zip file to read
+        #[arg(value_hint = ValueHint::FilePath)]
+        zip_file: PathBuf,
+        /// Target directory inside the archive
+        #[arg(short, long)]
+        target_dir: Option<String>,
+        /// Compression level 0-9
+        #[arg(short, long)]
+        compression: Option<u8>,
+        /// Force DAT1 format for new archives
+        #[arg(long)]
+        dat1: bool,
+        /// Extra extensions to always store uncompressed (comma-separated, e.g. acm,mve,wav)
+        #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+        store_ext: Vec<String>,
+        /// Don't store the built-in extensions (acm, mve) uncompressed
+        #[arg(long)]
+        no_default_store_ext: bool,
+        /// Only keep the compressed form if it saves at least this percent of the size
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+        min_gain: f64,
+        /// Keep the existing entry order instead of re-sorting; new entries go last
+        #[arg(long)]
+        keep_order: bool,
+        /// Store archive paths of added files in this case
+        #[arg(long, value_enum, default_value_t = NameCase::Preserve)]
+        case: NameCase,
+        /// Store archive paths of added files in lower case (same as --case lower)
+        #[arg(long, conflicts_with = "case")]
+        lowercase: bool,
+        /// Add files even if their paths differ from an entry's only by case
+        #[arg(long)]
+        allow_case_dupes: bool,
+        /// Also leave out files whose name matches this pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Add .DS_Store, ._*, Thumbs.db and desktop.ini files too
+        #[arg(long)]
+        no_default_excludes: bool,
+        /// Keep the archive as it was as ARCHIVE.bak, or with another suffix
+        /// (`--backup=.old`)
+        #[arg(
+            long,
+            value_name = "SUFFIX",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ".bak",
+            value_parser = NonEmptyStringValueParser::new()
+        )]
+        backup: Option<String>,
+    },
+
+    /// Run commands read from stdin against an archive opened once
+    #[command(after_help = BATCH_HELP)]
+    Batch {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Stop at the first command that fails
+        #[arg(long)]
+        fail_fast: bool,
+        /// Compression level 0-9 of added files
+        #[arg(short, long)]
+        compression: Option<u8>,
+    },
+
+    /// Create a new archive of a directory's contents
+    #[command(name = "c")]
+    Create {
+        /// Archive to create
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Directory whose contents become the archive root
+        #[arg(value_hint = ValueHint::DirPath)]
+        source: PathBuf,
+        /// Compression level 0-9
+        #[arg(short, long)]
+        compression: Option<u8>,
+        /// Create a DAT1 archive
+        #[arg(long)]
+        dat1: bool,
+        /// Replace the archive if it already exists
+        #[arg(long)]
+        overwrite: bool,
+        /// Store archive paths in lower case
+        #[arg(long)]
+        lowercase: bool,
+        /// Store identical file contents once, with every entry that has them
+        /// pointing at the one copy (DAT2 only)
+        #[arg(long)]
+        dedup: bool,
+        /// Also leave out files whose name matches this pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Add .DS_Store, ._*, Thumbs.db and desktop.ini files too
+        #[arg(long)]
+        no_default_excludes: bool,
+        /// Don't read .dat3ignore files of the walked directories
+        #[arg(long)]
+        no_ignore_files: bool,
     },
 
     /// Add files to a DAT archive
-    #[command(name = "a")]
+    #[command(name = "a", after_help = ADD_RESPONSE_FILE_HELP)]
     Add {
+        #[arg(value_hint = ValueHint::FilePath)]
         dat_file: PathBuf,
         /// Resolve add operands relative to this directory
-        #[arg(short = 'C', long = "change-dir", value_name = "DIR")]
+        #[arg(short = 'C', long = "change-dir", value_name = "DIR", value_hint = ValueHint::DirPath)]
         change_dir: Option<PathBuf>,
         /// Files or directories to add
         files: Vec<PathBuf>,
+        /// Include subdirectories of directory operands
+        #[arg(short, long)]
+        recursive: bool,
         /// Compression level 0-9
         #[arg(short, long)]
         compression: Option<u8>,
@@ -83,135 +562,2237 @@ enum Commands {
         /// Target directory inside the archive
         #[arg(short, long)]
         target_dir: Option<String>,
+        /// Also create this empty directory, kept until deleted (DAT1 only, repeatable)
+        #[arg(long, value_name = "DIR")]
+        mkdir: Vec<String>,
+        /// Extra extensions to always store uncompressed (comma-separated, e.g. acm,mve,wav)
+        #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+        store_ext: Vec<String>,
+        /// Don't store the built-in extensions (acm, mve) uncompressed
+        #[arg(long)]
+        no_default_store_ext: bool,
+        /// Only keep the compressed form if it saves at least this percent of the size
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+        min_gain: f64,
+        /// Keep the existing entry order instead of re-sorting; new entries go last
+        #[arg(long)]
+        keep_order: bool,
+        /// Store archive paths of added files in this case
+        #[arg(long, value_enum, default_value_t = NameCase::Preserve)]
+        case: NameCase,
+        /// Store archive paths of added files in lower case (same as --case lower)
+        #[arg(long, conflicts_with = "case")]
+        lowercase: bool,
+        /// Also change the case of entries already in the archive
+        #[arg(long)]
+        rename_existing: bool,
+        /// Add files even if their paths differ from an entry's only by case
+        #[arg(long)]
+        allow_case_dupes: bool,
+        /// Also leave out files whose name matches this pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Add .DS_Store, ._*, Thumbs.db and desktop.ini files too
+        #[arg(long)]
+        no_default_excludes: bool,
+        /// Don't read .dat3ignore files of the walked directories
+        #[arg(long)]
+        no_ignore_files: bool,
+        /// Only add files modified after this UTC time (YYYY-MM-DD[THH:MM[:SS]])
+        #[arg(long, value_name = "TIME")]
+        newer_than: Option<String>,
+        /// Only add files modified after this file was
+        #[arg(long, value_name = "FILE", conflicts_with = "newer_than", value_hint = ValueHint::FilePath)]
+        newer_than_file: Option<PathBuf>,
+        /// Append new data to an existing DAT2 archive instead of rewriting it
+        /// (falls back to a rewrite if entries were replaced)
+        #[arg(long, conflicts_with = "dedup")]
+        fast_append: bool,
+        /// Store identical file contents once, with every entry that has them
+        /// pointing at the one copy (DAT2 only)
+        #[arg(long)]
+        dedup: bool,
+        /// Keep the archive as it was as ARCHIVE.bak, or with another suffix
+        /// (`--backup=.old`)
+        #[arg(
+            long,
+            value_name = "SUFFIX",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ".bak",
+            value_parser = NonEmptyStringValueParser::new()
+        )]
+        backup: Option<String>,
+        /// Build a new archive of exactly the files listed in this manifest
+        #[arg(
+            long,
+            value_name = "MANIFEST",
+            conflicts_with_all = ["files", "change_dir", "recursive", "dat1", "target_dir", "keep_order", "rename_existing", "allow_case_dupes", "newer_than", "newer_than_file", "fast_append", "backup", "dedup"],
+            value_hint = ValueHint::FilePath
+        )]
+        from_manifest: Option<PathBuf>,
+        /// Directory the manifest's files are read from (default: current directory)
+        #[arg(long, value_name = "DIR", requires = "from_manifest", value_hint = ValueHint::DirPath)]
+        root: Option<PathBuf>,
+    },
+
+    /// Show one entry as text (or a hex dump if it's binary)
+    View {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Entry to show
+        file: String,
+        /// Text encoding of the entry
+        #[arg(long, value_enum, default_value_t = TextEncoding::Auto)]
+        encoding: TextEncoding,
+        /// Write the entry's bytes unchanged
+        #[arg(long)]
+        raw: bool,
+        /// Print directly instead of through $PAGER
+        #[arg(long)]
+        no_pager: bool,
     },
 
     /// Delete files from a DAT archive
-    #[command(name = "d")]
+    #[command(name = "d", after_help = RESPONSE_FILE_HELP)]
     Delete {
+        #[arg(value_hint = ValueHint::FilePath)]
         dat_file: PathBuf,
+        /// Names or glob patterns of entries to delete; a name ending in `/`
+        /// deletes everything under that directory
         files: Vec<String>,
+        /// Allow `/`, which deletes every entry
+        #[arg(long)]
+        force: bool,
+        /// Delete the names that exist and report the rest, failing only if
+        /// nothing was deleted
+        #[arg(long)]
+        ignore_missing: bool,
+        /// Keep DAT1 directory records emptied by deleting a directory
+        #[arg(long)]
+        keep_empty_dirs: bool,
+        /// Remove every DAT1 directory record left without files, not only
+        /// those under deleted directories
+        #[arg(long, conflicts_with = "keep_empty_dirs")]
+        prune_empty_dirs: bool,
+        /// Rewrite the whole DAT2 archive to drop the deleted data, instead of
+        /// only rewriting its directory tree
+        #[arg(long)]
+        compact: bool,
+        /// Write the new DAT2 directory tree over the old one in the file
+        /// itself instead of replacing the file; faster on large archives, but
+        /// an interrupted save can leave the archive without a valid tree
+        #[arg(long, conflicts_with = "compact")]
+        in_place: bool,
+        /// Keep the archive as it was as ARCHIVE.bak, or with another suffix
+        /// (`--backup=.old`)
+        #[arg(
+            long,
+            value_name = "SUFFIX",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ".bak",
+            value_parser = NonEmptyStringValueParser::new()
+        )]
+        backup: Option<String>,
     },
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+    /// Rewrite an archive in canonical, byte-reproducible form
+    Repack {
+        /// Archive to read
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// Archive to write (may be the same as the input)
+        #[arg(value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// Compression level 0-9
+        #[arg(short, long)]
+        compression: Option<u8>,
+        /// Don't store the built-in extensions (acm, mve) uncompressed
+        #[arg(long)]
+        no_default_store_ext: bool,
+    },
 
-    match cli.command {
-        Commands::List { dat_file, files } => {
-            let archive = DatArchive::open(&dat_file)?;
-            let patterns = utils::expand_response_files_for_archive(&files)?;
-            archive.list(&patterns)?;
-        }
+    /// Merge archives into one; later archives override earlier ones
+    Merge {
+        /// Archives to merge, in order
+        #[arg(required_unless_present = "sequence", conflicts_with = "sequence", value_hint = ValueHint::FilePath)]
+        inputs: Vec<PathBuf>,
+        /// Archive to write
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// Glob of split archive parts, merged in natural order (part2 before part10)
+        #[arg(long)]
+        sequence: Option<String>,
+        /// Report how many entries each part overrides from earlier parts
+        #[arg(long)]
+        check_overlap: bool,
+        /// Compression level 0-9
+        #[arg(short, long)]
+        compression: Option<u8>,
+    },
 
-        Commands::Extract {
-            dat_file,
-            output,
-            files,
-        } => {
-            let archive = DatArchive::open(&dat_file)?;
-            let output_dir = output.unwrap_or_else(|| PathBuf::from(".")); // default: current directory
-            let patterns = utils::expand_response_files_for_archive(&files)?;
-            archive.extract(&output_dir, &patterns, ExtractionMode::PreserveStructure)?;
-        }
+    /// Build a mod directory into the game's next free patchNNN.dat
+    Patch {
+        /// Mod directory; its contents go to the archive root
+        #[arg(value_hint = ValueHint::DirPath)]
+        source: PathBuf,
+        /// Game directory to scan for patch archives and write the new one to
+        #[arg(long, value_name = "DIR", default_value = ".", value_hint = ValueHint::DirPath)]
+        game_dir: PathBuf,
+        /// Patch number to write instead of the first free one
+        #[arg(long, value_name = "N")]
+        number: Option<u32>,
+        /// Print the chosen file name and the files, without writing
+        #[arg(long)]
+        dry_run: bool,
+        /// Compression level 0-9
+        #[arg(short, long, default_value_t = 9)]
+        compression: u8,
+    },
 
-        Commands::ExtractFlat {
-            dat_file,
-            output,
-            files,
-        } => {
-            let archive = DatArchive::open(&dat_file)?;
-            let output_dir = output.unwrap_or_else(|| PathBuf::from(".")); // default: current directory
-            let patterns = utils::expand_response_files_for_archive(&files)?;
-            archive.extract(&output_dir, &patterns, ExtractionMode::Flat)?;
-        }
+    /// Drop data no entry references (left behind by some editors' deletes)
+    Optimize {
+        /// DAT file to optimize in place
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Only report unreferenced data, don't rewrite the archive
+        #[arg(long)]
+        dry_run: bool,
+        /// Also remove DAT1 directory records without files
+        #[arg(long, conflicts_with = "dry_run")]
+        prune_empty_dirs: bool,
+    },
 
-        Commands::Add {
-            dat_file,
-            files,
-            change_dir,
-            compression,
-            dat1,
-            target_dir,
-        } => {
-            // Track if the user explicitly set compression (for the DAT1 warning below)
-            let compression_explicitly_set = compression.is_some();
-            let compression = compression.unwrap_or(1); // default: level 1
-            let compression_level = CompressionLevel::new(compression)?;
+    /// Keep one entry of each name that has several, dropping the others
+    Dedupe {
+        /// DAT file to rewrite in place
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Which entry of a duplicated name stays, in directory tree order
+        #[arg(long, value_enum, default_value_t = Keep::Last)]
+        keep: Keep,
+        /// Only report duplicated names, don't rewrite the archive
+        #[arg(long)]
+        dry_run: bool,
+        /// Also remove DAT1 directory records left without files
+        #[arg(long, conflicts_with = "dry_run")]
+        prune_empty_dirs: bool,
+    },
 
-            let change_dir = match change_dir {
-                Some(path) => {
-                    let resolved = std::fs::canonicalize(&path).with_context(|| {
-                        format!("Failed to resolve -C directory: {}", path.display())
-                    })?;
-                    if !resolved.is_dir() {
-                        bail!("-C must point to a directory: {}", path.display());
-                    }
-                    Some(resolved)
-                }
-                None => None,
-            };
+    /// Print the lines of entries that contain a string
+    Grep {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// String to search for (a regular expression with --regex)
+        pattern: String,
+        /// Only search entries matching this pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        include: Vec<String>,
+        /// Skip entries matching this pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Treat the search string as a regular expression
+        #[arg(short = 'E', long)]
+        regex: bool,
+        /// Ignore case when matching
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        /// Print only the names of entries with matches
+        #[arg(short = 'l', long)]
+        files_with_matches: bool,
+        /// Also search entries that look binary
+        #[arg(long)]
+        binary: bool,
+    },
 
-            // Expand @response files and glob patterns
-            let file_strings: Vec<String> = files
-                .iter()
-                .map(|p| p.to_string_lossy().to_string())
-                .collect();
-            let expanded =
-                utils::expand_response_files_with_stripping(&file_strings, change_dir.as_deref())?;
-            let expanded: Vec<PathBuf> = expanded
-                .iter()
-                .map(|path| utils::resolve_add_input_path(path, change_dir.as_deref()))
-                .collect::<Result<_>>()?;
+    /// Print a checksum of each entry's content, or check them against a manifest
+    Hash {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Specific files to hash (if empty, hashes all)
+        files: Vec<String>,
+        /// Checksum algorithm
+        #[arg(long, value_enum, default_value_t = HashAlgo::Sha256)]
+        algo: HashAlgo,
+        /// Verify the archive against a manifest printed by `hash` or sha256sum
+        #[arg(long, value_name = "MANIFEST", value_hint = ValueHint::FilePath)]
+        check: Option<PathBuf>,
+    },
 
-            // Count files upfront - fails immediately if any path doesn't exist
-            let mut total_files_to_add = 0;
-            for file_path in &expanded {
-                let collected_files = utils::collect_files(file_path)?;
-                total_files_to_add += collected_files.len();
-            }
+    /// Show which of several archives supplies each path, first archive first
+    Which {
+        /// Archives in priority order, e.g. patch000.dat master.dat critter.dat
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        archives: Vec<PathBuf>,
+        /// Entry paths to resolve, after `--`
+        #[arg(last = true, required = true)]
+        paths: Vec<String>,
+        /// List every archive that has the path, not just the one used
+        #[arg(long)]
+        all: bool,
+    },
 
-            if total_files_to_add == 0 {
-                bail!("No files to add to archive");
-            }
+    /// Print a manifest of entry hashes and sizes, for `a --from-manifest`
+    Manifest { dat_file: PathBuf },
 
-            let mut archive = if dat_file.exists() {
-                // Open existing archive - format is fixed, can't change it
-                let archive = DatArchive::open(&dat_file)?;
-                if dat1 && !archive.is_dat1() {
-                    bail!("Error: {} is a DAT2 archive, but --dat1 flag was specified. Cannot change archive format.", dat_file.display());
-                }
-                archive
-            } else if dat1 {
-                DatArchive::new_dat1() // Fallout 1 format
-            } else {
-                DatArchive::new_dat2() // Fallout 2 format (default)
-            };
+    /// Compare an archive's entries with the files in a directory
+    Verify {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Directory to compare against
+        #[arg(value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// Don't report files on disk that aren't in the archive
+        #[arg(long)]
+        ignore_extra: bool,
+    },
 
-            if archive.is_dat1() && compression_explicitly_set && compression > 0 {
-                eprintln!("Warning: DAT1 format does not support compression, files will be stored uncompressed");
-            }
+    /// Report footer, ordering, naming and data layout problems in an archive
+    Check {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Also decompress every entry
+        #[arg(long)]
+        deep: bool,
+        /// Fail if any problem is at least this severe
+        #[arg(long, value_enum, default_value_t = Severity::Warning)]
+        severity: Severity,
+    },
 
-            for file_path in expanded {
-                archive.add_file(
-                    &file_path,
-                    compression_level,
-                    target_dir.as_deref(),
-                    change_dir.as_deref(),
-                )?;
-            }
+    /// Time decompression, on growing thread counts, and recompression at given levels
+    Bench {
+        #[arg(value_hint = ValueHint::FilePath)]
+        dat_file: PathBuf,
+        /// Also time recompressing the contents at these zlib levels (0-9)
+        #[arg(long, value_name = "N", value_delimiter = ',')]
+        compress_level: Vec<u8>,
+        /// Keep the fastest of this many runs of each measurement
+        #[arg(long, default_value_t = NonZeroUsize::new(3).unwrap())]
+        rounds: NonZeroUsize,
+    },
+
+    /// Recover the intact entries of a damaged DAT2 archive into a new one
+    Repair {
+        /// Damaged DAT file, which is only read
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// Where to write the recovered archive
+        #[arg(value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+    },
+
+    /// Manage the decompressed entry cache (requires --cache-dir)
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
 
-            archive.save(&dat_file)?;
+    /// Print where the config file is read from, or with --show the effective settings
+    Config {
+        /// Print each setting, commented with where its value came from
+        #[arg(long)]
+        show: bool,
+    },
+
+    /// Print a shell completion script (e.g. `dat3 completions bash > /etc/bash_completion.d/dat3`)
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+impl Commands {
+    /// Command name as typed on the command line
+    fn name(&self) -> &'static str {
+        match self {
+            Self::List { .. } => "l",
+            Self::Find { .. } => "find",
+            Self::Tree { .. } => "tree",
+            Self::Du { .. } => "du",
+            Self::Extract { .. } => "x",
+            Self::ExtractFlat { .. } => "e",
+            Self::View { .. } => "view",
+            Self::Export { .. } => "export",
+            Self::Import { .. } => "import",
+            Self::Batch { .. } => "batch",
+            Self::Create { .. } => "c",
+            Self::Add { .. } => "a",
+            Self::Delete { .. } => "d",
+            Self::Repack { .. } => "repack",
+            Self::Merge { .. } => "merge",
+            Self::Patch { .. } => "patch",
+            Self::Optimize { .. } => "optimize",
+            Self::Dedupe { .. } => "dedupe",
+            Self::Grep { .. } => "grep",
+            Self::Hash { .. } => "hash",
+            Self::Verify { .. } => "verify",
+            Self::Check { .. } => "check",
+            Self::Bench { .. } => "bench",
+            Self::Manifest { .. } => "manifest",
+            Self::Which { .. } => "which",
+            Self::Repair { .. } => "repair",
+            Self::Cache { .. } => "cache",
+            Self::Config { .. } => "config",
+            Self::Completions { .. } => "completions",
         }
+    }
+}
 
-        Commands::Delete { dat_file, files } => {
-            let mut archive = DatArchive::open(&dat_file)?;
-            let patterns = utils::expand_response_files_for_archive(&files)?;
+/// Remove every DAT1 directory record other than the root that holds no
+/// files (`--prune-empty-dirs`), saying how many went
+fn prune_empty_directories(archive: &mut DatArchive) -> usize {
+    if !archive.is_dat1() {
+        return 0;
+    }
+    let pruned = archive.remove_empty_directories("/");
+    print_stdout(format_args!("Pruned {pruned} empty directories"));
+    pruned
+}
 
-            for pattern in patterns {
-                archive.delete_file(&pattern)?;
+/// The files `c` and `a` leave out: the built-in list unless
+/// `--no-default-excludes`, every `--exclude` pattern, and what ignore files
+/// list unless `--no-ignore-files`
+fn exclude_list(
+    no_default_excludes: bool,
+    no_ignore_files: bool,
+    exclude: &[String],
+) -> Result<ExcludeList> {
+    let excludes = if no_default_excludes {
+        ExcludeList::empty()
+    } else {
+        ExcludeList::new()
+    };
+    let mut excludes = excludes.with_patterns(exclude)?;
+    excludes.ignore_files = !no_ignore_files;
+    Ok(excludes)
+}
+
+/// Whether `error` comes from writing to a closed pipe (e.g., piped to `head`)
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Run one line of `dat3 batch` against `archive`, opened from `dat_file`.
+/// Returns whether the archive now has unsaved changes, or None if the
+/// command didn't touch it.
+fn run_batch_command(
+    archive: &mut DatArchive,
+    command: BatchCommand,
+    dat_file: &Path,
+    add_options: &AddOptions,
+    progress: &Progress,
+) -> Result<Option<bool>> {
+    match command {
+        BatchCommand::List { patterns } => {
+            let (entries, missing) = archive.find_entries(&patterns);
+            common::report_missing_patterns(&missing, progress)?;
+            for file in &entries {
+                print_stdout(format_args!("{}", file.path()));
             }
+            Progress::add(&progress.listed, entries.len());
+            Ok(None)
+        }
+        BatchCommand::Extract { pattern, dest } => {
+            let options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+            archive.extract(&dest, &[pattern], &options, progress)?;
+            Ok(None)
+        }
+        BatchCommand::Add { source, dest } => {
+            let options = AddOptions {
+                dest,
+                ..add_options.clone()
+            };
+            let stats = archive.add_file(&source, &options, progress)?;
+            Progress::add(&progress.added, stats.files_added);
+            out!("{stats}");
+            Ok(Some(true))
+        }
+        BatchCommand::Delete { pattern } => {
+            let plan = archive.plan_delete(&[pattern]);
+            common::report_missing_patterns(&plan.missing(), progress)?;
+            for name in &plan.names {
+                archive.delete_file(name, progress)?;
+            }
+            Progress::add(&progress.deleted, plan.names.len());
+            Ok(Some(true))
+        }
+        BatchCommand::Save => {
+            archive.save(dat_file)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            print_stdout(format_args!("Saved {}", dat_file.display()));
+            Ok(Some(false))
+        }
+    }
+}
+
+/// Fail if adding made two entries whose names differ only by case
+/// (`--allow-case-dupes` skips this)
+fn reject_case_dupes(archive: &DatArchive) -> Result<()> {
+    // Only pairs this add makes; same-named entries are replaced
+    if let Some(collision) = find_duplicates(archive, Keep::Last)
+        .iter()
+        .find(|duplicate| duplicate.has_added() && duplicate.differs_by_case())
+    {
+        bail!(
+            "{} would differ only by case, and one overwrites the other when \
+             extracted on case-insensitive file systems (use --allow-case-dupes \
+             to add anyway)",
+            collision.spellings().join(" and ")
+        );
+    }
+    Ok(())
+}
+
+/// The time `--mtime` gives extracted files: None for `now`, the archive
+/// file's modification time for `archive` (the outer file's, for a nested
+/// archive), or a literal time
+fn extract_mtime(mtime: &str, dat_file: &Path) -> Result<Option<SystemTime>> {
+    match mtime {
+        "now" => Ok(None),
+        "archive" => {
+            let (path, _) = utils::split_nested_archive_path(dat_file)?;
+            std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map(Some)
+                .with_context(|| {
+                    format!("Failed to read the modification time of {}", path.display())
+                })
+        }
+        time => utils::parse_timestamp(time)
+            .map(Some)
+            .context("--mtime takes now, archive or a time"),
+    }
+}
+
+/// Write to stdout (stderr under `--porcelain`), exiting cleanly on broken
+/// pipe (e.g., when piped to `head`)
+fn print_stdout(args: std::fmt::Arguments) {
+    let written = if porcelain::enabled() {
+        writeln!(anstream::stderr(), "{args}")
+    } else {
+        writeln!(anstream::stdout(), "{args}")
+    };
+    if written.is_err() {
+        std::process::exit(0);
+    }
+}
+
+/// Terminal width in columns, or None when stdout is not a terminal
+fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize)
+}
+
+/// Print formatted file listing to stdout.
+/// Exits cleanly on broken pipe (e.g., when piped to `head`).
+///
+/// `width` is the terminal width to fit the table into; None prints the
+/// full table without truncation. `summary` is printed under the table.
+fn print_file_listing(
+    files: &[&FileEntry],
+    width: Option<usize>,
+    show_index: bool,
+    verbose: bool,
+    summary: Option<&ListingSummary>,
+) {
+    let longest_name = files
+        .iter()
+        .map(|f| f.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let layout = listing_layout(width, longest_name, show_index, verbose);
+
+    print_stdout(format_args!("{}", layout.header()));
+    print_stdout(format_args!("{}", layout.rule()));
+
+    for file in files {
+        print_stdout(format_args!(
+            "{}",
+            layout.styled_row(file, color::paint_row_part)
+        ));
+    }
+
+    if let Some(summary) = summary {
+        print_stdout(format_args!("{}", layout.rule()));
+        print_stdout(format_args!("{summary}"));
+    }
+}
+
+/// The table layout for `l`, fitted to names of `longest_name` characters
+fn listing_layout(
+    width: Option<usize>,
+    longest_name: usize,
+    show_index: bool,
+    verbose: bool,
+) -> ListingLayout {
+    if verbose {
+        ListingLayout::verbose(width, longest_name, show_index)
+    } else if show_index {
+        ListingLayout::with_index(width, longest_name)
+    } else {
+        ListingLayout::for_width(width, longest_name)
+    }
+}
+
+/// Print `l --by-dir`: the table with each directory's entries under a line
+/// naming it with its file count and sizes, their names relative to it
+fn print_grouped_listing(
+    groups: &[usage::DirectoryGroup],
+    width: Option<usize>,
+    show_index: bool,
+    verbose: bool,
+    summary: Option<&ListingSummary>,
+) {
+    let named: Vec<(&usage::DirectoryGroup, Vec<String>)> = groups
+        .iter()
+        .map(|group| {
+            let names = group
+                .files
+                .iter()
+                .map(|file| format!("  {}", name_in_directory(&file.path(), &group.usage.name)))
+                .collect();
+            (group, names)
+        })
+        .collect();
+    let longest_name = named
+        .iter()
+        .flat_map(|(_, names)| names.iter().map(|name| name.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let layout = listing_layout(width, longest_name, show_index, verbose);
+
+    print_stdout(format_args!("{}", layout.header()));
+    print_stdout(format_args!("{}", layout.rule()));
+
+    for (group, names) in &named {
+        let usage = &group.usage;
+        let noun = if usage.files == 1 { "file" } else { "files" };
+        print_stdout(format_args!(
+            "{}  {} {noun}, {} bytes, {} packed",
+            color::paint_row_part(utils::RowPart::Directory, &usage.name),
+            usage::group_thousands(usage.files as u64),
+            usage::group_thousands(usage.size),
+            usage::group_thousands(usage.packed)
+        ));
+        for (file, name) in group.files.iter().zip(names) {
+            print_stdout(format_args!(
+                "{}",
+                layout.styled_row_named(file, name, color::paint_row_part)
+            ));
+        }
+    }
+
+    if let Some(summary) = summary {
+        print_stdout(format_args!("{}", layout.rule()));
+        print_stdout(format_args!("{summary}"));
+    }
+}
+
+/// `path` relative to the `du`-style directory group `dir` (`art/critters/`
+/// or `.`), or all of it if it isn't under it
+fn name_in_directory<'a>(path: &'a str, dir: &str) -> &'a str {
+    match path.get(..dir.len()) {
+        Some(prefix) if dir != usage::ROOT_GROUP && prefix.eq_ignore_ascii_case(dir) => {
+            &path[dir.len()..]
+        }
+        _ => path,
+    }
+}
+
+/// Size and compression of an entry, as `which` shows them
+fn describe_entry(entry: &FileEntry) -> String {
+    if entry.compressed {
+        format!("{} bytes, compressed to {}", entry.size, entry.packed_size)
+    } else {
+        format!("{} bytes, stored", entry.size)
+    }
+}
+
+/// Say after a `--raw` extraction that compressed entries were written as
+/// stored, so nobody mistakes them for usable files
+fn warn_raw_extraction(archive: &DatArchive, keep_names: bool) {
+    let (format, suffix) = if archive.is_dat1() {
+        ("LZSS", ".lzss")
+    } else {
+        ("zlib", ".z")
+    };
+    let named = if keep_names {
+        "under their own names".to_string()
+    } else {
+        format!("with a {suffix} suffix")
+    };
+    color::warning(format_args!(
+        "Raw mode: compressed entries were written as stored {format} data {named}, \
+         not as usable game files"
+    ));
+}
+
+/// Print what an extraction left out, if anything
+fn print_extract_skips(written: usize, skipped: usize, current: usize) {
+    if skipped > 0 {
+        outln!("Skipped {skipped} entries with too few path components");
+    }
+    if current > 0 {
+        outln!("Wrote {written} files, skipped {current} already up to date");
+    }
+}
+
+/// Console output for library events; progress messages only if `show_progress`
+fn print_event(event: Event<'_>, show_progress: bool) {
+    match event {
+        Event::ExtractStarted { .. }
+        | Event::Extracting { .. }
+        | Event::Extracted { .. }
+        | Event::Adding { .. }
+        | Event::Deleting { .. }
+            if !show_progress => {}
+        Event::ExtractFinished {
+            written,
+            skipped,
+            current,
+            ..
+        } if !show_progress => print_extract_skips(written, skipped, current),
+        // Only for --porcelain records; the other events say it for people
+        Event::EntryExtracted { .. } | Event::AddStarted { .. } | Event::Added { .. } => {}
+        Event::ExtractStarted { total } => outln!("Extracting {total} files..."),
+        Event::Extracting { name } => {
+            outln!("Extracting: {}", utils::canonical_entry_path(name));
+        }
+        Event::Extracted {
+            count,
+            total,
+            elapsed,
+        } => {
+            let files_per_sec = count as f64 / elapsed.as_millis() as f64 * 1000.0;
+            outln!("Progress: {count}/{total} files extracted ({files_per_sec:.1} files/sec)");
+        }
+        Event::ExtractFinished {
+            elapsed,
+            written,
+            skipped,
+            current,
+        } => {
+            outln!("Extraction completed in {:.2}s", elapsed.as_secs_f64());
+            print_extract_skips(written, skipped, current);
+        }
+        Event::EntryFailed { error } => color::error(format_args!("{error:#}")),
+        Event::SkippedShallow {
+            name,
+            strip_components,
+        } => color::warning(format_args!(
+            "Skipping {}: nothing is left after stripping {strip_components} components",
+            utils::canonical_entry_path(name)
+        )),
+        Event::SkippedDuplicateName { name, kept } => color::warning(format_args!(
+            "Skipping an earlier entry named {}; {} is extracted instead",
+            utils::canonical_entry_path(name),
+            utils::canonical_entry_path(kept)
+        )),
+        Event::TimeNotSet { path, error } => color::warning(format_args!(
+            "Failed to set the modification time of {}: {error}",
+            path.display()
+        )),
+        Event::NotFound { patterns } => {
+            eprintln!("\nFiles not found:");
+            for pattern in patterns {
+                eprintln!("  {}", utils::canonical_entry_path(pattern));
+            }
+        }
+        Event::Adding { name } => {
+            outln!("Adding: {}", utils::canonical_entry_path(name));
+        }
+        Event::Deleting { name } => {
+            outln!("Deleting: {}", utils::canonical_entry_path(name));
+        }
+        Event::SkippedSymlink { path, dangling } => {
+            if dangling {
+                eprintln!("Skipping dangling symlink: {}", path.display());
+            } else {
+                eprintln!("Skipping symlink: {}", path.display());
+            }
+        }
+    }
+}
+
+/// Open an archive for reading, attaching the entry cache if one was requested.
+/// Warns about entries with a compression type that can't be decoded.
+fn open_archive(
+    path: &Path,
+    options: &OpenOptions,
+    cache: Option<&CacheConfig>,
+) -> Result<DatArchive> {
+    let mut archive =
+        DatArchive::open_nested(path, options).map_err(|e| match DatError::find(&e) {
+            Some(DatError::FooterSizeMismatch { .. }) => e.context(format!(
+                "Failed to open {} (use --lenient to open it anyway)",
+                path.display()
+            )),
+            _ if options.format != ArchiveFormat::Auto => e.context(format!(
+                "Failed to open {} as {} (forced by --format)",
+                path.display(),
+                options.format
+            )),
+            _ => e,
+        })?;
+    if let Some(config) = cache {
+        archive.enable_cache(config)?;
+    }
+    for warning in archive.warnings() {
+        color::warning(format_args!("{warning}"));
+    }
+    Ok(archive)
+}
+
+/// Run `save`, first keeping a backup of `path` if a `--backup` suffix was given
+fn save_keeping_backup(
+    path: &Path,
+    backup: Option<&str>,
+    save: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let Some(suffix) = backup else {
+        return save();
+    };
+    utils::save_with_backup(path, suffix, save)?;
+    print_stdout(format_args!(
+        "Kept the previous archive as {}",
+        utils::backup_path(path, suffix).display()
+    ));
+    Ok(())
+}
+
+/// Save `archive`, sharing identical data between entries if `dedup`
+/// (`--dedup`), and return what was shared
+fn save_deduplicating(
+    archive: &DatArchive,
+    path: &Path,
+    dedup: bool,
+) -> Result<Option<SharedData>> {
+    if !dedup {
+        archive.save(path)?;
+        return Ok(None);
+    }
+    if archive.is_dat1() {
+        color::warning(format_args!(
+            "--dedup only works on DAT2 archives, every file is stored separately"
+        ));
+        archive.save(path)?;
+        return Ok(None);
+    }
+    archive.save_deduplicated(path).map(Some)
+}
+
+fn main() {
+    let cli = Cli::parse();
+    cli.color.apply();
+    logging::init(cli.verbose, cli.color);
+    let json_to = cli.json_to.clone();
+    let json = cli.json || json_to.is_some();
+    let command = cli.command.name();
+    let settings =
+        ConfigFile::load().map(|file| Settings::new(file, cli.threads, cli.progress_flag()));
+    let show_progress = settings.as_ref().map_or(true, |s| s.progress.value);
+    let progress = if cli.porcelain {
+        porcelain::enable();
+        // Records replace the progress lines; warnings still go to stderr
+        let tally = porcelain::Tally::default();
+        Progress::with_events(move |event| {
+            tally.record(&event);
+            print_event(event, false);
+        })
+    } else {
+        Progress::with_events(move |event| print_event(event, show_progress))
+    };
+
+    // Run the command to completion or failure, then report: errors are
+    // caught here rather than returned so --json can describe partial work
+    let result = settings.and_then(|settings| {
+        thread_pool(settings.threads.value)
+            .and_then(|pool| pool.install(|| run(cli, &settings, &progress)))
+    });
+
+    if let Err(e) = &result {
+        color::error(format_args!("{e:?}"));
+    }
+    if json {
+        if let Err(e) = CommandResult::new(command, &progress, &result).emit(json_to.as_deref()) {
+            color::error(format_args!("{e:?}"));
+        }
+    }
+    if porcelain::enabled() {
+        porcelain::result(&progress, &result);
+    }
+
+    std::process::exit(ExitCategory::of(&result).code());
+}
+
+/// The pool parallel work runs in. Without `--threads`, rayon's own default
+/// applies: `RAYON_NUM_THREADS` if set, otherwise one thread per CPU.
+fn thread_pool(threads: Option<NonZeroUsize>) -> Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.map_or(0, NonZeroUsize::get))
+        .build()
+        .context("Failed to start worker threads")
+}
+
+/// Execute the parsed command, recording completed work in `progress`
+fn run(cli: Cli, settings: &Settings, progress: &Progress) -> Result<()> {
+    let cache_config = match cli.cache_dir {
+        Some(dir) => Some(CacheConfig {
+            dir,
+            max_bytes: utils::parse_size(&cli.cache_size)?,
+            zstd: cli.cache_zstd,
+        }),
+        None => None,
+    };
+    let open_options = OpenOptions {
+        format: cli.format,
+        lenient: cli.lenient,
+        strict: cli.strict,
+        nul_names: cli.nul_names,
+    };
+    let confirm = Confirm { yes: cli.yes };
+    let json = cli.json || cli.json_to.is_some();
+
+    match cli.command {
+        Commands::List {
+            dat_file,
+            files,
+            full_paths,
+            index,
+            sort,
+            reverse,
+            long,
+            no_summary,
+            by_dir,
+            ext,
+            not_ext,
+            compressed_only,
+            stored_only,
+        } => {
+            let archive = open_archive(&dat_file, &open_options, None)?;
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let extensions = ExtensionFilter::new(&ext, &not_ext);
+            let (mut entries, missing_patterns) = if extensions.is_empty() {
+                archive.find_entries(&patterns)
+            } else {
+                archive.find_entries_by_extension(&patterns, &extensions)
+            };
+            if compressed_only || stored_only {
+                entries.retain(|entry| entry.compressed == compressed_only);
+            }
+            match sort {
+                Some(sort) => sort.sort(&mut entries, reverse),
+                None if reverse => entries.reverse(),
+                None => {}
+            }
+
+            // Only fit the table to the terminal when printing to one; pipes get full paths
+            let width = if full_paths { None } else { terminal_width() };
+            let summary = (!no_summary).then(|| ListingSummary::new(&entries, archive.len()));
+            if json {
+                outln!(
+                    "{}",
+                    serde_json::to_string(&report::Listing::new(&entries))?
+                );
+            } else if by_dir {
+                let groups = usage::group_by_directory(&archive, &entries);
+                print_grouped_listing(&groups, width, index, long, summary.as_ref());
+            } else {
+                print_file_listing(&entries, width, index, long, summary.as_ref());
+            }
+            Progress::add(&progress.listed, entries.len());
+
+            common::report_missing_patterns(&missing_patterns, progress)?;
+        }
+
+        Commands::Find {
+            dat_file,
+            files,
+            min_size,
+            max_size,
+            compressed,
+            stored,
+            dir,
+            null,
+        } => {
+            let query = EntryQuery {
+                patterns: utils::expand_response_files_for_archive(&files)?,
+                min_size: min_size.as_deref().map(utils::parse_size).transpose()?,
+                max_size: max_size.as_deref().map(utils::parse_size).transpose()?,
+                compressed: match (compressed, stored) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                dir,
+            };
+            let archive = open_archive(&dat_file, &open_options, None)?;
+            let found = query.find(&archive);
+
+            let mut out: Box<dyn Write> = if porcelain::enabled() {
+                Box::new(io::stderr().lock())
+            } else {
+                Box::new(io::stdout().lock())
+            };
+            for file in &found {
+                let separator = if null { '\0' } else { '\n' };
+                if write!(out, "{}{separator}", file.path()).is_err() {
+                    std::process::exit(0);
+                }
+            }
+            if out.flush().is_err() {
+                std::process::exit(0);
+            }
+            Progress::add(&progress.listed, found.len());
+            if found.is_empty() {
+                fail!(NotFound, "No entries match");
+            }
+        }
+
+        Commands::Tree { dat_file, depth } => {
+            let archive = open_archive(&dat_file, &open_options, None)?;
+            let tree = DirNode::of(&archive);
+            let label = dat_file.display().to_string();
+            print_stdout(format_args!(
+                "{}",
+                tree.display(&label, depth).to_string().trim_end()
+            ));
+            Progress::add(&progress.listed, tree.total_files);
+        }
+
+        Commands::Du {
+            dat_file,
+            files,
+            depth,
+            by_ext,
+        } => {
+            let archive = open_archive(&dat_file, &open_options, None)?;
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let (entries, missing_patterns) = archive.find_entries(&patterns);
+            let grouping = if by_ext {
+                Grouping::Extension
+            } else {
+                Grouping::Directory { depth }
+            };
+
+            let report = usage::summarize(&entries, grouping);
+            print_stdout(format_args!("{}", report.to_string().trim_end()));
+            Progress::add(&progress.listed, entries.len());
+
+            common::report_missing_patterns(&missing_patterns, progress)?;
+        }
+
+        Commands::Extract {
+            dat_file,
+            output,
+            files,
+            keep_going,
+            ignore_missing,
+            tree_order,
+            force_raw,
+            max_memory,
+            strip_prefix,
+            strip_components,
+            lowercase,
+            update,
+            checksum,
+            mtime,
+            raw,
+            raw_keep_names,
+        } => {
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let output_dir = output.unwrap_or_else(|| settings.output_dir.value.clone());
+            let mut patterns = utils::expand_response_files_for_archive(&files)?;
+            if let Some(prefix) = strip_prefix.as_deref().filter(|_| patterns.is_empty()) {
+                let prefix = utils::canonical_entry_path(prefix);
+                patterns.push(format!("{}/*", prefix.trim_end_matches('/')));
+            }
+            let options = ExtractOptions {
+                mode: ExtractionMode::PreserveStructure,
+                keep_going,
+                ignore_missing,
+                tree_order,
+                force_raw,
+                max_memory: max_memory.as_deref().map(utils::parse_size).transpose()?,
+                strip_prefix,
+                strip_components,
+                case: if lowercase {
+                    NameCase::Lower
+                } else {
+                    NameCase::Preserve
+                },
+                update,
+                checksum,
+                mtime: extract_mtime(&mtime, &dat_file)?,
+                raw,
+                raw_keep_names,
+            };
+            archive.extract(&output_dir, &patterns, &options, progress)?;
+            if raw {
+                warn_raw_extraction(&archive, raw_keep_names);
+            }
+        }
+
+        Commands::ExtractFlat {
+            dat_file,
+            output,
+            files,
+            keep_going,
+            ignore_missing,
+            tree_order,
+            force_raw,
+            max_memory,
+            lowercase,
+            update,
+            checksum,
+            mtime,
+            raw,
+            raw_keep_names,
+        } => {
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let output_dir = output.unwrap_or_else(|| settings.output_dir.value.clone());
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let options = ExtractOptions {
+                mode: ExtractionMode::Flat,
+                keep_going,
+                ignore_missing,
+                tree_order,
+                force_raw,
+                max_memory: max_memory.as_deref().map(utils::parse_size).transpose()?,
+                strip_prefix: None,
+                strip_components: 0,
+                case: if lowercase {
+                    NameCase::Lower
+                } else {
+                    NameCase::Preserve
+                },
+                update,
+                checksum,
+                mtime: extract_mtime(&mtime, &dat_file)?,
+                raw,
+                raw_keep_names,
+            };
+            archive.extract(&output_dir, &patterns, &options, progress)?;
+            if raw {
+                warn_raw_extraction(&archive, raw_keep_names);
+            }
+        }
+
+        Commands::View {
+            dat_file,
+            file,
+            encoding,
+            raw,
+            no_pager,
+        } => {
+            if porcelain::enabled() {
+                fail!(
+                    Usage,
+                    "--porcelain keeps stdout for its records; use x to get the entry"
+                );
+            }
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let data = archive.read_file(&file)?;
+            if raw {
+                view::show(&data, false)?;
+            } else {
+                view::show(view::render(&data, encoding).as_bytes(), !no_pager)?;
+            }
+        }
+
+        Commands::Export {
+            dat_file,
+            output,
+            mut files,
+            tar,
+            compression,
+            store_ext,
+            no_default_store_ext,
+            min_gain,
+            overwrite,
+        } => {
+            let output = match &tar {
+                Some(tar) => {
+                    // With --tar the zip operand is the first pattern
+                    if let Some(pattern) = output {
+                        files.insert(0, pattern.to_string_lossy().into_owned());
+                    }
+                    tar.clone()
+                }
+                None => output.context("Missing the zip file to write")?,
+            };
+            let to_stdout = tar.is_some() && output.as_os_str() == "-";
+            if to_stdout && porcelain::enabled() {
+                fail!(
+                    Usage,
+                    "--porcelain keeps stdout for its records; write the tar stream to a file"
+                );
+            }
+            if to_stdout && io::stdout().is_terminal() {
+                fail!(
+                    Usage,
+                    "Refusing to write a tar stream to a terminal; redirect it or pipe it"
+                );
+            }
+            if !to_stdout && output.exists() && !overwrite {
+                bail!(
+                    "{} already exists (use --overwrite to replace it)",
+                    output.display()
+                );
+            }
+            let compression_level =
+                CompressionLevel::new(compression.unwrap_or(settings.compression.value))?;
+            let compression_policy = if no_default_store_ext {
+                CompressionPolicy::without_store_extensions(compression_level)
+            } else {
+                CompressionPolicy::new(compression_level)
+            }
+            .with_store_extensions(if store_ext.is_empty() {
+                &settings.store_ext.value
+            } else {
+                &store_ext
+            })
+            .with_min_gain(min_gain)?;
+
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let (mut entries, missing) = archive.find_entries(&patterns);
+            common::report_missing_patterns(&missing, progress)?;
+            // A zip can't hold one name twice either: the last entry wins, as when extracting
+            ExtractOptions::new(ExtractionMode::PreserveStructure)
+                .skip_duplicate_names(&mut entries, progress);
+
+            let write = |out: &mut dyn Write| {
+                if tar.is_some() {
+                    let mtime = extract_mtime("archive", &dat_file)?
+                        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |since| since.as_secs());
+                    export::export_tar(&archive, &entries, mtime, out).map(|(_, stats)| stats)
+                } else {
+                    export::export_zip(&archive, &entries, &compression_policy, out)
+                        .map(|(_, stats)| stats)
+                }
+            };
+            if to_stdout {
+                let mut stdout = io::BufWriter::new(io::stdout().lock());
+                match write(&mut stdout) {
+                    Ok(stats) => eprint!("{stats}"),
+                    Err(e) if is_broken_pipe(&e) => std::process::exit(0),
+                    Err(e) => return Err(e.context("Failed to write the tar stream")),
+                }
+                return Ok(());
+            }
+
+            let mut stats = None;
+            utils::write_atomically(&output, |file| {
+                let mut out = io::BufWriter::new(file);
+                let exported = write(&mut out)
+                    .with_context(|| format!("Failed to export to {}", output.display()))?;
+                stats = Some(exported);
+                Ok(())
+            })?;
+            if let Some(stats) = stats {
+                out!("{stats}");
+            }
+        }
+
+        Commands::Import {
+            dat_file,
+            zip_file,
+            target_dir,
+            compression,
+            dat1,
+            store_ext,
+            no_default_store_ext,
+            min_gain,
+            keep_order,
+            case,
+            lowercase,
+            allow_case_dupes,
+            exclude,
+            no_default_excludes,
+            backup,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let case = if lowercase { NameCase::Lower } else { case };
+            let compression_explicitly_set = compression.is_some();
+            let compression = compression.unwrap_or(settings.compression.value);
+            let compression_level = CompressionLevel::new(compression)?;
+            let compression_policy = if no_default_store_ext {
+                CompressionPolicy::without_store_extensions(compression_level)
+            } else {
+                CompressionPolicy::new(compression_level)
+            }
+            .with_store_extensions(if store_ext.is_empty() {
+                &settings.store_ext.value
+            } else {
+                &store_ext
+            })
+            .with_min_gain(min_gain)?;
+
+            let zip = std::fs::read(&zip_file)
+                .with_context(|| format!("Failed to read zip: {}", zip_file.display()))?;
+            let contents = import::read_zip(&zip)
+                .with_context(|| format!("Failed to import {}", zip_file.display()))?;
+            drop(zip);
+            if contents.is_empty() {
+                bail!("{} has no files to import", zip_file.display());
+            }
+
+            let existed = dat_file.exists();
+            if backup.is_some() && !existed {
+                fail!(
+                    Usage,
+                    "--backup needs an existing archive, and {} doesn't exist",
+                    dat_file.display()
+                );
+            }
+            let mut archive = if existed {
+                let archive = open_archive(&dat_file, &open_options, None)?;
+                if dat1 && !archive.is_dat1() {
+                    fail!(Usage, "Error: {} is a DAT2 archive, but --dat1 flag was specified. Cannot change archive format.", dat_file.display());
+                }
+                archive
+            } else if dat1 {
+                DatArchive::new_dat1()
+            } else {
+                DatArchive::new_dat2()
+            };
+            archive.set_keep_order(keep_order);
+            if archive.is_dat1() && compression_explicitly_set && compression > 0 {
+                color::warning(format_args!(
+                    "DAT1 format does not support compression, files will be stored uncompressed"
+                ));
+            }
+
+            let options = AddOptions {
+                target_dir,
+                case,
+                excludes: exclude_list(no_default_excludes, true, &exclude)?,
+                ..AddOptions::new(compression_policy)
+            };
+            let stats = archive.add_contents(contents, &options, progress)?;
+            Progress::add(&progress.added, stats.files_added);
+            if !allow_case_dupes {
+                reject_case_dupes(&archive)?;
+            }
+
+            save_keeping_backup(&dat_file, backup.as_deref(), || archive.save(&dat_file))?;
+            progress.saved.store(true, Ordering::Relaxed);
+            out!("{stats}");
+        }
+
+        Commands::Batch {
+            dat_file,
+            fail_fast,
+            compression,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let compression_level =
+                CompressionLevel::new(compression.unwrap_or(settings.compression.value))?;
+            let add_options = AddOptions {
+                recursive: true,
+                ..AddOptions::new(
+                    CompressionPolicy::new(compression_level)
+                        .with_store_extensions(&settings.store_ext.value),
+                )
+            };
+            let mut archive = open_archive(&dat_file, &open_options, None)?;
+
+            let (mut run, mut failed, mut unsaved) = (0, 0, false);
+            for (number, line) in io::stdin().lock().lines().enumerate() {
+                let line = line.context("Failed to read commands from stdin")?;
+                let command = match BatchCommand::parse(&line) {
+                    Ok(None) => continue,
+                    Ok(Some(command)) => Ok(command),
+                    Err(e) => Err(e),
+                };
+                run += 1;
+                let result = command.and_then(|command| {
+                    let changed = run_batch_command(
+                        &mut archive,
+                        command,
+                        &dat_file,
+                        &add_options,
+                        progress,
+                    )?;
+                    unsaved = changed.unwrap_or(unsaved);
+                    Ok(())
+                });
+                if let Err(e) = result {
+                    let e = e.context(format!("Line {}: {}", number + 1, line.trim()));
+                    if fail_fast {
+                        return Err(e);
+                    }
+                    color::error(format_args!("{e:#}"));
+                    failed += 1;
+                }
+            }
+
+            if unsaved {
+                color::warning(format_args!(
+                    "Changes after the last save were not written (end the commands with save)"
+                ));
+            }
+            if failed > 0 {
+                bail!("{failed} of {run} commands failed");
+            }
+        }
+
+        Commands::Create {
+            dat_file,
+            source,
+            compression,
+            dat1,
+            overwrite,
+            lowercase,
+            dedup,
+            exclude,
+            no_default_excludes,
+            no_ignore_files,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            if dat_file.exists() && !overwrite {
+                bail!(
+                    "{} already exists (use --overwrite to replace it)",
+                    dat_file.display()
+                );
+            }
+            if dat1 && compression.is_some_and(|level| level > 0) {
+                color::warning(format_args!(
+                    "DAT1 format does not support compression, files will be stored uncompressed"
+                ));
+            }
+            let compression_policy = CompressionPolicy::new(CompressionLevel::new(
+                compression.unwrap_or(settings.compression.value),
+            )?)
+            .with_store_extensions(&settings.store_ext.value);
+
+            let case = if lowercase {
+                NameCase::Lower
+            } else {
+                NameCase::Preserve
+            };
+            let excludes = exclude_list(no_default_excludes, no_ignore_files, &exclude)?;
+            let mut contents =
+                DirectoryContents::with_excludes(&source, compression_policy, case, excludes)?;
+            contents.options.dedup = dedup;
+            let (archive, stats) = contents.build(dat1, progress)?;
+            let shared = save_deduplicating(&archive, &dat_file, dedup)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            Progress::add(&progress.added, stats.files_added);
+            out!("{stats}");
+            if let Some(shared) = shared {
+                out!("{shared}");
+            }
+        }
+
+        Commands::Add {
+            dat_file,
+            files,
+            recursive,
+            change_dir,
+            compression,
+            dat1,
+            target_dir,
+            mkdir,
+            store_ext,
+            no_default_store_ext,
+            min_gain,
+            keep_order,
+            case,
+            lowercase,
+            rename_existing,
+            allow_case_dupes,
+            exclude,
+            no_default_excludes,
+            no_ignore_files,
+            newer_than,
+            newer_than_file,
+            fast_append,
+            dedup,
+            backup,
+            from_manifest,
+            root,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let case = if lowercase { NameCase::Lower } else { case };
+            if rename_existing && case == NameCase::Preserve {
+                fail!(Usage, "--rename-existing needs --case or --lowercase");
+            }
+            // Track if the user explicitly set compression (for the DAT1 warning below)
+            let compression_explicitly_set = compression.is_some();
+            let compression = compression.unwrap_or(settings.compression.value);
+            let compression_level = CompressionLevel::new(compression)?;
+            let compression_policy = if no_default_store_ext {
+                CompressionPolicy::without_store_extensions(compression_level)
+            } else {
+                CompressionPolicy::new(compression_level)
+            }
+            .with_store_extensions(if store_ext.is_empty() {
+                &settings.store_ext.value
+            } else {
+                &store_ext
+            })
+            .with_min_gain(min_gain)?;
+
+            if let Some(manifest_path) = from_manifest {
+                let text = std::fs::read_to_string(&manifest_path).with_context(|| {
+                    format!("Failed to read manifest: {}", manifest_path.display())
+                })?;
+                let manifest = Manifest::parse(&text)?;
+                let root = root.unwrap_or_else(|| PathBuf::from("."));
+                let (archive, stats, drifted) = manifest.build(&root, &compression_policy)?;
+                if !drifted.is_empty() {
+                    color::warning(format_args!(
+                        "{} entries are packed differently than the manifest records (other compression settings?):",
+                        drifted.len()
+                    ));
+                    for path in &drifted {
+                        eprintln!("  {path}");
+                    }
+                }
+
+                archive.save(&dat_file)?;
+                progress.saved.store(true, Ordering::Relaxed);
+                Progress::add(&progress.added, stats.files_added);
+                out!("{stats}");
+                return Ok(());
+            }
+
+            let change_dir = match change_dir {
+                Some(path) => {
+                    let resolved = std::fs::canonicalize(&path).with_context(|| {
+                        format!("Failed to resolve -C directory: {}", path.display())
+                    })?;
+                    if !resolved.is_dir() {
+                        fail!(Usage, "-C must point to a directory: {}", path.display());
+                    }
+                    Some(resolved)
+                }
+                None => None,
+            };
+
+            // Expand @response files and glob patterns
+            let file_strings: Vec<String> = files
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            let expanded =
+                utils::expand_response_files_with_stripping(&file_strings, change_dir.as_deref())?;
+            let expanded: Vec<AddOperand> = expanded
+                .into_iter()
+                .map(|operand| {
+                    Ok(AddOperand {
+                        source: utils::resolve_add_input_path(
+                            &operand.source,
+                            change_dir.as_deref(),
+                        )?,
+                        ..operand
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            // Collect files upfront - fails immediately if any path doesn't exist
+            let excludes = exclude_list(no_default_excludes, no_ignore_files, &exclude)?;
+            let newer_than = match (newer_than, newer_than_file) {
+                (Some(time), _) => Some(utils::parse_timestamp(&time)?),
+                (None, Some(file)) => Some(
+                    std::fs::metadata(&file)
+                        .and_then(|metadata| metadata.modified())
+                        .with_context(|| {
+                            format!("Failed to read the modification time of {}", file.display())
+                        })?,
+                ),
+                (None, None) => None,
+            };
+            let mut collected = Vec::with_capacity(expanded.len());
+            for operand in expanded {
+                let files = utils::collect_files_excluding(
+                    &operand.source,
+                    recursive,
+                    &excludes,
+                    progress,
+                )?
+                .files;
+                collected.push((operand, files));
+            }
+            let total_files_to_add: usize = collected.iter().map(|(_, files)| files.len()).sum();
+
+            if total_files_to_add == 0 && mkdir.is_empty() {
+                if recursive {
+                    bail!("No files to add to archive");
+                }
+                bail!("No files to add to archive (use -r to include subdirectories)");
+            }
+
+            let existed = dat_file.exists();
+            if backup.is_some() && !existed {
+                fail!(
+                    Usage,
+                    "--backup needs an existing archive, and {} doesn't exist",
+                    dat_file.display()
+                );
+            }
+            let mut archive = if existed {
+                // Open existing archive - format is fixed, can't change it
+                let archive = open_archive(&dat_file, &open_options, None)?;
+                if dat1 && !archive.is_dat1() {
+                    fail!(Usage, "Error: {} is a DAT2 archive, but --dat1 flag was specified. Cannot change archive format.", dat_file.display());
+                }
+                archive
+            } else if dat1 {
+                DatArchive::new_dat1() // Fallout 1 format
+            } else {
+                DatArchive::new_dat2() // Fallout 2 format (default)
+            };
+            archive.set_keep_order(keep_order);
+
+            if archive.is_dat1() && compression_explicitly_set && compression > 0 {
+                color::warning(format_args!(
+                    "DAT1 format does not support compression, files will be stored uncompressed"
+                ));
+            }
+
+            if rename_existing {
+                let renamed = archive.fold_existing_names(case)?;
+                outln!("Renamed {renamed} existing entries");
+            }
+
+            let options = AddOptions {
+                target_dir,
+                source_root: change_dir,
+                recursive,
+                case,
+                excludes,
+                newer_than,
+                dedup,
+                ..AddOptions::new(compression_policy)
+            };
+            // `source => dest` response file lines each get their own archive path
+            let operand_options: Vec<AddOptions> = collected
+                .iter()
+                .map(|(operand, _)| AddOptions {
+                    dest: operand.dest.clone(),
+                    ..options.clone()
+                })
+                .collect();
+            // Across operands too, not just within each one as `add_file` checks
+            if case != NameCase::Preserve {
+                let mut paths = BTreeSet::new();
+                for ((operand, files), options) in collected.iter().zip(&operand_options) {
+                    for file in files {
+                        paths.insert(options.source_archive_path(file, &operand.source)?);
+                    }
+                }
+                case.check_collisions(paths.iter().map(String::as_str))?;
+            }
+            for dir in &mkdir {
+                if !archive.add_directory(dir)? {
+                    color::warning(format_args!("Directory already exists: {dir}"));
+                }
+            }
+            let mut stats = AddStats::default();
+            for ((operand, _), options) in collected.iter().zip(&operand_options) {
+                let file_stats = archive.add_file(&operand.source, options, progress)?;
+                Progress::add(&progress.added, file_stats.files_added);
+                stats.merge(file_stats);
+            }
+            if !allow_case_dupes {
+                reject_case_dupes(&archive)?;
+            }
+
+            if existed && newer_than.is_some() && stats.files_added == 0 {
+                // Nothing changed since the given time: leave the archive as it is
+                out!("{stats}");
+                return Ok(());
+            }
+
+            let mut shared = None;
+            let save = || {
+                if fast_append && existed {
+                    if !archive.save_appending(&dat_file)? {
+                        let reason = if archive.is_dat1() {
+                            "only works on DAT2 archives"
+                        } else {
+                            "can't be used after entries were replaced"
+                        };
+                        color::warning(format_args!(
+                            "--fast-append {reason}; rewrote the whole archive"
+                        ));
+                    }
+                    Ok(())
+                } else {
+                    shared = save_deduplicating(&archive, &dat_file, dedup)?;
+                    Ok(())
+                }
+            };
+            save_keeping_backup(&dat_file, backup.as_deref(), save)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            out!("{stats}");
+            if let Some(shared) = shared {
+                out!("{shared}");
+            }
+        }
+
+        Commands::Delete {
+            dat_file,
+            files,
+            force,
+            ignore_missing,
+            keep_empty_dirs,
+            prune_empty_dirs,
+            compact,
+            in_place,
+            backup,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let mut archive = open_archive(&dat_file, &open_options, None)?;
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let prefixes: Vec<&String> = patterns
+                .iter()
+                .filter(|pattern| utils::is_directory_prefix(pattern))
+                .collect();
+            if !force {
+                if let Some(root) = prefixes
+                    .iter()
+                    .find(|prefix| utils::entry_path_key(prefix).trim_matches('/').is_empty())
+                {
+                    fail!(
+                        Usage,
+                        "'{root}' would delete every entry; pass --force to do that"
+                    );
+                }
+            }
+
+            // Resolve every pattern before deleting anything
+            let plan = archive.plan_delete(&patterns);
+            let mut missing = plan.missing();
+            if !keep_empty_dirs {
+                // A directory with no files is a DAT1 record of its own
+                missing.retain(|pattern| {
+                    if !utils::is_directory_prefix(pattern) {
+                        return true;
+                    }
+                    let removed = archive.remove_empty_directories(pattern);
+                    if removed > 0 {
+                        print_stdout(format_args!(
+                            "Deleted {removed} empty directories under {}",
+                            utils::canonical_entry_path(pattern)
+                        ));
+                    }
+                    removed == 0
+                });
+            }
+            if !ignore_missing || plan.names.is_empty() {
+                common::report_missing_patterns(&missing, progress)?;
+            }
+            if !plan.names.is_empty() {
+                let names: Vec<String> = plan
+                    .names
+                    .iter()
+                    .map(|name| utils::canonical_entry_path(name))
+                    .collect();
+                let action = format!(
+                    "Delete {} entries from {}?",
+                    names.len(),
+                    dat_file.display()
+                );
+                confirm.ask(&action, &names)?;
+            }
+            for name in &plan.names {
+                archive.delete_file(name, progress)?;
+                Progress::add(&progress.deleted, 1);
+            }
+            for (pattern, count) in &plan.matches {
+                // Prefixes that only named empty directories were reported above
+                if utils::is_directory_prefix(pattern) && (*count > 0 || missing.contains(pattern))
+                {
+                    let dir = utils::canonical_entry_path(pattern);
+                    let dir = if dir.is_empty() { "/" } else { &dir };
+                    print_stdout(format_args!("Deleted {count} entries under {dir}"));
+                }
+            }
+            if !keep_empty_dirs {
+                for prefix in prefixes {
+                    archive.remove_empty_directories(prefix);
+                }
+            }
+            if prune_empty_dirs {
+                prune_empty_directories(&mut archive);
+            }
+
+            save_keeping_backup(&dat_file, backup.as_deref(), || {
+                if compact {
+                    archive.save(&dat_file)
+                } else if in_place {
+                    archive.save_tree_in_place(&dat_file).map(|_| ())
+                } else {
+                    archive.save_tree_only(&dat_file).map(|_| ())
+                }
+            })?;
+            progress.saved.store(true, Ordering::Relaxed);
+            // Under --ignore-missing, missing names only fail a run that deleted nothing
+            if !missing.is_empty() {
+                progress.emit(Event::NotFound { patterns: &missing });
+            }
+        }
+
+        Commands::Repack {
+            input,
+            output,
+            compression,
+            no_default_store_ext,
+        } => {
+            let compression_level =
+                CompressionLevel::new(compression.unwrap_or(settings.compression.value))?;
+            let compression_policy = if no_default_store_ext {
+                CompressionPolicy::without_store_extensions(compression_level)
+            } else {
+                CompressionPolicy::new(compression_level)
+            };
+
+            utils::reject_nested_archive_path(&output)?;
+            let archive = open_archive(&input, &open_options, None)?;
+            let (repacked, stats) = archive.repack(&compression_policy)?;
+            repacked.save(&output)?;
+            progress.saved.store(true, Ordering::Relaxed);
+
+            let after = std::fs::metadata(&output)?.len();
+            match std::fs::metadata(&input) {
+                Ok(before) => outln!(
+                    "Repacked {} entries: {} -> {after} bytes",
+                    stats.files_added,
+                    before.len()
+                ),
+                // Nested input: there's no file on disk to compare against
+                Err(_) => outln!("Repacked {} entries: {after} bytes", stats.files_added),
+            }
+
+            // Make sure what we wrote is a valid archive
+            DatArchive::open_with(&output, &open_options).with_context(|| {
+                format!("Repacked archive failed to re-parse: {}", output.display())
+            })?;
+            outln!("Verified: {} re-parses cleanly", output.display());
+        }
+
+        Commands::Merge {
+            inputs,
+            output,
+            sequence,
+            check_overlap,
+            compression,
+        } => {
+            let compression_policy = CompressionPolicy::new(CompressionLevel::new(
+                compression.unwrap_or(settings.compression.value),
+            )?);
+            utils::reject_nested_archive_path(&output)?;
+
+            let inputs = match sequence {
+                Some(pattern) => merge::expand_sequence(&pattern)?,
+                None => inputs,
+            };
+            let parts = inputs
+                .iter()
+                .map(|path| {
+                    outln!("Merging: {}", path.display());
+                    open_archive(path, &open_options, None)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let (merged, stats, layers) = merge::merge(&parts, &compression_policy)?;
+            merged.save(&output)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            Progress::add(&progress.added, stats.files_added);
+
+            outln!(
+                "Merged {} entries from {} archives into {}",
+                stats.files_added,
+                parts.len(),
+                output.display()
+            );
+            if check_overlap {
+                for (earlier, later, count) in layers.overrides() {
+                    outln!(
+                        "{} overrides {count} entries from {}",
+                        inputs[later].display(),
+                        inputs[earlier].display()
+                    );
+                }
+                outln!("Overridden: {} entries", layers.overridden());
+            }
+        }
+
+        Commands::Patch {
+            source,
+            game_dir,
+            number,
+            dry_run,
+            compression,
+        } => {
+            let compression_policy = CompressionPolicy::new(CompressionLevel::new(compression)?);
+            let number = match number {
+                Some(number) if number > patch::MAX_PATCH_NUMBER => {
+                    fail!(
+                        Usage,
+                        "Patch number must be 0-{}, got {number}",
+                        patch::MAX_PATCH_NUMBER
+                    )
+                }
+                Some(number) => number,
+                None => patch::next_patch_number(&game_dir)?,
+            };
+            let output = game_dir.join(patch::patch_file_name(number));
+            if output.exists() {
+                bail!("{} already exists", output.display());
+            }
+
+            let contents = DirectoryContents::new(&source, compression_policy, NameCase::Preserve)?;
+            if dry_run {
+                outln!("Would write {}", output.display());
+                for (_, path) in &contents.files {
+                    print_stdout(format_args!("{}", utils::canonical_entry_path(path)));
+                }
+                Progress::add(&progress.listed, contents.files.len());
+                return Ok(());
+            }
+
+            let (archive, stats) = contents.build(false, progress)?;
+            archive.save(&output)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            Progress::add(&progress.added, stats.files_added);
+            out!("{stats}");
+            outln!("Wrote {}", output.display());
+        }
+
+        Commands::Optimize {
+            dat_file,
+            dry_run,
+            prune_empty_dirs,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let mut archive = open_archive(&dat_file, &open_options, None)?;
+            let report = archive.space_report();
+            out!("{report}");
+            let pruned = if prune_empty_dirs {
+                prune_empty_directories(&mut archive)
+            } else {
+                0
+            };
+
+            if report.unreferenced() == 0 && pruned == 0 {
+                outln!("Nothing to optimize");
+            } else if !dry_run {
+                archive.save(&dat_file)?;
+                progress.saved.store(true, Ordering::Relaxed);
+                let after = std::fs::metadata(&dat_file)?.len();
+                outln!("Optimized: {} -> {after} bytes", report.archive_size);
+            }
+        }
+
+        Commands::Dedupe {
+            dat_file,
+            keep,
+            dry_run,
+            prune_empty_dirs,
+        } => {
+            utils::reject_nested_archive_path(&dat_file)?;
+            let mut archive = open_archive(&dat_file, &open_options, None)?;
+            let duplicates = find_duplicates(&archive, keep);
+            for duplicate in &duplicates {
+                out!("{duplicate}");
+            }
+
+            if duplicates.is_empty() {
+                outln!("No duplicate names");
+            }
+            if !dry_run {
+                let removed = remove_duplicates(&mut archive, &duplicates);
+                let pruned = if prune_empty_dirs {
+                    prune_empty_directories(&mut archive)
+                } else {
+                    0
+                };
+                if removed > 0 || pruned > 0 {
+                    archive.save(&dat_file)?;
+                    progress.saved.store(true, Ordering::Relaxed);
+                }
+                if removed > 0 {
+                    outln!(
+                        "Removed {removed} duplicate entries of {} names",
+                        duplicates.len()
+                    );
+                }
+            }
+        }
+
+        Commands::Grep {
+            dat_file,
+            pattern,
+            include,
+            exclude,
+            regex,
+            ignore_case,
+            files_with_matches,
+            binary,
+        } => {
+            let searcher = Searcher::new(
+                &pattern,
+                GrepOptions {
+                    regex,
+                    ignore_case,
+                    binary,
+                    include,
+                    exclude,
+                },
+            )?;
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let found = searcher.search(&archive)?;
+
+            for entry in &found {
+                if files_with_matches {
+                    print_stdout(format_args!("{}", entry.path));
+                } else if entry.binary {
+                    print_stdout(format_args!("Binary entry {} matches", entry.path));
+                } else {
+                    for (number, line) in &entry.lines {
+                        print_stdout(format_args!("{}:{number}:{line}", entry.path));
+                    }
+                }
+            }
+            Progress::add(&progress.listed, found.len());
+            if found.is_empty() {
+                bail!("No matches for {pattern}");
+            }
+        }
+
+        Commands::Hash {
+            dat_file,
+            files,
+            algo,
+            check,
+        } => {
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let patterns = utils::expand_response_files_for_archive(&files)?;
+            let (entries, missing_patterns) = archive.find_entries(&patterns);
+
+            match check {
+                None => {
+                    common::report_missing_patterns(&missing_patterns, progress)?;
+                    for entry in hash::hash_entries(&archive, &entries, algo)? {
+                        print_stdout(format_args!("{entry}"));
+                    }
+                    Progress::add(&progress.listed, entries.len());
+                }
+                Some(manifest_path) => {
+                    let text = std::fs::read_to_string(&manifest_path).with_context(|| {
+                        format!("Failed to read manifest: {}", manifest_path.display())
+                    })?;
+                    let mut manifest = hash::parse_manifest(&text, algo)?;
+                    // Patterns narrow the check to the entries and lines they match
+                    if !patterns.is_empty() {
+                        manifest.retain(|expected| {
+                            patterns
+                                .iter()
+                                .any(|pattern| utils::matches_pattern(&expected.path, pattern))
+                        });
+                    }
+
+                    let report = hash::check_manifest(&archive, &entries, &manifest, algo);
+                    out!("{report}");
+                    Progress::add(&progress.listed, report.matched);
+                    if !report.is_ok() {
+                        fail!(
+                            Verification,
+                            "{} doesn't match {}",
+                            dat_file.display(),
+                            manifest_path.display()
+                        );
+                    }
+                }
+            }
+        }
+
+        Commands::Which {
+            archives,
+            paths,
+            all,
+        } => {
+            let opened = archives
+                .iter()
+                .map(|path| open_archive(path, &open_options, None))
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut not_found = 0;
+            for path in &paths {
+                let path = utils::canonical_entry_path(path);
+                let found = merge::which(&opened, &path);
+                let Some(&(winner, entry)) = found.first() else {
+                    print_stdout(format_args!("{path}: not found"));
+                    not_found += 1;
+                    continue;
+                };
+                if !all {
+                    print_stdout(format_args!(
+                        "{path}: {} ({})",
+                        archives[winner].display(),
+                        describe_entry(entry)
+                    ));
+                    continue;
+                }
+                print_stdout(format_args!("{path}:"));
+                for (index, entry) in found {
+                    let used = if index == winner { ", used" } else { "" };
+                    print_stdout(format_args!(
+                        "  {} ({}{used})",
+                        archives[index].display(),
+                        describe_entry(entry)
+                    ));
+                }
+            }
+
+            Progress::add(&progress.listed, paths.len() - not_found);
+            if not_found > 0 {
+                fail!(
+                    NotFound,
+                    "{not_found} of {} paths are in none of the archives",
+                    paths.len()
+                );
+            }
+        }
+
+        Commands::Manifest { dat_file } => {
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let manifest = Manifest::of(&archive)?;
+            print_stdout(format_args!("{}", manifest.to_string().trim_end()));
+            Progress::add(&progress.listed, manifest.entries.len());
+        }
+
+        Commands::Verify {
+            dat_file,
+            dir,
+            ignore_extra,
+        } => {
+            if !dir.is_dir() {
+                bail!("Not a directory: {}", dir.display());
+            }
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let report = verify::verify(&archive, &dir, ignore_extra, progress)?;
+            out!("{report}");
+            Progress::add(&progress.listed, report.matched);
+            if !report.is_ok() {
+                fail!(
+                    Verification,
+                    "{} and {} differ",
+                    dat_file.display(),
+                    dir.display()
+                );
+            }
+        }
+
+        Commands::Check {
+            dat_file,
+            deep,
+            severity,
+        } => {
+            // Footer problems are findings here, not reasons to refuse the archive
+            let open_options = OpenOptions {
+                lenient: true,
+                ..open_options
+            };
+            let archive = open_archive(&dat_file, &open_options, cache_config.as_ref())?;
+            let report = check::check(&archive, deep);
+            if json {
+                outln!("{}", serde_json::to_string(&report)?);
+            } else {
+                out!("{report}");
+            }
+            Progress::add(&progress.listed, report.entries);
+            let failing = report.count_at_least(severity);
+            if failing > 0 {
+                fail!(
+                    Verification,
+                    "{failing} problems at or above {severity} in {}",
+                    dat_file.display()
+                );
+            }
+        }
+
+        Commands::Bench {
+            dat_file,
+            compress_level,
+            rounds,
+        } => {
+            let levels = compress_level
+                .into_iter()
+                .map(CompressionLevel::new)
+                .collect::<Result<Vec<_>>>()?;
+            let archive = open_archive(&dat_file, &open_options, None)?;
+            let report = bench::bench(
+                &archive,
+                rayon::current_num_threads(),
+                &levels,
+                rounds.get(),
+            )?;
+            out!("{report}");
+            Progress::add(&progress.listed, report.entries);
+        }
+
+        Commands::Repair { input, output } => {
+            utils::reject_nested_archive_path(&input)?;
+            utils::reject_nested_archive_path(&output)?;
+            if output.exists() && std::fs::canonicalize(&input)? == std::fs::canonicalize(&output)?
+            {
+                fail!(
+                    Usage,
+                    "The recovered archive must not overwrite the damaged one"
+                );
+            }
+
+            let data = std::fs::read(&input)
+                .with_context(|| format!("Failed to read DAT file: {}", input.display()))?;
+            let recovery = repair::recover_as(data, open_options.format)?;
+            out!("{recovery}");
+            if recovery.archive.is_empty() {
+                bail!("Nothing could be recovered from {}", input.display());
+            }
+
+            recovery.archive.save(&output)?;
+            progress.saved.store(true, Ordering::Relaxed);
+            Progress::add(&progress.added, recovery.archive.len());
+            outln!("Wrote {}", output.display());
+        }
+
+        Commands::Cache { action } => {
+            let Some(config) = cache_config else {
+                fail!(Usage, "--cache-dir is required for cache commands");
+            };
+            match action {
+                CacheCommand::Clear => {
+                    let freed = cache::clear(&config.dir)?;
+                    outln!("Cleared {freed} bytes from {}", config.dir.display());
+                }
+            }
+        }
+
+        Commands::Config { show } => {
+            match config::config_path() {
+                Some((path, _)) if path.exists() => outln!("Config file: {}", path.display()),
+                Some((path, _)) => outln!("Config file: {} (not found)", path.display()),
+                None => outln!("Config file: none (no home directory)"),
+            }
+            if show {
+                out!("{settings}");
+            }
+        }
 
-            archive.save(&dat_file)?;
+        Commands::Completions { shell } => {
+            let script = completions::script(shell, &mut Cli::command());
+            print_stdout(format_args!("{}", script.trim_end()));
         }
     }
 
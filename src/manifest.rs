@@ -0,0 +1,229 @@
+/*!
+# Build Manifests
+
+`dat3 manifest archive.dat` describes an archive as text: its format, then
+one line per entry with the SHA-256 of its content, its real and packed
+sizes, whether it's compressed, and its path. Lines are sorted the way DAT2
+sorts entries, so the same archive always gives the same manifest and
+manifests diff cleanly.
+
+```text
+# dat3 manifest v1 dat2
+# sha256 size packed compressed path
+2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae 3 3 0 text/foo.txt
+```
+
+`dat3 a out.dat --from-manifest build.manifest --root ./src` builds an
+archive of exactly the listed files, read from under the root, and fails if
+any is missing or its content doesn't hash to the listed SHA-256. Built with
+the compression settings of the original, the archive is byte-identical to it.
+*/
+
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::common::{utils, AddStats, CompressionPolicy, DatArchive, Progress};
+use crate::hash::HashAlgo;
+
+/// First line of every manifest, before the format
+const HEADER: &str = "# dat3 manifest v1";
+
+/// Second line, naming the columns
+const COLUMNS: &str = "# sha256 size packed compressed path";
+
+/// One entry of a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Lowercase hex SHA-256 of the decompressed content
+    pub hash: String,
+    pub size: u32,
+    pub packed_size: u32,
+    pub compressed: bool,
+    /// Entry path with forward slashes
+    pub path: String,
+}
+
+/// The entries of an archive and its format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub dat1: bool,
+    /// Sorted with `compare_entry_paths`
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// The manifest text, ending in a newline
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = if self.dat1 { "dat1" } else { "dat2" };
+        writeln!(f, "{HEADER} {format}")?;
+        writeln!(f, "{COLUMNS}")?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{} {} {} {} {}",
+                entry.hash,
+                entry.size,
+                entry.packed_size,
+                u8::from(entry.compressed),
+                entry.path
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    /// Describe `archive`, hashing entries in parallel
+    pub fn of(archive: &DatArchive) -> Result<Self> {
+        let mut entries = archive
+            .entries()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|file| {
+                Ok(ManifestEntry {
+                    hash: HashAlgo::Sha256.digest(&archive.read_entry(file)?),
+                    size: file.size,
+                    packed_size: file.packed_size,
+                    compressed: file.compressed,
+                    path: file.path(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| utils::compare_entry_paths(&a.path, &b.path));
+
+        Ok(Self {
+            dat1: archive.is_dat1(),
+            entries,
+        })
+    }
+
+    /// Parse a manifest written by `dat3 manifest`
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines().enumerate();
+        let dat1 = match lines.next().map(|(_, line)| line.trim_end()) {
+            Some(line) if line == format!("{HEADER} dat1") => true,
+            Some(line) if line == format!("{HEADER} dat2") => false,
+            _ => bail!(
+                "Not a dat3 manifest: the first line must be '{HEADER} dat1' or '{HEADER} dat2'"
+            ),
+        };
+
+        let entries = lines
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                parse_entry(line).with_context(|| format!("Manifest line {}", index + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { dat1, entries })
+    }
+
+    /// Build an archive of the listed entries from the files under `root`.
+    ///
+    /// Files are matched to entries like entry paths, in any case, and read
+    /// in parallel. Every listed file must exist and hash to its listed
+    /// SHA-256; all that don't are reported in one error. Also returns the
+    /// entries that came out compressed differently than listed, which means
+    /// the compression settings differ from the original's.
+    pub fn build(
+        &self,
+        root: &Path,
+        compression: &CompressionPolicy,
+    ) -> Result<(DatArchive, AddStats, Vec<String>)> {
+        let on_disk: HashMap<String, _> = utils::collect_files(root, true, &Progress::default())?
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(root).ok()?.to_string_lossy().into_owned();
+                Some((utils::entry_path_key(&relative), path))
+            })
+            .collect();
+
+        let read: Vec<Result<(String, Vec<u8>), String>> = self
+            .entries
+            .par_iter()
+            .map(|entry| {
+                let Some(path) = on_disk.get(&utils::entry_path_key(&entry.path)) else {
+                    return Err(format!("{}: missing under {}", entry.path, root.display()));
+                };
+                let data = utils::read_file_to_add(path).map_err(|e| format!("{e:#}"))?;
+                if HashAlgo::Sha256.digest(&data) != entry.hash {
+                    return Err(format!("{}: content doesn't match its hash", entry.path));
+                }
+                Ok((utils::stored_entry_path(&entry.path), data))
+            })
+            .collect();
+
+        let problems: Vec<&str> = read
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(String::as_str))
+            .collect();
+        if !problems.is_empty() {
+            bail!(
+                "{} of {} listed files can't be used:\n  {}",
+                problems.len(),
+                self.entries.len(),
+                problems.join("\n  ")
+            );
+        }
+        let contents = read.into_iter().filter_map(Result::ok).collect();
+
+        let (archive, stats) = DatArchive::from_entries(self.dat1, contents, compression)?;
+        let built: HashMap<String, _> = archive
+            .entries()
+            .map(|file| (utils::entry_path_key(&file.name), file))
+            .collect();
+        let drifted = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                built
+                    .get(&utils::entry_path_key(&entry.path))
+                    .is_some_and(|file| {
+                        file.compressed != entry.compressed || file.packed_size != entry.packed_size
+                    })
+            })
+            .map(|entry| entry.path.clone())
+            .collect();
+
+        Ok((archive, stats, drifted))
+    }
+}
+
+/// Parse `<sha256> <size> <packed> <compressed> <path>`; the path is the
+/// rest of the line, so it may contain spaces
+fn parse_entry(line: &str) -> Result<ManifestEntry> {
+    let mut fields = line.trim_end_matches('\r').splitn(5, ' ');
+    let mut next = |what: &str| {
+        fields
+            .next()
+            .filter(|field| !field.is_empty())
+            .with_context(|| format!("Missing {what}"))
+    };
+
+    let hash = next("sha256")?;
+    if hash.len() != HashAlgo::Sha256.hex_len() || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("'{hash}' is not a sha256 checksum");
+    }
+    let size = next("size")?;
+    let packed = next("packed size")?;
+    let compressed = match next("compressed flag")? {
+        "0" => false,
+        "1" => true,
+        other => bail!("Compressed flag must be 0 or 1, got '{other}'"),
+    };
+    let path = next("path")?;
+
+    Ok(ManifestEntry {
+        hash: hash.to_ascii_lowercase(),
+        size: size
+            .parse()
+            .with_context(|| format!("Invalid size '{size}'"))?,
+        packed_size: packed
+            .parse()
+            .with_context(|| format!("Invalid packed size '{packed}'"))?,
+        compressed,
+        path: utils::canonical_entry_path(path),
+    })
+}
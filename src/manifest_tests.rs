@@ -0,0 +1,103 @@
+/*!
+Unit tests for build manifests.
+
+Tests describe a freshly built archive, round-trip the text, and rebuild the
+archive from a source tree: identically, and failing on missing or changed
+files.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::manifest::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    fn policy() -> CompressionPolicy {
+        CompressionPolicy::new(CompressionLevel::new(9).unwrap())
+    }
+
+    fn sample_entries() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("text\\readme.txt".to_string(), b"foo".to_vec()),
+            ("Art\\a.frm".to_string(), vec![7; 4096]),
+        ]
+    }
+
+    /// A source tree holding the sample entries' files
+    fn source_tree(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        for (entry, data) in sample_entries() {
+            let path = dir.join(entry.to_lowercase().replace('\\', "/"));
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        dir
+    }
+
+    fn sample_manifest() -> Manifest {
+        let (archive, _) = DatArchive::from_entries(false, sample_entries(), &policy()).unwrap();
+        Manifest::of(&archive).unwrap()
+    }
+
+    #[test]
+    fn describes_entries_in_sorted_order() {
+        let text = sample_manifest().to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "# dat3 manifest v1 dat2");
+        assert_eq!(lines[1], "# sha256 size packed compressed path");
+        assert!(lines[2].ends_with(" 1 Art/a.frm"));
+        assert!(lines[2].contains(" 4096 "));
+        assert_eq!(
+            lines[3],
+            "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae 3 3 0 text/readme.txt"
+        );
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn parses_its_own_output() {
+        let manifest = sample_manifest();
+        assert_eq!(Manifest::parse(&manifest.to_string()).unwrap(), manifest);
+    }
+
+    #[test]
+    fn rejects_other_text() {
+        assert!(Manifest::parse("abc  text/readme.txt\n").is_err());
+        let err = Manifest::parse("# dat3 manifest v1 dat2\n00 3 3 0 a.txt\n").unwrap_err();
+        assert_eq!(err.to_string(), "Manifest line 2");
+        let line = format!("# dat3 manifest v1 dat2\n{} 3 3 2 a.txt\n", "0".repeat(64));
+        assert!(format!("{:#}", Manifest::parse(&line).unwrap_err())
+            .contains("Compressed flag must be 0 or 1"));
+    }
+
+    #[test]
+    fn rebuilds_the_same_archive() {
+        let (original, _) = DatArchive::from_entries(false, sample_entries(), &policy()).unwrap();
+        let manifest = Manifest::of(&original).unwrap();
+        let dir = source_tree("rebuild");
+
+        let (rebuilt, stats, drifted) = manifest.build(&dir, &policy()).unwrap();
+        assert_eq!(stats.files_added, 2);
+        assert!(drifted.is_empty());
+        assert_eq!(Manifest::of(&rebuilt).unwrap(), manifest);
+
+        // Other compression settings still build, but are reported
+        let stored = CompressionPolicy::new(CompressionLevel::new(0).unwrap());
+        let (_, _, drifted) = manifest.build(&dir, &stored).unwrap();
+        assert_eq!(drifted, ["Art/a.frm"]);
+    }
+
+    #[test]
+    fn missing_and_changed_files_fail_the_build() {
+        let manifest = sample_manifest();
+        let dir = source_tree("drift");
+        fs::write(dir.join("text/readme.txt"), b"bar").unwrap();
+        fs::remove_file(dir.join("art/a.frm")).unwrap();
+
+        let err = manifest.build(&dir, &policy()).unwrap_err().to_string();
+        assert!(err.starts_with("2 of 2 listed files can't be used:"));
+        assert!(err.contains("Art/a.frm: missing under"));
+        assert!(err.contains("text/readme.txt: content doesn't match its hash"));
+    }
+}
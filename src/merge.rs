@@ -0,0 +1,174 @@
+/*!
+# Merging Archives
+
+`dat3 merge` layers several archives into one, the way the game layers
+`patch000.dat` over `master.dat`: when two archives contain the same path
+(compared ASCII case-insensitively), the later one wins.
+
+`dat3 which` answers the same question for single paths, in the engine's
+priority order: `which patch000.dat master.dat critter.dat -- <path>` names
+the first archive that has the path.
+
+`--sequence 'mod_part*.dat'` expands a glob of split archives and merges them
+in natural order, so `part2` comes before `part10`.
+*/
+
+use anyhow::{bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+use crate::common::{utils, AddStats, CompressionPolicy, DatArchive, FileEntry};
+
+/// Compare two strings, ordering runs of ASCII digits by numeric value.
+///
+/// `part2` sorts before `part10`. Leading zeros don't change a number's value;
+/// strings that only differ in them fall back to plain byte order.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a.as_bytes(), b.as_bytes());
+
+    while let (Some(&a_first), Some(&b_first)) = (a_rest.first(), b_rest.first()) {
+        if a_first.is_ascii_digit() && b_first.is_ascii_digit() {
+            let a_len = a_rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            let b_len = b_rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            let a_num = trim_leading_zeros(&a_rest[..a_len]);
+            let b_num = trim_leading_zeros(&b_rest[..b_len]);
+
+            // More significant digits is a bigger number; same count compares digit-wise
+            let order = a_num.len().cmp(&b_num.len()).then(a_num.cmp(b_num));
+            if order != Ordering::Equal {
+                return order;
+            }
+            a_rest = &a_rest[a_len..];
+            b_rest = &b_rest[b_len..];
+        } else {
+            if a_first != b_first {
+                return a_first.cmp(&b_first);
+            }
+            a_rest = &a_rest[1..];
+            b_rest = &b_rest[1..];
+        }
+    }
+
+    a_rest.len().cmp(&b_rest.len()).then_with(|| a.cmp(b))
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&b| b == b'0').count();
+    &digits[zeros..]
+}
+
+/// Expand a glob of split archive parts and sort the matches naturally
+pub fn expand_sequence(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut parts = glob::glob(pattern)
+        .with_context(|| format!("Invalid glob pattern: {pattern}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to expand {pattern}"))?;
+
+    if parts.is_empty() {
+        bail!("No archives match {pattern}");
+    }
+
+    parts.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    Ok(parts)
+}
+
+/// Which part provides each path, and how often later parts overrode earlier ones
+#[derive(Debug, Default)]
+pub struct Layers {
+    /// `entry_path_key` -> (part index, name as stored in that part)
+    latest: BTreeMap<String, (usize, String)>,
+    /// (earlier part, later part) -> entries the later part overrode
+    overrides: BTreeMap<(usize, usize), usize>,
+}
+
+impl Layers {
+    /// Record that `part` contains `name`; it replaces any earlier entry with the same path.
+    /// A repeated name within one part isn't counted as an override.
+    pub fn add(&mut self, part: usize, name: &str) {
+        let key = utils::entry_path_key(name);
+        if let Some((previous, _)) = self.latest.insert(key, (part, name.to_string())) {
+            if previous != part {
+                *self.overrides.entry((previous, part)).or_default() += 1;
+            }
+        }
+    }
+
+    /// Number of distinct paths in the merged archive
+    pub fn len(&self) -> usize {
+        self.latest.len()
+    }
+
+    /// Whether no part has contributed any path yet
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_empty()
+    }
+
+    /// Total number of entries replaced by a later part
+    pub fn overridden(&self) -> usize {
+        self.overrides.values().sum()
+    }
+
+    /// `(earlier part, later part, count)` for every pair of parts that overlap
+    pub fn overrides(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.overrides
+            .iter()
+            .map(|(&(earlier, later), &count)| (earlier, later, count))
+    }
+
+    /// Names each part contributes to the merged archive
+    fn names_from(&self, part: usize) -> HashSet<String> {
+        self.latest
+            .values()
+            .filter(|(p, _)| *p == part)
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+}
+
+/// Merge `parts` in order, later parts winning on overlapping paths.
+///
+/// The result has the format of the first part. Stored names use
+/// backslashes and keep the case of the winning entry.
+pub fn merge(
+    parts: &[DatArchive],
+    compression: &CompressionPolicy,
+) -> Result<(DatArchive, AddStats, Layers)> {
+    let Some(first) = parts.first() else {
+        bail!("No archives to merge");
+    };
+
+    let mut layers = Layers::default();
+    for (index, part) in parts.iter().enumerate() {
+        for name in part.entry_names() {
+            layers.add(index, &name);
+        }
+    }
+
+    let mut contents = Vec::with_capacity(layers.len());
+    for (index, part) in parts.iter().enumerate() {
+        let names = layers.names_from(index);
+        for (name, data) in part.read_entries(&names)? {
+            contents.push((utils::stored_entry_path(&name), data));
+        }
+    }
+
+    // A part may repeat a name; keep its last occurrence, like repack
+    let mut unique: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    unique.extend(contents);
+
+    let (merged, stats) =
+        DatArchive::from_entries(first.is_dat1(), unique.into_iter().collect(), compression)?;
+    Ok((merged, stats, layers))
+}
+
+/// The archives among `archives` that have an entry at `path`, with that
+/// entry, in the order given. Listed by priority like the engine's search
+/// path, the first is the copy the game loads.
+pub fn which<'a>(archives: &'a [DatArchive], path: &str) -> Vec<(usize, &'a FileEntry)> {
+    archives
+        .iter()
+        .enumerate()
+        .filter_map(|(index, archive)| archive.entry(path).map(|entry| (index, entry)))
+        .collect()
+}
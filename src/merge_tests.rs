@@ -0,0 +1,117 @@
+/*!
+Unit tests for merging archives.
+
+Tests cover natural ordering of split archive names, the later-wins
+layering with its override accounting, and resolving a path across archives
+in priority order.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::*;
+    use std::cmp::Ordering;
+
+    mod natural_cmp {
+        use super::*;
+
+        #[test]
+        fn numbers_compare_by_value() {
+            assert_eq!(natural_cmp("part2.dat", "part10.dat"), Ordering::Less);
+            assert_eq!(natural_cmp("part10.dat", "part9.dat"), Ordering::Greater);
+        }
+
+        #[test]
+        fn sorts_split_parts() {
+            let mut names = vec!["mod_part10.dat", "mod_part1.dat", "mod_part2.dat"];
+            names.sort_by(|a, b| natural_cmp(a, b));
+            assert_eq!(names, ["mod_part1.dat", "mod_part2.dat", "mod_part10.dat"]);
+        }
+
+        #[test]
+        fn leading_zeros_only_break_ties() {
+            assert_eq!(natural_cmp("part02", "part3"), Ordering::Less);
+            assert_ne!(natural_cmp("part02", "part2"), Ordering::Equal);
+        }
+
+        #[test]
+        fn prefix_sorts_first() {
+            assert_eq!(natural_cmp("part", "part1"), Ordering::Less);
+            assert_eq!(natural_cmp("mod.dat", "mod.dat"), Ordering::Equal);
+        }
+    }
+
+    mod layers {
+        use super::*;
+
+        #[test]
+        fn later_part_overrides_earlier() {
+            let mut layers = Layers::default();
+            layers.add(0, "art\\a.frm");
+            layers.add(0, "art\\b.frm");
+            layers.add(1, "art\\a.frm");
+            layers.add(2, "art\\b.frm");
+            layers.add(2, "art\\c.frm");
+
+            assert_eq!(layers.len(), 3);
+            assert_eq!(layers.overridden(), 2);
+            assert_eq!(
+                layers.overrides().collect::<Vec<_>>(),
+                [(0, 1, 1), (0, 2, 1)]
+            );
+        }
+
+        #[test]
+        fn paths_match_case_insensitively_across_separators() {
+            let mut layers = Layers::default();
+            layers.add(0, "ART\\A.FRM");
+            layers.add(1, "art/a.frm");
+
+            assert_eq!(layers.len(), 1);
+            assert_eq!(layers.overridden(), 1);
+        }
+
+        #[test]
+        fn repeated_name_within_a_part_is_not_an_override() {
+            let mut layers = Layers::default();
+            layers.add(0, "a.txt");
+            layers.add(0, "a.txt");
+
+            assert_eq!(layers.len(), 1);
+            assert_eq!(layers.overridden(), 0);
+        }
+    }
+    mod which {
+        use super::*;
+        use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+
+        fn archive(names: &[&str]) -> DatArchive {
+            let entries = names
+                .iter()
+                .map(|name| (name.to_string(), name.as_bytes().to_vec()))
+                .collect();
+            let policy = CompressionPolicy::new(CompressionLevel::new(0).unwrap());
+            DatArchive::from_entries(false, entries, &policy).unwrap().0
+        }
+
+        #[test]
+        fn lists_archives_in_priority_order() {
+            let archives = [
+                archive(&["art\\a.frm"]),
+                archive(&["art\\a.frm", "art\\b.frm"]),
+                archive(&["ART\\A.FRM"]),
+            ];
+
+            let found = which(&archives, "art/a.frm");
+            assert_eq!(found.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [0, 1, 2]);
+            assert_eq!(found[2].1.path(), "ART/A.FRM");
+            assert_eq!(
+                which(&archives, "Art\\B.frm")
+                    .iter()
+                    .map(|(i, _)| *i)
+                    .collect::<Vec<_>>(),
+                [1]
+            );
+            assert!(which(&archives, "art/c.frm").is_empty());
+        }
+    }
+}
@@ -0,0 +1,63 @@
+/*!
+# Patch Archives
+
+Fallout 2 reads `patch000.dat`, `patch001.dat` and so on from the game
+directory, later ones overriding earlier ones. `dat3 patch ./my_mod --game-dir
+C:/Fallout2` picks the first free number there and builds the archive from
+the mod directory with the settings such patches need: a DAT2 archive of the
+directory's `DirectoryContents`, at compression 9 with the usual store-only
+extensions.
+
+The first free number is taken, not the one after the highest, so a gap in
+the sequence is filled rather than left for the game to stop at.
+*/
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Highest number that fits the three-digit file name
+pub const MAX_PATCH_NUMBER: u32 = 999;
+
+/// File name of patch `number`, e.g. `patch007.dat`
+pub fn patch_file_name(number: u32) -> String {
+    format!("patch{number:03}.dat")
+}
+
+/// The number of a patch archive's file name, in any case
+pub fn parse_patch_number(file_name: &str) -> Option<u32> {
+    let lower = file_name.to_ascii_lowercase();
+    let digits = lower.strip_prefix("patch")?.strip_suffix(".dat")?;
+    if digits.len() != 3 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Numbers of the patch archives in `game_dir`
+pub fn existing_patch_numbers(game_dir: &Path) -> Result<BTreeSet<u32>> {
+    let entries = std::fs::read_dir(game_dir)
+        .with_context(|| format!("Failed to read game directory: {}", game_dir.display()))?;
+    let mut numbers = BTreeSet::new();
+    for entry in entries {
+        let entry = entry
+            .with_context(|| format!("Failed to read game directory: {}", game_dir.display()))?;
+        if let Some(number) = entry.file_name().to_str().and_then(parse_patch_number) {
+            numbers.insert(number);
+        }
+    }
+    Ok(numbers)
+}
+
+/// The lowest patch number not used in `game_dir`
+pub fn next_patch_number(game_dir: &Path) -> Result<u32> {
+    let used = existing_patch_numbers(game_dir)?;
+    match (0..=MAX_PATCH_NUMBER).find(|number| !used.contains(number)) {
+        Some(number) => Ok(number),
+        None => bail!(
+            "{} already has all patch numbers up to {}",
+            game_dir.display(),
+            patch_file_name(MAX_PATCH_NUMBER)
+        ),
+    }
+}
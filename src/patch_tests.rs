@@ -0,0 +1,52 @@
+/*!
+Unit tests for building patch archives.
+
+Tests cover patch file names in both directions and picking the first free
+number in a game directory.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::patch::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(patch_file_name(0), "patch000.dat");
+        assert_eq!(patch_file_name(42), "patch042.dat");
+        assert_eq!(parse_patch_number("patch000.dat"), Some(0));
+        assert_eq!(parse_patch_number("PATCH012.DAT"), Some(12));
+        assert_eq!(parse_patch_number("patch1.dat"), None);
+        assert_eq!(parse_patch_number("patch0001.dat"), None);
+        assert_eq!(parse_patch_number("patch00a.dat"), None);
+        assert_eq!(parse_patch_number("master.dat"), None);
+    }
+
+    #[test]
+    fn next_number_fills_the_first_gap() {
+        let dir = TempDir::new("next");
+        assert_eq!(next_patch_number(&dir).unwrap(), 0);
+
+        for name in ["master.dat", "patch000.dat", "PATCH001.DAT", "patch003.dat"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        assert_eq!(
+            existing_patch_numbers(&dir)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [0, 1, 3]
+        );
+        assert_eq!(next_patch_number(&dir).unwrap(), 2);
+
+        fs::write(dir.join("patch002.dat"), b"").unwrap();
+        assert_eq!(next_patch_number(&dir).unwrap(), 4);
+    }
+
+    #[test]
+    fn missing_game_dir_fails() {
+        let dir = TempDir::new("missing");
+        assert!(next_patch_number(&dir.join("nope")).is_err());
+    }
+}
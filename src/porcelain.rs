@@ -0,0 +1,171 @@
+/*!
+# Porcelain Output
+
+With `--porcelain`, stdout carries only records for frontends to parse, one
+per line, and everything meant for people goes to stderr. A record is
+tab-separated fields, the first naming it. Within a format version records
+keep their fields and order; new records or fields get a new version.
+
+Format version 1:
+
+- `version <version>`: always the first record
+- `progress <done> <total> <bytes done>`: after each entry extracted or
+  added; `total` grows as `a` reaches each path, and bytes are decompressed
+  sizes of written entries
+- `file <status> <name>`: an entry was `extracted`, left `current` by
+  `--update`, `failed` (with `--keep-going`), `added` or `deleted`; names
+  have forward slashes
+- `result <ok|error> <summary>`: always the last record; the summary is the
+  counts of work done, or the error
+
+Tabs, line breaks and backslashes in fields are written as `\t`, `\n`, `\r`
+and `\\`.
+*/
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use fallout_dat3::{canonical_entry_path, Event, ExtractOutcome, Progress};
+
+/// Format version in the `version` record
+pub const VERSION: u32 = 1;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// `print!`, or `eprint!` under `--porcelain`
+macro_rules! out {
+    ($($arg:tt)*) => {
+        if $crate::porcelain::enabled() {
+            eprint!($($arg)*)
+        } else {
+            print!($($arg)*)
+        }
+    };
+}
+
+/// `println!`, or `eprintln!` under `--porcelain`
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if $crate::porcelain::enabled() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Switch to porcelain output and write the `version` record
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+    record(&[&"version", &VERSION]);
+}
+
+/// Whether `--porcelain` is on, so stdout is reserved for records
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// `text` with tabs, line breaks and backslashes escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// One record as a line, fields escaped
+pub fn line(fields: &[&dyn fmt::Display]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| escape(&field.to_string()))
+        .collect::<Vec<_>>()
+        .join("\t");
+    line.push('\n');
+    line
+}
+
+/// Write a record to stdout in one piece, exiting cleanly on a broken pipe
+fn record(fields: &[&dyn fmt::Display]) {
+    let mut stdout = io::stdout().lock();
+    if stdout
+        .write_all(line(fields).as_bytes())
+        .and_then(|()| stdout.flush())
+        .is_err()
+    {
+        std::process::exit(0);
+    }
+}
+
+/// Entries done and bytes written so far, for `progress` records
+#[derive(Default)]
+pub struct Tally {
+    /// Entries done, entries in total, bytes done
+    counts: Mutex<(usize, usize, u64)>,
+}
+
+impl Tally {
+    /// Write the records for `event`, if it has any
+    pub fn record(&self, event: &Event<'_>) {
+        // Held while writing, so progress records never go backwards
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let (done, total, bytes) = &mut *counts;
+        match *event {
+            Event::ExtractStarted { total: started } | Event::AddStarted { total: started } => {
+                *total += started;
+            }
+            Event::EntryExtracted {
+                name,
+                bytes: written,
+                outcome,
+            } => {
+                let status = match outcome {
+                    ExtractOutcome::Written => "extracted",
+                    ExtractOutcome::Current => "current",
+                    ExtractOutcome::Failed => "failed",
+                };
+                record(&[&"file", &status, &canonical_entry_path(name)]);
+                *done += 1;
+                *bytes += written;
+            }
+            Event::Added { name, bytes: added } => {
+                record(&[&"file", &"added", &canonical_entry_path(name)]);
+                *done += 1;
+                *bytes += added;
+            }
+            Event::Deleting { name } => {
+                record(&[&"file", &"deleted", &canonical_entry_path(name)]);
+                return;
+            }
+            _ => return,
+        }
+        record(&[&"progress", done, total, bytes]);
+    }
+}
+
+/// Write the `result` record for a finished command
+pub fn result(progress: &Progress, result: &Result<()>) {
+    match result {
+        Ok(()) => {
+            let count = |counter: &std::sync::atomic::AtomicUsize| counter.load(Ordering::Relaxed);
+            let summary = format!(
+                "{} listed, {} extracted, {} added, {} deleted",
+                count(&progress.listed),
+                count(&progress.extracted),
+                count(&progress.added),
+                count(&progress.deleted)
+            );
+            record(&[&"result", &"ok", &summary]);
+        }
+        Err(e) => record(&[&"result", &"error", &format!("{e:#}")]),
+    }
+}
@@ -0,0 +1,27 @@
+/*!
+Unit tests for porcelain records.
+
+Tests cover field separation and escaping.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::porcelain::*;
+
+    #[test]
+    fn fields_are_tab_separated() {
+        assert_eq!(line(&[&"version", &VERSION]), "version\t1\n");
+        assert_eq!(
+            line(&[&"progress", &3, &10, &4096u64]),
+            "progress\t3\t10\t4096\n"
+        );
+    }
+
+    #[test]
+    fn separators_in_fields_are_escaped() {
+        assert_eq!(
+            line(&[&"result", &"error", &"bad\tname\r\nin C:\\dat"]),
+            "result\terror\tbad\\tname\\r\\nin C:\\\\dat\n"
+        );
+    }
+}
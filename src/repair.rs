@@ -0,0 +1,280 @@
+/*!
+# Repairing Damaged DAT2 Archives
+
+`dat3 repair` salvages what it can from a DAT2 archive whose directory tree
+or footer is damaged, and writes the recovered entries to a fresh archive.
+
+The footer points to the tree while it's intact. When it isn't (zeroed or cut
+off), the tree is found by scanning backwards for an offset holding a file
+count followed by plausible entries. From there entries are parsed up to the
+recorded count; a damaged stretch of the tree is skipped by resynchronizing
+on the next plausible entry. Every recovered entry's data is then read back
+and decompressed, and entries that fail are dropped.
+
+The damaged archive is only ever read.
+*/
+
+use anyhow::{bail, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::common::{ArchiveFormat, DatArchive, FileEntry};
+use crate::dat2::Dat2Archive;
+use crate::error::DatError;
+
+/// Smallest tree entry: name size, a 1-byte name, compression type, then
+/// real size, packed size and offset
+const MIN_ENTRY_SIZE: usize = 4 + 1 + 1 + 12;
+
+/// Longest name accepted while probing; real entry paths are far shorter
+const MAX_NAME_SIZE: usize = 260;
+
+/// How the directory tree was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeSource {
+    /// At the offset the footer records
+    Footer,
+    /// By scanning backwards, because the footer doesn't point to a tree
+    Scan,
+}
+
+/// What `recover` salvaged from a damaged archive
+#[derive(Debug)]
+pub struct Recovery {
+    /// The recovered entries over the damaged archive's data, ready to save
+    pub archive: Dat2Archive,
+    /// Offset of the tree's file count
+    pub tree_start: u64,
+    pub tree_source: TreeSource,
+    /// Entry count recorded at the start of the tree
+    pub file_count: u32,
+    /// Entries whose tree record parsed but whose data doesn't read back
+    pub unreadable: Vec<DatError>,
+    /// Stretches of the tree that didn't parse and were skipped
+    pub skipped: Vec<Range<u64>>,
+}
+
+impl Recovery {
+    /// Entries recorded in the tree but not recovered
+    pub fn lost(&self) -> usize {
+        (self.file_count as usize).saturating_sub(self.archive.len())
+    }
+}
+
+/// The recovery report as printed by `repair`
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tree_source {
+            TreeSource::Footer => writeln!(f, "Directory tree at offset {}", self.tree_start)?,
+            TreeSource::Scan => writeln!(
+                f,
+                "Footer damaged, directory tree found at offset {} by scanning",
+                self.tree_start
+            )?,
+        }
+        for range in &self.skipped {
+            writeln!(
+                f,
+                "Skipped {} bytes of damaged tree at offset {}",
+                range.end - range.start,
+                range.start
+            )?;
+        }
+        for error in &self.unreadable {
+            match error.source() {
+                Some(source) => writeln!(f, "Lost: {error}: {source}")?,
+                None => writeln!(f, "Lost: {error}")?,
+            }
+        }
+        let with_tree = self.lost().saturating_sub(self.unreadable.len());
+        if with_tree > 0 {
+            writeln!(f, "Lost with the damaged tree: {with_tree} entries")?;
+        }
+        writeln!(
+            f,
+            "Recovered {} of {} entries",
+            self.archive.len(),
+            self.file_count
+        )
+    }
+}
+
+/// Recover the intact entries of a damaged DAT2 archive
+pub fn recover(data: Vec<u8>) -> Result<Recovery> {
+    recover_as(data, ArchiveFormat::Auto)
+}
+
+/// `recover`, with `ArchiveFormat::Dat2` skipping the check that the data
+/// doesn't look like a DAT1 archive
+pub fn recover_as(data: Vec<u8>, format: ArchiveFormat) -> Result<Recovery> {
+    let dat1 = match format {
+        ArchiveFormat::Auto => DatArchive::is_dat1_format(&data),
+        ArchiveFormat::Dat1 => true,
+        ArchiveFormat::Dat2 => false,
+    };
+    if dat1 {
+        bail!("repair only supports DAT2 archives");
+    }
+    let Some((tree_start, tree_source)) = find_tree(&data) else {
+        bail!("No directory tree found, neither at the footer's offset nor by scanning");
+    };
+
+    // An intact footer takes the last 8 bytes; without one, the tree may run
+    // to the end of the file
+    let tree_end = match tree_source {
+        TreeSource::Footer => data.len() - 8,
+        TreeSource::Scan => data.len(),
+    };
+    let file_count = LittleEndian::read_u32(&data[tree_start..]);
+    let (files, skipped) = walk_tree(&data, tree_start, tree_end, file_count as usize);
+    let (archive, unreadable) = Dat2Archive::from_recovered(files, data);
+
+    Ok(Recovery {
+        archive,
+        tree_start: tree_start as u64,
+        tree_source,
+        file_count,
+        unreadable,
+        skipped,
+    })
+}
+
+/// Where the tree starts: at the footer's offset if entries parse there,
+/// otherwise wherever a backwards scan finds the most
+fn find_tree(data: &[u8]) -> Option<(usize, TreeSource)> {
+    if let Some(footer_start) = data.len().checked_sub(8) {
+        let tree_size = LittleEndian::read_u32(&data[footer_start..]) as usize;
+        if let Some(start) = footer_start.checked_sub(tree_size) {
+            if probe_tree(data, start, footer_start).0 > 0 {
+                return Some((start, TreeSource::Footer));
+            }
+        }
+    }
+    scan_for_tree(data).map(|start| (start, TreeSource::Scan))
+}
+
+/// Scan backwards for the offset followed by the most plausible entries.
+///
+/// An offset inside the real tree can look like a tree start too, but then
+/// only parses the entries after it, so the real start wins. The real tree
+/// also comes after every entry's data, so the scan stops at the end of the
+/// furthest data range found so far.
+fn scan_for_tree(data: &[u8]) -> Option<usize> {
+    let last = data.len().checked_sub(4 + MIN_ENTRY_SIZE)?;
+    let mut best: Option<(usize, usize)> = None;
+    let mut floor = 0;
+
+    for start in (0..=last).rev() {
+        if start < floor {
+            break;
+        }
+        let (entries, data_end) = probe_tree(data, start, data.len());
+        if entries == 0 {
+            continue;
+        }
+        floor = floor.max(data_end);
+        if best.is_none_or(|(_, most)| entries >= most) {
+            best = Some((start, entries));
+        }
+    }
+
+    best.map(|(start, _)| start)
+}
+
+/// How many plausible entries follow a tree starting at `start`, stopping at
+/// the first that isn't, and where the furthest data range among them ends
+fn probe_tree(data: &[u8], start: usize, end: usize) -> (usize, usize) {
+    let Some(count) = file_count(data, start) else {
+        return (0, 0);
+    };
+    let (mut entries, mut data_end) = (0, 0);
+    let mut pos = start + 4;
+
+    while entries < count {
+        let Some((entry, size)) = probe_entry(data, pos, start, end, entries) else {
+            break;
+        };
+        data_end = data_end.max((entry.offset + entry.packed_size as u64) as usize);
+        entries += 1;
+        pos += size;
+    }
+
+    (entries, data_end)
+}
+
+/// The non-zero file count at `start`
+fn file_count(data: &[u8], start: usize) -> Option<usize> {
+    let count = LittleEndian::read_u32(data.get(start..start + 4)?) as usize;
+    (count > 0).then_some(count)
+}
+
+/// Parse the entries of the tree at `start`, skipping stretches that don't
+/// parse, up to `count` entries or the end of the tree
+fn walk_tree(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    count: usize,
+) -> (Vec<FileEntry>, Vec<Range<u64>>) {
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut pos = start + 4;
+
+    while files.len() < count && pos < end {
+        if let Some((entry, size)) = probe_entry(data, pos, start, end, files.len()) {
+            files.push(entry);
+            pos += size;
+            continue;
+        }
+
+        // Resynchronize on the next plausible entry
+        let next =
+            (pos + 1..end).find(|&at| probe_entry(data, at, start, end, files.len()).is_some());
+        skipped.push(pos as u64..next.unwrap_or(end) as u64);
+        match next {
+            Some(at) => pos = at,
+            None => break,
+        }
+    }
+
+    (files, skipped)
+}
+
+/// Parse the entry at `pos` if it looks like a real one: a printable ASCII
+/// name, a known compression type, matching sizes if stored or a non-empty
+/// stream if compressed, and data that lies before the tree
+fn probe_entry(
+    data: &[u8],
+    pos: usize,
+    tree_start: usize,
+    end: usize,
+    index: usize,
+) -> Option<(FileEntry, usize)> {
+    let name_size = LittleEndian::read_u32(data.get(pos..pos + 4)?) as usize;
+    if name_size == 0 || name_size > MAX_NAME_SIZE {
+        return None;
+    }
+    let record_end = pos + MIN_ENTRY_SIZE - 1 + name_size;
+    if record_end > end {
+        return None;
+    }
+    let record = &data[pos..record_end];
+    if !record[4..4 + name_size]
+        .iter()
+        .all(|b| (0x20..0x7f).contains(b))
+    {
+        return None;
+    }
+
+    let (entry, size) = Dat2Archive::parse_tree_entry(record, index)?;
+    let plausible = entry.unsupported_compression.is_none()
+        && if entry.compressed {
+            entry.packed_size > 0
+        } else {
+            entry.size == entry.packed_size
+        }
+        && entry.offset + entry.packed_size as u64 <= tree_start as u64;
+    plausible.then_some((entry, size))
+}
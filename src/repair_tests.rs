@@ -0,0 +1,115 @@
+/*!
+Unit tests for repairing damaged DAT2 archives.
+
+Tests damage a freshly built archive in the ways `repair` handles: a zeroed
+tree tail and footer, a garbled tree entry and corrupt entry data.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::repair::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    /// Saved bytes of a DAT2 archive with four compressed entries
+    fn sample_bytes(name: &str) -> Vec<u8> {
+        let entries = (1..=4)
+            .map(|i| {
+                let text = format!("line of entry {i}\n").repeat(20 * i);
+                (format!("text\\file{i}.txt"), text.into_bytes())
+            })
+            .collect();
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        let (archive, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+
+        let dir = TempDir::new(name);
+        let path = dir.join("sample.dat");
+        archive.save(&path).unwrap();
+        fs::read(&path).unwrap()
+    }
+
+    /// Offset of the tree record of `name`
+    fn record_of(bytes: &[u8], name: &str) -> usize {
+        bytes
+            .windows(name.len())
+            .rposition(|window| window == name.as_bytes())
+            .unwrap()
+            - 4
+    }
+
+    fn recovered_names(recovery: &Recovery) -> Vec<String> {
+        recovery.archive.entries().map(|e| e.path()).collect()
+    }
+
+    #[test]
+    fn intact_archive_is_found_through_the_footer() {
+        let recovery = recover(sample_bytes("intact")).unwrap();
+        assert_eq!(recovery.tree_source, TreeSource::Footer);
+        assert_eq!(recovery.archive.len(), 4);
+        assert_eq!(recovery.lost(), 0);
+        assert!(recovery.skipped.is_empty());
+        assert!(recovery.unreadable.is_empty());
+    }
+
+    #[test]
+    fn zeroed_tail_is_found_by_scanning() {
+        let mut bytes = sample_bytes("tail");
+        // Zero from the middle of file3's record through the footer
+        let from = record_of(&bytes, "text\\file3.txt") + 8;
+        let len = bytes.len();
+        bytes[from..].fill(0);
+
+        let recovery = recover(bytes).unwrap();
+        assert_eq!(recovery.tree_source, TreeSource::Scan);
+        assert_eq!(recovery.file_count, 4);
+        assert_eq!(
+            recovered_names(&recovery),
+            ["text/file1.txt", "text/file2.txt"]
+        );
+        assert_eq!(recovery.lost(), 2);
+        assert_eq!(recovery.skipped.last().unwrap().end, len as u64);
+        assert!(recovery
+            .to_string()
+            .contains("Lost with the damaged tree: 2 entries"));
+    }
+
+    #[test]
+    fn garbled_entry_is_skipped() {
+        let mut bytes = sample_bytes("garbled");
+        let at = record_of(&bytes, "text\\file2.txt");
+        // A name size no entry has
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let recovery = recover(bytes).unwrap();
+        assert_eq!(recovery.tree_source, TreeSource::Footer);
+        assert_eq!(
+            recovered_names(&recovery),
+            ["text/file1.txt", "text/file3.txt", "text/file4.txt"]
+        );
+        assert_eq!(recovery.skipped.len(), 1);
+        assert_eq!(recovery.skipped[0].start, at as u64);
+    }
+
+    #[test]
+    fn corrupt_data_is_reported() {
+        let mut bytes = sample_bytes("data");
+        let archive = DatArchive::from_bytes(bytes.clone()).unwrap();
+        let file1 = archive.entries().next().unwrap();
+        let last = (file1.offset + file1.packed_size as u64 - 1) as usize;
+        bytes[last] ^= 1;
+
+        let recovery = recover(bytes).unwrap();
+        assert_eq!(recovery.archive.len(), 3);
+        assert_eq!(recovery.unreadable.len(), 1);
+        assert_eq!(recovery.lost(), 1);
+        assert!(recovery
+            .to_string()
+            .contains("Lost: Failed to decompress text/file1.txt"));
+    }
+
+    #[test]
+    fn archive_without_tree_fails() {
+        assert!(recover(vec![0; 4096]).is_err());
+    }
+}
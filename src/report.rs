@@ -0,0 +1,206 @@
+/*!
+# Command Result Reports
+
+With `--json`, every command ends by emitting exactly one machine-readable
+result object, even when it fails midway, so wrapper tools can tell what
+partially happened. With or without it, the process exits with the code of
+the result's exit category, so scripts can tell a corrupt archive from a
+missing entry or a failed write.
+*/
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use fallout_dat3::{DatError, FileEntry, Progress};
+
+/// Broad classes of command outcomes, each with its own process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitCategory {
+    /// 0
+    Success,
+    /// 1: anything not in a more specific category
+    Failure,
+    /// 2: invalid arguments or options, including ones clap rejects
+    Usage,
+    /// 3: the archive's header, tree or entry data is invalid
+    Archive,
+    /// 4: requested entries or patterns aren't in the archive
+    NotFound,
+    /// 5: reading or writing a file failed, including permission errors
+    Io,
+    /// 6: a check, comparison or manifest found differences
+    Verification,
+}
+
+impl ExitCategory {
+    /// Classify a command result: a `Failed` in the error chain gives its
+    /// category, then a `DatError`, then any I/O error
+    pub fn of(result: &Result<()>) -> Self {
+        let Err(e) = result else {
+            return Self::Success;
+        };
+        if let Some(failed) = e.chain().find_map(|cause| cause.downcast_ref::<Failed>()) {
+            return failed.category;
+        }
+        if let Some(error) = DatError::find(e) {
+            return match error {
+                DatError::EntryNotFound { .. } | DatError::PatternsNotFound { .. } => {
+                    Self::NotFound
+                }
+                DatError::Write { .. } | DatError::Io { .. } => Self::Io,
+                _ => Self::Archive,
+            };
+        }
+        if e.chain().any(|cause| cause.is::<io::Error>()) {
+            return Self::Io;
+        }
+        Self::Failure
+    }
+
+    /// Process exit code for this category
+    pub fn code(self) -> i32 {
+        match self {
+            Self::Success => 0,
+            Self::Failure => 1,
+            Self::Usage => 2,
+            Self::Archive => 3,
+            Self::NotFound => 4,
+            Self::Io => 5,
+            Self::Verification => 6,
+        }
+    }
+}
+
+/// A command failure the library has no type for, with its exit category
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct Failed {
+    pub category: ExitCategory,
+    pub message: String,
+}
+
+/// Return early with a `Failed` of `category`, like `bail!`
+macro_rules! fail {
+    ($category:ident, $($arg:tt)*) => {
+        return Err($crate::report::Failed {
+            category: $crate::report::ExitCategory::$category,
+            message: format!($($arg)*),
+        }
+        .into())
+    };
+}
+
+/// Completed-work counters as reported in the result object
+#[derive(Debug, Serialize)]
+pub struct Counts {
+    pub listed: usize,
+    pub extracted: usize,
+    pub added: usize,
+    pub deleted: usize,
+    pub saved: bool,
+}
+
+impl From<&Progress> for Counts {
+    fn from(progress: &Progress) -> Self {
+        Self {
+            listed: progress.listed.load(Ordering::Relaxed),
+            extracted: progress.extracted.load(Ordering::Relaxed),
+            added: progress.added.load(Ordering::Relaxed),
+            deleted: progress.deleted.load(Ordering::Relaxed),
+            saved: progress.saved.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// One entry of the listing `l` prints with `--json`
+#[derive(Debug, Serialize)]
+pub struct ListedEntry {
+    /// Position in the directory tree as parsed; null for entries added since
+    pub index: Option<usize>,
+    /// Archive path with forward slashes
+    pub name: String,
+    pub offset: u64,
+    pub size: u32,
+    pub packed_size: u32,
+    pub compressed: bool,
+}
+
+impl From<&FileEntry> for ListedEntry {
+    fn from(file: &FileEntry) -> Self {
+        Self {
+            index: file.original_index,
+            name: file.path(),
+            offset: file.offset,
+            size: file.size,
+            packed_size: file.packed_size,
+            compressed: file.compressed,
+        }
+    }
+}
+
+/// The listing `l` prints with `--json`, one line before the result object
+#[derive(Debug, Serialize)]
+pub struct Listing {
+    pub entries: Vec<ListedEntry>,
+}
+
+impl Listing {
+    pub fn new(files: &[&FileEntry]) -> Self {
+        Self {
+            entries: files.iter().map(|&file| ListedEntry::from(file)).collect(),
+        }
+    }
+}
+
+/// The terminal result object printed by `--json`
+#[derive(Debug, Serialize)]
+pub struct CommandResult {
+    pub command: &'static str,
+    /// "ok" or "error"
+    pub status: &'static str,
+    pub category: ExitCategory,
+    pub exit_code: i32,
+    pub counts: Counts,
+    /// Error chain, outermost context first (empty on success)
+    pub errors: Vec<String>,
+}
+
+impl CommandResult {
+    /// Assemble the result of a finished (or aborted) command
+    pub fn new(command: &'static str, progress: &Progress, result: &Result<()>) -> Self {
+        let category = ExitCategory::of(result);
+        let errors = match result {
+            Ok(()) => Vec::new(),
+            Err(e) => e.chain().map(|cause| cause.to_string()).collect(),
+        };
+
+        Self {
+            command,
+            status: if result.is_ok() { "ok" } else { "error" },
+            category,
+            exit_code: category.code(),
+            counts: Counts::from(progress),
+            errors,
+        }
+    }
+
+    /// Write the result as a single JSON line to `path`, or to stdout if None
+    pub fn emit(&self, path: Option<&Path>) -> Result<()> {
+        let json = serde_json::to_string(self).context("Failed to serialize result")?;
+        match path {
+            Some(path) => fs::write(path, format!("{json}\n"))
+                .with_context(|| format!("Failed to write result to {}", path.display())),
+            None => {
+                let mut stdout = io::stdout().lock();
+                // A closed pipe must not turn a finished command into a failure
+                let _ = writeln!(stdout, "{json}");
+                Ok(())
+            }
+        }
+    }
+}
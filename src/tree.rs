@@ -0,0 +1,142 @@
+/*!
+# Directory Trees
+
+`dat3 tree` shows an archive as the directory hierarchy its entry paths
+describe, with each directory's file count and the total size of everything
+under it. DAT2 stores a flat list of paths and DAT1 a list of directories,
+so both are rebuilt the same way, from the entries' paths. DAT1 directory
+records without files are shown too.
+
+Directories that differ only in case are one directory, named as first
+seen. Subdirectories are sorted with `compare_entry_paths`.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::common::{utils, DatArchive};
+
+/// A directory and everything under it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirNode {
+    /// Last path component; empty for the archive root
+    pub name: String,
+    /// Files directly in this directory
+    pub files: usize,
+    /// Files in this directory and all below it
+    pub total_files: usize,
+    /// Real size of all files in and below this directory
+    pub total_size: u64,
+    /// Subdirectories by `entry_path_key` of their name, so in sorted order
+    pub children: BTreeMap<String, DirNode>,
+}
+
+impl DirNode {
+    /// The directory hierarchy of `archive`'s entries
+    pub fn of(archive: &DatArchive) -> Self {
+        let mut root = Self::default();
+        for file in archive.entries() {
+            root.insert(&file.path(), file.size as u64);
+        }
+        if let DatArchive::Dat1(dat1) = archive {
+            for (name, _) in dat1
+                .directories()
+                .filter(|(name, files)| files.is_empty() && *name != ".")
+            {
+                root.insert_directory(&utils::canonical_entry_path(name));
+            }
+        }
+        root
+    }
+
+    /// Count a file at `path` (forward slashes) of `size` bytes
+    pub fn insert(&mut self, path: &str, size: u64) {
+        let dirs = path.rsplit_once('/').map_or("", |(dirs, _)| dirs);
+        let mut node = self;
+        node.count(size);
+        for name in dirs.split('/').filter(|name| !name.is_empty()) {
+            node = node.child(name);
+            node.count(size);
+        }
+        node.files += 1;
+    }
+
+    /// Add the directory at `path` (forward slashes) and its parents, without
+    /// files: DAT1 directory records can be empty
+    pub fn insert_directory(&mut self, path: &str) {
+        let mut node = self;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            node = node.child(name);
+        }
+    }
+
+    /// The subdirectory `name`, added if it isn't there yet
+    fn child(&mut self, name: &str) -> &mut Self {
+        self.children
+            .entry(utils::entry_path_key(name))
+            .or_insert_with(|| Self {
+                name: name.to_string(),
+                ..Self::default()
+            })
+    }
+
+    fn count(&mut self, size: u64) {
+        self.total_files += 1;
+        self.total_size += size;
+    }
+
+    /// Render with the root labelled `label`, showing `depth` levels of
+    /// subdirectories below it (all with None)
+    pub fn display(&self, label: &str, depth: Option<usize>) -> TreeDisplay<'_> {
+        TreeDisplay {
+            root: self,
+            label: label.to_string(),
+            depth,
+        }
+    }
+}
+
+/// An indented rendering of a `DirNode`, one directory per line
+#[derive(Debug)]
+pub struct TreeDisplay<'a> {
+    root: &'a DirNode,
+    label: String,
+    depth: Option<usize>,
+}
+
+impl TreeDisplay<'_> {
+    fn write_node(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        node: &DirNode,
+        name: &str,
+        level: usize,
+    ) -> fmt::Result {
+        let plural = if node.files == 1 { "file" } else { "files" };
+        let count = if node.files == node.total_files {
+            format!("{} {plural}", node.files)
+        } else {
+            format!("{} {plural}, {} in all", node.files, node.total_files)
+        };
+        writeln!(
+            f,
+            "{:indent$}{name}  ({count}, {} bytes)",
+            "",
+            node.total_size,
+            indent = level * 2
+        )?;
+        if self.depth.is_some_and(|depth| level >= depth) {
+            return Ok(());
+        }
+        for child in node.children.values() {
+            self.write_node(f, child, &format!("{}/", child.name), level + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TreeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_node(f, self.root, &self.label, 0)
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+Unit tests for directory trees.
+
+Tests cover counting files into directories, merging directories that differ
+in case, sorting, and rendering with and without a depth limit.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{CompressionLevel, CompressionPolicy, DatArchive};
+    use crate::tree::*;
+
+    fn sample_tree() -> DirNode {
+        let mut root = DirNode::default();
+        root.insert("readme.txt", 5);
+        root.insert("art/critters/a.frm", 100);
+        root.insert("ART/Critters/b.frm", 200);
+        root.insert("art/intrface/c.frm", 10);
+        root.insert("art/a_file.frm", 1);
+        root.insert("Data/d.msg", 20);
+        root
+    }
+
+    #[test]
+    fn counts_direct_and_total_files() {
+        let root = sample_tree();
+        assert_eq!((root.files, root.total_files, root.total_size), (1, 6, 336));
+
+        let art = &root.children["art"];
+        assert_eq!(art.name, "art");
+        assert_eq!((art.files, art.total_files, art.total_size), (1, 4, 311));
+        let critters = &art.children["critters"];
+        assert_eq!((critters.files, critters.total_size), (2, 300));
+    }
+
+    #[test]
+    fn renders_sorted_and_indented() {
+        assert_eq!(
+            sample_tree().display("test.dat", None).to_string(),
+            "test.dat  (1 file, 6 in all, 336 bytes)\n\
+             \x20 art/  (1 file, 4 in all, 311 bytes)\n\
+             \x20   critters/  (2 files, 300 bytes)\n\
+             \x20   intrface/  (1 file, 10 bytes)\n\
+             \x20 Data/  (1 file, 20 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn depth_limits_subdirectory_levels() {
+        assert_eq!(
+            sample_tree().display("test.dat", Some(1)).to_string(),
+            "test.dat  (1 file, 6 in all, 336 bytes)\n\
+             \x20 art/  (1 file, 4 in all, 311 bytes)\n\
+             \x20 Data/  (1 file, 20 bytes)\n"
+        );
+        assert_eq!(
+            sample_tree().display("test.dat", Some(0)).to_string(),
+            "test.dat  (1 file, 6 in all, 336 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn both_formats_give_the_same_tree() {
+        let entries = vec![
+            ("art\\critters\\a.frm".to_string(), vec![1; 100]),
+            ("text\\english\\game\\b.msg".to_string(), vec![2; 30]),
+            ("c.txt".to_string(), vec![3; 5]),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        let (dat1, _) = DatArchive::from_entries(true, entries.clone(), &policy).unwrap();
+        let (dat2, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+
+        let tree = DirNode::of(&dat2);
+        assert_eq!(DirNode::of(&dat1), tree);
+        assert_eq!((tree.total_files, tree.total_size), (3, 135));
+        assert_eq!(tree.children["text"].children["english"].total_files, 1);
+    }
+
+    #[test]
+    fn empty_dat1_directories_are_shown() {
+        let entries = vec![("art\\a.frm".to_string(), vec![1; 10])];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        let (mut dat1, _) = DatArchive::from_entries(true, entries, &policy).unwrap();
+        dat1.add_directory("data\\maps").unwrap();
+
+        assert_eq!(
+            DirNode::of(&dat1).display("test.dat", None).to_string(),
+            "test.dat  (0 files, 1 in all, 10 bytes)\n\
+             \x20 art/  (1 file, 10 bytes)\n\
+             \x20 data/  (0 files, 0 bytes)\n\
+             \x20   maps/  (0 files, 0 bytes)\n"
+        );
+    }
+}
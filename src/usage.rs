@@ -0,0 +1,302 @@
+/*!
+# Space Usage
+
+`dat3 du` sums entries' real and packed sizes by directory, down to a given
+depth, or by file extension, to show which parts of an archive take the
+space. Groups are sorted by packed size, largest first, and followed by the
+totals.
+
+`ListingSummary` is the same sum over a listing's entries, printed under
+`dat3 l`, and `group_by_directory` sums them per directory for `l --by-dir`.
+
+Directories and extensions are grouped ignoring case, and named as first
+seen.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::common::{utils, DatArchive, FileEntry};
+
+/// How `summarize` groups entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grouping {
+    /// By their directory's first `depth` levels; files above that depth
+    /// count toward their own directory
+    Directory { depth: usize },
+    /// By lowercased file extension
+    Extension,
+}
+
+/// Name of the group of files directly in the archive root
+pub const ROOT_GROUP: &str = ".";
+
+/// Name of the group of files without an extension
+pub const NO_EXTENSION_GROUP: &str = "(none)";
+
+/// Sizes summed over a group of entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageRow {
+    pub name: String,
+    pub files: usize,
+    /// Sum of real (decompressed) sizes
+    pub size: u64,
+    /// Sum of packed (stored) sizes
+    pub packed: u64,
+}
+
+impl UsageRow {
+    fn add(&mut self, file: &FileEntry) {
+        self.files += 1;
+        self.size += file.size as u64;
+        self.packed += file.packed_size as u64;
+    }
+
+    /// Packed size as a percentage of the real size
+    pub fn ratio(&self) -> f64 {
+        if self.size == 0 {
+            100.0
+        } else {
+            self.packed as f64 / self.size as f64 * 100.0
+        }
+    }
+}
+
+/// Entries' sizes by group, with totals
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageReport {
+    /// Sorted by packed size descending, then name
+    pub rows: Vec<UsageRow>,
+    pub total: UsageRow,
+}
+
+/// Sum `entries`' sizes by `grouping`
+pub fn summarize(entries: &[&FileEntry], grouping: Grouping) -> UsageReport {
+    let mut groups: HashMap<String, UsageRow> = HashMap::new();
+    let mut total = UsageRow {
+        name: "Total".to_string(),
+        ..UsageRow::default()
+    };
+
+    for file in entries {
+        let path = file.path();
+        let name = match grouping {
+            Grouping::Directory { depth } => directory_group(&path, depth),
+            Grouping::Extension => extension_group(&path),
+        };
+        groups
+            .entry(utils::entry_path_key(&name))
+            .or_insert_with(|| UsageRow {
+                name,
+                ..UsageRow::default()
+            })
+            .add(file);
+        total.add(file);
+    }
+
+    let mut rows: Vec<UsageRow> = groups.into_values().collect();
+    rows.sort_by(|a, b| {
+        b.packed
+            .cmp(&a.packed)
+            .then_with(|| utils::compare_entry_paths(&a.name, &b.name))
+    });
+    UsageReport { rows, total }
+}
+
+/// Listed entries of one directory, with their sizes summed
+#[derive(Debug, Clone)]
+pub struct DirectoryGroup<'a> {
+    /// Named like `du` groups: `art/critters/`, or `.` for the root
+    pub usage: UsageRow,
+    pub files: Vec<&'a FileEntry>,
+}
+
+/// Group `entries` of `archive` by the directory holding them.
+///
+/// DAT1 groups are the archive's directory records, in tree order; DAT2
+/// has no directory records, so its groups are the entries' directory
+/// paths (ignoring case, named as first seen) in order of appearance.
+/// Each group keeps the order of `entries`. Directories with no listed
+/// entries are left out, except DAT1 records (other than the root) that
+/// hold no files at all.
+pub fn group_by_directory<'a>(
+    archive: &DatArchive,
+    entries: &[&'a FileEntry],
+) -> Vec<DirectoryGroup<'a>> {
+    let mut groups: Vec<DirectoryGroup<'a>> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    // DAT1 entries belong to the record that holds them, whatever their name
+    let mut records: HashMap<*const FileEntry, usize> = HashMap::new();
+    let mut empty_records = HashSet::new();
+    if let DatArchive::Dat1(dat1) = archive {
+        for (name, files) in dat1.directories() {
+            let name = match utils::canonical_entry_path(name) {
+                path if path == ROOT_GROUP || path.is_empty() => ROOT_GROUP.to_string(),
+                path => format!("{path}/"),
+            };
+            // Every DAT1 archive has a root record, with or without root files
+            if files.is_empty() && name != ROOT_GROUP {
+                empty_records.insert(groups.len());
+            }
+            for file in files {
+                records.insert(file, groups.len());
+            }
+            groups.push(DirectoryGroup {
+                usage: UsageRow {
+                    name,
+                    ..UsageRow::default()
+                },
+                files: Vec::new(),
+            });
+        }
+    }
+
+    for &file in entries {
+        let index = match records.get(&(file as *const FileEntry)) {
+            Some(&index) => index,
+            None => {
+                let name = directory_group(&file.path(), usize::MAX);
+                *positions
+                    .entry(utils::entry_path_key(&name))
+                    .or_insert_with(|| {
+                        groups.push(DirectoryGroup {
+                            usage: UsageRow {
+                                name,
+                                ..UsageRow::default()
+                            },
+                            files: Vec::new(),
+                        });
+                        groups.len() - 1
+                    })
+            }
+        };
+        groups[index].usage.add(file);
+        groups[index].files.push(file);
+    }
+
+    groups
+        .into_iter()
+        .enumerate()
+        .filter(|(index, group)| !group.files.is_empty() || empty_records.contains(index))
+        .map(|(_, group)| group)
+        .collect()
+}
+
+/// The first `depth` directories of `path`, with a trailing slash
+fn directory_group(path: &str, depth: usize) -> String {
+    let Some((dirs, _)) = path.rsplit_once('/') else {
+        return ROOT_GROUP.to_string();
+    };
+    let group: Vec<&str> = dirs.split('/').take(depth).collect();
+    if group.is_empty() {
+        return ROOT_GROUP.to_string();
+    }
+    format!("{}/", group.join("/"))
+}
+
+/// The lowercased extension of `path`'s file name, with its dot
+fn extension_group(path: &str) -> String {
+    let name = utils::get_filename_from_dat_path(path);
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => {
+            format!(".{}", ext.to_ascii_lowercase())
+        }
+        _ => NO_EXTENSION_GROUP.to_string(),
+    }
+}
+
+/// Width of the number columns
+const COLUMN_WIDTH: usize = 12;
+
+/// A table of the groups, then a rule and the totals
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let w = COLUMN_WIDTH;
+        writeln!(
+            f,
+            "{:>w$} {:>w$} {:>6} {:>8}  Name",
+            "Packed", "Size", "Ratio", "Files"
+        )?;
+        let row = |f: &mut fmt::Formatter<'_>, row: &UsageRow| {
+            writeln!(
+                f,
+                "{:>w$} {:>w$} {:>5.1}% {:>8}  {}",
+                row.packed,
+                row.size,
+                row.ratio(),
+                row.files,
+                row.name
+            )
+        };
+        for group in &self.rows {
+            row(f, group)?;
+        }
+        writeln!(f, "{}", "-".repeat(2 * w + 27))?;
+        row(f, &self.total)
+    }
+}
+
+/// The totals line under a listing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListingSummary {
+    /// Sums over the listed entries
+    pub listed: UsageRow,
+    /// Entries in the whole archive, to tell a filtered listing apart
+    pub archive_entries: usize,
+}
+
+impl ListingSummary {
+    /// Sum the listed `entries` of an archive holding `archive_entries`
+    pub fn new(entries: &[&FileEntry], archive_entries: usize) -> Self {
+        let mut listed = UsageRow::default();
+        for file in entries {
+            listed.add(file);
+        }
+        Self {
+            listed,
+            archive_entries,
+        }
+    }
+}
+
+/// "N files" or "N of M files", then the size sums and the packed ratio
+impl fmt::Display for ListingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let listed = &self.listed;
+        let noun = if self.archive_entries == 1 {
+            "file"
+        } else {
+            "files"
+        };
+        if listed.files == self.archive_entries {
+            write!(f, "{} {noun}", group_thousands(listed.files as u64))?;
+        } else {
+            write!(
+                f,
+                "{} of {} {noun}",
+                group_thousands(listed.files as u64),
+                group_thousands(self.archive_entries as u64)
+            )?;
+        }
+        write!(
+            f,
+            ", {} bytes, {} packed ({:.1}%)",
+            group_thousands(listed.size),
+            group_thousands(listed.packed),
+            listed.ratio()
+        )
+    }
+}
+
+/// `n` with a comma between each group of three digits
+pub fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
@@ -0,0 +1,152 @@
+/*!
+Unit tests for space usage summaries.
+
+Tests cover grouping by directory at several depths and by extension,
+sorting by packed size, the rendered table, the listing summary and
+listing groups.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::FileEntry;
+    use crate::usage::*;
+
+    fn entry(name: &str, size: u32, packed_size: u32) -> FileEntry {
+        let mut file = FileEntry::with_data(name.to_string(), Vec::new(), packed_size < size);
+        file.size = size;
+        file.packed_size = packed_size;
+        file
+    }
+
+    fn sample_entries() -> Vec<FileEntry> {
+        vec![
+            entry("art\\critters\\a.frm", 1000, 400),
+            entry("ART\\Critters\\b.FRM", 1000, 500),
+            entry("art\\items\\c.frm", 300, 300),
+            entry("sound\\music\\m.acm", 2000, 2000),
+            entry("readme", 50, 50),
+            entry("text\\.hidden", 10, 10),
+        ]
+    }
+
+    fn names(report: &UsageReport) -> Vec<&str> {
+        report.rows.iter().map(|row| row.name.as_str()).collect()
+    }
+
+    #[test]
+    fn groups_by_top_level_directory() {
+        let entries = sample_entries();
+        let refs: Vec<_> = entries.iter().collect();
+        let report = summarize(&refs, Grouping::Directory { depth: 1 });
+
+        assert_eq!(names(&report), ["sound/", "art/", ".", "text/"]);
+        assert_eq!(
+            report.rows[1],
+            UsageRow {
+                name: "art/".into(),
+                files: 3,
+                size: 2300,
+                packed: 1200
+            }
+        );
+        assert_eq!(
+            (report.total.files, report.total.size, report.total.packed),
+            (6, 4360, 3260)
+        );
+    }
+
+    #[test]
+    fn deeper_levels_split_directories() {
+        let entries = sample_entries();
+        let refs: Vec<_> = entries.iter().collect();
+        let report = summarize(&refs, Grouping::Directory { depth: 2 });
+
+        assert_eq!(
+            names(&report),
+            ["sound/music/", "art/critters/", "art/items/", ".", "text/"]
+        );
+        assert_eq!(report.rows[1].files, 2);
+    }
+
+    #[test]
+    fn groups_by_extension_ignoring_case() {
+        let entries = sample_entries();
+        let refs: Vec<_> = entries.iter().collect();
+        let report = summarize(&refs, Grouping::Extension);
+
+        assert_eq!(names(&report), [".acm", ".frm", NO_EXTENSION_GROUP]);
+        assert_eq!(report.rows[1].files, 3);
+        assert_eq!(report.rows[2].files, 2);
+    }
+
+    #[test]
+    fn renders_a_table_with_totals() {
+        let entries = [entry("a\\x.frm", 200, 50)];
+        let refs: Vec<_> = entries.iter().collect();
+        let report = summarize(&refs, Grouping::Directory { depth: 1 });
+
+        let text = report.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "      Packed         Size  Ratio    Files  Name");
+        assert_eq!(lines[1], "          50          200  25.0%        1  a/");
+        assert!(lines[2].chars().all(|c| c == '-'));
+        assert!(lines[3].ends_with("1  Total"));
+    }
+
+    #[test]
+    fn listing_summary_sums_listed_entries() {
+        let entries = sample_entries();
+        let refs: Vec<_> = entries.iter().collect();
+        let summary = ListingSummary::new(&refs, refs.len());
+
+        assert_eq!(summary.listed.files, 6);
+        assert_eq!(summary.listed.size, 4360);
+        assert_eq!(
+            summary.to_string(),
+            "6 files, 4,360 bytes, 3,260 packed (74.8%)"
+        );
+    }
+
+    #[test]
+    fn listing_summary_names_the_archive_total_when_filtered() {
+        let entries = [entry("a.frm", 200, 50)];
+        let refs: Vec<_> = entries.iter().collect();
+        assert_eq!(
+            ListingSummary::new(&refs, 1234).to_string(),
+            "1 of 1,234 files, 200 bytes, 50 packed (25.0%)"
+        );
+        assert_eq!(
+            ListingSummary::new(&[], 3).to_string(),
+            "0 of 3 files, 0 bytes, 0 packed (100.0%)"
+        );
+        assert_eq!(
+            ListingSummary::new(&refs, 1).to_string(),
+            "1 file, 200 bytes, 50 packed (25.0%)"
+        );
+    }
+
+    #[test]
+    fn listing_groups_follow_directory_paths() {
+        let entries = sample_entries();
+        let listed: Vec<&FileEntry> = entries.iter().collect();
+        let groups = group_by_directory(&crate::common::DatArchive::new_dat2(), &listed);
+
+        let names: Vec<&str> = groups.iter().map(|g| g.usage.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["art/critters/", "art/items/", "sound/music/", ".", "text/"]
+        );
+        assert_eq!(groups[0].files.len(), 2);
+        assert_eq!(groups[0].usage.size, 2000);
+        assert_eq!(groups[0].usage.packed, 900);
+        assert_eq!(groups[3].files[0].name, "readme");
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1000), "1,000");
+        assert_eq!(group_thousands(123_456_789), "123,456,789");
+    }
+}
@@ -0,0 +1,170 @@
+/*!
+# Comparing an Archive with a Directory
+
+`dat3 verify archive.dat ./data` checks that an archive and a directory hold
+the same content: every entry has a file at the same relative path with the
+same bytes, and every file under the directory is an entry.
+
+Paths are compared like entry paths: either slash, any case. Sizes are
+compared first, so only entries whose sizes match are decompressed and
+compared byte for byte.
+*/
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::common::{utils, DatArchive, FileEntry, Progress};
+
+/// Differences between an archive and a directory
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Entries whose file has the same content
+    pub matched: usize,
+    /// Entries with no file on disk
+    pub missing_on_disk: Vec<String>,
+    /// Files on disk with no entry, as paths relative to the directory
+    pub missing_from_archive: Vec<String>,
+    /// Entries whose file differs, with how
+    pub mismatched: Vec<(String, String)>,
+}
+
+impl VerifyReport {
+    /// Whether the archive and the directory hold the same content
+    pub fn is_ok(&self) -> bool {
+        self.missing_on_disk.is_empty()
+            && self.missing_from_archive.is_empty()
+            && self.mismatched.is_empty()
+    }
+}
+
+/// One section per kind of difference, then a summary
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.missing_on_disk.is_empty() {
+            writeln!(f, "Missing on disk ({}):", self.missing_on_disk.len())?;
+            for path in &self.missing_on_disk {
+                writeln!(f, "  {path}")?;
+            }
+        }
+        if !self.missing_from_archive.is_empty() {
+            writeln!(
+                f,
+                "On disk but not in archive ({}):",
+                self.missing_from_archive.len()
+            )?;
+            for path in &self.missing_from_archive {
+                writeln!(f, "  {path}")?;
+            }
+        }
+        if !self.mismatched.is_empty() {
+            writeln!(f, "Content differs ({}):", self.mismatched.len())?;
+            for (path, how) in &self.mismatched {
+                writeln!(f, "  {path}: {how}")?;
+            }
+        }
+        writeln!(
+            f,
+            "{} matched, {} missing on disk, {} not in archive, {} differ",
+            self.matched,
+            self.missing_on_disk.len(),
+            self.missing_from_archive.len(),
+            self.mismatched.len()
+        )
+    }
+}
+
+/// Compare every entry of `archive` with the file at the same path under `dir`.
+///
+/// Entries are compared in parallel. With `ignore_extra`, files on disk
+/// that aren't in the archive aren't reported. Skipped symlinks are reported
+/// as `Event::SkippedSymlink`.
+pub fn verify(
+    archive: &DatArchive,
+    dir: &Path,
+    ignore_extra: bool,
+    progress: &Progress,
+) -> Result<VerifyReport> {
+    let mut on_disk: HashMap<String, (String, PathBuf)> = HashMap::new();
+    for path in utils::collect_files(dir, true, progress)? {
+        let relative = path
+            .strip_prefix(dir)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+        let relative = utils::canonical_entry_path(&relative.to_string_lossy());
+        on_disk.insert(utils::entry_path_key(&relative), (relative, path));
+    }
+
+    let mut report = VerifyReport::default();
+    let mut entry_keys = HashSet::new();
+    let mut to_compare = Vec::new();
+    for file in archive.entries() {
+        let key = utils::entry_path_key(&file.name);
+        match on_disk.get(&key) {
+            Some((_, path)) => to_compare.push((file, path)),
+            None => report.missing_on_disk.push(file.path()),
+        }
+        entry_keys.insert(key);
+    }
+
+    let results: Vec<_> = to_compare
+        .par_iter()
+        .map(|(file, path)| compare_entry(archive, file, path).map(|how| (file.path(), how)))
+        .collect::<Result<_>>()?;
+    for (path, how) in results {
+        match how {
+            None => report.matched += 1,
+            Some(how) => report.mismatched.push((path, how)),
+        }
+    }
+
+    if !ignore_extra {
+        report.missing_from_archive = on_disk
+            .into_iter()
+            .filter(|(key, _)| !entry_keys.contains(key))
+            .map(|(_, (relative, _))| relative)
+            .collect();
+        report
+            .missing_from_archive
+            .sort_by(|a, b| utils::compare_entry_paths(a, b));
+    }
+    Ok(report)
+}
+
+/// How an entry's content differs from the file at `path`, if it does
+fn compare_entry(archive: &DatArchive, file: &FileEntry, path: &Path) -> Result<Option<String>> {
+    let disk_size = fs::metadata(path)
+        .with_context(|| format!("Failed to inspect {}", path.display()))?
+        .len();
+    if disk_size != file.size as u64 {
+        return Ok(Some(format!(
+            "{} bytes in archive, {disk_size} on disk",
+            file.size
+        )));
+    }
+
+    let content = match archive.read_entry(file) {
+        Ok(content) => content,
+        Err(e) => {
+            return Ok(Some(format!(
+                "unreadable in archive: {:#}",
+                anyhow::Error::from(e)
+            )))
+        }
+    };
+    let on_disk = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if content.len() != on_disk.len() {
+        return Ok(Some(format!(
+            "{} bytes in archive, {} on disk",
+            content.len(),
+            on_disk.len()
+        )));
+    }
+    Ok(content
+        .iter()
+        .zip(&on_disk)
+        .position(|(a, b)| a != b)
+        .map(|offset| format!("bytes differ from offset {offset}")))
+}
@@ -0,0 +1,87 @@
+/*!
+Unit tests for comparing an archive with a directory.
+
+Tests extract a freshly built archive, then change the directory in the ways
+`verify` reports: a missing file, an extra file and changed content.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{
+        CompressionLevel, CompressionPolicy, DatArchive, ExtractOptions, ExtractionMode, Progress,
+    };
+    use crate::test_support::TempDir;
+    use crate::verify::*;
+    use std::fs;
+
+    fn sample_archive() -> DatArchive {
+        let entries = vec![
+            ("art\\a.frm".to_string(), vec![7; 4096]),
+            ("text\\readme.txt".to_string(), b"Hello".to_vec()),
+            ("text\\notes.txt".to_string(), b"Notes".to_vec()),
+        ];
+        let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+        DatArchive::from_entries(false, entries, &policy).unwrap().0
+    }
+
+    /// A directory holding the extracted sample archive
+    fn extracted(archive: &DatArchive, name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        let options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+        archive
+            .extract(&dir, &[], &options, &Progress::default())
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn extracted_archive_matches() {
+        let archive = sample_archive();
+        let dir = extracted(&archive, "match");
+
+        let report = verify(&archive, &dir, false, &Progress::default()).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.matched, 3);
+        assert_eq!(
+            report.to_string(),
+            "3 matched, 0 missing on disk, 0 not in archive, 0 differ\n"
+        );
+    }
+
+    #[test]
+    fn paths_match_in_any_case() {
+        let archive = sample_archive();
+        let dir = extracted(&archive, "case");
+        fs::rename(dir.join("text"), dir.join("TEXT")).unwrap();
+
+        assert!(verify(&archive, &dir, false, &Progress::default())
+            .unwrap()
+            .is_ok());
+    }
+
+    #[test]
+    fn reports_each_kind_of_difference() {
+        let archive = sample_archive();
+        let dir = extracted(&archive, "differ");
+        fs::remove_file(dir.join("text/notes.txt")).unwrap();
+        fs::write(dir.join("text/extra.txt"), b"Extra").unwrap();
+        fs::write(dir.join("text/readme.txt"), b"Hellp").unwrap();
+        fs::write(dir.join("art/a.frm"), b"short").unwrap();
+
+        let report = verify(&archive, &dir, false, &Progress::default()).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "Missing on disk (1):\n  text/notes.txt\n\
+             On disk but not in archive (1):\n  text/extra.txt\n\
+             Content differs (2):\n  \
+             art/a.frm: 4096 bytes in archive, 5 on disk\n  \
+             text/readme.txt: bytes differ from offset 4\n\
+             0 matched, 1 missing on disk, 1 not in archive, 2 differ\n"
+        );
+
+        let report = verify(&archive, &dir, true, &Progress::default()).unwrap();
+        assert!(report.missing_from_archive.is_empty());
+        assert_eq!(report.missing_on_disk, ["text/notes.txt"]);
+    }
+}
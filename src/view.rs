@@ -0,0 +1,192 @@
+/*!
+# Entry Preview
+
+`dat3 view` prints one entry for reading without extracting it. Text is
+decoded to UTF-8 and line endings are normalized; binary content falls back
+to a hex dump. On a terminal, output goes through the pager (`$PAGER`,
+default `less`).
+
+Fallout text (MSG, TXT, SSL) is plain 8-bit: cp1252 for western releases,
+cp866 for some Russian ones. Without `--encoding`, valid UTF-8 is kept as
+is and otherwise the code page whose letters explain more of the high bytes
+wins, cp1252 on ties.
+*/
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use fallout_dat3::cp1252;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// How many leading bytes are inspected to tell text from binary
+const SNIFF_LEN: usize = 8192;
+
+/// Control characters (other than whitespace) tolerated in text, per mille
+const MAX_CONTROL_PER_MILLE: usize = 10;
+
+/// Bytes per hex dump line
+const HEX_LINE_LEN: usize = 16;
+
+/// Text encoding of an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TextEncoding {
+    /// Detect from content
+    Auto,
+    Utf8,
+    Cp1252,
+    Cp866,
+}
+
+/// cp866 0xB0-0xDF: shades and box drawing
+const CP866_BOX: [char; 48] = [
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+];
+
+/// cp866 0xF0-0xFF
+const CP866_TAIL: [char; 16] = [
+    '\u{0401}', '\u{0451}', '\u{0404}', '\u{0454}', '\u{0407}', '\u{0457}', '\u{040E}', '\u{045E}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{2116}', '\u{00A4}', '\u{25A0}', '\u{00A0}',
+];
+
+impl TextEncoding {
+    /// Pick an encoding for `data`: valid UTF-8, else the likelier code page
+    pub fn detect(data: &[u8]) -> Self {
+        if std::str::from_utf8(data).is_ok() {
+            return Self::Utf8;
+        }
+
+        // Bytes that are letters in one code page and symbols in the other
+        let cp866_letters = data
+            .iter()
+            .filter(|&&b| matches!(b, 0x80..=0xAF | 0xE0..=0xF1))
+            .count();
+        let cp1252_letters = data
+            .iter()
+            .filter(|&&b| {
+                matches!(
+                    b,
+                    0x8A | 0x8C | 0x8E | 0x9A | 0x9C | 0x9E | 0x9F | 0xC0..=0xFF
+                )
+            })
+            .count();
+
+        if cp866_letters > cp1252_letters {
+            Self::Cp866
+        } else {
+            Self::Cp1252
+        }
+    }
+
+    /// Decode `data` to a string; `Auto` detects first, invalid UTF-8 is replaced
+    pub fn decode(self, data: &[u8]) -> String {
+        match self {
+            Self::Auto => Self::detect(data).decode(data),
+            Self::Utf8 => String::from_utf8_lossy(data).into_owned(),
+            Self::Cp1252 => cp1252::decode(data),
+            Self::Cp866 => data.iter().map(|&b| cp866_char(b)).collect(),
+        }
+    }
+}
+
+fn cp866_char(b: u8) -> char {
+    match b {
+        // А-Я, а-п
+        0x80..=0xAF => char::from_u32(0x0410 + (b - 0x80) as u32).unwrap_or('\u{FFFD}'),
+        0xB0..=0xDF => CP866_BOX[(b - 0xB0) as usize],
+        // р-я
+        0xE0..=0xEF => char::from_u32(0x0440 + (b - 0xE0) as u32).unwrap_or('\u{FFFD}'),
+        0xF0..=0xFF => CP866_TAIL[(b - 0xF0) as usize],
+        _ => b as char,
+    }
+}
+
+/// Guess whether `data` is binary from its first bytes: any NUL, or more
+/// than a few control characters other than whitespace and DOS EOF.
+pub fn is_binary(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(SNIFF_LEN)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let controls = sample
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1A))
+        .count();
+    controls * 1000 > sample.len() * MAX_CONTROL_PER_MILLE
+}
+
+/// Convert CRLF and lone CR line endings to LF
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Format `data` as a hex dump: offset, hex bytes, printable ASCII
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in data.chunks(HEX_LINE_LEN).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:08x}  {:<width$}  {ascii}\n",
+            line * HEX_LINE_LEN,
+            hex.join(" "),
+            width = HEX_LINE_LEN * 3 - 1
+        ));
+    }
+    out
+}
+
+/// Render an entry for reading: decoded text, or a hex dump for binary data
+pub fn render(data: &[u8], encoding: TextEncoding) -> String {
+    if is_binary(data) {
+        eprintln!("Binary content, showing a hex dump (use --raw for the bytes)");
+        return hex_dump(data);
+    }
+    let mut text = normalize_line_endings(&encoding.decode(data));
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Write `bytes` to stdout, through the pager when stdout is a terminal.
+/// A closed pipe or a pager quit early is not an error.
+pub fn show(bytes: &[u8], use_pager: bool) -> Result<()> {
+    if use_pager && io::stdout().is_terminal() {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+        let mut parts = pager.split_whitespace();
+        if let Some(program) = parts.next() {
+            // If the pager can't be started, fall back to plain output
+            if let Ok(mut child) = Command::new(program)
+                .args(parts)
+                .stdin(Stdio::piped())
+                .spawn()
+            {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(bytes);
+                }
+                child.wait().context("Failed to wait for the pager")?;
+                return Ok(());
+            }
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.context("Failed to write to stdout"),
+    }
+}
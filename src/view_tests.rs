@@ -0,0 +1,112 @@
+/*!
+Unit tests for entry preview.
+
+Tests cover code page decoding, encoding detection, binary sniffing,
+line ending normalization and the hex dump format.
+*/
+
+#[cfg(test)]
+mod tests {
+    use crate::view::*;
+
+    mod decode {
+        use super::*;
+
+        #[test]
+        fn cp1252_maps_high_punctuation_and_latin1() {
+            let data = b"\x93caf\xe9\x94 \x80 \x85";
+            assert_eq!(
+                TextEncoding::Cp1252.decode(data),
+                "\u{201C}caf\u{e9}\u{201D} \u{20AC} \u{2026}"
+            );
+        }
+
+        #[test]
+        fn cp866_maps_cyrillic_and_box_drawing() {
+            // "Привет" followed by a horizontal box line and "Ёё"
+            let data = b"\x8f\xe0\xa8\xa2\xa5\xe2 \xc4 \xf0\xf1";
+            assert_eq!(TextEncoding::Cp866.decode(data), "Привет ─ Ёё");
+        }
+
+        #[test]
+        fn ascii_is_identical_in_all_encodings() {
+            let data = b"{100}{}{Hello}";
+            for encoding in [
+                TextEncoding::Utf8,
+                TextEncoding::Cp1252,
+                TextEncoding::Cp866,
+            ] {
+                assert_eq!(encoding.decode(data), "{100}{}{Hello}");
+            }
+        }
+    }
+
+    mod detect {
+        use super::*;
+
+        #[test]
+        fn valid_utf8_is_kept() {
+            assert_eq!(
+                TextEncoding::detect("{1}{}{Ünïcödé}".as_bytes()),
+                TextEncoding::Utf8
+            );
+        }
+
+        #[test]
+        fn russian_text_is_cp866() {
+            // "Привет, мир" in cp866
+            let data = b"\x8f\xe0\xa8\xa2\xa5\xe2, \xac\xa8\xe0";
+            assert_eq!(TextEncoding::detect(data), TextEncoding::Cp866);
+        }
+
+        #[test]
+        fn western_text_is_cp1252() {
+            // "Über café" in cp1252
+            let data = b"\xdcber caf\xe9";
+            assert_eq!(TextEncoding::detect(data), TextEncoding::Cp1252);
+        }
+    }
+
+    mod binary {
+        use super::*;
+
+        #[test]
+        fn text_with_crlf_and_tabs_is_not_binary() {
+            assert!(!is_binary(b"{100}{}{Hello}\r\n\t{101}{}{World}\r\n\x1a"));
+        }
+
+        #[test]
+        fn nul_bytes_mean_binary() {
+            assert!(is_binary(b"FRM\x00\x00\x00\x04"));
+        }
+
+        #[test]
+        fn many_control_characters_mean_binary() {
+            assert!(is_binary(b"\x01\x02\x03\x04abcdefgh"));
+        }
+    }
+
+    #[test]
+    fn line_endings_are_normalized() {
+        assert_eq!(normalize_line_endings("a\r\nb\rc\n"), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn render_ends_text_with_newline() {
+        assert_eq!(render(b"{1}{}{x}", TextEncoding::Auto), "{1}{}{x}\n");
+    }
+
+    #[test]
+    fn hex_dump_format() {
+        let dump = hex_dump(b"FRM\x00abcdefghijklmnop");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            "00000000  46 52 4d 00 61 62 63 64 65 66 67 68 69 6a 6b 6c  FRM.abcdefghijkl"
+        );
+        assert_eq!(
+            lines[1],
+            format!("00000010  6d 6e 6f 70{}  mnop", " ".repeat(36))
+        );
+    }
+}
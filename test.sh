@@ -29,3 +29,195 @@ fi
 
 # Glob pattern handling test
 ./glob_handling.sh
+
+# JSON result object test
+./json_result.sh
+
+# Repack test
+./repack.sh
+
+# Nested archive test
+./nested_archive.sh
+
+# Optimize test
+./optimize.sh
+
+# Keep order test
+./keep_order.sh
+
+# Zlib validation test
+./zlib_validation.sh
+
+# View test
+./view.sh
+
+# Recursive add test
+./recursive_add.sh
+
+# Case folding test
+./case_fold.sh
+
+# Extract missing files test
+./extract_missing.sh
+
+# Merge test
+./merge.sh
+
+# Compression type test
+./compression_type.sh
+
+# Lenient open test
+./lenient.sh
+
+# Repair test
+./repair.sh
+
+# Entry bounds test
+./entry_bounds.sh
+
+# DAT1 name length test
+./dat1_name_length.sh
+
+# Thread pool test
+./threads.sh
+
+# Hash test
+./hash.sh
+
+# Verify test
+./verify.sh
+
+# Manifest test
+./manifest.sh
+
+# Tree test
+./tree.sh
+
+# Space usage test
+./du.sh
+
+# Grep test
+./grep.sh
+
+# Find test
+./find.sh
+
+# Which test
+./which.sh
+
+# Patch test
+./patch.sh
+
+# Create test
+./create.sh
+
+# Strip prefix test
+./strip_prefix.sh
+
+# Strip components test
+./strip_components.sh
+
+# Extract lowercase test
+./extract_lowercase.sh
+
+# Response file mapping test
+./response_mapping.sh
+
+# Completions test
+./completions.sh
+
+# Config file test
+./config.sh
+
+# Color test
+./color.sh
+
+# List sort test
+./list_sort.sh
+
+# List verbose test
+./list_verbose.sh
+
+# List extension filter test
+./list_ext.sh
+
+# List compression filter test
+./list_compression.sh
+
+# Delete glob test
+./delete_glob.sh
+
+# Delete directory test
+./delete_dir.sh
+
+# Delete missing test
+./delete_missing.sh
+
+# Fast append test
+./fast_append.sh
+
+# Tree-only delete test
+./delete_tree_only.sh
+
+# Backup test
+./backup.sh
+
+# Format test
+./format.sh
+
+# Dedup test
+./dedup.sh
+
+# dedupe test
+./dedupe.sh
+
+# case_dupes test
+./case_dupes.sh
+
+# excludes test
+./excludes.sh
+
+# ignore_files test
+./ignore_files.sh
+
+# newer_than test
+./newer_than.sh
+
+# extract_update test
+./extract_update.sh
+
+# extract_mtime test
+./extract_mtime.sh
+
+# extract_raw test
+./extract_raw.sh
+
+# export_zip test
+./export_zip.sh
+
+# import_zip test
+./import_zip.sh
+
+# export_tar test
+./export_tar.sh
+
+# batch test
+./batch.sh
+
+# list_by_dir test
+./list_by_dir.sh
+
+# empty_dirs test
+./empty_dirs.sh
+
+# check test
+./check.sh
+
+# bench test
+./bench.sh
+
+# porcelain test
+./porcelain.sh
+
+# logging test
+./logging.sh
@@ -161,6 +161,19 @@ if $DAT3 l symlink_skip.dat | grep -q "patch001/link.txt"; then
 	exit 1
 fi
 
+# Test 11: A file over 4 GiB is rejected before it's read (sparse, so it takes no space)
+truncate -s 4294967296 huge.bin
+for flag in "" --dat1; do
+	# shellcheck disable=SC2086
+	if $DAT3 a huge.dat $flag huge.bin 2>huge.err; then
+		echo "Error: Should have failed with a file over 4 GiB"
+		exit 1
+	fi
+	grep -q "huge.bin is 4294967296 bytes, over the 4 GiB DAT limit for a single file" huge.err
+	[ ! -e huge.dat ]
+done
+rm huge.bin
+
 # Clean up
 cd ..
 rm -rf "$TEST_DIR"
@@ -0,0 +1,71 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test --backup on a and d
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_backup"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p text
+for name in one two three; do
+	echo "$name $name $name" >"text/$name.txt"
+done
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+# Test 1: --backup needs an archive to back up
+if $DAT3 a new.dat text/one.txt --backup; then
+	echo "Error: --backup on a new archive should fail"
+	exit 1
+fi
+[ ! -e new.dat ]
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag text/one.txt text/two.txt
+	cp "$format.dat" before.dat
+
+	# Test 2: a --backup keeps the archive as it was
+	$DAT3 a "$format.dat" text/three.txt --backup >add.log
+	grep -q "Kept the previous archive as $format.dat.bak" add.log
+	cmp before.dat "$format.dat.bak"
+	[ "$(names "$format.dat" | wc -l)" -eq 3 ]
+
+	# Test 3: d --backup replaces the old backup, here with another suffix too
+	cp "$format.dat" added.dat
+	$DAT3 d -y "$format.dat" text/one.txt --backup
+	cmp added.dat "$format.dat.bak"
+	$DAT3 d -y "$format.dat" text/two.txt --backup=.old
+	printf 'text/three.txt\n' | diff - <(names "$format.dat")
+	printf 'text/three.txt\ntext/two.txt\n' | diff - <(names "$format.dat.old")
+
+	# Test 4: A failed delete leaves the backup alone
+	cp "$format.dat.bak" bak_before.dat
+	if $DAT3 d -y "$format.dat" text/missing.txt --backup; then
+		echo "Error: a missing name should fail the delete"
+		exit 1
+	fi
+	cmp bak_before.dat "$format.dat.bak"
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,69 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that batch runs commands from stdin against one archive, writing it only on save
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_batch"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text "mods/new dir"
+echo "a" >src/text/a.msg
+echo "b" >src/text/b.msg
+echo "new" >"mods/new dir/new.msg"
+
+for flag in "" "--dat1"; do
+	rm -rf out
+	$DAT3 c test.dat src $flag --overwrite
+
+	# Test 1: Commands run in order against the archive, and save writes it
+	cat >script.txt <<'SCRIPT'
+# Replace a.msg with a new file, then look at the result
+list text/*
+add "mods/new dir/new.msg" as text/new.msg
+delete text/a.msg
+
+list
+extract text/new.msg out
+save
+SCRIPT
+	$DAT3 batch test.dat <script.txt >batch.log
+	grep -qx "text/a.msg" batch.log
+	grep -q "Saved test.dat" batch.log
+	$DAT3 l test.dat >list.log
+	grep -q " text/new.msg$" list.log
+	if grep -q " text/a.msg$" list.log; then exit 1; fi
+	[ "$(cat out/text/new.msg)" = new ]
+done
+
+# Test 2: Without save nothing is written, with a warning
+cp test.dat before.dat
+echo "delete text/b.msg" | $DAT3 batch test.dat 2>err.log >/dev/null
+grep -q "were not written" err.log
+cmp test.dat before.dat
+
+# Test 3: A failed command is reported, the rest still run, and the exit status is non-zero
+printf 'delete text/missing.msg\nrename a b\ndelete text/b.msg\nsave\n' >script.txt
+if $DAT3 batch test.dat <script.txt >batch.log 2>err.log; then exit 1; fi
+grep -q "Line 1: delete text/missing.msg" err.log
+grep -q "Line 2: rename a b: Unknown command 'rename'" err.log
+grep -q "2 of 4 commands failed" err.log
+if $DAT3 l test.dat | grep -q " text/b.msg$"; then exit 1; fi
+
+# Test 4: --fail-fast stops at the first failure
+cp before.dat test.dat
+if $DAT3 batch test.dat --fail-fast <script.txt >batch.log 2>err.log; then exit 1; fi
+cmp test.dat before.dat
+
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,56 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test timing decompression and recompression with bench
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_bench"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+for i in $(seq 1 5); do
+	seq 1 2000 | sed "s/^/line $i /" >"f$i.txt"
+done
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	$DAT3 a "$format.dat" $flag f*.txt
+
+	# Test 1: decompression scales from one thread to --threads
+	$DAT3 --threads 2 bench "$format.dat" --rounds 1 >bench.log
+	grep -q "^5 entries, " bench.log
+	grep -q "^      1 " bench.log
+	grep -q "^      2 " bench.log
+	if grep -q "Level" bench.log; then
+		echo "Recompression timed without --compress-level"
+		exit 1
+	fi
+
+	# Test 2: recompression at each level, stored at level 0
+	$DAT3 bench "$format.dat" --rounds 1 --compress-level 0,9 >bench.log
+	size=$(head -n 1 bench.log | awk '{print $3}')
+	grep -Eq "^      0 .* $size 100\.0%$" bench.log
+	grep -q "^      9 " bench.log
+done
+
+# Test 3: levels are checked
+if $DAT3 bench dat2.dat --compress-level 10 2>bench.log; then
+	echo "Level 10 accepted"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
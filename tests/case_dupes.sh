@@ -0,0 +1,56 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test entries whose names differ only by case
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_case_dupes"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p lower/art upper/ART
+echo "lower case frame" >lower/art/foo.frm
+echo "upper case frame" >upper/ART/FOO.FRM
+
+$DAT3 a case.dat -C lower art/foo.frm
+cp case.dat before.dat
+
+# Test 1: An add that would make such a pair fails and leaves the archive alone
+if $DAT3 a case.dat -C upper ART/FOO.FRM 2>add.err; then
+	echo "Error: adding ART/FOO.FRM next to art/foo.frm should fail"
+	exit 1
+fi
+grep -q "art/foo.frm and ART/FOO.FRM would differ only by case" add.err
+cmp before.dat case.dat
+
+# Test 2: Re-adding the same spelling still replaces the entry
+$DAT3 a case.dat -C lower art/foo.frm
+
+# Test 3: --allow-case-dupes adds it, and opening warns
+$DAT3 a case.dat -C upper ART/FOO.FRM --allow-case-dupes
+$DAT3 l case.dat >/dev/null 2>list.err
+grep -q "art/foo.frm and ART/FOO.FRM differ only by case" list.err
+
+# Test 4: Extraction writes only the later entry, whatever the file system
+$DAT3 x case.dat -o out 2>extract.err
+grep -q "Skipping an earlier entry named art/foo.frm; ART/FOO.FRM is extracted instead" extract.err
+cmp upper/ART/FOO.FRM out/ART/FOO.FRM
+[ ! -e out/art ]
+
+# Test 5: dedupe removes the pair
+$DAT3 dedupe case.dat --keep first
+$DAT3 x case.dat -o out_deduped
+cmp lower/art/foo.frm out_deduped/art/foo.frm
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,110 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test --case folding of added archive paths and --rename-existing
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_case_fold"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/Art/Critters
+echo "critter" >src/Art/Critters/HmJmpSaa.frm
+echo "readme" >src/ReadMe.txt
+echo "replaced" >replacement.txt
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Test 1: --case upper folds new names and says so in the summary
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r -C src Art --case upper | tee add.log
+	grep -q "1 file names changed case" add.log
+	printf 'ART/CRITTERS/HMJMPSAA.FRM\n' | diff - <(names "$format.dat")
+
+	# Test 2: Existing names are left alone without --rename-existing
+	$DAT3 a "$format.dat" -C src ReadMe.txt --case lower
+	printf 'ART/CRITTERS/HMJMPSAA.FRM\nreadme.txt\n' | diff - <(names "$format.dat")
+
+	# Test 3: Replacing matches on the folded name
+	mkdir -p upper
+	cp replacement.txt upper/README.TXT
+	$DAT3 a "$format.dat" -C upper README.TXT --case lower
+	printf 'ART/CRITTERS/HMJMPSAA.FRM\nreadme.txt\n' | diff - <(names "$format.dat")
+	$DAT3 x "$format.dat" -o "${format}_out" readme.txt
+	cmp replacement.txt "${format}_out/readme.txt"
+
+	# Test 4: --rename-existing folds entries already in the archive
+	$DAT3 a "$format.dat" -C src ReadMe.txt --case lower --rename-existing
+	printf 'art/critters/hmjmpsaa.frm\nreadme.txt\n' | diff - <(names "$format.dat")
+	$DAT3 x "$format.dat" -o "${format}_renamed"
+	cmp src/Art/Critters/HmJmpSaa.frm "${format}_renamed/art/critters/hmjmpsaa.frm"
+
+	# Test 5: --rename-existing refuses to merge entries that differ only in case
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_clash.dat" $flag -C src ReadMe.txt --case upper
+	$DAT3 a "${format}_clash.dat" -C src ReadMe.txt --allow-case-dupes
+	if $DAT3 a "${format}_clash.dat" -C upper README.TXT --case lower --rename-existing; then
+		echo "--rename-existing should refuse to merge README.TXT and ReadMe.txt"
+		exit 1
+	fi
+
+	# Test 6: --lowercase on a and c
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_lower.dat" $flag -r -C src Art ReadMe.txt --lowercase
+	printf 'art/critters/hmjmpsaa.frm\nreadme.txt\n' | diff - <(names "${format}_lower.dat")
+	# shellcheck disable=SC2086
+	$DAT3 c "${format}_created.dat" src $flag --lowercase
+	printf 'art/critters/hmjmpsaa.frm\nreadme.txt\n' | diff - <(names "${format}_created.dat")
+
+	# Test 7: Two source files that lowercase to the same name are refused
+	mkdir -p clash/Art clash/art other
+	echo "one" >clash/Art/a.frm
+	echo "two" >clash/art/A.FRM
+	echo "three" >other/README.txt
+	# shellcheck disable=SC2086
+	if $DAT3 c "${format}_clash_c.dat" clash $flag --lowercase; then
+		echo "c --lowercase should refuse Art/a.frm and art/A.FRM"
+		exit 1
+	fi
+	[ ! -e "${format}_clash_c.dat" ]
+	# shellcheck disable=SC2086
+	if $DAT3 a "${format}_clash_a.dat" $flag -r -C clash Art art --lowercase; then
+		echo "a --lowercase should refuse files from two operands with the same name"
+		exit 1
+	fi
+	[ ! -e "${format}_clash_a.dat" ]
+	# shellcheck disable=SC2086
+	if $DAT3 a "${format}_clash_a.dat" $flag src/ReadMe.txt other/README.txt -t text --lowercase; then
+		echo "a --lowercase should refuse ReadMe.txt and README.txt"
+		exit 1
+	fi
+	rm -rf clash other
+done
+
+# Test 8: --rename-existing needs a case to fold to
+if $DAT3 a dat2.dat -C src ReadMe.txt --rename-existing; then
+	echo "--rename-existing without --case or --lowercase should fail"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
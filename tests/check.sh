@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test the archive health report and its --severity threshold
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_check"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+for name in a b z; do
+	seq 1 200 | sed "s/^/$name /" >"$name.txt"
+done
+cp a.txt copy.txt
+
+# Test 1: freshly built archives are clean, also when decompressed
+$DAT3 a clean.dat a.txt b.txt
+$DAT3 a clean1.dat --dat1 a.txt b.txt
+for archive in clean.dat clean1.dat; do
+	$DAT3 check --deep "$archive" >check.log
+	grep -q "2 entries, decompressed: no problems found" check.log
+done
+
+# Test 2: entries out of order are warnings, failing only at the default threshold
+$DAT3 a unsorted.dat z.txt
+$DAT3 a unsorted.dat --keep-order a.txt
+if $DAT3 check unsorted.dat >check.log; then
+	echo "Unsorted archive passed the check"
+	exit 1
+fi
+grep -q "warning \[sort_order\] a.txt" check.log
+$DAT3 check unsorted.dat --severity error >check.log
+
+# Test 3: shared data is only noted
+$DAT3 a shared.dat --dedup a.txt copy.txt
+$DAT3 check shared.dat >check.log
+grep -q "info    \[overlap\]" check.log
+if $DAT3 check shared.dat --severity info >check.log; then
+	echo "Info finding passed --severity info"
+	exit 1
+fi
+
+# Test 4: bytes after the footer are an error, reported as JSON with --json
+cp clean.dat padded.dat
+printf 'JUNKJUNK' >>padded.dat
+if $DAT3 --json check padded.dat --severity error >check.json 2>/dev/null; then
+	echo "Padded archive passed the check"
+	exit 1
+fi
+head -n 1 check.json | grep -q '"findings":\[{"check":"footer","severity":"error"'
+tail -n 1 check.json | grep -q '"command":"check"'
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
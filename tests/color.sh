@@ -0,0 +1,57 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test --color: colors only when asked for, plain text otherwise
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_color"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art
+head -c 4096 /dev/zero >src/art/zeros.frm
+echo "readme" >src/readme.txt
+$DAT3 a test.dat -r src
+
+ESC=$'\x1b'
+
+# Test 1: Piped output has no escape codes, and matches --color never
+$DAT3 l test.dat >auto.log
+if grep -q "$ESC" auto.log; then
+	echo "piped output should not be colored"
+	exit 1
+fi
+$DAT3 l test.dat --color never | diff auto.log -
+
+# Test 2: --color always colors even through a pipe, NO_COLOR doesn't stop it
+$DAT3 l test.dat --color always | grep -q "$ESC"
+NO_COLOR=1 $DAT3 l test.dat --color always | grep -q "$ESC"
+
+# Test 3: Errors are colored only with --color always
+if $DAT3 l missing.dat --color always 2>err.log; then
+	echo "listing a missing archive should fail"
+	exit 1
+fi
+grep -q "$ESC" err.log
+if $DAT3 l missing.dat 2>err.log; then
+	echo "listing a missing archive should fail"
+	exit 1
+fi
+if grep -q "$ESC" err.log; then
+	echo "piped errors should not be colored"
+	exit 1
+fi
+grep -q "^Error: " err.log
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
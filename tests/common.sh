@@ -9,6 +9,10 @@ SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 DAT3="$SCRIPT_DIR/../target/x86_64-unknown-linux-musl/release/dat3"
 export DAT3
 
+# Keep the user's config file out of the tests
+DAT3_CONFIG=/dev/null
+export DAT3_CONFIG
+
 # Helper function to verify a file exists and has content
 verify_file() {
 	if [ ! -f "$1" ]; then
@@ -0,0 +1,25 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that completions prints a script for each shell
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+for shell in bash zsh fish powershell elvish; do
+	$DAT3 completions "$shell" | grep -q dat3
+done
+
+# The bash script is valid bash
+bash -n <($DAT3 completions bash)
+
+# Unknown shells are rejected
+if $DAT3 completions tcsh; then
+	echo "completions should reject an unsupported shell"
+	exit 1
+fi
@@ -0,0 +1,76 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that DAT2 entries with an unknown compression type fail instead of being read as stored
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_compression_type"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+echo "raw bytes of a" >src/a.txt
+for i in $(seq 1 50); do
+	echo "line $i of some compressible text" >>src/b.txt
+done
+
+# Write byte value $3 at offset $2 of file $1
+poke() {
+	# shellcheck disable=SC2059
+	printf "\\x$(printf %02x "$3")" | dd of="$1" bs=1 seek="$2" conv=notrunc 2>/dev/null
+}
+
+# a.txt is stored, b.txt compressed
+$DAT3 a good.dat -c 0 -C src a.txt
+$DAT3 a good.dat -c 9 -C src b.txt
+size=$(stat -c %s good.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 good.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+# First tree entry: file count (4), name size (4), "a.txt" (5), then the compression type
+cp good.dat doctored.dat
+poke doctored.dat $((tree_start + 4 + 4 + 5)) 2
+
+# Test 1: Types 0 and 1 behave as before
+$DAT3 x good.dat -o good_out
+diff -r src good_out
+
+# Test 2: Opening warns, and extracting the entry fails with its type and name
+$DAT3 l doctored.dat 2>list.err
+grep -q "Warning: unsupported compression type 2 in entry a.txt" list.err
+if $DAT3 x doctored.dat -o doctored_out 2>extract.err; then
+	echo "Extracting an entry with compression type 2 should fail"
+	exit 1
+fi
+grep -q "Unsupported compression type 2 in entry a.txt" extract.err
+[ ! -e doctored_out/a.txt ]
+
+# Test 3: --keep-going extracts the other entries
+if $DAT3 x doctored.dat -o kg_out --keep-going; then
+	echo "--keep-going should still fail"
+	exit 1
+fi
+cmp src/b.txt kg_out/b.txt
+
+# Test 4: --force-raw writes the stored bytes
+$DAT3 x doctored.dat -o raw_out --force-raw
+diff -r src raw_out
+$DAT3 e doctored.dat -o raw_flat --force-raw a.txt
+cmp src/a.txt raw_flat/a.txt
+
+# Test 5: Saving the archive keeps the unknown type
+$DAT3 d -y doctored.dat b.txt
+$DAT3 l doctored.dat 2>list.err
+grep -q "Warning: unsupported compression type 2 in entry a.txt" list.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
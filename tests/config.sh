@@ -0,0 +1,70 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test per-user defaults from the config file
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_config"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+head -c 4096 /dev/zero >src/zeros.bin
+echo "sound" >src/voice.wav
+
+cat >config.toml <<EOF
+compression = 0
+store-ext = ["bin"]
+progress = false
+output-dir = "from_config"
+colour = "red"
+EOF
+DAT3_CONFIG="$PWD/config.toml"
+
+# Test 1: config --show prints each value and where it came from
+$DAT3 config --show --threads 2 2>show.err | tee show.log
+grep -q "colour" show.err
+grep -q "compression = 0 .*# .*config.toml" show.log
+grep -q 'store-ext = \["bin"\] .*# .*config.toml' show.log
+grep -q "threads = 2 .*# --threads" show.log
+grep -q 'output-dir = "from_config"' show.log
+
+# Test 2: The file's defaults apply, quietly
+$DAT3 a test.dat src/zeros.bin src/voice.wav | tee add.log
+if grep -q "Adding:" add.log; then
+	echo "progress = false should hide progress messages"
+	exit 1
+fi
+$DAT3 x test.dat
+verify_file from_config/src/zeros.bin
+
+# Test 3: Flags win over the file
+$DAT3 a flags.dat -c 9 --store-ext wav --progress src/zeros.bin | tee add.log
+grep -q "Adding:" add.log
+$DAT3 x flags.dat -o from_flag
+verify_file from_flag/src/zeros.bin
+[ "$(stat -c %s flags.dat)" -lt "$(stat -c %s test.dat)" ]
+
+# Test 4: A missing or invalid $DAT3_CONFIG is an error
+if DAT3_CONFIG="$PWD/missing.toml" $DAT3 l test.dat; then
+	echo "a missing \$DAT3_CONFIG should fail"
+	exit 1
+fi
+echo "compression = 12" >bad.toml
+if DAT3_CONFIG="$PWD/bad.toml" $DAT3 l test.dat; then
+	echo "an invalid compression level should fail"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
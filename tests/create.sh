@@ -0,0 +1,65 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test creating an archive from a directory's contents
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_create"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p source_dir/art/critters source_dir/text/english
+head -c 4096 /dev/zero >source_dir/art/critters/hmjmpsaa.frm
+echo "hello" >source_dir/text/english/readme.txt
+
+# Test 1: The source directory itself is not part of the archive paths
+$DAT3 c new.dat source_dir -c 9
+$DAT3 l new.dat >list.out
+grep -q "art/critters/hmjmpsaa.frm" list.out
+grep -q "text/english/readme.txt" list.out
+if grep -q "source_dir" list.out; then
+	echo "The source directory should be stripped from archive paths"
+	exit 1
+fi
+$DAT3 x new.dat -o out
+cmp out/art/critters/hmjmpsaa.frm source_dir/art/critters/hmjmpsaa.frm
+cmp out/text/english/readme.txt source_dir/text/english/readme.txt
+
+# Test 2: An existing archive is only replaced with --overwrite
+if $DAT3 c new.dat source_dir; then
+	echo "An existing archive should not be replaced without --overwrite"
+	exit 1
+fi
+rm source_dir/text/english/readme.txt
+$DAT3 c new.dat source_dir --overwrite
+$DAT3 l new.dat >list2.out
+if grep -q "readme.txt" list2.out; then
+	echo "--overwrite should start from an empty archive"
+	exit 1
+fi
+
+# Test 3: DAT1 archives
+$DAT3 c new1.dat source_dir --dat1
+$DAT3 x new1.dat -o out1
+cmp out1/art/critters/hmjmpsaa.frm source_dir/art/critters/hmjmpsaa.frm
+
+# Test 4: A missing or empty source fails and writes nothing
+mkdir empty_dir
+if $DAT3 c empty.dat empty_dir; then
+	echo "An empty source directory should fail"
+	exit 1
+fi
+[ ! -e empty.dat ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
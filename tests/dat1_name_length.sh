@@ -0,0 +1,44 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that DAT1 rejects names longer than its one-byte length fields
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_dat1_name_length"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+# A 304-character directory path: "deep", then 10 levels of 29 characters
+long="deep"
+for i in $(seq 0 9); do
+	long="$long/${i}dddddddddddddddddddddddddddd"
+done
+mkdir -p "src/$long"
+echo "deep" >"src/$long/a.txt"
+echo "short" >src/b.txt
+
+# Test 1: Adding fails, naming the path, and writes no archive
+if $DAT3 a --dat1 long.dat -r -C src deep b.txt 2>long.err; then
+	echo "Adding a 304-character DAT1 directory name should fail"
+	exit 1
+fi
+grep -q "its directory name is 304 bytes, DAT1 allows at most 255" long.err
+[ ! -e long.dat ]
+
+# Test 2: DAT2 has no such limit
+$DAT3 a dat2.dat -r -C src deep b.txt
+$DAT3 x dat2.dat -o out
+diff -r src out
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
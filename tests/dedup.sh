@@ -0,0 +1,67 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test --dedup on c and a: identical files share one copy of their data
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_dedup"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p mod/art/critters mod/text more/art
+for i in $(seq 1 12); do
+	seq 1 500 >"mod/art/critters/placeholder$i.frm"
+done
+echo "hello" >mod/text/hello.msg
+seq 1 500 >more/art/copy.frm
+echo "other" >more/art/other.frm
+
+# Test 1: c --dedup writes the placeholder once and says what it saved
+$DAT3 c plain.dat mod
+$DAT3 c dedup.dat mod --dedup >create.log
+grep -q "11 entries share identical data" create.log
+[ "$(stat -c %s dedup.dat)" -lt "$(stat -c %s plain.dat)" ]
+
+# Test 2: Both extract the same files
+$DAT3 x plain.dat -o out_plain
+$DAT3 x dedup.dat -o out_dedup
+diff -r out_plain out_dedup
+diff -r mod out_dedup
+
+# Test 3: Overlapping entries are not unreferenced data, so optimize keeps them
+$DAT3 optimize --dry-run dedup.dat >optimize.log
+grep -q "Nothing to optimize" optimize.log
+
+# Test 4: a --dedup shares new files with the data already in the archive
+before=$(stat -c %s dedup.dat)
+$DAT3 a dedup.dat -C more -r art --dedup >add.log
+# The count is for the whole archive: 11 placeholder copies, and copy.frm
+grep -q "12 entries share identical data" add.log
+# Only other.frm's data and the two tree entries are new
+[ "$(($(stat -c %s dedup.dat) - before))" -lt 100 ]
+$DAT3 x dedup.dat art/copy.frm -o out_copy
+cmp more/art/copy.frm out_copy/art/copy.frm
+
+# Test 5: A plain add keeps the sharing
+before=$(stat -c %s dedup.dat)
+$DAT3 a dedup.dat -C mod text/hello.msg
+[ "$(stat -c %s dedup.dat)" -eq "$before" ]
+
+# Test 6: DAT1 archives store every file, with a warning
+$DAT3 c dat1.dat mod --dat1 --dedup 2>dat1.log
+grep -q "only works on DAT2" dat1.log
+$DAT3 x dat1.dat -o out_dat1
+diff -r mod out_dat1
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,78 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test dedupe on an archive with two entries of the same name
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_dedupe"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+echo "text of a" >src/a.txt
+echo "text of b" >src/b.txt
+echo "text of c" >src/c.txt
+
+# Write byte value $3 at offset $2 of file $1
+poke() {
+	# shellcheck disable=SC2059
+	printf "\\x$(printf %02x "$3")" | dd of="$1" bs=1 seek="$2" conv=notrunc 2>/dev/null
+}
+
+# The fixture: rename c.txt to A.txt in the tree. Each tree entry is a name
+# size (4), the name (5), type (1), real size (4), packed size (4) and
+# offset (4), after the file count (4); the third name starts at 52.
+$DAT3 a clean.dat -c 0 -C src a.txt b.txt c.txt
+size=$(stat -c %s clean.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 clean.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+cp clean.dat dup.dat
+poke dup.dat $((tree_start + 52)) 65
+
+# Test 1: An archive without duplicates has nothing to do
+$DAT3 dedupe clean.dat >clean.log
+grep -q "No duplicate names" clean.log
+
+# Test 2: Opening the fixture warns, and --dry-run reports without writing
+$DAT3 l dup.dat >/dev/null 2>list.err
+grep -q "a.txt and A.txt differ only by case" list.err
+cp dup.dat before.dat
+$DAT3 dedupe dup.dat --dry-run >dry.log
+grep -q "a.txt (2 entries):" dry.log
+grep -q "keep #2 A.txt: 10 bytes" dry.log
+cmp before.dat dup.dat
+
+# Test 3: By default the last entry stays
+cp dup.dat last.dat
+$DAT3 dedupe last.dat >last.log
+grep -q "Removed 1 duplicate entries of 1 names" last.log
+[ "$($DAT3 l --no-summary last.dat | tail -n +3 | wc -l)" -eq 2 ]
+$DAT3 x last.dat -o out_last
+cmp src/c.txt out_last/A.txt
+cmp src/b.txt out_last/b.txt
+
+# Test 4: --keep first keeps the other one
+cp dup.dat first.dat
+$DAT3 dedupe first.dat --keep first
+$DAT3 x first.dat -o out_first
+cmp src/a.txt out_first/a.txt
+
+# Test 5: A deduped archive opens without warnings
+$DAT3 l first.dat >/dev/null 2>first.err
+if grep -q "Warning" first.err; then
+	echo "first.dat should open without warnings"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
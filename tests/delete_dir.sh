@@ -0,0 +1,100 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test deleting a whole directory with a trailing slash in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_delete_dir"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p art/intrface/deep art/interface text
+for i in $(seq 1 30); do
+	echo "frame $i" >"art/intrface/f$i.frm"
+done
+echo "deep" >art/intrface/deep/d.frm
+echo "similar name" >art/interface/keep.frm
+echo "hello" >text/hello.msg
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+# Number of directory records in a DAT1 archive (big-endian u32 at offset 0)
+dat1_dirs() {
+	od -An -tu1 -N4 "$1" | awk '{print $1 * 16777216 + $2 * 65536 + $3 * 256 + $4}'
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r art text
+	cp "$format.dat" "$format.keep.dat"
+
+	# Test 1: A trailing slash deletes everything under the directory
+	$DAT3 d -y "$format.dat" art/intrface/ | tee delete.log
+	grep -q "Deleted 31 entries under art/intrface/" delete.log
+	printf 'art/interface/keep.frm\ntext/hello.msg\n' | diff - <(names "$format.dat")
+
+	# Test 2: Either slash and any case work
+	$DAT3 d -y "$format.keep.dat" 'ART\INTRFACE\DEEP\' | grep -q "Deleted 1 entries"
+
+	# Test 3: An empty prefix needs --force
+	if $DAT3 d -y "$format.dat" /; then
+		echo "Error: '/' should need --force"
+		exit 1
+	fi
+	[ "$(names "$format.dat" | wc -l)" -eq 2 ]
+	$DAT3 d -y "$format.dat" / --force | grep -q "Deleted 2 entries under /"
+	[ "$(names "$format.dat" | wc -l)" -eq 0 ]
+done
+
+# Test 4: DAT1 directory records emptied by the delete go, unless kept
+$DAT3 a dirs.dat --dat1 -r art text
+[ "$(dat1_dirs dirs.dat)" -eq 5 ]
+cp dirs.dat kept.dat
+$DAT3 d -y dirs.dat art/intrface/
+[ "$(dat1_dirs dirs.dat)" -eq 3 ]
+$DAT3 d -y kept.dat art/intrface/ --keep-empty-dirs
+[ "$(dat1_dirs kept.dat)" -eq 5 ]
+
+# Test 5: Deleting by name keeps emptied records; --prune-empty-dirs removes
+# every empty record but the root, including ones that were empty before
+$DAT3 a kept.dat --mkdir sound/music
+[ "$(dat1_dirs kept.dat)" -eq 6 ]
+$DAT3 d -y kept.dat text/hello.msg
+[ "$(dat1_dirs kept.dat)" -eq 6 ]
+$DAT3 d -y kept.dat art/interface/keep.frm --prune-empty-dirs | grep -q "Pruned 5 empty directories"
+[ "$(dat1_dirs kept.dat)" -eq 1 ]
+if $DAT3 d -y kept.dat x --keep-empty-dirs --prune-empty-dirs 2>/dev/null; then exit 1; fi
+
+# Test 6: optimize and dedupe prune too, rewriting the archive only if something went
+$DAT3 a kept.dat --mkdir sound/music
+$DAT3 optimize kept.dat --prune-empty-dirs >prune.log
+grep -q "Pruned 1 empty directories" prune.log
+[ "$(dat1_dirs kept.dat)" -eq 1 ]
+$DAT3 a kept.dat --mkdir sound/music
+$DAT3 dedupe kept.dat --prune-empty-dirs >prune.log
+grep -q "Pruned 1 empty directories" prune.log
+[ "$(dat1_dirs kept.dat)" -eq 1 ]
+cp kept.dat pruned.dat
+$DAT3 optimize kept.dat --prune-empty-dirs >prune.log
+grep -q "Nothing to optimize" prune.log
+cmp kept.dat pruned.dat
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
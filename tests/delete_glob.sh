@@ -0,0 +1,77 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test deleting entries by glob pattern in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_delete_glob"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p sound/sfx text
+for i in $(seq 1 25); do
+	echo "sfx $i" >"sound/sfx/s$i.acm"
+done
+echo "music" >sound/music.acm
+echo "hello" >text/hello.msg
+echo "bye" >text/bye.msg
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r sound text
+
+	# Test 1: A glob deletes every entry it matches, beside exact names
+	$DAT3 d -y "$format.dat" 'text/*.msg' sound/music.acm
+	[ "$(names "$format.dat" | grep -c "^sound/sfx/")" -eq 25 ]
+	[ "$(names "$format.dat" | wc -l)" -eq 25 ]
+
+	# Test 2: A plain name is exact, never a substring
+	if $DAT3 d -y "$format.dat" sfx; then
+		echo "Error: 'sfx' should match no entry"
+		exit 1
+	fi
+
+	# Test 3: A missing pattern fails before anything is deleted
+	if $DAT3 d -y "$format.dat" sound/sfx/s1.acm 'nothing/*'; then
+		echo "Error: a pattern matching nothing should fail"
+		exit 1
+	fi
+	[ "$(names "$format.dat" | wc -l)" -eq 25 ]
+	$DAT3 d -y "$format.dat" sound/sfx/s1.acm 'nothing/*' --ignore-missing
+	[ "$(names "$format.dat" | wc -l)" -eq 24 ]
+
+	# Test 4: Off a terminal, d lists what would go and refuses without -y
+	if $DAT3 d "$format.dat" 'sound/sfx/*' </dev/null >refused.log 2>&1; then
+		echo "Error: deleting off a terminal should need -y"
+		exit 1
+	fi
+	grep -q "Delete 24 entries from $format.dat?" refused.log
+	[ "$(grep -c "^  sound/sfx/" refused.log)" -eq 10 ]
+	grep -q "and 14 more" refused.log
+	grep -q "pass -y" refused.log
+	[ "$(names "$format.dat" | wc -l)" -eq 24 ]
+	$DAT3 --yes d "$format.dat" 'SOUND\SFX\*'
+	[ "$(names "$format.dat" | wc -l)" -eq 0 ]
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,67 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test d with names that aren't in the archive, strict and with --ignore-missing
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_delete_missing"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p text
+for name in one two three; do
+	echo "$name" >"text/$name.txt"
+done
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+printf 'text/one.txt\ntext/stale1.txt\ntext/two.txt\ntext/stale2.txt\n' >delete.txt
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r text
+	cp "$format.dat" before.dat
+
+	# Test 1: By default every missing name is reported and nothing is deleted
+	if $DAT3 d -y "$format.dat" @delete.txt 2>strict.log; then
+		echo "Error: stale names should fail the delete"
+		exit 1
+	fi
+	grep -q "text/stale1.txt" strict.log
+	grep -q "text/stale2.txt" strict.log
+	cmp before.dat "$format.dat"
+
+	# Test 2: --ignore-missing deletes the rest, saves, and still names the stale ones
+	$DAT3 d -y "$format.dat" @delete.txt --ignore-missing 2>ignored.log
+	grep -q "text/stale1.txt" ignored.log
+	grep -q "text/stale2.txt" ignored.log
+	printf 'text/three.txt\n' | diff - <(names "$format.dat")
+
+	# Test 3: ...but fails if nothing at all was deleted
+	cp "$format.dat" before.dat
+	if $DAT3 d -y "$format.dat" text/stale1.txt --ignore-missing; then
+		echo "Error: a delete that deletes nothing should fail"
+		exit 1
+	fi
+	cmp before.dat "$format.dat"
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
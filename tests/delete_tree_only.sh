@@ -0,0 +1,63 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that d rewrites only the DAT2 tree, and --compact the whole archive
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_delete_tree_only"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p art text
+for i in $(seq 1 10); do
+	echo "frame $i frame $i frame $i" >"art/f$i.frm"
+done
+echo "hello" >text/hello.msg
+
+$DAT3 a tree.dat -r art text
+cp tree.dat original.dat
+cp tree.dat compact.dat
+cp tree.dat in_place.dat
+
+# Test 1: The data before the old tree is left untouched
+$DAT3 d -y tree.dat art/f1.frm art/f2.frm
+size=$(stat -c %s original.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 original.dat | tr -d ' ')
+cmp -n $((size - 8 - tree_size)) original.dat tree.dat
+
+# Test 2: --compact rewrites the archive, which then has no gaps
+$DAT3 d -y compact.dat art/f1.frm art/f2.frm --compact
+[ "$(stat -c %s compact.dat)" -lt "$(stat -c %s tree.dat)" ]
+$DAT3 optimize --dry-run compact.dat >compact.log
+grep -q "Unreferenced: 0 bytes" compact.log
+
+# Test 3: Both list and extract the same
+diff <($DAT3 l --no-summary tree.dat) <($DAT3 l --no-summary compact.dat)
+$DAT3 x tree.dat -o out_tree
+$DAT3 x compact.dat -o out_compact
+diff -r out_tree out_compact
+
+# Test 4: optimize reclaims what the tree-only delete left behind
+$DAT3 optimize --dry-run tree.dat >tree.log
+# f10 sorts between f1 and f2, so their data leaves two gaps
+grep -q "in 2 gaps" tree.log
+$DAT3 optimize tree.dat
+cmp tree.dat compact.dat
+
+# Test 5: --in-place writes the same bytes into the archive itself
+$DAT3 d -y in_place.dat art/f1.frm art/f2.frm --in-place
+$DAT3 d -y original.dat art/f1.frm art/f2.frm
+cmp in_place.dat original.dat
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,52 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that du sums sizes by directory and by extension
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_du"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/art/items src/sound
+head -c 4000 /dev/zero >src/art/critters/a.frm
+head -c 1000 /dev/zero >src/art/items/b.frm
+head -c 3000 /dev/urandom >src/sound/m.acm
+$DAT3 a test.dat -r -c 9 -C src art sound
+
+# Test 1: Top-level directories, largest packed size first, then totals
+$DAT3 du test.dat >du.out
+[ "$(sed -n 2p du.out | awk '{print $5}')" = "sound/" ]
+[ "$(sed -n 3p du.out | awk '{print $2, $4, $5}')" = "5000 2 art/" ]
+[ "$(tail -1 du.out | awk '{print $2, $4, $5}')" = "8000 3 Total" ]
+
+# Test 2: --depth and patterns narrow it down
+$DAT3 du test.dat --depth 2 'art/*' >deep.out
+grep -q " 4000 .* 1  art/critters/$" deep.out
+grep -q " 1000 .* 1  art/items/$" deep.out
+[ "$(tail -1 deep.out | awk '{print $2, $4}')" = "5000 2" ]
+
+# Test 3: --by-ext groups by extension
+$DAT3 du test.dat --by-ext >ext.out
+grep -q " 5000 .* 2  .frm$" ext.out
+grep -q " 3000 100.0% .* 1  .acm$" ext.out
+
+# Test 4: Unmatched patterns fail like l
+if $DAT3 du test.dat 'maps/*' 2>missing.err; then
+	echo "An unmatched pattern should fail"
+	exit 1
+fi
+grep -q "maps/\*" missing.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,52 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that a --mkdir adds empty DAT1 directories that list, survive changes and delete
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_empty_dirs"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text
+echo "first message" >src/text/a.msg
+echo "b" >b.msg
+
+$DAT3 c test.dat src --dat1
+
+# Test 1: --mkdir alone adds the directory, and tree and l --by-dir show it
+$DAT3 a test.dat --mkdir data/maps
+$DAT3 tree test.dat >tree.log
+grep -q "^    maps/  (0 files, 0 bytes)$" tree.log
+$DAT3 l --by-dir test.dat >list.log
+grep -qx "data/maps/  0 files, 0 bytes, 0 packed" list.log
+
+# Test 2: An existing directory, in any case, is only a warning
+$DAT3 a test.dat --mkdir 'DATA\Maps' 2>err.log
+grep -q "Directory already exists" err.log
+
+# Test 3: The directory survives adding files elsewhere
+$DAT3 a test.dat b.msg -t text
+$DAT3 tree test.dat | grep -q "maps/"
+
+# Test 4: Deleting the directory removes it
+$DAT3 d -y test.dat data/ >delete.log
+grep -q "Deleted 1 empty directories under data" delete.log
+if $DAT3 tree test.dat | grep -q "maps/"; then exit 1; fi
+
+# Test 5: DAT2 archives can't hold empty directories
+$DAT3 c test2.dat src
+if $DAT3 a test2.dat --mkdir data 2>err.log; then exit 1; fi
+grep -q "DAT2 archives can't hold empty directories" err.log
+
+cd ..
+rm -rf "$TEST_DIR"
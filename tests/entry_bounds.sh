@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that invalid entry locations are reported on open, and fail it with --strict
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_entry_bounds"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+echo "text of a" >src/a.txt
+echo "text of b" >src/b.txt
+
+# Write byte value $3 at offset $2 of file $1
+poke() {
+	# shellcheck disable=SC2059
+	printf "\\x$(printf %02x "$3")" | dd of="$1" bs=1 seek="$2" conv=notrunc 2>/dev/null
+}
+
+$DAT3 a good.dat -c 0 -C src a.txt b.txt
+size=$(stat -c %s good.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 good.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+# First tree entry: file count (4), name size (4), "a.txt" (5), type (1),
+# real size (4), packed size (4), then the offset; move a.txt far past the end
+cp good.dat doctored.dat
+poke doctored.dat $((tree_start + 4 + 4 + 5 + 1 + 4 + 4 + 3)) 127
+
+# Test 1: An intact archive opens without warnings, also with --strict
+$DAT3 l good.dat --strict 2>good.err
+if grep -q "Warning" good.err; then
+	echo "good.dat should open without warnings"
+	exit 1
+fi
+
+# Test 2: By default the problem is a warning, and the other entries extract
+$DAT3 l doctored.dat 2>list.err
+grep -q "Warning: a.txt: data ends at .*, past the end of the archive ($size bytes)" list.err
+if $DAT3 x doctored.dat -o out --keep-going; then
+	echo "Extracting a.txt should still fail"
+	exit 1
+fi
+cmp src/b.txt out/b.txt
+
+# Test 3: --strict fails the open
+if $DAT3 l doctored.dat --strict 2>strict.err; then
+	echo "Opening doctored.dat with --strict should fail"
+	exit 1
+fi
+grep -q "1 entries have invalid data locations" strict.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
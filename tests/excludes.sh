@@ -0,0 +1,62 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that c and a leave out OS junk files, and --exclude/--no-default-excludes
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_excludes"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p mod/art/critters mod/text
+echo "frame data" >mod/art/critters/hmjmps.frm
+echo "hello world" >mod/text/hello.msg
+echo "finder" >mod/.DS_Store
+echo "fork" >mod/art/critters/._hmjmps.frm
+echo "thumbs" >mod/art/THUMBS.DB
+echo "ini" >mod/text/desktop.ini
+echo "old" >mod/text/hello.msg.bak
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+# Test 1: c leaves the junk out and says how much
+$DAT3 c create.dat mod >create.log
+grep -q "4 files skipped by the exclude list" create.log
+printf 'art/critters/hmjmps.frm\ntext/hello.msg\ntext/hello.msg.bak\n' | diff - <(names create.dat)
+
+# Test 2: --exclude extends the list
+$DAT3 c extended.dat mod --exclude '*.bak' >extended.log
+grep -q "5 files skipped by the exclude list" extended.log
+printf 'art/critters/hmjmps.frm\ntext/hello.msg\n' | diff - <(names extended.dat)
+
+# Test 3: a leaves it out too, also inside recursively added directories
+$DAT3 a add.dat -r -C mod art text >add.log
+grep -q "3 files skipped by the exclude list" add.log
+printf 'art/critters/hmjmps.frm\ntext/hello.msg\ntext/hello.msg.bak\n' | diff - <(names add.dat)
+
+# Test 4: Named directly, a junk file is still left out
+$DAT3 a add.dat -C mod text/hello.msg text/desktop.ini >named.log
+grep -q "1 files skipped by the exclude list" named.log
+[ "$(names add.dat | wc -l)" -eq 3 ]
+
+# Test 5: --no-default-excludes adds everything, --exclude still applies
+$DAT3 c everything.dat mod --no-default-excludes --exclude '*.bak'
+names everything.dat >everything.txt
+[ "$(wc -l <everything.txt)" -eq 6 ]
+grep -qx ".DS_Store" everything.txt
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
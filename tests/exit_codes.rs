@@ -0,0 +1,143 @@
+/*!
+Integration tests for dat3's exit codes.
+
+Tests run the binary on representative failures of each category and check
+the code it exits with, and that `--json` reports the same category.
+*/
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use assert_cmd::Command;
+use fallout_dat3::{CompressionLevel, CompressionPolicy, DatArchive};
+use std::fs;
+use std::path::{Path, PathBuf};
+use test_support::TempDir;
+
+/// The dat3 binary, with the user's config file kept out
+fn dat3() -> Command {
+    let mut command = Command::cargo_bin("dat3").unwrap();
+    command.env("DAT3_CONFIG", "/dev/null");
+    command
+}
+
+/// A DAT2 archive with two entries, saved in `dir`
+fn sample_archive(dir: &Path) -> PathBuf {
+    let path = dir.join("sample.dat");
+    let entries = vec![
+        ("art\\a.frm".to_string(), vec![7; 4096]),
+        ("text\\readme.txt".to_string(), b"Hello".to_vec()),
+    ];
+    let policy = CompressionPolicy::new(CompressionLevel::new(9).unwrap());
+    let (archive, _) = DatArchive::from_entries(false, entries, &policy).unwrap();
+    archive.save(&path).unwrap();
+    path
+}
+
+#[test]
+fn success_is_zero() {
+    let dir = TempDir::new("success");
+    let archive = sample_archive(&dir);
+    dat3().arg("l").arg(&archive).assert().code(0);
+}
+
+#[test]
+fn bad_usage_is_two() {
+    let dir = TempDir::new("usage");
+    let archive = sample_archive(&dir);
+    dat3().args(["l", "--no-such-flag"]).assert().code(2);
+    dat3().arg("d").arg(&archive).arg("/").assert().code(2);
+}
+
+#[test]
+fn unreadable_archive_is_three() {
+    let dir = TempDir::new("archive");
+    let garbage = dir.join("garbage.dat");
+    fs::write(&garbage, b"this is not a DAT archive at all").unwrap();
+    dat3().arg("l").arg(&garbage).assert().code(3);
+}
+
+#[test]
+fn missing_entries_are_four() {
+    let dir = TempDir::new("not_found");
+    let archive = sample_archive(&dir);
+    dat3()
+        .arg("x")
+        .arg(&archive)
+        .arg("text/missing.txt")
+        .arg("-o")
+        .arg(dir.join("out"))
+        .assert()
+        .code(4);
+    dat3()
+        .arg("d")
+        .arg(&archive)
+        .arg("text/missing.txt")
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn io_failures_are_five() {
+    let dir = TempDir::new("io");
+    dat3()
+        .arg("l")
+        .arg(dir.join("nonexistent.dat"))
+        .assert()
+        .code(5);
+
+    // Extracting under a regular file can't create the directories
+    let archive = sample_archive(&dir);
+    let blocker = dir.join("blocker");
+    fs::write(&blocker, b"").unwrap();
+    dat3()
+        .arg("x")
+        .arg(&archive)
+        .arg("-o")
+        .arg(&blocker)
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn verification_failures_are_six() {
+    let dir = TempDir::new("verification");
+    let archive = sample_archive(&dir);
+    let out = dir.join("out");
+    dat3()
+        .arg("x")
+        .arg(&archive)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .code(0);
+    fs::write(out.join("text").join("readme.txt"), b"Changed").unwrap();
+    dat3()
+        .arg("verify")
+        .arg(&archive)
+        .arg(&out)
+        .assert()
+        .code(6);
+}
+
+#[test]
+fn json_result_reports_the_exit_category() {
+    let dir = TempDir::new("json");
+    let archive = sample_archive(&dir);
+    let output = dat3()
+        .arg("--json")
+        .arg("x")
+        .arg(&archive)
+        .arg("missing.txt")
+        .arg("-o")
+        .arg(dir.join("out"))
+        .assert()
+        .code(4)
+        .get_output()
+        .stdout
+        .clone();
+    let stdout = String::from_utf8(output).unwrap();
+    let result: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert_eq!(result["category"], "not_found");
+    assert_eq!(result["exit_code"], 4);
+}
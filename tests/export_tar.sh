@@ -0,0 +1,56 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that export --tar writes an archive's entries as a tar stream, to a file or stdout
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_export_tar"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/text
+for _ in $(seq 200); do echo "frame data"; done >src/art/critters/hmjmps.frm
+echo "hello" >src/text/hello.msg
+
+for flag in "" "--dat1"; do
+	rm -rf out test.tar
+	$DAT3 c test.dat src $flag -c 9 --overwrite
+	touch -d 2024-05-06T07:08:09Z test.dat
+
+	# Test 1: A tar stream on stdout unpacks to the original files, with the archive's time
+	$DAT3 export test.dat --tar - 2>export.log | tar -tvf - >list.log
+	grep -q "Exported 2 files" export.log
+	grep -q "rw-r--r--.*2024-05-06 .*art/critters/hmjmps.frm" list.log
+	mkdir out
+	$DAT3 export test.dat --tar - 2>/dev/null | tar -xf - -C out
+	diff -r src out
+
+	# Test 2: A tar file of only the matching entries
+	$DAT3 export test.dat --tar test.tar 'text/*' >export.log
+	grep -q "Exported 1 files" export.log
+	[ "$(tar -tf test.tar)" = text/hello.msg ]
+done
+
+# Test 3: The same archive gives the same stream
+$DAT3 export test.dat --tar - 2>/dev/null >a.tar
+$DAT3 export test.dat --tar - 2>/dev/null >b.tar
+cmp a.tar b.tar
+
+# Test 4: An existing file needs --overwrite, and zip options don't apply
+if $DAT3 export test.dat --tar test.tar 2>err.log; then exit 1; fi
+grep -q "already exists" err.log
+$DAT3 export test.dat --tar test.tar --overwrite >/dev/null
+if $DAT3 export test.dat --tar test.tar --overwrite -c 0 2>err.log; then exit 1; fi
+grep -q "cannot be used with" err.log
+
+cd ..
+rm -rf "$TEST_DIR"
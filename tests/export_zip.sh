@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that export writes an archive's entries into a standard zip file
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_export_zip"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/sound/music src/text
+for _ in $(seq 200); do echo "frame data"; done >src/art/critters/hmjmps.frm
+for _ in $(seq 200); do echo "music"; done >src/sound/music/intro.acm
+echo "hello" >src/text/hello.msg
+
+for flag in "" "--dat1"; do
+	rm -rf out test.zip
+	$DAT3 c test.dat src $flag -c 9 --overwrite
+
+	# Test 1: Every entry goes into the zip with forward slashes, and unzips to the original
+	$DAT3 export test.dat test.zip >export.log
+	grep -q "Exported 3 files" export.log
+	unzip -tq test.zip
+	unzip -Z1 test.zip | sort >names.txt
+	printf 'art/critters/hmjmps.frm\nsound/music/intro.acm\ntext/hello.msg\n' | diff - names.txt
+	unzip -q test.zip -d out
+	diff -r src out
+
+	# Test 2: Store-only extensions are stored, others deflated
+	unzip -v test.zip >listing.txt
+	grep "intro.acm" listing.txt | grep -q "Stored"
+	grep "hmjmps.frm" listing.txt | grep -q "Defl"
+done
+
+# Test 3: Patterns pick entries; unmatched ones fail without writing the zip
+$DAT3 export test.dat frames.zip 'art/*' >frames.log
+[ "$(unzip -Z1 frames.zip)" = "art/critters/hmjmps.frm" ]
+if $DAT3 export test.dat missing.zip art/missing.frm 2>missing.err; then
+	exit 1
+fi
+grep -q "art/missing.frm" missing.err
+[ ! -e missing.zip ]
+
+# Test 4: An existing zip needs --overwrite; -c 0 stores everything
+if $DAT3 export test.dat frames.zip 2>exists.err; then
+	exit 1
+fi
+grep -q "already exists" exists.err
+$DAT3 export test.dat frames.zip -c 0 --overwrite >stored.log
+grep -q "Exported 3 files (0 deflated)" stored.log
+unzip -tq frames.zip
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
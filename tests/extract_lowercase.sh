@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test lowercasing output paths on extraction
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_extract_lowercase"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/Art/Critters src/TEXT clash/art
+echo "critter" >src/Art/Critters/HmJmpSaa.FRM
+echo "readme" >src/TEXT/ReadMe.txt
+echo "one" >clash/art/Foo.FRM
+echo "two" >clash/art/FOO.frm
+
+for flag in "" "--dat1"; do
+	rm -rf out flat out_clash
+	# shellcheck disable=SC2086
+	$DAT3 c test.dat src $flag --overwrite
+	# shellcheck disable=SC2086
+	$DAT3 c clash.dat clash $flag --overwrite
+
+	# Test 1: Every file and directory name is written in lower case
+	$DAT3 x test.dat -o out --lowercase
+	cmp out/art/critters/hmjmpsaa.frm src/Art/Critters/HmJmpSaa.FRM
+	cmp out/text/readme.txt src/TEXT/ReadMe.txt
+	[ ! -e out/Art ]
+
+	# Test 2: Flat extraction lowercases file names
+	$DAT3 e test.dat -o flat --lowercase
+	cmp flat/hmjmpsaa.frm src/Art/Critters/HmJmpSaa.FRM
+
+	# Test 3: Entries that would collide fail before anything is written
+	if $DAT3 x clash.dat -o out_clash --lowercase 2>err.out; then
+		echo "Foo.FRM and FOO.frm should collide"
+		exit 1
+	fi
+	grep -q "would merge art/FOO.frm and art/Foo.FRM" err.out
+	[ ! -e out_clash ]
+
+	# Without --lowercase only the later entry is written, on any file system
+	$DAT3 x clash.dat -o out_clash 2>clash.err
+	grep -q "Skipping an earlier entry named art/FOO.frm; art/Foo.FRM is extracted instead" clash.err
+	cmp out_clash/art/Foo.FRM clash/art/Foo.FRM
+	[ ! -e out_clash/art/FOO.frm ]
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that extract reports requested files that match no entries
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_extract_missing"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art
+echo "one" >src/art/one.txt
+echo "two" >src/art/two.txt
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r -C src art
+
+	for cmd in x e; do
+		out="${format}_${cmd}"
+
+		# Test 1: A typo fails and is listed under "Files not found"
+		if $DAT3 "$cmd" "$format.dat" -o "$out" art/typo.txt 2>"$out.err"; then
+			echo "Extracting a missing file should fail"
+			exit 1
+		fi
+		grep -q "Files not found" "$out.err"
+		grep -q "art/typo.txt" "$out.err"
+
+		# Test 2: Matching files are still extracted, only the missing pattern is reported
+		if $DAT3 "$cmd" "$format.dat" -o "$out" art/one.txt 'nope/*' 2>"$out.err"; then
+			echo "Extracting with an unmatched pattern should fail"
+			exit 1
+		fi
+		grep -q "nope/\*" "$out.err"
+		if grep -q "art/one.txt" "$out.err"; then
+			echo "Matched file reported as missing"
+			exit 1
+		fi
+		find "$out" -name one.txt | grep -q .
+
+		# Test 3: --ignore-missing keeps going and exits 0
+		$DAT3 "$cmd" "$format.dat" -o "$out" art/two.txt art/typo.txt --ignore-missing
+		find "$out" -name two.txt | grep -q .
+	done
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,58 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that x --mtime and e --mtime set the modification time of extracted files
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_extract_mtime"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art src/text
+echo "frame data" >src/art/a.frm
+echo "hello" >src/text/b.msg
+
+# Modification time in seconds since the epoch
+mtime() {
+	stat -c %Y "$1"
+}
+
+for flag in "" "--dat1"; do
+	rm -rf out flat now
+	$DAT3 c test.dat src $flag --overwrite
+	touch -d "2020-02-03T04:05:06Z" test.dat
+
+	# Test 1: archive uses the .dat file's time
+	$DAT3 x test.dat -o out --mtime archive
+	[ "$(mtime out/art/a.frm)" = "$(mtime test.dat)" ]
+	[ "$(mtime out/text/b.msg)" = "$(mtime test.dat)" ]
+
+	# Test 2: A literal time, with an offset
+	$DAT3 e test.dat -o flat --mtime 2024-06-01T14:00:00+02:00
+	[ "$(mtime flat/a.frm)" = "1717243200" ]
+	[ "$(mtime flat/b.msg)" = "1717243200" ]
+
+	# Test 3: now (the default) leaves the time of writing
+	$DAT3 x test.dat -o now --mtime now
+	[ "$(mtime now/art/a.frm)" -gt "$(mtime test.dat)" ]
+done
+
+# Test 4: Anything else is rejected
+if $DAT3 x test.dat -o bad --mtime yesterday 2>err.log; then
+	exit 1
+fi
+grep -q "\-\-mtime takes now, archive or a time" err.log
+[ ! -e bad ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,61 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that x --raw and e --raw write entries' stored bytes without decompressing
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_extract_raw"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art
+for _ in $(seq 200); do echo frame; done >src/art/a.frm
+echo "hi" >src/b.msg
+$DAT3 c test.dat src -c 9
+
+# Test 1: A compressed entry is written as its zlib stream, with a .z suffix
+$DAT3 x test.dat -o out --raw 2>raw.err
+[ ! -e out/art/a.frm ]
+packed=$($DAT3 l --no-summary test.dat | awk '$NF == "art/a.frm" {print $2}')
+[ "$(stat -c %s out/art/a.frm.z)" = "$packed" ]
+# zlib streams start with 0x78
+[ "$(od -An -tx1 -N1 out/art/a.frm.z | tr -d ' ')" = "78" ]
+# Stored entries are written as they are, without a suffix
+cmp out/b.msg src/b.msg
+grep -q "Raw mode: compressed entries were written as stored zlib data with a .z suffix" raw.err
+
+# Test 2: --raw-keep-names keeps the entry's name, and e takes it too
+$DAT3 e test.dat -o flat --raw --raw-keep-names 2>kept.err
+cmp flat/a.frm out/art/a.frm.z
+grep -q "under their own names" kept.err
+
+# Test 3: Without --raw, the entry is decompressed as usual
+$DAT3 x test.dat -o normal art/a.frm 2>normal.err
+cmp normal/art/a.frm src/art/a.frm
+if grep -q "Raw mode" normal.err; then
+	exit 1
+fi
+
+# Test 4: DAT1 stored entries come out unchanged and without a suffix
+$DAT3 c test1.dat src --dat1
+$DAT3 x test1.dat -o out1 --raw 2>dat1.err
+cmp out1/art/a.frm src/art/a.frm
+grep -q "stored LZSS data with a .lzss suffix" dat1.err
+
+# Test 5: --raw-keep-names needs --raw
+if $DAT3 x test.dat -o bad --raw-keep-names 2>err.log; then
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,66 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that x --update and e --update only write files that aren't up to date
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_extract_update"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art src/text
+echo "frame data frame data frame data" >src/art/a.frm
+echo "hello" >src/text/b.msg
+echo "bye" >src/text/c.msg
+
+for flag in "" "--dat1"; do
+	rm -rf out flat
+	$DAT3 c test.dat src $flag --overwrite
+
+	# Test 1: The first run writes everything, the second nothing
+	$DAT3 x test.dat -o out --update >first.log
+	if grep -q "up to date" first.log; then
+		exit 1
+	fi
+	$DAT3 x test.dat -o out --update >second.log
+	grep -q "Wrote 0 files, skipped 3 already up to date" second.log
+
+	# Test 2: A file of another size is rewritten
+	echo "changed to a longer text" >out/text/b.msg
+	$DAT3 x test.dat -o out --update >size.log
+	grep -q "Wrote 1 files, skipped 2 already up to date" size.log
+	cmp out/text/b.msg src/text/b.msg
+
+	# Test 3: Same size, other content: only --checksum rewrites it
+	echo "HELLO" >out/text/b.msg
+	$DAT3 x test.dat -o out --update >same_size.log
+	grep -q "skipped 3 already up to date" same_size.log
+	[ "$(cat out/text/b.msg)" = "HELLO" ]
+	$DAT3 x test.dat -o out --update --checksum >checksum.log
+	grep -q "Wrote 1 files, skipped 2 already up to date" checksum.log
+	cmp out/text/b.msg src/text/b.msg
+
+	# Test 4: e takes it too
+	$DAT3 e test.dat -o flat
+	$DAT3 e test.dat -o flat --update >flat.log
+	grep -q "skipped 3 already up to date" flat.log
+done
+
+# Test 5: --checksum needs --update
+if $DAT3 x test.dat -o out --checksum 2>err.log; then
+	exit 1
+fi
+grep -q "\-\-update" err.log
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
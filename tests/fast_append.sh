@@ -0,0 +1,63 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test a --fast-append against a full rewrite of the same archive
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_fast_append"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p base/art new/text new/art
+for i in $(seq 1 20); do
+	echo "frame $i frame $i frame $i" >"base/art/f$i.frm"
+done
+echo "hello" >new/text/hello.msg
+echo "appended frame" >new/art/zz.frm
+
+$DAT3 a appended.dat -C base -r art
+cp appended.dat rewritten.dat
+cp appended.dat original.dat
+
+# Test 1: The appended archive lists and extracts like a rewritten one
+$DAT3 a appended.dat -C new -r text art --fast-append 2>append.log
+$DAT3 a rewritten.dat -C new -r text art
+if grep -q "Warning" append.log; then
+	echo "Error: adding new names should append"
+	exit 1
+fi
+diff <($DAT3 l --no-summary appended.dat) <($DAT3 l --no-summary rewritten.dat)
+$DAT3 x appended.dat -o out_appended
+$DAT3 x rewritten.dat -o out_rewritten
+diff -r out_appended out_rewritten
+
+# Test 2: The existing data was left in place, up to where the old tree began
+size=$(stat -c %s original.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 original.dat | tr -d ' ')
+cmp -n $((size - 8 - tree_size)) original.dat appended.dat
+
+# Test 3: Replacing an entry falls back to a full rewrite
+mkdir -p replace/art
+echo "replaced" >replace/art/f1.frm
+$DAT3 a appended.dat -C replace art/f1.frm --fast-append 2>replace.log
+grep -q "rewrote the whole archive" replace.log
+[ "$($DAT3 x appended.dat art/f1.frm -o replaced >/dev/null && cat replaced/art/f1.frm)" = "replaced" ]
+
+# Test 4: DAT1 archives are always rewritten
+$DAT3 a dat1.dat --dat1 -C base -r art
+$DAT3 a dat1.dat -C new -r text --fast-append 2>dat1.log
+grep -q "only works on DAT2" dat1.log
+[ "$($DAT3 l --no-summary dat1.dat | tail -n +3 | wc -l)" -eq 21 ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
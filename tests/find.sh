@@ -0,0 +1,53 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that find prints matching entry paths for piping and response files
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_find"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/scripts src/art/critters src/sound
+head -c 200000 /dev/zero >src/scripts/big.int
+head -c 500 /dev/zero >src/scripts/small.int
+head -c 4000 /dev/zero >src/art/critters/a.frm
+head -c 3000 /dev/zero >src/sound/m.acm
+$DAT3 a test.dat -r -c 9 -C src scripts art sound
+
+# Test 1: Patterns and sizes
+[ "$($DAT3 find test.dat '*.int' --min-size 100k)" = "scripts/big.int" ]
+[ "$($DAT3 find test.dat --max-size 4000 | wc -l)" -eq 3 ]
+
+# Test 2: Compression state and directory
+[ "$($DAT3 find test.dat --stored)" = "sound/m.acm" ]
+[ "$($DAT3 find test.dat --compressed --dir ART/Critters)" = "art/critters/a.frm" ]
+
+# Test 3: -0 separates with NUL bytes
+$DAT3 find test.dat '*.int' -0 | xargs -0 -n1 echo >null.out
+[ "$(cat null.out)" = "$(printf 'scripts/big.int\nscripts/small.int')" ]
+
+# Test 4: The output is a response file for x
+$DAT3 find test.dat --dir scripts >scripts.txt
+$DAT3 x test.dat @scripts.txt -o out
+[ -f out/scripts/big.int ] && [ -f out/scripts/small.int ] && [ ! -e out/sound ]
+
+# Test 5: Matching nothing exits with an error
+if $DAT3 find test.dat '*.map' >none.out; then
+	echo "Finding nothing should fail"
+	exit 1
+fi
+[ ! -s none.out ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,49 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test --format on a DAT2 archive whose first bytes look like a DAT1 header
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_format"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+# A complete DAT1 tree: one directory named "." with no files
+mkdir -p files
+printf '\x00\x00\x00\x01\x00\x00\x00\x0a\x00\x00\x00\x00\x00\x00\x00\x00\x01.' >files/a.bin
+printf '\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00' >>files/a.bin
+echo "second entry" >files/b.txt
+
+# Stored uncompressed, a.bin's data comes first in the archive
+$DAT3 a lookalike.dat -C files a.bin b.txt -c 0
+
+# Test 1: Detection takes it for an empty DAT1 archive
+[ "$($DAT3 l --no-summary lookalike.dat | tail -n +3 | wc -l)" -eq 0 ]
+
+# Test 2: --format dat2 reads it as what it is
+$DAT3 --format dat2 l --no-summary lookalike.dat >forced.log
+grep -q "a.bin" forced.log
+grep -q "b.txt" forced.log
+$DAT3 x lookalike.dat --format dat2 -o out
+cmp files/b.txt out/b.txt
+
+# Test 3: A forced format that doesn't parse says which one was forced
+$DAT3 a plain.dat -C files b.txt
+if $DAT3 l plain.dat --format dat1 2>dat1.log; then
+	echo "Error: a DAT2 archive shouldn't open as DAT1"
+	exit 1
+fi
+grep -q "as DAT1 (forced by --format)" dat1.log
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -271,7 +271,7 @@ echo ""
 echo "=== Test 9: Glob pattern filtering for list command ==="
 
 # Create a test archive with various file types
-"$DAT3" a test9.dat patch000/
+"$DAT3" a test9.dat -r patch000/
 
 # Test Linux - list only .txt files using glob
 echo "Testing Linux glob filter: *.txt"
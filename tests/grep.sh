@@ -0,0 +1,51 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that grep finds lines inside entries without extracting them
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_grep"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text/dialog src/art
+printf '{100}{}{Hi, I am Tandi.}\r\n{101}{}{Bye.}\r\n{102}{}{TANDI waves.}\r\n' >src/text/dialog/tandi.msg
+printf '{1}{}{Price: 5 caps}\n' >src/text/misc.msg
+printf 'Tandi notes\n' >src/text/notes.txt
+printf 'FRM\0\0Tandi\0' >src/art/tandi.frm
+$DAT3 a test.dat -r -c 9 -C src text art
+
+# Test 1: Literal search prints entry:line:text, skipping binary entries
+$DAT3 grep test.dat Tandi --include '*.msg' >literal.out
+[ "$(cat literal.out)" = "text/dialog/tandi.msg:1:{100}{}{Hi, I am Tandi.}" ]
+
+# Test 2: -i, -l and --exclude
+$DAT3 grep test.dat tandi -i -l --exclude '*.msg' >names.out
+[ "$(cat names.out)" = "text/notes.txt" ]
+[ "$($DAT3 grep test.dat tandi -i --include '*.msg' | cut -d: -f2 | tr '\n' ' ')" = "1 3 " ]
+
+# Test 3: --regex
+$DAT3 grep test.dat -E 'Price: [0-9]+ caps' | grep -q "^text/misc.msg:1:"
+
+# Test 4: --binary searches binary entries too
+$DAT3 grep test.dat Tandi --binary --include '*.frm' | grep -qx "Binary entry art/tandi.frm matches"
+
+# Test 5: No matches is an error
+if $DAT3 grep test.dat Marcus 2>none.err; then
+	echo "A search without matches should fail"
+	exit 1
+fi
+grep -q "No matches for Marcus" none.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
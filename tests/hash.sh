@@ -0,0 +1,68 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that hash prints sha256sum-compatible manifests and checks archives against them
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_hash"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text src/art
+for i in $(seq 1 20); do
+	seq 1 "$((i * 50))" >"src/text/file$i.txt"
+done
+head -c 5000 /dev/zero >src/art/blank.frm
+$DAT3 a test.dat -r -c 9 -C src text art
+
+# Test 1: The manifest verifies the extracted files with sha256sum
+$DAT3 hash test.dat >manifest.txt
+[ "$(wc -l <manifest.txt)" -eq 21 ]
+$DAT3 x test.dat -o out
+(cd out && sha256sum -c --quiet ../manifest.txt)
+
+# Test 2: The archive matches its own manifest, and sha256sum's of the sources
+$DAT3 hash test.dat --check manifest.txt | grep -q "21 matched, 0 mismatched"
+(cd src && sha256sum text/* art/*) >source.txt
+$DAT3 hash test.dat --check source.txt
+
+# Test 3: Patterns select entries, for hashing and for checking
+$DAT3 hash test.dat 'art/*' >art.txt
+[ "$(wc -l <art.txt)" -eq 1 ]
+grep -q "  art/blank.frm$" art.txt
+$DAT3 hash test.dat 'art/*' --check manifest.txt
+
+# Test 4: Mismatched, missing and extra entries fail the check
+grep -v "file3.txt" manifest.txt | sed 's/^[0-9a-f]\{8\}/00000000/' >bad.txt
+echo "$(sha256sum src/art/blank.frm | cut -d' ' -f1)  maps/gone.map" >>bad.txt
+if $DAT3 hash test.dat --check bad.txt >check.out; then
+	echo "A bad manifest should fail the check"
+	exit 1
+fi
+grep -q "text/file1.txt: FAILED$" check.out
+grep -q "maps/gone.map: MISSING" check.out
+grep -q "text/file3.txt: EXTRA" check.out
+grep -q "0 matched, 20 mismatched, 0 unreadable, 1 missing, 1 extra" check.out
+
+# Test 5: CRC-32 manifests
+$DAT3 hash test.dat --algo crc32 >crc.txt
+grep -q "^[0-9a-f]\{8\}  art/blank.frm$" crc.txt
+$DAT3 hash test.dat --algo crc32 --check crc.txt
+if $DAT3 hash test.dat --algo crc32 --check manifest.txt 2>algo.err; then
+	echo "A sha256 manifest should not parse as crc32"
+	exit 1
+fi
+grep -q "is not a crc32 checksum" algo.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
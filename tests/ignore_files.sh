@@ -0,0 +1,73 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test .dat3ignore files when c and a walk directories
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_ignore_files"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p mod/scripts/templates mod/docs mod/art
+echo "compiled script" >mod/scripts/a.int
+echo "script source" >mod/scripts/a.ssl
+echo "shipped source" >mod/scripts/global.ssl
+echo "template" >mod/scripts/templates/t.int
+echo "documentation" >mod/docs/readme.txt
+echo "frame data" >mod/art/a.frm
+printf '# Sources and docs stay out\n*.ssl\n/docs/\n' >mod/.dat3ignore
+printf '!global.ssl\ntemplates/\n' >mod/scripts/.dat3ignore
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+expected='art/a.frm
+scripts/a.int
+scripts/global.ssl'
+
+# Test 1: c honors both ignore files and leaves the ignore files out
+$DAT3 c create.dat mod >create.log
+grep -q "3 files or directories skipped by .dat3ignore files" create.log
+diff <(echo "$expected") <(names create.dat)
+
+# Test 2: So does a -r, relative to each added directory
+$DAT3 a add.dat -r -C mod scripts art docs >add.log
+grep -q "1 files or directories skipped by .dat3ignore files" add.log
+# mod/.dat3ignore is above the added directories, so only templates is skipped
+printf 'art/a.frm\ndocs/readme.txt\nscripts/a.int\nscripts/a.ssl\nscripts/global.ssl\n' |
+	diff - <(names add.dat)
+
+# Test 3: Adding the root honors its ignore file
+$DAT3 a root.dat -r mod/ >root.log
+grep -q "3 files or directories skipped" root.log
+
+# Test 4: --no-ignore-files adds everything, ignore files included
+$DAT3 c all.dat mod --no-ignore-files >all.log
+if grep -q "skipped by .dat3ignore" all.log; then
+	echo "Error: --no-ignore-files shouldn't skip anything"
+	exit 1
+fi
+[ "$(names all.dat | wc -l)" -eq 8 ]
+
+# Test 5: An invalid pattern fails the add and names the file
+printf '[a\n' >mod/art/.dat3ignore
+if $DAT3 c bad.dat mod 2>bad.err; then
+	echo "Error: an invalid pattern should fail"
+	exit 1
+fi
+grep -q "Invalid ignore file" bad.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that import adds a zip's files to an archive, replacing same-named entries
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_import_zip"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p base/text mod/art/critters mod/text mod/__MACOSX/text
+echo "old" >base/text/hello.msg
+echo "kept" >base/text/other.msg
+for _ in $(seq 200); do echo "frame data"; done >mod/art/critters/hmjmps.frm
+echo "new" >mod/text/hello.msg
+echo "junk" >mod/__MACOSX/text/._hello.msg
+(cd mod && zip -qr ../mod.zip .)
+
+for flag in "" "--dat1"; do
+	rm -rf out
+	$DAT3 c test.dat base $flag --overwrite
+
+	# Test 1: Files are added with their directories, replacing the existing entry;
+	# directory entries and excluded junk are left out
+	$DAT3 import test.dat mod.zip -c 9 >import.log
+	grep -q "Added 2 files" import.log
+	$DAT3 l test.dat >names.log
+	grep -q "^3 files" names.log
+	grep -q " art/critters/hmjmps.frm$" names.log
+	if grep -q MACOSX names.log; then exit 1; fi
+	$DAT3 x test.dat -o out
+	[ "$(cat out/text/hello.msg)" = new ]
+	[ "$(cat out/text/other.msg)" = kept ]
+	cmp out/art/critters/hmjmps.frm mod/art/critters/hmjmps.frm
+done
+
+# Test 2: -t puts the files under a directory, and a missing archive is created
+rm -f new.dat
+$DAT3 import new.dat mod.zip -t data >/dev/null
+$DAT3 l new.dat >names.log
+grep -q " data/text/hello.msg$" names.log
+
+# Test 3: --backup keeps the archive as it was
+cp test.dat before.dat
+$DAT3 import test.dat mod.zip --backup >/dev/null
+cmp test.dat.bak before.dat
+
+# Test 4: A file that isn't a zip fails without touching the archive
+echo "not a zip" >bad.zip
+if $DAT3 import test.dat bad.zip 2>err.log; then exit 1; fi
+grep -q "Not a zip file" err.log
+cmp test.dat before.dat
+
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,50 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test the --json terminal result object on success and failure
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_json_result"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+echo "one" >one.txt
+echo "two" >two.txt
+
+# Test 1: Successful add reports counts and a saved archive
+$DAT3 a test.dat one.txt two.txt --json-to add.json
+grep -q '"status":"ok"' add.json
+grep -q '"added":2' add.json
+grep -q '"saved":true' add.json
+
+# Test 2: The result object is the last line of stdout with --json
+$DAT3 l test.dat --json | tail -n 1 | grep -q '"listed":2'
+
+# Test 2b: l prints its listing as one JSON line first, with each entry's tree index
+$DAT3 l test.dat --json | head -n 1 | grep -q '"index":0,"name":"one.txt"'
+$DAT3 l test.dat two.txt --json | head -n 1 | grep -q '"index":1,"name":"two.txt"'
+
+# Test 3: A failing delete still emits a result with the error chain and exit code.
+# Every name is resolved first, so nothing is deleted.
+if $DAT3 d -y test.dat one.txt missing.txt --json-to delete.json; then
+	echo "Error: Delete of a missing file should fail"
+	exit 1
+fi
+grep -q '"status":"error"' delete.json
+grep -q '"deleted":0' delete.json
+grep -q '"saved":false' delete.json
+grep -q '"category":"not_found","exit_code":4' delete.json
+grep -q 'Some requested files were not found' delete.json
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
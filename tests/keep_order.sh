@@ -0,0 +1,71 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that --keep-order preserves the existing entry order across saves
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_keep_order"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+for name in a b c m z; do
+	echo "$name" >"src/$name.txt"
+done
+
+# Print entry names in tree order
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}'
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Build an unsorted archive: z, m first, then a appended with --keep-order
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -C src z.txt m.txt
+	$DAT3 a "$format.dat" --keep-order -C src a.txt
+	names "$format.dat" >before.txt
+	if [ "$format" = "dat2" ]; then
+		printf 'm.txt\nz.txt\na.txt\n' | diff - before.txt
+	fi
+
+	# Test 1: Replacing an existing entry is a no-op for the order
+	echo "new m" >src/m.txt
+	$DAT3 a "$format.dat" --keep-order -C src m.txt
+	names "$format.dat" | diff before.txt -
+	$DAT3 x "$format.dat" -o "${format}_out" m.txt
+	cmp src/m.txt "${format}_out/m.txt"
+
+	# Test 2: New entries are appended in operand order
+	$DAT3 a "$format.dat" --keep-order -C src c.txt b.txt
+	{
+		cat before.txt
+		printf 'c.txt\nb.txt\n'
+	} | diff - <(names "$format.dat")
+
+	# Test 3: Delete keeps the remaining order
+	$DAT3 d -y "$format.dat" z.txt
+	grep -v z.txt before.txt | head -n 2 | diff - <(names "$format.dat" | head -n 2)
+	echo "m" >src/m.txt
+done
+
+# Test 4: Without the flag DAT2 entries are re-sorted
+$DAT3 a dat2.dat -C src m.txt
+printf 'a.txt\nb.txt\nc.txt\nm.txt\n' | diff - <(names dat2.dat)
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,79 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that --lenient opens DAT2 archives whose footer records the wrong size
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_lenient"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+echo "text of a" >src/a.txt
+for i in $(seq 1 50); do
+	echo "line $i of some compressible text" >>src/b.txt
+done
+
+# Write byte value $3 at offset $2 of file $1
+poke() {
+	# shellcheck disable=SC2059
+	printf "\\x$(printf %02x "$3")" | dd of="$1" bs=1 seek="$2" conv=notrunc 2>/dev/null
+}
+
+$DAT3 a good.dat -c 9 -C src a.txt b.txt
+size=$(stat -c %s good.dat)
+# The recorded size is the last 4 bytes of the footer; bump its low byte
+cp good.dat doctored.dat
+byte=$(od -An -tu1 -j $((size - 4)) -N1 doctored.dat | tr -d ' ')
+poke doctored.dat $((size - 4)) $(((byte + 1) & 0xff))
+
+# Test 1: Strict mode fails and suggests --lenient
+if $DAT3 l doctored.dat 2>strict.err; then
+	echo "Opening doctored.dat without --lenient should fail"
+	exit 1
+fi
+grep -q "use --lenient" strict.err
+grep -q "DAT size mismatch" strict.err
+
+# Test 2: --lenient lists and extracts with a warning
+$DAT3 l doctored.dat --lenient 2>list.err
+grep -q "Warning: DAT size mismatch: footer records .* bytes, file has $size" list.err
+$DAT3 x doctored.dat -o out --lenient
+diff -r src out
+
+# Test 3: An intact archive opens without a warning
+$DAT3 l good.dat --lenient 2>good.err
+if grep -q "Warning" good.err; then
+	echo "good.dat should open without warnings"
+	exit 1
+fi
+
+# Test 4: Entries past the end of the file are still rejected
+cp doctored.dat outside.dat
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 good.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+# First tree entry: file count (4), name size (4), "a.txt" (5), type (1),
+# real size (4), packed size (4), then the offset; point it far past the end
+poke outside.dat $((tree_start + 4 + 4 + 5 + 1 + 4 + 4 + 3)) 127
+if $DAT3 l outside.dat --lenient 2>outside.err; then
+	echo "Opening outside.dat should fail even with --lenient"
+	exit 1
+fi
+grep -q "extends past the end of the file" outside.err
+
+# Test 5: Saving writes the correct size
+$DAT3 d -y doctored.dat b.txt --lenient
+$DAT3 l doctored.dat
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
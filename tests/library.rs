@@ -0,0 +1,1019 @@
+/*!
+Integration tests for the library API.
+
+Tests build, save, reopen, read and extract archives in both formats through
+the public interface only, and check that work is reported as events.
+*/
+
+#[path = "../src/test_support.rs"]
+mod test_support;
+
+use fallout_dat3::{
+    AddOptions, CompressionLevel, CompressionPolicy, DatArchive, DatError, EntryProblemKind, Event,
+    ExtractOptions, ExtractionMode, OpenOptions, Progress,
+};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use test_support::TempDir;
+
+fn policy() -> CompressionPolicy {
+    CompressionPolicy::new(CompressionLevel::new(9).unwrap())
+}
+
+fn sample_entries() -> Vec<(String, Vec<u8>)> {
+    vec![
+        ("art\\critters\\a.frm".to_string(), vec![7; 4096]),
+        ("text\\english\\readme.txt".to_string(), b"Hello".to_vec()),
+    ]
+}
+
+/// Progress whose events are rendered to strings for inspection
+fn recording_progress() -> (Progress, Arc<Mutex<Vec<String>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let progress = Progress::with_events(move |event| {
+        let line = match event {
+            Event::Adding { name } => format!("add {name}"),
+            Event::Deleting { name } => format!("delete {name}"),
+            Event::Added { name, bytes } => format!("added {name} {bytes}"),
+            Event::AddStarted { total } => format!("adding {total}"),
+            Event::ExtractStarted { total } => format!("extract {total}"),
+            Event::EntryExtracted {
+                name,
+                bytes,
+                outcome,
+            } => format!("extracted {name} {bytes} {outcome:?}"),
+            Event::NotFound { patterns } => format!("missing {}", patterns.join(",")),
+            _ => return,
+        };
+        sink.lock().unwrap().push(line);
+    });
+    (progress, events)
+}
+
+#[test]
+fn both_formats_round_trip_through_disk() {
+    let dir = TempDir::new("round_trip");
+
+    for dat1 in [false, true] {
+        let path = dir.join(format!("dat1_{dat1}.dat"));
+        let (archive, stats) = DatArchive::from_entries(dat1, sample_entries(), &policy()).unwrap();
+        assert_eq!(stats.files_added, 2);
+        archive.save(&path).unwrap();
+
+        let reopened = DatArchive::open(&path).unwrap();
+        assert_eq!(reopened.is_dat1(), dat1);
+        assert_eq!(
+            reopened.entry_names(),
+            ["art\\critters\\a.frm", "text\\english\\readme.txt"]
+        );
+        assert_eq!(
+            reopened.read_file("text/english/readme.txt").unwrap(),
+            b"Hello"
+        );
+        assert!(reopened.read_file("art/critters/b.frm").is_err());
+        assert_eq!(
+            reopened.read_file("art\\critters\\a.frm").unwrap().len(),
+            4096
+        );
+    }
+}
+
+/// Windows-1252 names read as UTF-8 and are saved as the bytes they were
+/// read as; a name with a character cp1252 lacks fails the save instead of
+/// being written as UTF-8
+#[test]
+fn cp1252_names_round_trip() {
+    let dir = TempDir::new("names");
+    let path = dir.join("resaved.dat");
+
+    let original = dat2_with_name_fields(&[
+        (b"art\\m\xfcller.frm", b"first entry"),
+        (b"text\\\x80uro.txt", b"second entry"),
+    ]);
+    let archive = DatArchive::from_bytes(original.clone()).unwrap();
+    assert_eq!(
+        archive.read_file("art/m\u{fc}ller.frm").unwrap(),
+        b"first entry"
+    );
+    assert!(archive.entry("text\\\u{20AC}uro.txt").is_some());
+    archive.save(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    for dat1 in [false, true] {
+        let bytes = sample_bytes(&dir, dat1);
+        DatArchive::from_bytes(bytes.clone())
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        let entries = vec![("art\\m\u{fc}ller.frm".to_string(), vec![1; 16])];
+        let (archive, _) = DatArchive::from_entries(dat1, entries, &policy()).unwrap();
+        archive.save(&path).unwrap();
+        let saved = fs::read(&path).unwrap();
+        assert!(saved.windows(10).any(|window| window == b"m\xfcller.frm"));
+        let reopened = DatArchive::from_bytes(saved.clone()).unwrap();
+        assert_eq!(
+            reopened.read_file("art/m\u{fc}ller.frm").unwrap(),
+            vec![1; 16]
+        );
+        reopened.save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), saved);
+
+        let entries = vec![("art\\\u{0416}.frm".to_string(), vec![1; 16])];
+        let (archive, _) = DatArchive::from_entries(dat1, entries, &policy()).unwrap();
+        let err = archive.save(dir.join("utf8.dat")).unwrap_err();
+        assert!(format!("{err:#}").contains("Windows-1252 lacks"));
+    }
+}
+
+#[test]
+fn entries_span_both_formats() {
+    for dat1 in [false, true] {
+        let (archive, _) = DatArchive::from_entries(dat1, sample_entries(), &policy()).unwrap();
+
+        assert_eq!(archive.len(), 2);
+        assert!(!archive.is_empty());
+        let paths: Vec<String> = archive.entries().map(|entry| entry.path()).collect();
+        assert_eq!(paths, ["art/critters/a.frm", "text/english/readme.txt"]);
+        let sizes: Vec<u32> = archive.entries().map(|entry| entry.size).collect();
+        assert_eq!(sizes, [4096, 5]);
+
+        assert!(archive.contains("ART\\Critters\\A.FRM"));
+        assert!(archive.contains("text/english/readme.txt"));
+        assert!(!archive.contains("text/english"));
+    }
+}
+
+#[test]
+fn find_entries_reports_unmatched_patterns() {
+    let (archive, _) = DatArchive::from_entries(false, sample_entries(), &policy()).unwrap();
+
+    let (found, missing) =
+        archive.find_entries(&["art/*/*.frm".to_string(), "nope.txt".to_string()]);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].name, "art\\critters\\a.frm");
+    assert_eq!(missing, ["nope.txt"]);
+
+    let (all, missing) = archive.find_entries(&[]);
+    assert_eq!(all.len(), 2);
+    assert!(missing.is_empty());
+}
+
+/// Saved bytes of a sample archive
+fn sample_bytes(dir: &std::path::Path, dat1: bool) -> Vec<u8> {
+    let path = dir.join(format!("sample_{dat1}.dat"));
+    let (archive, _) = DatArchive::from_entries(dat1, sample_entries(), &policy()).unwrap();
+    archive.save(&path).unwrap();
+    fs::read(&path).unwrap()
+}
+
+#[test]
+fn read_api_errors_are_typed() {
+    let dir = TempDir::new("errors");
+
+    let err = DatArchive::open(dir.join("missing.dat")).unwrap_err();
+    assert!(matches!(err, DatError::Io { .. }));
+    assert!(err.to_string().starts_with("Failed to read DAT file: "));
+
+    for dat1 in [false, true] {
+        let bytes = sample_bytes(&dir, dat1);
+        let archive = DatArchive::from_bytes(bytes.clone()).unwrap();
+        let err = archive.read_file("text/english/missing.txt").unwrap_err();
+        assert!(matches!(
+            err,
+            DatError::EntryNotFound { ref name } if name == "text/english/missing.txt"
+        ));
+        assert_eq!(err.to_string(), "File not found: text/english/missing.txt");
+
+        // Cut off inside the DAT1 directory names or before the DAT2 footer,
+        // whose recorded size then no longer matches
+        let truncated = bytes[..20].to_vec();
+        let err = DatArchive::from_bytes(truncated).unwrap_err();
+        if dat1 {
+            assert!(matches!(err, DatError::CorruptTree { .. }));
+        } else {
+            assert!(matches!(
+                err,
+                DatError::FooterSizeMismatch { actual: 20, .. }
+            ));
+        }
+    }
+}
+
+#[test]
+fn dat2_entry_errors_are_typed() {
+    let dir = TempDir::new("dat2_errors");
+    let bytes = sample_bytes(&dir, false);
+    let name = b"art\\critters\\a.frm";
+    // In the tree, the compression type byte follows the name, then the real size
+    let type_at = bytes
+        .windows(name.len())
+        .rposition(|window| window == name)
+        .unwrap()
+        + name.len();
+
+    let mut wrong_size = bytes.clone();
+    wrong_size[type_at + 1..type_at + 5].copy_from_slice(&4097u32.to_le_bytes());
+    let archive = DatArchive::from_bytes(wrong_size).unwrap();
+    let err = archive.read_file("art/critters/a.frm").unwrap_err();
+    assert!(matches!(
+        err,
+        DatError::SizeMismatch {
+            expected: 4097,
+            actual: 4096,
+            ..
+        }
+    ));
+
+    // An unknown compression type opens, but the entry can't be read
+    let mut unknown_type = bytes;
+    unknown_type[type_at] = 7;
+    let archive = DatArchive::from_bytes(unknown_type).unwrap();
+    let entry = archive.entries().next().unwrap();
+    assert_eq!(entry.unsupported_compression, Some(7));
+    assert!(!entry.compressed);
+    let err = archive.read_file("art/critters/a.frm").unwrap_err();
+    assert!(matches!(
+        err,
+        DatError::UnsupportedCompression { kind: 7, .. }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Unsupported compression type 7 in entry art/critters/a.frm"
+    );
+    assert_eq!(
+        archive.read_file("text/english/readme.txt").unwrap(),
+        b"Hello"
+    );
+}
+
+#[test]
+fn dat1_lzss_entries_are_bounded() {
+    let dir = TempDir::new("dat1_lzss");
+    // "hello" as one LZSS block of literals, stored; then the tree is
+    // patched to mark it compressed
+    let block = [0x00, 0x06, 0xFF, b'h', b'e', b'l', b'l', b'o', 0x00, 0x00];
+    let path = dir.join("lzss.dat");
+    let entries = vec![("hello.txt".to_string(), block.to_vec())];
+    let (archive, _) = DatArchive::from_entries(true, entries, &policy()).unwrap();
+    archive.save(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    // Attributes, offset, real size and packed size follow the name
+    let fields_at = bytes
+        .windows(9)
+        .rposition(|window| window == b"hello.txt")
+        .unwrap()
+        + 9;
+    let with_size = |size: u32| {
+        let mut patched = bytes.clone();
+        patched[fields_at..fields_at + 4].copy_from_slice(&0x40u32.to_be_bytes());
+        patched[fields_at + 8..fields_at + 12].copy_from_slice(&size.to_be_bytes());
+        patched[fields_at + 12..fields_at + 16].copy_from_slice(&10u32.to_be_bytes());
+        DatArchive::from_bytes(patched).unwrap()
+    };
+
+    assert_eq!(with_size(5).read_file("hello.txt").unwrap(), b"hello");
+
+    // Decoding stops at the recorded size instead of running on
+    let err = with_size(4).read_file("hello.txt").unwrap_err();
+    assert!(matches!(err, DatError::CorruptData { ref name, .. } if name == "hello.txt"));
+    assert_eq!(
+        std::error::Error::source(&err).unwrap().to_string(),
+        "Output exceeds the limit of 4 bytes in block 0 at offset 0"
+    );
+
+    // A stream that ends short of the recorded size is a size mismatch,
+    // whether read or extracted
+    let short = with_size(6);
+    let err = short.read_file("hello.txt").unwrap_err();
+    assert!(matches!(
+        err,
+        DatError::SizeMismatch { ref name, expected: 6, actual: 5 } if name == "hello.txt"
+    ));
+    let options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+    let err = short
+        .extract(dir.join("out"), &[], &options, &Progress::default())
+        .unwrap_err();
+    assert!(matches!(
+        DatError::find(&err),
+        Some(DatError::SizeMismatch {
+            expected: 6,
+            actual: 5,
+            ..
+        })
+    ));
+}
+
+/// Bytes appended after the footer, like a self-extractor's padding, are
+/// found, warned about and dropped on save
+#[test]
+fn dat2_trailing_bytes_after_the_footer() {
+    let dir = TempDir::new("trailing");
+    let bytes = sample_bytes(&dir, false);
+    for junk in [vec![0; 512], vec![0xAB; 3000]] {
+        let mut padded = bytes.clone();
+        padded.extend(&junk);
+
+        let archive = DatArchive::from_bytes(padded.clone()).unwrap();
+        let DatArchive::Dat2(dat2) = &archive else {
+            panic!("not a DAT2 archive");
+        };
+        assert_eq!(dat2.trailing_bytes(), Some(junk.len()));
+        assert!(archive.warnings().contains(&format!(
+            "{} bytes after the DAT2 footer were ignored; saving drops them",
+            junk.len()
+        )));
+        assert_eq!(archive.len(), 2);
+        for (name, data) in sample_entries() {
+            assert_eq!(archive.read_file(&name).unwrap(), data);
+        }
+
+        let path = dir.join("clean.dat");
+        archive.save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+    }
+
+    // Past the search window the footer size mismatch stands
+    let mut padded = bytes.clone();
+    padded.extend(vec![0; 70 * 1024]);
+    assert!(matches!(
+        DatArchive::from_bytes(padded).unwrap_err(),
+        DatError::FooterSizeMismatch { .. }
+    ));
+}
+
+/// A DAT2 archive of stored entries whose name fields are given as bytes,
+/// the way other tools lay them out: data, tree, footer
+fn dat2_with_name_fields(entries: &[(&[u8], &[u8])]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = entries.iter().flat_map(|(_, data)| data.to_vec()).collect();
+    let tree_start = bytes.len();
+    bytes.extend((entries.len() as u32).to_le_bytes());
+    let mut offset = 0u32;
+    for (name, data) in entries {
+        bytes.extend((name.len() as u32).to_le_bytes());
+        bytes.extend(*name);
+        bytes.push(0); // stored
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(offset.to_le_bytes());
+        offset += data.len() as u32;
+    }
+    let tree_size = (bytes.len() - tree_start) as u32;
+    bytes.extend(tree_size.to_le_bytes());
+    bytes.extend((bytes.len() as u32 + 4).to_le_bytes());
+    bytes
+}
+
+/// Names stored with a trailing NUL read without it, and `nul_names`
+/// saves them with it again, byte for byte
+#[test]
+fn dat2_nul_terminated_names_round_trip() {
+    let dir = TempDir::new("nul_names");
+    let original = dat2_with_name_fields(&[
+        (b"text\\a.txt\0", b"first entry"),
+        (b"b.txt", b"second entry"),
+    ]);
+    let path = dir.join("nul.dat");
+    let nul_names = OpenOptions {
+        nul_names: true,
+        ..OpenOptions::default()
+    };
+
+    let archive = DatArchive::from_bytes(original.clone()).unwrap();
+    let entries: Vec<(&str, bool)> = archive
+        .entries()
+        .map(|file| (file.name.as_str(), file.nul_terminated_name))
+        .collect();
+    assert_eq!(entries, [("text\\a.txt", true), ("b.txt", false)]);
+    assert_eq!(archive.read_file("text/a.txt").unwrap(), b"first entry");
+
+    // By default the NUL is dropped
+    archive.save(&path).unwrap();
+    let saved = fs::read(&path).unwrap();
+    assert_eq!(saved.len(), original.len() - 1);
+    assert_eq!(DatArchive::open(&path).unwrap().len(), 2);
+
+    let archive = DatArchive::from_bytes_with(original.clone(), &nul_names).unwrap();
+    archive.save(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    // Added entries are saved without one
+    let mut archive = DatArchive::from_bytes_with(original.clone(), &nul_names).unwrap();
+    let contents = vec![("c.txt".to_string(), b"third entry".to_vec())];
+    archive
+        .add_contents(contents, &AddOptions::new(policy()), &Progress::default())
+        .unwrap();
+    archive.save(&path).unwrap();
+    let saved = fs::read(&path).unwrap();
+    assert!(saved.windows(11).any(|window| window == b"text\\a.txt\0"));
+    let reopened = DatArchive::open_with(&path, &nul_names).unwrap();
+    assert!(!reopened.entry("c.txt").unwrap().nul_terminated_name);
+    assert_eq!(reopened.read_file("text/a.txt").unwrap(), b"first entry");
+}
+
+/// A wrong footer size fails by default; a lenient open locates the tree from
+/// the end of the file and reports the mismatch as a warning
+#[test]
+fn lenient_open_accepts_wrong_footer_size() {
+    let dir = TempDir::new("lenient");
+    let bytes = sample_bytes(&dir, false);
+    let actual = bytes.len() as u64;
+    let lenient = OpenOptions {
+        lenient: true,
+        ..OpenOptions::default()
+    };
+
+    // The footer records 16 bytes more than the file has
+    let mut doctored = bytes.clone();
+    let footer = doctored.len() - 4;
+    doctored[footer..].copy_from_slice(&(actual as u32 + 16).to_le_bytes());
+
+    let err = DatArchive::from_bytes(doctored.clone()).unwrap_err();
+    assert!(matches!(
+        err,
+        DatError::FooterSizeMismatch { recorded, actual: a } if recorded == actual + 16 && a == actual
+    ));
+
+    let archive = DatArchive::from_bytes_with(doctored, &lenient).unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(
+        archive.read_file("text/english/readme.txt").unwrap(),
+        b"Hello"
+    );
+    assert_eq!(
+        archive.warnings(),
+        [format!(
+            "DAT size mismatch: footer records {} bytes, file has {actual}",
+            actual + 16
+        )]
+    );
+
+    // An intact archive opens the same either way, without warnings
+    let archive = DatArchive::from_bytes_with(bytes.clone(), &lenient).unwrap();
+    assert!(archive.warnings().is_empty());
+
+    // Entries must still lie within the file: point one past its end
+    let mut truncated = bytes;
+    let name = b"text\\english\\readme.txt";
+    let offset_at = truncated
+        .windows(name.len())
+        .rposition(|window| window == name)
+        .unwrap()
+        + name.len()
+        + 9;
+    truncated[offset_at..offset_at + 4].copy_from_slice(&(actual as u32).to_le_bytes());
+    let footer = truncated.len() - 4;
+    truncated[footer..].copy_from_slice(&0u32.to_le_bytes());
+    let err = DatArchive::from_bytes_with(truncated, &lenient).unwrap_err();
+    assert!(matches!(err, DatError::CorruptTree { .. }));
+    assert!(err.to_string().contains("extends past the end of the file"));
+}
+
+/// Entry locations are checked on open: problems are listed and reported as
+/// warnings by default, and fail the open in strict mode
+#[test]
+fn entry_locations_are_validated_on_open() {
+    let dir = TempDir::new("bounds");
+    let strict = OpenOptions {
+        strict: true,
+        ..OpenOptions::default()
+    };
+    for dat1 in [false, true] {
+        let archive = DatArchive::from_bytes_with(sample_bytes(&dir, dat1), &strict).unwrap();
+        assert!(archive.entry_problems().is_empty());
+    }
+
+    let bytes = sample_bytes(&dir, false);
+    let size = bytes.len() as u64;
+    let name = b"text\\english\\readme.txt";
+    let record = bytes
+        .windows(name.len())
+        .rposition(|window| window == name)
+        .unwrap()
+        + name.len();
+    let tree_start =
+        size - 8 - u32::from_le_bytes(bytes[size as usize - 8..][..4].try_into().unwrap()) as u64;
+    let with_offset = |offset: u64| {
+        let mut doctored = bytes.clone();
+        doctored[record + 9..record + 13].copy_from_slice(&(offset as u32).to_le_bytes());
+        doctored
+    };
+
+    let archive = DatArchive::from_bytes(with_offset(size - 2)).unwrap();
+    let problems = archive.entry_problems();
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].name, "text/english/readme.txt");
+    assert_eq!(
+        problems[0].kind,
+        EntryProblemKind::PastEnd {
+            end: size + 3,
+            archive_size: size
+        }
+    );
+    assert_eq!(archive.warnings(), [problems[0].to_string()]);
+
+    let archive = DatArchive::from_bytes(with_offset(tree_start)).unwrap();
+    assert_eq!(
+        archive.entry_problems()[0].kind,
+        EntryProblemKind::OutsideDataRegion {
+            data: tree_start..tree_start + 5,
+            data_region: 0..tree_start
+        }
+    );
+
+    let err = DatArchive::from_bytes_with(with_offset(size - 2), &strict).unwrap_err();
+    assert!(matches!(err, DatError::InvalidEntries { ref problems } if problems.len() == 1));
+}
+
+/// Every separator and case spelling of a path finds the same entry, in both formats
+#[test]
+fn entry_paths_match_any_separator_and_case() {
+    let spellings = [
+        "text\\english\\readme.txt",
+        "text/english/readme.txt",
+        "TEXT/ENGLISH/README.TXT",
+        "Text\\English/ReadMe.txt",
+        "/text//english\\\\readme.txt",
+    ];
+
+    for dat1 in [false, true] {
+        let (archive, _) = DatArchive::from_entries(dat1, sample_entries(), &policy()).unwrap();
+        for spelling in spellings {
+            assert_eq!(archive.read_file(spelling).unwrap(), b"Hello", "{spelling}");
+
+            let (found, missing) = archive.find_entries(&[spelling.to_string()]);
+            assert_eq!(found.len(), 1, "{spelling}");
+            assert_eq!(found[0].name, "text\\english\\readme.txt");
+            assert!(missing.is_empty());
+
+            let mut copy = DatArchive::from_entries(dat1, sample_entries(), &policy())
+                .unwrap()
+                .0;
+            copy.delete_file(spelling, &Progress::default()).unwrap();
+            assert_eq!(copy.entry_names(), ["art\\critters\\a.frm"]);
+        }
+    }
+}
+
+/// Names a third-party packer stored with `/` separators are read back with
+/// `\\` ones, so they match, delete and save like any other
+#[test]
+fn dat2_names_with_forward_slashes_are_normalized() {
+    let dir = TempDir::new("slashes");
+    let path = dir.join("mixed.dat");
+    let entries = vec![
+        ("art/critters/a.frm".to_string(), vec![7; 64]),
+        ("text\\english/readme.txt".to_string(), b"Hello".to_vec()),
+        ("text\\english\\notes.txt".to_string(), b"Notes".to_vec()),
+    ];
+    let (archive, _) = DatArchive::from_entries(false, entries, &policy()).unwrap();
+    archive.save(&path).unwrap();
+    assert!(fs::read(&path)
+        .unwrap()
+        .windows(18)
+        .any(|window| window == b"art/critters/a.frm"));
+
+    let mut archive = DatArchive::open(&path).unwrap();
+    assert_eq!(
+        archive.entry_names(),
+        [
+            "art\\critters\\a.frm",
+            "text\\english\\notes.txt",
+            "text\\english\\readme.txt"
+        ]
+    );
+    assert_eq!(
+        archive.read_file("text/english/readme.txt").unwrap(),
+        b"Hello"
+    );
+    archive
+        .delete_file("art/critters/a.frm", &Progress::default())
+        .unwrap();
+    archive.save(&path).unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert!(!bytes
+        .windows(18)
+        .any(|window| window == b"english/readme.txt"));
+    let archive = DatArchive::open(&path).unwrap();
+    assert_eq!(
+        archive.entry_names(),
+        ["text\\english\\notes.txt", "text\\english\\readme.txt"]
+    );
+}
+
+#[test]
+fn extract_writes_files_and_reports_events() {
+    let dir = TempDir::new("extract");
+    let (archive, _) = DatArchive::from_entries(false, sample_entries(), &policy()).unwrap();
+    let (progress, events) = recording_progress();
+
+    let options = ExtractOptions::new(ExtractionMode::Flat);
+    let result = archive.extract(
+        &dir,
+        &["text/*".to_string(), "missing.txt".to_string()],
+        &options,
+        &progress,
+    );
+
+    // The matching entry is extracted, then the unmatched pattern fails the call
+    assert!(result.is_err());
+    assert_eq!(fs::read(dir.join("readme.txt")).unwrap(), b"Hello");
+    assert!(!dir.join("a.frm").exists());
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "extract 1",
+            "extracted text\\english\\readme.txt 5 Written",
+            "missing missing.txt"
+        ]
+    );
+    assert_eq!(
+        progress
+            .extracted
+            .load(std::sync::atomic::Ordering::Relaxed),
+        1
+    );
+}
+
+/// Entries larger than the memory budget are decompressed straight to their
+/// file, with the same checks as in memory
+#[test]
+fn extract_streams_entries_over_the_memory_budget() {
+    let dir = TempDir::new("extract_budget");
+    let bytes = sample_bytes(&dir, false);
+    let mut options = ExtractOptions::new(ExtractionMode::PreserveStructure);
+    options.max_memory = Some(1024);
+
+    let archive = DatArchive::from_bytes(bytes.clone()).unwrap();
+    let out = dir.join("out");
+    archive
+        .extract(&out, &[], &options, &Progress::default())
+        .unwrap();
+    assert_eq!(
+        fs::read(out.join("art/critters/a.frm")).unwrap(),
+        vec![7; 4096]
+    );
+    assert_eq!(
+        fs::read(out.join("text/english/readme.txt")).unwrap(),
+        b"Hello"
+    );
+
+    // A wrong recorded size fails the entry and leaves no partial file
+    let name = b"art\\critters\\a.frm";
+    let size_at = bytes
+        .windows(name.len())
+        .rposition(|window| window == name)
+        .unwrap()
+        + name.len()
+        + 1;
+    let mut wrong_size = bytes;
+    wrong_size[size_at..size_at + 4].copy_from_slice(&4097u32.to_le_bytes());
+    let archive = DatArchive::from_bytes(wrong_size).unwrap();
+    let out = dir.join("wrong");
+    let err = archive
+        .extract(&out, &[], &options, &Progress::default())
+        .unwrap_err();
+    assert!(matches!(
+        DatError::find(&err),
+        Some(DatError::SizeMismatch {
+            expected: 4097,
+            actual: 4096,
+            ..
+        })
+    ));
+    assert!(!out.join("art/critters/a.frm").exists());
+}
+
+#[test]
+fn add_and_delete_report_events() {
+    let temp = TempDir::new("add");
+    let dir = fs::canonicalize(&temp).unwrap();
+    let source = dir.join("mod");
+    fs::create_dir_all(source.join("data")).unwrap();
+    fs::write(source.join("data").join("a.txt"), b"a").unwrap();
+
+    for mut archive in [DatArchive::new_dat1(), DatArchive::new_dat2()] {
+        let (progress, events) = recording_progress();
+        // Store paths relative to `dir`, like `dat3 a -C dir`
+        let options = AddOptions {
+            source_root: Some(dir.clone()),
+            recursive: true,
+            ..AddOptions::new(policy())
+        };
+        let stats = archive.add_file(&source, &options, &progress).unwrap();
+        assert_eq!(stats.files_added, 1);
+        archive.delete_file("mod/data/a.txt", &progress).unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "adding 1",
+                "add mod\\data\\a.txt",
+                "added mod\\data\\a.txt 1",
+                "delete mod\\data\\a.txt"
+            ]
+        );
+        assert!(archive.is_empty());
+        assert_eq!(archive.len(), 0);
+    }
+}
+
+/// DAT1 names are kept with backslashes whatever separators the tree
+/// uses, so entries added in a session match parsed ones, before and
+/// after a save
+#[test]
+fn dat1_entries_match_within_and_across_sessions() {
+    let dir = TempDir::new("dat1_separators");
+    let path = dir.join("slashes.dat");
+    let entries = vec![("art\\critters\\a.frm".to_string(), b"a".to_vec())];
+    let (archive, _) = DatArchive::from_entries(true, entries, &policy()).unwrap();
+    archive.save(&path).unwrap();
+    // As a third-party packer would have written the directory name
+    let mut bytes = fs::read(&path).unwrap();
+    let dir_at = bytes
+        .windows(12)
+        .position(|window| window == b"art\\critters")
+        .unwrap();
+    bytes[dir_at + 3] = b'/';
+    fs::write(&path, &bytes).unwrap();
+
+    let progress = Progress::default();
+    let options = AddOptions::new(policy());
+    let names = |archive: &DatArchive| -> Vec<String> {
+        archive.entries().map(|file| file.name.clone()).collect()
+    };
+
+    let mut archive = DatArchive::open(&path).unwrap();
+    assert_eq!(names(&archive), ["art\\critters\\a.frm"]);
+    // Replaces the parsed entry instead of adding a second one
+    let contents = vec![
+        ("art/critters/a.frm".to_string(), b"new".to_vec()),
+        ("art/critters/b.frm".to_string(), b"b".to_vec()),
+    ];
+    archive.add_contents(contents, &options, &progress).unwrap();
+    assert_eq!(
+        names(&archive),
+        ["art\\critters\\a.frm", "art\\critters\\b.frm"]
+    );
+    assert_eq!(archive.read_file("art/critters/a.frm").unwrap(), b"new");
+
+    let out = dir.join("out");
+    let extract = ExtractOptions::new(ExtractionMode::PreserveStructure);
+    archive
+        .extract(
+            &out,
+            &["art/critters/b.frm".to_string()],
+            &extract,
+            &progress,
+        )
+        .unwrap();
+    assert_eq!(fs::read(out.join("art/critters/b.frm")).unwrap(), b"b");
+
+    archive
+        .delete_file("art/critters/a.frm", &progress)
+        .unwrap();
+    assert_eq!(names(&archive), ["art\\critters\\b.frm"]);
+
+    // The saved tree has one directory, with backslashes
+    archive.save(&path).unwrap();
+    let saved = fs::read(&path).unwrap();
+    assert!(saved.windows(12).any(|window| window == b"art\\critters"));
+    assert!(!saved.windows(12).any(|window| window == b"art/critters"));
+
+    let mut reopened = DatArchive::open(&path).unwrap();
+    assert_eq!(names(&reopened), ["art\\critters\\b.frm"]);
+    reopened
+        .add_contents(
+            vec![("art\\critters\\b.frm".to_string(), b"c".to_vec())],
+            &options,
+            &progress,
+        )
+        .unwrap();
+    assert_eq!(reopened.len(), 1);
+    reopened
+        .delete_file("ART/CRITTERS/B.FRM", &progress)
+        .unwrap();
+    assert!(reopened.is_empty());
+}
+
+/// The DAT1 header and directory records have fields of unknown meaning:
+/// an untouched archive saves back byte for byte, whatever they hold
+#[test]
+fn dat1_unknown_fields_survive_a_save() {
+    let dir = TempDir::new("dat1_unknowns");
+    let mut bytes = sample_bytes(&dir, true);
+    // The values a new archive gets, in the header and in each directory record
+    let header_defaults = [0, 0, 0, 0x0A, 0, 0, 0, 0, 0, 0, 0, 0];
+    let directory_defaults = [0, 0, 0, 0x0A, 0, 0, 0, 0x10, 0, 0, 0, 0];
+    assert_eq!(bytes[4..16], header_defaults);
+    bytes[4..16].copy_from_slice(&[0, 0, 0, 0x5E, 0, 0, 0, 1, 0, 0, 0, 2]);
+    let records: Vec<usize> = (16..bytes.len() - 12)
+        .filter(|&at| bytes[at..at + 12] == directory_defaults)
+        .collect();
+    assert_eq!(records.len(), 3);
+    for (i, at) in records.into_iter().enumerate() {
+        bytes[at + 11] = 0x20 + i as u8;
+    }
+
+    let original = dir.join("original.dat");
+    fs::write(&original, &bytes).unwrap();
+    let resaved = dir.join("resaved.dat");
+    DatArchive::open(&original).unwrap().save(&resaved).unwrap();
+    assert_eq!(fs::read(&resaved).unwrap(), bytes);
+
+    // A directory added later gets the defaults; the others keep theirs
+    let mut archive = DatArchive::open(&original).unwrap();
+    let contents = vec![("sound\\new.acm".to_string(), b"acm".to_vec())];
+    archive
+        .add_contents(contents, &AddOptions::new(policy()), &Progress::default())
+        .unwrap();
+    archive.save(&resaved).unwrap();
+    let saved = fs::read(&resaved).unwrap();
+    assert_eq!(saved[4..16], bytes[4..16]);
+    let defaults = (16..saved.len() - 12)
+        .filter(|&at| saved[at..at + 12] == directory_defaults)
+        .count();
+    assert_eq!(defaults, 1);
+    for i in 0..3u8 {
+        let patched = [0, 0, 0, 0x0A, 0, 0, 0, 0x10, 0, 0, 0, 0x20 + i];
+        assert!(saved.windows(12).any(|window| window == patched));
+    }
+}
+
+/// DAT1 directory records without files survive opening, saving, adding
+/// and repacking; DAT2 has nowhere to keep them
+#[test]
+fn dat1_empty_directories_are_kept() {
+    let dir = TempDir::new("dat1_empty_dirs");
+    let (mut archive, _) = DatArchive::from_entries(true, sample_entries(), &policy()).unwrap();
+    assert!(archive.add_directory("data/empty/").unwrap());
+    assert!(!archive.add_directory("DATA\\Empty").unwrap());
+    assert!(!archive.add_directory("/").unwrap());
+    let path = dir.join("empty_dirs.dat");
+    archive.save(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+
+    let dirs = |archive: &DatArchive| -> Vec<String> {
+        let DatArchive::Dat1(dat1) = archive else {
+            panic!("not a DAT1 archive");
+        };
+        dat1.directories()
+            .filter(|(name, files)| files.is_empty() && *name != ".")
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+    let mut archive = DatArchive::open(&path).unwrap();
+    assert_eq!(dirs(&archive), ["data\\empty"]);
+    archive.save(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), bytes);
+
+    let contents = vec![("text\\new.msg".to_string(), b"new".to_vec())];
+    archive
+        .add_contents(contents, &AddOptions::new(policy()), &Progress::default())
+        .unwrap();
+    assert_eq!(dirs(&archive), ["data\\empty"]);
+    let (repacked, _) = archive.repack(&policy()).unwrap();
+    assert_eq!(dirs(&repacked), ["data\\empty"]);
+
+    assert!(DatArchive::new_dat2().add_directory("data").is_err());
+}
+
+/// A nested target directory gives DAT1 one record named by the whole
+/// path, which later adds find whatever their case or separators
+#[test]
+fn dat1_nested_target_directories() {
+    let dir = TempDir::new("dat1_nested_target");
+    let source = dir.join("music.acm");
+    fs::write(&source, b"music").unwrap();
+    let mut archive = DatArchive::new_dat1();
+    let options = AddOptions {
+        target_dir: Some("sound/music/deep".to_string()),
+        ..AddOptions::new(policy())
+    };
+    archive
+        .add_file(&source, &options, &Progress::default())
+        .unwrap();
+    let contents = vec![("SOUND\\Music/deep/other.acm".to_string(), b"other".to_vec())];
+    archive
+        .add_contents(contents, &AddOptions::new(policy()), &Progress::default())
+        .unwrap();
+    let path = dir.join("nested.dat");
+    archive.save(&path).unwrap();
+
+    let archive = DatArchive::open(&path).unwrap();
+    let DatArchive::Dat1(dat1) = &archive else {
+        panic!("not a DAT1 archive");
+    };
+    let records: Vec<(&str, Vec<&str>)> = dat1
+        .directories()
+        .filter(|(_, files)| !files.is_empty())
+        .map(|(name, files)| (name, files.iter().map(|f| f.name.as_str()).collect()))
+        .collect();
+    assert_eq!(
+        records,
+        [(
+            "sound\\music\\deep",
+            vec![
+                "sound\\music\\deep\\music.acm",
+                "sound\\music\\deep\\other.acm"
+            ]
+        )]
+    );
+    assert_eq!(
+        archive.read_file("sound/music/deep/music.acm").unwrap(),
+        b"music"
+    );
+}
+
+/// DAT1 stores name lengths in one byte: longer names fail on add and on
+/// save instead of being truncated into a corrupt archive
+#[test]
+fn dat1_rejects_names_over_255_bytes() {
+    let temp = TempDir::new("dat1_names");
+    let dir = fs::canonicalize(&temp).unwrap();
+    let long_dir = (0..10).fold(dir.join("mod"), |path, i| {
+        path.join(format!("{i}{}", "d".repeat(29)))
+    });
+    fs::create_dir_all(&long_dir).unwrap();
+    fs::write(long_dir.join("a.txt"), b"a").unwrap();
+
+    let mut archive = DatArchive::new_dat1();
+    let options = AddOptions {
+        source_root: Some(dir.clone()),
+        recursive: true,
+        ..AddOptions::new(policy())
+    };
+    let err = archive
+        .add_file(dir.join("mod"), &options, &Progress::default())
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("its directory name is 313 bytes, DAT1 allows at most 255"));
+    assert!(archive.is_empty());
+
+    let long_name = format!("art\\{}.frm", "f".repeat(296));
+    let (archive, _) =
+        DatArchive::from_entries(true, vec![(long_name, vec![1])], &policy()).unwrap();
+    let path = dir.join("long.dat");
+    let err = archive.save(&path).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Name too long for DAT1 (300 bytes"));
+    assert!(!path.exists());
+}
+
+/// Offsets and sizes are u32: a save that would pass 4 GiB fails before
+/// anything is written instead of wrapping them
+#[test]
+fn save_rejects_archives_over_4_gib() {
+    let dir = TempDir::new("4gib");
+    let mut bytes = sample_bytes(&dir, false);
+    // Record 3 GiB of packed data for both entries; no data is read before the check
+    for name in [&b"art\\critters\\a.frm"[..], b"text\\english\\readme.txt"] {
+        let packed_at = bytes
+            .windows(name.len())
+            .rposition(|window| window == name)
+            .unwrap()
+            + name.len()
+            + 5;
+        bytes[packed_at..packed_at + 4].copy_from_slice(&(3u32 << 30).to_le_bytes());
+    }
+
+    let archive = DatArchive::from_bytes(bytes).unwrap();
+    let path = dir.join("huge.dat");
+    let err = archive.save(&path).unwrap_err();
+    // Two 3 GiB entries, a 79-byte tree and the 8-byte footer
+    assert_eq!(
+        err.to_string(),
+        "Archive would exceed the 4 GiB DAT limit (current total: 6442451031 bytes)"
+    );
+    assert!(!path.exists());
+}
+
+/// Entries sharing one stretch of data count once toward the 4 GiB limit
+#[test]
+fn shared_data_counts_once_toward_4_gib() {
+    let dir = TempDir::new("4gib_shared");
+    let mut bytes = sample_bytes(&dir, false);
+    // Point both entries at the same 3 GiB of packed data at offset 0
+    for name in [&b"art\\critters\\a.frm"[..], b"text\\english\\readme.txt"] {
+        let packed_at = bytes
+            .windows(name.len())
+            .rposition(|window| window == name)
+            .unwrap()
+            + name.len()
+            + 5;
+        bytes[packed_at..packed_at + 4].copy_from_slice(&(3u32 << 30).to_le_bytes());
+        bytes[packed_at + 4..packed_at + 8].copy_from_slice(&0u32.to_le_bytes());
+    }
+
+    let archive = DatArchive::from_bytes(bytes).unwrap();
+    let path = dir.join("shared.dat");
+    // Within the limit, so the save gets as far as reading the missing data
+    let err = archive.save(&path).unwrap_err();
+    assert!(err.to_string().contains("File data extends beyond archive"));
+    assert!(!path.exists());
+}
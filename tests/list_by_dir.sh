@@ -0,0 +1,51 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that l --by-dir prints a line per directory with its totals, then its files
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_list_by_dir"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/text
+printf 'aaaa' >src/art/critters/a.frm
+printf 'bb' >src/art/critters/b.frm
+printf 'hello' >src/text/readme.txt
+printf 'r' >src/root.txt
+
+for flag in "" "--dat1"; do
+	$DAT3 c test.dat src $flag --overwrite
+
+	# Test 1: Each directory line carries its file count and sizes, and its files follow it
+	$DAT3 l --by-dir test.dat >list.log
+	grep -qx "art/critters/  2 files, 6 bytes, 6 packed" list.log
+	grep -qx "text/  1 file, 5 bytes, 5 packed" list.log
+	grep -qx "\.  1 file, 1 bytes, 1 packed" list.log
+	grep -A1 "^text/" list.log | grep -q " readme.txt$"
+	grep -q "^4 files, 12 bytes" list.log
+
+	# Test 2: Patterns filter the files, and directories with none left are dropped
+	$DAT3 l --by-dir test.dat "*.frm" >list.log
+	grep -qx "art/critters/  2 files, 6 bytes, 6 packed" list.log
+	if grep -q "^text/" list.log; then exit 1; fi
+done
+
+# Test 3: A nested target directory is one DAT1 directory, which later adds find in any case
+$DAT3 a nested.dat --dat1 src/root.txt -t sound/music/deep
+$DAT3 a nested.dat src/art/critters/a.frm -t 'SOUND\Music\Deep'
+$DAT3 l --by-dir nested.dat >list.log
+grep -qx "sound/music/deep/  2 files, 5 bytes, 5 packed" list.log
+[ "$(grep -c "packed$" list.log)" -eq 1 ]
+
+cd ..
+rm -rf "$TEST_DIR"
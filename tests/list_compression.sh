@@ -0,0 +1,64 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test l --compressed-only and --stored-only in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_list_compression"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$@" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+# DAT2: type 1 entries are compressed
+mkdir -p src
+head -c 4000 /dev/zero >src/zeros.frm
+head -c 4000 /dev/zero >src/zeros.txt
+# Too small to gain from compression, so stored
+echo "short" >src/short.msg
+$DAT3 a dat2.dat -c 9 --store-ext txt -r src
+
+# DAT1: entries with attribute 0x40 are compressed. dat3 only writes stored
+# DAT1 entries, so the tree is written by hand: one directory "." holding
+# a.txt (0x20, stored) and b.frm (0x40, 10 bytes packed into 4).
+printf '\x00\x00\x00\x01\x00\x00\x00\x0a\x00\x00\x00\x00\x00\x00\x00\x00' >dat1.dat
+printf '\x01.' >>dat1.dat
+printf '\x00\x00\x00\x02\x00\x00\x00\x0a\x00\x00\x00\x10\x00\x00\x00\x00' >>dat1.dat
+printf '\x05a.txt\x00\x00\x00\x20\x00\x00\x00\x4e\x00\x00\x00\x03\x00\x00\x00\x00' >>dat1.dat
+printf '\x05b.frm\x00\x00\x00\x40\x00\x00\x00\x51\x00\x00\x00\x0a\x00\x00\x00\x04' >>dat1.dat
+printf 'abc\xff\x01\x02\x03' >>dat1.dat
+
+# Test 1: Each flag keeps one side
+printf 'src/zeros.frm\n' | diff - <(names dat2.dat --compressed-only)
+printf 'src/short.msg\nsrc/zeros.txt\n' | diff - <(names dat2.dat --stored-only)
+printf 'b.frm\n' | diff - <(names dat1.dat --compressed-only)
+printf 'a.txt\n' | diff - <(names dat1.dat --stored-only)
+
+# Test 2: Combined with patterns and extension filters
+printf 'src/zeros.frm\n' | diff - <(names dat2.dat --compressed-only '*.frm')
+printf 'src/zeros.frm\n' | diff - <(names dat2.dat --compressed-only --ext frm src)
+
+# Test 3: The summary counts only the kept entries
+$DAT3 l dat1.dat --compressed-only | tail -n 1 | grep -qx "1 of 2 files, 10 bytes, 4 packed (40.0%)"
+
+# Test 4: The flags exclude each other
+if $DAT3 l dat2.dat --compressed-only --stored-only; then
+	echo "Error: --compressed-only and --stored-only should conflict"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
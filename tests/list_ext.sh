@@ -0,0 +1,62 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test l --ext and --not-ext in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_list_ext"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p art/critters art/items text
+echo "critter" >art/critters/a.FRM
+echo "item" >art/items/b.frm
+echo "not a frame" >art/items/c.fr
+echo "message" >text/d.msg
+echo "readme" >text/readme
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$@" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r art text
+
+	# Test 1: Extensions match whole suffixes, in any case
+	printf 'art/critters/a.FRM\nart/items/b.frm\n' | diff - <(names "$format.dat" --ext frm)
+	printf 'art/critters/a.FRM\nart/items/b.frm\ntext/d.msg\n' |
+		diff - <(names "$format.dat" --ext .FRM,msg)
+
+	# Test 2: Exclusion
+	printf 'art/items/c.fr\ntext/readme\n' | diff - <(names "$format.dat" --not-ext frm --not-ext msg)
+
+	# Test 3: Patterns become path prefixes
+	printf 'art/items/b.frm\n' | diff - <(names "$format.dat" --ext frm art/items)
+	printf 'art/critters/a.FRM\nart/items/b.frm\n' | diff - <(names "$format.dat" --ext frm ART)
+	if $DAT3 l "$format.dat" --ext frm art/crit; then
+		echo "Error: a partial directory name should match nothing"
+		exit 1
+	fi
+
+	# Test 4: The summary counts the filtered set
+	$DAT3 l "$format.dat" --ext msg | tail -n 1 | grep -q "^1 of 5 files, 8 bytes"
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,63 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test l --sort and --reverse in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_list_sort"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+head -c 8000 /dev/zero >src/big.frm
+head -c 2000 /dev/urandom >src/noise.acm
+echo "small text" >src/small.txt
+: >src/empty.txt
+
+# Print entry names, one per line, in listed order
+names() {
+	$DAT3 l --no-summary "$@" | tail -n +3 | awk '{print $NF}'
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -c 9 -r src
+
+	# Test 1: By size, both ways
+	printf 'src/empty.txt\nsrc/small.txt\nsrc/noise.acm\nsrc/big.frm\n' |
+		diff - <(names "$format.dat" --sort size)
+	printf 'src/big.frm\nsrc/noise.acm\nsrc/small.txt\nsrc/empty.txt\n' |
+		diff - <(names "$format.dat" --sort size --reverse)
+
+	# Test 2: By name
+	printf 'src/big.frm\nsrc/empty.txt\nsrc/noise.acm\nsrc/small.txt\n' |
+		diff - <(names "$format.dat" --sort name)
+
+	# Test 3: By ratio, applied after filtering
+	names "$format.dat" --sort ratio 'src/*.txt' 'src/*.acm' >ratio.log
+	[ "$(wc -l <ratio.log)" -eq 3 ]
+
+	# Test 4: --reverse alone reverses archive order
+	diff <(names "$format.dat" | tac) <(names "$format.dat" --reverse)
+done
+
+# Test 5: Compressed entries come first by ratio
+[ "$(names dat2.dat --sort ratio | head -n 1)" = "src/big.frm" ]
+[ "$(names dat2.dat --sort packed --reverse | head -n 1)" = "src/noise.acm" ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
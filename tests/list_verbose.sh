@@ -0,0 +1,75 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test l and l -l listings of small DAT1 and DAT2 archives, with their totals
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_list_verbose"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/sub
+printf 'hello\n' >src/a.txt
+printf 'world!\n' >src/sub/b.txt
+$DAT3 a dat2.dat --store-ext txt -r src
+$DAT3 a dat1.dat --dat1 -r src
+head -c 4000 /dev/zero >zeros.frm
+$DAT3 a packed.dat -c 9 zeros.frm
+
+# Test 1: The normal DAT2 listing
+cat >expected.log <<EOF
+       Size      Packed  Comp  Name
+--------------------------------------------------
+          6           6    No  src/a.txt
+          7           7    No  src/sub/b.txt
+--------------------------------------------------
+2 files, 13 bytes, 13 packed (100.0%)
+EOF
+$DAT3 l dat2.dat | diff expected.log -
+
+# Test 2: DAT2 entries show their offset and compression type byte
+cat >expected.log <<EOF
+    Offset        Size      Packed  Comp  Type   Dir  Name
+--------------------------------------------------
+         0           6           6    No  0x00     -  src/a.txt
+         6           7           7    No  0x00     -  src/sub/b.txt
+EOF
+$DAT3 l -l --no-summary dat2.dat | diff expected.log -
+$DAT3 l --long packed.dat | grep -q "^ *0 *4000 *[0-9]* *Yes  0x01     -  zeros.frm$"
+
+# Test 3: The normal DAT1 listing
+cat >expected.log <<EOF
+       Size      Packed  Comp  Name
+--------------------------------------------------
+          7           7    No  src/sub/b.txt
+          6           6    No  src/a.txt
+--------------------------------------------------
+2 files, 13 bytes, 13 packed (100.0%)
+EOF
+$DAT3 l dat1.dat | diff expected.log -
+
+# Test 4: DAT1 entries show their attributes and directory index
+cat >expected.log <<EOF
+ Index     Offset        Size      Packed  Comp  Type   Dir  Name
+--------------------------------------------------
+     0        122           7           7    No  0x20     1  src/sub/b.txt
+     1        129           6           6    No  0x20     2  src/a.txt
+EOF
+$DAT3 l -l --index --no-summary dat1.dat | diff expected.log -
+
+# Test 5: Filtered totals count only the listed entries, with separators
+$DAT3 l dat1.dat 'src/sub/*' | tail -n 1 | grep -qx "1 of 2 files, 7 bytes, 7 packed (100.0%)"
+$DAT3 l packed.dat | tail -n 1 | grep -q "^1 file, 4,000 bytes, [0-9]* packed"
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,67 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test -v log levels and RUST_LOG filters
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_logging"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+seq 1 300 >src/a.txt
+seq 1 30 >src/b.txt
+
+# Test 1: no log messages by default
+$DAT3 a test.dat -r src >default.log 2>&1
+if grep -q "^\[" default.log; then
+	echo "Log messages without -v"
+	exit 1
+fi
+
+# Test 2: -v logs opens and saves, -vv each entry too
+$DAT3 -v x test.dat -o out >info.log 2>&1
+grep -q "^\[INFO fallout_dat3::common\] Opened test.dat: DAT2, 2 entries$" info.log
+if grep -q "DEBUG" info.log; then
+	echo "Debug messages with -v"
+	exit 1
+fi
+$DAT3 -vv x test.dat -o out2 >debug.log 2>&1
+grep -q "^\[DEBUG fallout_dat3::dat2\] Extracting src/a.txt: Written$" debug.log
+$DAT3 -vv d -y test.dat src/a.txt >debug.log 2>&1
+grep -q "^\[DEBUG fallout_dat3::common\] Deleting src/a.txt$" debug.log
+grep -q "^\[INFO fallout_dat3::common\] Saved test.dat rewriting the tree: 1 entries$" debug.log
+
+# Test 3: RUST_LOG picks modules instead of -v
+RUST_LOG=fallout_dat3::dat2=debug $DAT3 x test.dat -o out3 >filtered.log 2>&1
+grep -q "Extracting src/b.txt" filtered.log
+if grep -q "INFO" filtered.log; then
+	echo "RUST_LOG didn't replace the default filter"
+	exit 1
+fi
+
+# Test 4: -v only logs with l; the detail columns come from -l
+$DAT3 l -v test.dat >list.log 2>/dev/null
+if grep -q "Offset" list.log; then
+	echo "-v showed the long listing"
+	exit 1
+fi
+$DAT3 l -l test.dat >long.log 2>&1
+grep -q "Offset" long.log
+if grep -q "INFO" long.log; then
+	echo "-l logged info messages"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
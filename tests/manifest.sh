@@ -0,0 +1,65 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that a manifest rebuilds an identical archive and catches content drift
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_manifest"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text src/art
+for i in $(seq 1 10); do
+	seq 1 "$((i * 50))" >"src/text/file$i.txt"
+done
+head -c 5000 /dev/zero >src/art/blank.frm
+$DAT3 a test.dat -r -c 9 -C src text art
+
+# Test 1: The manifest is stable and sorted
+$DAT3 manifest test.dat >build.manifest
+head -1 build.manifest | grep -qx "# dat3 manifest v1 dat2"
+[ "$(grep -vc '^#' build.manifest)" -eq 11 ]
+grep -q " 5000 [0-9]* 1 art/blank.frm$" build.manifest
+$DAT3 manifest test.dat | cmp - build.manifest
+
+# Test 2: Rebuilding with the same settings gives a byte-identical archive
+echo unlisted >src/unlisted.txt
+$DAT3 a rebuilt.dat -c 9 --from-manifest build.manifest --root src 2>rebuild.err
+cmp test.dat rebuilt.dat
+[ ! -s rebuild.err ]
+
+# Test 3: Other compression settings build but warn
+$DAT3 a level1.dat --from-manifest build.manifest --root src 2>level1.err
+grep -q "Warning: 11 entries are packed differently" level1.err
+
+# Test 4: Missing and changed files fail the build
+echo changed >>src/text/file3.txt
+rm src/text/file7.txt
+if $DAT3 a drift.dat --from-manifest build.manifest --root src 2>drift.err; then
+	echo "Content drift should fail the build"
+	exit 1
+fi
+grep -q "2 of 11 listed files can't be used" drift.err
+grep -q "text/file3.txt: content doesn't match its hash" drift.err
+grep -q "text/file7.txt: missing under src" drift.err
+[ ! -e drift.dat ]
+
+# Test 5: --from-manifest doesn't take file operands
+if $DAT3 a other.dat src --from-manifest build.manifest 2>operands.err; then
+	echo "File operands should conflict with --from-manifest"
+	exit 1
+fi
+grep -q "cannot be used with" operands.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
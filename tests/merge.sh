@@ -0,0 +1,80 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test merging archives with later-wins semantics, including --sequence
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_merge"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+# Three parts: part2 overrides a.txt, part10 overrides b.txt and then a.txt from part2
+mkdir -p p1/data p2/data p10/DATA
+echo "a from 1" >p1/data/a.txt
+echo "b from 1" >p1/data/b.txt
+echo "a from 2" >p2/data/a.txt
+echo "c from 2" >p2/data/c.txt
+echo "a from 10" >p10/DATA/A.TXT
+echo "b from 10" >p10/DATA/b.txt
+
+for format in dat2 dat1; do
+	flag=""
+	# DAT1 has one directory record per path, spelled as first added
+	later_dir="data"
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+		later_dir="DATA"
+	fi
+
+	for part in 1 2 10; do
+		# shellcheck disable=SC2086
+		$DAT3 a "${format}_part$part.dat" $flag -r -C "p$part" "$(ls "p$part")"
+	done
+
+	# Test 1: --sequence merges part1, part2, part10 in that order
+	$DAT3 merge --sequence "${format}_part*.dat" -o "${format}_merged.dat" --check-overlap >merge.out
+	grep -q "Merged 3 entries from 3 archives" merge.out
+	grep -q "${format}_part2.dat overrides 1 entries from ${format}_part1.dat" merge.out
+	grep -q "${format}_part10.dat overrides 1 entries from ${format}_part1.dat" merge.out
+	grep -q "${format}_part10.dat overrides 1 entries from ${format}_part2.dat" merge.out
+	grep -q "Overridden: 3 entries" merge.out
+
+	# Merged archive keeps the input format: DAT1 starts with a big-endian
+	# directory count, DAT2 with file data
+	first_byte=$(od -An -tu1 -N1 "${format}_merged.dat" | tr -d ' ')
+	if [ "$format" = "dat1" ]; then
+		[ "$first_byte" = "0" ]
+	else
+		[ "$first_byte" != "0" ]
+	fi
+
+	$DAT3 x "${format}_merged.dat" -o "${format}_out"
+	grep -qx "a from 10" "${format}_out/DATA/A.TXT"
+	grep -qx "b from 10" "${format}_out/DATA/b.txt"
+	grep -qx "c from 2" "${format}_out/$later_dir/c.txt"
+
+	# Test 2: Explicit inputs are merged in the order given
+	$DAT3 merge "${format}_part10.dat" "${format}_part1.dat" -o "${format}_reversed.dat"
+	$DAT3 x "${format}_reversed.dat" -o "${format}_rev"
+	grep -qx "a from 1" "${format}_rev/data/a.txt"
+
+	# Test 3: A glob matching nothing fails
+	if $DAT3 merge --sequence "${format}_nothing*.dat" -o "${format}_none.dat" 2>none.err; then
+		echo "Merging an empty sequence should fail"
+		exit 1
+	fi
+	grep -q "No archives match" none.err
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,72 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test reading archives stored inside other archives (outer.dat::inner.dat)
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_nested_archive"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p inner_src/text outer_src/mods
+echo "hello from the inner archive" >inner_src/text/hello.txt
+echo "outer file" >outer_src/readme.txt
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Build inner.dat, then store it (compressed) inside outer.dat
+	rm -f outer_src/mods/inner.dat "$format.dat"
+	# shellcheck disable=SC2086
+	$DAT3 a outer_src/mods/inner.dat $flag -C inner_src text
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -C outer_src mods readme.txt
+
+	# Test 1: Listing the inner archive
+	$DAT3 l "$format.dat::mods/inner.dat" | grep -q "hello.txt"
+
+	# Test 2: Extracting from the inner archive
+	$DAT3 x "$format.dat::mods\\inner.dat" -o "${format}_out"
+	diff -r inner_src "${format}_out"
+
+	# Test 3: Two levels deep
+	mkdir -p twice_src
+	cp "$format.dat" twice_src/
+	rm -f twice.dat
+	$DAT3 a twice.dat -C twice_src "$format.dat"
+	$DAT3 e "twice.dat::$format.dat::mods/inner.dat" -o "${format}_flat" text/hello.txt
+	cmp inner_src/text/hello.txt "${format}_flat/hello.txt"
+	rm -rf twice_src
+
+	# Test 4: Mutating commands reject nested paths
+	if $DAT3 d -y "$format.dat::mods/inner.dat" text/hello.txt; then
+		echo "Delete on a nested archive should fail"
+		exit 1
+	fi
+	if $DAT3 a "$format.dat::mods/inner.dat" -C outer_src readme.txt; then
+		echo "Add to a nested archive should fail"
+		exit 1
+	fi
+
+	# Test 5: Missing inner entry is an error
+	if $DAT3 l "$format.dat::mods/missing.dat"; then
+		echo "Listing a missing nested archive should fail"
+		exit 1
+	fi
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,69 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that a --newer-than and --newer-than-file only add recently modified files
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_newer_than"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p data/text
+echo "old" >data/text/old.msg
+echo "new" >data/text/new.msg
+touch -d "2024-01-01T00:00:00Z" data/text/old.msg
+touch -d "2024-07-01T00:00:00Z" data/text/new.msg
+touch -d "2024-06-01T00:00:00Z" lastbuild.stamp
+
+for flag in "" "--dat1"; do
+	rm -rf patch.dat out
+	$DAT3 c patch.dat data $flag
+	echo "changed old" >data/text/old.msg
+	touch -d "2024-01-01T00:00:00Z" data/text/old.msg
+
+	# Test 1: Only the newer file is added; the older entry stays as it was
+	$DAT3 a patch.dat -r data --newer-than 2024-06-01T00:00:00 >newer.log
+	grep -q "1 files skipped as not modified after the given time" newer.log
+	grep -q "Adding: data/text/new.msg" newer.log
+	if grep -q "old.msg" newer.log; then
+		exit 1
+	fi
+	$DAT3 x patch.dat -o out text/old.msg
+	[ "$(cat out/text/old.msg)" = "old" ]
+
+	# Test 2: A stamp file's modification time works the same way
+	$DAT3 a patch.dat -r data --newer-than-file lastbuild.stamp >stamp.log
+	grep -q "1 files skipped as not modified after the given time" stamp.log
+
+	# Test 3: Nothing newer leaves the archive untouched
+	cp patch.dat before.dat
+	$DAT3 a patch.dat -r data --newer-than 2025-01-01 >none.log
+	grep -q "2 files skipped as not modified after the given time" none.log
+	cmp patch.dat before.dat
+
+	echo "old" >data/text/old.msg
+	touch -d "2024-01-01T00:00:00Z" data/text/old.msg
+done
+
+# Test 4: Invalid times and missing stamp files fail
+if $DAT3 a patch.dat -r data --newer-than yesterday 2>err.log; then
+	exit 1
+fi
+grep -q "Invalid time 'yesterday'" err.log
+if $DAT3 a patch.dat -r data --newer-than-file missing.stamp 2>err.log; then
+	exit 1
+fi
+grep -q "Failed to read the modification time of missing.stamp" err.log
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,71 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that optimize drops data no entry references and keeps content intact
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_optimize"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text
+for i in $(seq 1 20); do
+	echo "line $i of some compressible text" >>src/text/notes.txt
+done
+echo "readme" >src/readme.txt
+
+# Print a little-endian u32 as printf escapes
+le32() {
+	printf '\\x%02x\\x%02x\\x%02x\\x%02x' \
+		$(($1 & 0xff)) $((($1 >> 8) & 0xff)) $((($1 >> 16) & 0xff)) $((($1 >> 24) & 0xff))
+}
+
+JUNK_SIZE=4096
+head -c "$JUNK_SIZE" /dev/zero >junk.bin
+
+# DAT2: insert junk between the data and the directory tree, then fix the footer
+$DAT3 a dat2.dat -C src text readme.txt
+size=$(stat -c %s dat2.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 dat2.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+{
+	head -c "$tree_start" dat2.dat
+	cat junk.bin
+	tail -c +$((tree_start + 1)) dat2.dat | head -c $((tree_size + 4))
+	# shellcheck disable=SC2059
+	printf "$(le32 $((size + JUNK_SIZE)))"
+} >dat2_gap.dat
+
+# DAT1: file data is at the end, so trailing junk is unreferenced
+$DAT3 a dat1.dat --dat1 -C src text readme.txt
+cat dat1.dat junk.bin >dat1_gap.dat
+
+for format in dat2 dat1; do
+	# Test 1: Dry run reports the gap and leaves the file alone
+	cp "${format}_gap.dat" "${format}_dry.dat"
+	$DAT3 optimize "${format}_dry.dat" --dry-run | tee dry.log
+	grep -q "Unreferenced: $JUNK_SIZE bytes" dry.log
+	cmp "${format}_gap.dat" "${format}_dry.dat"
+
+	# Test 2: Optimizing drops exactly the junk and keeps content
+	$DAT3 optimize "${format}_gap.dat"
+	cmp "${format}.dat" "${format}_gap.dat"
+	$DAT3 x "${format}_gap.dat" -o "${format}_out"
+	diff -r src "${format}_out"
+
+	# Test 3: An optimized archive has nothing left to drop
+	$DAT3 optimize "${format}_gap.dat" | grep -q "Nothing to optimize"
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,58 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test building the next patchNNN.dat of a game directory
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_patch"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p game my_mod/art/critters my_mod/scripts
+head -c 4096 /dev/zero >my_mod/art/critters/hmjmpsaa.frm
+head -c 100 /dev/zero >my_mod/scripts/obj_dude.int
+touch game/master.dat game/patch000.dat game/PATCH001.DAT game/patch003.dat
+
+# Test 1: --dry-run names the first free number and lists the files
+$DAT3 patch my_mod --game-dir game --dry-run >dry.out
+grep -qx "Would write game/patch002.dat" dry.out
+grep -qx "art/critters/hmjmpsaa.frm" dry.out
+grep -qx "scripts/obj_dude.int" dry.out
+[ ! -e game/patch002.dat ]
+
+# Test 2: The patch is built with the mod directory's contents at the root
+$DAT3 patch my_mod --game-dir game
+$DAT3 l game/patch002.dat >list.out
+grep -q "art/critters/hmjmpsaa.frm" list.out
+grep -q "scripts/obj_dude.int" list.out
+$DAT3 x game/patch002.dat -o out
+cmp out/art/critters/hmjmpsaa.frm my_mod/art/critters/hmjmpsaa.frm
+cmp out/scripts/obj_dude.int my_mod/scripts/obj_dude.int
+
+# Test 3: The next run skips to the number after the filled gap
+$DAT3 patch my_mod --game-dir game --dry-run | grep -qx "Would write game/patch004.dat"
+
+# Test 4: --number overrides the detection, but never overwrites
+$DAT3 patch my_mod --game-dir game --number 10
+[ -f game/patch010.dat ]
+if $DAT3 patch my_mod --game-dir game --number 10; then
+	echo "An existing patch should not be overwritten"
+	exit 1
+fi
+if $DAT3 patch my_mod --game-dir game --number 1000; then
+	echo "Patch numbers over 999 should be rejected"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -29,7 +29,7 @@ echo "Listing archive contents..."
 
 # Check for path consistency - look for paths that don't start with patch000/
 echo "Checking path consistency..."
-if "$DAT3" l patch000.dat | awk 'NR>2 {print $4}' | grep -v "^patch000/"; then
+if "$DAT3" l --no-summary patch000.dat | awk 'NR>2 {print $4}' | grep -v "^patch000/"; then
 	echo "ERROR: Found paths that don't start with 'patch000/'"
 	exit 1
 fi
@@ -0,0 +1,93 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that --porcelain stdout holds only records, in both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_porcelain"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text
+echo "hello" >src/a.txt
+seq 1 100 >src/text/b.txt
+
+# Fail unless every line of $1 is a version 1 record, starting with version
+# and ending with a result of status $2
+records_only() {
+	head -n 1 "$1" | grep -qx "version	1"
+	tail -n 1 "$1" | grep -q "^result	$2	"
+	if grep -Ev "^(version	1|progress	[0-9]+	[0-9]+	[0-9]+|file	(extracted|current|failed|added|deleted)	[^	]+|result	(ok|error)	.*)$" "$1"; then
+		echo "Non-record output in $1"
+		exit 1
+	fi
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Test 1: adding reports each file and the bytes added so far
+	$DAT3 --porcelain a "$format.dat" $flag -r src >add.out 2>add.err
+	records_only add.out ok
+	grep -qx "file	added	src/a.txt" add.out
+	grep -qx "progress	2	2	298" add.out
+	grep -q "Added 2 files" add.err
+
+	# Test 2: extraction, then an update that leaves everything current
+	$DAT3 --porcelain x "$format.dat" -o out >x.out 2>x.err
+	records_only x.out ok
+	grep -qx "file	extracted	src/text/b.txt" x.out
+	grep -qx "progress	2	2	298" x.out
+	grep -qx "result	ok	0 listed, 2 extracted, 0 added, 0 deleted" x.out
+	$DAT3 --porcelain x "$format.dat" -o out --update >x.out 2>x.err
+	records_only x.out ok
+	grep -qx "file	current	src/a.txt" x.out
+	grep -qx "progress	2	2	0" x.out
+
+	# Test 3: commands without records still keep stdout for them
+	for command in "l $format.dat" "tree $format.dat" "du $format.dat" "find $format.dat --dir src" "hash $format.dat"; do
+		# shellcheck disable=SC2086
+		$DAT3 --porcelain $command >other.out 2>other.err
+		records_only other.out ok
+		[ -s other.err ]
+	done
+
+	# Test 4: deleting
+	$DAT3 --porcelain -y d "$format.dat" src/a.txt >d.out 2>d.err
+	records_only d.out ok
+	grep -qx "file	deleted	src/a.txt" d.out
+done
+
+# Test 5: failures end with an error result
+if $DAT3 --porcelain x missing.dat -o out >err.out 2>err.err; then
+	echo "Extracting a missing archive succeeded"
+	exit 1
+fi
+records_only err.out error
+
+# Test 6: stdout can't also carry entry data or a JSON result
+if $DAT3 --porcelain view dat2.dat src/text/b.txt >view.out 2>view.err; then
+	echo "view wrote to the porcelain stream"
+	exit 1
+fi
+records_only view.out error
+if $DAT3 --porcelain --json l dat2.dat >/dev/null 2>&1; then
+	echo "--porcelain and --json were accepted together"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
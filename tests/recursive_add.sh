@@ -0,0 +1,66 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that -r controls whether subdirectories of directory operands are added
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_recursive_add"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p mod/art/critters mod/empty/nested
+echo "top" >mod/top.txt
+echo "art" >mod/art/art.txt
+echo "critter" >mod/art/critters/hmjmpsaa.frm
+echo "deep" >mod/empty/nested/deep.txt
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Test 1: Without -r only the directory's own files are added
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_flat.dat" $flag mod
+	printf 'mod/top.txt\n' | diff - <(names "${format}_flat.dat")
+
+	# Test 2: With -r the whole tree is added with full archive paths
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_deep.dat" $flag -r mod
+	printf 'mod/art/art.txt\nmod/art/critters/hmjmpsaa.frm\nmod/empty/nested/deep.txt\nmod/top.txt\n' |
+		diff - <(names "${format}_deep.dat")
+	$DAT3 x "${format}_deep.dat" -o "${format}_out"
+	diff -r mod "${format}_out/mod"
+
+	# Test 3: -C with -r stores paths relative to the -C directory
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_c.dat" $flag -r -C mod art
+	printf 'art/art.txt\nart/critters/hmjmpsaa.frm\n' | diff - <(names "${format}_c.dat")
+
+	# Test 4: A directory with only subdirectories needs -r
+	# shellcheck disable=SC2086
+	if $DAT3 a "${format}_empty.dat" $flag mod/empty 2>empty.err; then
+		echo "Adding a directory with no immediate files should fail without -r"
+		exit 1
+	fi
+	grep -q "use -r" empty.err
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,52 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that repack produces valid, byte-reproducible archives with unchanged content
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_repack"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/text
+for i in $(seq 1 20); do
+	echo "line $i of some compressible text" >>src/text/Notes.txt
+done
+echo "critter" >src/art/critters/HMJMPSAA.FRM
+echo "zzz" >src/zlast.txt
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" -c 1 $flag -r -C src art text zlast.txt
+
+	# Test 1: Repacking twice yields byte-identical output
+	$DAT3 repack "$format.dat" "${format}_a.dat" -c 9
+	$DAT3 repack "$format.dat" "${format}_b.dat" -c 9
+	cmp "${format}_a.dat" "${format}_b.dat"
+
+	# Test 2: Repacking a repacked archive is a fixed point
+	$DAT3 repack "${format}_a.dat" "${format}_c.dat" -c 9
+	cmp "${format}_a.dat" "${format}_c.dat"
+
+	# Test 3: Content is unchanged
+	$DAT3 x "${format}_a.dat" -o "${format}_out"
+	diff -r src "${format}_out"
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
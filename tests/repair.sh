@@ -0,0 +1,72 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that repair recovers the intact entries of a damaged DAT2 archive
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_repair"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+for name in a b c d; do
+	for i in $(seq 1 50); do
+		echo "line $i of $name" >>"src/$name.txt"
+	done
+done
+
+$DAT3 a good.dat -c 9 -C src a.txt b.txt c.txt d.txt
+size=$(stat -c %s good.dat)
+
+# Test 1: An intact archive is copied whole
+$DAT3 repair good.dat copy.dat | tee copy.log
+grep -q "Recovered 4 of 4 entries" copy.log
+$DAT3 x copy.dat -o copy_out
+diff -r src copy_out
+
+# Test 2: Zeroed tree tail and footer. Each record is name size (4), name (5),
+# type (1), sizes and offset (12); keep the file count and two records.
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 good.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+keep=$((tree_start + 4 + 2 * 22 + 10))
+cp good.dat tail.dat
+dd if=/dev/zero of=tail.dat bs=1 seek="$keep" count=$((size - keep)) conv=notrunc 2>/dev/null
+cp tail.dat tail.orig
+$DAT3 repair tail.dat recovered.dat | tee tail.log
+grep -q "Footer damaged, directory tree found at offset $tree_start by scanning" tail.log
+grep -q "Lost with the damaged tree: 2 entries" tail.log
+grep -q "Recovered 2 of 4 entries" tail.log
+cmp tail.dat tail.orig
+$DAT3 x recovered.dat -o tail_out
+diff src/a.txt tail_out/a.txt
+diff src/b.txt tail_out/b.txt
+[ ! -e tail_out/c.txt ]
+
+# Test 3: The damaged input is never the output
+if $DAT3 repair tail.dat tail.dat 2>same.err; then
+	echo "Repairing onto the input should fail"
+	exit 1
+fi
+grep -q "must not overwrite" same.err
+cmp tail.dat tail.orig
+
+# Test 4: Nothing to recover
+head -c 1000 /dev/zero >zeros.dat
+if $DAT3 repair zeros.dat none.dat; then
+	echo "Repairing an archive without a tree should fail"
+	exit 1
+fi
+[ ! -e none.dat ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
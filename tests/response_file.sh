@@ -28,7 +28,7 @@ echo "File 4 content" >test_data/dir2/file4.txt
 echo "File 5 content" >test_data/file5.txt
 
 # Create a test archive
-$DAT3 a test_response.dat test_data
+$DAT3 a test_response.dat -r test_data
 
 # Create response file with specific files to extract
 cat >test_response.txt <<EOF
@@ -70,9 +70,77 @@ verify_file "response_flat/file1.txt"
 verify_file "response_flat/file2.txt"
 verify_file "response_flat/file3.txt"
 
-# Test error case - mixing @response-file with explicit files
-if $DAT3 l test_response.dat @test_response.txt test_data/file5.txt 2>/dev/null; then
-	echo "Error: Command should have failed when mixing @response-file with explicit files"
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$@" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+# Test several response files mixed with explicit files, repeats listed once
+echo "test_data/dir2/file4.txt" >test_response_more.txt
+names test_response.dat @test_response.txt test_data/file5.txt @test_response_more.txt test_data/file1.txt >mixed.log
+printf '%s\n' test_data/dir1/file2.txt test_data/dir1/subdir/file3.txt test_data/dir2/file4.txt \
+	test_data/file1.txt test_data/file5.txt | diff - mixed.log
+
+# Test a response file listing another
+printf '@test_response_more.txt\ntest_data/file5.txt\n' >test_response_nested.txt
+rm -rf response_nested
+$DAT3 x test_response.dat -o response_nested @test_response_nested.txt test_data/file1.txt
+verify_file "response_nested/test_data/dir2/file4.txt"
+verify_file "response_nested/test_data/file5.txt"
+verify_file "response_nested/test_data/file1.txt"
+
+# Test missing response files, directly or nested, and a file including itself
+if $DAT3 l test_response.dat @missing.txt; then
+	echo "Error: a missing response file should fail"
+	exit 1
+fi
+echo "@missing.txt" >test_response_broken.txt
+if $DAT3 l test_response.dat @test_response_broken.txt 2>broken.log; then
+	echo "Error: a missing nested response file should fail"
+	exit 1
+fi
+grep -q "test_response_broken.txt" broken.log
+echo "@test_response_loop.txt" >test_response_loop.txt
+if $DAT3 l test_response.dat @test_response_loop.txt 2>loop.log; then
+	echo "Error: a response file including itself should fail"
+	exit 1
+fi
+grep -q "includes itself" loop.log
+
+# Test @@ for a file name starting with @, on the command line and in response files
+echo "at file" >@notes.txt
+echo "at todo" >@todo.txt
+echo "@@notes.txt" >test_response_at.txt
+$DAT3 a at.dat @test_response_at.txt @@todo.txt
+printf '@notes.txt\n@todo.txt\n' | diff - <(names at.dat)
+$DAT3 d -y at.dat @@notes.txt
+printf '@todo.txt\n' | diff - <(names at.dat)
+
+# Test quoted names, trailing comments and globs in response files
+mkdir -p quoted
+echo "spaced" >"quoted/ two words.txt "
+echo "hashed" >"quoted/a #1.txt"
+cat >test_response_quoted.txt <<EOF
+"quoted/ two words.txt " # trailing space kept
+"quoted/a #1.txt"
+test_data/dir1/*.txt   # glob on disk
+EOF
+$DAT3 a quoted.dat @test_response_quoted.txt
+rm -rf response_quoted
+$DAT3 x quoted.dat -o response_quoted
+cmp "quoted/ two words.txt " "response_quoted/quoted/ two words.txt "
+cmp "quoted/a #1.txt" "response_quoted/quoted/a #1.txt"
+verify_file "response_quoted/test_data/dir1/file2.txt"
+cat >test_response_glob.txt <<EOF
+test_data/dir1/*.txt # glob in the archive
+"quoted/ two words.txt "
+EOF
+rm -rf response_glob
+$DAT3 x quoted.dat -o response_glob @test_response_glob.txt
+verify_file "response_glob/test_data/dir1/file2.txt"
+verify_file "response_glob/quoted/ two words.txt "
+if [ -e "response_glob/quoted/a #1.txt" ]; then
+	echo "Error: a #1.txt should not have been extracted"
 	exit 1
 fi
 
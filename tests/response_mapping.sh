@@ -0,0 +1,67 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test `source => dest` lines in add response files
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_response_mapping"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p build/obj data
+echo "highres script" >build/obj/gl_highres.int
+echo "plain file" >data/readme.txt
+
+# Print entry names, one per line, sorted
+names() {
+	$DAT3 l --no-summary "$1" | tail -n +3 | awk '{print $NF}' | sort
+}
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+
+	# Test 1: Mapped and plain lines in one response file
+	cat >files.txt <<LIST
+# comment
+./build/obj/gl_highres.int => scripts/gl_highres.int
+data/readme.txt
+LIST
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag @files.txt
+	printf 'data/readme.txt\nscripts/gl_highres.int\n' | diff - <(names "$format.dat")
+	$DAT3 x "$format.dat" -o "${format}_out"
+	cmp build/obj/gl_highres.int "${format}_out/scripts/gl_highres.int"
+
+	# Test 2: --case folds the mapped path
+	echo "build/obj/gl_highres.int => Scripts/GL_HIGHRES.INT" >upper.txt
+	# shellcheck disable=SC2086
+	$DAT3 a "${format}_lower.dat" $flag @upper.txt --lowercase
+	printf 'scripts/gl_highres.int\n' | diff - <(names "${format}_lower.dat")
+done
+
+# Test 3: Malformed lines are rejected
+for line in "build/obj/gl_highres.int =>" "=> scripts/x.int" "build/obj/*.int => scripts/x.int" \
+	"build/obj => scripts/obj" "build/obj/gl_highres.int => ../x.int"; do
+	echo "$line" >bad.txt
+	if $DAT3 a bad.dat -r @bad.txt; then
+		echo "'$line' should be rejected"
+		exit 1
+	fi
+	[ ! -e bad.dat ]
+done
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -56,7 +56,7 @@ diff -u rpu.md5 rpu2.md5
 # DAT2 format with automatic recursive directory structure preservation
 rm -f "$RPU2_DAT"
 cd "$RPU_DIR"
-$DAT3 a "../$RPU2_DAT" -- *
+$DAT3 a "../$RPU2_DAT" -r -- *
 cd ..
 
 # Test with original dat2.exe via wine
@@ -89,8 +89,8 @@ dat2 l "$RPU2_DAT" | grep -q "$DUMMY1_WINDOWS"
 dat2 l "$RPU2_DAT" | grep -q "$DUMMY2_WINDOWS"
 
 # Remove dummy files from archive
-$DAT3 d "$RPU2_DAT" "$DUMMY1_LINUX"
-$DAT3 d "$RPU2_DAT" "$DUMMY2_LINUX"
+$DAT3 d -y "$RPU2_DAT" "$DUMMY1_LINUX"
+$DAT3 d -y "$RPU2_DAT" "$DUMMY2_LINUX"
 
 # Verify files are no longer present with both dat3 and wine+dat2.exe
 echo "Checking both tools no longer show deleted files..."
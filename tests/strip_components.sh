@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test dropping leading path components on extraction
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_strip_components"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/data/art/critters src/data/text
+echo "frm" >src/data/art/critters/a.frm
+echo "msg" >src/data/text/b.msg
+echo "top" >src/data/top.txt
+echo "root" >src/root.txt
+$DAT3 c test2.dat src -c 9
+$DAT3 c test1.dat src --dat1
+
+for dat in test2.dat test1.dat; do
+	rm -rf data out
+
+	# Test 1: One component unpacks data\ straight into an existing data/
+	mkdir data
+	$DAT3 x "$dat" -o data --strip-components 1 >x.out 2>x.err
+	cmp data/art/critters/a.frm src/data/art/critters/a.frm
+	cmp data/text/b.msg src/data/text/b.msg
+	cmp data/top.txt src/data/top.txt
+	[ ! -e data/data ]
+
+	# Entries with too few components are skipped with a warning and counted
+	[ ! -e data/root.txt ]
+	grep -q "Skipping root.txt" x.err
+	grep -q "Skipped 1 entries with too few path components" x.out
+
+	# Test 2: Deeper stripping skips every entry at or above that depth
+	$DAT3 x "$dat" -o out --strip-components 2 >x2.out 2>x2.err
+	cmp out/critters/a.frm src/data/art/critters/a.frm
+	cmp out/b.msg src/data/text/b.msg
+	[ ! -e out/top.txt ]
+	grep -q "Skipped 2 entries with too few path components" x2.out
+done
+
+# Test 3: Flat extraction has no --strip-components
+if $DAT3 e test2.dat -o flat --strip-components 1; then
+	echo "e should reject --strip-components"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,59 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test extracting a subdirectory with its leading path stripped
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_strip_prefix"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text/english/dialog src/text/german
+echo "english" >src/text/english/dialog/a.msg
+echo "game" >src/text/english/game.msg
+echo "german" >src/text/german/game.msg
+$DAT3 c test2.dat src -c 9
+$DAT3 c test1.dat src --dat1
+
+for dat in test2.dat test1.dat; do
+	rm -rf out out2 out3
+
+	# Test 1: The matched subtree lands directly in the output directory
+	$DAT3 x "$dat" "text/english/*" -o out --strip-prefix text/english
+	cmp out/dialog/a.msg src/text/english/dialog/a.msg
+	cmp out/game.msg src/text/english/game.msg
+	[ ! -e out/text ]
+
+	# Test 2: Without files, everything under the prefix is extracted
+	$DAT3 x "$dat" -o out2 --strip-prefix TEXT/English/
+	cmp out2/dialog/a.msg src/text/english/dialog/a.msg
+	[ ! -e out2/german ]
+
+	# Test 3: Entries outside the prefix fail the run before anything is written
+	if $DAT3 x "$dat" "*.msg" -o out3 --strip-prefix text/english 2>err.out; then
+		echo "Entries outside the prefix should be rejected"
+		exit 1
+	fi
+	grep -q "1 of 3 entries to extract are not under text/english" err.out
+	grep -q "text/german/game.msg" err.out
+	[ ! -e out3 ]
+done
+
+# Test 4: Flat extraction has no --strip-prefix
+if $DAT3 e test2.dat -o out4 --strip-prefix text/english; then
+	echo "e should reject --strip-prefix"
+	exit 1
+fi
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that --threads and --max-memory give the same results as the defaults
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_threads"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/sub
+for i in $(seq 1 40); do
+	for j in $(seq 1 "$i"); do
+		echo "line $j of file $i" >>"src/sub/file$i.txt"
+	done
+done
+
+# Test 1: Adding with one thread writes the same archive as the default pool
+$DAT3 a default.dat -r -c 9 -C src sub
+$DAT3 a single.dat -r -c 9 -C src sub --threads 1
+cmp default.dat single.dat
+RAYON_NUM_THREADS=2 $DAT3 a env.dat -r -c 9 -C src sub
+cmp default.dat env.dat
+
+# Test 2: Extracting with one thread gives the same files
+$DAT3 x default.dat -o out_default
+$DAT3 x default.dat -o out_single --threads 1
+diff -r src out_default
+diff -r out_default out_single
+
+# Test 3: Zero threads is rejected
+if $DAT3 l default.dat --threads 0 2>zero.err; then
+	echo "--threads 0 should be rejected"
+	exit 1
+fi
+grep -q "invalid value '0' for '--threads <N>'" zero.err
+
+# Test 4: A tiny memory budget streams entries to disk with the same result
+$DAT3 x default.dat -o out_budget --max-memory 128
+diff -r src out_budget
+$DAT3 e default.dat -o out_flat --max-memory 1K
+diff src/sub out_flat
+if $DAT3 x default.dat -o out_bad --max-memory 12Q 2>budget.err; then
+	echo "--max-memory 12Q should be rejected"
+	exit 1
+fi
+grep -q "Invalid size suffix" budget.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
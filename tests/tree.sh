@@ -0,0 +1,45 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that tree shows the directory hierarchy of both formats
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_tree"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/art/critters src/art/intrface src/text
+head -c 100 /dev/zero >src/art/critters/a.frm
+head -c 200 /dev/zero >src/art/critters/b.frm
+head -c 10 /dev/zero >src/art/intrface/c.frm
+head -c 20 /dev/zero >src/text/d.msg
+$DAT3 a test.dat -r -C src art text
+$DAT3 a test1.dat -r --dat1 -C src art text
+
+cat >expected.txt <<'TREE'
+test.dat  (0 files, 4 in all, 330 bytes)
+  art/  (0 files, 3 in all, 310 bytes)
+    critters/  (2 files, 300 bytes)
+    intrface/  (1 file, 10 bytes)
+  text/  (1 file, 20 bytes)
+TREE
+
+# Test 1: The full tree, the same for DAT1
+$DAT3 tree test.dat | diff expected.txt -
+$DAT3 tree test1.dat | sed 's/^test1.dat/test.dat/' | diff expected.txt -
+
+# Test 2: --depth limits the levels shown
+$DAT3 tree test.dat --depth 1 | diff <(grep -v "^    " expected.txt) -
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,60 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that verify compares an archive with a directory
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_verify"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text src/art
+for i in $(seq 1 10); do
+	seq 1 "$((i * 50))" >"src/text/file$i.txt"
+done
+head -c 5000 /dev/zero >src/art/blank.frm
+$DAT3 a test.dat -r -c 9 -C src text art
+
+# Test 1: The packed directory and the extracted files both match
+$DAT3 verify test.dat src | grep -q "11 matched, 0 missing on disk, 0 not in archive, 0 differ"
+$DAT3 x test.dat -o out
+$DAT3 verify test.dat out
+
+# Test 2: Each kind of difference is reported in its own section
+rm out/text/file2.txt
+echo extra >out/text/extra.txt
+echo changed >>out/text/file5.txt
+if $DAT3 verify test.dat out >verify.out; then
+	echo "A changed directory should fail verify"
+	exit 1
+fi
+grep -A1 "^Missing on disk (1):" verify.out | grep -q "  text/file2.txt"
+grep -A1 "^On disk but not in archive (1):" verify.out | grep -q "  text/extra.txt"
+grep -A1 "^Content differs (1):" verify.out | grep -q "  text/file5.txt: "
+
+# Test 3: --ignore-extra only drops the on-disk-only section
+if $DAT3 verify test.dat out --ignore-extra >ignore.out; then
+	echo "Missing and changed files should still fail verify"
+	exit 1
+fi
+if grep -q "not in archive (" ignore.out; then
+	echo "--ignore-extra should not list extra files"
+	exit 1
+fi
+rm -rf out
+$DAT3 x test.dat -o out
+echo extra >out/extra.txt
+$DAT3 verify test.dat out --ignore-extra
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
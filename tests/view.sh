@@ -0,0 +1,68 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test previewing single entries as text or hex
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_view"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src/text/english src/text/russian src/art
+# cp1252 "café" with CRLF line endings
+printf '{100}{}{caf\xe9}\r\n{101}{}{Bye}\r\n' >src/text/english/combat.msg
+# cp866 "Привет"
+printf '{100}{}{\x8f\xe0\xa8\xa2\xa5\xe2}\r\n' >src/text/russian/combat.msg
+printf 'FRM\x00\x00\x00\x04\x01\x02' >src/art/test.frm
+
+for format in dat2 dat1; do
+	flag=""
+	if [ "$format" = "dat1" ]; then
+		flag="--dat1"
+	fi
+	# shellcheck disable=SC2086
+	$DAT3 a "$format.dat" $flag -r -C src text art
+
+	# Test 1: Text is decoded to UTF-8 with LF line endings
+	$DAT3 view "$format.dat" text/english/combat.msg >english.txt
+	printf '{100}{}{caf\xc3\xa9}\n{101}{}{Bye}\n' | cmp - english.txt
+
+	# Test 2: Heuristics pick cp866 for Cyrillic text, --encoding overrides
+	$DAT3 view "$format.dat" 'text\russian\combat.msg' | grep -q "Привет"
+	if $DAT3 view "$format.dat" text/russian/combat.msg --encoding cp1252 | grep -q "Привет"; then
+		echo "--encoding cp1252 should not decode Cyrillic"
+		exit 1
+	fi
+
+	# Test 3: Binary content becomes a hex dump
+	$DAT3 view "$format.dat" art/test.frm | grep -q "^00000000  46 52 4d 00"
+
+	# Test 4: --raw writes the bytes unchanged
+	$DAT3 view "$format.dat" art/test.frm --raw | cmp - src/art/test.frm
+	$DAT3 view "$format.dat" text/english/combat.msg --raw | cmp - src/text/english/combat.msg
+
+	# Test 5: Missing entries are an error
+	if $DAT3 view "$format.dat" text/missing.msg; then
+		echo "Viewing a missing entry should fail"
+		exit 1
+	fi
+done
+
+# Test 6: Entries of nested archives can be viewed
+mkdir -p outer
+cp dat2.dat outer/inner.dat
+$DAT3 a outer.dat -C outer inner.dat
+$DAT3 view outer.dat::inner.dat text/english/combat.msg | grep -q "Bye"
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,48 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that which resolves paths across archives in priority order
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_which"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p patch/art/critters master/art/critters master/text
+head -c 4096 /dev/zero >patch/art/critters/hmjmpsaa.frm
+head -c 1000 /dev/urandom >master/art/critters/hmjmpsaa.frm
+echo hello >master/text/readme.txt
+$DAT3 a patch000.dat -r -c 9 -C patch art
+$DAT3 a master.dat -r -c 0 -C master art text
+
+# Test 1: The first archive that has the path wins
+$DAT3 which patch000.dat master.dat -- ART/Critters/hmjmpsaa.frm text/readme.txt >which.out
+grep -qx "ART/Critters/hmjmpsaa.frm: patch000.dat (4096 bytes, compressed to [0-9]*)" which.out
+grep -qx "text/readme.txt: master.dat (6 bytes, stored)" which.out
+
+# Test 2: --all lists every archive with the path
+$DAT3 which patch000.dat master.dat --all -- art/critters/hmjmpsaa.frm >all.out
+[ "$(wc -l <all.out)" -eq 3 ]
+grep -q "^  patch000.dat (.*, used)$" all.out
+grep -qx "  master.dat (1000 bytes, stored)" all.out
+
+# Test 3: Paths in no archive are reported and fail the command
+if $DAT3 which patch000.dat master.dat -- maps/gone.map text/readme.txt >missing.out 2>missing.err; then
+	echo "A path in no archive should fail"
+	exit 1
+fi
+grep -qx "maps/gone.map: not found" missing.out
+grep -q "1 of 2 paths are in none of the archives" missing.err
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"
@@ -0,0 +1,93 @@
+#!/bin/bash
+
+set -xeu -o pipefail
+
+# Test that corrupt zlib entries are detected and --keep-going extracts the rest
+
+# Work inside tests directory
+cd "$(dirname "$0")"
+
+# Load common variables and functions
+# shellcheck source=tests/common.sh
+source ./common.sh
+
+# Create test directory
+TEST_DIR="test_zlib_validation"
+rm -rf "$TEST_DIR"
+mkdir -p "$TEST_DIR"
+cd "$TEST_DIR"
+
+mkdir -p src
+for i in $(seq 1 50); do
+	echo "line $i of some compressible text" >>src/a.txt
+done
+cp src/a.txt src/b.txt
+
+# Write byte value $3 at offset $2 of file $1
+poke() {
+	# shellcheck disable=SC2059
+	printf "\\x$(printf %02x "$3")" | dd of="$1" bs=1 seek="$2" conv=notrunc 2>/dev/null
+}
+
+$DAT3 a good.dat -c 9 -C src a.txt b.txt
+size=$(stat -c %s good.dat)
+tree_size=$(od -An -tu4 -j $((size - 8)) -N4 good.dat | tr -d ' ')
+tree_start=$((size - 8 - tree_size))
+# First tree entry: name size (4), "a.txt" (5), compression type (1), then real size
+real_size_offset=$((tree_start + 4 + 4 + 5 + 1))
+packed_size=$(od -An -tu4 -j $((real_size_offset + 4)) -N4 good.dat | tr -d ' ')
+
+# Fixture 1: a.txt's last payload byte (part of the Adler-32 trailer) flipped
+cp good.dat flipped.dat
+last=$((packed_size - 1))
+byte=$(od -An -tu1 -j "$last" -N1 flipped.dat | tr -d ' ')
+poke flipped.dat "$last" $((byte ^ 1))
+
+# Fixture 2: a.txt's real size in the tree is one byte too large
+cp good.dat wrong_size.dat
+byte=$(od -An -tu1 -j "$real_size_offset" -N1 wrong_size.dat | tr -d ' ')
+poke wrong_size.dat "$real_size_offset" $(((byte + 1) & 0xff))
+
+$DAT3 x good.dat -o good_out
+diff -r src good_out
+
+for fixture in flipped wrong_size; do
+	# Test 1: Extraction fails and names the entry
+	if $DAT3 x "$fixture.dat" -o "${fixture}_out" 2>"$fixture.err"; then
+		echo "Extracting $fixture.dat should fail"
+		exit 1
+	fi
+	grep -q "a.txt" "$fixture.err"
+
+	# Test 2: --keep-going extracts the intact entry but still fails
+	if $DAT3 x "$fixture.dat" -o "${fixture}_kg" --keep-going 2>"$fixture.err"; then
+		echo "Extracting $fixture.dat with --keep-going should still fail"
+		exit 1
+	fi
+	grep -q "Failed to extract 1 of 2 files" "$fixture.err"
+	cmp src/b.txt "${fixture}_kg/b.txt"
+	[ ! -e "${fixture}_kg/a.txt" ]
+done
+
+grep -q "checksum mismatch" flipped.err
+grep -q "size mismatch" wrong_size.err
+
+# Fixture 3: a DAT1 entry whose LZSS stream decodes to fewer bytes than recorded.
+# DAT1 archives are written uncompressed, so store a raw LZSS stream ("hello",
+# 5 bytes, in a 10-byte stream) and flip its attributes to compressed (0x40).
+mkdir -p lzss
+printf '\x00\x06\xffhello\x00\x00' >lzss/h.lz
+$DAT3 a --dat1 lzss.dat -C lzss h.lz
+# Header (16), root name "." (2), directory header (16), name length and "h.lz" (5),
+# then the big-endian attributes, whose low byte is the last of 4
+poke lzss.dat $((16 + 2 + 16 + 5 + 3)) 64
+if $DAT3 x lzss.dat -o lzss_out 2>lzss.err; then
+	echo "Extracting lzss.dat should fail"
+	exit 1
+fi
+grep -q "Failed to decompress h.lz: Decompressed size mismatch: expected 10 bytes, got 5" lzss.err
+[ ! -e lzss_out/h.lz ]
+
+# Clean up
+cd ..
+rm -rf "$TEST_DIR"